@@ -0,0 +1,317 @@
+use chrono::{DateTime, Local, Utc};
+use clap::{CommandFactory, Parser, Subcommand};
+use clap_complete::Shell;
+use colored::*;
+use fi_slurm::error::SlurmError;
+use fi_slurm::jobs::{Job, MemoryRequest, get_job};
+use fi_slurm::parser::parse_slurm_hostlist;
+use fi_slurm::utils::{SlurmConfig, initialize_slurm};
+use std::collections::HashMap;
+use std::process::ExitCode;
+use thiserror::Error;
+
+/// The main function for the fi-jobs CLI application
+/// Loads the requested job from Slurm and prints its details
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::from(e.exit_code())
+        }
+    }
+}
+
+/// Errors that end a run of fi-jobs
+#[derive(Error, Debug)]
+enum CliError {
+    #[error(transparent)]
+    Slurm(#[from] SlurmError),
+    #[error("{0}")]
+    Other(String),
+}
+
+impl From<String> for CliError {
+    fn from(message: String) -> Self {
+        CliError::Other(message)
+    }
+}
+
+impl CliError {
+    fn exit_code(&self) -> u8 {
+        match self {
+            CliError::Slurm(e) => e.exit_code(),
+            CliError::Other(_) => 1,
+        }
+    }
+}
+
+fn run() -> Result<(), CliError> {
+    let args = Args::parse();
+
+    if let Some(shell) = args.completions {
+        print_completions(shell);
+        return Ok(());
+    }
+    if args.generate_man {
+        print_man_page();
+        return Ok(());
+    }
+
+    match args.command {
+        Some(Command::Show { job, json }) => show(&job, json),
+        None => {
+            // a subcommand is only optional so that the hidden flags above work on their own
+            Args::command().print_help().map_err(|e| e.to_string())?;
+            Ok(())
+        }
+    }
+}
+
+/// Prints the details of a job, or of every task of a job array
+fn show(job: &str, json: bool) -> Result<(), CliError> {
+    let (job_id, task) = parse_job_id(job)?;
+
+    initialize_slurm();
+    let _slurm_config = SlurmConfig::load()?;
+
+    let jobs: Vec<Job> = get_job(job_id)?
+        .into_iter()
+        .filter(|job| {
+            task.is_none_or(|task| {
+                job.array_task() == Some(task) || job.pending_array_tasks().contains(&task)
+            })
+        })
+        .collect();
+    if jobs.is_empty() {
+        return Err(format!("Job {} not found", job).into());
+    }
+
+    if json {
+        let json = serde_json::to_string_pretty(&jobs).map_err(|e| e.to_string())?;
+        println!("{}", json);
+    } else {
+        for (i, job) in jobs.iter().enumerate() {
+            if i > 0 {
+                println!();
+            }
+            print_job(job);
+        }
+    }
+    Ok(())
+}
+
+/// Splits a job id as `squeue` shows it, such as "1234" or "1234_5" for an array task, into
+/// the job id and the task id
+fn parse_job_id(job: &str) -> Result<(u32, Option<u32>), String> {
+    let invalid = || format!("Invalid job id '{}', expected such as 1234 or 1234_5", job);
+    match job.split_once('_') {
+        Some((job_id, task)) => Ok((
+            job_id.parse().map_err(|_| invalid())?,
+            Some(task.parse().map_err(|_| invalid())?),
+        )),
+        None => Ok((job.parse().map_err(|_| invalid())?, None)),
+    }
+}
+
+/// Formats a time limit in minutes the way `scontrol` does, as `[days-]hours:minutes:00`
+fn format_time_limit(minutes: Option<u32>) -> String {
+    match minutes {
+        None => "UNLIMITED".to_string(),
+        Some(minutes) => {
+            let (days, hours, minutes) = (minutes / 1440, minutes / 60 % 24, minutes % 60);
+            if days > 0 {
+                format!("{}-{:02}:{:02}:00", days, hours, minutes)
+            } else {
+                format!("{}:{:02}:00", hours, minutes)
+            }
+        }
+    }
+}
+
+/// Formats a time in the local timezone, or "-" for a time Slurm left unset
+fn format_time(time: DateTime<Utc>) -> String {
+    if time == DateTime::UNIX_EPOCH {
+        "-".to_string()
+    } else {
+        time.with_timezone(&Local)
+            .format("%Y-%m-%d %H:%M:%S")
+            .to_string()
+    }
+}
+
+/// Formats TRES such as "cpu=64,gres/gpu=4,mem=256000,node=1", in name order
+fn format_tres(tres: &HashMap<String, u64>) -> String {
+    if tres.is_empty() {
+        return "-".to_string();
+    }
+    let mut tres: Vec<(&String, &u64)> = tres.iter().collect();
+    tres.sort();
+    tres.into_iter()
+        .map(|(name, count)| format!("{}={}", name, count))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn format_memory(memory: MemoryRequest) -> String {
+    match memory {
+        MemoryRequest::PerNode(mb) => format!("{}M per node", mb),
+        MemoryRequest::PerCpu(mb) => format!("{}M per CPU", mb),
+        MemoryRequest::Unknown => "-".to_string(),
+    }
+}
+
+fn or_dash(text: &str) -> &str {
+    if text.is_empty() { "-" } else { text }
+}
+
+/// Prints one job as labelled lines, followed by its nodes with the CPUs and GPUs it holds on each
+fn print_job(job: &Job) {
+    let field = |label: &str, value: &str| println!("  {:<13} {}", format!("{}:", label), value);
+
+    println!(
+        "{} {}",
+        "Job".bold(),
+        format!("{} ({})", job.display_id(), job.name).bold()
+    );
+    field("User", &format!("{} ({})", job.user_name, job.user_id));
+    field("Account", or_dash(&job.account));
+    field("Partition", or_dash(&job.partition));
+    field("QOS", or_dash(&job.qos));
+    field(
+        "State",
+        &format!("{} ({})", job.job_state, job.state_reason),
+    );
+    if !job.state_description.is_empty() {
+        field("Description", &job.state_description);
+    }
+    field("Priority", &job.priority.to_string());
+    field("Submitted", &format_time(job.submit_time));
+    field("Started", &format_time(job.start_time));
+    if let Some(start) = job.estimated_start() {
+        field("Est. start", &format_time(start));
+    }
+    field(
+        "Ends",
+        &format_time(job.expected_end().unwrap_or(job.end_time)),
+    );
+    field("Time limit", &format_time_limit(job.time_limit()));
+    field("Preemptable", &format_time(job.preemptable_time));
+    field(
+        "Resources",
+        &format!(
+            "{} nodes, {} CPUs, {} tasks, {} GPUs",
+            job.num_nodes,
+            job.num_cpus,
+            job.num_tasks,
+            job.allocated_gpus().max(job.requested_gpus())
+        ),
+    );
+    field("Memory", &format_memory(job.memory_request));
+    field("GRES", job.gres_total.as_deref().unwrap_or("-"));
+    field("Requested", &format_tres(&job.requested_tres));
+    field("Allocated", &format_tres(&job.allocated_gres));
+    field("Features", or_dash(&job.requested_features));
+    field("Licenses", or_dash(&job.licenses));
+    field("Work dir", or_dash(&job.work_dir));
+    field("Command", or_dash(&job.command));
+
+    let hostnames = parse_slurm_hostlist(&job.raw_hostlist);
+    if hostnames.is_empty() {
+        return;
+    }
+    println!("  {}", format!("Nodes ({}):", job.raw_hostlist).bold());
+    let width = hostnames.iter().map(String::len).max().unwrap_or(0);
+    for (i, hostname) in hostnames.iter().enumerate() {
+        // the per-node layouts are in hostlist order, when Slurm reports them at all
+        let cpus = job
+            .node_cpus
+            .get(i)
+            .map_or("-".to_string(), |cpus| cpus.to_string());
+        let gpus = job
+            .node_gpus
+            .get(i)
+            .map_or("-".to_string(), |gpus| gpus.to_string());
+        println!(
+            "    {:<width$}  {:>4} CPUs  {:>2} GPUs",
+            hostname,
+            cpus,
+            gpus,
+            width = width
+        );
+    }
+}
+
+/// Writes a man page generated from the command line definition to stdout
+fn print_man_page() {
+    // rendering can only fail on writing to stdout, such as into a closed pipe
+    let _ = clap_mangen::Man::new(Args::command()).render(&mut std::io::stdout());
+}
+
+/// Writes a completion script for the given shell to stdout
+fn print_completions(shell: Shell) {
+    clap_complete::generate(
+        shell,
+        &mut Args::command(),
+        env!("CARGO_BIN_NAME"),
+        &mut std::io::stdout(),
+    );
+}
+
+const HELP: &str = "Inspects Slurm jobs. `fi-jobs show 1234` prints everything Slurm knows of job 1234 in a readable layout, including the nodes it runs on with the CPUs and GPUs it holds on each, or every task of it if it is a job array.";
+
+#[derive(Parser, Debug)]
+#[command(
+    version,
+    about,
+    after_help = HELP,
+    after_long_help = format!("{}\n\n{}", HELP, fi_slurm::AUTHOR_HELP),
+)]
+struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[arg(long, value_name = "SHELL", hide = true)]
+    #[arg(help = "Prints a completion script for the given shell")]
+    completions: Option<Shell>,
+
+    #[arg(long, hide = true)]
+    #[arg(help = "Prints a man page in roff format, generated from these options")]
+    generate_man: bool,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Prints the details of a job
+    Show {
+        #[arg(value_name = "JOBID")]
+        #[arg(help = "The job to show, such as 1234, or 1234_5 for a single task of a job array")]
+        job: String,
+
+        #[arg(long)]
+        #[arg(
+            help = "Prints the job as JSON instead, as a list holding the job or its array tasks"
+        )]
+        json: bool,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_job_id() {
+        assert_eq!(parse_job_id("1234"), Ok((1234, None)));
+        assert_eq!(parse_job_id("1234_5"), Ok((1234, Some(5))));
+        assert!(parse_job_id("1234_[5-6]").is_err());
+        assert!(parse_job_id("abc").is_err());
+    }
+
+    #[test]
+    fn test_format_time_limit() {
+        assert_eq!(format_time_limit(Some(30)), "0:30:00");
+        assert_eq!(format_time_limit(Some(7 * 1440 + 90)), "7-01:30:00");
+        assert_eq!(format_time_limit(None), "UNLIMITED");
+    }
+}
@@ -0,0 +1,112 @@
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use fi_nodes::report::build_report;
+use fi_nodes::tree_report::build_tree_report;
+use fi_slurm::cluster_state::ClusterState;
+use fi_slurm::jobs::{build_node_to_job_map, enrich_jobs_with_node_ids};
+use fi_slurm::nodes::Node;
+use fi_slurm::synthetic::SyntheticCluster;
+use std::collections::HashSet;
+use std::hint::black_box;
+
+/// The clusters benchmarked, as (nodes, jobs), up to the size of a large site
+const SIZES: [(usize, usize); 3] = [(100, 1_000), (1_000, 10_000), (10_000, 100_000)];
+
+fn clusters() -> Vec<(String, ClusterState)> {
+    SIZES
+        .iter()
+        .map(|&(nodes, jobs)| {
+            let label = format!("{}n-{}j", nodes, jobs);
+            (label, SyntheticCluster::new(nodes, jobs).build())
+        })
+        .collect()
+}
+
+fn bench_reports(c: &mut Criterion) {
+    let clusters = clusters();
+    let hidden = HashSet::new();
+
+    let mut group = c.benchmark_group("reports");
+    for (label, state) in &clusters {
+        let nodes: Vec<&Node> = state.nodes.nodes.iter().collect();
+
+        group.bench_with_input(BenchmarkId::new("tree", label), state, |b, state| {
+            b.iter(|| {
+                build_tree_report(
+                    black_box(&nodes),
+                    &state.jobs,
+                    &state.node_to_job_map,
+                    &[],
+                    &hidden,
+                    false,
+                    None,
+                    false,
+                    false,
+                    None,
+                    None,
+                    None,
+                )
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("tree_gpu", label), state, |b, state| {
+            b.iter(|| {
+                build_tree_report(
+                    black_box(&nodes),
+                    &state.jobs,
+                    &state.node_to_job_map,
+                    &[],
+                    &hidden,
+                    false,
+                    None,
+                    false,
+                    true,
+                    None,
+                    None,
+                    None,
+                )
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("detailed", label), state, |b, state| {
+            b.iter(|| {
+                build_report(
+                    black_box(&nodes),
+                    &state.jobs,
+                    &state.node_to_job_map,
+                    false,
+                    false,
+                    false,
+                )
+            })
+        });
+    }
+    group.finish();
+}
+
+/// Cross-referencing the jobs with the nodes, which every report waits on after loading
+fn bench_node_to_job_map(c: &mut Criterion) {
+    let mut group = c.benchmark_group("node_to_job_map");
+    for &(nodes, jobs) in &SIZES {
+        let cluster = SyntheticCluster::new(nodes, jobs);
+        let mut slurm_nodes = cluster.build_nodes();
+        let slurm_jobs = cluster.build_jobs(&mut slurm_nodes);
+        let label = format!("{}n-{}j", nodes, jobs);
+
+        group.bench_with_input(
+            BenchmarkId::from_parameter(label),
+            &slurm_jobs,
+            |b, jobs| {
+                b.iter_batched(
+                    || jobs.clone(),
+                    |mut jobs| {
+                        enrich_jobs_with_node_ids(&mut jobs, &slurm_nodes.name_to_id);
+                        build_node_to_job_map(&jobs)
+                    },
+                    criterion::BatchSize::LargeInput,
+                )
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_reports, bench_node_to_job_map);
+criterion_main!(benches);
@@ -0,0 +1,31 @@
+use fi_slurm::partitions::{PartitionFlags, PartitionState, SlurmPartitions};
+use fi_slurm_db::acct::AssocLimits;
+use std::collections::HashSet;
+
+/// The partitions a user could run jobs in, given their associations
+///
+/// A partition is accessible if it is up, doesn't require a reservation or root, and lets in
+/// the account of one of the user's associations, where associations tied to a partition only
+/// count for that partition. The QOS a partition allows are not checked
+pub fn accessible_partitions<'a>(
+    partitions: &'a SlurmPartitions,
+    assocs: &[AssocLimits],
+) -> HashSet<&'a str> {
+    partitions
+        .partitions
+        .iter()
+        .filter(|partition| partition.state == PartitionState::Up)
+        .filter(|partition| {
+            !partition
+                .flags
+                .intersects(PartitionFlags::REQ_RESV | PartitionFlags::ROOT_ONLY)
+        })
+        .filter(|partition| {
+            assocs.iter().any(|assoc| {
+                (assoc.partition.is_empty() || assoc.partition == partition.name)
+                    && partition.allows_account(&assoc.account)
+            })
+        })
+        .map(|partition| partition.name.as_str())
+        .collect()
+}
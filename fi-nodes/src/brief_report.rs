@@ -0,0 +1,180 @@
+use colored::*;
+use fi_slurm::availability::{NodeAvailability, allocated_cpus};
+use fi_slurm::jobs::SlurmJobs;
+use fi_slurm::nodes::Node;
+use fi_slurm::theme::Theme;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+/// The architectures listed, the largest first
+const TOP_ARCHITECTURES: usize = 8;
+
+/// Features that say what a node is for rather than what it is built on, so never its
+/// architecture
+const KIND_FEATURES: &[&str] = &["cpu", "gpu"];
+
+/// How much of a resource there is, and how much of it is idle
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Availability {
+    pub total: u32,
+    pub idle: u32,
+}
+
+impl Availability {
+    fn add(&mut self, total: u32, idle: u32) {
+        self.total += total;
+        self.idle += idle;
+    }
+
+    /// The idle share, as a whole percentage rounded down
+    pub fn idle_percent(&self) -> u32 {
+        if self.total == 0 {
+            0
+        } else {
+            (self.idle as u64 * 100 / self.total as u64) as u32
+        }
+    }
+}
+
+/// The nodes, cores and GPUs of the cluster or of one architecture
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct BriefLine {
+    /// Idle nodes are those that can take whole-node work
+    pub nodes: Availability,
+    pub cpus: Availability,
+    pub gpus: Availability,
+}
+
+impl BriefLine {
+    fn add(&mut self, node: &Node, availability: &NodeAvailability) {
+        let gpus = node
+            .gpu_info
+            .as_ref()
+            .map_or(0, |gpu| gpu.total_gpus as u32);
+        self.nodes.add(1, availability.available as u32);
+        self.cpus.add(node.cpus as u32, availability.idle_cpus);
+        self.gpus.add(gpus, availability.idle_gpus);
+    }
+}
+
+/// The whole cluster in a few lines: its totals, and one line for each architecture
+#[derive(Default, Debug, Clone, Serialize)]
+pub struct BriefReport {
+    pub cluster: BriefLine,
+    /// By architecture, the most nodes first
+    pub architectures: Vec<(String, BriefLine)>,
+}
+
+/// The architecture of a node, taken to be its first feature that is neither hidden nor one
+/// of `KIND_FEATURES`, such as "genoa" for a node with the features "cpu,genoa,ib"
+fn architecture<'a>(node: &'a Node, hidden_features: &HashSet<String>) -> Option<&'a str> {
+    node.features
+        .iter()
+        .map(|feature| &**feature)
+        .find(|feature| !KIND_FEATURES.contains(feature) && !hidden_features.contains(*feature))
+}
+
+/// Builds the brief report of the given nodes
+pub fn build_brief_report(
+    nodes: &[&Node],
+    jobs: &SlurmJobs,
+    node_to_job_map: &HashMap<usize, Vec<u32>>,
+    hidden_features: &HashSet<String>,
+) -> BriefReport {
+    let mut cluster = BriefLine::default();
+    let mut by_architecture: HashMap<&str, BriefLine> = HashMap::new();
+
+    for &node in nodes {
+        let alloc_cpus = allocated_cpus(node, jobs, node_to_job_map);
+        let availability = NodeAvailability::of_node(node, alloc_cpus);
+
+        cluster.add(node, &availability);
+        let name = architecture(node, hidden_features).unwrap_or("other");
+        by_architecture
+            .entry(name)
+            .or_default()
+            .add(node, &availability);
+    }
+
+    let mut architectures: Vec<(String, BriefLine)> = by_architecture
+        .into_iter()
+        .map(|(name, line)| (name.to_string(), line))
+        .collect();
+    architectures.sort_by(|(a_name, a), (b_name, b)| {
+        b.nodes
+            .total
+            .cmp(&a.nodes.total)
+            .then_with(|| a_name.cmp(b_name))
+    });
+
+    BriefReport {
+        cluster,
+        architectures,
+    }
+}
+
+/// Formats a resource as "412/1024 nodes idle (40%)", with the share colored by how much is free
+fn format_availability(label: &str, availability: &Availability, theme: &Theme) -> String {
+    let percent = availability.idle_percent();
+    let color = if percent <= 10 {
+        theme.palette.high
+    } else if percent <= 50 {
+        theme.palette.medium
+    } else {
+        theme.palette.low
+    };
+    format!(
+        "{}/{} {} idle ({})",
+        availability.idle,
+        availability.total,
+        label,
+        theme.paint(&format!("{}%", percent), color)
+    )
+}
+
+fn format_line(line: &BriefLine, theme: &Theme) -> String {
+    let mut parts = vec![
+        format_availability("nodes", &line.nodes, theme),
+        format_availability("cores", &line.cpus, theme),
+    ];
+    if line.gpus.total > 0 {
+        parts.push(format_availability("GPUs", &line.gpus, theme));
+    }
+    parts.join(", ")
+}
+
+/// Prints the brief report as a single block, short enough for a login banner
+pub fn print_brief_report(report: &BriefReport, theme: &Theme) {
+    println!(
+        "{} {}",
+        "Cluster:".bold(),
+        format_line(&report.cluster, theme)
+    );
+
+    let shown = &report.architectures[..report.architectures.len().min(TOP_ARCHITECTURES)];
+    let width = shown.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+    for (name, line) in shown {
+        println!(
+            "  {:<width$}  {}",
+            name,
+            format_line(line, theme),
+            width = width
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_idle_percent() {
+        let availability = Availability {
+            total: 300,
+            idle: 299,
+        };
+        // rounded down, so 100% only when everything is idle
+        assert_eq!(availability.idle_percent(), 99);
+        assert_eq!(Availability::default().idle_percent(), 0);
+    }
+}
@@ -0,0 +1,90 @@
+use colored::*;
+use fi_slurm::burst_buffer::{BurstBuffer, BurstBufferPool};
+use fi_slurm::theme::Theme;
+
+/// A pool with less than this share of its space free is flagged
+const LOW_FREE_SHARE: f64 = 0.1;
+
+/// Formats bytes with the largest binary unit that keeps the number above 1
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 6] = ["B", "K", "M", "G", "T", "P"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{}B", bytes)
+    } else {
+        format!("{:.1}{}", value, UNITS[unit])
+    }
+}
+
+fn print_pool(pool: &BurstBufferPool, width: usize, theme: &Theme) {
+    let used_share = if pool.total_space == 0 {
+        0.0
+    } else {
+        pool.used_space as f64 / pool.total_space as f64 * 100.0
+    };
+    let row = format!(
+        "  {:<width$}  {:>9}  {:>9}  {:>5.1}%  {:>9}",
+        pool.name,
+        format_bytes(pool.total_space),
+        format_bytes(pool.used_space),
+        used_share,
+        format_bytes(pool.free_space()),
+    );
+    // flag the pools close to running out of space
+    if pool.total_space > 0 && (pool.free_space() as f64) < pool.total_space as f64 * LOW_FREE_SHARE
+    {
+        println!("{}", theme.paint(&row, theme.palette.warning));
+    } else {
+        println!("{}", row);
+    }
+}
+
+/// Prints the space of each pool of each burst buffer plugin, flagging the nearly full ones
+pub fn print_burst_buffer_report(burst_buffers: &[BurstBuffer], theme: &Theme) {
+    if burst_buffers.is_empty() {
+        println!("No burst buffers are configured");
+        return;
+    }
+
+    for (i, bb) in burst_buffers.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        println!(
+            "{} {} ({} buffers allocated)",
+            "Burst buffer".bold(),
+            bb.name,
+            bb.buffer_count
+        );
+
+        // the plugin's own totals stand for its default pool, unless it is also listed
+        let listed_default = bb.pools.iter().any(|pool| pool.name == bb.default_pool);
+        let pools: Vec<&BurstBufferPool> = (!listed_default)
+            .then_some(&bb.default)
+            .into_iter()
+            .chain(&bb.pools)
+            .collect();
+        let width = pools
+            .iter()
+            .map(|pool| pool.name.len())
+            .max()
+            .unwrap_or(0)
+            .max("POOL".len());
+        println!(
+            "{}",
+            format!(
+                "  {:<width$}  {:>9}  {:>9}  {:>6}  {:>9}",
+                "POOL", "TOTAL", "USED", "%USED", "FREE"
+            )
+            .bold()
+        );
+        for pool in pools {
+            print_pool(pool, width, theme);
+        }
+    }
+}
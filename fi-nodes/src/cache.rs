@@ -0,0 +1,151 @@
+use chrono::{DateTime, Local, Utc};
+use colored::*;
+use fi_slurm::cluster_state::ClusterState;
+use fi_slurm::error::SlurmError;
+use fi_slurm::theme::Theme;
+use std::path::PathBuf;
+
+/// The file the last state of a cluster loaded with --cached is kept in, one per cluster so
+/// that a state is never shown for another cluster, under `$XDG_CACHE_HOME`, or `~/.cache` if
+/// that isn't set
+fn cache_file(cluster: &str) -> Option<PathBuf> {
+    if cluster.is_empty() || cluster.contains(['/', '\\']) || cluster.starts_with('.') {
+        return None;
+    }
+    let cache_home = std::env::var_os("XDG_CACHE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+    Some(
+        cache_home
+            .join("fi-nodes")
+            .join(format!("cluster-state-{}.json", cluster)),
+    )
+}
+
+/// The ClusterName set in a slurm.conf, whose keys are case-insensitive
+fn cluster_name_in(conf: &str) -> Option<String> {
+    conf.lines()
+        .map(|line| line.split('#').next().unwrap_or("").trim())
+        .filter_map(|line| line.split_once('='))
+        .find(|(key, _)| key.trim().eq_ignore_ascii_case("ClusterName"))
+        .map(|(_, value)| value.trim().to_string())
+        .filter(|name| !name.is_empty())
+}
+
+/// The ClusterName of the local slurm.conf, at `$SLURM_CONF` or else in /etc/slurm, read from
+/// the file rather than from the controller so that it is known while the controller can't be
+/// reached
+pub fn local_cluster_name() -> Option<String> {
+    let path = std::env::var_os("SLURM_CONF")
+        .filter(|path| !path.is_empty())
+        .map_or_else(|| PathBuf::from("/etc/slurm/slurm.conf"), PathBuf::from);
+    cluster_name_in(&std::fs::read_to_string(path).ok()?)
+}
+
+/// Keeps the state for when Slurm can't be reached
+///
+/// The cache is only a fallback, so failing to write it is not an error. The state is written
+/// next to the cache and moved over it, so that a run interrupted while writing never leaves a
+/// truncated cache behind
+pub fn save_cached_state(state: &ClusterState, cluster: &str) {
+    let Some(path) = cache_file(cluster) else {
+        return;
+    };
+    if let Some(dir) = path.parent()
+        && std::fs::create_dir_all(dir).is_err()
+    {
+        return;
+    }
+    let partial = path.with_extension("json.partial");
+    if state.save(&partial.to_string_lossy()).is_ok() {
+        let _ = std::fs::rename(&partial, &path);
+    }
+}
+
+/// The state of the cluster last saved with `save_cached_state`, if there is one
+pub fn load_cached_state(cluster: &str) -> Option<ClusterState> {
+    let path = cache_file(cluster)?;
+    ClusterState::from_snapshot(&path.to_string_lossy()).ok()
+}
+
+/// Keeps a successfully loaded state in the cache, or stands in the cached state for one that
+/// failed to load because the controller couldn't be reached, along with when it was taken
+///
+/// Without the name of the cluster, nothing is cached, as the state couldn't be told apart from
+/// that of another cluster
+pub fn with_fallback(
+    loaded: Result<ClusterState, SlurmError>,
+    cluster: Option<&str>,
+) -> Result<(ClusterState, Option<DateTime<Utc>>), SlurmError> {
+    let Some(cluster) = cluster else {
+        return loaded.map(|state| (state, None));
+    };
+    match loaded {
+        Ok(state) => {
+            save_cached_state(&state, cluster);
+            Ok((state, None))
+        }
+        Err(e) if e.is_unreachable() => match load_cached_state(cluster) {
+            Some(state) => {
+                let taken_at = state.taken_at;
+                Ok((state, Some(taken_at)))
+            }
+            None => Err(e),
+        },
+        Err(e) => Err(e),
+    }
+}
+
+/// How long ago the time was, as in "3m", "2h05m" or "4d"
+fn format_age(taken_at: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    let minutes = (now - taken_at).num_minutes().max(0);
+    if minutes < 60 {
+        format!("{}m", minutes)
+    } else if minutes < 24 * 60 {
+        format!("{}h{:02}m", minutes / 60, minutes % 60)
+    } else {
+        format!("{}d", minutes / (24 * 60))
+    }
+}
+
+/// Prints the banner over a report drawn from the cached state
+pub fn print_stale_banner(taken_at: DateTime<Utc>, theme: &Theme) {
+    let banner = format!(
+        "STALE ({} old): Slurm can't be reached, so this is the cluster state from {}",
+        format_age(taken_at, Utc::now()),
+        taken_at.with_timezone(&Local).format("%m-%d %H:%M")
+    );
+    println!("{}", theme.paint(&banner, theme.palette.down).bold());
+    println!();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn test_format_age() {
+        let now = Utc::now();
+        assert_eq!(format_age(now - Duration::minutes(3), now), "3m");
+        assert_eq!(format_age(now - Duration::minutes(125), now), "2h05m");
+        assert_eq!(format_age(now - Duration::days(4), now), "4d");
+        // a clock that moved backwards doesn't make the state younger than new
+        assert_eq!(format_age(now + Duration::minutes(1), now), "0m");
+    }
+
+    #[test]
+    fn test_cluster_name_in() {
+        let conf = "# ClusterName=commented\nSlurmctldHost=ctl1\nclustername = rusty # main\n";
+        assert_eq!(cluster_name_in(conf), Some("rusty".to_string()));
+        assert_eq!(cluster_name_in("ClusterName=\n"), None);
+    }
+
+    #[test]
+    fn test_cache_file_per_cluster() {
+        assert_ne!(cache_file("rusty"), cache_file("popeye"));
+        assert_eq!(cache_file("../rusty"), None);
+        assert_eq!(cache_file(""), None);
+    }
+}
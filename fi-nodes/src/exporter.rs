@@ -0,0 +1,326 @@
+use fi_slurm::availability::allocated_cpus;
+use fi_slurm::cluster_state::{ClusterState, FeatureAvailability};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::Duration;
+
+/// The address the exporter listens on when `--exporter` is given without one
+pub const DEFAULT_EXPORTER_ADDR: &str = "0.0.0.0:9750";
+
+/// How long a client may take to send its request, and to take each part of the response
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The most of a request read, headers included, which is far more than any scraper sends
+const MAX_REQUEST_BYTES: u64 = 16 * 1024;
+
+/// Resources of the nodes in one state
+#[derive(Default, Debug, Clone, Copy)]
+struct StateTotals {
+    nodes: u32,
+    cpus: u32,
+    alloc_cpus: u32,
+    gpus: u64,
+    alloc_gpus: u64,
+}
+
+/// Escapes a Prometheus label value
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Writes one metric family, with its HELP and TYPE lines, for a set of labelled samples
+fn write_family<'a, T: std::fmt::Display + 'a>(
+    out: &mut String,
+    name: &str,
+    help: &str,
+    label: &str,
+    samples: impl Iterator<Item = (&'a str, T)>,
+) {
+    // writing to a String cannot fail
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} gauge", name);
+    for (value, sample) in samples {
+        let _ = writeln!(
+            out,
+            "{}{{{}=\"{}\"}} {}",
+            name,
+            label,
+            escape_label(value),
+            sample
+        );
+    }
+}
+
+/// Renders the per-feature and per-state metrics of a cluster state in the Prometheus text
+/// format
+///
+/// Idle counts follow the tree report: only available nodes, and the unallocated resources of
+/// mixed nodes, count as idle
+pub fn render_metrics(state: &ClusterState) -> String {
+    let features: BTreeMap<&str, _> = state.feature_availability().into_iter().collect();
+
+    let mut states: BTreeMap<String, StateTotals> = BTreeMap::new();
+    for node in &state.nodes.nodes {
+        let (gpus, alloc_gpus) = node
+            .gpu_info
+            .as_ref()
+            .map_or((0, 0), |gpu| (gpu.total_gpus, gpu.allocated_gpus));
+        let entry = states.entry(node.state.to_string()).or_default();
+        entry.nodes += 1;
+        entry.cpus += node.cpus as u32;
+        entry.alloc_cpus += allocated_cpus(node, &state.jobs, &state.node_to_job_map);
+        entry.gpus += gpus;
+        entry.alloc_gpus += alloc_gpus;
+    }
+
+    let mut out = String::new();
+    let by_feature =
+        |f: fn(&FeatureAvailability) -> u64| features.iter().map(move |(name, a)| (*name, f(a)));
+    write_family(
+        &mut out,
+        "fi_nodes_feature_nodes",
+        "Number of nodes with the feature",
+        "feature",
+        by_feature(|a| a.total_nodes as u64),
+    );
+    write_family(
+        &mut out,
+        "fi_nodes_feature_idle_nodes",
+        "Number of nodes with the feature available for new work",
+        "feature",
+        by_feature(|a| a.idle_nodes as u64),
+    );
+    write_family(
+        &mut out,
+        "fi_nodes_feature_cpus",
+        "Number of cores on nodes with the feature",
+        "feature",
+        by_feature(|a| a.total_cpus as u64),
+    );
+    write_family(
+        &mut out,
+        "fi_nodes_feature_idle_cpus",
+        "Number of idle cores on available or mixed nodes with the feature",
+        "feature",
+        by_feature(|a| a.idle_cpus as u64),
+    );
+    write_family(
+        &mut out,
+        "fi_nodes_feature_gpus",
+        "Number of GPUs on nodes with the feature",
+        "feature",
+        by_feature(|a| a.total_gpus),
+    );
+    write_family(
+        &mut out,
+        "fi_nodes_feature_idle_gpus",
+        "Number of idle GPUs on available or mixed nodes with the feature",
+        "feature",
+        by_feature(|a| a.idle_gpus),
+    );
+
+    let by_state =
+        |f: fn(&StateTotals) -> u64| states.iter().map(move |(name, t)| (name.as_str(), f(t)));
+    write_family(
+        &mut out,
+        "fi_nodes_state_nodes",
+        "Number of nodes in the state",
+        "state",
+        by_state(|t| t.nodes as u64),
+    );
+    write_family(
+        &mut out,
+        "fi_nodes_state_cpus",
+        "Number of cores on nodes in the state",
+        "state",
+        by_state(|t| t.cpus as u64),
+    );
+    write_family(
+        &mut out,
+        "fi_nodes_state_alloc_cpus",
+        "Number of allocated cores on nodes in the state",
+        "state",
+        by_state(|t| t.alloc_cpus as u64),
+    );
+    write_family(
+        &mut out,
+        "fi_nodes_state_gpus",
+        "Number of GPUs on nodes in the state",
+        "state",
+        by_state(|t| t.gpus),
+    );
+    write_family(
+        &mut out,
+        "fi_nodes_state_alloc_gpus",
+        "Number of allocated GPUs on nodes in the state",
+        "state",
+        by_state(|t| t.alloc_gpus),
+    );
+
+    out
+}
+
+/// Reads a request up to the blank line ending its headers, returning its method and path
+///
+/// The headers are read even though they are ignored, as closing a connection with unread data
+/// makes the kernel reset it, which can cut the response short at the client
+fn read_request(reader: &mut impl BufRead) -> io::Result<(String, String)> {
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut header = String::new();
+    loop {
+        header.clear();
+        if reader.read_line(&mut header)? == 0 || header.trim_end().is_empty() {
+            break;
+        }
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or("").to_string();
+    let path = parts.next().unwrap_or("").to_string();
+    Ok((method, path))
+}
+
+/// Answers a single HTTP request, loading fresh data from Slurm for each scrape of /metrics
+///
+/// A client that sends nothing, or stops reading, is dropped after `CLIENT_TIMEOUT`
+fn handle_connection(mut stream: TcpStream) -> io::Result<()> {
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+    stream.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+    let (method, path) = read_request(&mut BufReader::new((&stream).take(MAX_REQUEST_BYTES)))?;
+
+    let (status, content_type, body) = match (method.as_str(), path.as_str()) {
+        ("GET", "/metrics") => match ClusterState::load() {
+            Ok(state) => (
+                "200 OK",
+                "text/plain; version=0.0.4",
+                render_metrics(&state),
+            ),
+            Err(e) => (
+                "500 Internal Server Error",
+                "text/plain",
+                format!("Failed to load Slurm data: {}\n", e),
+            ),
+        },
+        ("GET", "/") => (
+            "200 OK",
+            "text/html",
+            "<html><body><a href=\"/metrics\">Metrics</a></body></html>\n".to_string(),
+        ),
+        _ => ("404 Not Found", "text/plain", "Not found\n".to_string()),
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+/// Serves the cluster metrics in the Prometheus text format at `http://ADDR/metrics`
+///
+/// Each connection is answered on a thread of its own, so that a slow or idle client doesn't
+/// hold up the scrapes behind it, and every scrape queries Slurm, so the numbers always match
+/// what the CLI reports at that moment. Only returns if the address cannot be bound
+pub fn run_exporter(addr: &str) -> Result<(), String> {
+    let listener =
+        TcpListener::bind(addr).map_err(|e| format!("Failed to listen on {}: {}", addr, e))?;
+    println!("Serving metrics at http://{}/metrics", addr);
+
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                std::thread::spawn(move || {
+                    if let Err(e) = handle_connection(stream) {
+                        eprintln!("Failed to answer metrics request: {}", e);
+                    }
+                });
+            }
+            Err(e) => eprintln!("Failed to accept connection: {}", e),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fi_slurm::nodes::{Node, NodeState};
+    use fi_slurm::states::NodeStateFlags;
+    use fi_slurm::synthetic::SyntheticCluster;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_render_metrics() {
+        let drained = NodeState::Compound {
+            base: Box::new(NodeState::Idle),
+            flags: NodeStateFlags::DRAIN,
+        };
+        let mut state = SyntheticCluster::new(1, 0).build();
+        state.nodes.nodes = vec![Node::new(
+            0,
+            "n01".to_string(),
+            drained,
+            64,
+            vec![Arc::from("genoa")],
+            None,
+        )];
+
+        let metrics = render_metrics(&state);
+        assert!(metrics.contains("# TYPE fi_nodes_feature_nodes gauge\n"));
+        assert!(metrics.contains("fi_nodes_feature_nodes{feature=\"genoa\"} 1\n"));
+        assert!(metrics.contains("fi_nodes_feature_idle_nodes{feature=\"genoa\"} 0\n"));
+        assert!(metrics.contains("fi_nodes_state_cpus{state=\"IDLE+DRAIN\"} 64\n"));
+    }
+
+    #[test]
+    fn test_read_request() {
+        let request = "GET /metrics HTTP/1.1\r\nHost: exporter\r\nAccept: */*\r\n\r\nextra";
+        let mut reader = io::Cursor::new(request);
+        let (method, path) = read_request(&mut reader).unwrap();
+        assert_eq!((method.as_str(), path.as_str()), ("GET", "/metrics"));
+        // the headers are read, and nothing past them
+        let mut rest = String::new();
+        reader.read_to_string(&mut rest).unwrap();
+        assert_eq!(rest, "extra");
+    }
+
+    #[test]
+    fn test_idle_client_does_not_block() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = std::thread::spawn(move || {
+            for stream in listener.incoming().take(2) {
+                let stream = stream.unwrap();
+                std::thread::spawn(move || handle_connection(stream));
+            }
+        });
+
+        // a client that connects and sends nothing, then one that asks for the index page
+        let _idle = TcpStream::connect(addr).unwrap();
+        let mut client = TcpStream::connect(addr).unwrap();
+        client.set_read_timeout(Some(CLIENT_TIMEOUT / 2)).unwrap();
+        client
+            .write_all(b"GET / HTTP/1.1\r\nHost: exporter\r\n\r\n")
+            .unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.ends_with("</html>\n"));
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_escape_label() {
+        assert_eq!(escape_label("a\"b\\c"), "a\\\"b\\\\c");
+    }
+}
@@ -0,0 +1,260 @@
+use crate::SortOrder;
+use crate::tree_report::{ReportLine, TreeNode, TreeReportData, sorted_children, top_level};
+
+/// The ReportLine fields, and derived values, available to a `--format` template
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Field {
+    Feature,
+    Path,
+    Depth,
+    TotalNodes,
+    IdleNodes,
+    AllocNodes,
+    PreemptNodes,
+    TotalCpus,
+    IdleCpus,
+    AllocCpus,
+    PreemptCpus,
+    PreemptGpus,
+    TotalMemoryMb,
+    AllocMemoryMb,
+    SoonCpus,
+    ReservedNodes,
+    Load,
+    WastedNodes,
+    OversubscribedNodes,
+    Nodes,
+}
+
+impl Field {
+    const NAMES: [(&'static str, Field); 20] = [
+        ("feature", Field::Feature),
+        ("path", Field::Path),
+        ("depth", Field::Depth),
+        ("total_nodes", Field::TotalNodes),
+        ("idle_nodes", Field::IdleNodes),
+        ("alloc_nodes", Field::AllocNodes),
+        ("preempt_nodes", Field::PreemptNodes),
+        ("total_cpus", Field::TotalCpus),
+        ("idle_cpus", Field::IdleCpus),
+        ("alloc_cpus", Field::AllocCpus),
+        ("preempt_cpus", Field::PreemptCpus),
+        ("preempt_gpus", Field::PreemptGpus),
+        ("total_memory_mb", Field::TotalMemoryMb),
+        ("alloc_memory_mb", Field::AllocMemoryMb),
+        ("soon_cpus", Field::SoonCpus),
+        ("reserved_nodes", Field::ReservedNodes),
+        ("load", Field::Load),
+        ("wasted_nodes", Field::WastedNodes),
+        ("oversubscribed_nodes", Field::OversubscribedNodes),
+        ("nodes", Field::Nodes),
+    ];
+
+    fn from_name(name: &str) -> Option<Field> {
+        Field::NAMES
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, field)| *field)
+    }
+}
+
+/// A piece of a parsed template, either literal text or a field placeholder
+#[derive(Debug, Clone, PartialEq)]
+pub enum Segment {
+    Literal(String),
+    Field(Field),
+}
+
+/// A parsed `--format` template, such as `{feature}\t{idle_nodes}/{total_nodes}`
+///
+/// Fields are written in braces, `{{` and `}}` produce literal braces, and the escapes `\t`,
+/// `\n` and `\\` are interpreted so that templates can be passed as plain shell strings
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    segments: Vec<Segment>,
+}
+
+impl Template {
+    /// Parses a template string, failing on unknown fields or unbalanced braces
+    pub fn parse(template: &str) -> Result<Self, String> {
+        let mut segments = Vec::new();
+        let mut literal = String::new();
+        let mut chars = template.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => match chars.next() {
+                    Some('t') => literal.push('\t'),
+                    Some('n') => literal.push('\n'),
+                    Some('\\') => literal.push('\\'),
+                    Some(other) => {
+                        literal.push('\\');
+                        literal.push(other);
+                    }
+                    None => literal.push('\\'),
+                },
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(ch) => name.push(ch),
+                            None => return Err(format!("Unclosed '{{' in format '{template}'")),
+                        }
+                    }
+                    let field = Field::from_name(name.trim()).ok_or_else(|| {
+                        let known: Vec<&str> = Field::NAMES.iter().map(|(n, _)| *n).collect();
+                        format!(
+                            "Unknown format field '{{{name}}}'. Available fields: {}",
+                            known.join(", ")
+                        )
+                    })?;
+                    if !literal.is_empty() {
+                        segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                    }
+                    segments.push(Segment::Field(field));
+                }
+                '}' => return Err(format!("Unmatched '}}' in format '{template}'")),
+                _ => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            segments.push(Segment::Literal(literal));
+        }
+
+        Ok(Template { segments })
+    }
+
+    /// Renders the template for a single line of the tree report
+    pub fn render(&self, name: &str, path: &[&str], stats: &ReportLine) -> String {
+        let mut out = String::new();
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(text) => out.push_str(text),
+                Segment::Field(field) => out.push_str(&field_value(*field, name, path, stats)),
+            }
+        }
+        out
+    }
+}
+
+fn field_value(field: Field, name: &str, path: &[&str], stats: &ReportLine) -> String {
+    match field {
+        Field::Feature => name.to_string(),
+        Field::Path => path.join("/"),
+        Field::Depth => path.len().to_string(),
+        Field::TotalNodes => stats.total_nodes.to_string(),
+        Field::IdleNodes => stats.idle_nodes.to_string(),
+        Field::AllocNodes => stats
+            .total_nodes
+            .saturating_sub(stats.idle_nodes)
+            .to_string(),
+        Field::PreemptNodes => stats.preempt_nodes.unwrap_or(0).to_string(),
+        Field::TotalCpus => stats.total_cpus.to_string(),
+        Field::IdleCpus => stats.idle_cpus.to_string(),
+        Field::AllocCpus => stats.alloc_cpus.to_string(),
+        Field::PreemptCpus => stats.preempt_cpus.unwrap_or(0).to_string(),
+        Field::PreemptGpus => stats.preempt_gpus.unwrap_or(0).to_string(),
+        Field::TotalMemoryMb => stats.total_memory_mb.to_string(),
+        Field::AllocMemoryMb => stats.alloc_memory_mb.to_string(),
+        Field::SoonCpus => stats.soon_cpus.unwrap_or(0).to_string(),
+        Field::ReservedNodes => stats.reserved_nodes.unwrap_or(0).to_string(),
+        Field::Load => format!("{:.2}", stats.load.load),
+        Field::WastedNodes => stats.load.wasted_nodes.to_string(),
+        Field::OversubscribedNodes => stats.load.oversubscribed_nodes.to_string(),
+        Field::Nodes => fi_slurm::parser::compress_hostlist(&stats.node_names),
+    }
+}
+
+/// Prints one templated line per node of the tree report, in the same order as the tree view
+pub fn print_formatted_tree(root: &TreeReportData, template: &Template, sort: SortOrder) {
+    let top_level_node = top_level(root);
+    println!(
+        "{}",
+        template.render(&top_level_node.name, &[], &top_level_node.stats)
+    );
+    for child in sorted_children(&top_level_node.children, sort) {
+        print_formatted_recursive(child, &mut Vec::new(), template, sort);
+    }
+}
+
+fn print_formatted_recursive<'a>(
+    tree_node: &'a TreeNode,
+    path: &mut Vec<&'a str>,
+    template: &Template,
+    sort: SortOrder,
+) {
+    path.push(&tree_node.name);
+    println!(
+        "{}",
+        template.render(&tree_node.name, path, &tree_node.stats)
+    );
+    for child in sorted_children(&tree_node.children, sort) {
+        print_formatted_recursive(child, path, template, sort);
+    }
+    path.pop();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats() -> ReportLine {
+        ReportLine {
+            total_nodes: 10,
+            idle_nodes: 4,
+            preempt_nodes: None,
+            total_cpus: 640,
+            idle_cpus: 256,
+            preempt_cpus: None,
+            preempt_gpus: None,
+            next_preemptable: None,
+            soon_cpus: None,
+            reserved_nodes: None,
+            alloc_cpus: 384,
+            total_memory_mb: 5_120_000,
+            alloc_memory_mb: 1_024_000,
+            load: Default::default(),
+            node_names: vec![],
+        }
+    }
+
+    #[test]
+    fn test_render_template() {
+        let template =
+            Template::parse(r"{feature}\t{idle_nodes}/{total_nodes}\t{idle_cpus}").unwrap();
+        assert_eq!(
+            template.render("genoa", &["genoa"], &stats()),
+            "genoa\t4/10\t256"
+        );
+    }
+
+    #[test]
+    fn test_render_path_and_braces() {
+        let template = Template::parse("{{{path}}} {depth} {alloc_nodes} {preempt_cpus}").unwrap();
+        assert_eq!(
+            template.render("ib", &["icelake", "ib"], &stats()),
+            "{icelake/ib} 2 6 0"
+        );
+        let template = Template::parse("{alloc_memory_mb}/{total_memory_mb}").unwrap();
+        assert_eq!(
+            template.render("ib", &["icelake", "ib"], &stats()),
+            "1024000/5120000"
+        );
+    }
+
+    #[test]
+    fn test_invalid_templates() {
+        assert!(Template::parse("{idle}").is_err());
+        assert!(Template::parse("{feature").is_err());
+        assert!(Template::parse("feature}").is_err());
+    }
+}
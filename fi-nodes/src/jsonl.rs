@@ -0,0 +1,278 @@
+use crate::SortOrder;
+use crate::tree_report::{ReportLine, TreeNode, TreeReportData, sorted_children, top_level};
+use clap::ValueEnum;
+use fi_slurm::availability::{NodeAvailability, NodeMemory, allocated_cpus};
+use fi_slurm::jobs::SlurmJobs;
+use fi_slurm::nodes::{GpuInfo, Node};
+use fi_slurm::output::{OutputFormat, write_records};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::sync::Arc;
+
+/// What each line of the --jsonl output describes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum JsonlRecord {
+    /// One object per node
+    Nodes,
+    /// One object per line of the tree report
+    Tree,
+}
+
+/// A node as written by --jsonl nodes
+#[derive(Serialize)]
+struct NodeRecord<'a> {
+    name: &'a str,
+    state: String,
+    features: &'a [Arc<str>],
+    partitions: Vec<&'a str>,
+    cpus: u32,
+    alloc_cpus: u32,
+    idle_cpus: u32,
+    gpus: u32,
+    alloc_gpus: u32,
+    idle_gpus: u32,
+    mig_instances: u64,
+    alloc_mig_instances: u64,
+    shards: u64,
+    alloc_shards: u64,
+    gpu_equivalents: f64,
+    alloc_gpu_equivalents: f64,
+    memory_mb: u64,
+    alloc_memory_mb: u64,
+    jobs: &'a [u32],
+}
+
+/// A line of the tree report as written by --jsonl tree, with the features leading to it
+#[derive(Serialize)]
+struct TreeRecord<'a> {
+    path: String,
+    depth: usize,
+    name: &'a str,
+    #[serde(flatten)]
+    stats: &'a ReportLine,
+}
+
+/// Writes one line of JSON, flushing nothing, so that the caller decides how often to flush
+fn write_line(out: &mut impl Write, record: &impl Serialize) -> io::Result<()> {
+    serde_json::to_writer(&mut *out, record)?;
+    out.write_all(b"\n")
+}
+
+/// The record of a node, with the CPUs, GPUs and memory allocated on it
+fn node_record<'a>(
+    node: &'a Node,
+    jobs: &SlurmJobs,
+    node_to_job_map: &'a HashMap<usize, Vec<u32>>,
+) -> NodeRecord<'a> {
+    let job_ids = node_to_job_map.get(&node.id).map_or(&[][..], Vec::as_slice);
+    let alloc_cpus = allocated_cpus(node, jobs, node_to_job_map);
+    let no_gpus = GpuInfo::default();
+    let gpu = node.gpu_info.as_ref().unwrap_or(&no_gpus);
+    let availability = NodeAvailability::of_node(node, alloc_cpus);
+
+    NodeRecord {
+        name: &node.name,
+        state: node.state.to_string(),
+        features: &node.features,
+        partitions: node
+            .partitions
+            .split(',')
+            .filter(|partition| !partition.is_empty())
+            .collect(),
+        cpus: node.cpus as u32,
+        alloc_cpus,
+        idle_cpus: availability.idle_cpus,
+        gpus: gpu.total_gpus as u32,
+        alloc_gpus: gpu.allocated_gpus as u32,
+        idle_gpus: availability.idle_gpus,
+        mig_instances: gpu.mig_instances.configured,
+        alloc_mig_instances: gpu.mig_instances.allocated,
+        shards: gpu.shards.configured,
+        alloc_shards: gpu.shards.allocated,
+        gpu_equivalents: gpu.equivalents.configured,
+        alloc_gpu_equivalents: gpu.equivalents.allocated,
+        memory_mb: node.real_memory,
+        alloc_memory_mb: NodeMemory::of_node(node, jobs, node_to_job_map).allocated_mb,
+        jobs: job_ids,
+    }
+}
+
+/// Streams one JSON object per node, so that no document of the whole cluster is ever built
+pub fn write_node_lines(
+    nodes: &[&Node],
+    jobs: &SlurmJobs,
+    node_to_job_map: &HashMap<usize, Vec<u32>>,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    for &node in nodes {
+        write_line(out, &node_record(node, jobs, node_to_job_map))?;
+    }
+    out.flush()
+}
+
+/// A node as written by --output csv, whose fields can't hold lists, so its features,
+/// partitions and jobs are each joined into one field
+#[derive(Serialize)]
+struct NodeRow<'a> {
+    name: &'a str,
+    state: String,
+    features: String,
+    partitions: String,
+    cpus: u32,
+    alloc_cpus: u32,
+    idle_cpus: u32,
+    gpus: u32,
+    alloc_gpus: u32,
+    idle_gpus: u32,
+    mig_instances: u64,
+    alloc_mig_instances: u64,
+    shards: u64,
+    alloc_shards: u64,
+    gpu_equivalents: f64,
+    alloc_gpu_equivalents: f64,
+    memory_mb: u64,
+    alloc_memory_mb: u64,
+    jobs: String,
+}
+
+impl<'a> From<NodeRecord<'a>> for NodeRow<'a> {
+    fn from(record: NodeRecord<'a>) -> Self {
+        NodeRow {
+            name: record.name,
+            state: record.state,
+            features: record.features.join(","),
+            partitions: record.partitions.join(","),
+            cpus: record.cpus,
+            alloc_cpus: record.alloc_cpus,
+            idle_cpus: record.idle_cpus,
+            gpus: record.gpus,
+            alloc_gpus: record.alloc_gpus,
+            idle_gpus: record.idle_gpus,
+            mig_instances: record.mig_instances,
+            alloc_mig_instances: record.alloc_mig_instances,
+            shards: record.shards,
+            alloc_shards: record.alloc_shards,
+            gpu_equivalents: record.gpu_equivalents,
+            alloc_gpu_equivalents: record.alloc_gpu_equivalents,
+            memory_mb: record.memory_mb,
+            alloc_memory_mb: record.alloc_memory_mb,
+            jobs: record
+                .jobs
+                .iter()
+                .map(u32::to_string)
+                .collect::<Vec<_>>()
+                .join(","),
+        }
+    }
+}
+
+/// Writes the nodes in one of the structured output formats, with the same fields whichever it
+/// is. JSON Lines are streamed as by --jsonl nodes
+pub fn write_nodes(
+    format: OutputFormat,
+    nodes: &[&Node],
+    jobs: &SlurmJobs,
+    node_to_job_map: &HashMap<usize, Vec<u32>>,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    let records = nodes
+        .iter()
+        .map(|node| node_record(node, jobs, node_to_job_map));
+    match format {
+        OutputFormat::Jsonl => write_node_lines(nodes, jobs, node_to_job_map, out),
+        OutputFormat::Csv => {
+            let rows: Vec<NodeRow> = records.map(NodeRow::from).collect();
+            write_records(format, &rows, out)
+        }
+        _ => write_records(format, &records.collect::<Vec<_>>(), out),
+    }
+}
+
+/// Streams one JSON object per line of the tree report, in the order of the tree view
+pub fn write_tree_lines(
+    root: &TreeReportData,
+    sort: SortOrder,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    let top_level_node = top_level(root);
+    write_line(
+        out,
+        &TreeRecord {
+            path: String::new(),
+            depth: 0,
+            name: &top_level_node.name,
+            stats: &top_level_node.stats,
+        },
+    )?;
+    let mut path = Vec::new();
+    for child in sorted_children(&top_level_node.children, sort) {
+        write_tree_recursive(child, &mut path, sort, out)?;
+    }
+    out.flush()
+}
+
+fn write_tree_recursive<'a>(
+    tree_node: &'a TreeNode,
+    path: &mut Vec<&'a str>,
+    sort: SortOrder,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    path.push(&tree_node.name);
+    write_line(
+        out,
+        &TreeRecord {
+            path: path.join("/"),
+            depth: path.len(),
+            name: &tree_node.name,
+            stats: &tree_node.stats,
+        },
+    )?;
+    for child in sorted_children(&tree_node.children, sort) {
+        write_tree_recursive(child, path, sort, out)?;
+    }
+    path.pop();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree_node(name: &str, total_nodes: u32, children: Vec<TreeNode>) -> TreeNode {
+        TreeNode {
+            name: Arc::from(name),
+            stats: ReportLine {
+                total_nodes,
+                ..Default::default()
+            },
+            single_filter: false,
+            children: children
+                .into_iter()
+                .map(|child| (child.name.clone(), child))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_tree_lines() {
+        let root = tree_node(
+            "TOTAL",
+            12,
+            vec![tree_node("cpu", 12, vec![tree_node("genoa", 8, vec![])])],
+        );
+        let mut out = Vec::new();
+        write_tree_lines(&root, SortOrder::Nodes, &mut out).unwrap();
+
+        let lines: Vec<serde_json::Value> = String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0]["depth"], 0);
+        assert_eq!(lines[2]["path"], "cpu/genoa");
+        assert_eq!(lines[2]["depth"], 2);
+        assert_eq!(lines[2]["total_nodes"], 8);
+    }
+}
@@ -0,0 +1,258 @@
+//! The reports of fi-nodes, kept apart from its command line so that benchmarks and other
+//! tools can build them from a `ClusterState` of their own
+
+pub mod accessible;
+pub mod brief_report;
+pub mod burst_buffer_report;
+pub mod cache;
+pub mod exporter;
+pub mod format_report;
+pub mod jsonl;
+pub mod license_report;
+pub mod motd;
+pub mod queue_report;
+pub mod report;
+pub mod sched_stats;
+pub mod snapshot;
+pub mod tree_report;
+pub mod when;
+
+#[cfg(feature = "tui")]
+pub mod tui;
+
+use chrono::{DateTime, Duration, Utc};
+use clap::ValueEnum;
+use fi_slurm::jobs::SlurmJobs;
+use fi_slurm::nodes::{NodeState, SlurmNodes};
+use fi_slurm::reservations::SlurmReservations;
+use std::collections::{HashMap, HashSet};
+
+/// Newtype for the ids of preemptable nodes
+#[derive(Clone)]
+pub struct PreemptNodes(Vec<usize>);
+
+/// The nodes held by reservations now or within the window given to --reserved-within
+#[derive(Clone, Debug, Default)]
+pub struct ReservedNodes {
+    pub node_ids: HashSet<usize>,
+    /// Whether the reserved nodes count as unavailable, with --respect-reservations
+    pub respect: bool,
+}
+
+impl ReservedNodes {
+    /// Collects the nodes of the reservations that hold them at some time between `now` and
+    /// the end of the window, leaving out those of nodes that aren't loaded, or an error if the
+    /// window ends beyond the times chrono can represent
+    pub fn within(
+        reservations: &SlurmReservations,
+        nodes: &SlurmNodes,
+        now: DateTime<Utc>,
+        window: Duration,
+        respect: bool,
+    ) -> Result<Self, String> {
+        let end = now
+            .checked_add_signed(window)
+            .ok_or("the reservation window ends past the latest time that can be represented")?;
+        let node_ids = reservations
+            .overlapping(now, end)
+            .flat_map(|reservation| &reservation.node_names)
+            .filter_map(|name| nodes.name_to_id.get(name).copied())
+            .collect();
+        Ok(ReservedNodes { node_ids, respect })
+    }
+}
+
+/// Orderings for the features of the tree report and the subgroups of the detailed report
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SortOrder {
+    /// By total node count, largest first (tree report default)
+    Nodes,
+    /// Alphabetically (detailed report default)
+    Name,
+    /// By idle node count, largest first
+    IdleNodes,
+    /// By idle CPU (or GPU) count, largest first
+    IdleCpus,
+    /// By the fraction of CPUs (or GPUs) allocated, least utilized first
+    Utilization,
+}
+
+/// The longest window `parse_window` accepts, about ten years
+pub const MAX_WINDOW_DAYS: i64 = 3650;
+
+/// Parses a window such as `30m`, `2h` or `1d` into its length, which must be positive and at
+/// most `MAX_WINDOW_DAYS`, so that adding it to the current time can't overflow
+pub fn parse_window(window: &str) -> Result<Duration, String> {
+    let invalid = || {
+        format!(
+            "invalid window '{}': expected a positive number and m, h or d, up to {}d",
+            window, MAX_WINDOW_DAYS
+        )
+    };
+    // the unit is stripped as a whole character, so that a multibyte one is just invalid
+    let (amount, unit): (&str, fn(i64) -> Option<Duration>) =
+        if let Some(amount) = window.strip_suffix('m') {
+            (amount, Duration::try_minutes)
+        } else if let Some(amount) = window.strip_suffix('h') {
+            (amount, Duration::try_hours)
+        } else if let Some(amount) = window.strip_suffix('d') {
+            (amount, Duration::try_days)
+        } else {
+            return Err(invalid());
+        };
+    let amount: i64 = amount.parse().map_err(|_| invalid())?;
+    unit(amount)
+        .filter(|window| *window > Duration::zero() && *window <= Duration::days(MAX_WINDOW_DAYS))
+        .ok_or_else(invalid)
+}
+
+/// Function to crawl through the node to job map and change the status of a given node if the
+/// job/s running on it are preempt.
+///
+/// If a preempt job is othe only one running on that node, we change its base state to Idle. If
+/// a preempt job is one of several running on the node, we can change it from Allocated to Mixed,
+/// assuming it was not already Mixed.
+pub fn preempt_node(
+    slurm_nodes: &mut SlurmNodes,
+    node_to_job_map: &HashMap<usize, Vec<u32>>,
+    slurm_jobs: &SlurmJobs,
+) -> PreemptNodes {
+    let now: DateTime<Utc> = Utc::now();
+
+    let mut preemptable_jobs: HashSet<u32> = HashSet::new();
+
+    // in order to figure out which nodes are preempt, we have to take the current UTC date time
+    // and compare it to the preemptable_time feature in the job
+    for job in slurm_jobs.jobs.values() {
+        if job.preemptable_time <= now && job.preemptable_time != chrono::DateTime::UNIX_EPOCH {
+            // we ensure that the time is not just 0, the start of the Unix Epoch
+            preemptable_jobs.insert(job.job_id);
+        }
+    }
+
+    let mut all_preempt = HashSet::new();
+    let mut partially_preempt = HashSet::new();
+
+    // we iterate through the nodes and the jobs on them, and collect them into the preempt lists
+    for (node_id, jobs_on_node) in node_to_job_map.iter() {
+        if jobs_on_node.is_empty() {
+            continue;
+        }
+
+        let is_all_preempt = jobs_on_node
+            .iter()
+            .all(|job_id| preemptable_jobs.contains(job_id));
+
+        if is_all_preempt {
+            all_preempt.insert(*node_id);
+        } else {
+            let has_any_preempt = jobs_on_node
+                .iter()
+                .any(|job_id| preemptable_jobs.contains(job_id));
+
+            if has_any_preempt {
+                partially_preempt.insert(*node_id);
+            }
+        }
+    }
+
+    // having both lists, now we go through SlurmNodes.nodes, check ids, and convert the base
+    // node_state, taking into account compound states as well
+    //
+    // for nodes in the all_preempt vector, we want to turn allocated and mixed nodes to idle, and
+    // compound allocated/mixed to idle
+    //
+    // for nodes in the partially_preempt list, we want to turn allocated into mixed
+    // we leave mixed be, because if the jobs running on it were all preempt, the node would be in
+    // the other category
+
+    let mut preemptable_nodes: Vec<usize> = Vec::new();
+
+    for node in slurm_nodes.nodes.iter_mut() {
+        if all_preempt.contains(&node.id) {
+            match &node.state {
+                NodeState::Allocated | NodeState::Mixed => {
+                    preemptable_nodes.push(node.id);
+                    node.state = NodeState::Idle
+                }
+                NodeState::Compound { base, flags } => match **base {
+                    NodeState::Allocated | NodeState::Mixed => {
+                        preemptable_nodes.push(node.id);
+                        node.state = NodeState::Compound {
+                            base: Box::new(NodeState::Idle),
+                            flags: *flags,
+                        }
+                    }
+                    _ => (),
+                },
+                _ => (),
+            }
+        } else if partially_preempt.contains(&node.id) {
+            match &node.state {
+                NodeState::Allocated => {
+                    preemptable_nodes.push(node.id);
+                    node.state = NodeState::Mixed
+                }
+                NodeState::Compound { base, flags } => {
+                    if **base == NodeState::Allocated {
+                        preemptable_nodes.push(node.id);
+                        node.state = NodeState::Compound {
+                            base: Box::new(NodeState::Mixed),
+                            flags: *flags,
+                        }
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+
+    PreemptNodes(preemptable_nodes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reserved_within_oversized_window() {
+        let reservations = SlurmReservations {
+            reservations: Vec::new(),
+            last_update: Utc::now(),
+        };
+        let nodes = SlurmNodes {
+            nodes: Vec::new(),
+            name_to_id: HashMap::new(),
+            last_update: Utc::now(),
+            skip_count: 0,
+        };
+        let within =
+            |window| ReservedNodes::within(&reservations, &nodes, Utc::now(), window, false);
+        assert!(within(Duration::days(MAX_WINDOW_DAYS)).is_ok());
+        assert!(within(Duration::MAX).is_err());
+    }
+
+    #[test]
+    fn test_parse_window() {
+        assert_eq!(parse_window("30m"), Ok(Duration::minutes(30)));
+        assert_eq!(parse_window("2h"), Ok(Duration::hours(2)));
+        assert_eq!(parse_window("1d"), Ok(Duration::days(1)));
+        assert_eq!(parse_window("3650d"), Ok(Duration::days(MAX_WINDOW_DAYS)));
+        for invalid in [
+            "",
+            "h",
+            "2",
+            "2w",
+            "2µ",
+            "µ",
+            "1é",
+            "0m",
+            "-2h",
+            "3651d",
+            "1000000000d",
+            "99999999999999d",
+        ] {
+            assert!(parse_window(invalid).is_err(), "{}", invalid);
+        }
+    }
+}
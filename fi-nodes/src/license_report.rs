@@ -0,0 +1,112 @@
+use colored::*;
+use fi_slurm::jobs::SlurmJobs;
+use fi_slurm::licenses::{SlurmLicenses, parse_license_request};
+use fi_slurm::theme::Theme;
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// The pending reason of jobs waiting for licenses to free up, as Slurm reports it
+const WAITING_FOR_LICENSES: &str = "Licenses";
+
+/// The counts of one license, and the demand from the jobs waiting for it
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct LicenseLine {
+    pub name: String,
+    pub total: u32,
+    pub in_use: u32,
+    pub reserved: u32,
+    pub available: u32,
+    pub remote: bool,
+    /// Pending jobs held back by a lack of licenses that ask for this one, counting each
+    /// pending task of an array separately
+    pub waiting_jobs: u32,
+    /// The licenses those jobs ask for in all
+    pub waiting_count: u32,
+}
+
+/// Lists each license with the jobs pending for lack of licenses that ask for it
+pub fn build_license_report(licenses: &SlurmLicenses, jobs: &SlurmJobs) -> Vec<LicenseLine> {
+    let mut waiting: HashMap<String, (u32, u32)> = HashMap::new();
+    for job in jobs
+        .pending()
+        .filter(|job| job.state_reason == WAITING_FOR_LICENSES)
+    {
+        let tasks = job.task_count();
+        for (name, count) in parse_license_request(&job.licenses) {
+            let entry = waiting.entry(name).or_default();
+            entry.0 += tasks;
+            entry.1 += count * tasks;
+        }
+    }
+
+    let mut lines: Vec<LicenseLine> = licenses
+        .licenses
+        .iter()
+        .map(|license| {
+            let (waiting_jobs, waiting_count) =
+                waiting.get(&license.name).copied().unwrap_or_default();
+            LicenseLine {
+                name: license.name.clone(),
+                total: license.total,
+                in_use: license.in_use,
+                reserved: license.reserved,
+                available: license.available,
+                remote: license.remote,
+                waiting_jobs,
+                waiting_count,
+            }
+        })
+        .collect();
+    lines.sort_by(|a, b| a.name.cmp(&b.name));
+    lines
+}
+
+/// Prints the license table, flagging the licenses jobs are waiting for
+pub fn print_license_report(lines: &[LicenseLine], theme: &Theme) {
+    if lines.is_empty() {
+        println!("No licenses are configured");
+        return;
+    }
+
+    let width = lines
+        .iter()
+        .map(|line| line.name.len() + if line.remote { 9 } else { 0 })
+        .max()
+        .unwrap_or(0)
+        .max("LICENSE".len());
+
+    println!(
+        "{}",
+        format!(
+            "{:<width$}  {:>6}  {:>6}  {:>8}  {:>6}  {:>12}",
+            "LICENSE", "TOTAL", "USED", "RESERVED", "FREE", "WAITING JOBS"
+        )
+        .bold()
+    );
+    for line in lines {
+        let name = if line.remote {
+            format!("{} (remote)", line.name)
+        } else {
+            line.name.clone()
+        };
+        let waiting = if line.waiting_jobs == 0 {
+            "-".to_string()
+        } else {
+            format!("{} ({})", line.waiting_jobs, line.waiting_count)
+        };
+        let row = format!(
+            "{:<width$}  {:>6}  {:>6}  {:>8}  {:>6}  {:>12}",
+            name, line.total, line.in_use, line.reserved, line.available, waiting
+        );
+        // flag the licenses that are holding jobs back
+        if line.waiting_jobs > 0 {
+            println!("{}", theme.paint(&row, theme.palette.warning));
+        } else {
+            println!("{}", row);
+        }
+    }
+    println!();
+    println!(
+        "Waiting jobs are pending for lack of licenses, with the licenses they ask for in parentheses"
+    );
+}
@@ -1,23 +1,43 @@
-pub mod report;
-pub mod summary_report;
-pub mod tree_report;
-
-#[cfg(feature = "tui")]
-pub mod tui;
-
 #[cfg(feature = "tui")]
-use crate::tui::app::tui_execute;
-
-use clap::Parser;
-use fi_slurm::filter::filter_nodes_by_feature;
-use fi_slurm::jobs::{SlurmJobs, build_node_to_job_map, enrich_jobs_with_node_ids, get_jobs};
-use fi_slurm::nodes::get_nodes;
-use fi_slurm::nodes::{NodeState, SlurmNodes};
-use fi_slurm::utils::{SlurmConfig, initialize_slurm};
-use std::collections::{HashMap, HashSet};
-use tree_report::{GpuFilter, build_tree_report, print_tree_report};
-
-use chrono::{DateTime, Utc};
+use fi_nodes::tui::app::tui_execute;
+
+use clap::builder::PossibleValuesParser;
+use clap::{CommandFactory, Parser};
+use clap_complete::Shell;
+use colored::Colorize;
+use fi_nodes::jsonl::JsonlRecord;
+use fi_nodes::tree_report::{GpuFilter, build_tree_report, print_tree_report};
+use fi_nodes::{
+    ReservedNodes, SortOrder, accessible, brief_report, burst_buffer_report, cache, exporter,
+    format_report, jsonl, license_report, motd, parse_window, preempt_node, queue_report, report,
+    sched_stats, snapshot, when,
+};
+use fi_slurm::burst_buffer::get_burst_buffers;
+use fi_slurm::cluster_state::ClusterState;
+use fi_slurm::error::SlurmError;
+use fi_slurm::federation::{Federation, FederationState, get_federation, use_cluster};
+use fi_slurm::filter::{
+    FeatureExpr, filter_nodes_by_feature, filter_nodes_by_job, gather_all_features,
+    suggest_features,
+};
+use fi_slurm::jobs::FilterMethod;
+use fi_slurm::licenses::get_licenses;
+use fi_slurm::nodes::{SlurmNodes, get_nodes};
+use fi_slurm::output::{OutputFormat, ignore_broken_pipe};
+use fi_slurm::reservations::{SlurmReservations, get_reservations};
+use fi_slurm::site::{cluster, hidden_features};
+use fi_slurm::source::source_for;
+use fi_slurm::statistics::get_scheduler_statistics;
+use fi_slurm::theme::Theme;
+use fi_slurm::topology::get_topology;
+use fi_slurm::utils::{Pager, SlurmConfig, initialize_slurm};
+use fi_slurm_db::acct::get_user_assocs;
+use fi_slurm_db::db::{DbError, DbPool};
+use std::collections::HashSet;
+use std::process::ExitCode;
+use thiserror::Error;
+
+use chrono::Duration;
 use std::time::Instant;
 
 /// The main entry point for the `fi-nodes` utility
@@ -27,43 +47,158 @@ use std::time::Instant;
 /// 2. Create a cross-reference map to link nodes to the jobs running on them
 /// 3. Aggregate all data into a structured report format
 /// 4. Print the final, formatted report to the console
-fn main() -> Result<(), String> {
+///
+/// Errors are printed to stderr, and Slurm failures exit with a code describing their cause
+/// (see `SlurmError::exit_code`) so that scripts can tell them apart
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::from(e.exit_code())
+        }
+    }
+}
+
+/// Errors that end a run of fi-nodes
+#[derive(Error, Debug)]
+pub enum CliError {
+    #[error(transparent)]
+    Slurm(#[from] SlurmError),
+    #[error(transparent)]
+    Db(#[from] DbError),
+    #[error("{0}")]
+    Other(String),
+}
+
+impl From<String> for CliError {
+    fn from(message: String) -> Self {
+        CliError::Other(message)
+    }
+}
+
+impl CliError {
+    fn exit_code(&self) -> u8 {
+        match self {
+            CliError::Slurm(e) => e.exit_code(),
+            CliError::Db(_) | CliError::Other(_) => 1,
+        }
+    }
+}
+
+fn run() -> Result<(), CliError> {
     let start = Instant::now();
 
     let args = Args::parse();
 
+    if let Some(shell) = args.completions {
+        print_completions(shell);
+        return Ok(());
+    }
+    if args.generate_man {
+        print_man_page();
+        return Ok(());
+    }
+
+    // a mistyped theme or broken palette file is reported before anything is drawn
+    let theme = Theme::load(args.theme.as_deref(), args.no_color)?;
+
     // entry point for the prometheus TUI utility
     #[cfg(feature = "tui")]
     {
         if args.term {
-            let _ = tui_execute();
+            let _ = tui_execute(theme);
             return Ok(());
         }
     }
 
-    if args.debug {
-        println!("Started initializing Slurm: {:?}", start.elapsed());
+    // parse the output template up front so that mistakes are reported before loading any data
+    let template = args
+        .format
+        .as_deref()
+        .map(format_report::Template::parse)
+        .transpose()?;
+
+    // likewise for mistakes in feature expressions such as "icelake&!gpu"
+    for filter in &args.feature {
+        FeatureExpr::parse(filter)?;
     }
 
-    // has no output, only passes a null pointer to Slurm directly in order to initialize
-    // non-trivial functions of the Slurm API
-    initialize_slurm();
+    // and in the request of --when
+    let when_request = args
+        .when
+        .as_deref()
+        .map(when::WhenRequest::parse)
+        .transpose()?;
+
+    // the live cluster, or a state file standing in for it, with which Slurm isn't touched
+    let source = source_for(args.state_file.as_deref());
+
+    // with --cached, an unreachable controller falls back to the cached state further down
+    let mut unreachable = None;
+    // Kept until the end of main(), when its Drop implementation frees the configuration, and
+    // read for the name of the cluster whose state --cached keeps
+    let slurm_config = if source.needs_slurm() {
+        if args.debug {
+            println!("Started initializing Slurm: {:?}", start.elapsed());
+        }
 
-    if args.debug {
-        println!("Finished initializing Slurm: {:?}", start.elapsed());
+        // has no output, only passes a null pointer to Slurm directly in order to initialize
+        // non-trivial functions of the Slurm API
+        initialize_slurm();
+
+        // direct everything that follows to another cluster, as -M does
+        if let Some(cluster) = &args.cluster {
+            use_cluster(cluster)?;
+        }
+
+        if args.debug {
+            println!("Finished initializing Slurm: {:?}", start.elapsed());
+        }
+
+        // After initializing, we load the conf to get a handle that we can
+        // manage for proper cleanup
+        if args.debug {
+            println!("Started loading Slurm config: {:?}", start.elapsed());
+        }
+        let config = match SlurmConfig::load() {
+            Ok(config) => Some(config),
+            Err(e) if args.cached && e.is_unreachable() => {
+                unreachable = Some(e);
+                None
+            }
+            Err(e) => return Err(e.into()),
+        };
+        if args.debug {
+            println!("Finished loading Slurm config: {:?}", start.elapsed());
+        }
+        config
+    } else {
+        None
+    };
+
+    // list the clusters of the federation instead of the nodes
+    if args.federation {
+        print_federation(get_federation()?.as_ref(), &theme);
+        return Ok(());
     }
 
-    // After initializing, we load the conf to get a handle that we can
-    // manage for proper cleanup
-    if args.debug {
-        println!("Started loading Slurm config: {:?}", start.elapsed());
+    // show the burst buffer pools instead of the nodes
+    if args.burst_buffers {
+        burst_buffer_report::print_burst_buffer_report(&get_burst_buffers()?, &theme);
+        return Ok(());
     }
 
-    // We don't need to actually use this variable, but we store it anyway in order to
-    // automatically invoke its Drop implementation when it goes out of scope at the end of main()
-    let _slurm_config = SlurmConfig::load()?;
-    if args.debug {
-        println!("Finished loading Slurm config: {:?}", start.elapsed());
+    // the scheduler's statistics need none of the cluster state
+    if args.sched_stats {
+        let stats = get_scheduler_statistics()?;
+        sched_stats::print_sched_stats(&stats, &theme);
+        return Ok(());
+    }
+
+    // serve metrics until interrupted, loading fresh data from Slurm on every scrape
+    if let Some(addr) = &args.exporter {
+        return Ok(exporter::run_exporter(addr)?);
     }
 
     // Load Data
@@ -71,40 +206,108 @@ fn main() -> Result<(), String> {
         println!("Starting to load Slurm data: {:?}", start.elapsed());
     }
 
-    // Collect current node information from the cluster
-    let mut nodes_collection = get_nodes()?;
+    // Collect current node, job and partition information from the cluster, with the jobs
+    // cross-referenced against the nodes they run on
+    let loaded = match unreachable {
+        Some(e) => Err(e),
+        None => source.load(),
+    };
+    // with --cached, keep each state loaded, and show the last one kept if the controller
+    // can't be reached, as during a failover
+    // the cache is kept per cluster, and never for a state file, which is a cache of its own
+    let (cluster_state, stale_since) = if args.cached && source.needs_slurm() {
+        let cluster = args
+            .cluster
+            .clone()
+            .or_else(|| slurm_config.as_ref().and_then(SlurmConfig::cluster_name))
+            .or_else(cache::local_cluster_name);
+        cache::with_fallback(loaded, cluster.as_deref())?
+    } else {
+        (loaded?, None)
+    };
     if args.debug {
         println!(
-            "Finished loading node data for {} nodes ({} skipped with 0 CPUs) from Slurm: {:?}",
-            nodes_collection.nodes.len(),
-            nodes_collection.skip_count,
+            "Finished loading {} nodes ({} skipped with 0 CPUs) and {} jobs from Slurm: {:?}",
+            cluster_state.nodes.nodes.len(),
+            cluster_state.nodes.skip_count,
+            cluster_state.jobs.jobs.len(),
             start.elapsed()
         );
-    }
-
-    // collect current job information from the cluster
-    let mut jobs_collection = get_jobs()?;
-    if args.debug {
         println!(
-            "Finished loading job data for {} jobs from Slurm: {:?}",
-            jobs_collection.jobs.len(),
-            start.elapsed()
+            "Built map cross-referencing {} nodes with active jobs.",
+            cluster_state.node_to_job_map.len()
         );
     }
 
-    // add the node ids instead of just node hostnames to the jobs collection
-    // necessary in order for cross-referencing and creating the node to job mapping in the build
-    // report functions
-    enrich_jobs_with_node_ids(&mut jobs_collection, &nodes_collection.name_to_id);
+    // everything from here on prints to stdout, which may run to thousands of lines with -n,
+    // so page it when writing to a terminal; the pager is waited on when this goes out of scope
+    let _pager = Pager::start(args.no_pager);
+    if let Some(taken_at) = stale_since {
+        cache::print_stale_banner(taken_at, &theme);
+    }
 
-    // Build Cross-Reference Map
-    let node_to_job_map = build_node_to_job_map(&jobs_collection);
-    if args.debug {
+    // features left out of the tree and diff, per the site configuration, unless asked to show them
+    let hidden_features: HashSet<String> = if args.verbose {
+        HashSet::new()
+    } else {
+        hidden_features()
+            .iter()
+            .filter(|f| !args.show_feature.contains(f))
+            .cloned()
+            .collect()
+    };
+
+    // save or compare against a snapshot of the full cluster state, before any preempt
+    // reclassification of nodes
+    if let Some(path) = &args.snapshot {
+        cluster_state.save(path)?;
         println!(
-            "Built map cross-referencing {} nodes with active jobs.",
-            node_to_job_map.len()
+            "Saved snapshot of {} nodes and {} jobs to {}",
+            cluster_state.nodes.nodes.len(),
+            cluster_state.jobs.jobs.len(),
+            path
         );
-        println!("Finished building node to job map: {:?}", start.elapsed());
+        return Ok(());
+    }
+    if let Some(path) = &args.diff {
+        let mut old = ClusterState::from_snapshot(path)?;
+        let mut new = cluster_state;
+        old.retain_features(&args.feature);
+        new.retain_features(&args.feature);
+        snapshot::print_diff(&old.diff(&new), &hidden_features, &theme);
+        return Ok(());
+    }
+
+    let ClusterState {
+        nodes: mut nodes_collection,
+        jobs: jobs_collection,
+        partitions,
+        node_to_job_map,
+        taken_at,
+    } = cluster_state;
+
+    // the jobs of the given users or accounts, for restricting the nodes or the queue report
+    let owners: Vec<FilterMethod> = args
+        .user
+        .iter()
+        .cloned()
+        .map(FilterMethod::UserName)
+        .chain(args.account.iter().cloned().map(FilterMethod::Account))
+        .collect();
+
+    // summarize the pending queue instead of the nodes
+    if args.queue {
+        let queue_report = queue_report::build_queue_report(&jobs_collection, &owners);
+        queue_report::print_queue_report(&queue_report, &theme);
+        return Ok(());
+    }
+
+    // show the licenses, and the jobs waiting for them, instead of the nodes
+    if args.licenses {
+        let licenses = get_licenses()?;
+        let license_report = license_report::build_license_report(&licenses, &jobs_collection);
+        license_report::print_license_report(&license_report, &theme);
+        return Ok(());
     }
 
     // getting information on which nodes are preemptable, to be used in the build report functions
@@ -120,10 +323,52 @@ fn main() -> Result<(), String> {
 
     // filtering nodes by feature
     let mut filtered_nodes = filter_nodes_by_feature(&nodes_collection, &args.feature, args.exact);
-    if args.debug && !args.feature.is_empty() {
+    if !args.feature.is_empty() && filtered_nodes.is_empty() {
+        warn_unmatched_features(&nodes_collection, &args.feature);
+    }
+
+    // restricting to the nodes running jobs of the given users or accounts
+    filtered_nodes =
+        filter_nodes_by_job(filtered_nodes, &jobs_collection, &node_to_job_map, &owners);
+
+    // restricting to the nodes of the partitions the current user could run jobs in
+    if args.accessible {
+        let assocs = get_user_assocs(None, &mut DbPool::new())?;
+        let accessible = accessible::accessible_partitions(&partitions, &assocs);
+        filtered_nodes.retain(|node| {
+            node.partitions
+                .split(',')
+                .any(|partition| accessible.contains(partition))
+        });
+    }
+
+    if args.debug && (!(args.feature.is_empty() && owners.is_empty()) || args.accessible) {
         println!("Finished filtering data: {:?}", start.elapsed());
     }
 
+    // estimate when a hypothetical job could start on the filtered nodes
+    if let Some(request) = &when_request {
+        // a state file holds no reservations, and the controller isn't to be contacted for them
+        let reservations = if source.needs_slurm() {
+            get_reservations()?
+        } else {
+            SlurmReservations {
+                reservations: Vec::new(),
+                last_update: taken_at,
+            }
+        };
+        let (matching_nodes, start) = when::estimate_start(
+            request,
+            &filtered_nodes,
+            &jobs_collection,
+            &node_to_job_map,
+            &reservations,
+            args.preempt,
+        );
+        when::print_estimate(request, matching_nodes, start);
+        return Ok(());
+    }
+
     // if all filtered nodes are GPU nodes, then automatically enable -g,
     // if the user did not specify -a
     let do_gpu_report = !args.all
@@ -152,6 +397,26 @@ fn main() -> Result<(), String> {
         println!("Started building node to job map: {:?}", start.elapsed());
     }
 
+    // the nodes as records for other programs; --jsonl streams them, for pipelines that can't
+    // take the cluster as one document
+    let output = if args.jsonl == Some(JsonlRecord::Nodes) {
+        OutputFormat::Jsonl
+    } else {
+        args.output
+    };
+    if output.is_structured() {
+        let mut out = std::io::BufWriter::new(std::io::stdout().lock());
+        ignore_broken_pipe(jsonl::write_nodes(
+            output,
+            &filtered_nodes,
+            &jobs_collection,
+            &node_to_job_map,
+            &mut out,
+        ))
+        .map_err(|e| CliError::Other(format!("Failed to write the nodes: {}", e)))?;
+        return Ok(());
+    }
+
     // entry point for the detailed report (replacement for nick carriero's featureInfo utility)
     if args.detailed {
         if args.debug {
@@ -173,30 +438,44 @@ fn main() -> Result<(), String> {
         }
 
         // Print Report
-        report::print_report(&report, args.no_color, args.names, args.allocated);
+        report::print_report(
+            &report,
+            &theme,
+            args.names,
+            args.allocated,
+            args.sort.unwrap_or(SortOrder::Name),
+        );
         if args.debug {
             println!("Finished printing report: {:?}", start.elapsed());
         }
 
         return Ok(());
 
-    // the entry point for the summary report (DEPRECATED)
-    } else if args.summary {
-        // Aggregate data into summary report
-        let summary_report = summary_report::build_summary_report(
+    // the overview of the whole cluster in a few lines
+    } else if args.brief {
+        let brief_report = brief_report::build_brief_report(
             &filtered_nodes,
             &jobs_collection,
             &node_to_job_map,
+            &hidden_features,
         );
         if args.debug {
-            println!(
-                "Aggregated data into {} feature types.",
-                summary_report.len()
-            );
-            println!("Finished building summary report: {:?}", start.elapsed());
+            println!("Finished building brief report: {:?}", start.elapsed());
         }
 
-        summary_report::print_summary_report(&summary_report, args.no_color);
+        brief_report::print_brief_report(&brief_report, &theme);
+
+        return Ok(());
+
+    // the same overview as bars of a fixed width, for /etc/motd
+    } else if args.motd {
+        let brief_report = brief_report::build_brief_report(
+            &filtered_nodes,
+            &jobs_collection,
+            &node_to_job_map,
+            &hidden_features,
+        );
+        motd::print_motd(&brief_report, cluster().as_deref(), taken_at, &theme);
 
         return Ok(());
     } else {
@@ -217,26 +496,72 @@ fn main() -> Result<(), String> {
             GpuFilter::All => {}
         }
 
+        // the switches above each node, to group the tree report by
+        let node_paths = if args.topology {
+            let topology = get_topology()?;
+            if topology.switches.is_empty() {
+                return Err(CliError::Other(
+                    "The cluster has no switch topology; --topology needs TopologyPlugin=topology/tree".to_string(),
+                ));
+            }
+            Some(topology.node_paths())
+        } else {
+            None
+        };
+
+        // the nodes held by reservations within the window, flagged on each branch
+        let reserved_nodes = match args.reserved_within {
+            Some(window) => Some(ReservedNodes::within(
+                &get_reservations()?,
+                &nodes_collection,
+                taken_at,
+                window,
+                args.respect_reservations,
+            )?),
+            None => None,
+        };
+
         // Aggregate data into the tree report
         let tree_report = build_tree_report(
             &filtered_nodes,
             &jobs_collection,
             &node_to_job_map,
             &args.feature,
-            args.verbose,
+            &hidden_features,
             args.names,
             preemptable_nodes,
             args.preempt,
             do_gpu_report, // count GPUs instead of CPUs
+            args.forecast,
+            node_paths.as_ref(),
+            reserved_nodes.as_ref(),
         );
-        print_tree_report(
-            &tree_report,
-            args.no_color,
-            args.names,
-            args.alphabetical,
-            args.preempt,
-            do_gpu_report, // display GPU column
-        );
+        // --alphabetical is kept as a shorthand for --sort name
+        let tree_sort = if args.alphabetical {
+            SortOrder::Name
+        } else {
+            args.sort.unwrap_or(SortOrder::Nodes)
+        };
+
+        if let Some(template) = &template {
+            format_report::print_formatted_tree(&tree_report, template, tree_sort);
+        } else if args.jsonl == Some(JsonlRecord::Tree) {
+            let mut out = std::io::BufWriter::new(std::io::stdout().lock());
+            ignore_broken_pipe(jsonl::write_tree_lines(&tree_report, tree_sort, &mut out))
+                .map_err(|e| CliError::Other(format!("Failed to write JSON lines: {}", e)))?;
+        } else {
+            print_tree_report(
+                &tree_report,
+                &theme,
+                args.names,
+                tree_sort,
+                args.preempt,
+                do_gpu_report, // display GPU column
+                args.forecast.is_some(),
+                args.load,
+                reserved_nodes.is_some(),
+            );
+        }
 
         if args.debug {
             println!("Finished building tree report: {:?}", start.elapsed());
@@ -246,119 +571,125 @@ fn main() -> Result<(), String> {
     Ok(())
 }
 
-/// Newtype for the ids of preemptable nodes
-#[derive(Clone)]
-pub struct PreemptNodes(Vec<usize>);
+/// Writes a man page generated from the command line definition to stdout
+fn print_man_page() {
+    // rendering can only fail on writing to stdout, such as into a closed pipe
+    let _ = clap_mangen::Man::new(Args::command()).render(&mut std::io::stdout());
+}
 
-/// Function to crawl through the node to job map and change the status of a given node if the
-/// job/s running on it are preempt.
+/// Writes a completion script for the given shell to stdout
 ///
-/// If a preempt job is othe only one running on that node, we change its base state to Idle. If
-/// a preempt job is one of several running on the node, we can change it from Allocated to Mixed,
-/// assuming it was not already Mixed.
-fn preempt_node(
-    slurm_nodes: &mut SlurmNodes,
-    node_to_job_map: &HashMap<usize, Vec<u32>>,
-    slurm_jobs: &SlurmJobs,
-) -> PreemptNodes {
-    let now: DateTime<Utc> = Utc::now();
-
-    let mut preemptable_jobs: HashSet<u32> = HashSet::new();
-
-    // in order to figure out which nodes are preempt, we have to take the current UTC date time
-    // and compare it to the preemptable_time feature in the job
-    for job in slurm_jobs.jobs.values() {
-        if job.preemptable_time <= now && job.preemptable_time != chrono::DateTime::UNIX_EPOCH {
-            // we ensure that the time is not just 0, the start of the Unix Epoch
-            preemptable_jobs.insert(job.job_id);
-        }
+/// If Slurm can be reached, the cluster's node features are offered as completions for the
+/// feature arguments, so the script is best generated on a cluster node
+fn print_completions(shell: Shell) {
+    let mut command = Args::command();
+    let features = cluster_features();
+    if !features.is_empty() {
+        // the command is only used to write the script, so the features needn't outlive it
+        let features: Vec<&'static str> = features
+            .into_iter()
+            .map(|feature| &*feature.leak())
+            .collect();
+        command = command
+            .mut_arg("feature", |arg| {
+                arg.value_parser(PossibleValuesParser::new(features.clone()))
+            })
+            .mut_arg("show_feature", |arg| {
+                arg.value_parser(PossibleValuesParser::new(features.clone()))
+            });
     }
+    clap_complete::generate(
+        shell,
+        &mut command,
+        env!("CARGO_BIN_NAME"),
+        &mut std::io::stdout(),
+    );
+}
 
-    let mut all_preempt = HashSet::new();
-    let mut partially_preempt = HashSet::new();
-
-    // we iterate through the nodes and the jobs on them, and collect them into the preempt lists
-    for (node_id, jobs_on_node) in node_to_job_map.iter() {
-        if jobs_on_node.is_empty() {
-            continue;
-        }
-
-        let is_all_preempt = jobs_on_node
-            .iter()
-            .all(|job_id| preemptable_jobs.contains(job_id));
+/// The sorted node features of the cluster, or none if Slurm can't be reached
+fn cluster_features() -> Vec<String> {
+    initialize_slurm();
+    let Ok(_slurm_config) = SlurmConfig::load() else {
+        return Vec::new();
+    };
+    let Ok(nodes) = get_nodes() else {
+        return Vec::new();
+    };
+    let mut features: Vec<String> = gather_all_features(&nodes).into_iter().collect();
+    features.sort();
+    features
+}
 
-        if is_all_preempt {
-            all_preempt.insert(*node_id);
+/// Prints the clusters of a federation, with those not taking new jobs in the warning color
+fn print_federation(federation: Option<&Federation>, theme: &Theme) {
+    let Some(federation) = federation else {
+        println!("This cluster is not part of a federation");
+        return;
+    };
+    println!("{} {}", "Federation".bold(), federation.name);
+
+    let width = federation
+        .clusters
+        .iter()
+        .map(|cluster| cluster.name.len())
+        .max()
+        .unwrap_or(0)
+        .max("CLUSTER".len());
+    println!(
+        "{}",
+        format!(
+            "{:>3}  {:<width$}  {:<8}  {:<24}  FEATURES",
+            "ID", "CLUSTER", "STATE", "CONTROLLER"
+        )
+        .bold()
+    );
+    for cluster in &federation.clusters {
+        let row = format!(
+            "{:>3}  {:<width$}  {:<8}  {:<24}  {}",
+            cluster.fed_id,
+            cluster.name,
+            cluster.state,
+            format!("{}:{}", cluster.control_host, cluster.control_port),
+            cluster.features.join(",")
+        );
+        if cluster.state == FederationState::Active {
+            println!("{}", row);
         } else {
-            let has_any_preempt = jobs_on_node
-                .iter()
-                .any(|job_id| preemptable_jobs.contains(job_id));
-
-            if has_any_preempt {
-                partially_preempt.insert(*node_id);
-            }
+            println!("{}", theme.paint(&row, theme.palette.warning));
         }
     }
+}
 
-    // having both lists, now we go through SlurmNodes.nodes, check ids, and convert the base
-    // node_state, taking into account compound states as well
-    //
-    // for nodes in the all_preempt vector, we want to turn allocated and mixed nodes to idle, and
-    // compound allocated/mixed to idle
-    //
-    // for nodes in the partially_preempt list, we want to turn allocated into mixed
-    // we leave mixed be, because if the jobs running on it were all preempt, the node would be in
-    // the other category
-
-    let mut preemptable_nodes: Vec<usize> = Vec::new();
-
-    for node in slurm_nodes.nodes.iter_mut() {
-        if all_preempt.contains(&node.id) {
-            match &node.state {
-                NodeState::Allocated | NodeState::Mixed => {
-                    preemptable_nodes.push(node.id);
-                    node.state = NodeState::Idle
-                }
-                NodeState::Compound { base, flags } => match **base {
-                    NodeState::Allocated | NodeState::Mixed => {
-                        preemptable_nodes.push(node.id);
-                        node.state = NodeState::Compound {
-                            base: Box::new(NodeState::Idle),
-                            flags: flags.to_vec(),
-                        }
-                    }
-                    _ => (),
-                },
-                _ => (),
+/// Warns that the feature filters matched no nodes, suggesting known features close to any
+/// names in them that no node has
+fn warn_unmatched_features(nodes: &SlurmNodes, filters: &[String]) {
+    let known_features = gather_all_features(nodes);
+    let mut suggestions: Vec<&str> = Vec::new();
+    for filter in filters {
+        for name in FeatureExpr::from_filter(filter).feature_names() {
+            if known_features.contains(name) {
+                continue;
             }
-        } else if partially_preempt.contains(&node.id) {
-            match &node.state {
-                NodeState::Allocated => {
-                    preemptable_nodes.push(node.id);
-                    node.state = NodeState::Mixed
-                }
-                NodeState::Compound { base, flags } => {
-                    if **base == NodeState::Allocated {
-                        preemptable_nodes.push(node.id);
-                        node.state = NodeState::Compound {
-                            base: Box::new(NodeState::Mixed),
-                            flags: flags.to_vec(),
-                        }
-                    }
+            for suggestion in suggest_features(name, &known_features, 3) {
+                if !suggestions.contains(&suggestion) {
+                    suggestions.push(suggestion);
                 }
-                _ => (),
             }
         }
     }
 
-    PreemptNodes(preemptable_nodes)
+    eprintln!("Warning: no nodes match {}", filters.join(", "));
+    if !suggestions.is_empty() {
+        eprintln!("Did you mean: {}", suggestions.join(", "));
+    }
 }
 
-const HELP: &str = "Report the state of nodes in a Slurm cluster, grouped by feature (tree view, the default) or state (-d, detailed view). Only CPU nodes are shown by default in the tree view; use -g to show only GPU nodes or -a to see all. The graphical availability bars display absolute node counts.";
+const HELP: &str = "Report the state of nodes in a Slurm cluster, grouped by feature (tree view, the default) or state (-d, detailed view). Only CPU nodes are shown by default in the tree view; use -g to show only GPU nodes or -a to see all. The graphical availability bars display absolute node counts, while the Used column shows the percentage of each branch's cores (or GPUs) that are allocated.";
 
 #[derive(Parser, Debug)]
 #[command(
     version,
+    about,
     after_help = HELP,
     after_long_help = format!("{}\n\n{}\n\n{}", HELP, fi_slurm::AUTHOR_HELP,
 "See also https://grafana.flatironinstitute.org for cluster monitoring dashboards."),
@@ -376,10 +707,73 @@ struct Args {
 
     #[arg(long)]
     #[arg(
-        help = "Sort the tree report at each level in alphabetical order instead of by total node count."
+        help = "Counts only the nodes in partitions you could run jobs in, per your Slurm associations"
+    )]
+    #[arg(
+        long_help = "Counts only the nodes in partitions you could run jobs in: partitions that are up, don't require a reservation, and let in the account of one of your associations in the Slurm database, where associations tied to a partition only count for it. Idle nodes of maintenance or restricted partitions are otherwise shown as available to everyone. The QOS a partition allows are not checked."
+    )]
+    accessible: bool,
+
+    #[arg(long, value_name = "ACCOUNT")]
+    #[arg(
+        help = "Shows only the nodes running jobs of the given account. Accepts multiple uses, and combines with --user."
+    )]
+    account: Vec<String>,
+
+    #[arg(long)]
+    #[arg(
+        help = "Sort the tree report at each level in alphabetical order instead of by total node count. Same as --sort name."
     )]
     alphabetical: bool,
 
+    #[arg(long, conflicts_with = "alphabetical", value_name = "ORDER")]
+    #[arg(
+        help = "Sort features in the tree report, and subgroups in the detailed report, by the given order. Defaults to total nodes in the tree report and name in the detailed report."
+    )]
+    sort: Option<SortOrder>,
+
+    #[arg(short = 's', long, alias = "summary")]
+    #[arg(help = "Prints a few lines of cluster-wide availability, for login banners")]
+    #[arg(
+        long_help = "Prints a compact overview instead of the tree report: the nodes, cores and GPUs of the cluster and how many are idle, then one line for each architecture, the largest first. A node's architecture is its first feature other than cpu, gpu and the hidden features. Idle nodes are those free for whole-node jobs, while idle cores and GPUs also count those free on partly allocated nodes. Combines with the feature filters. Replaces the old summary report, and --summary is kept as an alias."
+    )]
+    brief: bool,
+
+    #[arg(long)]
+    #[arg(help = "Shows the total, used and free space of each burst buffer pool")]
+    #[arg(
+        long_help = "Shows the total, used and free space of each pool of each burst buffer plugin, such as datawarp or lua, instead of the nodes, along with the number of buffers allocated to jobs. Pools with less than a tenth of their space free are highlighted. Space that is neither used nor free is set aside by the plugin, such as for buffers being torn down."
+    )]
+    burst_buffers: bool,
+
+    #[arg(long)]
+    #[arg(
+        help = "Keeps each cluster state loaded, and shows the last one kept, marked as stale, if Slurm can't be reached"
+    )]
+    #[arg(
+        long_help = "Keeps a copy of each cluster state loaded in $XDG_CACHE_HOME/fi-nodes/cluster-state-NAME.json (or ~/.cache/fi-nodes/cluster-state-NAME.json), where NAME is the cluster given with --cluster or else the ClusterName of slurm.conf, and if the controller can't be reached, as during a failover, shows the reports from the last copy kept under a STALE banner giving its age, instead of failing. Other errors from Slurm still fail. Reports that need more than the nodes, jobs and partitions, such as --licenses or --topology, still need the controller."
+    )]
+    cached: bool,
+
+    #[arg(short = 'M', long, value_name = "NAME")]
+    #[arg(
+        help = "Reports on the named cluster instead of the local one, like -M in the Slurm commands"
+    )]
+    #[arg(
+        long_help = "Reports on the named cluster instead of the local one, like -M in the Slurm commands, such as a sibling cluster of a federation listed by --federation. The cluster is looked up in the Slurm database, which must be reachable, and its controller must accept connections from this host."
+    )]
+    cluster: Option<String>,
+
+    #[arg(long, value_name = "SHELL", hide = true)]
+    #[arg(
+        help = "Prints a completion script for the given shell, including the cluster's features when Slurm is reachable"
+    )]
+    completions: Option<Shell>,
+
+    #[arg(long, hide = true)]
+    #[arg(help = "Prints a man page in roff format, generated from these options")]
+    generate_man: bool,
+
     #[arg(long, hide = true)]
     #[arg(help = "Prints debug-level logging steps to terminal")]
     debug: bool,
@@ -391,6 +785,19 @@ struct Args {
     )]
     detailed: bool,
 
+    #[arg(long, value_name = "FILE", conflicts_with = "snapshot")]
+    #[arg(
+        help = "Compares the current cluster state against a snapshot saved with --snapshot, showing node state changes, newly drained nodes and availability changes per feature"
+    )]
+    diff: Option<String>,
+
+    #[arg(long, value_name = "ADDR", num_args = 0..=1)]
+    #[arg(default_missing_value = exporter::DEFAULT_EXPORTER_ADDR)]
+    #[arg(
+        help = "Runs an HTTP server exposing node, core and GPU counts per feature and per state in the Prometheus text format at /metrics (default address 0.0.0.0:9750)"
+    )]
+    exporter: Option<String>,
+
     #[arg(short, long)]
     #[arg(help = "filter features only by exact match rather than substrings ")]
     #[arg(default_value_t = true, hide = true)]
@@ -398,50 +805,198 @@ struct Args {
     exact: bool,
 
     #[arg(
-        help = "Node features to display, such as \"icelake\" or \"genoa\". Accepts multiple features.\nFor GPUs, use -g instead of \"gpu\"."
+        help = "Node features to display, such as \"icelake\" or \"genoa\". Accepts multiple features,\nor expressions in Slurm's --constraint syntax such as \"icelake&!ib\" or \"(genoa|rome)\".\nFor GPUs, use -g instead of \"gpu\"."
     )]
     feature: Vec<String>,
 
+    #[arg(long, value_name = "TEMPLATE")]
+    #[arg(
+        help = "Prints one line per feature of the tree report using a template, e.g. '{feature}\\t{idle_nodes}/{total_nodes}\\t{idle_cpus}'"
+    )]
+    #[arg(
+        long_help = "Prints one line per feature of the tree report using a template instead of the tree view, e.g. '{feature}\\t{idle_nodes}/{total_nodes}\\t{idle_cpus}'. Available fields: feature, path, depth, total_nodes, idle_nodes, alloc_nodes, preempt_nodes, total_cpus, idle_cpus, alloc_cpus, preempt_cpus, preempt_gpus, total_memory_mb, alloc_memory_mb, soon_cpus, reserved_nodes, load, wasted_nodes, oversubscribed_nodes, nodes. The CPU fields count GPUs in the GPU report, while preempt_gpus always counts GPUs, and the memory fields count megabytes in either. soon_cpus needs --forecast, and reserved_nodes --reserved-within. \\t and \\n are interpreted as tab and newline, and {{ and }} print literal braces. Lines follow the tree order, starting with the top level."
+    )]
+    format: Option<String>,
+
+    #[arg(long, value_name = "WINDOW", value_parser = parse_window)]
+    #[arg(
+        help = "Adds a column to the tree report with the cores (or GPUs) of running jobs that reach their time limit within the window, such as 30m, 2h or 1d"
+    )]
+    #[arg(
+        long_help = "Adds a \"Soon Available\" column to the tree report with the cores (or GPUs, in the GPU report) held by running jobs that reach their time limit within the window, such as 30m, 2h or 1d. Jobs often end before their limit, so this is a lower bound on what will free up. With --preempt, jobs that are already preemptable are left out, as they are counted as idle."
+    )]
+    forecast: Option<Duration>,
+
+    #[arg(long)]
+    #[arg(help = "Lists the clusters of the federation this cluster belongs to")]
+    #[arg(
+        long_help = "Lists the clusters of the Slurm federation this cluster (or the one given to --cluster) belongs to, with each one's id, federation state, controller and features, instead of the nodes. Draining clusters finish their jobs but take no new ones. Reports for a sibling cluster can then be shown with --cluster."
+    )]
+    federation: bool,
+
     #[arg(short, long)]
     #[arg(
         help = "Shows only gpu nodes in the tree view (default if all selected nodes have GPUs)"
     )]
     gpu: bool,
 
+    #[arg(long)]
+    #[arg(help = "Shows the total, used, reserved and free count of each Slurm license")]
+    #[arg(
+        long_help = "Shows the total, used, reserved and free count of each Slurm license instead of the nodes, along with the pending jobs held back for lack of licenses that ask for each, and how many licenses they ask for in all. Licenses with waiting jobs are highlighted. Remote licenses, tracked in the Slurm database, are marked as such."
+    )]
+    licenses: bool,
+
+    #[arg(long)]
+    #[arg(
+        help = "Adds a column to the tree report comparing the load of the nodes to the cores allocated on them"
+    )]
+    #[arg(
+        long_help = "Adds a \"Load/Alloc\" column to the tree report with the summed load average of each branch's nodes against the cores allocated on them, in the CPU report as well as the GPU one. Nodes whose load is under half their allocated cores, wasting cores, are counted after a ↓, and those whose load is over one and a half times their allocated cores plus one, oversubscribing them, after a ↑. Down nodes are left out, as their load is out of date."
+    )]
+    load: bool,
+
     #[arg(short, long)]
     #[arg(
         help = "Include preempt information in the output.\n\"123(-45)\" means 123 nodes are idle or preemptable, while 45 are preemptable."
     )]
     #[arg(
-        long_help = "Reclassifies the base state of nodes according to the preemptability of the jobs running on them: an allocated node with some jobs which are preemptable will be reclassified as Mixed, while an Allocated or Mixed node where all jobs are preemptable will be reclassified as Idle."
+        long_help = "Reclassifies the base state of nodes according to the preemptability of the jobs running on them: an allocated node with some jobs which are preemptable will be reclassified as Mixed, while an Allocated or Mixed node where all jobs are preemptable will be reclassified as Idle. The tree report also shows how long until the next job on each branch that can't be preempted yet becomes preemptable."
     )]
     preempt: bool,
 
+    #[arg(short, long)]
+    #[arg(
+        help = "Summarizes the pending queue: jobs, cores and GPUs requested per feature constraint, account and QOS, with Slurm's estimated start times"
+    )]
+    #[arg(
+        long_help = "Summarizes the pending queue instead of the nodes, showing the jobs, cores and GPUs requested per feature constraint, per account and per QOS, and the range of start times Slurm's scheduler has estimated for them. Groups without any estimated start are highlighted. Combines with --user and --account to show only their jobs. Pending array tasks count as separate jobs."
+    )]
+    queue: bool,
+
+    #[arg(long, value_name = "WINDOW", value_parser = parse_window)]
+    #[arg(
+        help = "Adds a column to the tree report with the nodes held by reservations now or within the window, such as 2h or 7d"
+    )]
+    #[arg(
+        long_help = "Adds a \"Reserved\" column to the tree report with the nodes of each branch held by a Slurm reservation at some time between now and the end of the window, such as 2h or 7d, taken as the walltime of the job to run: an idle node with a maintenance reservation starting within it can't run the job to the end. Reservations the job could run in are counted too. With --respect-reservations, these nodes are also left out of the available nodes, cores and GPUs."
+    )]
+    reserved_within: Option<Duration>,
+
+    #[arg(long, requires = "reserved_within")]
+    #[arg(help = "Counts the nodes reserved within the --reserved-within window as unavailable")]
+    respect_reservations: bool,
+
     #[arg(short, long)]
     #[arg(help = "Shows node names")]
     names: bool,
 
+    #[arg(short, long, value_enum, default_value_t, value_name = "FORMAT")]
+    #[arg(conflicts_with_all = ["brief", "detailed", "format", "jsonl", "motd", "when"])]
+    #[arg(help = "How to print the report: a table, or json, csv or jsonl for other programs")]
+    #[arg(
+        long_help = "How to print the report: the tree view or other tables, or json, csv or jsonl for other programs. The structured formats list the nodes that pass the filters, one record each, with the same fields as --jsonl nodes; in CSV, the features, partitions and job ids of a node are each joined by commas into one field. jsonl is the same as --jsonl nodes."
+    )]
+    output: OutputFormat,
+
+    #[arg(long, value_name = "RECORD", num_args = 0..=1, default_missing_value = "nodes")]
+    #[arg(conflicts_with_all = ["brief", "detailed", "format", "motd", "when"])]
+    #[arg(help = "Streams the nodes (default) or the tree report as JSON, one object per line")]
+    #[arg(
+        long_help = "Streams JSON Lines, one object per line, for pipelines on clusters too large to handle as one document. With 'nodes', the default, each line is a node that passes the filters, with its state, features, partitions, total, allocated and idle CPUs and GPUs, memory in MB and the ids of the jobs running on it. With 'tree', each line is a line of the tree report, in the order of the tree view, with its path of features, its depth and its node and CPU (or GPU, with -g) counts. Lines are written as they are built, and the output ends quietly when the reader stops reading."
+    )]
+    jsonl: Option<JsonlRecord>,
+
+    #[arg(long, conflicts_with = "brief")]
+    #[arg(help = "Prints availability bars of a fixed width, for /etc/motd")]
+    #[arg(
+        long_help = "Prints a banner for /etc/motd or other login messages, as from a cron job: the idle share of the cluster's CPU cores and GPUs, and of its three largest architectures, as bars. GPU architectures show their GPUs and the others their cores, and architectures are found as for --brief. Every line is the same width, whatever the size of the cluster, with counts abbreviated as 12.3k or 1.2M. Colors follow --theme, and --no-color or NO_COLOR leave the banner plain text. The title names the cluster from site.conf."
+    )]
+    motd: bool,
+
     #[arg(long)]
     #[arg(help = "Disable colors in output")]
     no_color: bool,
 
+    #[arg(long)]
+    #[arg(
+        help = "Prints straight to the terminal instead of through a pager ($PAGER, or less by default)"
+    )]
+    no_pager: bool,
+
+    #[arg(long)]
+    #[arg(help = "Shows the scheduler's statistics, as sdiag does, flagging signs of stress")]
+    #[arg(
+        long_help = "Shows the statistics slurmctld keeps on its scheduling, as sdiag does, instead of the nodes: the controller's threads and queues, the jobs submitted, started and ended, the cycle times and depths of the main and backfill schedulers, and the RPC types and users that took the most time. Slow cycles and a backlog of messages for slurmdbd are highlighted. The counters cover the time since they were last reset, at midnight or by sdiag --reset. RPC types are shown by number, as defined in Slurm's slurm_protocol_defs.h."
+    )]
+    sched_stats: bool,
+
+    #[arg(long, value_name = "FEATURE")]
+    #[arg(
+        help = "Shows the given feature in the tree report even if it is hidden by the site configuration. Accepts multiple uses."
+    )]
+    #[arg(
+        long_help = "Shows the given feature in the tree report even if it is hidden by the site configuration. Accepts multiple uses. The hidden features are read from the FI_HIDDEN_FEATURES environment variable (comma-separated) or from hidden_features.conf next to the binary. Use -v to show all hidden features."
+    )]
+    show_feature: Vec<String>,
+
+    #[arg(long, value_name = "FILE")]
+    #[arg(
+        help = "Saves the state of all nodes, jobs and partitions to a file, for later use with --diff or --state-file"
+    )]
+    snapshot: Option<String>,
+
     #[cfg(feature = "tui")]
     #[arg(short, long)]
     #[arg(
         help = "[Experimental] Displays time-series cluster usage in an interactive Terminal User Interface (TUI)."
     )]
     #[arg(
-        long_help = "[Experimental] The TUI shows time-series cluster usage data from Prometheus.The default display is the last 30 days in 1 day increments. The range and increment of data can be customized by selecting 'Custom Query' in setup. Note that the loading times from Prometheus are directly related to the number of requested increments. Requesting the last month's data in 1 minute increments will take a very, very long time."
+        long_help = "[Experimental] The TUI shows time-series cluster usage data from Prometheus. The default display is the last 30 days in 1 day increments, and the range and increment can be customized by selecting 'Custom Query' in setup. Loading times grow with the number of increments requested, so the last month in 1 minute increments takes a very long time.\n\nThe Live view (0) shows the current tree report from Slurm, refreshed every 30 seconds, and is opened instead of the charts if Prometheus can't be reached. Views (4) and (5) chart the GPUs used by each account and the cores in use on the nodes with each feature, the latter from the metrics of fi-nodes --exporter. A status line under the charts keeps the idle nodes, cores and GPUs of the cluster in view.\n\nPress (?) at any point to list the keys, which sort, search, pin, compare, zoom, pan and export the charts.\n\nThe last custom query and the view open on quitting are remembered in $XDG_STATE_HOME/fi-nodes/tui.json, or ~/.local/state/fi-nodes/tui.json.\n\nThe charts are drawn in the colors of --theme; with --no-color or NO_COLOR, highlights are bold or reversed instead, and the accounts stacked in the cluster total are told apart by their fill."
     )]
     term: bool,
 
+    #[arg(long, value_name = "FILE")]
+    #[arg(conflicts_with_all = [
+        "accessible", "burst_buffers", "cached", "cluster", "exporter", "federation", "licenses",
+        "reserved_within", "sched_stats", "topology",
+    ])]
+    #[arg(help = "Reports on a cluster state saved to a file instead of the live cluster")]
+    #[arg(
+        long_help = "Reports on a cluster state saved to a JSON file instead of loading it from Slurm, without contacting the controller, for tests and for looking back at an earlier state. Files saved with --snapshot, and the files --cached keeps, $XDG_CACHE_HOME/fi-nodes/cluster-state-NAME.json, are such files. Can't be combined with the options that need more than the nodes, jobs and partitions, such as --licenses, --reserved-within, --topology or --sched-stats."
+    )]
+    state_file: Option<String>,
+
+    #[arg(long, value_name = "NAME|FILE")]
+    #[arg(help = "Colors to use: a preset (default, colorblind) or a palette file")]
+    #[arg(
+        long_help = "Colors to use: a preset (default, colorblind) or the path of a palette file. Palette files hold 'role = color' lines, where the roles are idle, mixed, allocated, down, error, other, nodes, cpus, gpus, low, medium, high, gain, loss and warning, and the colors are names such as 'red' or 'bright blue', or #rrggbb hex codes. A 'preset = colorblind' line starts from a preset. Without this flag, the theme is taken from the FI_THEME environment variable or from theme.conf next to the binary. NO_COLOR or --no-color turn colors off."
+    )]
+    theme: Option<String>,
+
+    #[arg(long)]
+    #[arg(help = "Groups the tree report by network switch instead of by feature")]
+    #[arg(
+        long_help = "Groups the tree report by the network switches of Slurm's topology/tree plugin instead of by feature, from the top-level switches down to the leaf switches the nodes connect to, showing the availability under each switch for jobs that need topology-contiguous nodes. Nodes missing from topology.conf are listed under \"(no switch)\". The feature filters still select the nodes shown. Fails if the cluster doesn't use topology/tree."
+    )]
+    topology: bool,
+
+    #[arg(long, value_name = "USER")]
+    #[arg(
+        help = "Shows only the nodes running jobs of the given user. Accepts multiple uses, and combines with --account."
+    )]
+    user: Vec<String>,
+
+    #[arg(long, value_name = "REQUEST")]
+    #[arg(help = "Estimates when a job could start, e.g. 'cpus=64,constraint=genoa,time=4h'")]
+    #[arg(
+        long_help = "Estimates when a hypothetical job could start, from the resources free now and the time limits of the running jobs, as a quick alternative to squeue --start. The request is a comma-separated list of cpus, gpus, nodes, constraint and time, e.g. 'cpus=64,constraint=genoa,time=4h' or 'gpus=8,nodes=2,constraint=h100'. With nodes, the resources are split evenly across that many nodes; otherwise they are taken wherever they are free. The constraint uses Slurm's --constraint syntax. A node held by a reservation at some time before the job would reach its time limit, or when it would start without one, is left out until the reservation ends, even if the job could run in it; with --state-file, which holds no reservations, none are taken into account. With --preempt, the resources of jobs that can already be preempted count as free. Combines with the feature filters, --user, --account and --accessible. Pending jobs ahead of the request are not accounted for, and running jobs often end before their time limit, so the estimate is only a rough guide."
+    )]
+    when: Option<String>,
+
     #[arg(short, long)]
     #[arg(
         help = "In the tree report, shows hidden node features. In the detailed view, breaks out GPU types."
     )]
     verbose: bool,
-
-    #[arg(short, long, hide = true)] // summary report is deprecated in favor of tree view
-    #[arg(help = "Prints the top-level summary report for each feature type")]
-    summary: bool,
 }
@@ -0,0 +1,154 @@
+use crate::brief_report::{Availability, BriefReport};
+use chrono::{DateTime, Local, Utc};
+use colored::Color;
+use fi_slurm::theme::Theme;
+
+/// The architectures given a bar of their own, the largest first
+const TOP_ARCHITECTURES: usize = 3;
+
+// the columns of a line: the label, the bar, the idle and total counts and the idle share
+const LABEL_WIDTH: usize = 10;
+const BAR_WIDTH: usize = 30;
+const COUNT_WIDTH: usize = 11;
+const PERCENT_WIDTH: usize = 4;
+
+/// The width of every line of the banner, whatever the size of the cluster
+pub const MOTD_WIDTH: usize =
+    LABEL_WIDTH + 1 + BAR_WIDTH + 1 + COUNT_WIDTH + " idle ".len() + PERCENT_WIDTH;
+
+/// A count in at most 5 characters, such as "9876", "12.3k", "123k" or "1.2M"
+fn format_compact(count: u32) -> String {
+    match count {
+        0..10_000 => count.to_string(),
+        // stopping short of the counts that would round up to "100.0k"
+        10_000..99_950 => format!("{:.1}k", count as f64 / 1_000.0),
+        99_950..1_000_000 => format!("{}k", count / 1_000),
+        1_000_000..9_950_000 => format!("{:.1}M", count as f64 / 1_000_000.0),
+        _ => format!("{}M", count / 1_000_000),
+    }
+}
+
+/// Cuts or pads text to exactly `width` characters
+fn fit(text: &str, width: usize) -> String {
+    format!("{:<width$.width$}", text, width = width)
+}
+
+/// One line of the banner: the label, a bar filled in proportion to the idle share, and the
+/// counts
+fn motd_line(label: &str, availability: &Availability, color: Color, theme: &Theme) -> String {
+    let filled = if availability.total == 0 {
+        0
+    } else {
+        (BAR_WIDTH as u64 * availability.idle as u64 / availability.total as u64) as usize
+    };
+    let counts = format!(
+        "{}/{}",
+        format_compact(availability.idle),
+        format_compact(availability.total)
+    );
+    format!(
+        "{} {}{} {:>count_w$} idle {:>percent_w$}",
+        fit(label, LABEL_WIDTH),
+        theme.paint(&"█".repeat(filled), color),
+        "░".repeat(BAR_WIDTH - filled),
+        counts,
+        format!("{}%", availability.idle_percent()),
+        count_w = COUNT_WIDTH,
+        percent_w = PERCENT_WIDTH
+    )
+}
+
+/// The lines of the login banner, each `MOTD_WIDTH` characters wide not counting colors: the
+/// idle cores and GPUs of the cluster, and those of its largest architectures
+pub fn render_motd(
+    report: &BriefReport,
+    cluster_name: Option<&str>,
+    taken_at: DateTime<Utc>,
+    theme: &Theme,
+) -> Vec<String> {
+    let title = format!(
+        "{} availability at {}",
+        cluster_name.unwrap_or("Cluster"),
+        taken_at.with_timezone(&Local).format("%Y-%m-%d %H:%M")
+    );
+    let mut lines = vec![fit(&title, MOTD_WIDTH)];
+
+    lines.push(motd_line(
+        "CPU cores",
+        &report.cluster.cpus,
+        theme.palette.cpus,
+        theme,
+    ));
+    if report.cluster.gpus.total > 0 {
+        lines.push(motd_line(
+            "GPUs",
+            &report.cluster.gpus,
+            theme.palette.gpus,
+            theme,
+        ));
+    }
+
+    // GPU architectures are shown by their GPUs, which are what their users wait for
+    for (name, line) in report.architectures.iter().take(TOP_ARCHITECTURES) {
+        let (availability, color) = if line.gpus.total > 0 {
+            (&line.gpus, theme.palette.gpus)
+        } else {
+            (&line.cpus, theme.palette.cpus)
+        };
+        lines.push(motd_line(name, availability, color, theme));
+    }
+    lines
+}
+
+/// Prints the login banner, for /etc/motd
+pub fn print_motd(
+    report: &BriefReport,
+    cluster_name: Option<&str>,
+    taken_at: DateTime<Utc>,
+    theme: &Theme,
+) {
+    for line in render_motd(report, cluster_name, taken_at, theme) {
+        println!("{}", line);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::brief_report::BriefLine;
+
+    #[test]
+    fn test_format_compact() {
+        assert_eq!(format_compact(9_876), "9876");
+        assert_eq!(format_compact(12_345), "12.3k");
+        assert_eq!(format_compact(123_456), "123k");
+        assert_eq!(format_compact(99_999), "99k");
+        assert_eq!(format_compact(1_234_567), "1.2M");
+        assert_eq!(format_compact(u32::MAX), "4294M");
+    }
+
+    #[test]
+    fn test_motd_width_is_fixed() {
+        let line = |total, idle| BriefLine {
+            nodes: Availability { total, idle },
+            cpus: Availability { total, idle },
+            gpus: Availability { total, idle },
+        };
+        for (total, idle) in [(0, 0), (7, 3), (4_000_000, 3_999_999)] {
+            let report = BriefReport {
+                cluster: line(total, idle),
+                architectures: vec![
+                    (
+                        "a-very-long-architecture-name".to_string(),
+                        line(total, idle),
+                    ),
+                    ("genoa".to_string(), line(total, 0)),
+                ],
+            };
+            let lines = render_motd(&report, Some("rusty"), Utc::now(), &Theme::plain());
+            for line in lines {
+                assert_eq!(line.chars().count(), MOTD_WIDTH, "{:?}", line);
+            }
+        }
+    }
+}
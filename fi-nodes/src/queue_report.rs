@@ -0,0 +1,193 @@
+use chrono::{DateTime, Local, Utc};
+use colored::*;
+use fi_slurm::jobs::{FilterMethod, Job, SlurmJobs};
+use fi_slurm::theme::Theme;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// The label for pending jobs that asked for no feature constraint
+const NO_CONSTRAINT: &str = "(none)";
+
+/// The demand from the pending jobs in one group of the queue report
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct QueueLine {
+    /// Pending jobs, counting each pending task of an array separately
+    pub jobs: u32,
+    pub cpus: u64,
+    pub gpus: u64,
+    /// The earliest start Slurm has estimated for any job in the group
+    pub earliest_start: Option<DateTime<Utc>>,
+    /// The latest start Slurm has estimated for any job in the group
+    pub latest_start: Option<DateTime<Utc>>,
+    /// Jobs the scheduler has not yet estimated a start time for
+    pub unestimated: u32,
+}
+
+impl QueueLine {
+    /// Adds `tasks` jobs, each asking for the given CPUs and GPUs, and their estimated start
+    fn add(&mut self, tasks: u32, cpus: u64, gpus: u64, start: Option<DateTime<Utc>>) {
+        self.jobs += tasks;
+        self.cpus += cpus * tasks as u64;
+        self.gpus += gpus * tasks as u64;
+        match start {
+            Some(start) => {
+                self.earliest_start = Some(self.earliest_start.map_or(start, |s| s.min(start)));
+                self.latest_start = Some(self.latest_start.map_or(start, |s| s.max(start)));
+            }
+            None => self.unestimated += tasks,
+        }
+    }
+
+    fn add_job(&mut self, job: &Job) {
+        self.add(
+            job.task_count(),
+            job.num_cpus as u64,
+            job.requested_gpus(),
+            job.estimated_start(),
+        );
+    }
+}
+
+/// The pending queue broken down by the feature constraint, account and QOS of its jobs
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct QueueReport {
+    pub by_constraint: BTreeMap<String, QueueLine>,
+    pub by_account: BTreeMap<String, QueueLine>,
+    pub by_qos: BTreeMap<String, QueueLine>,
+    pub total: QueueLine,
+}
+
+/// Sums the CPUs and GPUs requested by pending jobs per feature constraint, account and QOS
+///
+/// Only the jobs matching any of `owners` are counted, or all pending jobs if it is empty
+pub fn build_queue_report(jobs: &SlurmJobs, owners: &[FilterMethod]) -> QueueReport {
+    let mut report = QueueReport::default();
+    for job in jobs
+        .pending()
+        .filter(|job| owners.is_empty() || owners.iter().any(|owner| owner.matches(job)))
+    {
+        let constraint = if job.requested_features.is_empty() {
+            NO_CONSTRAINT.to_string()
+        } else {
+            job.requested_features.clone()
+        };
+        report
+            .by_constraint
+            .entry(constraint)
+            .or_default()
+            .add_job(job);
+        report
+            .by_account
+            .entry(job.account.clone())
+            .or_default()
+            .add_job(job);
+        report
+            .by_qos
+            .entry(job.qos.clone())
+            .or_default()
+            .add_job(job);
+        report.total.add_job(job);
+    }
+    report
+}
+
+fn format_start(start: Option<DateTime<Utc>>) -> String {
+    start
+        .map(|t| t.with_timezone(&Local).format("%m-%d %H:%M").to_string())
+        .unwrap_or_else(|| "-".to_string())
+}
+
+/// Prints one section of the queue report, with its groups ordered by requested CPUs
+fn print_section(
+    title: &str,
+    lines: &BTreeMap<String, QueueLine>,
+    total: &QueueLine,
+    theme: &Theme,
+) {
+    let mut sorted: Vec<(&String, &QueueLine)> = lines.iter().collect();
+    sorted.sort_by(|a, b| b.1.cpus.cmp(&a.1.cpus).then_with(|| a.0.cmp(b.0)));
+
+    let width = lines
+        .keys()
+        .map(|name| name.len())
+        .max()
+        .unwrap_or(0)
+        .max(title.len());
+
+    println!(
+        "{}",
+        format!(
+            "{:<width$}  {:>7}  {:>8}  {:>6}  {:>5}  {:>11}  {:>11}",
+            title, "JOBS", "CORES", "GPUS", "%CORE", "EARLIEST", "LATEST"
+        )
+        .bold()
+    );
+    for (name, line) in sorted {
+        let share = if total.cpus == 0 {
+            0.0
+        } else {
+            line.cpus as f64 / total.cpus as f64 * 100.0
+        };
+        let row = format!(
+            "{:<width$}  {:>7}  {:>8}  {:>6}  {:>4.1}%  {:>11}  {:>11}",
+            name,
+            line.jobs,
+            line.cpus,
+            line.gpus,
+            share,
+            format_start(line.earliest_start),
+            format_start(line.latest_start),
+        );
+        // flag the groups that Slurm cannot yet place at all
+        if line.earliest_start.is_none() {
+            println!("{}", theme.paint(&row, theme.palette.warning));
+        } else {
+            println!("{}", row);
+        }
+    }
+}
+
+/// Prints the queue report, one section each for feature constraints, accounts and QOS
+pub fn print_queue_report(report: &QueueReport, theme: &Theme) {
+    let total = &report.total;
+    if total.jobs == 0 {
+        println!("No pending jobs");
+        return;
+    }
+
+    print_section("CONSTRAINT", &report.by_constraint, total, theme);
+    println!();
+    print_section("ACCOUNT", &report.by_account, total, theme);
+    println!();
+    print_section("QOS", &report.by_qos, total, theme);
+    println!();
+    println!(
+        "{} {} pending jobs requesting {} cores and {} GPUs; {} without an estimated start",
+        "Total:".bold(),
+        total.jobs,
+        total.cpus,
+        total.gpus,
+        total.unestimated
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_queue_line_tracks_demand_and_start_range() {
+        let at = |secs| DateTime::from_timestamp(secs, 0);
+        let mut line = QueueLine::default();
+        line.add(1, 16, 0, at(2_000));
+        line.add(4, 8, 1, None);
+        line.add(1, 64, 4, at(1_000));
+
+        assert_eq!(line.jobs, 6);
+        assert_eq!(line.cpus, 16 + 32 + 64);
+        assert_eq!(line.gpus, 8);
+        assert_eq!(line.earliest_start, at(1_000));
+        assert_eq!(line.latest_start, at(2_000));
+        assert_eq!(line.unestimated, 4);
+    }
+}
@@ -1,7 +1,16 @@
+use crate::SortOrder;
 use colored::*;
+use fi_slurm::availability::{
+    NodeAvailability, allocated_cpus, derive_node_state, is_node_available, is_node_mixed,
+};
 use fi_slurm::jobs::SlurmJobs;
-use fi_slurm::nodes::{Node, NodeState};
+use fi_slurm::nodes::{GpuInfo, Node, NodeState};
+use fi_slurm::states::NodeStateFlags;
+use fi_slurm::theme::Theme;
 use fi_slurm::utils::count_blocks;
+use rayon::prelude::*;
+use serde::Serialize;
+use std::cmp::Reverse;
 use std::collections::HashMap;
 
 /// Represents the aggregated statistics for a single line in the final report
@@ -10,7 +19,7 @@ use std::collections::HashMap;
 /// and the indented subgroup lines (e.g., "  genoa  8...")
 ///
 /// `#[derive(Default)]` allows us to easily create a new, zeroed-out instance
-#[derive(Default, Debug, Clone)]
+#[derive(Default, Debug, Clone, Serialize)]
 pub struct ReportLine {
     pub node_count: u32,
     pub total_cpus: u32,
@@ -19,13 +28,65 @@ pub struct ReportLine {
     pub total_gpus: u64,
     pub alloc_gpus: u64,
     pub idle_gpus: u64,
+    /// The MIG instances, which the GPU counts leave out
+    pub mig_instances: u64,
+    /// The shards, carved out of the whole GPUs counted in `total_gpus`
+    pub shards: u64,
+    pub gpu_equivalents: f64,
+    pub alloc_gpu_equivalents: f64,
     pub node_names: Vec<String>,
 }
 
+impl ReportLine {
+    /// Adds the counts of another line, as when merging partial reports
+    fn merge(&mut self, other: ReportLine) {
+        self.node_count += other.node_count;
+        self.total_cpus += other.total_cpus;
+        self.alloc_cpus += other.alloc_cpus;
+        self.idle_cpus += other.idle_cpus;
+        self.total_gpus += other.total_gpus;
+        self.alloc_gpus += other.alloc_gpus;
+        self.idle_gpus += other.idle_gpus;
+        self.add_gpu_shares(&other);
+        self.node_names.extend(other.node_names);
+    }
+
+    fn add_gpu_shares(&mut self, other: &ReportLine) {
+        self.mig_instances += other.mig_instances;
+        self.shards += other.shards;
+        self.gpu_equivalents += other.gpu_equivalents;
+        self.alloc_gpu_equivalents += other.alloc_gpu_equivalents;
+    }
+
+    fn add_gpu_info(&mut self, gpu: &GpuInfo) {
+        self.total_gpus += gpu.total_gpus;
+        self.alloc_gpus += gpu.allocated_gpus;
+        self.mig_instances += gpu.mig_instances.configured;
+        self.shards += gpu.shards.configured;
+        self.gpu_equivalents += gpu.equivalents.configured;
+        self.alloc_gpu_equivalents += gpu.equivalents.allocated;
+    }
+
+    /// Whether any GPUs are split into MIG instances or shards, so that the GPU counts alone
+    /// don't tell how much of them is used
+    fn has_gpu_shares(&self) -> bool {
+        (self.mig_instances > 0 || self.shards > 0) && self.gpu_equivalents > 0.0
+    }
+
+    /// Fraction of the CPUs that are allocated, from 0 to 1
+    pub fn utilization(&self) -> f64 {
+        if self.total_cpus == 0 {
+            0.0
+        } else {
+            self.alloc_cpus as f64 / self.total_cpus as f64
+        }
+    }
+}
+
 /// Represents a top-level group in the report, categorized by a `NodeState`
 ///
 /// For example, this would hold all the data for the "IDLE" or "MIXED" sections
-#[derive(Default, Debug, Clone)]
+#[derive(Default, Debug, Clone, Serialize)]
 pub struct ReportGroup {
     /// The aggregated statistics for the main summary line of this group
     pub summary: ReportLine,
@@ -34,6 +95,16 @@ pub struct ReportGroup {
     pub subgroups: HashMap<String, ReportLine>,
 }
 
+impl ReportGroup {
+    /// Adds the counts of another group, as when merging partial reports
+    fn merge(&mut self, other: ReportGroup) {
+        self.summary.merge(other.summary);
+        for (name, line) in other.subgroups {
+            self.subgroups.entry(name).or_default().merge(line);
+        }
+    }
+}
+
 /// The final data structure that holds the entire report, organized by `NodeState`
 ///
 /// This will be a map from a `NodeState` enum to its corresponding `ReportGroup`
@@ -41,6 +112,9 @@ pub struct ReportGroup {
 /// iterate through all the nodes
 pub type ReportData = HashMap<NodeState, ReportGroup>;
 
+// nodes handled per parallel task, large enough that merging partial reports stays cheap
+const NODES_PER_TASK: usize = 64;
+
 /// Builds the final report by aggregating data from all nodes
 ///
 /// This function iterates through every node, categorizes it by its state,
@@ -63,133 +137,114 @@ pub fn build_report(
     allocated: bool,
     verbose: bool,
 ) -> ReportData {
-    let mut report_data = ReportData::new();
-
-    // creates a new Vec<u32> where each element corresponds to a node in the input `n` slice
+    // creates a new Vec<u32> where each element corresponds to a node in the input `n` slice,
+    // from the node's allocated TRES or else the jobs on it
     let alloc_cpus_per_node: Vec<u32> = nodes
-        .iter()
-        .map(|&node| {
-            // for each node, look up its job IDs, returning an option
-            node_to_job_map
-                .get(&node.id)
-                // if the Option is Some(job_ids), we map over it to perform the calculation
-                .map(|job_ids| {
-                    job_ids
-                        .iter()
-                        // for each job_id, look up the job details. filter_map unwraps the Some results
-                        .filter_map(|job_id| jobs.jobs.get(job_id))
-                        // for each valid job, calculate the CPUs allocated to this specific node
-                        .map(|job| {
-                            if job.num_nodes > 0 {
-                                job.num_cpus / job.num_nodes
-                            } else {
-                                job.num_cpus // should not happen, but handles malformed job data
-                            }
-                        })
-                        .sum() // sum the CPUs for all jobs on this node
-                })
-                // if the initial .get() returned None (no jobs on the node), default to 0
-                .unwrap_or(0)
-        })
+        .par_iter()
+        .map(|&node| allocated_cpus(node, jobs, node_to_job_map))
         .collect(); // collect all the results into our vector
 
-    for (node, &alloc_cpus_for_node) in nodes.iter().zip(alloc_cpus_per_node.iter()) {
-        // slurm does not mark nodes as mixed by default, so we have to do it
-        let derived_state = if alloc_cpus_for_node > 0 && alloc_cpus_for_node < node.cpus as u32 {
-            match &node.state {
-                NodeState::Compound { flags, .. } => NodeState::Compound {
-                    base: Box::new(NodeState::Mixed),
-                    flags: flags.to_vec(),
-                },
-                _ => NodeState::Mixed,
+    // aggregate the nodes into partial reports in parallel, then merge them
+    nodes
+        .par_iter()
+        .zip(alloc_cpus_per_node.par_iter())
+        .with_min_len(NODES_PER_TASK)
+        .fold(
+            ReportData::new,
+            |mut report_data, (node, &alloc_cpus_for_node)| {
+                add_node_to_report(
+                    &mut report_data,
+                    node,
+                    alloc_cpus_for_node,
+                    show_node_names,
+                    allocated,
+                    verbose,
+                );
+                report_data
+            },
+        )
+        .reduce(ReportData::new, |mut report_data, other| {
+            for (state, group) in other {
+                report_data.entry(state).or_default().merge(group);
             }
-        } else {
-            // otherwise, we trust the state reported by Slurm
-            node.state.clone()
-        };
+            report_data
+        })
+}
 
-        // get the report group for the node's derived state
-        let group = report_data.entry(derived_state.clone()).or_default();
+/// Adds the stats of a single node to the group for its state, and to its GPU or feature
+/// subgroup
+fn add_node_to_report(
+    report_data: &mut ReportData,
+    node: &Node,
+    alloc_cpus_for_node: u32,
+    show_node_names: bool,
+    allocated: bool,
+    verbose: bool,
+) {
+    // slurm does not mark nodes as mixed by default, so we have to do it
+    let derived_state = derive_node_state(node, alloc_cpus_for_node);
+
+    // get the report group for the node's derived state
+    let group = report_data.entry(derived_state.clone()).or_default();
+
+    // update the main summary line for the group
+    group.summary.node_count += 1;
+    group.summary.total_cpus += node.cpus as u32;
+    group.summary.alloc_cpus += alloc_cpus_for_node;
+    if show_node_names {
+        group.summary.node_names.push(node.name.clone());
+    }
+    if let Some(gpu) = &node.gpu_info {
+        group.summary.add_gpu_info(gpu);
+    }
 
-        // update the main summary line for the group
-        group.summary.node_count += 1;
-        group.summary.total_cpus += node.cpus as u32;
-        group.summary.alloc_cpus += alloc_cpus_for_node;
-        if show_node_names {
-            group.summary.node_names.push(node.name.clone());
-        }
-        if let Some(gpu) = &node.gpu_info {
-            group.summary.total_gpus += gpu.total_gpus;
-            group.summary.alloc_gpus += gpu.allocated_gpus;
-        }
+    // determine this node's contribution to idle resources, the same way as the tree report
+    let (idle_cpus_for_node, idle_gpus_for_node) = if !allocated {
+        let availability = NodeAvailability::of_node(node, alloc_cpus_for_node);
+        (availability.idle_cpus, availability.idle_gpus as u64)
+    } else {
+        // if we're in allocated mode, idle counts are not needed
+        (0, 0)
+    };
 
-        // determine this node's contribution to idle resources
-        let (idle_cpus_for_node, idle_gpus_for_node) = if !allocated {
-            let base_state = match &derived_state {
-                NodeState::Compound { base, .. } => base,
-                _ => &derived_state,
-            };
+    // add the calculated idle resources to the summary totals
+    group.summary.idle_cpus += idle_cpus_for_node;
+    group.summary.idle_gpus += idle_gpus_for_node;
 
-            match base_state {
-                // for Idle and Mixed nodes, idle resources are what's not allocated
-                NodeState::Idle | NodeState::Mixed => {
-                    let cpus = node.cpus as u32 - alloc_cpus_for_node;
-                    let gpus = if let Some(gpu) = &node.gpu_info {
-                        gpu.total_gpus - gpu.allocated_gpus
-                    } else {
-                        0
-                    };
-                    (cpus, gpus)
-                }
-                // for any other state (Allocated, Down, etc.), no resources are considered idle
-                _ => (0, 0),
-            }
+    // update subgroups (gpu or feature)
+    if let Some(gpu) = &node.gpu_info {
+        let subgroup_key = if !verbose && gpu.name.starts_with("gpu:") {
+            "gpu".to_string()
         } else {
-            // if we're in allocated mode, idle counts are not needed
-            (0, 0)
+            gpu.name.clone()
         };
 
-        // add the calculated idle resources to the summary totals
-        group.summary.idle_cpus += idle_cpus_for_node;
-        group.summary.idle_gpus += idle_gpus_for_node;
-
-        // update subgroups (gpu or feature)
-        if let Some(gpu) = &node.gpu_info {
-            let subgroup_key = if !verbose && gpu.name.starts_with("gpu:") {
-                "gpu".to_string()
-            } else {
-                gpu.name.clone()
-            };
-
-            let subgroup_line = group.subgroups.entry(subgroup_key).or_default();
+        let subgroup_line = group.subgroups.entry(subgroup_key).or_default();
 
-            subgroup_line.node_count += 1;
-            subgroup_line.total_cpus += node.cpus as u32;
-            subgroup_line.alloc_cpus += alloc_cpus_for_node;
-            subgroup_line.total_gpus += gpu.total_gpus;
-            subgroup_line.alloc_gpus += gpu.allocated_gpus;
-            if show_node_names {
-                subgroup_line.node_names.push(node.name.clone());
-            }
+        subgroup_line.node_count += 1;
+        subgroup_line.total_cpus += node.cpus as u32;
+        subgroup_line.alloc_cpus += alloc_cpus_for_node;
+        subgroup_line.add_gpu_info(gpu);
+        if show_node_names {
+            subgroup_line.node_names.push(node.name.clone());
+        }
 
-            // add this node's idle contribution to the subgroup
-            subgroup_line.idle_cpus += idle_cpus_for_node;
-            subgroup_line.idle_gpus += idle_gpus_for_node;
-        } else if let Some(feature) = node.features.first() {
-            let subgroup_line = group.subgroups.entry(feature.clone()).or_default();
-
-            subgroup_line.node_count += 1;
-            subgroup_line.total_cpus += node.cpus as u32;
-            subgroup_line.alloc_cpus += alloc_cpus_for_node;
-            if show_node_names {
-                subgroup_line.node_names.push(node.name.clone());
-            }
+        // add this node's idle contribution to the subgroup
+        subgroup_line.idle_cpus += idle_cpus_for_node;
+        subgroup_line.idle_gpus += idle_gpus_for_node;
+    } else if let Some(feature) = node.features.first() {
+        let subgroup_line = group.subgroups.entry(feature.to_string()).or_default();
 
-            // add this node's idle contribution to the subgroup
-            subgroup_line.idle_cpus += idle_cpus_for_node;
+        subgroup_line.node_count += 1;
+        subgroup_line.total_cpus += node.cpus as u32;
+        subgroup_line.alloc_cpus += alloc_cpus_for_node;
+        if show_node_names {
+            subgroup_line.node_names.push(node.name.clone());
         }
+
+        // add this node's idle contribution to the subgroup
+        subgroup_line.idle_cpus += idle_cpus_for_node;
     }
-    report_data
 }
 
 /// A struct for the widths of the different columns of a report
@@ -213,6 +268,7 @@ pub fn get_report_widths(report_data: &ReportData, allocated: bool) -> (ReportWi
         total_line.total_gpus += group.summary.total_gpus;
         total_line.alloc_gpus += group.summary.alloc_gpus;
         total_line.idle_gpus += group.summary.idle_gpus;
+        total_line.add_gpu_shares(&group.summary);
     }
 
     // use the totals to set the initial minimum widths
@@ -287,33 +343,32 @@ struct StateComponent {
 }
 
 impl StateComponent {
-    fn new(name: String, width: usize, no_color: bool, state: Option<&NodeState>) -> Self {
+    fn new(name: String, width: usize, theme: &Theme, state: Option<&NodeState>) -> Self {
         let padding = " ".repeat(width.saturating_sub(name.len()));
-        let colored_text = if no_color {
+        let palette = &theme.palette;
+        let colored_text = if !theme.enabled {
             name.normal()
         } else if let Some(s) = state {
             match s {
                 NodeState::Compound { base, flags } => {
                     let base_str = base.to_string();
-                    let flags_str = format!("+{}", flags.join("+").to_uppercase());
-                    let colored_base = match **base {
-                        NodeState::Idle => base_str.green(),
-                        NodeState::Mixed => base_str.blue(),
-                        NodeState::Allocated => base_str.yellow(),
-                        NodeState::Down => base_str.red(),
-                        NodeState::Error => base_str.magenta(),
-                        _ => base_str.cyan(),
+                    let flags_str = format!("+{}", flags);
+                    let color = match **base {
+                        NodeState::Idle => palette.idle,
+                        NodeState::Mixed => palette.mixed,
+                        NodeState::Allocated => palette.allocated,
+                        NodeState::Down => palette.down,
+                        NodeState::Error => palette.error,
+                        _ => palette.other,
                     };
-                    format!("{}{}", colored_base, flags_str).normal()
+                    format!("{}{}", theme.paint(&base_str, color), flags_str).normal()
                 }
-                _ => match s {
-                    NodeState::Idle => name.green(),
-                    NodeState::Mixed => name.blue(),
-                    NodeState::Allocated => name.yellow(),
-                    NodeState::Down => name.red(),
-                    NodeState::Error => name.magenta(),
-                    _ => name.dimmed(),
-                },
+                NodeState::Idle => theme.paint(&name, palette.idle),
+                NodeState::Mixed => theme.paint(&name, palette.mixed),
+                NodeState::Allocated => theme.paint(&name, palette.allocated),
+                NodeState::Down => theme.paint(&name, palette.down),
+                NodeState::Error => theme.paint(&name, palette.error),
+                _ => name.dimmed(),
             }
         } else {
             name.normal() // Make TOTAL bold
@@ -387,12 +442,35 @@ impl GPUComponent {
     }
 }
 
+/// Returns the subgroups of a state group in display order. Within a state, all nodes of a
+/// subgroup share that state, so sorting by idle nodes orders by node count
+fn sorted_subgroups(
+    subgroups: &HashMap<String, ReportLine>,
+    sort: SortOrder,
+) -> Vec<(&String, &ReportLine)> {
+    let mut sorted: Vec<_> = subgroups.iter().collect();
+    // sort by name first so that ties are broken alphabetically
+    sorted.sort_by(|a, b| a.0.cmp(b.0));
+    match sort {
+        SortOrder::Name => {}
+        SortOrder::Nodes | SortOrder::IdleNodes => {
+            sorted.sort_by_key(|(_, line)| Reverse(line.node_count))
+        }
+        SortOrder::IdleCpus => sorted.sort_by_key(|(_, line)| Reverse(line.idle_cpus)),
+        SortOrder::Utilization => {
+            sorted.sort_by(|a, b| a.1.utilization().total_cmp(&b.1.utilization()))
+        }
+    }
+    sorted
+}
+
 /// Formats and prints the aggregated report data to the console
 pub fn print_report(
     report_data: &ReportData,
-    no_color: bool,
+    theme: &Theme,
     show_node_names: bool,
     allocated: bool,
+    sort: SortOrder,
 ) {
     let padding: usize = 2;
     let padding_str = " ".repeat(padding);
@@ -446,14 +524,14 @@ pub fn print_report(
     sorted_states.sort_by(|a, b| {
         let to_key = |state: &&NodeState| {
             let (base_state, flags) = match state {
-                NodeState::Compound { base, flags } => (base.as_ref(), flags),
-                _ => (*state, &Vec::new()), // Treat simple state as having no flags
+                NodeState::Compound { base, flags } => (base.as_ref(), *flags),
+                _ => (*state, NodeStateFlags::empty()), // Treat simple state as having no flags
             };
             let base_priority = *state_order.get(base_state).unwrap_or(&99);
 
             let mut flag_priorities: Vec<usize> = flags
-                .iter()
-                .map(|f| *flag_order.get(f.to_uppercase().as_str()).unwrap_or(&99))
+                .iter_names()
+                .map(|(name, _)| *flag_order.get(name).unwrap_or(&99))
                 .collect();
             flag_priorities.sort_unstable(); // Sort by priority numbers for canonical comparison
 
@@ -508,7 +586,7 @@ pub fn print_report(
             let state_comp = StateComponent::new(
                 state.to_string(),
                 report_widths.state_width,
-                no_color,
+                theme,
                 Some(state),
             );
             let count_comp = CountComponent::new(group.summary.node_count, count_data_width);
@@ -533,39 +611,34 @@ pub fn print_report(
                 },
             );
 
-            let mut sorted_subgroups: Vec<&String> = group.subgroups.keys().collect();
-            sorted_subgroups.sort();
-
-            for subgroup_name in sorted_subgroups {
-                if let Some(line) = group.subgroups.get(subgroup_name) {
-                    let state_comp = StateComponent::new(
-                        format!("  {}", subgroup_name),
-                        report_widths.state_width,
-                        no_color,
-                        None,
-                    );
-                    let count_comp = CountComponent::new(line.node_count, count_data_width);
-                    let cpu_comp = CPUComponent::new(line, &report_widths, allocated);
-                    let gpu_comp = GPUComponent::new(line, &report_widths, allocated);
-                    let node_names = &line.node_names.clone();
-
-                    println!(
-                        "{}{}{}{}{}{}{}{}  {}",
-                        state_comp.colored_text,
-                        state_comp.padding,
-                        padding_str,
-                        count_comp.text,
-                        padding_str,
-                        cpu_comp.text,
-                        padding_str,
-                        gpu_comp.text,
-                        if show_node_names {
-                            fi_slurm::parser::compress_hostlist(node_names)
-                        } else {
-                            "".to_string()
-                        }
-                    );
-                }
+            for (subgroup_name, line) in sorted_subgroups(&group.subgroups, sort) {
+                let state_comp = StateComponent::new(
+                    format!("  {}", subgroup_name),
+                    report_widths.state_width,
+                    theme,
+                    None,
+                );
+                let count_comp = CountComponent::new(line.node_count, count_data_width);
+                let cpu_comp = CPUComponent::new(line, &report_widths, allocated);
+                let gpu_comp = GPUComponent::new(line, &report_widths, allocated);
+                let node_names = &line.node_names.clone();
+
+                println!(
+                    "{}{}{}{}{}{}{}{}  {}",
+                    state_comp.colored_text,
+                    state_comp.padding,
+                    padding_str,
+                    count_comp.text,
+                    padding_str,
+                    cpu_comp.text,
+                    padding_str,
+                    gpu_comp.text,
+                    if show_node_names {
+                        fi_slurm::parser::compress_hostlist(node_names)
+                    } else {
+                        "".to_string()
+                    }
+                );
             }
         }
     }
@@ -575,7 +648,7 @@ pub fn print_report(
     let state_comp = StateComponent::new(
         "TOTAL (Idle/Total)".to_string(),
         report_widths.state_width,
-        no_color,
+        theme,
         None,
     );
     let count_comp = CountComponent::new(total_line.node_count, count_data_width);
@@ -591,14 +664,14 @@ pub fn print_report(
     println!("{}", gpu_comp.text);
 
     // print the availability/utilization bars
-    print_utilization_bars(report_data, &total_line, allocated, no_color);
+    print_utilization_bars(report_data, &total_line, allocated, theme);
 }
 
 fn print_utilization_bars(
     report_data: &ReportData,
     total_line: &ReportLine,
     allocated: bool,
-    no_color: bool,
+    theme: &Theme,
 ) {
     println!(); // blank line for spacing
     if allocated {
@@ -616,54 +689,56 @@ fn print_utilization_bars(
                 }
             });
             let percent = (utilized_nodes as f64 / total_line.node_count as f64) * 100.0;
-            print_utilization(percent, 50, BarColor::Green, "Node", no_color, allocated);
+            print_utilization(percent, 50, theme.palette.nodes, "Node", theme, allocated);
         }
         if total_line.total_cpus > 0 {
             let percent = (total_line.alloc_cpus as f64 / total_line.total_cpus as f64) * 100.0;
-            print_utilization(percent, 50, BarColor::Cyan, "CPU", no_color, allocated);
+            print_utilization(percent, 50, theme.palette.cpus, "CPU", theme, allocated);
         }
         if total_line.total_gpus > 0 {
             let percent = (total_line.alloc_gpus as f64 / total_line.total_gpus as f64) * 100.0;
-            print_utilization(percent, 50, BarColor::Red, "GPU", no_color, allocated);
+            print_utilization(percent, 50, theme.palette.gpus, "GPU", theme, allocated);
+        }
+        if total_line.has_gpu_shares() {
+            let percent = total_line.alloc_gpu_equivalents / total_line.gpu_equivalents * 100.0;
+            print_utilization(
+                percent,
+                50,
+                theme.palette.gpus,
+                "GPU-equivalent",
+                theme,
+                allocated,
+            );
         }
     } else {
         // --- Availability ---
         if total_line.node_count > 0 {
             let available_nodes = get_available_nodes(report_data);
             let percent = (available_nodes as f64 / total_line.node_count as f64) * 100.0;
-            print_utilization(percent, 50, BarColor::Green, "Node", no_color, allocated);
+            print_utilization(percent, 50, theme.palette.nodes, "Node", theme, allocated);
         }
         if total_line.total_cpus > 0 {
             let available_cpus = get_available_cpus(report_data);
             let percent = (available_cpus as f64 / total_line.total_cpus as f64) * 100.0;
-            print_utilization(percent, 50, BarColor::Cyan, "CPU", no_color, allocated);
+            print_utilization(percent, 50, theme.palette.cpus, "CPU", theme, allocated);
         }
         if total_line.total_gpus > 0 {
             let available_gpus = get_available_gpus(report_data);
             let percent = (available_gpus as f64 / total_line.total_gpus as f64) * 100.0;
-            print_utilization(percent, 50, BarColor::Red, "GPU", no_color, allocated);
+            print_utilization(percent, 50, theme.palette.gpus, "GPU", theme, allocated);
         }
-    }
-}
-
-fn is_node_available(state: &NodeState) -> bool {
-    match state {
-        NodeState::Idle => true,
-        NodeState::Compound { base, flags } => {
-            if **base == NodeState::Idle {
-                // Node is idle, but check for disqualifying flags
-                !flags.iter().any(|flag| {
-                    let flag_str = flag.as_str();
-                    flag_str == "MAINT"
-                        || flag_str == "DOWN"
-                        || flag_str == "DRAIN"
-                        || flag_str == "INVALID_REG"
-                })
-            } else {
-                false
-            }
+        if total_line.has_gpu_shares() {
+            let free = get_free_gpu_equivalents(report_data);
+            let percent = free / total_line.gpu_equivalents * 100.0;
+            print_utilization(
+                percent,
+                50,
+                theme.palette.gpus,
+                "GPU-equivalent",
+                theme,
+                allocated,
+            );
         }
-        _ => false,
     }
 }
 
@@ -699,29 +774,24 @@ fn get_available_gpus(report_data: &ReportData) -> u64 {
     })
 }
 
-#[derive(Clone)]
-enum BarColor {
-    Red,
-    Green,
-    Cyan,
-}
-
-impl BarColor {
-    pub fn apply_color(&self, text: &str) -> ColoredString {
-        match self {
-            BarColor::Cyan => text.cyan(),
-            BarColor::Red => text.red(),
-            BarColor::Green => text.green(),
-        }
-    }
+/// Gets the GPU-equivalents left free on nodes that can run jobs, counting partly allocated
+/// nodes too, since MIG instances and shards can be taken one at a time
+fn get_free_gpu_equivalents(report_data: &ReportData) -> f64 {
+    report_data
+        .iter()
+        .filter(|(state, _)| is_node_available(state) || is_node_mixed(state))
+        .map(|(_, group)| {
+            (group.summary.gpu_equivalents - group.summary.alloc_gpu_equivalents).max(0.0)
+        })
+        .sum()
 }
 
 fn print_utilization(
     utilization_percent: f64,
     bar_width: usize,
-    bar_color: BarColor,
+    bar_color: Color,
     name: &str,
-    no_color: bool,
+    theme: &Theme,
     allocated: bool,
 ) {
     // Call count_blocks to get the components of the bar
@@ -737,16 +807,8 @@ fn print_utilization(
     let empty_bar = " ".repeat(empty);
 
     // Apply color to the filled parts of the bar
-    let colored_full = if no_color {
-        full_bar.white()
-    } else {
-        bar_color.apply_color(&full_bar)
-    };
-    let colored_partial = if no_color {
-        partial_bar.white()
-    } else {
-        bar_color.apply_color(&partial_bar)
-    };
+    let colored_full = theme.paint(&full_bar, bar_color);
+    let colored_partial = theme.paint(&partial_bar, bar_color);
 
     // Print the assembled bar
     if allocated {
@@ -0,0 +1,191 @@
+use chrono::{Local, Utc};
+use colored::*;
+use fi_slurm::statistics::{CycleStatistics, RpcStatistics, SchedulerStatistics};
+use fi_slurm::theme::Theme;
+use users::get_user_by_uid;
+
+/// The RPC types and users listed, the busiest by total time
+const TOP_RPCS: usize = 10;
+
+/// A main scheduling cycle longer than this is flagged, in microseconds; slurmctld holds its
+/// locks through a cycle, so long cycles slow every other RPC
+const SLOW_MAIN_CYCLE_US: u64 = 1_000_000;
+/// Backfill releases its locks periodically, so only much longer cycles are flagged
+const SLOW_BACKFILL_CYCLE_US: u64 = 60_000_000;
+/// A backlog of messages for slurmdbd this long suggests it is unreachable or falling behind
+const DBD_BACKLOG: u32 = 1_000;
+
+/// A number of microseconds, in the most readable unit
+fn format_us(us: u64) -> String {
+    if us >= 1_000_000 {
+        format!("{:.2} s", us as f64 / 1_000_000.0)
+    } else if us >= 1_000 {
+        format!("{:.1} ms", us as f64 / 1_000.0)
+    } else {
+        format!("{} us", us)
+    }
+}
+
+fn format_mean(mean: Option<u64>) -> String {
+    mean.map_or_else(|| "-".to_string(), format_us)
+}
+
+/// A line of the report, in the warning color if `flagged`
+fn print_stat(label: &str, value: String, flagged: bool, theme: &Theme) {
+    let line = format!("  {:<28}{}", label, value);
+    if flagged {
+        println!("{}", theme.paint(&line, theme.palette.warning));
+    } else {
+        println!("{}", line);
+    }
+}
+
+fn print_cycles(title: &str, cycles: &CycleStatistics, slow_us: u64, theme: &Theme) {
+    println!("{}", title.bold());
+    print_stat("Cycles", cycles.cycles.to_string(), false, theme);
+    print_stat(
+        "Last cycle",
+        format_us(cycles.last_cycle_us as u64),
+        cycles.last_cycle_us as u64 > slow_us,
+        theme,
+    );
+    print_stat(
+        "Max cycle",
+        format_us(cycles.max_cycle_us as u64),
+        cycles.max_cycle_us as u64 > slow_us,
+        theme,
+    );
+    print_stat(
+        "Mean cycle",
+        format_mean(cycles.mean_cycle_us()),
+        cycles.mean_cycle_us().is_some_and(|mean| mean > slow_us),
+        theme,
+    );
+    print_stat(
+        "Mean depth",
+        cycles
+            .mean_depth()
+            .map_or_else(|| "-".to_string(), |depth| format!("{} jobs", depth)),
+        false,
+        theme,
+    );
+    print_stat(
+        "Last queue length",
+        cycles.queue_len.to_string(),
+        false,
+        theme,
+    );
+}
+
+fn print_rpcs(title: &str, rpcs: &[RpcStatistics], name: impl Fn(u32) -> String) {
+    println!(
+        "{}",
+        format!(
+            "{:<24}  {:>10}  {:>12}  {:>12}",
+            title, "COUNT", "TOTAL", "MEAN"
+        )
+        .bold()
+    );
+    for rpc in rpcs.iter().take(TOP_RPCS) {
+        println!(
+            "{:<24}  {:>10}  {:>12}  {:>12}",
+            name(rpc.id),
+            rpc.count,
+            format_us(rpc.total_time_us),
+            format_mean(rpc.mean_time_us())
+        );
+    }
+}
+
+/// Prints the scheduler statistics, flagging the values that point to a scheduler under stress
+pub fn print_sched_stats(stats: &SchedulerStatistics, theme: &Theme) {
+    let local = |time: chrono::DateTime<Utc>| time.with_timezone(&Local).format("%m-%d %H:%M:%S");
+    println!(
+        "{} since {}, as of {}",
+        "Scheduler statistics".bold(),
+        local(stats.counting_since),
+        local(stats.taken_at)
+    );
+    println!();
+
+    println!("{}", "Controller".bold());
+    print_stat(
+        "Server threads",
+        stats.server_thread_count.to_string(),
+        false,
+        theme,
+    );
+    print_stat(
+        "Agent queue",
+        stats.agent_queue_size.to_string(),
+        false,
+        theme,
+    );
+    print_stat(
+        "slurmdbd queue",
+        stats.dbd_agent_queue_size.to_string(),
+        stats.dbd_agent_queue_size > DBD_BACKLOG,
+        theme,
+    );
+    println!();
+
+    println!("{}", "Jobs".bold());
+    print_stat(
+        "Submitted / started",
+        format!("{} / {}", stats.jobs_submitted, stats.jobs_started),
+        false,
+        theme,
+    );
+    print_stat(
+        "Completed / canceled / failed",
+        format!(
+            "{} / {} / {}",
+            stats.jobs_completed, stats.jobs_canceled, stats.jobs_failed
+        ),
+        false,
+        theme,
+    );
+    print_stat(
+        "Pending / running",
+        format!("{} / {}", stats.jobs_pending, stats.jobs_running),
+        false,
+        theme,
+    );
+    println!();
+
+    print_cycles("Main scheduler", &stats.main, SLOW_MAIN_CYCLE_US, theme);
+    println!();
+
+    print_cycles("Backfill", &stats.backfill, SLOW_BACKFILL_CYCLE_US, theme);
+    print_stat(
+        "Backfilled jobs",
+        stats.backfilled_jobs.to_string(),
+        false,
+        theme,
+    );
+    print_stat(
+        "Last depth (tried)",
+        format!(
+            "{} ({})",
+            stats.backfill_last_depth, stats.backfill_last_depth_try
+        ),
+        false,
+        theme,
+    );
+    let last_cycle = match (stats.backfill_active, stats.backfill_last_cycle_at) {
+        (true, _) => "running now".to_string(),
+        (false, Some(at)) => local(at).to_string(),
+        (false, None) => "never".to_string(),
+    };
+    print_stat("Last cycle at", last_cycle, false, theme);
+    println!();
+
+    // sdiag names the message types, from tables private to Slurm; these are their numbers
+    print_rpcs("RPC TYPE", &stats.rpc_types, |id| format!("#{}", id));
+    println!();
+    print_rpcs("RPC USER", &stats.rpc_users, |uid| {
+        get_user_by_uid(uid)
+            .map(|user| user.name().to_string_lossy().into_owned())
+            .unwrap_or_else(|| uid.to_string())
+    });
+}
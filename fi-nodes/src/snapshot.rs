@@ -0,0 +1,133 @@
+use chrono::{DateTime, Local, Utc};
+use colored::*;
+use fi_slurm::cluster_state::ClusterDiff;
+use fi_slurm::theme::Theme;
+use std::collections::HashSet;
+
+fn format_time(time: DateTime<Utc>) -> String {
+    time.with_timezone(&Local)
+        .format("%Y-%m-%d %H:%M:%S")
+        .to_string()
+}
+
+/// Formats a before/after pair of counts as "old -> new (+delta)", colored by whether
+/// availability went up or down
+fn format_delta(old: i64, new: i64, theme: &Theme) -> String {
+    let delta = new - old;
+    let text = format!("{} -> {} ({:+})", old, new, delta);
+    if delta == 0 {
+        text
+    } else if delta > 0 {
+        theme.paint(&text, theme.palette.gain).to_string()
+    } else {
+        theme.paint(&text, theme.palette.loss).to_string()
+    }
+}
+
+/// Prints a list of added or removed nodes as a hostlist, putting each prefix on its own
+/// line when the nodes span several
+fn print_node_groups(label: &str, node_names: &[String]) {
+    let groups = fi_slurm::parser::compress_hostlist_grouped(node_names);
+    if groups.len() == 1 {
+        println!("{} ({}): {}", label.bold(), node_names.len(), groups[0].1);
+        return;
+    }
+    println!("{} ({}):", label.bold(), node_names.len());
+    for (_, hostlist) in groups {
+        println!("  {}", hostlist);
+    }
+}
+
+/// Prints the differences between two cluster states, leaving out hidden features and features
+/// whose availability did not change
+pub fn print_diff(diff: &ClusterDiff, hidden_features: &HashSet<String>, theme: &Theme) {
+    println!(
+        "{} {} {} {}",
+        "Changes from".bold(),
+        format_time(diff.old_taken_at),
+        "to".bold(),
+        format_time(diff.new_taken_at)
+    );
+
+    if !diff.newly_drained.is_empty() {
+        println!();
+        println!("{} ({})", "Newly drained".bold(), diff.newly_drained.len());
+        let width = diff
+            .newly_drained
+            .iter()
+            .map(|c| c.name.len())
+            .max()
+            .unwrap_or(0);
+        for change in &diff.newly_drained {
+            let name = format!("{:<width$}", change.name);
+            println!(
+                "  {}  {}  {}",
+                theme.paint(&name, theme.palette.down),
+                change.new_state,
+                change.reason
+            );
+        }
+    }
+
+    if !diff.node_state_changes.is_empty() {
+        println!();
+        println!(
+            "{} ({})",
+            "State changes".bold(),
+            diff.node_state_changes.len()
+        );
+        let width = diff
+            .node_state_changes
+            .iter()
+            .map(|c| c.name.len())
+            .max()
+            .unwrap_or(0);
+        for change in &diff.node_state_changes {
+            println!(
+                "  {:<width$}  {} -> {}",
+                change.name, change.old_state, change.new_state
+            );
+        }
+    }
+
+    if !diff.added_nodes.is_empty() {
+        println!();
+        print_node_groups("Added nodes", &diff.added_nodes);
+    }
+    if !diff.removed_nodes.is_empty() {
+        println!();
+        print_node_groups("Removed nodes", &diff.removed_nodes);
+    }
+
+    println!();
+    println!(
+        "{} {} new, {} gone, {} changed state",
+        "Jobs:".bold(),
+        diff.new_jobs.len(),
+        diff.gone_jobs.len(),
+        diff.job_state_changes.len()
+    );
+
+    let changed: Vec<_> = diff
+        .features
+        .iter()
+        .filter(|(feature, (old, new))| !hidden_features.contains(*feature) && old != new)
+        .collect();
+    println!();
+    if changed.is_empty() {
+        println!("{}", "No change in availability by feature".bold());
+        return;
+    }
+
+    println!("{}", "Availability by feature".bold());
+    let width = changed.iter().map(|(f, _)| f.len()).max().unwrap_or(0);
+    for (feature, (old, new)) in changed {
+        println!(
+            "  {:<width$}  nodes {}  cores {}  gpus {}",
+            feature,
+            format_delta(old.idle_nodes as i64, new.idle_nodes as i64, theme),
+            format_delta(old.idle_cpus as i64, new.idle_cpus as i64, theme),
+            format_delta(old.idle_gpus as i64, new.idle_gpus as i64, theme),
+        );
+    }
+}
@@ -1,40 +1,33 @@
-use crate::PreemptNodes;
+use crate::{PreemptNodes, ReservedNodes, SortOrder};
+use chrono::{DateTime, Duration, Utc};
 use colored::*;
+use fi_slurm::availability::{NodeAvailability, NodeMemory, allocated_cpus, derive_node_state};
+use fi_slurm::filter::FeatureExpr;
 use fi_slurm::jobs::SlurmJobs;
 use fi_slurm::nodes::{Node, NodeState};
+use fi_slurm::theme::Theme;
 use fi_slurm::utils::count_blocks;
+use rayon::prelude::*;
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::collections::hash_map::Entry;
 use std::collections::{HashMap, HashSet};
-use std::sync::OnceLock;
-
-// a custom list of uninformative or redundant features excluded from the default presentation
-static HIDDEN_FEATURES: OnceLock<HashSet<&str>> = OnceLock::new();
-
-// TODO: per-site hidden feature configuration
-fn hidden_features() -> &'static HashSet<&'static str> {
-    HIDDEN_FEATURES.get_or_init(|| {
-        [
-            "rocky8", "rocky9", "sxm", "sxm2", "sxm4", "sxm5", "nvlink", "a100", "h100", "v100",
-            "ib",
-        ]
-        .iter()
-        .cloned()
-        .collect()
-    })
-}
+use std::sync::Arc;
 
 // Data Structures for the Tree Report
 
 /// Represents a single node in the feature hierarchy tree
-#[derive(Default, Debug, Clone)]
+#[derive(Default, Debug, Clone, Serialize)]
 pub struct TreeNode {
-    pub name: String,
+    pub name: Arc<str>,
     pub stats: ReportLine,
     pub single_filter: bool, // used to determine whether we are filtering on a single item
-    pub children: HashMap<String, TreeNode>,
+    /// Keyed by feature name, sharing the interned names of the nodes' features
+    pub children: HashMap<Arc<str>, TreeNode>,
 }
 
 /// A simplified version of the ReportLine from the detailed report
-#[derive(Default, Debug, Clone)]
+#[derive(Default, Debug, Clone, Serialize)]
 pub struct ReportLine {
     pub total_nodes: u32,
     pub idle_nodes: u32,
@@ -42,51 +35,94 @@ pub struct ReportLine {
     pub total_cpus: u32,
     pub idle_cpus: u32,
     pub preempt_cpus: Option<u32>,
+    /// The GPUs freed by preemption, counted in the CPU report as well as the GPU one
+    pub preempt_gpus: Option<u32>,
+    /// When the next of the jobs on the branch becomes preemptable, as a Unix timestamp, in the
+    /// preempt report
+    pub next_preemptable: Option<i64>,
+    /// The CPUs (or GPUs, in the GPU report) held by running jobs that reach their time limit
+    /// within the forecast window, with --forecast
+    pub soon_cpus: Option<u32>,
+    /// The nodes held by reservations now or within the window, with --reserved-within
+    pub reserved_nodes: Option<u32>,
     pub alloc_cpus: u32,
+    /// The memory of the branch's nodes available to jobs, in megabytes
+    pub total_memory_mb: u64,
+    /// The memory held by jobs on the branch's nodes, in megabytes
+    pub alloc_memory_mb: u64,
+    /// The load of the branch's nodes against the CPUs allocated on them, shown with --load
+    pub load: LoadLine,
     pub node_names: Vec<String>,
 }
 
-/// A Newtype for TreeNode, representing the output of build_tree_report
-pub type TreeReportData = TreeNode;
-
-// Aggregation Logic
+/// A node whose load is below this share of its allocated CPUs leaves cores it holds unused
+const WASTED_LOAD_RATIO: f64 = 0.5;
+/// A node whose load is above this share of its allocated CPUs, give or take `LOAD_SLACK`,
+/// runs more than its jobs were given
+const OVERSUBSCRIBED_LOAD_RATIO: f64 = 1.5;
+/// The load of system daemons, tolerated on top of what jobs were given
+const LOAD_SLACK: f64 = 1.0;
+
+/// How the load of the nodes on a branch compares with the CPUs allocated on them
+#[derive(Default, Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct LoadLine {
+    /// The load averages of the nodes that report one, in CPUs
+    pub load: f64,
+    /// The CPUs allocated on those same nodes
+    pub alloc_cpus: u32,
+    /// Nodes whose load is well below their allocation, wasting cores
+    pub wasted_nodes: u32,
+    /// Nodes whose load is well above their allocation, oversubscribing their cores
+    pub oversubscribed_nodes: u32,
+}
 
-/// Helper function to determine if a node is available for new work
-fn is_node_available(state: &NodeState) -> bool {
-    match state {
-        NodeState::Idle => true,
-        NodeState::Compound { base, flags } => {
-            if **base == NodeState::Idle {
-                // Node is idle, but check for disqualifying flags
-                !flags.iter().any(|flag| {
-                    flag == "MAINT" || flag == "DOWN" || flag == "DRAIN" || flag == "INVALID_REG"
-                })
-            } else {
-                false
-            }
+impl LoadLine {
+    /// Adds a single node, flagging it if its load is far from its allocation
+    fn add(&mut self, load: f64, alloc_cpus: u32) {
+        self.load += load;
+        self.alloc_cpus += alloc_cpus;
+        let allocated = alloc_cpus as f64;
+        if load < allocated * WASTED_LOAD_RATIO {
+            self.wasted_nodes += 1;
+        } else if load > allocated * OVERSUBSCRIBED_LOAD_RATIO + LOAD_SLACK {
+            self.oversubscribed_nodes += 1;
         }
-        _ => false,
+    }
+
+    fn merge(&mut self, other: LoadLine) {
+        self.load += other.load;
+        self.alloc_cpus += other.alloc_cpus;
+        self.wasted_nodes += other.wasted_nodes;
+        self.oversubscribed_nodes += other.oversubscribed_nodes;
     }
 }
 
-/// Helper function to determine if a node partly available for new work
-fn is_node_mixed(state: &NodeState) -> bool {
-    match state {
-        NodeState::Mixed => true,
-        NodeState::Compound { base, flags } => {
-            if **base == NodeState::Mixed {
-                // Node is mixed, but check for disqualifying flags
-                !flags.iter().any(|flag| {
-                    flag == "MAINT" || flag == "DOWN" || flag == "DRAIN" || flag == "INVALID_REG"
-                })
-            } else {
-                false
-            }
+impl ReportLine {
+    /// Fraction of the CPUs (or GPUs, in the GPU report) that are allocated, from 0 to 1
+    pub fn utilization(&self) -> f64 {
+        if self.total_cpus == 0 {
+            0.0
+        } else {
+            self.alloc_cpus as f64 / self.total_cpus as f64
+        }
+    }
+
+    /// The resources freed by preemption in the column the report shows, GPUs in the GPU
+    /// report and CPUs otherwise
+    fn preempt_resources(&self, gpu: bool) -> Option<u32> {
+        if gpu {
+            self.preempt_gpus
+        } else {
+            self.preempt_cpus
         }
-        _ => false,
     }
 }
 
+/// A Newtype for TreeNode, representing the output of build_tree_report
+pub type TreeReportData = TreeNode;
+
+// Aggregation Logic
+
 /// A filter enum to decide whether we want to show only nodes with gpu, nodes without gpu, or show both
 pub enum GpuFilter {
     Gpu,
@@ -94,387 +130,410 @@ pub enum GpuFilter {
     All,
 }
 
+/// The inputs shared by every node added to the tree report
+#[derive(Clone, Copy)]
+struct TreeContext<'a> {
+    jobs: &'a SlurmJobs,
+    node_to_job_map: &'a HashMap<usize, Vec<u32>>,
+    feature_filter: &'a [Arc<str>],
+    /// The parsed form of each filter in `feature_filter`, in the same order
+    feature_exprs: &'a [FeatureExpr],
+    hidden_features: &'a HashSet<String>,
+    show_node_names: bool,
+    preemptable_node_ids: &'a [usize],
+    preempt: bool,
+    gpu: bool,
+    /// The time jobs' preemptable times are compared against, as a Unix timestamp
+    now: i64,
+    /// The end of the forecast window, as a Unix timestamp
+    forecast_until: Option<i64>,
+    /// The switches above each node, with --topology, which then make up the branches instead
+    /// of the features
+    topology: Option<&'a HashMap<String, Vec<Arc<str>>>>,
+    /// The nodes reserved within the window, with --reserved-within
+    reserved: Option<&'a ReservedNodes>,
+}
+
+/// The branch of the topology report for nodes that aren't connected to any switch
+const NO_SWITCH: &str = "(no switch)";
+
+// nodes handled per parallel task, large enough that merging partial trees stays cheap
+const NODES_PER_TASK: usize = 64;
+
+impl ReportLine {
+    /// Adds the counts of another line, as when merging partial reports
+    fn merge(&mut self, other: ReportLine) {
+        fn add(a: Option<u32>, b: Option<u32>) -> Option<u32> {
+            match (a, b) {
+                (None, None) => None,
+                (a, b) => Some(a.unwrap_or(0) + b.unwrap_or(0)),
+            }
+        }
+        self.total_nodes += other.total_nodes;
+        self.idle_nodes += other.idle_nodes;
+        self.preempt_nodes = add(self.preempt_nodes, other.preempt_nodes);
+        self.total_cpus += other.total_cpus;
+        self.idle_cpus += other.idle_cpus;
+        self.preempt_cpus = add(self.preempt_cpus, other.preempt_cpus);
+        self.preempt_gpus = add(self.preempt_gpus, other.preempt_gpus);
+        self.next_preemptable = earliest(self.next_preemptable, other.next_preemptable);
+        self.soon_cpus = add(self.soon_cpus, other.soon_cpus);
+        self.reserved_nodes = add(self.reserved_nodes, other.reserved_nodes);
+        self.alloc_cpus += other.alloc_cpus;
+        self.total_memory_mb += other.total_memory_mb;
+        self.alloc_memory_mb += other.alloc_memory_mb;
+        self.load.merge(other.load);
+        self.node_names.extend(other.node_names);
+    }
+}
+
+/// The earlier of two optional times
+fn earliest(a: Option<i64>, b: Option<i64>) -> Option<i64> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+impl TreeNode {
+    /// Merges a partial tree built from another set of nodes into this one
+    fn merge(&mut self, other: TreeNode) {
+        self.stats.merge(other.stats);
+        for (name, child) in other.children {
+            match self.children.entry(name) {
+                Entry::Occupied(mut entry) => entry.get_mut().merge(child),
+                Entry::Vacant(entry) => {
+                    entry.insert(child);
+                }
+            }
+        }
+    }
+}
+
 /// Builds a hierarchical tree report from a flat list of Slurm nodes
-/// Strong candidate for refactor, currently very repetitive and confusing
+///
+/// Nodes are aggregated into partial trees in parallel, which are then merged
 #[allow(clippy::too_many_arguments)]
 pub fn build_tree_report(
     nodes: &[&Node],
     jobs: &SlurmJobs,
     node_to_job_map: &HashMap<usize, Vec<u32>>,
     feature_filter: &[String],
-    show_hidden_features: bool,
+    hidden_features: &HashSet<String>,
     show_node_names: bool,
     preemptable_nodes: Option<PreemptNodes>,
     preempt: bool,
     gpu: bool,
+    forecast: Option<Duration>,
+    topology: Option<&HashMap<String, Vec<Arc<str>>>>,
+    reserved: Option<&ReservedNodes>,
 ) -> TreeReportData {
-    let mut root = TreeNode {
-        name: "Total".to_string(),
-        ..Default::default()
+    let preemptable_node_ids = match (&preemptable_nodes, preempt) {
+        (Some(preemptable_nodes), true) => preemptable_nodes.0.as_slice(),
+        _ => &[],
+    };
+    let feature_exprs: Vec<FeatureExpr> = feature_filter
+        .iter()
+        .map(|filter| FeatureExpr::from_filter(filter))
+        .collect();
+    let filter_names: Vec<Arc<str>> = feature_filter
+        .iter()
+        .map(|filter| Arc::from(filter.as_str()))
+        .collect();
+    let now = Utc::now();
+    let ctx = TreeContext {
+        jobs,
+        node_to_job_map,
+        feature_filter: &filter_names,
+        feature_exprs: &feature_exprs,
+        hidden_features,
+        show_node_names,
+        preemptable_node_ids,
+        preempt,
+        gpu,
+        now: now.timestamp(),
+        forecast_until: forecast.map(|window| (now + window).timestamp()),
+        topology,
+        reserved,
     };
 
-    if feature_filter.len() == 1 {
+    let mut root = nodes
+        .par_iter()
+        .with_min_len(NODES_PER_TASK)
+        .fold(TreeNode::default, |mut tree, &node| {
+            add_node_to_tree(&mut tree, node, &ctx);
+            tree
+        })
+        .reduce(TreeNode::default, |mut tree, other| {
+            tree.merge(other);
+            tree
+        });
+
+    root.name = Arc::from("Total");
+    if feature_filter.len() == 1 && topology.is_none() {
         root.single_filter = true
     };
 
-    // the main loop, iterating over the nodes in order to construct the tree structure
-    for &node in nodes {
-        let alloc_cpus_for_node: u32 = if let Some(job_ids) = node_to_job_map.get(&node.id) {
-            job_ids
-                .iter()
-                .filter_map(|id| jobs.jobs.get(id))
-                .map(|j| j.num_cpus / j.num_nodes.max(1))
-                .sum()
-        } else {
-            0
-        };
-
-        let mut total_gpus: u32 = 0;
-        let mut allocated_gpus: u32 = 0;
-
-        if let Some(gpu_info) = &node.gpu_info {
-            total_gpus = gpu_info.total_gpus as u32;
-            allocated_gpus = gpu_info.allocated_gpus as u32;
-        };
-
-        let derived_state = if alloc_cpus_for_node > 0 && alloc_cpus_for_node < node.cpus as u32 {
-            match &node.state {
-                NodeState::Compound { flags, .. } => NodeState::Compound {
-                    base: Box::new(NodeState::Mixed),
-                    flags: flags.to_vec(),
-                },
-                _ => NodeState::Mixed,
-            }
-        } else {
-            // Otherwise, we trust the state reported by Slurm
-            node.state.clone()
-        };
+    root
+}
 
-        let is_available = is_node_available(&derived_state);
-        let is_mixed = is_node_mixed(&derived_state);
+/// What a single node adds to each line of the tree report it belongs to
+#[derive(Debug, Clone, Copy)]
+struct NodeContribution {
+    cpus: u32,
+    alloc_cpus: u32,
+    gpus: u32,
+    alloc_gpus: u32,
+    memory: NodeMemory,
+    /// The free CPUs and GPUs of the node, after any preempt reclassification
+    availability: NodeAvailability,
+    /// Whether the node was reclassified because its jobs are preemptable
+    preemptable: bool,
+    /// When the next of the node's jobs that can't be preempted yet becomes preemptable
+    next_preemptable: Option<i64>,
+    /// The CPUs (or GPUs) the node's jobs free within the forecast window, with --forecast
+    soon: Option<u32>,
+    /// Whether a reservation holds the node within the window, with --reserved-within
+    reserved: Option<bool>,
+    /// The load average of the node, unless it is down or hasn't reported one
+    load: Option<f64>,
+    /// Whether the report counts GPUs instead of CPUs
+    gpu: bool,
+    /// Whether the report includes preempt information
+    preempt: bool,
+}
 
-        let preemptable_node_ids = if preempt {
-            &preemptable_nodes.as_ref().unwrap().0
+impl ReportLine {
+    /// Adds a single node to this line
+    ///
+    /// Available nodes add to the idle node count. In the preempt report, all the resources
+    /// of an available node count as idle, since its jobs can all be preempted, and the
+    /// resources freed by preemption are tallied separately
+    fn accumulate(&mut self, node: &NodeContribution) {
+        let NodeAvailability {
+            idle_cpus,
+            idle_gpus,
+            available,
+            mixed,
+        } = node.availability;
+        let (total, allocated, idle) = if node.gpu {
+            (node.gpus, node.alloc_gpus, idle_gpus)
         } else {
-            &Vec::new()
+            (node.cpus, node.alloc_cpus, idle_cpus)
         };
 
-        // Update Grand Total Stats
-        root.stats.total_nodes += 1;
-
-        if gpu {
-            root.stats.total_cpus += total_gpus;
-            root.stats.alloc_cpus += allocated_gpus;
-        } else {
-            root.stats.total_cpus += node.cpus as u32;
-            root.stats.alloc_cpus += alloc_cpus_for_node;
-        }
-
-        // Preemptable nodes have already had their state updated to Idle or Mixed
-        if is_available && preempt {
-            // we don't increment idle nodes or cpus in this case
-            // in order to keep idle nodes referring only to idle and not idle + preempt
-            root.stats.idle_nodes += 1;
-
-            if gpu {
-                root.stats.idle_cpus += total_gpus;
-            } else {
-                root.stats.idle_cpus += node.cpus as u32;
-            }
-
-            if preemptable_node_ids.contains(&node.id) {
-                *root.stats.preempt_nodes.get_or_insert(0) += 1;
-                if gpu {
-                    *root.stats.preempt_cpus.get_or_insert(0) += total_gpus;
-                } else {
-                    *root.stats.preempt_cpus.get_or_insert(0) += node.cpus as u32; // because unlike
+        self.total_nodes += 1;
+        self.total_cpus += total;
+        self.alloc_cpus += allocated;
+        self.total_memory_mb += node.memory.total_mb;
+        self.alloc_memory_mb += node.memory.allocated_mb;
+
+        if available {
+            self.idle_nodes += 1;
+            if node.preempt {
+                self.idle_cpus += total;
+                if node.preemptable {
+                    *self.preempt_nodes.get_or_insert(0) += 1;
+                    *self.preempt_cpus.get_or_insert(0) += total;
+                    *self.preempt_gpus.get_or_insert(0) += node.gpus;
                 }
-                // the nodes, cpus don't get any kind of base state change
-            }
-        } else if is_available && !preempt {
-            root.stats.idle_nodes += 1;
-            // we assume that, if we're using the gpu bool flag and have gotten to this point, all
-            // the nodes we loop over will unwrap without panicking, since is_some was the
-            // inclusion condition
-            // but the mixed logic above may not be fully accurate...
-            if gpu {
-                root.stats.idle_cpus += (total_gpus).saturating_sub(allocated_gpus);
             } else {
-                root.stats.idle_cpus += (node.cpus as u32).saturating_sub(alloc_cpus_for_node);
-            }
-        } else if is_mixed && preempt {
-            if gpu {
-                root.stats.idle_cpus += (total_gpus).saturating_sub(allocated_gpus);
-            } else {
-                root.stats.idle_cpus += (node.cpus as u32).saturating_sub(alloc_cpus_for_node);
-            }
-
-            if preemptable_node_ids.contains(&node.id) {
-                if gpu {
-                    *root.stats.preempt_cpus.get_or_insert(0) +=
-                        (total_gpus).saturating_sub(allocated_gpus);
-                } else {
-                    *root.stats.preempt_cpus.get_or_insert(0) +=
-                        (node.cpus as u32).saturating_sub(alloc_cpus_for_node);
-                }
+                self.idle_cpus += idle;
             }
-        } else if is_mixed && !preempt {
-            if gpu {
-                root.stats.idle_cpus += (total_gpus).saturating_sub(allocated_gpus);
-            } else {
-                root.stats.idle_cpus += (node.cpus as u32).saturating_sub(alloc_cpus_for_node);
+        } else if mixed {
+            self.idle_cpus += idle;
+            if node.preempt && node.preemptable {
+                *self.preempt_cpus.get_or_insert(0) += idle;
+                *self.preempt_gpus.get_or_insert(0) += idle_gpus;
             }
         }
 
-        // we filter the features list to remove the undesired features unless told otherwise
-        let features_for_tree: Vec<_> = if show_hidden_features {
-            node.features.iter().collect()
-        } else {
-            node.features
-                .iter()
-                .filter(|f| !hidden_features().contains(f.as_str()))
-                .collect()
-        };
+        if node.preempt {
+            self.next_preemptable = earliest(self.next_preemptable, node.next_preemptable);
+        }
+        if let Some(soon) = node.soon {
+            *self.soon_cpus.get_or_insert(0) += soon;
+        }
+        if let Some(reserved) = node.reserved {
+            *self.reserved_nodes.get_or_insert(0) += reserved as u32;
+        }
+        if let Some(load) = node.load {
+            self.load.add(load, node.alloc_cpus);
+        }
+    }
+}
 
-        // further refine with either gpu, not gpu, or both
-
-        // tree building logic
-        if feature_filter.is_empty() {
-            // by default, build tree from the (potentially filtered) feature list
-            let mut current_level = &mut root;
-            for feature in &features_for_tree {
-                current_level = current_level
-                    .children
-                    .entry(feature.to_string())
-                    .or_default();
-                current_level.name = feature.to_string();
-                // add stats to this branch
-                current_level.stats.total_nodes += 1;
+/// Adds the stats of a single node to the tree, at the root and along each of its feature
+/// branches, or the switches above it with --topology
+fn add_node_to_tree(root: &mut TreeNode, node: &Node, ctx: &TreeContext) {
+    let TreeContext {
+        jobs,
+        node_to_job_map,
+        feature_filter,
+        feature_exprs,
+        hidden_features,
+        show_node_names,
+        preemptable_node_ids,
+        preempt,
+        gpu,
+        now,
+        forecast_until,
+        topology,
+        reserved,
+    } = *ctx;
+
+    let alloc_cpus_for_node = allocated_cpus(node, jobs, node_to_job_map);
+
+    // jobs whose preemptable time is still to come will free their resources later
+    let next_preemptable = if preempt {
+        node_to_job_map
+            .get(&node.id)
+            .into_iter()
+            .flatten()
+            .filter_map(|id| jobs.jobs.get(id))
+            .map(|j| j.preemptable_time.timestamp())
+            .filter(|&time| time > now)
+            .min()
+    } else {
+        None
+    };
 
+    // the resources of running jobs that end within the forecast window, leaving out the jobs
+    // the preempt report already counts as idle
+    let soon = forecast_until.map(|until| {
+        node_to_job_map
+            .get(&node.id)
+            .into_iter()
+            .flatten()
+            .filter_map(|id| jobs.jobs.get(id))
+            .filter(|j| j.expected_end().is_some_and(|end| end.timestamp() <= until))
+            .filter(|j| {
+                !(preempt
+                    && j.preemptable_time.timestamp() <= now
+                    && j.preemptable_time != DateTime::UNIX_EPOCH)
+            })
+            .map(|j| {
                 if gpu {
-                    current_level.stats.total_cpus += total_gpus;
-                    current_level.stats.alloc_cpus += allocated_gpus;
+                    j.gpus_on_node(node.id)
                 } else {
-                    current_level.stats.total_cpus += node.cpus as u32;
-                    current_level.stats.alloc_cpus += alloc_cpus_for_node;
+                    j.cpus_on_node(node.id)
                 }
+            })
+            .sum()
+    });
+
+    // MIG instances count for the share of a GPU they are, so that a node split into them
+    // still has GPUs to report
+    let (total_gpus, allocated_gpus) = node
+        .gpu_info
+        .as_ref()
+        .map_or((0, 0), |gpu_info| gpu_info.whole_equivalents());
+
+    // the load of a down node is whatever it last reported
+    let base_state = match &node.state {
+        NodeState::Compound { base, .. } => base,
+        state => state,
+    };
+    let load = node.load().filter(|_| *base_state != NodeState::Down);
 
-                if is_available && preempt {
-                    current_level.stats.idle_nodes += 1;
-
-                    if gpu {
-                        current_level.stats.idle_cpus += total_gpus;
-                    } else {
-                        current_level.stats.idle_cpus += node.cpus as u32;
-                    }
-
-                    if preemptable_node_ids.contains(&node.id) {
-                        *current_level.stats.preempt_nodes.get_or_insert(0) += 1;
-                        if gpu {
-                            *current_level.stats.preempt_cpus.get_or_insert(0) += total_gpus;
-                        } else {
-                            *current_level.stats.preempt_cpus.get_or_insert(0) += node.cpus as u32;
-                        }
-                    }
-                } else if is_available && !preempt {
-                    current_level.stats.idle_nodes += 1;
-                    if gpu {
-                        current_level.stats.idle_cpus +=
-                            (total_gpus).saturating_sub(allocated_gpus);
-                    } else {
-                        current_level.stats.idle_cpus +=
-                            (node.cpus as u32).saturating_sub(alloc_cpus_for_node);
-                    }
-                } else if is_mixed && preempt {
-                    if gpu {
-                        current_level.stats.idle_cpus +=
-                            (total_gpus).saturating_sub(allocated_gpus);
-                    } else {
-                        current_level.stats.idle_cpus +=
-                            (node.cpus as u32).saturating_sub(alloc_cpus_for_node);
-                    }
-
-                    if preemptable_node_ids.contains(&node.id) {
-                        if gpu {
-                            *current_level.stats.preempt_cpus.get_or_insert(0) +=
-                                (total_gpus).saturating_sub(allocated_gpus);
-                        } else {
-                            *current_level.stats.preempt_cpus.get_or_insert(0) +=
-                                (node.cpus as u32).saturating_sub(alloc_cpus_for_node);
-                        }
-                    }
-                } else if is_mixed && !preempt {
-                    if gpu {
-                        current_level.stats.idle_cpus +=
-                            (total_gpus).saturating_sub(allocated_gpus);
-                    } else {
-                        current_level.stats.idle_cpus +=
-                            (node.cpus as u32).saturating_sub(alloc_cpus_for_node);
-                    }
-                }
+    // with --respect-reservations, a node reserved within the window can't take a job that
+    // needs it for that long, so none of it counts as available
+    let is_reserved = reserved.map(|reserved| reserved.node_ids.contains(&node.id));
+    let availability = if is_reserved == Some(true) && reserved.is_some_and(|r| r.respect) {
+        NodeAvailability::default()
+    } else {
+        NodeAvailability::new(
+            &derive_node_state(node, alloc_cpus_for_node),
+            (node.cpus as u32, alloc_cpus_for_node),
+            (total_gpus, allocated_gpus),
+        )
+    };
 
-                if show_node_names {
-                    current_level.stats.node_names.push(node.name.clone());
-                }
-            }
-        } else {
-            // bring the filtered features to the top level
-            for filter in feature_filter {
-                // IMPORTANT: The check to see if a node belongs under a filter
-                // must use the ORIGINAL, unfiltered features.
-                if node.features.contains(filter) {
-                    let mut current_level = root.children.entry(filter.clone()).or_default();
-                    current_level.name = filter.clone();
-                    // add stats to this top-level branch
-                    current_level.stats.total_nodes += 1;
-
-                    if gpu {
-                        current_level.stats.total_cpus += total_gpus;
-                        current_level.stats.alloc_cpus += allocated_gpus;
-                    } else {
-                        current_level.stats.total_cpus += node.cpus as u32;
-                        current_level.stats.alloc_cpus += alloc_cpus_for_node;
-                    }
-
-                    if is_available && preempt {
-                        current_level.stats.idle_nodes += 1;
-                        if gpu {
-                            current_level.stats.idle_cpus += total_gpus;
-                        } else {
-                            current_level.stats.idle_cpus += node.cpus as u32;
-                        }
-
-                        if preemptable_node_ids.contains(&node.id) {
-                            *current_level.stats.preempt_nodes.get_or_insert(0) += 1;
-
-                            if gpu {
-                                *current_level.stats.preempt_cpus.get_or_insert(0) += total_gpus;
-                            } else {
-                                *current_level.stats.preempt_cpus.get_or_insert(0) +=
-                                    node.cpus as u32;
-                            }
-                        }
-                    } else if is_available && !preempt {
-                        current_level.stats.idle_nodes += 1;
-                        if gpu {
-                            current_level.stats.idle_cpus +=
-                                (total_gpus).saturating_sub(allocated_gpus);
-                        } else {
-                            current_level.stats.idle_cpus +=
-                                (node.cpus as u32).saturating_sub(alloc_cpus_for_node);
-                        }
-                    } else if is_mixed && preempt {
-                        if gpu {
-                            current_level.stats.idle_cpus +=
-                                (total_gpus).saturating_sub(allocated_gpus);
-                        } else {
-                            current_level.stats.idle_cpus +=
-                                (node.cpus as u32).saturating_sub(alloc_cpus_for_node);
-                        }
-                        if preemptable_node_ids.contains(&node.id) {
-                            if gpu {
-                                *current_level.stats.preempt_cpus.get_or_insert(0) +=
-                                    (total_gpus).saturating_sub(allocated_gpus);
-                            } else {
-                                *current_level.stats.preempt_cpus.get_or_insert(0) +=
-                                    (node.cpus as u32).saturating_sub(alloc_cpus_for_node);
-                            }
-                        }
-                    } else if is_mixed && !preempt {
-                        if gpu {
-                            current_level.stats.idle_cpus +=
-                                (total_gpus).saturating_sub(allocated_gpus);
-                        } else {
-                            current_level.stats.idle_cpus +=
-                                (node.cpus as u32).saturating_sub(alloc_cpus_for_node);
-                        }
-                    }
-
-                    // build the sub-branch from the *remaining* features,
-                    // respecting the show_hidden_features flag
-                    for feature in features_for_tree.iter().filter(|f| f.as_str() != filter) {
-                        current_level = current_level
-                            .children
-                            .entry(feature.to_string())
-                            .or_default();
-                        current_level.name = feature.to_string();
-                        // add stats to the sub-branch
-                        current_level.stats.total_nodes += 1;
-
-                        if gpu {
-                            current_level.stats.total_cpus += total_gpus;
-                            current_level.stats.alloc_cpus += allocated_gpus;
-                        } else {
-                            current_level.stats.total_cpus += node.cpus as u32;
-                            current_level.stats.alloc_cpus += alloc_cpus_for_node;
-                        }
-
-                        if is_available && preempt {
-                            current_level.stats.idle_nodes += 1;
-
-                            if gpu {
-                                current_level.stats.idle_cpus += total_gpus;
-                            } else {
-                                current_level.stats.idle_cpus += node.cpus as u32;
-                            }
-
-                            if preemptable_node_ids.contains(&node.id) {
-                                *current_level.stats.preempt_nodes.get_or_insert(0) += 1;
-
-                                if gpu {
-                                    *current_level.stats.preempt_cpus.get_or_insert(0) +=
-                                        total_gpus;
-                                } else {
-                                    *current_level.stats.preempt_cpus.get_or_insert(0) +=
-                                        node.cpus as u32;
-                                }
-                            }
-                        } else if is_available && !preempt {
-                            current_level.stats.idle_nodes += 1;
-                            if gpu {
-                                current_level.stats.idle_cpus +=
-                                    (total_gpus).saturating_sub(allocated_gpus);
-                            } else {
-                                current_level.stats.idle_cpus +=
-                                    (node.cpus as u32).saturating_sub(alloc_cpus_for_node);
-                            }
-                        } else if is_mixed && preempt {
-                            if gpu {
-                                current_level.stats.idle_cpus +=
-                                    (total_gpus).saturating_sub(allocated_gpus);
-                            } else {
-                                current_level.stats.idle_cpus +=
-                                    (node.cpus as u32).saturating_sub(alloc_cpus_for_node);
-                            }
-
-                            if preemptable_node_ids.contains(&node.id) {
-                                if gpu {
-                                    *current_level.stats.preempt_cpus.get_or_insert(0) +=
-                                        (total_gpus).saturating_sub(allocated_gpus);
-                                } else {
-                                    *current_level.stats.preempt_cpus.get_or_insert(0) +=
-                                        (node.cpus as u32).saturating_sub(alloc_cpus_for_node);
-                                }
-                            }
-                        } else if is_mixed && !preempt {
-                            if gpu {
-                                current_level.stats.idle_cpus +=
-                                    (total_gpus).saturating_sub(allocated_gpus);
-                            } else {
-                                current_level.stats.idle_cpus +=
-                                    (node.cpus as u32).saturating_sub(alloc_cpus_for_node);
-                            }
-                        }
-
-                        if show_node_names {
-                            current_level.stats.node_names.push(node.name.clone());
-                        }
-                    }
+    // Preemptable nodes have already had their state updated to Idle or Mixed
+    let contribution = NodeContribution {
+        cpus: node.cpus as u32,
+        alloc_cpus: alloc_cpus_for_node,
+        gpus: total_gpus,
+        alloc_gpus: allocated_gpus,
+        memory: NodeMemory::of_node(node, jobs, node_to_job_map),
+        availability,
+        preemptable: preemptable_node_ids.contains(&node.id),
+        next_preemptable,
+        soon,
+        reserved: is_reserved,
+        load,
+        gpu,
+        preempt,
+    };
+    let add_to_branch = |line: &mut ReportLine| {
+        line.accumulate(&contribution);
+        if show_node_names {
+            line.node_names.push(node.name.clone());
+        }
+    };
+
+    // Update Grand Total Stats
+    root.stats.accumulate(&contribution);
+
+    // we filter the features list to remove the undesired features
+    let features_for_tree: Vec<_> = node
+        .features
+        .iter()
+        .filter(|f| !hidden_features.contains(&***f))
+        .collect();
+
+    // tree building logic
+    if let Some(topology) = topology {
+        // by switch, from the top of the topology down to the node's leaf switch; the nodes
+        // have already been filtered by feature
+        let no_switch = [Arc::from(NO_SWITCH)];
+        let path = topology
+            .get(&node.name)
+            .map_or(no_switch.as_slice(), Vec::as_slice);
+        let mut current_level = &mut *root;
+        for switch in path {
+            current_level = current_level
+                .children
+                .entry(Arc::clone(switch))
+                .or_default();
+            current_level.name = Arc::clone(switch);
+            add_to_branch(&mut current_level.stats);
+        }
+    } else if feature_filter.is_empty() {
+        // by default, build tree from the (potentially filtered) feature list
+        let mut current_level = &mut *root;
+        for feature in &features_for_tree {
+            current_level = current_level
+                .children
+                .entry(Arc::clone(feature))
+                .or_default();
+            current_level.name = Arc::clone(feature);
+            add_to_branch(&mut current_level.stats);
+        }
+    } else {
+        // bring the filtered features to the top level
+        for (filter, expr) in feature_filter.iter().zip(feature_exprs) {
+            // IMPORTANT: The check to see if a node belongs under a filter
+            // must use the ORIGINAL, unfiltered features.
+            if expr.matches(&node.features, true) {
+                let mut current_level = root.children.entry(Arc::clone(filter)).or_default();
+                current_level.name = Arc::clone(filter);
+                // node names are only listed on the feature branches below the filter
+                current_level.stats.accumulate(&contribution);
+
+                // build the sub-branch from the *remaining* features,
+                // leaving out the hidden features
+                for feature in features_for_tree.iter().filter(|&&f| f != filter) {
+                    current_level = current_level
+                        .children
+                        .entry(Arc::clone(feature))
+                        .or_default();
+                    current_level.name = Arc::clone(feature);
+                    add_to_branch(&mut current_level.stats);
                 }
             }
         }
     }
-    root
 }
 
 // Display Logic
@@ -491,7 +550,7 @@ struct ColumnWidths {
 }
 
 /// Helper function for calculating the widths of the columns
-fn calculate_column_widths(tree_node: &TreeNode) -> ColumnWidths {
+fn calculate_column_widths(tree_node: &TreeNode, gpu: bool) -> ColumnWidths {
     let mut widths = ColumnWidths {
         max_idle_nodes: tree_node.stats.idle_nodes.to_string().len(),
         max_total_nodes: tree_node.stats.total_nodes.to_string().len(),
@@ -504,12 +563,12 @@ fn calculate_column_widths(tree_node: &TreeNode) -> ColumnWidths {
     if let Some(node_count) = tree_node.stats.preempt_nodes {
         widths.max_preempt_nodes_width = node_count.to_string().len();
     }
-    if let Some(cpu_count) = tree_node.stats.preempt_cpus {
+    if let Some(cpu_count) = tree_node.stats.preempt_resources(gpu) {
         widths.max_preempt_cpus_width = cpu_count.to_string().len();
     }
 
     for child in tree_node.children.values() {
-        let child_widths = calculate_column_widths(child);
+        let child_widths = calculate_column_widths(child, gpu);
         widths.max_idle_nodes = widths.max_idle_nodes.max(child_widths.max_idle_nodes);
         widths.max_total_nodes = widths.max_total_nodes.max(child_widths.max_total_nodes);
         widths.max_preempt_nodes_width = widths
@@ -526,13 +585,7 @@ fn calculate_column_widths(tree_node: &TreeNode) -> ColumnWidths {
 }
 
 /// Creates a colored bar string for available resources (nodes or CPUs)
-fn create_avail_bar(
-    current: u32,
-    total: u32,
-    width: usize,
-    color: Color,
-    no_color: bool,
-) -> String {
+fn create_avail_bar(current: u32, total: u32, width: usize, color: Color, theme: &Theme) -> String {
     if total == 0 {
         // To avoid division by zero and provide clear output for empty categories
         let bar_content = " ".repeat(width);
@@ -543,31 +596,153 @@ fn create_avail_bar(
 
     let bars = count_blocks(20, percentage);
 
-    let filled = "█"
-        .repeat(bars.0)
-        .color(if no_color { Color::White } else { color });
+    let filled = theme.paint(&"█".repeat(bars.0), color);
     let empty = " ".repeat(bars.1);
 
     if let Some(remainder) = bars.2 {
-        format!(
-            "│{}{}{}│",
-            filled,
-            remainder.color(if no_color { Color::White } else { color }),
-            empty
-        )
+        format!("│{}{}{}│", filled, theme.paint(&remainder, color), empty)
     } else {
         format!("│{}{}│", filled, empty)
     }
 }
 
+// width of the utilization column, including the separating space: " 100%"
+const USED_WIDTH: usize = 5;
+
+// width of the forecast column, including the separating spaces
+const SOON_WIDTH: usize = 16;
+const HEADER_SOON: &str = "Soon Available";
+
+// width of the load column, including the separating spaces
+const LOAD_WIDTH: usize = 22;
+const HEADER_LOAD: &str = "Load/Alloc";
+
+// width of the reservations column, including the separating spaces
+const RESERVED_WIDTH: usize = 10;
+const HEADER_RESERVED: &str = "Reserved";
+
+// width of the next preemptable column of the preempt report, including the separating spaces
+const NEXT_PREEMPT_WIDTH: usize = 14;
+const HEADER_NEXT_PREEMPT: &str = "Next Preempt";
+
+/// The color of a branch's allocated share, by how busy the branch is
+fn utilization_color(utilization: f64, theme: &Theme) -> Color {
+    if utilization >= 0.9 {
+        theme.palette.high
+    } else if utilization >= 0.5 {
+        theme.palette.medium
+    } else {
+        theme.palette.low
+    }
+}
+
+/// Draws the allocated share of a branch's CPUs (or GPUs) as a bar, filled and colored by the
+/// same value as the percentage next to it
+fn create_used_bar(stats: &ReportLine, width: usize, theme: &Theme) -> String {
+    create_avail_bar(
+        stats.alloc_cpus,
+        stats.total_cpus,
+        width,
+        utilization_color(stats.utilization(), theme),
+        theme,
+    )
+}
+
+/// Formats the allocated share of a branch's CPUs (or GPUs) as a percentage, colored by how
+/// busy the branch is. The value is rounded down, so 100% means fully allocated
+fn format_utilization(stats: &ReportLine, theme: &Theme) -> String {
+    let utilization = stats.utilization();
+    let text = format!(
+        "{:>width$}",
+        format!("{:.0}%", (utilization * 100.0).floor()),
+        width = USED_WIDTH - 1
+    );
+    theme
+        .paint(&text, utilization_color(utilization, theme))
+        .to_string()
+}
+
+/// Formats the resources of a branch freed within the forecast window, such as "+128"
+fn format_soon(stats: &ReportLine, theme: &Theme) -> String {
+    let text = format!(
+        "{:>width$}",
+        format!("+{}", stats.soon_cpus.unwrap_or(0)),
+        width = SOON_WIDTH - 2
+    );
+    format!("  {}", theme.paint(&text, theme.palette.gain))
+}
+
+/// Formats the load of a branch against the CPUs allocated on it, such as "412/640 ↓3 ↑1", with
+/// the nodes wasting cores marked by ↓ and those oversubscribed by ↑
+fn format_load(stats: &ReportLine, theme: &Theme) -> String {
+    let load = &stats.load;
+    let counts = format!("{:.0}/{}", load.load, load.alloc_cpus);
+    let mut flags = Vec::new();
+    if load.wasted_nodes > 0 {
+        flags.push((format!("↓{}", load.wasted_nodes), theme.palette.warning));
+    }
+    if load.oversubscribed_nodes > 0 {
+        flags.push((
+            format!("↑{}", load.oversubscribed_nodes),
+            theme.palette.high,
+        ));
+    }
+
+    // padded by hand, since the colors of the flags would count towards a format width
+    let width = counts.len()
+        + flags
+            .iter()
+            .map(|(flag, _)| 1 + flag.chars().count())
+            .sum::<usize>();
+    let mut text = format!(
+        "  {}{}",
+        " ".repeat((LOAD_WIDTH - 2).saturating_sub(width)),
+        counts
+    );
+    for (flag, color) in flags {
+        text.push(' ');
+        text.push_str(&theme.paint(&flag, color).to_string());
+    }
+    text
+}
+
+/// Formats the nodes of a branch held by reservations within the window, highlighted if any are
+fn format_reserved(stats: &ReportLine, theme: &Theme) -> String {
+    let reserved = stats.reserved_nodes.unwrap_or(0);
+    let text = format!("{:>width$}", reserved, width = RESERVED_WIDTH - 2);
+    if reserved > 0 {
+        format!("  {}", theme.paint(&text, theme.palette.warning))
+    } else {
+        format!("  {}", text)
+    }
+}
+
+/// Formats how long until more of a branch's resources become preemptable, such as "in 2h05m",
+/// for the preempt report
+fn format_next_preemptable(stats: &ReportLine, theme: &Theme) -> String {
+    let Some(time) = stats.next_preemptable else {
+        return " ".repeat(NEXT_PREEMPT_WIDTH);
+    };
+    let minutes = (time - Utc::now().timestamp()).max(0) / 60;
+    let wait = if minutes < 60 {
+        format!("in {}m", minutes)
+    } else if minutes < 48 * 60 {
+        format!("in {}h{:02}m", minutes / 60, minutes % 60)
+    } else {
+        format!("in {}d", minutes / (24 * 60))
+    };
+    let text = format!("{:>width$}", wait, width = NEXT_PREEMPT_WIDTH - 2);
+    format!("  {}", theme.paint(&text, theme.palette.warning))
+}
+
 /// Recursively calculates the maximum width needed for the feature name column
 fn calculate_max_width(tree_node: &TreeNode, prefix_len: usize, collapse: bool) -> usize {
-    let mut path_parts = vec![tree_node.name.as_str()];
+    let mut path_parts = vec![&*tree_node.name];
     let mut current_node = tree_node;
     if collapse {
         while current_node.children.len() == 1 {
             let single_child = current_node.children.values().next().unwrap();
-            path_parts.push(single_child.name.as_str());
+            path_parts.push(&*single_child.name);
             current_node = single_child;
         }
     }
@@ -584,13 +759,17 @@ fn calculate_max_width(tree_node: &TreeNode, prefix_len: usize, collapse: bool)
 }
 
 /// Prints the tree report
+#[allow(clippy::too_many_arguments)]
 pub fn print_tree_report(
     root: &TreeReportData,
-    no_color: bool,
+    theme: &Theme,
     show_node_names: bool,
-    sort: bool,
+    sort: SortOrder,
     preempt: bool,
     gpu: bool,
+    forecast: bool,
+    load: bool,
+    reserved: bool,
 ) {
     // --- Define Headers ---
     const HEADER_FEATURE: &str = "Feature";
@@ -601,26 +780,20 @@ pub fn print_tree_report(
     const HEADER_GPUS_PREEMPT: &str = "";
     const HEADER_GPUS: &str = "";
     const HEADER_NODE_AVAIL: &str = "Nodes Available  ";
-    const HEADER_CPU_AVAIL: &str = "Cores Available  ";
-    const HEADER_GPU_AVAIL: &str = "GPUs Available  ";
+    const HEADER_CPU_USED: &str = "Cores Allocated  ";
+    const HEADER_GPU_USED: &str = "GPUs Allocated  ";
+    const HEADER_USED: &str = "Used";
 
     // Determine what to print as the top level
-    let (top_level_node, children_to_iterate) = if root.single_filter {
-        if let Some(single_child) = root.children.values().next() {
-            (single_child, &single_child.children)
-        } else {
-            (root, &root.children)
-        }
-    } else {
-        (root, &root.children)
-    };
+    let top_level_node = top_level(root);
+    let children_to_iterate = &top_level_node.children;
 
     // Calculate Column Widths
     let max_feature_width =
         calculate_max_width(top_level_node, 0, false).max(HEADER_FEATURE.len()) - 4;
     let bar_width = 20;
 
-    let col_widths = calculate_column_widths(top_level_node);
+    let col_widths = calculate_column_widths(top_level_node, gpu);
 
     // Calculate data width for the NODES column, accounting for the preempt count string
     let nodes_data_width = {
@@ -680,13 +853,16 @@ pub fn print_tree_report(
         );
 
         if let Some(preempt_count) = stats.preempt_nodes {
-            let preempt_str_colored = format!(
-                "(-{:>width$})",
-                preempt_count,
-                width = col_widths.max_preempt_nodes_width
-            )
-            .yellow()
-            .to_string();
+            let preempt_str_colored = theme
+                .paint(
+                    &format!(
+                        "(-{:>width$})",
+                        preempt_count,
+                        width = col_widths.max_preempt_nodes_width
+                    ),
+                    theme.palette.warning,
+                )
+                .to_string();
             let preempt_str_uncolored = format!(
                 "(-{:>width$})",
                 preempt_count,
@@ -720,14 +896,17 @@ pub fn print_tree_report(
             width = col_widths.max_total_cpus
         );
 
-        if let Some(preempt_count) = stats.preempt_cpus {
-            let preempt_str_colored = format!(
-                "(-{:>width$})",
-                preempt_count,
-                width = col_widths.max_preempt_cpus_width
-            )
-            .yellow()
-            .to_string();
+        if let Some(preempt_count) = stats.preempt_resources(gpu) {
+            let preempt_str_colored = theme
+                .paint(
+                    &format!(
+                        "(-{:>width$})",
+                        preempt_count,
+                        width = col_widths.max_preempt_cpus_width
+                    ),
+                    theme.palette.warning,
+                )
+                .to_string();
             let preempt_str_uncolored = format!(
                 "(-{:>width$})",
                 preempt_count,
@@ -758,30 +937,14 @@ pub fn print_tree_report(
         stats.idle_nodes,
         stats.total_nodes,
         bar_width,
-        Color::Green,
-        no_color,
+        theme.palette.nodes,
+        theme,
     );
-    let cpu_bar = if gpu {
-        create_avail_bar(
-            stats.idle_cpus,
-            stats.total_cpus,
-            bar_width,
-            Color::Red,
-            no_color,
-        )
-    } else {
-        create_avail_bar(
-            stats.idle_cpus,
-            stats.total_cpus,
-            bar_width,
-            Color::Cyan,
-            no_color,
-        )
-    };
+    let cpu_bar = create_used_bar(stats, bar_width, theme);
 
     // Print Headers with alignment
     println!(
-        "{:<feature_w$} {:<nodes_w$}  {:<bar_w$}{:<cpus_w$}  {:<bar_w$}",
+        "{:<feature_w$} {:<nodes_w$}  {:<bar_w$}{:<cpus_w$}  {:<bar_w$}{:>used_w$}{}{}{}{}",
         HEADER_FEATURE.bold(),
         if preempt {
             HEADER_NODES_PREEMPT.bold()
@@ -801,48 +964,102 @@ pub fn print_tree_report(
             HEADER_CPUS.bold()
         },
         if gpu {
-            HEADER_GPU_AVAIL.bold()
+            HEADER_GPU_USED.bold()
+        } else {
+            HEADER_CPU_USED.bold()
+        },
+        HEADER_USED.bold(),
+        if load {
+            format!("  {:>width$}", HEADER_LOAD.bold(), width = LOAD_WIDTH - 2)
+        } else {
+            String::new()
+        },
+        if forecast {
+            format!("  {:>width$}", HEADER_SOON.bold(), width = SOON_WIDTH - 2)
         } else {
-            HEADER_CPU_AVAIL.bold()
+            String::new()
+        },
+        if reserved {
+            format!(
+                "  {:>width$}",
+                HEADER_RESERVED.bold(),
+                width = RESERVED_WIDTH - 2
+            )
+        } else {
+            String::new()
+        },
+        if preempt {
+            format!(
+                "  {:>width$}",
+                HEADER_NEXT_PREEMPT.bold(),
+                width = NEXT_PREEMPT_WIDTH - 2
+            )
+        } else {
+            String::new()
         },
         feature_w = max_feature_width,
         nodes_w = nodes_final_width,
         cpus_w = cpus_final_width,
-        bar_w = bar_final_width
+        bar_w = bar_final_width,
+        used_w = USED_WIDTH - 1,
     );
 
     // Print Separator Line
-    let total_width =
-        max_feature_width + nodes_final_width + cpus_final_width + bar_final_width * 2 + 6; // +6 for spaces
+    let total_width = max_feature_width
+        + nodes_final_width
+        + cpus_final_width
+        + bar_final_width * 2
+        + USED_WIDTH
+        + if load { LOAD_WIDTH } else { 0 }
+        + if forecast { SOON_WIDTH } else { 0 }
+        + if reserved { RESERVED_WIDTH } else { 0 }
+        + if preempt { NEXT_PREEMPT_WIDTH } else { 0 }
+        + 6; // +6 for spaces
     println!("{}", "═".repeat(total_width - 2));
 
     // Print the top-level line using the adjusted widths for proper alignment
     println!(
-        "{:<feature_w$} {:>nodes_w$} {} {:>cpus_w$} {}",
+        "{:<feature_w$} {:>nodes_w$} {} {:>cpus_w$} {} {}{}{}{}{}",
         top_level_node.name.bold(),
         node_text,
         node_bar,
         cpu_text,
         cpu_bar,
+        format_utilization(stats, theme),
+        if load {
+            format_load(stats, theme)
+        } else {
+            String::new()
+        },
+        if forecast {
+            format_soon(stats, theme)
+        } else {
+            String::new()
+        },
+        if reserved {
+            format_reserved(stats, theme)
+        } else {
+            String::new()
+        },
+        if preempt {
+            format_next_preemptable(stats, theme)
+        } else {
+            String::new()
+        },
         feature_w = max_feature_width,
         nodes_w = nodes_width_adjusted,
         cpus_w = cpus_width_adjusted
     );
 
     // Print the children recursively
-    let mut sorted_children: Vec<_> = children_to_iterate.values().collect();
-    if !sort {
-        sorted_children.sort_by(|a, b| b.stats.total_nodes.cmp(&a.stats.total_nodes));
-    } else {
-        sorted_children.sort_by(|a, b| a.name.cmp(&b.name));
-    }
+    let sorted_children = sorted_children(children_to_iterate, sort);
     for (i, child) in sorted_children.iter().enumerate() {
         let is_last = i == sorted_children.len() - 1;
         print_node_recursive(
             child,
             "",
             is_last,
-            no_color,
+            theme,
             (
                 max_feature_width,
                 bar_width,
@@ -853,26 +1070,63 @@ pub fn print_tree_report(
             show_node_names,
             sort,
             (max_nodes, max_cores),
+            preempt,
             gpu,
+            forecast,
+            load,
+            reserved,
         );
     }
 }
 
+/// Returns the node printed as the top level of the report: the filtered feature itself when
+/// filtering on a single feature, otherwise the root
+pub fn top_level(root: &TreeReportData) -> &TreeNode {
+    if root.single_filter
+        && let Some(single_child) = root.children.values().next()
+    {
+        single_child
+    } else {
+        root
+    }
+}
+
+/// Returns the children of a tree node in display order, per the requested sort order.
+/// Idle counts sort descending and utilization ascending, so the most available branches
+/// come first
+pub fn sorted_children(children: &HashMap<Arc<str>, TreeNode>, sort: SortOrder) -> Vec<&TreeNode> {
+    let mut sorted: Vec<_> = children.values().collect();
+    match sort {
+        SortOrder::Nodes => sorted.sort_by_key(|child| Reverse(child.stats.total_nodes)),
+        SortOrder::Name => sorted.sort_by(|a, b| a.name.cmp(&b.name)),
+        SortOrder::IdleNodes => sorted.sort_by_key(|child| Reverse(child.stats.idle_nodes)),
+        SortOrder::IdleCpus => sorted.sort_by_key(|child| Reverse(child.stats.idle_cpus)),
+        SortOrder::Utilization => {
+            sorted.sort_by(|a, b| a.stats.utilization().total_cmp(&b.stats.utilization()))
+        }
+    }
+    sorted
+}
+
 /// Recursively prints a node and its children to form the tree structure
 #[allow(clippy::too_many_arguments)]
 fn print_node_recursive(
     tree_node: &TreeNode,
     prefix: &str,
     is_last: bool,
-    no_color: bool,
+    theme: &Theme,
     widths: (usize, usize, usize, usize),
     col_widths: &ColumnWidths,
     show_node_names: bool,
-    sort: bool,
+    sort: SortOrder,
     max: (u32, u32),
+    preempt: bool,
     gpu: bool,
+    forecast: bool,
+    load: bool,
+    reserved: bool,
 ) {
-    let mut path_parts = vec![tree_node.name.as_str()];
+    let mut path_parts = vec![&*tree_node.name];
     let mut current_node = tree_node;
 
     let max_width = widths.0;
@@ -885,7 +1139,7 @@ fn print_node_recursive(
         if current_node.stats.total_nodes != single_child.stats.total_nodes {
             break;
         }
-        path_parts.push(single_child.name.as_str());
+        path_parts.push(&*single_child.name);
         current_node = single_child;
     }
 
@@ -908,13 +1162,16 @@ fn print_node_recursive(
         );
 
         if let Some(preempt_count) = stats.preempt_nodes {
-            let preempt_str_colored = format!(
-                "(-{:>width$})",
-                preempt_count,
-                width = col_widths.max_preempt_nodes_width
-            )
-            .yellow()
-            .to_string();
+            let preempt_str_colored = theme
+                .paint(
+                    &format!(
+                        "(-{:>width$})",
+                        preempt_count,
+                        width = col_widths.max_preempt_nodes_width
+                    ),
+                    theme.palette.warning,
+                )
+                .to_string();
             let preempt_str_uncolored = format!(
                 "(-{:>width$})",
                 preempt_count,
@@ -948,14 +1205,17 @@ fn print_node_recursive(
             width = col_widths.max_total_cpus
         );
 
-        if let Some(preempt_count) = stats.preempt_cpus {
-            let preempt_str_colored = format!(
-                "(-{:>width$})",
-                preempt_count,
-                width = col_widths.max_preempt_cpus_width
-            )
-            .yellow()
-            .to_string();
+        if let Some(preempt_count) = stats.preempt_resources(gpu) {
+            let preempt_str_colored = theme
+                .paint(
+                    &format!(
+                        "(-{:>width$})",
+                        preempt_count,
+                        width = col_widths.max_preempt_cpus_width
+                    ),
+                    theme.palette.warning,
+                )
+                .to_string();
             let preempt_str_uncolored = format!(
                 "(-{:>width$})",
                 preempt_count,
@@ -977,23 +1237,46 @@ fn print_node_recursive(
     };
     let cpus_width_adjusted = cpus_final_width + cpu_text.len() - uncolored_cpu_text.len();
 
-    let node_bar = create_avail_bar(stats.idle_nodes, max.0, bar_width, Color::Green, no_color);
+    let node_bar = create_avail_bar(
+        stats.idle_nodes,
+        max.0,
+        bar_width,
+        theme.palette.nodes,
+        theme,
+    );
 
-    let cpu_bar = if gpu {
-        create_avail_bar(stats.idle_cpus, max.1, bar_width, Color::Red, no_color)
-    } else {
-        create_avail_bar(stats.idle_cpus, max.1, bar_width, Color::Cyan, no_color)
-    };
+    let cpu_bar = create_used_bar(stats, bar_width, theme);
 
     let node_names = &current_node.stats.node_names.clone();
 
     println!(
-        "{:<feature_w$} {:>nodes_w$} {} {:>cpus_w$} {} {}",
+        "{:<feature_w$} {:>nodes_w$} {} {:>cpus_w$} {} {}{}{}{}{} {}",
         display_name.bold(),
         node_text,
         node_bar,
         cpu_text,
         cpu_bar,
+        format_utilization(stats, theme),
+        if load {
+            format_load(stats, theme)
+        } else {
+            String::new()
+        },
+        if forecast {
+            format_soon(stats, theme)
+        } else {
+            String::new()
+        },
+        if reserved {
+            format_reserved(stats, theme)
+        } else {
+            String::new()
+        },
+        if preempt {
+            format_next_preemptable(stats, theme)
+        } else {
+            String::new()
+        },
         if show_node_names {
             fi_slurm::parser::compress_hostlist(node_names)
         } else {
@@ -1005,12 +1288,7 @@ fn print_node_recursive(
     );
 
     let full_child_prefix = format!("{}{}", prefix, if is_last { "   " } else { "│  " });
-    let mut sorted_children: Vec<_> = current_node.children.values().collect();
-    if !sort {
-        sorted_children.sort_by(|a, b| b.stats.total_nodes.cmp(&a.stats.total_nodes));
-    } else {
-        sorted_children.sort_by(|a, b| a.name.cmp(&b.name));
-    }
+    let sorted_children = sorted_children(&current_node.children, sort);
 
     for (i, child) in sorted_children.iter().enumerate() {
         let is_child_last = i == sorted_children.len() - 1;
@@ -1018,13 +1296,202 @@ fn print_node_recursive(
             child,
             &full_child_prefix,
             is_child_last,
-            no_color,
+            theme,
             (max_width, bar_width, nodes_final_width, cpus_final_width),
             col_widths,
             show_node_names,
             sort,
             (max.0, max.1),
+            preempt,
             gpu,
+            forecast,
+            load,
+            reserved,
         );
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A node with 64 CPUs, 24 of them allocated, and 4 GPUs, 3 of them allocated
+    fn contribution(available: bool, mixed: bool, gpu: bool, preempt: bool) -> NodeContribution {
+        let state = match (available, mixed) {
+            (true, _) => NodeState::Idle,
+            (false, true) => NodeState::Mixed,
+            (false, false) => NodeState::Down,
+        };
+        NodeContribution {
+            cpus: 64,
+            alloc_cpus: 24,
+            gpus: 4,
+            alloc_gpus: 3,
+            memory: NodeMemory {
+                total_mb: 512_000,
+                allocated_mb: 128_000,
+            },
+            availability: NodeAvailability::new(&state, (64, 24), (4, 3)),
+            preemptable: true,
+            next_preemptable: None,
+            soon: None,
+            reserved: None,
+            load: None,
+            gpu,
+            preempt,
+        }
+    }
+
+    #[test]
+    fn test_used_bar_matches_percentage() {
+        let theme = Theme::plain();
+        for (alloc_cpus, percent, filled) in [(48, "75%", 15), (64, "100%", 20), (0, "0%", 0)] {
+            let stats = ReportLine {
+                total_cpus: 64,
+                alloc_cpus,
+                // idle capacity, which the bar used to show, doesn't change it
+                idle_cpus: 64 - alloc_cpus,
+                ..Default::default()
+            };
+            assert_eq!(format_utilization(&stats, &theme).trim(), percent);
+            let bar = create_used_bar(&stats, 20, &theme);
+            assert_eq!(bar.matches('█').count(), filled, "{}", bar);
+        }
+    }
+
+    #[test]
+    fn test_load_flags() {
+        let mut load = LoadLine::default();
+        load.add(7.9, 16); // wasting more than half its cores
+        load.add(16.0, 16);
+        load.add(24.5, 16); // close enough, given the slack for system daemons
+        load.add(2.5, 0); // running more than Slurm gave out
+        load.add(0.3, 0);
+        assert_eq!((load.wasted_nodes, load.oversubscribed_nodes), (1, 1));
+        assert_eq!(load.alloc_cpus, 48);
+        assert!((load.load - 51.2).abs() < 1e-9);
+    }
+
+    fn accumulate(node: NodeContribution) -> ReportLine {
+        let mut line = ReportLine::default();
+        line.accumulate(&node);
+        line
+    }
+
+    #[test]
+    fn test_accumulate_available_node() {
+        for gpu in [false, true] {
+            let (total, free) = if gpu { (4, 1) } else { (64, 40) };
+
+            let line = accumulate(contribution(true, false, gpu, false));
+            assert_eq!((line.total_nodes, line.idle_nodes), (1, 1));
+            assert_eq!((line.total_cpus, line.idle_cpus), (total, free));
+            assert_eq!((line.preempt_nodes, line.preempt_cpus), (None, None));
+
+            // with --preempt, all the resources of an available node count as idle
+            let line = accumulate(contribution(true, false, gpu, true));
+            assert_eq!(line.idle_cpus, total);
+            assert_eq!(
+                (line.preempt_nodes, line.preempt_cpus),
+                (Some(1), Some(total))
+            );
+        }
+    }
+
+    #[test]
+    fn test_accumulate_mixed_node() {
+        for gpu in [false, true] {
+            let (total, alloc, free) = if gpu { (4, 3, 1) } else { (64, 24, 40) };
+
+            let line = accumulate(contribution(false, true, gpu, false));
+            assert_eq!((line.total_nodes, line.idle_nodes), (1, 0));
+            assert_eq!(
+                (line.total_cpus, line.alloc_cpus, line.idle_cpus),
+                (total, alloc, free)
+            );
+            assert_eq!(line.preempt_cpus, None);
+
+            let line = accumulate(contribution(false, true, gpu, true));
+            assert_eq!(line.idle_cpus, free);
+            assert_eq!((line.preempt_nodes, line.preempt_cpus), (None, Some(free)));
+        }
+    }
+
+    #[test]
+    fn test_accumulate_unavailable_node() {
+        for (gpu, preempt) in [(false, false), (false, true), (true, false), (true, true)] {
+            let line = accumulate(contribution(false, false, gpu, preempt));
+            assert_eq!(
+                (line.total_nodes, line.idle_nodes, line.idle_cpus),
+                (1, 0, 0)
+            );
+            assert_eq!((line.preempt_nodes, line.preempt_cpus), (None, None));
+        }
+    }
+
+    #[test]
+    fn test_accumulate_preempt_gpus() {
+        // the GPUs freed by preemption are counted whichever resource the report is about
+        for gpu in [false, true] {
+            let line = accumulate(contribution(true, false, gpu, true));
+            assert_eq!(line.preempt_gpus, Some(4));
+            assert_eq!(
+                line.preempt_resources(gpu),
+                if gpu { Some(4) } else { Some(64) }
+            );
+
+            let line = accumulate(contribution(false, true, gpu, true));
+            assert_eq!(line.preempt_gpus, Some(1));
+            assert_eq!(
+                line.preempt_resources(gpu),
+                if gpu { Some(1) } else { Some(40) }
+            );
+
+            for (available, mixed, preempt) in [(true, false, false), (false, false, true)] {
+                let line = accumulate(contribution(available, mixed, gpu, preempt));
+                assert_eq!(line.preempt_gpus, None);
+            }
+        }
+    }
+
+    #[test]
+    fn test_merge_preempt_gpus() {
+        let mut line = accumulate(contribution(true, false, false, true));
+        line.merge(accumulate(contribution(false, true, false, true)));
+        line.merge(accumulate(contribution(false, false, false, true)));
+        assert_eq!((line.preempt_cpus, line.preempt_gpus), (Some(104), Some(5)));
+    }
+
+    #[test]
+    fn test_next_preemptable() {
+        let at = |time| NodeContribution {
+            next_preemptable: time,
+            ..contribution(false, true, false, true)
+        };
+        let mut line = accumulate(at(Some(200)));
+        line.merge(accumulate(at(None)));
+        line.merge(accumulate(at(Some(100))));
+        assert_eq!(line.next_preemptable, Some(100));
+
+        // only the preempt report looks at when jobs become preemptable
+        let line = accumulate(NodeContribution {
+            preempt: false,
+            ..at(Some(100))
+        });
+        assert_eq!(line.next_preemptable, None);
+    }
+
+    #[test]
+    fn test_soon_cpus() {
+        let soon = |soon| NodeContribution {
+            soon,
+            ..contribution(false, true, false, false)
+        };
+        let mut line = accumulate(soon(None));
+        assert_eq!(line.soon_cpus, None);
+        line.merge(accumulate(soon(Some(16))));
+        line.merge(accumulate(soon(Some(0))));
+        line.merge(accumulate(soon(Some(8))));
+        assert_eq!(line.soon_cpus, Some(24));
+    }
+}
@@ -1,24 +1,35 @@
+use crate::tree_report::ReportLine;
 use crate::tui::{
+    export::export_chart_data,
     interface::{
-        get_cpu_by_account_data_async, get_cpu_by_node_data_async,
-        get_cpu_capacity_by_account_async, get_cpu_capacity_by_node_async,
-        get_gpu_by_type_data_async, get_gpu_capacity_by_type_async,
+        get_cpu_by_account_data_async, get_cpu_by_feature_data_async, get_cpu_by_node_data_async,
+        get_cpu_by_user_data_async, get_cpu_capacity_by_account_async,
+        get_cpu_capacity_by_feature_async, get_cpu_capacity_by_node_async,
+        get_gpu_by_account_data_async, get_gpu_by_type_data_async,
+        get_gpu_capacity_by_account_async, get_gpu_capacity_by_type_async, refresh_live_data_async,
     },
+    palette::TuiPalette,
+    saved::{SavedQuery, SavedState},
     ui::ui,
 };
+use chrono::{DateTime, Local, TimeDelta, Utc};
 use crossterm::{
     event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode},
     execute,
     terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
 };
-use fi_prometheus::PrometheusTimeScale;
+use fi_prometheus::{PrometheusTimeScale, query_timeout};
+use fi_slurm::theme::Theme;
+use fi_slurm::utils::{SlurmConfig, initialize_slurm};
 use ratatui::{
     Terminal,
     backend::{Backend, CrosstermBackend},
 };
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
 use std::io;
-use std::time::Duration;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
 use thiserror::Error;
 use tokio::sync::mpsc;
 
@@ -28,6 +39,10 @@ pub const CHART_HEIGHT: u16 = 10;
 pub const MAX_BARS_PER_CHART: usize = 20;
 pub const BAR_WIDTH: u16 = 3;
 pub const BAR_GAP: u16 = 1;
+// the fewest bars zooming leaves a window split into, before it switches to a finer step
+const MIN_ZOOM_STEPS: i64 = 10;
+// the longest window zooming out reaches, 10 years, far beyond any retention of Prometheus
+const MAX_ZOOM_SECS: i64 = 10 * 365 * 86400;
 
 // --- Data Structures ---
 
@@ -41,15 +56,44 @@ pub enum AppError {
     ChannelSend(String),
     #[error("Failed to get maximum capacity: {0}")]
     MaxFail(String),
-    #[error("Data fetching timed out after 20 seconds")]
+    #[error("Data fetching timed out")]
     TimeOut,
+    #[error("Failed to export the charts: {0}")]
+    Export(String),
 }
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum AppView {
+    /// The current cluster state from Slurm, rather than history from Prometheus
+    Live,
+    /// The usage of every account stacked into one chart, against the cluster's capacity
+    ClusterTotal,
     CpuByAccount,
     CpuByNode,
     GpuByType,
+    GpuByAccount,
+    /// The cores in use on the nodes with each feature, such as icelake or genoa
+    CpuByFeature,
+}
+
+impl AppView {
+    /// Whether the view is a grid of charts, which can be paged and scrolled through
+    pub fn has_chart_grid(self) -> bool {
+        matches!(
+            self,
+            AppView::CpuByAccount
+                | AppView::CpuByNode
+                | AppView::GpuByType
+                | AppView::GpuByAccount
+                | AppView::CpuByFeature
+        )
+    }
+
+    /// Whether every series of the view is measured against the cluster's total capacity,
+    /// rather than against a capacity of its own
+    pub fn has_shared_capacity(self) -> bool {
+        matches!(self, AppView::CpuByAccount | AppView::GpuByAccount)
+    }
 }
 
 #[derive(Debug, Default, Clone, Copy, PartialEq)]
@@ -73,12 +117,185 @@ impl DisplayMode {
         }
     }
 }
-#[derive(Debug)]
+
+/// The order of the charts of a grid, after the pinned ones
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ChartSort {
+    #[default]
+    Name,
+    /// The highest usage of the window first
+    Peak,
+    /// The highest usage of the last step first
+    Current,
+}
+
+impl ChartSort {
+    pub fn next(self) -> Self {
+        match self {
+            ChartSort::Name => ChartSort::Peak,
+            ChartSort::Peak => ChartSort::Current,
+            ChartSort::Current => ChartSort::Name,
+        }
+    }
+
+    /// What a series is sorted by, largest first, before its name
+    fn key(self, values: &[u64]) -> u64 {
+        match self {
+            ChartSort::Name => 0,
+            ChartSort::Peak => values.iter().copied().max().unwrap_or(0),
+            ChartSort::Current => values.last().copied().unwrap_or(0),
+        }
+    }
+}
+
+impl std::fmt::Display for ChartSort {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            ChartSort::Name => "Name",
+            ChartSort::Peak => "Peak",
+            ChartSort::Current => "Current",
+        };
+        write!(f, "{}", name)
+    }
+}
+#[derive(Debug, Default)]
 pub struct ChartData {
     pub source_data: HashMap<String, Vec<u64>>,
+    /// When each step of the series was, for labelling the bars
+    pub timestamps: Vec<DateTime<Utc>>,
     pub capacity_data: HashMap<String, Vec<u64>>,
     pub horizontal_scroll_offset: usize,
+    /// The series whose charts are kept on the first page, whatever the sort
+    pub pinned: BTreeSet<String>,
+    /// The series whose chart is selected in chart mode, or the first if none is
+    pub selected: Option<String>,
+    /// Whether the bars are drawn on a log scale, so that small series stay visible
+    pub log_scale: bool,
 }
+
+impl ChartData {
+    /// Pairs fetched usage with its capacity, scrolled to the most recent steps
+    fn from_fetched(usage: UsageData, capacity_data: HashMap<String, Vec<u64>>) -> Self {
+        let max_points = usage
+            .source_data
+            .values()
+            .map(|v| v.len())
+            .max()
+            .unwrap_or(0);
+        ChartData {
+            source_data: usage.source_data,
+            timestamps: usage.timestamps,
+            capacity_data,
+            horizontal_scroll_offset: max_points.saturating_sub(MAX_BARS_PER_CHART),
+            ..Default::default()
+        }
+    }
+
+    /// The series in the order their charts are drawn: the pinned ones first, then the rest,
+    /// each sorted by `sort` and then by name, leaving out those whose names don't contain
+    /// `search`, ignoring case
+    pub fn ordered_series(&self, sort: ChartSort, search: &str) -> Vec<(&String, &Vec<u64>)> {
+        let search = search.to_lowercase();
+        let mut series: Vec<_> = self
+            .source_data
+            .iter()
+            .filter(|(name, _)| name.to_lowercase().contains(&search))
+            .collect();
+        series.sort_by(|(a_name, a), (b_name, b)| {
+            let pinned = |name: &String| self.pinned.contains(name);
+            pinned(b_name)
+                .cmp(&pinned(a_name))
+                .then_with(|| sort.key(b).cmp(&sort.key(a)))
+                .then_with(|| a_name.cmp(b_name))
+        });
+        series
+    }
+
+    /// Where the selected chart is among the ordered charts
+    pub fn selected_index(&self, sort: ChartSort, search: &str) -> usize {
+        self.selected
+            .as_ref()
+            .and_then(|selected| {
+                self.ordered_series(sort, search)
+                    .iter()
+                    .position(|(name, _)| *name == selected)
+            })
+            .unwrap_or(0)
+    }
+
+    /// Replaces the charts with newly fetched ones, keeping which are pinned and selected,
+    /// and their scale
+    fn replace(&mut self, new: ChartData) {
+        *self = ChartData {
+            pinned: std::mem::take(&mut self.pinned),
+            selected: self.selected.take(),
+            log_scale: self.log_scale,
+            ..new
+        };
+    }
+}
+
+/// The charts of the window just before the one on screen, fetched with the same query so
+/// that each step can be set against the same step of the earlier window
+pub struct Comparison {
+    /// When the earlier window ends, which is when the one on screen starts
+    pub end: DateTime<Utc>,
+    /// The earlier charts of each view with a grid, in the order of
+    /// `PendingFetches::take_charts`, or None while they are being fetched
+    pub charts: Option<Result<[ChartData; 5], AppError>>,
+    rx: mpsc::Receiver<FetchedData>,
+    pending: PendingFetches,
+}
+
+impl Comparison {
+    /// Fetches the window of `range` steps before the one ending at `end`
+    fn start(range: i64, unit: PrometheusTimeScale, end: DateTime<Utc>) -> Self {
+        let end = end - TimeDelta::seconds(range * unit.step_secs());
+        let (tx, rx) = mpsc::channel(FETCH_COUNT);
+        spawn_custom_data_fetch(tx, range, unit, end);
+        Comparison {
+            end,
+            charts: None,
+            rx,
+            pending: PendingFetches::default(),
+        }
+    }
+
+    /// Takes in the results that have arrived, pairing them up into charts once all have
+    fn poll(&mut self) {
+        while let Ok(fetched_data) = self.rx.try_recv() {
+            self.pending.receive(fetched_data);
+        }
+        if self.charts.is_none() && self.pending.is_complete() {
+            self.charts = Some(self.pending.take_charts());
+        }
+    }
+
+    /// The earlier charts of a view, once they have been fetched
+    pub fn charts_of(&self, view: AppView) -> Option<&ChartData> {
+        let index = match view {
+            AppView::Live | AppView::ClusterTotal => return None,
+            AppView::CpuByAccount => 0,
+            AppView::CpuByNode => 1,
+            AppView::GpuByType => 2,
+            AppView::GpuByAccount => 3,
+            AppView::CpuByFeature => 4,
+        };
+        self.charts.as_ref()?.as_ref().ok()?.get(index)
+    }
+}
+
+/// The users of one account, opened from its chart in the cores by account view
+#[derive(Debug)]
+pub struct AccountUsers {
+    pub account: String,
+    /// The users' charts, or None while they are being fetched
+    pub charts: Option<Result<ChartData, AppError>>,
+    /// The account view's scroll and search, restored on going back to it
+    parent_scroll_offset: usize,
+    parent_search: String,
+}
+
 pub struct App {
     pub current_view: AppView,
     pub scroll_offset: usize,
@@ -86,29 +303,360 @@ pub struct App {
     pub cpu_by_account: ChartData,
     pub cpu_by_node: ChartData,
     pub gpu_by_type: ChartData,
+    pub gpu_by_account: ChartData,
+    pub cpu_by_feature: ChartData,
+    /// The account drilled down into, shown in place of the cores by account charts
+    pub account_users: Option<AccountUsers>,
+    /// The window before the one on screen, when the charts are compared with it
+    pub comparison: Option<Comparison>,
     pub should_quit: bool,
     pub query_range: i64,
     pub query_time_scale: PrometheusTimeScale,
+    /// When the charts end, or None for them to end at the time of each fetch
+    pub query_end: Option<DateTime<Utc>>,
     pub display_mode: DisplayMode,
+    pub chart_sort: ChartSort,
+    /// The text the names of the charts shown contain, ignoring case
+    pub search: String,
+    /// Whether keys are typed into the search rather than controlling the view
+    pub searching: bool,
+    /// Where the view was last exported to, shown until the next key
+    pub last_export: Option<Result<PathBuf, AppError>>,
+    /// Why the Prometheus charts are empty, if they couldn't be fetched
+    pub prometheus_error: Option<AppError>,
+    pub refresh: RefreshStatus,
+    /// How often the charts are refreshed on their own, if at all
+    pub auto_refresh: Option<Duration>,
+    /// When the charts are next refreshed on their own
+    pub next_refresh: Option<Instant>,
+}
+
+/// Whether the charts are being fetched again, while the old ones stay on screen
+#[derive(Debug, Default)]
+pub enum RefreshStatus {
+    #[default]
+    Idle,
+    Refreshing {
+        tick: usize,
+    },
+    /// The last refresh failed, so the charts are from before it
+    Failed(AppError),
 }
 
 impl App {
     fn next_view(&mut self) {
+        self.close_account_users();
         self.current_view = match self.current_view {
+            AppView::Live => AppView::ClusterTotal,
+            AppView::ClusterTotal => AppView::CpuByAccount,
             AppView::CpuByAccount => AppView::CpuByNode,
             AppView::CpuByNode => AppView::GpuByType,
-            AppView::GpuByType => AppView::CpuByAccount,
+            AppView::GpuByType => AppView::GpuByAccount,
+            AppView::GpuByAccount => AppView::CpuByFeature,
+            AppView::CpuByFeature => AppView::Live,
         };
         self.scroll_offset = 0;
     }
 
     fn prev_view(&mut self) {
+        self.close_account_users();
         self.current_view = match self.current_view {
-            AppView::CpuByAccount => AppView::GpuByType,
+            AppView::Live => AppView::CpuByFeature,
+            AppView::ClusterTotal => AppView::Live,
+            AppView::CpuByAccount => AppView::ClusterTotal,
             AppView::CpuByNode => AppView::CpuByAccount,
             AppView::GpuByType => AppView::CpuByNode,
+            AppView::GpuByAccount => AppView::GpuByType,
+            AppView::CpuByFeature => AppView::GpuByAccount,
+        };
+        self.scroll_offset = 0;
+    }
+
+    fn set_view(&mut self, view: AppView) {
+        self.close_account_users();
+        self.current_view = view;
+        self.scroll_offset = 0;
+    }
+
+    /// How many charts the grid of the view has, after the search
+    fn num_charts(&self) -> usize {
+        self.current_chart_data().map_or(0, |data| {
+            data.ordered_series(self.chart_sort, &self.search).len()
+        })
+    }
+
+    /// The grid of charts on screen: the users of an opened account, or the view's own
+    pub fn current_chart_data(&self) -> Option<&ChartData> {
+        if let Some(users) = &self.account_users {
+            return users
+                .charts
+                .as_ref()
+                .and_then(|charts| charts.as_ref().ok());
+        }
+        match self.current_view {
+            AppView::Live | AppView::ClusterTotal => None,
+            AppView::CpuByAccount => Some(&self.cpu_by_account),
+            AppView::CpuByNode => Some(&self.cpu_by_node),
+            AppView::GpuByType => Some(&self.gpu_by_type),
+            AppView::GpuByAccount => Some(&self.gpu_by_account),
+            AppView::CpuByFeature => Some(&self.cpu_by_feature),
+        }
+    }
+
+    fn current_chart_data_mut(&mut self) -> Option<&mut ChartData> {
+        if let Some(users) = &mut self.account_users {
+            return users
+                .charts
+                .as_mut()
+                .and_then(|charts| charts.as_mut().ok());
+        }
+        match self.current_view {
+            AppView::Live | AppView::ClusterTotal => None,
+            AppView::CpuByAccount => Some(&mut self.cpu_by_account),
+            AppView::CpuByNode => Some(&mut self.cpu_by_node),
+            AppView::GpuByType => Some(&mut self.gpu_by_type),
+            AppView::GpuByAccount => Some(&mut self.gpu_by_account),
+            AppView::CpuByFeature => Some(&mut self.cpu_by_feature),
+        }
+    }
+
+    /// Selects the chart `step` charts after the selected one, wrapping around, and scrolls
+    /// the grid so it's in view
+    fn move_selection(&mut self, step: isize, num_cols: usize, num_visible_rows: usize) {
+        let (sort, search) = (self.chart_sort, self.search.clone());
+        let Some(data) = self.current_chart_data_mut() else {
+            return;
         };
+        let ordered = data.ordered_series(sort, &search);
+        if ordered.is_empty() {
+            return;
+        }
+        let index = (data.selected_index(sort, &search) as isize + step)
+            .rem_euclid(ordered.len() as isize) as usize;
+        data.selected = Some(ordered[index].0.clone());
+
+        let row = index / num_cols;
+        if row < self.scroll_offset {
+            self.scroll_offset = row;
+        } else if row >= self.scroll_offset + num_visible_rows.max(1) {
+            self.scroll_offset = row + 1 - num_visible_rows.max(1);
+        }
+    }
+
+    /// Pins the selected chart to the first page, or unpins it if it already is
+    fn toggle_pin(&mut self) {
+        let (sort, search) = (self.chart_sort, self.search.clone());
+        let Some(data) = self.current_chart_data_mut() else {
+            return;
+        };
+        let ordered = data.ordered_series(sort, &search);
+        let Some((name, _)) = ordered.get(data.selected_index(sort, &search)) else {
+            return;
+        };
+        let name = name.to_string();
+        if !data.pinned.remove(&name) {
+            data.pinned.insert(name.clone());
+        }
+        // the selection follows the chart to its new place
+        data.selected = Some(name);
+    }
+
+    /// Opens the users of the selected account, fetching their usage over the same range
+    fn open_account_users(&mut self, users_rx: &mut mpsc::Receiver<Result<UsageData, AppError>>) {
+        if self.current_view != AppView::CpuByAccount || self.account_users.is_some() {
+            return;
+        }
+        let ordered = self
+            .cpu_by_account
+            .ordered_series(self.chart_sort, &self.search);
+        let index = self
+            .cpu_by_account
+            .selected_index(self.chart_sort, &self.search);
+        let Some((account, _)) = ordered.get(index) else {
+            return;
+        };
+        let account = account.to_string();
+
+        let (tx, rx) = mpsc::channel(1);
+        // a fetch for an account gone back from is dropped with its receiver
+        *users_rx = rx;
+        tokio::spawn(get_cpu_by_user_data_async(
+            tx,
+            account.clone(),
+            self.query_range,
+            self.query_time_scale,
+            self.query_end.unwrap_or_else(Utc::now),
+        ));
+
+        self.account_users = Some(AccountUsers {
+            account,
+            charts: None,
+            parent_scroll_offset: self.scroll_offset,
+            parent_search: std::mem::take(&mut self.search),
+        });
         self.scroll_offset = 0;
+        self.scroll_mode = ScrollMode::Page;
+    }
+
+    /// Writes the series of the view on screen to a CSV file
+    fn export(&self) -> Result<PathBuf, AppError> {
+        let no_series = || AppError::Export("the view has no series to export".to_string());
+        let name = match (self.current_view, &self.account_users) {
+            (AppView::Live, _) => return Err(no_series()),
+            (AppView::ClusterTotal, _) => "cluster total".to_string(),
+            (AppView::CpuByAccount, Some(users)) => format!("cores of {}", users.account),
+            (AppView::CpuByAccount, None) => "cores by account".to_string(),
+            (AppView::CpuByNode, _) => "cores by node".to_string(),
+            (AppView::GpuByType, _) => "gpus by type".to_string(),
+            (AppView::GpuByAccount, _) => "gpus by account".to_string(),
+            (AppView::CpuByFeature, _) => "cores by feature".to_string(),
+        };
+        // the total chart stacks the series of the accounts
+        let data = if self.current_view == AppView::ClusterTotal {
+            Some(&self.cpu_by_account)
+        } else {
+            self.current_chart_data()
+        };
+        match data {
+            Some(data) if !data.source_data.is_empty() => {
+                export_chart_data(data, &name, self.current_view.has_shared_capacity())
+            }
+            _ => Err(no_series()),
+        }
+    }
+
+    /// Goes back from the users of an account to the account charts
+    fn close_account_users(&mut self) {
+        if let Some(users) = self.account_users.take() {
+            self.scroll_offset = users.parent_scroll_offset;
+            self.search = users.parent_search;
+            self.scroll_mode = ScrollMode::Page;
+        }
+    }
+
+    /// Fetches the charts again with the current parameters, unless a refresh is under way,
+    /// and restarts the countdown to the next automatic refresh either way
+    fn start_refresh(
+        &mut self,
+        rx: &mut mpsc::Receiver<FetchedData>,
+        pending: &mut PendingFetches,
+    ) {
+        self.next_refresh = self.auto_refresh.map(|interval| Instant::now() + interval);
+        if matches!(self.refresh, RefreshStatus::Refreshing { .. }) {
+            return;
+        }
+        self.fetch(rx, pending);
+    }
+
+    /// Fetches the charts with the current parameters, dropping any fetch under way, along
+    /// with the window before them if they are being compared with it
+    fn fetch(&mut self, rx: &mut mpsc::Receiver<FetchedData>, pending: &mut PendingFetches) {
+        let end = self.query_end.unwrap_or_else(Utc::now);
+        start_fetch(rx, pending, self.query_range, self.query_time_scale, end);
+        if self.comparison.is_some() {
+            self.comparison = Some(Comparison::start(
+                self.query_range,
+                self.query_time_scale,
+                end,
+            ));
+        }
+        self.refresh = RefreshStatus::Refreshing { tick: 0 };
+    }
+
+    /// Starts or stops comparing the charts with the window before them
+    fn toggle_comparison(&mut self) {
+        self.comparison = match self.comparison {
+            Some(_) => None,
+            None => Some(Comparison::start(
+                self.query_range,
+                self.query_time_scale,
+                self.query_end.unwrap_or_else(Utc::now),
+            )),
+        };
+    }
+
+    /// Halves or doubles the length of the window, up to `MAX_ZOOM_SECS`, keeping when it
+    /// ends, with the coarsest step that still splits it into enough bars
+    fn zoom(&mut self, zoom_in: bool) {
+        let secs = self.query_range * self.query_time_scale.step_secs();
+        let secs = if zoom_in {
+            secs / 2
+        } else {
+            (secs * 2).min(MAX_ZOOM_SECS)
+        };
+        let scale = [
+            PrometheusTimeScale::Years,
+            PrometheusTimeScale::Weeks,
+            PrometheusTimeScale::Days,
+            PrometheusTimeScale::Hours,
+        ]
+        .into_iter()
+        .find(|scale| secs / scale.step_secs() >= MIN_ZOOM_STEPS)
+        .unwrap_or(PrometheusTimeScale::Minutes);
+        self.query_range = (secs / scale.step_secs()).max(1);
+        self.query_time_scale = scale;
+    }
+
+    /// Moves the window earlier or later by half its length, following now again once it
+    /// would end past it
+    fn pan(&mut self, earlier: bool) {
+        let now = Utc::now();
+        let half = TimeDelta::seconds(self.query_range * self.query_time_scale.step_secs() / 2);
+        let end = self.query_end.unwrap_or(now);
+        let end = if earlier {
+            end.checked_sub_signed(half).unwrap_or(end)
+        } else {
+            end.checked_add_signed(half).unwrap_or(now)
+        };
+        self.query_end = (end < now).then_some(end);
+    }
+}
+
+/// One line of the tree report, as shown in the live view
+#[derive(Debug, Clone)]
+pub struct LiveRow {
+    /// How far below the cluster total the feature is nested
+    pub depth: usize,
+    pub name: String,
+    pub stats: ReportLine,
+}
+
+/// The cluster state as last loaded from Slurm
+#[derive(Debug, Clone)]
+pub struct LiveData {
+    pub taken_at: DateTime<Local>,
+    pub rows: Vec<LiveRow>,
+    /// The GPUs of the cluster, counted in the CPU fields as in the GPU report
+    pub gpus: ReportLine,
+}
+
+impl LiveData {
+    /// The whole cluster, the first row of the tree
+    pub fn totals(&self) -> Option<&ReportLine> {
+        self.rows.first().map(|row| &row.stats)
+    }
+}
+
+/// The live view's data, which is kept when a later refresh fails
+#[derive(Debug, Default)]
+pub struct LiveStatus {
+    pub data: Option<LiveData>,
+    pub error: Option<AppError>,
+}
+
+impl LiveStatus {
+    fn update(&mut self, result: Result<LiveData, AppError>) {
+        match result {
+            Ok(data) => {
+                self.data = Some(data);
+                self.error = None;
+            }
+            Err(e) => self.error = Some(e),
+        }
+    }
+
+    fn num_rows(&self) -> usize {
+        self.data.as_ref().map_or(0, |data| data.rows.len())
     }
 }
 
@@ -134,6 +682,7 @@ pub enum ParameterFocus {
     #[default]
     Range,
     Unit,
+    AutoRefresh,
     Confirm,
 }
 
@@ -141,7 +690,8 @@ impl ParameterFocus {
     fn next(&self) -> Self {
         match self {
             ParameterFocus::Range => ParameterFocus::Unit,
-            ParameterFocus::Unit => ParameterFocus::Confirm,
+            ParameterFocus::Unit => ParameterFocus::AutoRefresh,
+            ParameterFocus::AutoRefresh => ParameterFocus::Confirm,
             ParameterFocus::Confirm => ParameterFocus::Range,
         }
     }
@@ -150,17 +700,43 @@ impl ParameterFocus {
 #[derive(Debug, Default)]
 pub struct ParameterSelectionState {
     pub range_input: String,
+    /// Minutes between refreshes of the charts, or empty for none
+    pub auto_refresh_input: String,
     pub selected_unit: PrometheusTimeScale,
     pub focused_widget: ParameterFocus,
 }
 
+impl ParameterSelectionState {
+    /// The parameter screen filled in with the last confirmed query, if there is one
+    fn with_query(query: Option<SavedQuery>) -> Self {
+        let Some(query) = query else {
+            return Self::default();
+        };
+        Self {
+            range_input: query.range.to_string(),
+            auto_refresh_input: query
+                .auto_refresh_minutes
+                .map_or_else(String::new, |minutes| minutes.to_string()),
+            selected_unit: query.unit,
+            focused_widget: ParameterFocus::default(),
+        }
+    }
+}
+
 // MODIFIED: The AppState enum now includes all application states.
 #[allow(clippy::large_enum_variant)]
 //#[derive(Debug, Clone)]
 pub enum AppState {
-    MainMenu { selected: MainMenuSelection },
+    MainMenu {
+        selected: MainMenuSelection,
+        /// The last confirmed custom query, which the parameter screen starts from
+        last_query: Option<SavedQuery>,
+    },
     ParameterSelection(ParameterSelectionState),
-    Loading { tick: usize },
+    Loading {
+        tick: usize,
+        progress: FetchProgress,
+    },
     Loaded(App),
     Error(AppError),
 }
@@ -168,6 +744,8 @@ pub enum AppState {
 #[derive(Debug)]
 pub struct UsageData {
     pub source_data: HashMap<String, Vec<u64>>,
+    /// When each step of the series was
+    pub timestamps: Vec<DateTime<Utc>>,
 }
 
 #[derive(Debug)]
@@ -180,72 +758,281 @@ pub enum FetchedData {
     CpuByAccount(Result<UsageData, AppError>),
     CpuByNode(Result<UsageData, AppError>),
     GpuByType(Result<UsageData, AppError>),
+    GpuByAccount(Result<UsageData, AppError>),
+    CpuByFeature(Result<UsageData, AppError>),
     CpuCapacityByAccount(Result<CapacityData, AppError>),
     CpuCapacityByNode(Result<CapacityData, AppError>),
     GpuCapacityByType(Result<CapacityData, AppError>),
+    GpuCapacityByAccount(Result<CapacityData, AppError>),
+    CpuCapacityByFeature(Result<CapacityData, AppError>),
+}
+
+/// The number of queries dispatched by `spawn_custom_data_fetch`
+const FETCH_COUNT: usize = 10;
+
+/// How far the queries of a fetch have got, for the loading screen
+#[derive(Debug, Default, Clone)]
+pub struct FetchProgress {
+    pub complete: usize,
+    pub total: usize,
+    /// The queries that failed, by what they fetch, with why
+    pub failed: Vec<(&'static str, AppError)>,
 }
 
-fn spawn_custom_data_fetch(tx: mpsc::Sender<FetchedData>, range: i64, unit: PrometheusTimeScale) {
-    tokio::spawn(get_cpu_by_account_data_async(tx.clone(), range, unit));
-    tokio::spawn(get_cpu_by_node_data_async(tx.clone(), range, unit));
-    tokio::spawn(get_gpu_by_type_data_async(tx.clone(), range, unit));
-    tokio::spawn(get_cpu_capacity_by_account_async(tx.clone(), range, unit));
-    tokio::spawn(get_cpu_capacity_by_node_async(tx.clone(), range, unit));
-    tokio::spawn(get_gpu_capacity_by_type_async(tx.clone(), range, unit));
+/// The results of the Prometheus queries of one fetch, as they arrive
+#[derive(Default)]
+struct PendingFetches {
+    cpu_by_account_data: Option<Result<UsageData, AppError>>,
+    cpu_by_node_data: Option<Result<UsageData, AppError>>,
+    gpu_by_type_data: Option<Result<UsageData, AppError>>,
+    gpu_by_account_data: Option<Result<UsageData, AppError>>,
+    cpu_by_feature_data: Option<Result<UsageData, AppError>>,
+    cpu_by_account_capacity: Option<Result<CapacityData, AppError>>,
+    cpu_by_node_capacity: Option<Result<CapacityData, AppError>>,
+    gpu_by_type_capacity: Option<Result<CapacityData, AppError>>,
+    gpu_by_account_capacity: Option<Result<CapacityData, AppError>>,
+    cpu_by_feature_capacity: Option<Result<CapacityData, AppError>>,
+    count: usize,
+    /// The fetch was given up on, so none of its results are coming
+    cancelled: bool,
+}
+
+impl PendingFetches {
+    fn receive(&mut self, fetched_data: FetchedData) {
+        self.count += 1;
+        match fetched_data {
+            FetchedData::CpuByAccount(res) => self.cpu_by_account_data = Some(res),
+            FetchedData::CpuByNode(res) => self.cpu_by_node_data = Some(res),
+            FetchedData::GpuByType(res) => self.gpu_by_type_data = Some(res),
+            FetchedData::GpuByAccount(res) => self.gpu_by_account_data = Some(res),
+            FetchedData::CpuByFeature(res) => self.cpu_by_feature_data = Some(res),
+            FetchedData::CpuCapacityByAccount(res) => self.cpu_by_account_capacity = Some(res),
+            FetchedData::CpuCapacityByNode(res) => self.cpu_by_node_capacity = Some(res),
+            FetchedData::GpuCapacityByType(res) => self.gpu_by_type_capacity = Some(res),
+            FetchedData::GpuCapacityByAccount(res) => self.gpu_by_account_capacity = Some(res),
+            FetchedData::CpuCapacityByFeature(res) => self.cpu_by_feature_capacity = Some(res),
+        }
+    }
+
+    fn is_complete(&self) -> bool {
+        self.count == FETCH_COUNT
+    }
+
+    fn progress(&self) -> FetchProgress {
+        fn failure<T>(
+            name: &'static str,
+            result: &Option<Result<T, AppError>>,
+        ) -> Option<(&'static str, AppError)> {
+            match result {
+                Some(Err(e)) => Some((name, e.clone())),
+                _ => None,
+            }
+        }
+
+        let failed = [
+            failure("cores by account", &self.cpu_by_account_data),
+            failure("cores by node", &self.cpu_by_node_data),
+            failure("GPUs by type", &self.gpu_by_type_data),
+            failure("GPUs by account", &self.gpu_by_account_data),
+            failure("cores by feature", &self.cpu_by_feature_data),
+            failure("core capacity by account", &self.cpu_by_account_capacity),
+            failure("core capacity by node", &self.cpu_by_node_capacity),
+            failure("GPU capacity by type", &self.gpu_by_type_capacity),
+            failure("GPU capacity by account", &self.gpu_by_account_capacity),
+            failure("core capacity by feature", &self.cpu_by_feature_capacity),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        FetchProgress {
+            complete: self.count,
+            total: FETCH_COUNT,
+            failed,
+        }
+    }
+
+    /// Pairs each view's usage with its capacity, or returns the first error of any query
+    fn take_charts(&mut self) -> Result<[ChartData; 5], AppError> {
+        fn chart(
+            usage: Option<Result<UsageData, AppError>>,
+            capacity: Option<Result<CapacityData, AppError>>,
+        ) -> Result<ChartData, AppError> {
+            let missing = || AppError::DataFetch("a query returned no data".to_string());
+            let usage = usage.ok_or_else(missing)??;
+            let capacity = capacity.ok_or_else(missing)??;
+            Ok(ChartData::from_fetched(usage, capacity.capacities))
+        }
+
+        Ok([
+            chart(
+                self.cpu_by_account_data.take(),
+                self.cpu_by_account_capacity.take(),
+            )?,
+            chart(
+                self.cpu_by_node_data.take(),
+                self.cpu_by_node_capacity.take(),
+            )?,
+            chart(
+                self.gpu_by_type_data.take(),
+                self.gpu_by_type_capacity.take(),
+            )?,
+            chart(
+                self.gpu_by_account_data.take(),
+                self.gpu_by_account_capacity.take(),
+            )?,
+            chart(
+                self.cpu_by_feature_data.take(),
+                self.cpu_by_feature_capacity.take(),
+            )?,
+        ])
+    }
+}
+
+/// Gives up on the fetch under way: its queries can't be stopped once sent, so they run on,
+/// but their results are dropped along with the receiver
+fn cancel_fetch(rx: &mut mpsc::Receiver<FetchedData>, pending: &mut PendingFetches) {
+    let (_, rx_new) = mpsc::channel(FETCH_COUNT);
+    *rx = rx_new;
+    *pending = PendingFetches {
+        cancelled: true,
+        ..Default::default()
+    };
+}
+
+/// How many ticks of the event loop a fetch of `range` steps is waited for, a little longer
+/// than its queries are given, so that they time out first and say which did
+fn loading_timeout_ticks(range: i64) -> usize {
+    const TICK_MILLIS: u128 = 100;
+    const SLACK_TICKS: usize = 20;
+    (query_timeout(range).as_millis() / TICK_MILLIS) as usize + SLACK_TICKS
+}
+
+/// Dispatches the queries for the range, dropping the results of any fetch still in flight
+fn start_fetch(
+    rx: &mut mpsc::Receiver<FetchedData>,
+    pending: &mut PendingFetches,
+    range: i64,
+    unit: PrometheusTimeScale,
+    end: DateTime<Utc>,
+) {
+    let (tx_new, rx_new) = mpsc::channel(FETCH_COUNT);
+    *rx = rx_new;
+    *pending = PendingFetches::default();
+    spawn_custom_data_fetch(tx_new, range, unit, end);
+}
+
+fn spawn_custom_data_fetch(
+    tx: mpsc::Sender<FetchedData>,
+    range: i64,
+    unit: PrometheusTimeScale,
+    end: DateTime<Utc>,
+) {
+    tokio::spawn(get_cpu_by_account_data_async(tx.clone(), range, unit, end));
+    tokio::spawn(get_cpu_by_node_data_async(tx.clone(), range, unit, end));
+    tokio::spawn(get_gpu_by_type_data_async(tx.clone(), range, unit, end));
+    tokio::spawn(get_gpu_by_account_data_async(tx.clone(), range, unit, end));
+    tokio::spawn(get_cpu_by_feature_data_async(tx.clone(), range, unit, end));
+    tokio::spawn(get_cpu_capacity_by_account_async(
+        tx.clone(),
+        range,
+        unit,
+        end,
+    ));
+    tokio::spawn(get_cpu_capacity_by_node_async(tx.clone(), range, unit, end));
+    tokio::spawn(get_gpu_capacity_by_type_async(tx.clone(), range, unit, end));
+    tokio::spawn(get_gpu_capacity_by_account_async(
+        tx.clone(),
+        range,
+        unit,
+        end,
+    ));
+    tokio::spawn(get_cpu_capacity_by_feature_async(
+        tx.clone(),
+        range,
+        unit,
+        end,
+    ));
 }
 
 async fn run_app<B: Backend>(
     terminal: &mut Terminal<B>,
     mut rx: mpsc::Receiver<FetchedData>,
+    mut live_rx: mpsc::Receiver<Result<LiveData, AppError>>,
+    palette: TuiPalette,
 ) -> io::Result<()> {
-    const LOADING_TIMEOUT_TICKS: usize = 200;
+    let mut saved = SavedState::load();
     // Start the app in the MainMenu state.
     let mut app_state = AppState::MainMenu {
         selected: MainMenuSelection::Default,
+        last_query: saved.query,
     };
 
-    let mut cpu_by_account_data: Option<Result<UsageData, AppError>> = None;
-    let mut cpu_by_node_data: Option<Result<UsageData, AppError>> = None;
-    let mut gpu_by_type_data: Option<Result<UsageData, AppError>> = None;
-    let mut cpu_by_account_capacity: Option<Result<CapacityData, AppError>> = None;
-    let mut cpu_by_node_capacity: Option<Result<CapacityData, AppError>> = None;
-    let mut gpu_by_type_capacity: Option<Result<CapacityData, AppError>> = None;
-
-    let mut data_fetch_count = 0;
+    let mut pending = PendingFetches::default();
+    let mut live = LiveStatus::default();
+    let mut show_help = false;
+    // replaced each time an account is drilled down into
+    let (_, mut users_rx) = mpsc::channel::<Result<UsageData, AppError>>(1);
 
     let mut current_query_range = 30;
     let mut current_query_time_scale = PrometheusTimeScale::Days;
+    let mut current_auto_refresh: Option<Duration> = None;
 
     loop {
-        terminal.draw(|f| ui(f, &app_state))?;
+        terminal.draw(|f| ui(f, &app_state, &live, show_help, &palette))?;
 
-        if data_fetch_count < 6
+        if let Ok(live_data) = live_rx.try_recv() {
+            live.update(live_data);
+        }
+
+        if let Ok(users) = users_rx.try_recv()
+            && let AppState::Loaded(app) = &mut app_state
+            && let Some(account_users) = &mut app.account_users
+        {
+            // the users' charts are measured against the cluster, as the account's are
+            let capacity = app.cpu_by_account.capacity_data.clone();
+            account_users.charts =
+                Some(users.map(|usage| ChartData::from_fetched(usage, capacity)));
+        }
+
+        if !pending.is_complete()
             && let Ok(fetched_data) = rx.try_recv()
         {
-            data_fetch_count += 1;
-            match fetched_data {
-                FetchedData::CpuByAccount(res) => cpu_by_account_data = Some(res),
-                FetchedData::CpuByNode(res) => cpu_by_node_data = Some(res),
-                FetchedData::GpuByType(res) => gpu_by_type_data = Some(res),
-                FetchedData::CpuCapacityByAccount(res) => cpu_by_account_capacity = Some(res),
-                FetchedData::CpuCapacityByNode(res) => cpu_by_node_capacity = Some(res),
-                FetchedData::GpuCapacityByType(res) => gpu_by_type_capacity = Some(res),
-            }
+            pending.receive(fetched_data);
+        }
+
+        if let AppState::Loaded(app) = &mut app_state
+            && let Some(comparison) = &mut app.comparison
+        {
+            comparison.poll();
         }
 
         if event::poll(Duration::from_millis(100))?
             && let Event::Key(key) = event::read()?
         {
+            // the overlay takes every key until it's closed
+            if show_help {
+                if matches!(key.code, KeyCode::Char('?') | KeyCode::Esc) {
+                    show_help = false;
+                }
+                continue;
+            }
+            let searching = matches!(&app_state, AppState::Loaded(app) if app.searching);
+            if key.code == KeyCode::Char('?') && !searching {
+                show_help = true;
+                continue;
+            }
+
             if key.code == KeyCode::Char('q') {
                 if let AppState::Loaded(ref mut app) = app_state {
-                    app.should_quit = true;
+                    // a q typed into the search is part of it
+                    app.should_quit = !app.searching;
                 } else {
                     return Ok(());
                 }
             }
 
             match &mut app_state {
-                AppState::MainMenu { selected } => match key.code {
+                AppState::MainMenu { selected, .. } => match key.code {
                     KeyCode::Up
                     | KeyCode::PageUp
                     | KeyCode::Down
@@ -254,24 +1041,40 @@ async fn run_app<B: Backend>(
                     | KeyCode::Char('j') => *selected = selected.toggle(),
                     KeyCode::Enter => match selected {
                         MainMenuSelection::Default => {
-                            if data_fetch_count == 6 {
+                            // the fetch of the default dashboard started at launch, unless it
+                            // was cancelled since
+                            if pending.cancelled {
+                                current_query_range = 30;
+                                current_query_time_scale = PrometheusTimeScale::Days;
+                                current_auto_refresh = None;
+                                start_fetch(
+                                    &mut rx,
+                                    &mut pending,
+                                    current_query_range,
+                                    current_query_time_scale,
+                                    Utc::now(),
+                                );
+                            }
+                            if pending.is_complete() {
                                 app_state = build_loaded_app(
-                                    &mut cpu_by_account_data,
-                                    &mut cpu_by_node_data,
-                                    &mut gpu_by_type_data,
-                                    &mut cpu_by_account_capacity,
-                                    &mut cpu_by_node_capacity,
-                                    &mut gpu_by_type_capacity,
+                                    &mut pending,
                                     current_query_range,
                                     current_query_time_scale,
+                                    current_auto_refresh,
+                                    saved.view,
+                                    &live,
                                 );
                             } else {
-                                app_state = AppState::Loading { tick: 0 };
+                                app_state = AppState::Loading {
+                                    tick: 0,
+                                    progress: pending.progress(),
+                                };
                             }
                         }
                         MainMenuSelection::Custom => {
-                            app_state =
-                                AppState::ParameterSelection(ParameterSelectionState::default());
+                            app_state = AppState::ParameterSelection(
+                                ParameterSelectionState::with_query(saved.query),
+                            );
                         }
                     },
                     _ => {}
@@ -288,6 +1091,9 @@ async fn run_app<B: Backend>(
                         (KeyCode::Enter, ParameterFocus::Unit) => {
                             state.focused_widget = state.focused_widget.next()
                         }
+                        (KeyCode::Enter, ParameterFocus::AutoRefresh) => {
+                            state.focused_widget = state.focused_widget.next()
+                        }
 
                         // --- Range Input Keys ---
                         (KeyCode::Char(c), ParameterFocus::Range) if c.is_ascii_digit() => {
@@ -297,6 +1103,14 @@ async fn run_app<B: Backend>(
                             state.range_input.pop();
                         }
 
+                        // --- Auto-Refresh Input Keys ---
+                        (KeyCode::Char(c), ParameterFocus::AutoRefresh) if c.is_ascii_digit() => {
+                            state.auto_refresh_input.push(c);
+                        }
+                        (KeyCode::Backspace, ParameterFocus::AutoRefresh) => {
+                            state.auto_refresh_input.pop();
+                        }
+
                         // --- Unit Selector Keys ---
                         (KeyCode::Left, ParameterFocus::Unit) => {
                             state.selected_unit = state.selected_unit.prev();
@@ -316,21 +1130,35 @@ async fn run_app<B: Backend>(
                             if let Ok(range) = state.range_input.parse::<i64>()
                                 && range > 0
                             {
-                                let (tx_new, rx_new) = mpsc::channel(6);
-                                rx = rx_new;
-                                cpu_by_account_data = None;
-                                cpu_by_node_data = None;
-                                gpu_by_type_data = None;
-                                cpu_by_account_capacity = None;
-                                cpu_by_node_capacity = None;
-                                gpu_by_type_capacity = None;
-                                data_fetch_count = 0;
-
                                 current_query_range = range;
                                 current_query_time_scale = state.selected_unit;
+                                // blank or zero minutes turns auto-refresh off
+                                let auto_refresh_minutes = state
+                                    .auto_refresh_input
+                                    .parse::<u64>()
+                                    .ok()
+                                    .filter(|&minutes| minutes > 0);
+                                current_auto_refresh = auto_refresh_minutes
+                                    .map(|minutes| Duration::from_secs(minutes * 60));
+
+                                saved.query = Some(SavedQuery {
+                                    range,
+                                    unit: state.selected_unit,
+                                    auto_refresh_minutes,
+                                });
+                                saved.save();
 
-                                spawn_custom_data_fetch(tx_new, range, state.selected_unit);
-                                app_state = AppState::Loading { tick: 0 };
+                                start_fetch(
+                                    &mut rx,
+                                    &mut pending,
+                                    range,
+                                    state.selected_unit,
+                                    Utc::now(),
+                                );
+                                app_state = AppState::Loading {
+                                    tick: 0,
+                                    progress: pending.progress(),
+                                };
                             }
                         }
                         // Ignore all other key presses
@@ -338,29 +1166,92 @@ async fn run_app<B: Backend>(
                     }
                 }
 
+                AppState::Loaded(app) if app.searching => {
+                    match key.code {
+                        KeyCode::Char(c) => app.search.push(c),
+                        KeyCode::Backspace => {
+                            app.search.pop();
+                        }
+                        KeyCode::Enter => app.searching = false,
+                        KeyCode::Esc => {
+                            app.search.clear();
+                            app.searching = false;
+                        }
+                        _ => {}
+                    }
+                    app.scroll_offset = 0;
+                }
+
                 // MODIFIED: Event handler is now a state machine based on scroll_mode.
                 AppState::Loaded(app) => {
+                    app.last_export = None;
+                    if key.code == KeyCode::Char('e') {
+                        app.last_export = Some(app.export());
+                    }
+                    // fetches the charts again, for any view and scroll mode
+                    if key.code == KeyCode::Char('r') {
+                        app.start_refresh(&mut rx, &mut pending);
+                    }
+                    // sorts the charts of every grid, in either scroll mode
+                    if key.code == KeyCode::Char('s') {
+                        app.chart_sort = app.chart_sort.next();
+                        app.scroll_offset = 0;
+                    }
+                    if key.code == KeyCode::Char('/') && app.current_view.has_chart_grid() {
+                        app.searching = true;
+                    }
+                    if key.code == KeyCode::Char('L')
+                        && let Some(data) = app.current_chart_data_mut()
+                    {
+                        data.log_scale = !data.log_scale;
+                    }
+                    if key.code == KeyCode::Char('c') && app.prometheus_error.is_none() {
+                        app.toggle_comparison();
+                    }
+                    if key.code == KeyCode::Backspace {
+                        app.close_account_users();
+                    }
+                    // moving the window fetches it at once, in place of any fetch under way
+                    if let KeyCode::Char(c @ ('+' | '=' | '-' | '[' | ']')) = key.code {
+                        match c {
+                            '+' | '=' => app.zoom(true),
+                            '-' => app.zoom(false),
+                            '[' => app.pan(true),
+                            _ => app.pan(false),
+                        }
+                        // the users of an account were fetched for the old window
+                        app.close_account_users();
+                        app.fetch(&mut rx, &mut pending);
+                    }
                     match app.scroll_mode {
                         ScrollMode::Page => match key.code {
-                            KeyCode::Char('1') => app.current_view = AppView::CpuByAccount,
-                            KeyCode::Char('2') => app.current_view = AppView::CpuByNode,
-                            KeyCode::Char('3') => app.current_view = AppView::GpuByType,
+                            KeyCode::Char('0') => app.set_view(AppView::Live),
+                            KeyCode::Char('t') => app.set_view(AppView::ClusterTotal),
+                            KeyCode::Char('1') => app.set_view(AppView::CpuByAccount),
+                            KeyCode::Char('2') => app.set_view(AppView::CpuByNode),
+                            KeyCode::Char('3') => app.set_view(AppView::GpuByType),
+                            KeyCode::Char('4') => app.set_view(AppView::GpuByAccount),
+                            KeyCode::Char('5') => app.set_view(AppView::CpuByFeature),
                             KeyCode::Right | KeyCode::Char('l') | KeyCode::Tab => app.next_view(),
                             KeyCode::Left | KeyCode::Char('h') => app.prev_view(),
                             KeyCode::Up | KeyCode::PageUp | KeyCode::Char('k') => {
                                 app.scroll_offset = app.scroll_offset.saturating_sub(1)
                             }
+                            KeyCode::Down | KeyCode::PageDown | KeyCode::Char('j')
+                                if app.current_view == AppView::Live =>
+                            {
+                                // the live table scrolls by row, keeping at least one in view
+                                app.scroll_offset =
+                                    (app.scroll_offset + 1).min(live.num_rows().saturating_sub(1));
+                            }
                             KeyCode::Down | KeyCode::PageDown | KeyCode::Char('j') => {
                                 let terminal_size = terminal.size()?;
-                                // compute how many chart-rows fit: subtract tabs (3 lines) and footer (1 line)
-                                let chartable_height = terminal_size.height.saturating_sub(3 + 1);
+                                // compute how many chart-rows fit: subtract tabs (3 lines), status line and footer (1 line each)
+                                let chartable_height =
+                                    terminal_size.height.saturating_sub(3 + 1 + 1);
                                 let num_cols =
                                     (terminal_size.width / MINIMUM_CHART_WIDTH).max(1) as usize;
-                                let num_charts = match app.current_view {
-                                    AppView::CpuByAccount => app.cpu_by_account.source_data.len(),
-                                    AppView::CpuByNode => app.cpu_by_node.source_data.len(),
-                                    AppView::GpuByType => app.gpu_by_type.source_data.len(),
-                                };
+                                let num_charts = app.num_charts();
                                 let total_rows = num_charts.div_ceil(num_cols);
                                 let num_visible_rows = (chartable_height / CHART_HEIGHT) as usize;
                                 let max_scroll_offset = total_rows.saturating_sub(num_visible_rows);
@@ -368,15 +1259,21 @@ async fn run_app<B: Backend>(
                                     app.scroll_offset = app.scroll_offset.saturating_add(1);
                                 }
                             }
-                            KeyCode::Enter => app.scroll_mode = ScrollMode::Chart,
+                            // the live table and the total chart have no charts to scroll through
+                            KeyCode::Enter if app.current_chart_data().is_some() => {
+                                app.scroll_mode = ScrollMode::Chart
+                            }
                             KeyCode::Char('a') => app.display_mode = app.display_mode.toggle(),
+                            KeyCode::Esc => {
+                                app.search.clear();
+                                app.scroll_offset = 0;
+                            }
                             _ => {}
                         },
                         ScrollMode::Chart => {
-                            let current_chart_data = match app.current_view {
-                                AppView::CpuByAccount => &mut app.cpu_by_account,
-                                AppView::CpuByNode => &mut app.cpu_by_node,
-                                AppView::GpuByType => &mut app.gpu_by_type,
+                            // chart mode is only entered from a grid of charts
+                            let Some(current_chart_data) = app.current_chart_data_mut() else {
+                                continue;
                             };
                             match key.code {
                                 KeyCode::Right | KeyCode::Char('l') => {
@@ -404,6 +1301,19 @@ async fn run_app<B: Backend>(
                                             .saturating_sub(1);
                                 }
                                 KeyCode::Esc => app.scroll_mode = ScrollMode::Page,
+                                KeyCode::Tab | KeyCode::BackTab => {
+                                    let terminal_size = terminal.size()?;
+                                    let num_cols =
+                                        (terminal_size.width / MINIMUM_CHART_WIDTH).max(1) as usize;
+                                    let num_visible_rows =
+                                        (terminal_size.height.saturating_sub(3 + 1 + 1)
+                                            / CHART_HEIGHT)
+                                            as usize;
+                                    let step = if key.code == KeyCode::Tab { 1 } else { -1 };
+                                    app.move_selection(step, num_cols, num_visible_rows);
+                                }
+                                KeyCode::Char('p') => app.toggle_pin(),
+                                KeyCode::Enter => app.open_account_users(&mut users_rx),
 
                                 KeyCode::Up | KeyCode::PageUp | KeyCode::Char('k') => {
                                     app.scroll_offset = app.scroll_offset.saturating_sub(1)
@@ -411,16 +1321,10 @@ async fn run_app<B: Backend>(
                                 KeyCode::Down | KeyCode::PageDown | KeyCode::Char('j') => {
                                     let terminal_size = terminal.size()?;
                                     let chartable_height =
-                                        terminal_size.height.saturating_sub(3 + 1);
+                                        terminal_size.height.saturating_sub(3 + 1 + 1);
                                     let num_cols =
                                         (terminal_size.width / MINIMUM_CHART_WIDTH).max(1) as usize;
-                                    let num_charts = match app.current_view {
-                                        AppView::CpuByAccount => {
-                                            app.cpu_by_account.source_data.len()
-                                        }
-                                        AppView::CpuByNode => app.cpu_by_node.source_data.len(),
-                                        AppView::GpuByType => app.gpu_by_type.source_data.len(),
-                                    };
+                                    let num_charts = app.num_charts();
                                     let total_rows = num_charts.div_ceil(num_cols);
                                     let num_visible_rows =
                                         (chartable_height / CHART_HEIGHT) as usize;
@@ -436,145 +1340,201 @@ async fn run_app<B: Backend>(
                         }
                     }
                 }
-                _ => {} // No input for Loading or Error states.
+                AppState::Loading { .. } if key.code == KeyCode::Esc => {
+                    cancel_fetch(&mut rx, &mut pending);
+                    app_state = AppState::MainMenu {
+                        selected: MainMenuSelection::Default,
+                        last_query: saved.query,
+                    };
+                }
+                _ => {} // No other input for Loading or Error states.
             }
         }
 
         // should we be able to quit out of a loading screen to go back to the main menu?
         // would it result in any other bugs to allow this?
 
-        if let AppState::Loading { ref mut tick } = app_state {
+        if let AppState::Loading {
+            ref mut tick,
+            ref mut progress,
+        } = app_state
+        {
             *tick += 1;
+            *progress = pending.progress();
 
-            if *tick > LOADING_TIMEOUT_TICKS {
-                app_state = AppState::Error(AppError::TimeOut);
-                continue; // Skip the rest of the loop to immediately draw the error screen.
+            if *tick > loading_timeout_ticks(current_query_range) {
+                // the live view is still worth showing without Prometheus
+                app_state = build_live_only_app(
+                    AppError::TimeOut,
+                    &live,
+                    current_query_range,
+                    current_query_time_scale,
+                    current_auto_refresh,
+                );
+                continue; // Skip the rest of the loop to immediately draw the new state.
             }
 
-            if data_fetch_count == 6 {
+            if pending.is_complete() {
                 app_state = build_loaded_app(
-                    &mut cpu_by_account_data,
-                    &mut cpu_by_node_data,
-                    &mut gpu_by_type_data,
-                    &mut cpu_by_account_capacity,
-                    &mut cpu_by_node_capacity,
-                    &mut gpu_by_type_capacity,
+                    &mut pending,
                     current_query_range,
                     current_query_time_scale,
+                    current_auto_refresh,
+                    saved.view,
+                    &live,
                 );
             }
         }
 
+        // for a dashboard left on a wall monitor
+        if let AppState::Loaded(app) = &mut app_state
+            && app.next_refresh.is_some_and(|at| Instant::now() >= at)
+        {
+            app.start_refresh(&mut rx, &mut pending);
+        }
+
+        // a refresh replaces the charts only once every query is back, keeping the view
+        if let AppState::Loaded(app) = &mut app_state
+            && let RefreshStatus::Refreshing { tick } = &mut app.refresh
+        {
+            *tick += 1;
+            if pending.is_complete() {
+                match pending.take_charts() {
+                    Ok(
+                        [
+                            cpu_by_account,
+                            cpu_by_node,
+                            gpu_by_type,
+                            gpu_by_account,
+                            cpu_by_feature,
+                        ],
+                    ) => {
+                        app.cpu_by_account.replace(cpu_by_account);
+                        app.cpu_by_node.replace(cpu_by_node);
+                        app.gpu_by_type.replace(gpu_by_type);
+                        app.gpu_by_account.replace(gpu_by_account);
+                        app.cpu_by_feature.replace(cpu_by_feature);
+                        app.prometheus_error = None;
+                        app.refresh = RefreshStatus::Idle;
+                    }
+                    // with no charts to keep, the error takes their place
+                    Err(e) if app.prometheus_error.is_some() => {
+                        app.prometheus_error = Some(e);
+                        app.refresh = RefreshStatus::Idle;
+                    }
+                    Err(e) => app.refresh = RefreshStatus::Failed(e),
+                }
+            } else if *tick > loading_timeout_ticks(app.query_range) {
+                app.refresh = RefreshStatus::Failed(AppError::TimeOut);
+            }
+        }
+
         if let AppState::Loaded(app) = &app_state
             && app.should_quit
         {
+            saved.view = Some(app.current_view);
+            saved.save();
             return Ok(());
         }
     }
 }
 
-#[allow(clippy::too_many_arguments)]
 fn build_loaded_app(
-    cpu_by_account_data: &mut Option<Result<UsageData, AppError>>,
-    cpu_by_node_data: &mut Option<Result<UsageData, AppError>>,
-    gpu_by_type_data: &mut Option<Result<UsageData, AppError>>,
-    cpu_by_account_capacity: &mut Option<Result<CapacityData, AppError>>,
-    cpu_by_node_capacity: &mut Option<Result<CapacityData, AppError>>,
-    gpu_by_type_capacity: &mut Option<Result<CapacityData, AppError>>,
+    pending: &mut PendingFetches,
     query_range: i64,
     query_time_scale: PrometheusTimeScale,
+    auto_refresh: Option<Duration>,
+    view: Option<AppView>,
+    live: &LiveStatus,
 ) -> AppState {
-    let error_checks = [
-        cpu_by_account_data
-            .as_ref()
-            .and_then(|r| r.as_ref().err().cloned()),
-        cpu_by_node_data
-            .as_ref()
-            .and_then(|r| r.as_ref().err().cloned()),
-        gpu_by_type_data
-            .as_ref()
-            .and_then(|r| r.as_ref().err().cloned()),
-        cpu_by_account_capacity
-            .as_ref()
-            .and_then(|r| r.as_ref().err().cloned()),
-        cpu_by_node_capacity
-            .as_ref()
-            .and_then(|r| r.as_ref().err().cloned()),
-        gpu_by_type_capacity
-            .as_ref()
-            .and_then(|r| r.as_ref().err().cloned()),
-    ];
-
-    if let Some(err_opt) = error_checks.iter().flatten().next() {
-        return AppState::Error(err_opt.clone());
-    }
-
-    let final_cpu_by_account = {
-        let usage = cpu_by_account_data.take().unwrap().unwrap();
-        let capacity = cpu_by_account_capacity.take().unwrap().unwrap();
-        let max_points = usage
-            .source_data
-            .values()
-            .map(|v| v.len())
-            .max()
-            .unwrap_or(0);
-        let initial_offset = max_points.saturating_sub(MAX_BARS_PER_CHART);
-        ChartData {
-            source_data: usage.source_data,
-            capacity_data: capacity.capacities,
-            horizontal_scroll_offset: initial_offset,
-        }
-    };
-    let final_cpu_by_node = {
-        let usage = cpu_by_node_data.take().unwrap().unwrap();
-        let capacity = cpu_by_node_capacity.take().unwrap().unwrap();
-        let max_points = usage
-            .source_data
-            .values()
-            .map(|v| v.len())
-            .max()
-            .unwrap_or(0);
-        let initial_offset = max_points.saturating_sub(MAX_BARS_PER_CHART);
-        ChartData {
-            source_data: usage.source_data,
-            capacity_data: capacity.capacities,
-            horizontal_scroll_offset: initial_offset,
-        }
-    };
-    let final_gpu_by_type = {
-        let usage = gpu_by_type_data.take().unwrap().unwrap();
-        let capacity = gpu_by_type_capacity.take().unwrap().unwrap();
-        let max_points = usage
-            .source_data
-            .values()
-            .map(|v| v.len())
-            .max()
-            .unwrap_or(0);
-        let initial_offset = max_points.saturating_sub(MAX_BARS_PER_CHART);
-        ChartData {
-            source_data: usage.source_data,
-            capacity_data: capacity.capacities,
-            horizontal_scroll_offset: initial_offset,
+    let [
+        final_cpu_by_account,
+        final_cpu_by_node,
+        final_gpu_by_type,
+        final_gpu_by_account,
+        final_cpu_by_feature,
+    ] = match pending.take_charts() {
+        Ok(charts) => charts,
+        Err(e) => {
+            return build_live_only_app(e, live, query_range, query_time_scale, auto_refresh);
         }
     };
 
     let app = App {
-        current_view: AppView::CpuByAccount,
+        current_view: view.unwrap_or(AppView::CpuByAccount),
         scroll_offset: 0,
         scroll_mode: ScrollMode::default(),
         cpu_by_account: final_cpu_by_account,
         cpu_by_node: final_cpu_by_node,
         gpu_by_type: final_gpu_by_type,
+        gpu_by_account: final_gpu_by_account,
+        cpu_by_feature: final_cpu_by_feature,
+        account_users: None,
+        comparison: None,
         should_quit: false,
         query_range,
         query_time_scale,
+        query_end: None,
         display_mode: DisplayMode::default(),
+        chart_sort: ChartSort::default(),
+        search: String::new(),
+        searching: false,
+        last_export: None,
+        prometheus_error: None,
+        refresh: RefreshStatus::default(),
+        auto_refresh,
+        next_refresh: auto_refresh.map(|interval| Instant::now() + interval),
     };
     AppState::Loaded(app)
 }
 
+/// The app with no Prometheus charts, opened on the live view, for when Prometheus can't be
+/// reached; only if Slurm can't be reached either is the error shown on its own
+fn build_live_only_app(
+    error: AppError,
+    live: &LiveStatus,
+    query_range: i64,
+    query_time_scale: PrometheusTimeScale,
+    auto_refresh: Option<Duration>,
+) -> AppState {
+    if live.data.is_none() && live.error.is_some() {
+        return AppState::Error(error);
+    }
+    AppState::Loaded(App {
+        current_view: AppView::Live,
+        scroll_offset: 0,
+        scroll_mode: ScrollMode::default(),
+        cpu_by_account: ChartData::default(),
+        cpu_by_node: ChartData::default(),
+        gpu_by_type: ChartData::default(),
+        gpu_by_account: ChartData::default(),
+        cpu_by_feature: ChartData::default(),
+        account_users: None,
+        comparison: None,
+        should_quit: false,
+        query_range,
+        query_time_scale,
+        query_end: None,
+        display_mode: DisplayMode::default(),
+        chart_sort: ChartSort::default(),
+        search: String::new(),
+        searching: false,
+        last_export: None,
+        prometheus_error: Some(error),
+        refresh: RefreshStatus::default(),
+        auto_refresh,
+        next_refresh: auto_refresh.map(|interval| Instant::now() + interval),
+    })
+}
+
+/// Runs the TUI, drawn in the colors of the theme
 #[tokio::main]
-pub async fn tui_execute() -> Result<(), Box<dyn std::error::Error>> {
+pub async fn tui_execute(theme: Theme) -> Result<(), Box<dyn std::error::Error>> {
+    // the config is held for the whole session, for the live view's refreshes; without it the
+    // live view shows the error from loading the cluster state, and the charts still work
+    initialize_slurm();
+    let _slurm_config = SlurmConfig::load().ok();
+
     enable_raw_mode()?;
     let mut stdout = io::stdout();
     execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
@@ -582,10 +1542,12 @@ pub async fn tui_execute() -> Result<(), Box<dyn std::error::Error>> {
     let mut terminal = Terminal::new(backend)?;
 
     // MODIFIED: Start fetching default data immediately.
-    let (tx, rx) = mpsc::channel(6);
-    spawn_custom_data_fetch(tx, 30, PrometheusTimeScale::Days);
+    let (tx, rx) = mpsc::channel(FETCH_COUNT);
+    spawn_custom_data_fetch(tx, 30, PrometheusTimeScale::Days, Utc::now());
+    let (live_tx, live_rx) = mpsc::channel(1);
+    tokio::spawn(refresh_live_data_async(live_tx));
 
-    let res = run_app(&mut terminal, rx).await;
+    let res = run_app(&mut terminal, rx, live_rx, TuiPalette::new(&theme)).await;
 
     disable_raw_mode()?;
     execute!(
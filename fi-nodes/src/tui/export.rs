@@ -0,0 +1,72 @@
+use crate::tui::app::{AppError, ChartData};
+use chrono::Local;
+use std::fs::File;
+use std::io;
+use std::path::PathBuf;
+
+/// Writes every series of a view's charts to a CSV file in the working directory, named after
+/// the view and the time, returning where it was written
+///
+/// Each row is one step of one series, with its usage and the capacity it's measured against;
+/// `shared_capacity` is for views whose series are all measured against the cluster's total
+pub fn export_chart_data(
+    data: &ChartData,
+    view_name: &str,
+    shared_capacity: bool,
+) -> Result<PathBuf, AppError> {
+    let path = PathBuf::from(format!(
+        "fi-nodes-{}-{}.csv",
+        file_name_part(view_name),
+        Local::now().format("%Y%m%d-%H%M%S")
+    ));
+    let file = File::create(&path).map_err(|e| AppError::Export(e.to_string()))?;
+    write_chart_csv(data, shared_capacity, file).map_err(|e| AppError::Export(e.to_string()))?;
+    Ok(path)
+}
+
+fn write_chart_csv(
+    data: &ChartData,
+    shared_capacity: bool,
+    out: impl io::Write,
+) -> Result<(), csv::Error> {
+    let mut writer = csv::Writer::from_writer(out);
+    writer.write_record(["time", "series", "usage", "capacity"])?;
+
+    let mut series: Vec<_> = data.source_data.iter().collect();
+    series.sort_by_key(|(name, _)| *name);
+    for (name, values) in series {
+        let capacity_key = if shared_capacity {
+            "Total"
+        } else {
+            name.as_str()
+        };
+        let capacities = data.capacity_data.get(capacity_key);
+        for (step, usage) in values.iter().enumerate() {
+            // a series without timestamps is still worth exporting, by step
+            let time = data
+                .timestamps
+                .get(step)
+                .map_or_else(|| step.to_string(), |time| time.to_rfc3339());
+            let capacity = capacities
+                .and_then(|capacities| capacities.get(step))
+                .map_or_else(String::new, u64::to_string);
+            writer.write_record([time, name.clone(), usage.to_string(), capacity])?;
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// The view's name made safe for a file name, such as "cores-by-account"
+fn file_name_part(view_name: &str) -> String {
+    view_name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' {
+                c.to_ascii_lowercase()
+            } else {
+                '-'
+            }
+        })
+        .collect()
+}
@@ -1,7 +1,15 @@
-use crate::tui::app::{AppError, CapacityData, FetchedData, UsageData};
+use crate::SortOrder;
+use crate::tree_report::{TreeNode, build_tree_report, sorted_children};
+use crate::tui::app::{AppError, CapacityData, FetchedData, LiveData, LiveRow, UsageData};
+use chrono::{DateTime, Local, Utc};
 use fi_prometheus::{
-    Cluster, Grouping, PrometheusTimeScale, Resource, get_max_resource, get_usage_by,
+    Cluster, Grouping, PrometheusTimeScale, Resource, get_account_usage_series, get_max_resource,
+    get_usage_series, query_timeout,
 };
+use fi_slurm::cluster_state::ClusterState;
+use fi_slurm::nodes::Node;
+use fi_slurm::site::{accounts_of, fold_by_center, hidden_features};
+use std::collections::{HashMap, HashSet};
 use std::time::Duration;
 use tokio::sync::mpsc;
 
@@ -16,6 +24,8 @@ struct PrometheusRequest {
     resource: Resource,
     range: i64,
     time_scale: PrometheusTimeScale,
+    /// When the last step of the range is
+    end: DateTime<Utc>,
 }
 
 impl PrometheusRequest {
@@ -26,6 +36,7 @@ impl PrometheusRequest {
         resource: Resource,
         range: i64,
         time_scale: PrometheusTimeScale,
+        end: DateTime<Utc>,
     ) -> Self {
         Self {
             cluster,
@@ -33,6 +44,7 @@ impl PrometheusRequest {
             resource,
             range,
             time_scale,
+            end,
         }
     }
 }
@@ -58,16 +70,20 @@ fn prometheus_data_request(
 ) -> Result<PrometheusDataResult, AppError> {
     match data_type {
         PrometheusDataType::Usage => {
-            let data = get_usage_by(
+            let series = get_usage_series(
                 request.cluster,
                 request.grouping.unwrap(), // No longer needs .unwrap()
                 request.resource,
                 request.range,
                 request.time_scale,
+                request.end,
             )
             .map_err(|e| AppError::DataFetch(e.to_string()))?;
 
-            Ok(PrometheusDataResult::Usage(UsageData { source_data: data }))
+            Ok(PrometheusDataResult::Usage(UsageData {
+                source_data: series.groups,
+                timestamps: series.timestamps,
+            }))
         }
 
         PrometheusDataType::Capacity => {
@@ -77,6 +93,7 @@ fn prometheus_data_request(
                 request.resource,
                 request.range, // This function also expects an Option
                 request.time_scale,
+                request.end,
             )
             .map_err(|e| AppError::DataFetch(e.to_string()))?;
 
@@ -87,11 +104,23 @@ fn prometheus_data_request(
     }
 }
 
+/// Adds up the series of accounts of the same center, as mapped in centers.conf, so that the
+/// account charts show each center once
+fn by_center(series: HashMap<String, Vec<u64>>) -> HashMap<String, Vec<u64>> {
+    fold_by_center(series, |total, series| {
+        total
+            .iter_mut()
+            .zip(series)
+            .for_each(|(total, value)| *total += value)
+    })
+}
+
 // --- CPU by Account ---
 
 pub fn get_cpu_by_account_data(
     range: i64,
     time_scale: PrometheusTimeScale,
+    end: DateTime<Utc>,
 ) -> Result<UsageData, AppError> {
     let request = PrometheusRequest::new(
         Cluster::Rusty,
@@ -99,12 +128,16 @@ pub fn get_cpu_by_account_data(
         Resource::Cpus,
         range,
         time_scale,
+        end,
     );
 
     let result = prometheus_data_request(request, PrometheusDataType::Usage)?;
 
     match result {
-        PrometheusDataResult::Usage(usage_data) => Ok(usage_data),
+        PrometheusDataResult::Usage(usage_data) => Ok(UsageData {
+            source_data: by_center(usage_data.source_data),
+            ..usage_data
+        }),
         PrometheusDataResult::Capacity(_) => Err(AppError::DataFetch(
             "Unexpected data type returned. Expected Usage.".to_string(),
         )),
@@ -115,9 +148,10 @@ pub async fn get_cpu_by_account_data_async(
     tx: mpsc::Sender<FetchedData>,
     range: i64,
     time_scale: PrometheusTimeScale,
+    end: DateTime<Utc>,
 ) {
-    let task = tokio::task::spawn_blocking(move || get_cpu_by_account_data(range, time_scale));
-    let result = tokio::time::timeout(TASK_TIMEOUT, task).await;
+    let task = tokio::task::spawn_blocking(move || get_cpu_by_account_data(range, time_scale, end));
+    let result = tokio::time::timeout(query_timeout(range), task).await;
 
     let data_to_send = match result {
         Ok(Ok(data_res)) => FetchedData::CpuByAccount(data_res),
@@ -130,6 +164,7 @@ pub async fn get_cpu_by_account_data_async(
 pub fn get_cpu_capacity_by_account(
     range: i64,
     time_scale: PrometheusTimeScale,
+    end: DateTime<Utc>,
 ) -> Result<CapacityData, AppError> {
     let request = PrometheusRequest::new(
         Cluster::Rusty,
@@ -137,12 +172,15 @@ pub fn get_cpu_capacity_by_account(
         Resource::Cpus,
         range,
         time_scale,
+        end,
     );
 
     let result = prometheus_data_request(request, PrometheusDataType::Capacity)?;
 
     match result {
-        PrometheusDataResult::Capacity(capacity_data) => Ok(capacity_data),
+        PrometheusDataResult::Capacity(capacity_data) => Ok(CapacityData {
+            capacities: by_center(capacity_data.capacities),
+        }),
         PrometheusDataResult::Usage(_) => Err(AppError::DataFetch(
             "Unexpected data type returned. Expected Capacity.".to_string(),
         )),
@@ -153,9 +191,11 @@ pub async fn get_cpu_capacity_by_account_async(
     tx: mpsc::Sender<FetchedData>,
     range: i64,
     time_scale: PrometheusTimeScale,
+    end: DateTime<Utc>,
 ) {
-    let task = tokio::task::spawn_blocking(move || get_cpu_capacity_by_account(range, time_scale));
-    let result = tokio::time::timeout(TASK_TIMEOUT, task).await;
+    let task =
+        tokio::task::spawn_blocking(move || get_cpu_capacity_by_account(range, time_scale, end));
+    let result = tokio::time::timeout(query_timeout(range), task).await;
 
     let data_to_send = match result {
         Ok(Ok(data_res)) => FetchedData::CpuCapacityByAccount(data_res),
@@ -165,11 +205,60 @@ pub async fn get_cpu_capacity_by_account_async(
     if tx.send(data_to_send).await.is_err() {}
 }
 
+// --- CPU by User of an Account ---
+
+/// The cores used by each user of one account or center, for drilling down from its chart
+pub fn get_cpu_by_user_data(
+    account: &str,
+    range: i64,
+    time_scale: PrometheusTimeScale,
+    end: DateTime<Utc>,
+) -> Result<UsageData, AppError> {
+    // the chart drilled down from may be that of a whole center
+    let accounts = accounts_of(account);
+    let accounts: Vec<&str> = accounts.iter().map(String::as_str).collect();
+    let series = get_account_usage_series(
+        Cluster::Rusty,
+        &accounts,
+        Grouping::User,
+        Resource::Cpus,
+        range,
+        time_scale,
+        end,
+    )
+    .map_err(|e| AppError::DataFetch(e.to_string()))?;
+
+    Ok(UsageData {
+        source_data: series.groups,
+        timestamps: series.timestamps,
+    })
+}
+
+pub async fn get_cpu_by_user_data_async(
+    tx: mpsc::Sender<Result<UsageData, AppError>>,
+    account: String,
+    range: i64,
+    time_scale: PrometheusTimeScale,
+    end: DateTime<Utc>,
+) {
+    let task =
+        tokio::task::spawn_blocking(move || get_cpu_by_user_data(&account, range, time_scale, end));
+    let result = tokio::time::timeout(query_timeout(range), task).await;
+
+    let data_to_send = match result {
+        Ok(Ok(data_res)) => data_res,
+        Ok(Err(e)) => Err(AppError::TaskJoin(e.to_string())),
+        Err(_) => Err(AppError::TimeOut),
+    };
+    if tx.send(data_to_send).await.is_err() {}
+}
+
 // --- CPU by Node ---
 
 pub fn get_cpu_by_node_data(
     range: i64,
     time_scale: PrometheusTimeScale,
+    end: DateTime<Utc>,
 ) -> Result<UsageData, AppError> {
     let request = PrometheusRequest::new(
         Cluster::Rusty,
@@ -177,6 +266,7 @@ pub fn get_cpu_by_node_data(
         Resource::Cpus,
         range,
         time_scale,
+        end,
     );
 
     let result = prometheus_data_request(request, PrometheusDataType::Usage)?;
@@ -193,9 +283,10 @@ pub async fn get_cpu_by_node_data_async(
     tx: mpsc::Sender<FetchedData>,
     range: i64,
     time_scale: PrometheusTimeScale,
+    end: DateTime<Utc>,
 ) {
-    let task = tokio::task::spawn_blocking(move || get_cpu_by_node_data(range, time_scale));
-    let result = tokio::time::timeout(TASK_TIMEOUT, task).await;
+    let task = tokio::task::spawn_blocking(move || get_cpu_by_node_data(range, time_scale, end));
+    let result = tokio::time::timeout(query_timeout(range), task).await;
 
     let data_to_send = match result {
         Ok(Ok(data_res)) => FetchedData::CpuByNode(data_res),
@@ -208,6 +299,7 @@ pub async fn get_cpu_by_node_data_async(
 pub fn get_cpu_capacity_by_node(
     range: i64,
     time_scale: PrometheusTimeScale,
+    end: DateTime<Utc>,
 ) -> Result<CapacityData, AppError> {
     let request = PrometheusRequest::new(
         Cluster::Rusty,
@@ -215,6 +307,7 @@ pub fn get_cpu_capacity_by_node(
         Resource::Cpus,
         range,
         time_scale,
+        end,
     );
 
     let result = prometheus_data_request(request, PrometheusDataType::Capacity)?;
@@ -231,9 +324,11 @@ pub async fn get_cpu_capacity_by_node_async(
     tx: mpsc::Sender<FetchedData>,
     range: i64,
     time_scale: PrometheusTimeScale,
+    end: DateTime<Utc>,
 ) {
-    let task = tokio::task::spawn_blocking(move || get_cpu_capacity_by_node(range, time_scale));
-    let result = tokio::time::timeout(TASK_TIMEOUT, task).await;
+    let task =
+        tokio::task::spawn_blocking(move || get_cpu_capacity_by_node(range, time_scale, end));
+    let result = tokio::time::timeout(query_timeout(range), task).await;
 
     let data_to_send = match result {
         Ok(Ok(data_res)) => FetchedData::CpuCapacityByNode(data_res),
@@ -248,6 +343,7 @@ pub async fn get_cpu_capacity_by_node_async(
 pub fn get_gpu_by_type_data(
     range: i64,
     time_scale: PrometheusTimeScale,
+    end: DateTime<Utc>,
 ) -> Result<UsageData, AppError> {
     let request = PrometheusRequest::new(
         Cluster::Rusty,
@@ -255,6 +351,7 @@ pub fn get_gpu_by_type_data(
         Resource::Gpus,
         range,
         time_scale,
+        end,
     );
 
     let result = prometheus_data_request(request, PrometheusDataType::Usage)?;
@@ -271,9 +368,10 @@ pub async fn get_gpu_by_type_data_async(
     tx: mpsc::Sender<FetchedData>,
     range: i64,
     time_scale: PrometheusTimeScale,
+    end: DateTime<Utc>,
 ) {
-    let task = tokio::task::spawn_blocking(move || get_gpu_by_type_data(range, time_scale));
-    let result = tokio::time::timeout(TASK_TIMEOUT, task).await;
+    let task = tokio::task::spawn_blocking(move || get_gpu_by_type_data(range, time_scale, end));
+    let result = tokio::time::timeout(query_timeout(range), task).await;
 
     let data_to_send = match result {
         Ok(Ok(data_res)) => FetchedData::GpuByType(data_res),
@@ -286,6 +384,7 @@ pub async fn get_gpu_by_type_data_async(
 pub fn get_gpu_capacity_by_type(
     range: i64,
     time_scale: PrometheusTimeScale,
+    end: DateTime<Utc>,
 ) -> Result<CapacityData, AppError> {
     let request = PrometheusRequest::new(
         Cluster::Rusty,
@@ -293,6 +392,7 @@ pub fn get_gpu_capacity_by_type(
         Resource::Gpus,
         range,
         time_scale,
+        end,
     );
 
     let result = prometheus_data_request(request, PrometheusDataType::Capacity)?;
@@ -309,9 +409,11 @@ pub async fn get_gpu_capacity_by_type_async(
     tx: mpsc::Sender<FetchedData>,
     range: i64,
     time_scale: PrometheusTimeScale,
+    end: DateTime<Utc>,
 ) {
-    let task = tokio::task::spawn_blocking(move || get_gpu_capacity_by_type(range, time_scale));
-    let result = tokio::time::timeout(TASK_TIMEOUT, task).await;
+    let task =
+        tokio::task::spawn_blocking(move || get_gpu_capacity_by_type(range, time_scale, end));
+    let result = tokio::time::timeout(query_timeout(range), task).await;
 
     let data_to_send = match result {
         Ok(Ok(data_res)) => FetchedData::GpuCapacityByType(data_res),
@@ -320,3 +422,264 @@ pub async fn get_gpu_capacity_by_type_async(
     };
     if tx.send(data_to_send).await.is_err() {}
 }
+
+// --- GPU by Account ---
+
+pub fn get_gpu_by_account_data(
+    range: i64,
+    time_scale: PrometheusTimeScale,
+    end: DateTime<Utc>,
+) -> Result<UsageData, AppError> {
+    let request = PrometheusRequest::new(
+        Cluster::Rusty,
+        Some(Grouping::Account),
+        Resource::Gpus,
+        range,
+        time_scale,
+        end,
+    );
+
+    let result = prometheus_data_request(request, PrometheusDataType::Usage)?;
+
+    match result {
+        PrometheusDataResult::Usage(usage_data) => Ok(UsageData {
+            source_data: by_center(usage_data.source_data),
+            ..usage_data
+        }),
+        PrometheusDataResult::Capacity(_) => Err(AppError::DataFetch(
+            "Unexpected data type returned. Expected Usage.".to_string(),
+        )),
+    }
+}
+
+pub async fn get_gpu_by_account_data_async(
+    tx: mpsc::Sender<FetchedData>,
+    range: i64,
+    time_scale: PrometheusTimeScale,
+    end: DateTime<Utc>,
+) {
+    let task = tokio::task::spawn_blocking(move || get_gpu_by_account_data(range, time_scale, end));
+    let result = tokio::time::timeout(query_timeout(range), task).await;
+
+    let data_to_send = match result {
+        Ok(Ok(data_res)) => FetchedData::GpuByAccount(data_res),
+        Ok(Err(e)) => FetchedData::GpuByAccount(Err(AppError::TaskJoin(e.to_string()))),
+        Err(_) => FetchedData::GpuByAccount(Err(AppError::TimeOut)),
+    };
+    if tx.send(data_to_send).await.is_err() {}
+}
+
+pub fn get_gpu_capacity_by_account(
+    range: i64,
+    time_scale: PrometheusTimeScale,
+    end: DateTime<Utc>,
+) -> Result<CapacityData, AppError> {
+    let request = PrometheusRequest::new(
+        Cluster::Rusty,
+        Some(Grouping::Account),
+        Resource::Gpus,
+        range,
+        time_scale,
+        end,
+    );
+
+    let result = prometheus_data_request(request, PrometheusDataType::Capacity)?;
+
+    match result {
+        PrometheusDataResult::Capacity(capacity_data) => Ok(CapacityData {
+            capacities: by_center(capacity_data.capacities),
+        }),
+        PrometheusDataResult::Usage(_) => Err(AppError::DataFetch(
+            "Unexpected data type returned. Expected Capacity.".to_string(),
+        )),
+    }
+}
+
+pub async fn get_gpu_capacity_by_account_async(
+    tx: mpsc::Sender<FetchedData>,
+    range: i64,
+    time_scale: PrometheusTimeScale,
+    end: DateTime<Utc>,
+) {
+    let task =
+        tokio::task::spawn_blocking(move || get_gpu_capacity_by_account(range, time_scale, end));
+    let result = tokio::time::timeout(query_timeout(range), task).await;
+
+    let data_to_send = match result {
+        Ok(Ok(data_res)) => FetchedData::GpuCapacityByAccount(data_res),
+        Ok(Err(e)) => FetchedData::GpuCapacityByAccount(Err(AppError::TaskJoin(e.to_string()))),
+        Err(_) => FetchedData::GpuCapacityByAccount(Err(AppError::TimeOut)),
+    };
+    if tx.send(data_to_send).await.is_err() {}
+}
+
+// --- CPU by Node Feature ---
+
+/// The cores in use on the nodes with each feature, from the fi-nodes exporter's metrics
+pub fn get_cpu_by_feature_data(
+    range: i64,
+    time_scale: PrometheusTimeScale,
+    end: DateTime<Utc>,
+) -> Result<UsageData, AppError> {
+    let request = PrometheusRequest::new(
+        Cluster::Rusty,
+        Some(Grouping::Feature),
+        Resource::Cpus,
+        range,
+        time_scale,
+        end,
+    );
+
+    let result = prometheus_data_request(request, PrometheusDataType::Usage)?;
+
+    match result {
+        PrometheusDataResult::Usage(usage_data) => Ok(usage_data),
+        PrometheusDataResult::Capacity(_) => Err(AppError::DataFetch(
+            "Unexpected data type returned. Expected Usage.".to_string(),
+        )),
+    }
+}
+
+pub async fn get_cpu_by_feature_data_async(
+    tx: mpsc::Sender<FetchedData>,
+    range: i64,
+    time_scale: PrometheusTimeScale,
+    end: DateTime<Utc>,
+) {
+    let task = tokio::task::spawn_blocking(move || get_cpu_by_feature_data(range, time_scale, end));
+    let result = tokio::time::timeout(query_timeout(range), task).await;
+
+    let data_to_send = match result {
+        Ok(Ok(data_res)) => FetchedData::CpuByFeature(data_res),
+        Ok(Err(e)) => FetchedData::CpuByFeature(Err(AppError::TaskJoin(e.to_string()))),
+        Err(_) => FetchedData::CpuByFeature(Err(AppError::TimeOut)),
+    };
+    if tx.send(data_to_send).await.is_err() {}
+}
+
+pub fn get_cpu_capacity_by_feature(
+    range: i64,
+    time_scale: PrometheusTimeScale,
+    end: DateTime<Utc>,
+) -> Result<CapacityData, AppError> {
+    let request = PrometheusRequest::new(
+        Cluster::Rusty,
+        Some(Grouping::Feature),
+        Resource::Cpus,
+        range,
+        time_scale,
+        end,
+    );
+
+    let result = prometheus_data_request(request, PrometheusDataType::Capacity)?;
+
+    match result {
+        PrometheusDataResult::Capacity(capacity_data) => Ok(capacity_data),
+        PrometheusDataResult::Usage(_) => Err(AppError::DataFetch(
+            "Unexpected data type returned. Expected Capacity.".to_string(),
+        )),
+    }
+}
+
+pub async fn get_cpu_capacity_by_feature_async(
+    tx: mpsc::Sender<FetchedData>,
+    range: i64,
+    time_scale: PrometheusTimeScale,
+    end: DateTime<Utc>,
+) {
+    let task =
+        tokio::task::spawn_blocking(move || get_cpu_capacity_by_feature(range, time_scale, end));
+    let result = tokio::time::timeout(query_timeout(range), task).await;
+
+    let data_to_send = match result {
+        Ok(Ok(data_res)) => FetchedData::CpuCapacityByFeature(data_res),
+        Ok(Err(e)) => FetchedData::CpuCapacityByFeature(Err(AppError::TaskJoin(e.to_string()))),
+        Err(_) => FetchedData::CpuCapacityByFeature(Err(AppError::TimeOut)),
+    };
+    if tx.send(data_to_send).await.is_err() {}
+}
+
+// --- Live Slurm State ---
+
+/// How often the live view loads the cluster state from Slurm again
+pub const LIVE_REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Loads the current cluster state from Slurm and flattens its tree report into table rows,
+/// largest branches first
+pub fn get_live_data() -> Result<LiveData, AppError> {
+    let state = ClusterState::load().map_err(|e| AppError::DataFetch(e.to_string()))?;
+    let nodes: Vec<&Node> = state.nodes.nodes.iter().collect();
+    let hidden: HashSet<String> = hidden_features().iter().cloned().collect();
+    let tree = build_tree_report(
+        &nodes,
+        &state.jobs,
+        &state.node_to_job_map,
+        &[],
+        &hidden,
+        false,
+        None,
+        false,
+        false,
+        None,
+        None,
+        None,
+    );
+
+    // the GPUs are counted as the GPU report counts them, over the nodes that have any
+    let gpu_nodes: Vec<&Node> = nodes
+        .iter()
+        .copied()
+        .filter(|node| node.gpu_info.is_some())
+        .collect();
+    let gpus = build_tree_report(
+        &gpu_nodes,
+        &state.jobs,
+        &state.node_to_job_map,
+        &[],
+        &hidden,
+        false,
+        None,
+        false,
+        true,
+        None,
+        None,
+        None,
+    )
+    .stats;
+
+    let mut rows = Vec::new();
+    flatten_tree(&tree, 0, &mut rows);
+    Ok(LiveData {
+        taken_at: Local::now(),
+        rows,
+        gpus,
+    })
+}
+
+fn flatten_tree(node: &TreeNode, depth: usize, rows: &mut Vec<LiveRow>) {
+    rows.push(LiveRow {
+        depth,
+        name: node.name.to_string(),
+        stats: node.stats.clone(),
+    });
+    for child in sorted_children(&node.children, SortOrder::Nodes) {
+        flatten_tree(child, depth + 1, rows);
+    }
+}
+
+/// Loads the live cluster state every `LIVE_REFRESH_INTERVAL` until the TUI exits
+pub async fn refresh_live_data_async(tx: mpsc::Sender<Result<LiveData, AppError>>) {
+    loop {
+        let task = tokio::task::spawn_blocking(get_live_data);
+        let data_to_send = match tokio::time::timeout(TASK_TIMEOUT, task).await {
+            Ok(Ok(data_res)) => data_res,
+            Ok(Err(e)) => Err(AppError::TaskJoin(e.to_string())),
+            Err(_) => Err(AppError::TimeOut),
+        };
+        // the receiver is only dropped when the TUI exits
+        if tx.send(data_to_send).await.is_err() {
+            return;
+        }
+        tokio::time::sleep(LIVE_REFRESH_INTERVAL).await;
+    }
+}
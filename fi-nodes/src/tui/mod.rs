@@ -1,3 +1,6 @@
 pub mod app;
+pub mod export;
 pub mod interface;
+pub mod palette;
+pub mod saved;
 pub mod ui;
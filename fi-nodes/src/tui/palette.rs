@@ -0,0 +1,175 @@
+use colored::Color as TermColor;
+use fi_slurm::theme::Theme;
+use ratatui::style::{Color, Modifier, Style};
+
+/// The fills of stacked series without color, which alternate so that neighbors stay apart
+const PLAIN_FILLS: [&str; 3] = ["█", "▓", "▒"];
+
+/// The colors the TUI draws with, taken from the same theme as the reports
+///
+/// Without color, as with `--no-color` or `NO_COLOR`, everything is drawn in the terminal's
+/// own colors: what would stand out by color is made bold or reversed instead, and the series
+/// stacked in one bar are told apart by their fill
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TuiPalette {
+    /// The colors charts cycle through, one per series
+    series: [Color; 10],
+    /// The selected tab and menu entry
+    pub highlight: Color,
+    /// Focused widgets, charts while they scroll, and refreshes in progress
+    pub focus: Color,
+    /// Keys in the help and the account being drilled into
+    pub accent: Color,
+    pub success: Color,
+    /// Failures the charts stay up through
+    pub alert: Color,
+    pub error: Color,
+    /// The idle node and core gauges of the live view
+    pub nodes: Color,
+    pub cpus: Color,
+    enabled: bool,
+}
+
+impl TuiPalette {
+    pub fn new(theme: &Theme) -> Self {
+        let palette = &theme.palette;
+        let [other, error, allocated, idle, down, mixed] = [
+            palette.other,
+            palette.error,
+            palette.allocated,
+            palette.idle,
+            palette.down,
+            palette.mixed,
+        ]
+        .map(to_ratatui);
+        TuiPalette {
+            // the node state colors, then their bright versions, which for the default theme
+            // are the colors the charts have always used
+            series: [
+                other,
+                error,
+                allocated,
+                idle,
+                down,
+                brighten(mixed),
+                brighten(error),
+                brighten(allocated),
+                brighten(idle),
+                brighten(down),
+            ],
+            highlight: mixed,
+            focus: to_ratatui(palette.warning),
+            accent: other,
+            success: to_ratatui(palette.gain),
+            alert: brighten(to_ratatui(palette.loss)),
+            error: down,
+            nodes: to_ratatui(palette.nodes),
+            cpus: to_ratatui(palette.cpus),
+            enabled: theme.enabled,
+        }
+    }
+
+    /// Text in the color, or plain without color
+    pub fn fg(&self, color: Color) -> Style {
+        if self.enabled {
+            Style::default().fg(color)
+        } else {
+            Style::default()
+        }
+    }
+
+    /// Text that should stand out, in the color, or bold without color
+    pub fn strong(&self, color: Color) -> Style {
+        if self.enabled {
+            Style::default().fg(color)
+        } else {
+            Style::default().add_modifier(Modifier::BOLD)
+        }
+    }
+
+    /// The selected entry of a menu
+    pub fn selected(&self) -> Style {
+        if self.enabled {
+            Style::default().bg(self.highlight).fg(Color::White)
+        } else {
+            Style::default().add_modifier(Modifier::REVERSED)
+        }
+    }
+
+    /// The bar along the bottom of the screen
+    pub fn bar(&self) -> Style {
+        if self.enabled {
+            Style::default().fg(Color::White).bg(Color::DarkGray)
+        } else {
+            Style::default().add_modifier(Modifier::REVERSED)
+        }
+    }
+
+    /// The color of the `i`th series of a chart
+    pub fn series_color(&self, i: usize) -> Color {
+        if self.enabled {
+            self.series[i % self.series.len()]
+        } else {
+            Color::Reset
+        }
+    }
+
+    /// The color and fill of the `i`th series stacked in a bar
+    pub fn series_fill(&self, i: usize) -> (Color, &'static str) {
+        if self.enabled {
+            (self.series_color(i), "█")
+        } else {
+            (Color::Reset, PLAIN_FILLS[i % PLAIN_FILLS.len()])
+        }
+    }
+
+    /// The color and fill of everything stacked above the named series of a bar
+    pub fn rest_fill(&self) -> (Color, &'static str) {
+        if self.enabled {
+            (Color::DarkGray, "█")
+        } else {
+            (Color::Reset, "░")
+        }
+    }
+}
+
+fn to_ratatui(color: TermColor) -> Color {
+    match color {
+        TermColor::Black => Color::Black,
+        TermColor::Red => Color::Red,
+        TermColor::Green => Color::Green,
+        TermColor::Yellow => Color::Yellow,
+        TermColor::Blue => Color::Blue,
+        TermColor::Magenta => Color::Magenta,
+        TermColor::Cyan => Color::Cyan,
+        TermColor::White => Color::Gray,
+        TermColor::BrightBlack => Color::DarkGray,
+        TermColor::BrightRed => Color::LightRed,
+        TermColor::BrightGreen => Color::LightGreen,
+        TermColor::BrightYellow => Color::LightYellow,
+        TermColor::BrightBlue => Color::LightBlue,
+        TermColor::BrightMagenta => Color::LightMagenta,
+        TermColor::BrightCyan => Color::LightCyan,
+        TermColor::BrightWhite => Color::White,
+        TermColor::AnsiColor(index) => Color::Indexed(index),
+        TermColor::TrueColor { r, g, b } => Color::Rgb(r, g, b),
+    }
+}
+
+/// The bright version of a terminal color, or a hex color blended halfway towards white
+fn brighten(color: Color) -> Color {
+    let lighter = |channel: u8| channel / 2 + 128;
+    match color {
+        Color::Black => Color::DarkGray,
+        Color::Red => Color::LightRed,
+        Color::Green => Color::LightGreen,
+        Color::Yellow => Color::LightYellow,
+        Color::Blue => Color::LightBlue,
+        Color::Magenta => Color::LightMagenta,
+        Color::Cyan => Color::LightCyan,
+        Color::Gray => Color::White,
+        Color::Indexed(index) if index < 8 => Color::Indexed(index + 8),
+        Color::Rgb(r, g, b) => Color::Rgb(lighter(r), lighter(g), lighter(b)),
+        color => color,
+    }
+}
@@ -0,0 +1,58 @@
+use crate::tui::app::AppView;
+use fi_prometheus::PrometheusTimeScale;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// The last confirmed custom query
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SavedQuery {
+    pub range: i64,
+    pub unit: PrometheusTimeScale,
+    /// Minutes between refreshes of the charts, if they refreshed on their own
+    pub auto_refresh_minutes: Option<u64>,
+}
+
+/// What the TUI remembers between launches, in `$XDG_STATE_HOME/fi-nodes/tui.json`
+///
+/// The file is only a convenience, so one that can't be read or written is treated as empty
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SavedState {
+    pub query: Option<SavedQuery>,
+    /// The view open when the TUI was last quit
+    pub view: Option<AppView>,
+}
+
+impl SavedState {
+    pub fn load() -> Self {
+        state_file()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) {
+        let Some(path) = state_file() else {
+            return;
+        };
+        if let Some(dir) = path.parent()
+            && std::fs::create_dir_all(dir).is_err()
+        {
+            return;
+        }
+        if let Ok(contents) = serde_json::to_string_pretty(self) {
+            let _ = std::fs::write(path, contents);
+        }
+    }
+}
+
+/// The state file, under `$XDG_STATE_HOME`, or `~/.local/state` if that isn't set
+fn state_file() -> Option<PathBuf> {
+    let state_home = std::env::var_os("XDG_STATE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| {
+            std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/state"))
+        })?;
+    Some(state_home.join("fi-nodes").join("tui.json"))
+}
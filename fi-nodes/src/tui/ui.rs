@@ -1,10 +1,15 @@
 use super::app::DisplayMode;
 use crate::tui::app::{
-    App, AppError, AppState, AppView, BAR_GAP, BAR_WIDTH, CHART_HEIGHT, ChartData,
-    MAX_BARS_PER_CHART, MINIMUM_CHART_WIDTH, MainMenuSelection, ParameterFocus,
-    ParameterSelectionState, ScrollMode,
+    AccountUsers, App, AppError, AppState, AppView, BAR_GAP, BAR_WIDTH, CHART_HEIGHT, ChartData,
+    FetchProgress, LiveStatus, MAX_BARS_PER_CHART, MINIMUM_CHART_WIDTH, MainMenuSelection,
+    ParameterFocus, ParameterSelectionState, RefreshStatus, ScrollMode,
 };
+use crate::tui::interface::LIVE_REFRESH_INTERVAL;
+use crate::tui::palette::TuiPalette;
+use crate::tui::saved::SavedQuery;
+use chrono::{DateTime, Datelike, Local, Timelike, Utc};
 use fi_prometheus::PrometheusTimeScale;
+use fi_slurm::utils::count_blocks;
 use ratatui::{
     Frame,
     layout::{Constraint, Direction, Layout, Rect},
@@ -12,50 +17,86 @@ use ratatui::{
     style::{Color, Modifier, Style, Stylize},
     symbols::border,
     text::{Line, Span, Text},
-    widgets::{Bar, BarChart, BarGroup, Block, Borders, Paragraph, Tabs, Wrap},
+    widgets::{
+        Bar, BarChart, BarGroup, Block, Borders, Cell, Clear, Paragraph, Row, Table, Tabs, Wrap,
+    },
 };
+use std::time::Instant;
 
 // --- UI Drawing ---
 
-pub fn ui(f: &mut Frame, app_state: &AppState) {
+pub fn ui(
+    f: &mut Frame,
+    app_state: &AppState,
+    live: &LiveStatus,
+    show_help: bool,
+    palette: &TuiPalette,
+) {
+    draw_state(f, app_state, live, palette);
+    if show_help {
+        draw_help_overlay(f, app_state, palette);
+    }
+}
+
+fn draw_state(f: &mut Frame, app_state: &AppState, live: &LiveStatus, palette: &TuiPalette) {
     match app_state {
-        AppState::MainMenu { selected } => {
+        AppState::MainMenu {
+            selected,
+            last_query,
+        } => {
             let chunks = Layout::default()
                 .direction(Direction::Vertical)
                 .constraints([Constraint::Min(0), Constraint::Length(1)])
                 .split(f.area());
-            draw_main_menu(f, chunks[0], *selected);
-            draw_footer(f, chunks[1], None, None, None);
+            draw_main_menu(f, chunks[0], *selected, *last_query, palette);
+            draw_footer(f, chunks[1], None, None, None, None, None, palette);
         }
         AppState::ParameterSelection(state) => {
             let chunks = Layout::default()
                 .direction(Direction::Vertical)
                 .constraints([Constraint::Min(0), Constraint::Length(1)])
                 .split(f.area());
-            draw_parameter_selection_menu(f, chunks[0], state);
-            draw_footer(f, chunks[1], None, Some(state.focused_widget), None);
+            draw_parameter_selection_menu(f, chunks[0], state, palette);
+            draw_footer(
+                f,
+                chunks[1],
+                None,
+                Some(state.focused_widget),
+                None,
+                None,
+                None,
+                palette,
+            );
         }
-        AppState::Loading { tick } => draw_loading_screen(f, *tick),
+        AppState::Loading { tick, progress } => draw_loading_screen(f, *tick, progress, palette),
         AppState::Loaded(app) => {
             let main_chunks = Layout::default()
                 .direction(Direction::Vertical)
                 .constraints([
                     Constraint::Length(3), // For tabs
                     Constraint::Min(0),    // For chart content
+                    Constraint::Length(1), // For the cluster's current totals
                     Constraint::Length(1), // For footer
                 ])
                 .split(f.area());
 
-            let chart_data = get_chart_data(app);
-            let page_info = draw_charts(
-                f,
-                main_chunks[1],
-                chart_data,
-                app.scroll_offset,
-                app.scroll_mode,
-                app.current_view,
-                app.display_mode,
-            );
+            let page_info = if app.current_view == AppView::Live {
+                draw_live_view(f, main_chunks[1], live, app.scroll_offset, palette);
+                (1, 1)
+            } else if let Some(err) = &app.prometheus_error {
+                draw_prometheus_error(f, main_chunks[1], err, palette);
+                (1, 1)
+            } else if let Some(users) = &app.account_users
+                && !matches!(users.charts, Some(Ok(_)))
+            {
+                draw_account_users_status(f, main_chunks[1], users, palette);
+                (1, 1)
+            } else if let Some(chart_data) = app.current_chart_data() {
+                draw_charts(f, main_chunks[1], app, chart_data, palette)
+            } else {
+                draw_cluster_total(f, main_chunks[1], &app.cpu_by_account, palette);
+                (1, 1)
+            };
 
             draw_tabs(
                 f,
@@ -63,20 +104,35 @@ pub fn ui(f: &mut Frame, app_state: &AppState) {
                 app.current_view,
                 Some(page_info),
                 app_state,
+                palette,
             );
-            draw_footer(
-                f,
-                main_chunks[2],
-                Some(page_info),
-                None,
-                Some(app.scroll_mode),
-            );
+            draw_status_bar(f, main_chunks[2], live, palette);
+            if app.searching {
+                draw_search_prompt(f, main_chunks[3], &app.search, palette);
+            } else {
+                draw_footer(
+                    f,
+                    main_chunks[3],
+                    Some(page_info),
+                    None,
+                    app.current_view.has_chart_grid().then_some(app.scroll_mode),
+                    (app.current_view == AppView::Live).then_some(live),
+                    app.next_refresh,
+                    palette,
+                );
+            }
         }
-        AppState::Error(err) => draw_error_screen(f, err),
+        AppState::Error(err) => draw_error_screen(f, err, palette),
     }
 }
 
-fn draw_main_menu(f: &mut Frame, area: Rect, selected: MainMenuSelection) {
+fn draw_main_menu(
+    f: &mut Frame,
+    area: Rect,
+    selected: MainMenuSelection,
+    last_query: Option<SavedQuery>,
+    palette: &TuiPalette,
+) {
     let vertical_chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
@@ -97,8 +153,8 @@ fn draw_main_menu(f: &mut Frame, area: Rect, selected: MainMenuSelection) {
 
     let menu_area = horizontal_chunks[1];
 
-    let selected_style = Style::default().bg(Color::Blue).fg(Color::White);
-    let normal_style = Style::default().fg(Color::White);
+    let selected_style = palette.selected();
+    let normal_style = palette.fg(Color::White);
 
     let default_text = Paragraph::new("View Default Dashboard (Last 30 Days)")
         .alignment(Alignment::Center)
@@ -108,7 +164,11 @@ fn draw_main_menu(f: &mut Frame, area: Rect, selected: MainMenuSelection) {
             normal_style
         });
 
-    let custom_text = Paragraph::new("Custom Query")
+    let custom_label = match last_query {
+        Some(query) => format!("Custom Query (last: {} x {})", query.range, query.unit),
+        None => "Custom Query".to_string(),
+    };
+    let custom_text = Paragraph::new(custom_label)
         .alignment(Alignment::Center)
         .style(if selected == MainMenuSelection::Custom {
             selected_style
@@ -137,12 +197,17 @@ fn draw_main_menu(f: &mut Frame, area: Rect, selected: MainMenuSelection) {
     f.render_widget(custom_text, inner_chunks[2]);
 }
 
-fn draw_parameter_selection_menu(f: &mut Frame, area: Rect, state: &ParameterSelectionState) {
+fn draw_parameter_selection_menu(
+    f: &mut Frame,
+    area: Rect,
+    state: &ParameterSelectionState,
+    palette: &TuiPalette,
+) {
     let vertical_chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
             Constraint::Percentage(35),
-            Constraint::Length(9),
+            Constraint::Length(12),
             Constraint::Percentage(35),
         ])
         .split(area);
@@ -168,14 +233,15 @@ fn draw_parameter_selection_menu(f: &mut Frame, area: Rect, state: &ParameterSel
     let inner_chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
+            Constraint::Length(3),
             Constraint::Length(3),
             Constraint::Length(3),
             Constraint::Length(1),
         ])
         .split(inner_area);
 
-    let focused_style = Style::default().fg(Color::Yellow);
-    let normal_style = Style::default().fg(Color::White);
+    let focused_style = palette.strong(palette.focus);
+    let normal_style = palette.fg(Color::White);
 
     let range_block = Block::default()
         .title("Range")
@@ -219,6 +285,23 @@ fn draw_parameter_selection_menu(f: &mut Frame, area: Rect, state: &ParameterSel
         .alignment(Alignment::Center);
     f.render_widget(unit_paragraph, inner_chunks[1]);
 
+    let auto_refresh_block = Block::default()
+        .title("Auto-refresh every (minutes, blank for never)")
+        .borders(Borders::ALL)
+        .border_style(if state.focused_widget == ParameterFocus::AutoRefresh {
+            focused_style
+        } else {
+            normal_style
+        });
+
+    let auto_refresh_text = if state.focused_widget == ParameterFocus::AutoRefresh {
+        format!("{}█", state.auto_refresh_input)
+    } else {
+        state.auto_refresh_input.clone()
+    };
+    let auto_refresh_paragraph = Paragraph::new(auto_refresh_text).block(auto_refresh_block);
+    f.render_widget(auto_refresh_paragraph, inner_chunks[2]);
+
     let confirm_text = "Confirm";
     let confirm_paragraph = Paragraph::new(confirm_text)
         .alignment(Alignment::Center)
@@ -227,28 +310,188 @@ fn draw_parameter_selection_menu(f: &mut Frame, area: Rect, state: &ParameterSel
         } else {
             normal_style
         });
-    f.render_widget(confirm_paragraph, inner_chunks[2]);
+    f.render_widget(confirm_paragraph, inner_chunks[3]);
+}
+
+/// The keys of each state of the app, for the help overlay
+const HELP_SECTIONS: [(HelpSection, &[(&str, &str)]); 6] = [
+    (
+        HelpSection::MainMenu,
+        &[("↑/↓, k/j", "Select a dashboard"), ("Enter", "Open it")],
+    ),
+    (
+        HelpSection::Parameters,
+        &[
+            ("Tab", "Move to the next field"),
+            ("0-9, Backspace", "Edit the range or the refresh minutes"),
+            ("←/→, h/l", "Change the time unit"),
+            ("Enter", "Move on, or fetch the charts from Confirm"),
+        ],
+    ),
+    (
+        HelpSection::Loading,
+        &[("Esc", "Cancel the fetch and go back to the main menu")],
+    ),
+    (
+        HelpSection::Dashboard,
+        &[
+            ("0, t, 1-5", "Open a view"),
+            ("←/→, h/l, Tab", "Switch views"),
+            ("↑/↓, k/j", "Scroll pages, or rows of the live view"),
+            ("Enter", "Scroll within the charts"),
+            ("a", "Show availability or usage"),
+            ("s", "Sort charts by name, peak or current usage"),
+            ("/", "Search charts by name"),
+            ("c", "Compare with the window before, step by step"),
+            ("L", "Draw the view's bars on a log scale, or a linear one"),
+            ("Esc", "Clear the search"),
+            ("+/-", "Halve or double the range"),
+            ("[/]", "Move the range earlier or later"),
+            ("r", "Refresh the charts"),
+            ("e", "Export the series of the view to a CSV file"),
+            ("Backspace", "Go back from the users of an account"),
+        ],
+    ),
+    (
+        HelpSection::ChartMode,
+        &[
+            ("←/→, h/l", "Scroll the bars of every chart"),
+            ("↑/↓, k/j", "Scroll pages"),
+            ("Tab, Shift+Tab", "Select the next or previous chart"),
+            ("p", "Pin or unpin the selected chart"),
+            ("Enter", "Open the users of the selected account"),
+            ("Esc", "Go back to scrolling pages"),
+        ],
+    ),
+    (
+        HelpSection::Search,
+        &[
+            ("Any key", "Type into the search"),
+            ("Enter", "Keep the search"),
+            ("Esc", "Clear the search"),
+        ],
+    ),
+];
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum HelpSection {
+    MainMenu,
+    Parameters,
+    Loading,
+    Dashboard,
+    ChartMode,
+    Search,
+}
+
+impl HelpSection {
+    fn title(self) -> &'static str {
+        match self {
+            HelpSection::MainMenu => "Main menu",
+            HelpSection::Parameters => "Custom query",
+            HelpSection::Loading => "Loading",
+            HelpSection::Dashboard => "Dashboard",
+            HelpSection::ChartMode => "Chart mode",
+            HelpSection::Search => "Searching",
+        }
+    }
+
+    /// The section of the keys the app takes right now
+    fn current(app_state: &AppState) -> Option<Self> {
+        match app_state {
+            AppState::MainMenu { .. } => Some(HelpSection::MainMenu),
+            AppState::ParameterSelection(_) => Some(HelpSection::Parameters),
+            AppState::Loaded(app) if app.searching => Some(HelpSection::Search),
+            AppState::Loaded(app) if app.scroll_mode == ScrollMode::Chart => {
+                Some(HelpSection::ChartMode)
+            }
+            AppState::Loaded(_) => Some(HelpSection::Dashboard),
+            AppState::Loading { .. } => Some(HelpSection::Loading),
+            AppState::Error(_) => None,
+        }
+    }
+}
+
+/// Lists the keys of every state over the screen, with those of the current state marked
+fn draw_help_overlay(f: &mut Frame, app_state: &AppState, palette: &TuiPalette) {
+    const KEY_WIDTH: usize = 16;
+    let current = HelpSection::current(app_state);
+
+    let mut lines = Vec::new();
+    for (section, keys) in HELP_SECTIONS {
+        if !lines.is_empty() {
+            lines.push(Line::from(""));
+        }
+        let heading = if Some(section) == current {
+            Span::styled(
+                format!("{} (now)", section.title()),
+                palette.strong(palette.focus).bold(),
+            )
+        } else {
+            Span::from(section.title()).bold()
+        };
+        lines.push(Line::from(heading));
+        for (key, action) in keys {
+            lines.push(Line::from(vec![
+                Span::styled(format!("  {:<KEY_WIDTH$}", key), palette.fg(palette.accent)),
+                Span::from(*action),
+            ]));
+        }
+    }
+    lines.push(Line::from(""));
+    lines.push(Line::from("(q) quits, except while searching").italic());
+
+    let screen = f.area();
+    let width = 64.min(screen.width);
+    let height = (lines.len() as u16 + 2).min(screen.height);
+    let area = Rect {
+        x: screen.x + (screen.width - width) / 2,
+        y: screen.y + (screen.height - height) / 2,
+        width,
+        height,
+    };
+
+    let paragraph = Paragraph::new(lines).block(
+        Block::default()
+            .title(" Keys ((?) or (Esc) to close) ")
+            .borders(Borders::ALL)
+            .border_set(border::ROUNDED)
+            .border_style(palette.strong(palette.focus)),
+    );
+    f.render_widget(Clear, area);
+    f.render_widget(paragraph, area);
 }
 
-fn draw_loading_screen(f: &mut Frame, tick: usize) {
+fn draw_loading_screen(f: &mut Frame, tick: usize, progress: &FetchProgress, palette: &TuiPalette) {
+    let mut lines = vec![
+        Line::from(format!("Loading Data{}", ".".repeat(tick % 4))),
+        Line::from(format!(
+            "{}/{} queries complete",
+            progress.complete, progress.total
+        )),
+    ];
+    // the others are still waited for, but the charts won't load without these
+    for (name, err) in &progress.failed {
+        lines.push(Line::styled(
+            format!("{} failed: {}", name, err),
+            palette.strong(palette.alert),
+        ));
+    }
+    lines.push(Line::styled("(Esc) to cancel", palette.fg(Color::Gray)));
+
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints(
             [
-                Constraint::Percentage(45),
-                Constraint::Length(3),
-                Constraint::Percentage(45),
+                Constraint::Percentage(40),
+                Constraint::Length(lines.len() as u16 + 2),
+                Constraint::Percentage(40),
             ]
             .as_ref(),
         )
         .split(f.area());
 
-    let loading_text = "Loading Data";
-    let dots = ".".repeat(tick % 4);
-    let text = format!("{}{}", loading_text, dots);
-
-    let paragraph = Paragraph::new(text)
-        .style(Style::default().fg(Color::White))
+    let paragraph = Paragraph::new(lines)
+        .style(palette.fg(Color::White))
         .block(
             Block::default()
                 .borders(Borders::ALL)
@@ -260,7 +503,7 @@ fn draw_loading_screen(f: &mut Frame, tick: usize) {
     f.render_widget(paragraph, chunks[1]);
 }
 
-fn draw_error_screen(f: &mut Frame, err: &AppError) {
+fn draw_error_screen(f: &mut Frame, err: &AppError, palette: &TuiPalette) {
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints(
@@ -276,7 +519,7 @@ fn draw_error_screen(f: &mut Frame, err: &AppError) {
     let error_text = Text::from(vec![
         Line::from(Span::styled(
             "An error occurred:",
-            Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+            palette.fg(palette.error).add_modifier(Modifier::BOLD),
         )),
         Line::from(""),
         Line::from(err.to_string()),
@@ -286,12 +529,12 @@ fn draw_error_screen(f: &mut Frame, err: &AppError) {
 
     let paragraph = Paragraph::new(error_text)
         .wrap(Wrap { trim: true })
-        .style(Style::default().fg(Color::White))
+        .style(palette.fg(Color::White))
         .block(
             Block::default()
                 .borders(Borders::ALL)
                 .title("Error")
-                .border_style(Style::default().fg(Color::Red))
+                .border_style(palette.strong(palette.error))
                 .border_set(border::ROUNDED),
         )
         .alignment(Alignment::Center);
@@ -299,12 +542,204 @@ fn draw_error_screen(f: &mut Frame, err: &AppError) {
     f.render_widget(paragraph, chunks[1]);
 }
 
-fn get_chart_data(app: &App) -> &ChartData {
-    match app.current_view {
-        AppView::CpuByAccount => &app.cpu_by_account,
-        AppView::CpuByNode => &app.cpu_by_node,
-        AppView::GpuByType => &app.gpu_by_type,
+/// The charts of the current view, or None for a view that isn't a grid of charts
+/// Shows why a Prometheus view has no charts, in place of them
+fn draw_prometheus_error(f: &mut Frame, area: Rect, err: &AppError, palette: &TuiPalette) {
+    let text = Text::from(vec![
+        Line::from(Span::styled(
+            "Prometheus data is unavailable:",
+            palette.fg(palette.error).add_modifier(Modifier::BOLD),
+        )),
+        Line::from(""),
+        Line::from(err.to_string()),
+        Line::from(""),
+        Line::from("Press (0) for the live view of the cluster from Slurm."),
+    ]);
+    let paragraph = Paragraph::new(text)
+        .wrap(Wrap { trim: true })
+        .alignment(Alignment::Center)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_set(border::ROUNDED),
+        );
+    f.render_widget(paragraph, area);
+}
+
+/// Shows that the users of an account are being fetched, or why they couldn't be, in place
+/// of their charts
+fn draw_account_users_status(
+    f: &mut Frame,
+    area: Rect,
+    users: &AccountUsers,
+    palette: &TuiPalette,
+) {
+    let text = match &users.charts {
+        Some(Err(err)) => Text::from(vec![
+            Line::from(Span::styled(
+                format!("The users of {} couldn't be fetched:", users.account),
+                palette.fg(palette.error).add_modifier(Modifier::BOLD),
+            )),
+            Line::from(""),
+            Line::from(err.to_string()),
+            Line::from(""),
+            Line::from("Press (Backspace) to go back to the accounts."),
+        ]),
+        _ => Text::from(format!("Fetching the users of {}...", users.account)),
+    };
+    let paragraph = Paragraph::new(text)
+        .wrap(Wrap { trim: true })
+        .alignment(Alignment::Center)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_set(border::ROUNDED),
+        );
+    f.render_widget(paragraph, area);
+}
+
+// width of the availability bars in the live view, in characters
+const LIVE_BAR_WIDTH: usize = 20;
+
+/// A bar of the share of `total` that is idle, drawn with the tree report's partial blocks
+fn avail_bar(idle: u32, total: u32) -> String {
+    if total == 0 {
+        return " ".repeat(LIVE_BAR_WIDTH);
     }
+    let (full, empty, partial) = count_blocks(LIVE_BAR_WIDTH, idle as f64 / total as f64);
+    format!(
+        "{}{}{}",
+        "█".repeat(full),
+        partial.unwrap_or_default(),
+        " ".repeat(empty)
+    )
+}
+
+/// Draws the tree report of the current cluster state as a table, with the idle share of each
+/// branch's nodes and cores as gauges
+fn draw_live_view(
+    f: &mut Frame,
+    area: Rect,
+    live: &LiveStatus,
+    scroll_offset: usize,
+    palette: &TuiPalette,
+) {
+    let block = Block::default()
+        .title(Span::from("Live Cluster State").bold())
+        .borders(Borders::ALL)
+        .border_set(border::ROUNDED);
+
+    let Some(data) = &live.data else {
+        let text = match &live.error {
+            Some(err) => format!("Failed to load the cluster state from Slurm: {}", err),
+            None => "Loading the cluster state from Slurm...".to_string(),
+        };
+        let paragraph = Paragraph::new(text)
+            .wrap(Wrap { trim: true })
+            .alignment(Alignment::Center)
+            .block(block);
+        f.render_widget(paragraph, area);
+        return;
+    };
+
+    let header = Row::new([
+        "FEATURE",
+        "NODES",
+        "IDLE NODES",
+        "CORES",
+        "IDLE CORES",
+        "USED",
+    ])
+    .style(Style::default().add_modifier(Modifier::BOLD));
+
+    let rows: Vec<Row> = data
+        .rows
+        .iter()
+        .skip(scroll_offset)
+        .map(|row| {
+            let stats = &row.stats;
+            Row::new([
+                Cell::from(format!("{}{}", "  ".repeat(row.depth), row.name)),
+                Cell::from(format!("{}/{}", stats.idle_nodes, stats.total_nodes)),
+                Cell::from(avail_bar(stats.idle_nodes, stats.total_nodes))
+                    .style(palette.fg(palette.nodes)),
+                Cell::from(format!("{}/{}", stats.idle_cpus, stats.total_cpus)),
+                Cell::from(avail_bar(stats.idle_cpus, stats.total_cpus))
+                    .style(palette.fg(palette.cpus)),
+                Cell::from(format!("{:>3.0}%", (stats.utilization() * 100.0).floor())),
+            ])
+        })
+        .collect();
+
+    let name_width = data
+        .rows
+        .iter()
+        .map(|row| row.depth * 2 + row.name.len())
+        .max()
+        .unwrap_or(0)
+        .max("FEATURE".len()) as u16;
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Length(name_width),
+            Constraint::Length(11),
+            Constraint::Length(LIVE_BAR_WIDTH as u16),
+            Constraint::Length(13),
+            Constraint::Length(LIVE_BAR_WIDTH as u16),
+            Constraint::Length(5),
+        ],
+    )
+    .header(header)
+    .column_spacing(2)
+    .block(block);
+    f.render_widget(table, area);
+}
+
+/// Shows the idle nodes, cores and GPUs of the cluster as last loaded from Slurm, so that
+/// the current state stays in view next to the history
+fn draw_status_bar(f: &mut Frame, area: Rect, live: &LiveStatus, palette: &TuiPalette) {
+    let mut spans = vec![Span::from("Now: ").bold()];
+    match (&live.data, &live.error) {
+        (Some(data), _) => {
+            let idle = |label: &str, idle: u32, total: u32| {
+                vec![
+                    Span::from(format!("{}/{}", idle, total)).bold(),
+                    Span::from(format!(" {} idle", label)),
+                ]
+            };
+            if let Some(totals) = data.totals() {
+                spans.extend(idle("nodes", totals.idle_nodes, totals.total_nodes));
+                spans.push(Span::from(" | "));
+                spans.extend(idle("cores", totals.idle_cpus, totals.total_cpus));
+            }
+            if data.gpus.total_cpus > 0 {
+                spans.push(Span::from(" | "));
+                spans.extend(idle("GPUs", data.gpus.idle_cpus, data.gpus.total_cpus));
+            }
+            spans.push(Span::styled(
+                format!(" (as of {})", data.taken_at.format("%H:%M:%S")),
+                palette.fg(Color::Gray),
+            ));
+            if live.error.is_some() {
+                spans.push(Span::styled(
+                    " (last refresh failed)",
+                    palette.strong(palette.alert),
+                ));
+            }
+        }
+        (None, Some(err)) => spans.push(Span::styled(
+            format!("the cluster state couldn't be loaded from Slurm: {}", err),
+            palette.strong(palette.alert),
+        )),
+        (None, None) => spans.push(Span::styled(
+            "loading the cluster state from Slurm...",
+            palette.fg(Color::Gray),
+        )),
+    }
+    f.render_widget(
+        Paragraph::new(Line::from(spans)).alignment(Alignment::Center),
+        area,
+    );
 }
 
 fn draw_tabs(
@@ -313,17 +748,26 @@ fn draw_tabs(
     current_view: AppView,
     page_info: Option<(CurrentPageIdx, TotalPagesCnt)>,
     app_state: &AppState,
+    palette: &TuiPalette,
 ) {
     let base_titles = [
+        "(0) Live",
+        "(t) Cluster Total",
         "(1) Cores by Account",
         "(2) Cores by Node",
         "(3) GPU by Type",
+        "(4) GPU by Account",
+        "(5) Cores by Feature",
     ];
 
     let selected_index = match current_view {
-        AppView::CpuByAccount => 0,
-        AppView::CpuByNode => 1,
-        AppView::GpuByType => 2,
+        AppView::Live => 0,
+        AppView::ClusterTotal => 1,
+        AppView::CpuByAccount => 2,
+        AppView::CpuByNode => 3,
+        AppView::GpuByType => 4,
+        AppView::GpuByAccount => 5,
+        AppView::CpuByFeature => 6,
     };
 
     let mut titles: Vec<Line> = base_titles
@@ -347,11 +791,36 @@ fn draw_tabs(
         })
         .collect();
 
-    let time_unit = match app_state {
-        AppState::Loaded(app) => app.query_time_scale,
+    let (range, time_unit, end) = match app_state {
+        AppState::Loaded(app) => (app.query_range, app.query_time_scale, app.query_end),
         _ => panic!(), // we should definitely be in a Loaded app state
     };
-    titles.push(Line::from(format!("Time Scale: {}", time_unit)));
+    titles.push(Line::from(format!("Time Scale: {} x {}", range, time_unit)));
+    if let Some(end) = end {
+        titles.push(Line::from(format!(
+            "Ending {}",
+            end.with_timezone(&Local).format("%b %-d %H:%M")
+        )));
+    }
+
+    if let AppState::Loaded(app) = app_state
+        && current_view.has_chart_grid()
+    {
+        titles.push(Line::from(format!("Sort(s): {}", app.chart_sort)));
+        if let Some(data) = app.current_chart_data() {
+            let scale = if data.log_scale { "log" } else { "linear" };
+            titles.push(Line::from(format!("Scale(L): {}", scale)));
+        }
+        if let Some(comparison) = &app.comparison {
+            titles.push(Line::from(format!(
+                "Compare(c): window ending {}",
+                comparison.end.with_timezone(&Local).format("%b %-d %H:%M")
+            )));
+        }
+        if !app.search.is_empty() && !app.searching {
+            titles.push(Line::from(format!("Search: {} (Esc clears)", app.search)));
+        }
+    }
 
     // display mode tab
     let display_mode = match app_state {
@@ -379,24 +848,311 @@ fn draw_tabs(
 
     titles.push(Line::from(vec![avail_span, Span::raw("/"), usage_span]));
 
+    // the charts stay on screen while they are fetched again, with only the title saying so
+    let mut block_title = vec![Span::from("Dashboard Views")];
+    if let AppState::Loaded(app) = app_state {
+        if let Some(users) = &app.account_users {
+            block_title.push(Span::styled(
+                format!(" > Users of {} ((Backspace) to go back)", users.account),
+                palette.fg(palette.accent),
+            ));
+        }
+        match &app.last_export {
+            None => {}
+            Some(Ok(path)) => block_title.push(Span::styled(
+                format!(" (exported to {})", path.display()),
+                palette.fg(palette.success),
+            )),
+            Some(Err(err)) => block_title.push(Span::styled(
+                format!(" ({})", err),
+                palette.strong(palette.alert),
+            )),
+        }
+        match app.comparison.as_ref().map(|comparison| &comparison.charts) {
+            Some(None) => block_title.push(Span::styled(
+                " (fetching the earlier window)",
+                palette.strong(palette.focus),
+            )),
+            Some(Some(Err(err))) => block_title.push(Span::styled(
+                format!(" (earlier window failed: {})", err),
+                palette.strong(palette.alert),
+            )),
+            _ => {}
+        }
+        match &app.refresh {
+            RefreshStatus::Idle => {}
+            RefreshStatus::Refreshing { tick } => block_title.push(Span::styled(
+                format!(" (refreshing{:<3})", ".".repeat(tick / 4 % 4)),
+                palette.strong(palette.focus),
+            )),
+            RefreshStatus::Failed(err) => block_title.push(Span::styled(
+                format!(" (refresh failed: {})", err),
+                palette.strong(palette.alert),
+            )),
+        }
+    }
+
     let tabs = Tabs::new(titles)
         .block(
             Block::default()
-                .title("Dashboard Views")
+                .title(Line::from(block_title))
                 .borders(Borders::ALL)
-                .border_style(Style::default().fg(Color::White)),
+                .border_style(palette.fg(Color::White)),
         )
         .select(selected_index)
-        .style(Style::default().fg(Color::Gray))
-        .highlight_style(
-            Style::default()
-                .add_modifier(Modifier::BOLD)
-                .bg(Color::Blue),
-        );
+        .style(palette.fg(Color::Gray))
+        .highlight_style(palette.selected().add_modifier(Modifier::BOLD));
 
     f.render_widget(tabs, area);
 }
 
+// accounts given their own color in the cluster total chart; the rest are stacked as "other"
+const STACKED_ACCOUNTS: usize = 8;
+
+/// Draws the usage of every account stacked into one bar per step, against the cluster's
+/// capacity, showing the most recent steps that fit
+///
+/// The accounts with the most usage over the window get their own colors, at the bottom of
+/// each bar, and the rest are stacked above them together
+fn draw_cluster_total(f: &mut Frame, area: Rect, data: &ChartData, palette: &TuiPalette) {
+    let mut accounts: Vec<(&str, &Vec<u64>)> = data
+        .source_data
+        .iter()
+        .map(|(name, values)| (name.as_str(), values))
+        .collect();
+    accounts.sort_by_key(|(name, values)| (std::cmp::Reverse(values.iter().sum::<u64>()), *name));
+    let num_points = accounts
+        .iter()
+        .map(|(_, values)| values.len())
+        .max()
+        .unwrap_or(0);
+    let capacity = data.capacity_data.get("Total");
+
+    // the stack of each step: the named accounts in order, then everyone else
+    let stack_at = |point: usize| -> Vec<u64> {
+        let value = |values: &Vec<u64>| values.get(point).copied().unwrap_or(0);
+        let mut stack: Vec<u64> = accounts
+            .iter()
+            .take(STACKED_ACCOUNTS)
+            .map(|(_, values)| value(values))
+            .collect();
+        stack.push(
+            accounts
+                .iter()
+                .skip(STACKED_ACCOUNTS)
+                .map(|(_, values)| value(values))
+                .sum(),
+        );
+        stack
+    };
+    let capacity_at = |point: usize| {
+        capacity
+            .and_then(|values| values.get(point))
+            .copied()
+            .unwrap_or(0)
+    };
+    let share = |used: u64, total: u64| {
+        if total == 0 {
+            0.0
+        } else {
+            used as f64 / total as f64 * 100.0
+        }
+    };
+
+    let title = if num_points == 0 {
+        "All Accounts".to_string()
+    } else {
+        let last = num_points - 1;
+        let peak = (0..num_points)
+            .map(|point| share(stack_at(point).iter().sum(), capacity_at(point)))
+            .fold(0.0, f64::max);
+        format!(
+            "All Accounts: {:.0}% of capacity now, {:.0}% at peak",
+            share(stack_at(last).iter().sum(), capacity_at(last)),
+            peak
+        )
+    };
+    let block = Block::default()
+        .title(Span::from(title).bold())
+        .borders(Borders::ALL)
+        .border_set(border::ROUNDED);
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(2), // legend
+            Constraint::Min(0),    // bars
+            Constraint::Length(1), // time labels
+        ])
+        .split(inner);
+
+    let mut legend: Vec<Span> = accounts
+        .iter()
+        .take(STACKED_ACCOUNTS)
+        .enumerate()
+        .map(|(i, (name, _))| {
+            let (color, fill) = palette.series_fill(i);
+            Span::styled(format!("{} {}  ", fill, name), Style::default().fg(color))
+        })
+        .collect();
+    if accounts.len() > STACKED_ACCOUNTS {
+        let (color, fill) = palette.rest_fill();
+        legend.push(Span::styled(
+            format!("{} other  ", fill),
+            Style::default().fg(color),
+        ));
+    }
+    legend.push(Span::styled("─ capacity", palette.fg(Color::White)));
+    f.render_widget(
+        Paragraph::new(Line::from(legend)).wrap(Wrap { trim: true }),
+        chunks[0],
+    );
+
+    let bars_area = chunks[1];
+    let step_width = BAR_WIDTH + BAR_GAP;
+    let num_visible = ((bars_area.width / step_width) as usize).min(num_points);
+    if num_visible == 0 || bars_area.height == 0 {
+        return;
+    }
+    let first = num_points - num_visible;
+    let scale = (first..num_points)
+        .map(|point| capacity_at(point).max(stack_at(point).iter().sum()))
+        .max()
+        .unwrap_or(0)
+        .max(1) as f64;
+    let height = bars_area.height as usize;
+    // the height of a value in rows, rounded so that a full cluster reaches the top row
+    let rows = |value: u64| ((value as f64 / scale) * height as f64).round() as usize;
+
+    let buf = f.buffer_mut();
+    for (column, point) in (first..num_points).enumerate() {
+        let x = bars_area.x + column as u16 * step_width;
+
+        // the colors and fills of the bar from the bottom row up
+        let mut cells: Vec<(Color, &str)> = Vec::with_capacity(height);
+        let mut stacked = 0;
+        for (i, value) in stack_at(point).into_iter().enumerate() {
+            stacked += value;
+            let fill = if i < STACKED_ACCOUNTS {
+                palette.series_fill(i)
+            } else {
+                palette.rest_fill()
+            };
+            let top = rows(stacked).min(height);
+            while cells.len() < top {
+                cells.push(fill);
+            }
+        }
+        let capacity_row = rows(capacity_at(point)).clamp(1, height) - 1;
+
+        for row in 0..height {
+            let y = bars_area.y + (height - 1 - row) as u16;
+            for dx in 0..step_width {
+                let Some(cell) = buf.cell_mut((x + dx, y)) else {
+                    continue;
+                };
+                if dx < BAR_WIDTH
+                    && let Some((color, fill)) = cells.get(row)
+                {
+                    cell.set_symbol(fill).set_fg(*color);
+                } else if row == capacity_row {
+                    cell.set_symbol("─").set_style(palette.fg(Color::White));
+                }
+            }
+        }
+
+        buf.set_string(
+            x,
+            chunks[2].y,
+            bar_label(&data.timestamps, point, num_points),
+            palette.fg(Color::Gray),
+        );
+    }
+}
+
+/// The height of a bar on a log scale, in hundredths of the natural log of one more than the
+/// value, so that a zero stays flat and every other value shows
+fn log_height(value: u64) -> u64 {
+    ((value as f64).ln_1p() * 100.0).round() as u64
+}
+
+/// A count shortened to fit on a bar, such as 950, 12k or 3M
+fn compact_count(value: u64) -> String {
+    match value {
+        0..1_000 => value.to_string(),
+        1_000..1_000_000 => format!("{}k", value / 1_000),
+        _ => format!("{}M", value / 1_000_000),
+    }
+}
+
+/// The step of a series, from the time between its first two points
+fn series_scale(timestamps: &[DateTime<Utc>]) -> PrometheusTimeScale {
+    let step = match timestamps {
+        [first, second, ..] => (*second - *first).num_seconds(),
+        _ => PrometheusTimeScale::Days.step_secs(),
+    };
+    [
+        PrometheusTimeScale::Minutes,
+        PrometheusTimeScale::Hours,
+        PrometheusTimeScale::Days,
+        PrometheusTimeScale::Weeks,
+    ]
+    .into_iter()
+    .find(|scale| step <= scale.step_secs())
+    .unwrap_or(PrometheusTimeScale::Years)
+}
+
+/// Labels the bar of one point of a series with its local time, cut to the width of a bar:
+/// the unit of the step, or the next larger unit where that rolls over, such as the weekday at
+/// midnight of an hourly series. Series without timestamps count the steps back from now
+fn bar_label(timestamps: &[DateTime<Utc>], point: usize, num_points: usize) -> String {
+    let Some(time) = timestamps.get(point).map(|t| t.with_timezone(&Local)) else {
+        let step = num_points.saturating_sub(point + 1);
+        return if step == 0 {
+            "Now".to_string()
+        } else {
+            format!("-{}", step)
+        };
+    };
+    let previous = point
+        .checked_sub(1)
+        .and_then(|p| timestamps.get(p))
+        .map(|t| t.with_timezone(&Local));
+    let rolled_over = |unit: fn(&DateTime<Local>) -> u32| {
+        previous.is_some_and(|previous| unit(&previous) != unit(&time))
+    };
+
+    let label = match series_scale(timestamps) {
+        PrometheusTimeScale::Minutes if rolled_over(|t| t.hour()) => time.format("%Hh"),
+        PrometheusTimeScale::Minutes => time.format(":%M"),
+        PrometheusTimeScale::Hours if rolled_over(|t| t.ordinal()) => time.format("%a"),
+        PrometheusTimeScale::Hours => time.format("%Hh"),
+        PrometheusTimeScale::Days | PrometheusTimeScale::Weeks if rolled_over(|t| t.month()) => {
+            time.format("%b")
+        }
+        PrometheusTimeScale::Days | PrometheusTimeScale::Weeks => time.format("%-d"),
+        PrometheusTimeScale::Years => time.format("'%y"),
+    };
+    label.to_string().chars().take(BAR_WIDTH as usize).collect()
+}
+
+/// The dates, and the times for a series finer than a day, from the first to the last of the
+/// visible points
+fn range_label(timestamps: &[DateTime<Utc>], first: usize, last: usize) -> Option<String> {
+    let format = match series_scale(timestamps) {
+        PrometheusTimeScale::Minutes | PrometheusTimeScale::Hours => "%b %-d %H:%M",
+        _ => "%b %-d %Y",
+    };
+    let at = |point: usize| {
+        timestamps
+            .get(point)
+            .map(|t| t.with_timezone(&Local).format(format).to_string())
+    };
+    Some(format!("{} - {}", at(first)?, at(last)?))
+}
+
 type CurrentPageIdx = usize;
 type TotalPagesCnt = usize;
 
@@ -404,30 +1160,37 @@ type TotalPagesCnt = usize;
 fn draw_charts(
     f: &mut Frame,
     area: Rect,
+    app: &App,
     data: &ChartData,
-    scroll_offset: usize,
-    scroll_mode: ScrollMode,
-    current_view: AppView,
-    display_mode: DisplayMode,
+    palette: &TuiPalette,
 ) -> (CurrentPageIdx, TotalPagesCnt) {
-    let colors = [
-        Color::Cyan,
-        Color::Magenta,
-        Color::Yellow,
-        Color::Green,
-        Color::Red,
-        Color::LightBlue,
-        Color::LightMagenta,
-        Color::LightYellow,
-        Color::LightGreen,
-        Color::LightRed,
-    ];
+    let App {
+        scroll_offset,
+        scroll_mode,
+        current_view,
+        display_mode,
+        chart_sort,
+        ..
+    } = *app;
+    // the earlier window is only fetched for the views' own charts, not an account's users
+    let earlier = match (&app.comparison, &app.account_users) {
+        (Some(comparison), None) => comparison.charts_of(current_view),
+        _ => None,
+    };
 
-    let mut sorted_series: Vec<_> = data.source_data.iter().collect();
-    sorted_series.sort_by_key(|(name, _)| *name);
+    let sorted_series = data.ordered_series(chart_sort, &app.search);
+    let selected_index = data.selected_index(chart_sort, &app.search);
 
     let num_charts = sorted_series.len();
     if num_charts == 0 {
+        if !app.search.is_empty() {
+            f.render_widget(
+                Paragraph::new(format!("No charts match '{}'", app.search))
+                    .alignment(Alignment::Center)
+                    .style(palette.fg(Color::Gray)),
+                area,
+            );
+        }
         return (1, 1);
     }
 
@@ -499,16 +1262,32 @@ fn draw_charts(
             if let Some((name, values)) = chart_iter.next() {
                 let cell_area = col_chunks[j];
 
+                let absolute_chart_index = (clamped_offset + i) * num_cols + j;
+
                 let border_style = if scroll_mode == ScrollMode::Chart {
-                    Style::default().fg(Color::Yellow)
+                    palette.strong(palette.focus)
                 } else {
-                    Style::default().fg(Color::White)
+                    palette.fg(Color::White)
                 };
+                // the chart pinning applies to stands out in chart mode
+                let border_set =
+                    if scroll_mode == ScrollMode::Chart && absolute_chart_index == selected_index {
+                        border::THICK
+                    } else {
+                        border::ROUNDED
+                    };
+                let mut title = vec![Span::from(name.as_str()).bold()];
+                if data.pinned.contains(*name) {
+                    title.push(Span::styled(" (pinned)", palette.fg(Color::Gray)));
+                }
+                if data.log_scale {
+                    title.push(Span::styled(" (log scale)", palette.fg(Color::Gray)));
+                }
 
                 let outer_block = Block::default()
-                    .title(Span::from(*name).bold())
+                    .title(Line::from(title))
                     .borders(Borders::ALL)
-                    .border_set(border::ROUNDED)
+                    .border_set(border_set)
                     .border_style(border_style);
 
                 let inner_area = outer_block.inner(cell_area);
@@ -527,36 +1306,31 @@ fn draw_charts(
                     height: inner_area.height.saturating_sub(1),
                 };
 
-                let absolute_chart_index = (clamped_offset + i) * num_cols + j;
-                let color = colors[absolute_chart_index % colors.len()];
+                let color = palette.series_color(absolute_chart_index);
 
                 let num_points = values.len();
 
-                let max_h_scroll = num_points.saturating_sub(MAX_BARS_PER_CHART);
+                // each step takes a pair of bars when compared with the window before
+                let bars_per_chart = if earlier.is_some() {
+                    MAX_BARS_PER_CHART / 2
+                } else {
+                    MAX_BARS_PER_CHART
+                };
+                let max_h_scroll = num_points.saturating_sub(bars_per_chart);
                 let h_offset = data.horizontal_scroll_offset.min(max_h_scroll);
 
-                let visible_values: Vec<_> = values
-                    .iter()
-                    .skip(h_offset)
-                    .take(MAX_BARS_PER_CHART)
-                    .collect();
+                let visible_values: Vec<_> =
+                    values.iter().skip(h_offset).take(bars_per_chart).collect();
 
                 let time_labels: Vec<String> = (h_offset..h_offset + visible_values.len())
-                    .map(|i| {
-                        let step = num_points - 1 - i;
-                        if step == 0 {
-                            "Now".to_string()
-                        } else {
-                            format!("-{}", step)
-                        }
-                    })
+                    .map(|i| bar_label(&data.timestamps, i, num_points))
                     .collect();
 
                 // bar values: capacity minus usage = available capacity
                 // /
                 // prime target to move this logic out of draw_charts and cache it somewhere else,
                 // no reason to be doing this once per frame, since it's the same for
-                let cap_key = if current_view == AppView::CpuByAccount {
+                let cap_key = if current_view.has_shared_capacity() {
                     "Total"
                 } else {
                     *name
@@ -570,24 +1344,75 @@ fn draw_charts(
                     .take(visible_values.len())
                     .cloned()
                     .collect();
-                let mut bar_data: Vec<Bar> = visible_values
-                    .iter()
-                    .enumerate()
-                    .map(|(k, &usage)| {
+                let bar_value = |usage: u64, cap: u64| match display_mode {
+                    DisplayMode::Usage => usage,
+                    DisplayMode::Availability => cap.saturating_sub(usage),
+                };
+                let bar_height = |value: u64| {
+                    if data.log_scale {
+                        log_height(value)
+                    } else {
+                        value
+                    }
+                };
+                // bars on a log scale are labelled with what they stand for, which their
+                // heights no longer show
+                let bar_text = |value: u64| {
+                    if data.log_scale {
+                        compact_count(value)
+                    } else {
+                        String::new()
+                    }
+                };
+                let mut bar_data: Vec<Bar> = Vec::new();
+                let mut pairs: Vec<BarGroup> = Vec::new();
+                if let Some(earlier) = earlier {
+                    // the earlier window's step in the same place, dimmed, then this window's
+                    let earlier_values = earlier.source_data.get(*name);
+                    let earlier_capacity = earlier.capacity_data.get(cap_key);
+                    for (k, &usage) in visible_values.iter().enumerate() {
+                        let at = |series: Option<&Vec<u64>>| {
+                            series
+                                .and_then(|values| values.get(h_offset + k))
+                                .copied()
+                                .unwrap_or(0)
+                        };
                         let cap = capacity_series.get(k).cloned().unwrap_or(0);
-                        let avail = cap.saturating_sub(*usage);
-                        Bar::default()
-                            .value(match display_mode {
-                                DisplayMode::Usage => *usage,
-                                DisplayMode::Availability => avail,
-                            })
-                            .label(time_labels.get(k).cloned().unwrap_or_default().into())
-                            .style(Style::default().fg(color))
-                            .text_value("".to_string())
-                    })
-                    .collect();
+                        pairs.push(
+                            BarGroup::default()
+                                .label(time_labels.get(k).cloned().unwrap_or_default().into())
+                                .bars(&[
+                                    Bar::default()
+                                        .value(bar_height(bar_value(
+                                            at(earlier_values),
+                                            at(earlier_capacity),
+                                        )))
+                                        .style(palette.fg(Color::DarkGray))
+                                        .text_value("".to_string()),
+                                    Bar::default()
+                                        .value(bar_height(bar_value(*usage, cap)))
+                                        .style(Style::default().fg(color))
+                                        .text_value("".to_string()),
+                                ]),
+                        );
+                    }
+                } else {
+                    bar_data = visible_values
+                        .iter()
+                        .enumerate()
+                        .map(|(k, &usage)| {
+                            let cap = capacity_series.get(k).cloned().unwrap_or(0);
+                            let value = bar_value(*usage, cap);
+                            Bar::default()
+                                .value(bar_height(value))
+                                .label(time_labels.get(k).cloned().unwrap_or_default().into())
+                                .style(Style::default().fg(color))
+                                .text_value(bar_text(value))
+                        })
+                        .collect();
+                }
 
-                let chart_specific_max = if current_view == AppView::CpuByAccount {
+                let chart_specific_max = if current_view.has_shared_capacity() {
                     data.capacity_data
                         .get("Total")
                         .and_then(|v| v.iter().max())
@@ -603,24 +1428,53 @@ fn draw_charts(
 
                 bar_data.push(
                     Bar::default()
-                        .value(chart_specific_max)
+                        .value(bar_height(chart_specific_max))
                         .label("MAX".into())
-                        .style(Style::default().fg(Color::White))
-                        .text_value("".to_string()),
+                        .style(palette.fg(Color::White))
+                        .text_value(bar_text(chart_specific_max)),
                 );
 
-                // Render the bar chart in the lower sub-area
-                let bar_group = BarGroup::default().bars(&bar_data);
-                let barchart = BarChart::default()
-                    .data(bar_group)
-                    .bar_width(BAR_WIDTH)
-                    .bar_gap(BAR_GAP);
+                // Render the bar chart in the lower sub-area, with the bars of each pair
+                // narrowed and together so that they read as one step
+                let barchart = if pairs.is_empty() {
+                    BarChart::default()
+                        .data(BarGroup::default().bars(&bar_data))
+                        .bar_width(BAR_WIDTH)
+                        .bar_gap(BAR_GAP)
+                } else {
+                    pairs
+                        .into_iter()
+                        .chain([BarGroup::default().bars(&bar_data)])
+                        .fold(BarChart::default(), |chart, pair| chart.data(pair))
+                        .bar_width(BAR_WIDTH - 1)
+                        .bar_gap(0)
+                        .group_gap(BAR_GAP)
+                };
                 f.render_widget(barchart, chart_area_inner);
-                // Render horizontal overflow indicators in the top 1-line slot
+                // Render the dates the visible bars cover, and horizontal overflow indicators, in
+                // the top 1-line slot
+                let last = (h_offset + visible_values.len()).saturating_sub(1);
+                let range =
+                    range_label(&data.timestamps, h_offset, last).map(|range| {
+                        match earlier
+                            .and_then(|earlier| range_label(&earlier.timestamps, h_offset, last))
+                        {
+                            Some(earlier_range) => format!("{} vs {}", range, earlier_range),
+                            None => range,
+                        }
+                    });
+                if let Some(range) = range {
+                    f.render_widget(
+                        Paragraph::new(range)
+                            .style(palette.fg(Color::Gray))
+                            .alignment(Alignment::Center),
+                        labels_area,
+                    );
+                }
                 if h_offset > 0 {
                     f.render_widget(
                         Paragraph::new("...")
-                            .style(Style::default().fg(Color::White))
+                            .style(palette.fg(Color::White))
                             .alignment(Alignment::Left),
                         labels_area,
                     );
@@ -628,7 +1482,7 @@ fn draw_charts(
                 if h_offset < max_h_scroll {
                     f.render_widget(
                         Paragraph::new("...")
-                            .style(Style::default().fg(Color::White))
+                            .style(palette.fg(Color::White))
                             .alignment(Alignment::Right),
                         labels_area,
                     );
@@ -641,16 +1495,36 @@ fn draw_charts(
     (current_page, total_pages)
 }
 
+#[allow(clippy::too_many_arguments)]
 fn draw_footer(
     f: &mut Frame,
     area: Rect,
     page_info: Option<(CurrentPageIdx, TotalPagesCnt)>,
     focus: Option<ParameterFocus>,
     scroll_mode: Option<ScrollMode>,
+    live: Option<&LiveStatus>,
+    next_refresh: Option<Instant>,
+    palette: &TuiPalette,
 ) {
-    let mut instructions = vec![Span::from("Use (q) to quit")];
-
-    if let Some((_, total)) = page_info {
+    let mut instructions = vec![Span::from("Use (q) to quit, (?) for help")];
+
+    if let Some(live) = live {
+        instructions.push(Span::from(", (h/l, ←/→, Tab, or numbers) to switch views"));
+        instructions.push(Span::from(", (k/j, ↑/↓) to scroll"));
+        if let Some(data) = &live.data {
+            instructions.push(Span::from(format!(
+                " | Updated {}, every {}s",
+                data.taken_at.format("%H:%M:%S"),
+                LIVE_REFRESH_INTERVAL.as_secs()
+            )));
+        }
+        if live.data.is_some() && live.error.is_some() {
+            instructions.push(Span::styled(
+                " (last refresh failed)",
+                palette.strong(palette.alert),
+            ));
+        }
+    } else if let Some((_, total)) = page_info {
         if let Some(mode) = scroll_mode {
             match mode {
                 ScrollMode::Page => {
@@ -659,29 +1533,58 @@ fn draw_footer(
                         instructions.push(Span::from(", (k/j, ↑/↓) to scroll pages"));
                     }
                     instructions.push(Span::from(", (Enter) to scroll charts"));
+                    instructions.push(Span::from(", (s) to sort, (/) to search"));
+                    instructions.push(Span::from(", (c) to compare"));
+                    instructions.push(Span::from(", (+/-) to zoom, ([/]) to pan"));
+                    instructions.push(Span::from(", (r) to refresh"));
                 }
                 ScrollMode::Chart => {
                     instructions.push(Span::from(", (h/l, ←/→) to scroll charts"));
                     instructions.push(Span::from(", (k/j, ↑/↓) to scroll pages"));
+                    instructions.push(Span::from(", (Tab) to select, (p) to pin"));
                     instructions.push(Span::from(", (Esc) to scroll pages"));
                 }
             }
+        } else {
+            // a single chart, which doesn't scroll
+            instructions.push(Span::from(", (h/l, ←/→, Tab, or numbers) to switch views"));
+            instructions.push(Span::from(", (r) to refresh"));
         }
     } else if let Some(focus_widget) = focus {
         instructions.push(Span::from(", (Tab to switch focus)"));
         match focus_widget {
             ParameterFocus::Range => instructions.push(Span::from(", (Enter numbers)")),
             ParameterFocus::Unit => instructions.push(Span::from(", (←/→ to change)")),
+            ParameterFocus::AutoRefresh => instructions.push(Span::from(", (Enter minutes)")),
             ParameterFocus::Confirm => instructions.push(Span::from(", (Enter to confirm)")),
         }
     } else {
         instructions.push(Span::from(", (↑/↓ to select), (Enter) to confirm"));
     }
 
+    if let Some(at) = next_refresh {
+        let remaining = at.saturating_duration_since(Instant::now()).as_secs();
+        instructions.push(Span::from(format!(
+            " | Next refresh in {}:{:02}",
+            remaining / 60,
+            remaining % 60
+        )));
+    }
+
     let footer_text = Line::from(instructions).alignment(Alignment::Center);
 
-    let footer_paragraph =
-        Paragraph::new(footer_text).style(Style::default().fg(Color::White).bg(Color::DarkGray));
+    let footer_paragraph = Paragraph::new(footer_text).style(palette.bar());
 
     f.render_widget(footer_paragraph, area);
 }
+
+/// Shows the search being typed in place of the footer
+fn draw_search_prompt(f: &mut Frame, area: Rect, search: &str, palette: &TuiPalette) {
+    let prompt = Line::from(vec![
+        Span::from("/"),
+        Span::from(search.to_string()).bold(),
+        Span::from("█"),
+        Span::styled("  (Enter) to keep, (Esc) to clear", palette.fg(Color::Gray)),
+    ]);
+    f.render_widget(Paragraph::new(prompt).style(palette.bar()), area);
+}
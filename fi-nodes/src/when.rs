@@ -0,0 +1,331 @@
+use crate::parse_window;
+use chrono::{DateTime, Duration, Local, Utc};
+use fi_slurm::filter::FeatureExpr;
+use fi_slurm::jobs::{JobState, SlurmJobs};
+use fi_slurm::nodes::{Node, NodeState};
+use fi_slurm::reservations::SlurmReservations;
+use fi_slurm::states::NodeStateFlags;
+use std::collections::HashMap;
+
+/// A hypothetical job, as given to --when, such as `cpus=64,gpus=4,nodes=2,constraint=genoa,time=4h`
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct WhenRequest {
+    pub cpus: u32,
+    pub gpus: u32,
+    /// The number of nodes the job's resources are split evenly across, or None to take them
+    /// from wherever they are free
+    pub nodes: Option<u32>,
+    pub constraint: Option<FeatureExpr>,
+    pub time: Option<Duration>,
+}
+
+impl WhenRequest {
+    /// Parses a comma-separated list of `key=value` pairs, where the keys are cpus, gpus, nodes,
+    /// constraint and time
+    pub fn parse(request: &str) -> Result<Self, String> {
+        let mut parsed = WhenRequest::default();
+        for pair in request.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let Some((key, value)) = pair.split_once('=') else {
+                return Err(format!("invalid request '{}': expected key=value", pair));
+            };
+            let count = || {
+                value
+                    .parse::<u32>()
+                    .map_err(|_| format!("invalid {} '{}': expected a number", key, value))
+            };
+            match key {
+                "cpus" => parsed.cpus = count()?,
+                "gpus" => parsed.gpus = count()?,
+                "nodes" => parsed.nodes = Some(count()?.max(1)),
+                "constraint" => parsed.constraint = Some(FeatureExpr::parse(value)?),
+                "time" => parsed.time = Some(parse_window(value)?),
+                _ => {
+                    return Err(format!(
+                        "unknown request key '{}': expected cpus, gpus, nodes, constraint or time",
+                        key
+                    ));
+                }
+            }
+        }
+        if parsed.cpus == 0 && parsed.gpus == 0 {
+            // like Slurm, a job gets at least one CPU
+            parsed.cpus = 1;
+        }
+        Ok(parsed)
+    }
+
+    /// The CPUs and GPUs the job needs on each of its nodes, if it asks for a number of nodes
+    fn per_node(&self) -> Option<(u32, u32)> {
+        self.nodes
+            .map(|nodes| (self.cpus.div_ceil(nodes), self.gpus.div_ceil(nodes)))
+    }
+}
+
+/// The resources of one node that are free now, and those its running jobs free as they end
+#[derive(Debug, Clone, Default)]
+struct NodeTimeline {
+    cpus: u32,
+    gpus: u32,
+    /// When each job on the node reaches its time limit, as a Unix timestamp, and the CPUs and
+    /// GPUs it frees
+    releases: Vec<(i64, u32, u32)>,
+    /// The start and end of each reservation holding the node, as Unix timestamps
+    reserved: Vec<(i64, i64)>,
+}
+
+impl NodeTimeline {
+    /// The CPUs and GPUs free by the given time
+    fn free_at(&self, time: i64) -> (u32, u32) {
+        self.releases
+            .iter()
+            .filter(|(end, _, _)| *end <= time)
+            .fold((self.cpus, self.gpus), |(cpus, gpus), (_, c, g)| {
+                (cpus + c, gpus + g)
+            })
+    }
+
+    /// Whether a reservation holds the node at some time between `start` and `end`, when a job
+    /// starting at `start` would still be running
+    fn is_reserved(&self, start: i64, end: i64) -> bool {
+        self.reserved
+            .iter()
+            .any(|&(from, until)| from < end && start < until)
+    }
+}
+
+/// Whether a node can run jobs now or once its jobs end, as opposed to being down, drained or
+/// in maintenance
+fn in_service(state: &NodeState) -> bool {
+    match state {
+        NodeState::Idle | NodeState::Mixed | NodeState::Allocated => true,
+        NodeState::Compound { base, flags } => {
+            in_service(base) && !flags.intersects(NodeStateFlags::UNAVAILABLE)
+        }
+        _ => false,
+    }
+}
+
+/// The earliest time at which the request fits, from `now` or the end of one of the running
+/// jobs or reservations, or None if it doesn't fit even once they have all ended
+///
+/// A node held by a reservation at some time before the job would reach its time limit, or at
+/// its start without one, offers nothing
+fn earliest_fit(request: &WhenRequest, nodes: &[NodeTimeline], now: i64) -> Option<i64> {
+    let mut times: Vec<i64> = nodes
+        .iter()
+        .flat_map(|node| {
+            let releases = node.releases.iter().map(|(end, _, _)| *end);
+            let reservation_ends = node.reserved.iter().map(|(_, end)| *end);
+            releases.chain(reservation_ends).map(|end| end.max(now))
+        })
+        .collect();
+    times.push(now);
+    times.sort_unstable();
+    times.dedup();
+
+    let walltime = request.time.map_or(1, |time| time.num_seconds().max(1));
+    times.into_iter().find(|&time| {
+        let free = nodes
+            .iter()
+            .filter(|node| !node.is_reserved(time, time.saturating_add(walltime)))
+            .map(|node| node.free_at(time));
+        match request.per_node() {
+            Some((cpus, gpus)) => {
+                free.filter(|&(c, g)| c >= cpus && g >= gpus).count() as u32
+                    >= request.nodes.unwrap_or(1)
+            }
+            None => {
+                // a node's free GPUs can't be used without one of its CPUs
+                let (cpus, gpus) = free
+                    .filter(|&(c, _)| c > 0)
+                    .fold((0, 0), |(cpus, gpus), (c, g)| (cpus + c, gpus + g));
+                cpus >= request.cpus && gpus >= request.gpus
+            }
+        }
+    })
+}
+
+/// Estimates when the request could start on the given nodes, from the resources free now, the
+/// time limits of the running jobs and the reservations holding the nodes
+///
+/// With `preempt`, the resources of jobs that can already be preempted count as free. Every
+/// reservation keeps the request off its nodes, even one the user could run in. Pending jobs
+/// ahead of the request are not accounted for, while running jobs often end before their time
+/// limit, so this is only a rough guide
+pub fn estimate_start(
+    request: &WhenRequest,
+    nodes: &[&Node],
+    jobs: &SlurmJobs,
+    node_to_job_map: &HashMap<usize, Vec<u32>>,
+    reservations: &SlurmReservations,
+    preempt: bool,
+) -> (usize, Option<DateTime<Utc>>) {
+    let now = Utc::now();
+    let timelines: Vec<NodeTimeline> = nodes
+        .iter()
+        .filter(|node| in_service(&node.state))
+        .filter(|node| {
+            request
+                .constraint
+                .as_ref()
+                .is_none_or(|expr| expr.matches(&node.features, true))
+        })
+        .map(|node| {
+            let gpus = node
+                .gpu_info
+                .as_ref()
+                .map_or(0, |gpu| gpu.total_gpus as u32);
+            let mut timeline = NodeTimeline {
+                cpus: node.cpus as u32,
+                gpus,
+                releases: Vec::new(),
+                reserved: reservations
+                    .for_node(&node.name)
+                    .filter(|r| r.end_time > now)
+                    .map(|r| (r.start_time.timestamp(), r.end_time.timestamp()))
+                    .collect(),
+            };
+            let running = node_to_job_map
+                .get(&node.id)
+                .into_iter()
+                .flatten()
+                .filter_map(|id| jobs.jobs.get(id))
+                .filter(|job| job.job_state == JobState::Running);
+            for job in running {
+                let (cpus, gpus) = (job.cpus_on_node(node.id), job.gpus_on_node(node.id));
+                timeline.cpus = timeline.cpus.saturating_sub(cpus);
+                timeline.gpus = timeline.gpus.saturating_sub(gpus);
+                let preemptable = preempt
+                    && job.preemptable_time <= now
+                    && job.preemptable_time != DateTime::UNIX_EPOCH;
+                if preemptable {
+                    timeline.releases.push((now.timestamp(), cpus, gpus));
+                } else if let Some(end) = job.expected_end() {
+                    timeline.releases.push((end.timestamp(), cpus, gpus));
+                }
+            }
+            timeline
+        })
+        .collect();
+
+    let start = earliest_fit(request, &timelines, now.timestamp())
+        .and_then(|time| DateTime::from_timestamp(time, 0));
+    (timelines.len(), start)
+}
+
+/// Prints the estimate for the request over the given number of matching nodes
+pub fn print_estimate(request: &WhenRequest, matching_nodes: usize, start: Option<DateTime<Utc>>) {
+    let nodes = match request.nodes {
+        Some(nodes) => format!(" on {} node{}", nodes, if nodes == 1 { "" } else { "s" }),
+        None => String::new(),
+    };
+    println!(
+        "Request: {} CPUs and {} GPUs{}, over {} matching nodes in service",
+        request.cpus, request.gpus, nodes, matching_nodes
+    );
+
+    let Some(start) = start else {
+        println!(
+            "Earliest start: unknown; the request doesn't fit on the matching nodes even once their jobs have ended"
+        );
+        return;
+    };
+    let wait_minutes = (start - Utc::now()).num_minutes();
+    let when = if wait_minutes <= 0 {
+        "now".to_string()
+    } else {
+        format!(
+            "in {}h{:02}m, around {}",
+            wait_minutes / 60,
+            wait_minutes % 60,
+            start.with_timezone(&Local).format("%m-%d %H:%M")
+        )
+    };
+    println!("Earliest start: {}", when);
+    if let Some(time) = request.time {
+        println!(
+            "Running until: {}",
+            (start.max(Utc::now()) + time)
+                .with_timezone(&Local)
+                .format("%m-%d %H:%M")
+        );
+    }
+    println!(
+        "This ignores pending jobs ahead of the request, and jobs often end before their time limit"
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(cpus: u32, gpus: u32, releases: &[(i64, u32, u32)]) -> NodeTimeline {
+        NodeTimeline {
+            cpus,
+            gpus,
+            releases: releases.to_vec(),
+            reserved: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_parse_request() {
+        let request = WhenRequest::parse("cpus=64,constraint=genoa,time=4h").unwrap();
+        assert_eq!(request.cpus, 64);
+        assert_eq!(request.nodes, None);
+        assert_eq!(request.time, Some(Duration::hours(4)));
+        assert!(request.constraint.is_some());
+
+        assert_eq!(WhenRequest::parse("gpus=1").unwrap().cpus, 0);
+        assert_eq!(WhenRequest::parse("").unwrap().cpus, 1);
+        assert!(WhenRequest::parse("cpus=lots").is_err());
+        assert!(WhenRequest::parse("mem=4G").is_err());
+        assert!(WhenRequest::parse("time=1é").is_err());
+        assert!(WhenRequest::parse("time=1000000000d").is_err());
+        assert!(WhenRequest::parse("time=-2h").is_err());
+    }
+
+    #[test]
+    fn test_earliest_fit() {
+        let nodes = [node(16, 0, &[(100, 32, 0)]), node(8, 1, &[(50, 8, 3)])];
+        let request = |cpus, gpus, nodes| WhenRequest {
+            cpus,
+            gpus,
+            nodes,
+            ..Default::default()
+        };
+
+        // fits now across both nodes, but only once a job ends on a single one
+        assert_eq!(earliest_fit(&request(24, 0, None), &nodes, 0), Some(0));
+        assert_eq!(earliest_fit(&request(24, 0, Some(1)), &nodes, 0), Some(100));
+        assert_eq!(earliest_fit(&request(8, 4, Some(1)), &nodes, 0), Some(50));
+        assert_eq!(earliest_fit(&request(128, 0, None), &nodes, 0), None);
+    }
+
+    #[test]
+    fn test_earliest_fit_around_reservations() {
+        // free now, but reserved from 3600 to 7200
+        let nodes = [NodeTimeline {
+            reserved: vec![(3_600, 7_200)],
+            ..node(16, 0, &[])
+        }];
+        let request = |time| WhenRequest {
+            cpus: 16,
+            time,
+            ..Default::default()
+        };
+
+        // a job that ends before the reservation starts runs now, a longer one waits it out
+        assert_eq!(earliest_fit(&request(None), &nodes, 0), Some(0));
+        assert_eq!(
+            earliest_fit(&request(Some(Duration::minutes(30))), &nodes, 0),
+            Some(0)
+        );
+        assert_eq!(
+            earliest_fit(&request(Some(Duration::hours(2))), &nodes, 0),
+            Some(7_200)
+        );
+        // nor does it start during the reservation
+        assert_eq!(earliest_fit(&request(None), &nodes, 5_000), Some(7_200));
+    }
+}
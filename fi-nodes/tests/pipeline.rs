@@ -0,0 +1,123 @@
+//! Runs the reports end to end on a cluster loaded from a fixture file, as fi-nodes
+//! --state-file does, so that the whole pipeline is tested without a Slurm controller
+
+use fi_nodes::jsonl::write_nodes;
+use fi_nodes::report::build_report;
+use fi_nodes::tree_report::{TreeReportData, build_tree_report};
+use fi_slurm::cluster_state::ClusterState;
+use fi_slurm::nodes::Node;
+use fi_slurm::output::OutputFormat;
+use fi_slurm::source::{ClusterDataSource, FixtureFile};
+use fi_slurm::synthetic::SyntheticCluster;
+use std::collections::HashSet;
+
+/// Saves a synthetic cluster to a fixture file and loads it back through `FixtureFile`
+fn load_fixture(name: &str) -> (ClusterState, ClusterState) {
+    let generated = SyntheticCluster::new(640, 5_000).load().unwrap();
+    let path = std::env::temp_dir().join(format!("fi-nodes-{}-{}.json", name, std::process::id()));
+    generated.save(&path.to_string_lossy()).unwrap();
+    let loaded = FixtureFile::new(&path).load();
+    let _ = std::fs::remove_file(&path);
+    (generated, loaded.unwrap())
+}
+
+fn tree_report(state: &ClusterState, gpu: bool) -> TreeReportData {
+    let nodes: Vec<&Node> = state.nodes.nodes.iter().collect();
+    build_tree_report(
+        &nodes,
+        &state.jobs,
+        &state.node_to_job_map,
+        &[],
+        &HashSet::new(),
+        false,
+        None,
+        false,
+        gpu,
+        None,
+        None,
+        None,
+    )
+}
+
+#[test]
+fn test_tree_report_from_fixture() {
+    let (generated, loaded) = load_fixture("tree");
+
+    for gpu in [false, true] {
+        let expected = tree_report(&generated, gpu);
+        let report = tree_report(&loaded, gpu);
+        assert!(report.stats.total_nodes > 0);
+        assert!(report.stats.idle_cpus < report.stats.total_cpus);
+        assert_eq!(report.stats.total_nodes, expected.stats.total_nodes);
+        assert_eq!(report.stats.idle_nodes, expected.stats.idle_nodes);
+        assert_eq!(report.stats.total_cpus, expected.stats.total_cpus);
+        assert_eq!(report.stats.idle_cpus, expected.stats.idle_cpus);
+    }
+
+    // every kind of CPU node is a branch of the CPU report
+    let report = tree_report(&loaded, false);
+    for feature in ["genoa", "icelake", "rome"] {
+        assert!(
+            report.children.values().any(|child| {
+                &*child.name == feature || child.children.keys().any(|name| &**name == feature)
+            }),
+            "no {} branch",
+            feature
+        );
+    }
+}
+
+#[test]
+fn test_detailed_report_from_fixture() {
+    let (_, loaded) = load_fixture("detailed");
+    let nodes: Vec<&Node> = loaded.nodes.nodes.iter().collect();
+    let report = build_report(
+        &nodes,
+        &loaded.jobs,
+        &loaded.node_to_job_map,
+        false,
+        false,
+        false,
+    );
+
+    // every node is counted under exactly one state
+    let counted: u32 = report.values().map(|group| group.summary.node_count).sum();
+    assert_eq!(counted as usize, nodes.len());
+
+    // and the CPUs allocated are those of the running jobs
+    let allocated: u32 = report.values().map(|group| group.summary.alloc_cpus).sum();
+    let running: u32 = loaded
+        .node_to_job_map
+        .values()
+        .flatten()
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .map(|id| loaded.jobs.jobs[id].num_cpus)
+        .sum();
+    assert_eq!(allocated, running);
+}
+
+#[test]
+fn test_nodes_as_csv_from_fixture() {
+    let (_, loaded) = load_fixture("csv");
+    let nodes: Vec<&Node> = loaded.nodes.nodes.iter().collect();
+    let mut out = Vec::new();
+    write_nodes(
+        OutputFormat::Csv,
+        &nodes,
+        &loaded.jobs,
+        &loaded.node_to_job_map,
+        &mut out,
+    )
+    .unwrap();
+
+    let csv = String::from_utf8(out).unwrap();
+    let mut lines = csv.lines();
+    assert!(
+        lines
+            .next()
+            .unwrap()
+            .starts_with("name,state,features,partitions,cpus")
+    );
+    assert_eq!(lines.count(), nodes.len());
+}
@@ -0,0 +1,382 @@
+use clap::builder::PossibleValuesParser;
+use clap::{CommandFactory, Parser};
+use clap_complete::Shell;
+use colored::*;
+use fi_slurm::availability::{NodeAvailability, allocated_cpus};
+use fi_slurm::cluster_state::ClusterState;
+use fi_slurm::error::SlurmError;
+use fi_slurm::nodes::{Node, NodeState};
+use fi_slurm::partitions::{Partition, PartitionFlags, PartitionState, get_partitions};
+use fi_slurm::source::source_for;
+use fi_slurm::states::NodeStateFlags;
+use fi_slurm::theme::Theme;
+use fi_slurm::utils::{SlurmConfig, initialize_slurm};
+use std::collections::HashMap;
+use std::process::ExitCode;
+use thiserror::Error;
+
+/// The main function for the fi-partitions CLI application
+/// Loads the cluster state and prints one line of availability and limits per partition
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::from(e.exit_code())
+        }
+    }
+}
+
+/// Errors that end a run of fi-partitions
+#[derive(Error, Debug)]
+enum CliError {
+    #[error(transparent)]
+    Slurm(#[from] SlurmError),
+    #[error("{0}")]
+    Other(String),
+}
+
+impl From<String> for CliError {
+    fn from(message: String) -> Self {
+        CliError::Other(message)
+    }
+}
+
+impl CliError {
+    fn exit_code(&self) -> u8 {
+        match self {
+            CliError::Slurm(e) => e.exit_code(),
+            CliError::Other(_) => 1,
+        }
+    }
+}
+
+fn run() -> Result<(), CliError> {
+    let args = Args::parse();
+
+    if let Some(shell) = args.completions {
+        print_completions(shell);
+        return Ok(());
+    }
+    if args.generate_man {
+        print_man_page();
+        return Ok(());
+    }
+
+    let theme = Theme::load(args.theme.as_deref(), args.no_color)?;
+
+    let source = source_for(args.state_file.as_deref());
+    let _slurm_config = if source.needs_slurm() {
+        initialize_slurm();
+        Some(SlurmConfig::load()?)
+    } else {
+        None
+    };
+
+    let state = source.load()?;
+
+    let nodes_by_name: HashMap<&str, &Node> = state
+        .nodes
+        .nodes
+        .iter()
+        .map(|node| (node.name.as_str(), node))
+        .collect();
+
+    let summaries: Vec<PartitionSummary> = state
+        .partitions
+        .partitions
+        .iter()
+        .filter(|partition| {
+            if args.partition.is_empty() {
+                args.all || !partition.is_hidden()
+            } else {
+                args.partition.contains(&partition.name)
+            }
+        })
+        .map(|partition| PartitionSummary::build(partition, &nodes_by_name, &state))
+        .collect();
+
+    print_partitions(&summaries, &theme);
+    Ok(())
+}
+
+/// The nodes of a partition by how usable they are for new work
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct StateCounts {
+    idle: u32,
+    mixed: u32,
+    allocated: u32,
+    /// Down, drained, reserved or otherwise unavailable nodes
+    unavailable: u32,
+}
+
+/// The availability, state breakdown and limits of one partition
+struct PartitionSummary<'a> {
+    partition: &'a Partition,
+    states: StateCounts,
+    total_cpus: u32,
+    idle_cpus: u32,
+    total_gpus: u32,
+    idle_gpus: u32,
+}
+
+impl<'a> PartitionSummary<'a> {
+    fn build(
+        partition: &'a Partition,
+        nodes_by_name: &HashMap<&str, &Node>,
+        state: &ClusterState,
+    ) -> Self {
+        let mut summary = PartitionSummary {
+            partition,
+            states: StateCounts::default(),
+            total_cpus: 0,
+            idle_cpus: 0,
+            total_gpus: 0,
+            idle_gpus: 0,
+        };
+
+        // nodes skipped while loading, such as those with no CPUs, are left out
+        for node in partition
+            .node_names
+            .iter()
+            .filter_map(|name| nodes_by_name.get(name.as_str()))
+        {
+            let alloc_cpus = allocated_cpus(node, &state.jobs, &state.node_to_job_map);
+            let availability = NodeAvailability::of_node(node, alloc_cpus);
+
+            if availability.available {
+                summary.states.idle += 1;
+            } else if availability.mixed {
+                summary.states.mixed += 1;
+            } else if is_node_allocated(&node.state) {
+                summary.states.allocated += 1;
+            } else {
+                summary.states.unavailable += 1;
+            }
+
+            summary.total_cpus += node.cpus as u32;
+            summary.idle_cpus += availability.idle_cpus;
+            summary.total_gpus += node
+                .gpu_info
+                .as_ref()
+                .map_or(0, |gpu| gpu.total_gpus as u32);
+            summary.idle_gpus += availability.idle_gpus;
+        }
+        summary
+    }
+}
+
+/// Whether a node is fully allocated, but otherwise healthy
+fn is_node_allocated(state: &NodeState) -> bool {
+    match state {
+        NodeState::Allocated => true,
+        NodeState::Compound { base, flags } => {
+            **base == NodeState::Allocated && !flags.intersects(NodeStateFlags::UNAVAILABLE)
+        }
+        _ => false,
+    }
+}
+
+/// Formats a time limit in minutes the way `sinfo` does, as `[days-]hours:minutes:00`
+fn format_minutes(minutes: Option<u32>) -> String {
+    match minutes {
+        None => "UNLIMITED".to_string(),
+        Some(minutes) => {
+            let (days, hours, minutes) = (minutes / 1440, minutes / 60 % 24, minutes % 60);
+            if days > 0 {
+                format!("{}-{:02}:{:02}:00", days, hours, minutes)
+            } else {
+                format!("{}:{:02}:00", hours, minutes)
+            }
+        }
+    }
+}
+
+/// The flags of a partition users care about, as a comma-separated list
+fn format_flags(flags: PartitionFlags) -> String {
+    let names: Vec<&str> = [
+        (PartitionFlags::DEFAULT, "default"),
+        (PartitionFlags::HIDDEN, "hidden"),
+        (PartitionFlags::EXCLUSIVE_USER, "exclusive_user"),
+        (PartitionFlags::REQ_RESV, "req_resv"),
+        (PartitionFlags::ROOT_ONLY, "root_only"),
+    ]
+    .into_iter()
+    .filter(|(flag, _)| flags.contains(*flag))
+    .map(|(_, name)| name)
+    .collect();
+    if names.is_empty() {
+        "-".to_string()
+    } else {
+        names.join(",")
+    }
+}
+
+/// Prints a table with one line per partition
+fn print_partitions(summaries: &[PartitionSummary], theme: &Theme) {
+    let name_width = summaries
+        .iter()
+        .map(|s| s.partition.name.len() + usize::from(s.partition.is_default()))
+        .max()
+        .unwrap_or(0)
+        .max("PARTITION".len());
+
+    println!(
+        "{}",
+        format!(
+            "{:<name_width$}  {:<8}  {:>5}  {:>5}  {:>5}  {:>5}  {:>5}  {:>13}  {:>9}  {:>12}  {:>11}  FLAGS",
+            "PARTITION",
+            "STATE",
+            "NODES",
+            "IDLE",
+            "MIX",
+            "ALLOC",
+            "UNAV",
+            "IDLE CORES",
+            "IDLE GPUS",
+            "DEFAULT TIME",
+            "MAX TIME"
+        )
+        .bold()
+    );
+
+    for summary in summaries {
+        let partition = summary.partition;
+        // sinfo marks the default partition with an asterisk
+        let name = if partition.is_default() {
+            format!("{}*", partition.name)
+        } else {
+            partition.name.clone()
+        };
+        let partition_state = format!("{:<8}", partition.state.to_string());
+        let partition_state = if partition.state == PartitionState::Up {
+            partition_state
+        } else {
+            theme
+                .paint(&partition_state, theme.palette.down)
+                .to_string()
+        };
+        let gpus = if summary.total_gpus == 0 {
+            "-".to_string()
+        } else {
+            format!("{}/{}", summary.idle_gpus, summary.total_gpus)
+        };
+        let states = summary.states;
+        println!(
+            "{:<name_width$}  {}  {:>5}  {:>5}  {:>5}  {:>5}  {:>5}  {:>13}  {:>9}  {:>12}  {:>11}  {}",
+            name,
+            partition_state,
+            states.idle + states.mixed + states.allocated + states.unavailable,
+            states.idle,
+            states.mixed,
+            states.allocated,
+            states.unavailable,
+            format!("{}/{}", summary.idle_cpus, summary.total_cpus),
+            gpus,
+            format_minutes(partition.limits.default_time_minutes),
+            format_minutes(partition.limits.max_time_minutes),
+            format_flags(partition.flags),
+        );
+    }
+}
+
+/// Writes a man page generated from the command line definition to stdout
+fn print_man_page() {
+    // rendering can only fail on writing to stdout, such as into a closed pipe
+    let _ = clap_mangen::Man::new(Args::command()).render(&mut std::io::stdout());
+}
+
+/// Writes a completion script for the given shell to stdout
+///
+/// If Slurm can be reached, the cluster's partitions are offered as completions, so the
+/// script is best generated on a cluster node
+fn print_completions(shell: Shell) {
+    let mut command = Args::command();
+    let partitions = cluster_partitions();
+    if !partitions.is_empty() {
+        // the command is only used to write the script, so the names needn't outlive it
+        let partitions: Vec<&'static str> = partitions
+            .into_iter()
+            .map(|partition| &*partition.leak())
+            .collect();
+        command = command.mut_arg("partition", |arg| {
+            arg.value_parser(PossibleValuesParser::new(partitions))
+        });
+    }
+    clap_complete::generate(
+        shell,
+        &mut command,
+        env!("CARGO_BIN_NAME"),
+        &mut std::io::stdout(),
+    );
+}
+
+/// The names of the cluster's partitions, or none if Slurm can't be reached
+fn cluster_partitions() -> Vec<String> {
+    initialize_slurm();
+    let Ok(_slurm_config) = SlurmConfig::load() else {
+        return Vec::new();
+    };
+    get_partitions()
+        .map(|partitions| partitions.partitions.into_iter().map(|p| p.name).collect())
+        .unwrap_or_default()
+}
+
+const HELP: &str = "Summarizes each Slurm partition: its state, nodes by availability (IDLE, MIX, ALLOC or UNAV for down, drained and reserved nodes), idle cores and GPUs, time limits and flags. The default partition is marked with an asterisk.";
+
+#[derive(Parser, Debug)]
+#[command(
+    version,
+    about,
+    after_help = HELP,
+    after_long_help = format!("{}\n\n{}", HELP, fi_slurm::AUTHOR_HELP),
+)]
+struct Args {
+    #[arg(
+        help = "The partitions to show, hidden or not. Defaults to all partitions not hidden from users."
+    )]
+    partition: Vec<String>,
+
+    #[arg(short, long)]
+    #[arg(help = "Also shows hidden partitions")]
+    all: bool,
+
+    #[arg(long)]
+    #[arg(help = "Disable colors in output")]
+    no_color: bool,
+
+    #[arg(long, value_name = "FILE")]
+    #[arg(
+        help = "Reports on a cluster state saved to a file, as fi-nodes --cached keeps, instead of the live cluster"
+    )]
+    state_file: Option<String>,
+
+    #[arg(long, value_name = "NAME|FILE")]
+    #[arg(
+        help = "Colors to use: a preset (default, colorblind) or a palette file, as described in fi-nodes --help"
+    )]
+    theme: Option<String>,
+
+    #[arg(long, value_name = "SHELL", hide = true)]
+    #[arg(
+        help = "Prints a completion script for the given shell, including the cluster's partitions when Slurm is reachable"
+    )]
+    completions: Option<Shell>,
+
+    #[arg(long, hide = true)]
+    #[arg(help = "Prints a man page in roff format, generated from these options")]
+    generate_man: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_minutes_like_sinfo() {
+        assert_eq!(format_minutes(None), "UNLIMITED");
+        assert_eq!(format_minutes(Some(30)), "0:30:00");
+        assert_eq!(format_minutes(Some(12 * 60)), "12:00:00");
+        assert_eq!(format_minutes(Some(7 * 1440 + 90)), "7-01:30:00");
+    }
+}
@@ -1,6 +1,6 @@
-use chrono::{DateTime, Datelike, Days, Duration, Utc};
+use chrono::{DateTime, Days, Duration, Months, Utc};
 use reqwest::blocking::Client;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
 // Configuration and Core Enums
@@ -27,6 +27,9 @@ pub enum Grouping {
     Account,
     Nodes,
     GpuType,
+    User,
+    /// The features of the nodes, from the fi-nodes exporter rather than Slurm's
+    Feature,
 }
 
 // Helper to convert the Grouping enum to its string representation for queries
@@ -36,6 +39,8 @@ impl std::fmt::Display for Grouping {
             Grouping::Account => write!(f, "account"),
             Grouping::Nodes => write!(f, "nodes"),
             Grouping::GpuType => write!(f, "gputype"),
+            Grouping::User => write!(f, "user"),
+            Grouping::Feature => write!(f, "feature"),
         }
     }
 }
@@ -57,7 +62,7 @@ impl std::fmt::Display for Resource {
     }
 }
 
-#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub enum PrometheusTimeScale {
     Minutes,
     Hours,
@@ -99,31 +104,69 @@ impl PrometheusTimeScale {
             Self::Years => Self::Weeks,
         }
     }
+
+    /// The length of one step in seconds, counting a year as 365 days
+    pub fn step_secs(&self) -> i64 {
+        match self {
+            Self::Minutes => 60,
+            Self::Hours => 3600,
+            Self::Days => 86400,
+            Self::Weeks => 86400 * 7,
+            Self::Years => 86400 * 365,
+        }
+    }
+}
+
+/// Grouped values of a range query, one per step, along with the time of each step
+#[derive(Debug, Clone, Default)]
+pub struct TimeSeries {
+    pub timestamps: Vec<DateTime<Utc>>,
+    pub groups: HashMap<String, Vec<u64>>,
 }
 
 struct TimeRangeReturn {
-    now: DateTime<Utc>,
+    end: DateTime<Utc>,
     start_time: DateTime<Utc>,
 }
 
-fn get_time_range(increments: i64, step: &PrometheusTimeScale) -> TimeRangeReturn {
-    let now = Utc::now();
-
+/// The window of `increments` steps ending at `end`, or an error if it would start before the
+/// earliest date chrono can represent
+///
+/// Years are counted as calendar years, so a window ending on 29 February starts on 28 February
+/// of a year that isn't a leap year
+fn get_time_range(
+    end: DateTime<Utc>,
+    increments: i64,
+    step: &PrometheusTimeScale,
+) -> Result<TimeRangeReturn, String> {
     let start_time = match step {
-        PrometheusTimeScale::Minutes => now - Duration::minutes(increments),
-        PrometheusTimeScale::Hours => now - Duration::hours(increments),
-        PrometheusTimeScale::Days => now.checked_sub_days(Days::new(increments as u64)).unwrap(),
-        PrometheusTimeScale::Weeks => now
-            .checked_sub_days(Days::new(increments as u64 * 7))
-            .unwrap(),
-        // PrometheusTimeScale::Months => now.checked_sub_months(Months::new(increments as u32)).unwrap(),
-        PrometheusTimeScale::Years => {
-            let current_year = now.year();
-            now.with_year(current_year - increments as i32).unwrap()
+        PrometheusTimeScale::Minutes => {
+            Duration::try_minutes(increments).and_then(|window| end.checked_sub_signed(window))
+        }
+        PrometheusTimeScale::Hours => {
+            Duration::try_hours(increments).and_then(|window| end.checked_sub_signed(window))
         }
+        PrometheusTimeScale::Days => u64::try_from(increments)
+            .ok()
+            .and_then(|days| end.checked_sub_days(Days::new(days))),
+        PrometheusTimeScale::Weeks => u64::try_from(increments)
+            .ok()
+            .and_then(|weeks| weeks.checked_mul(7))
+            .and_then(|days| end.checked_sub_days(Days::new(days))),
+        // PrometheusTimeScale::Months => end.checked_sub_months(Months::new(increments as u32)).unwrap(),
+        PrometheusTimeScale::Years => u32::try_from(increments)
+            .ok()
+            .and_then(|years| years.checked_mul(12))
+            .and_then(|months| end.checked_sub_months(Months::new(months))),
     };
-
-    TimeRangeReturn { now, start_time }
+    let start_time = start_time.ok_or_else(|| {
+        format!(
+            "The window of {} {:?} ending at {} starts too far in the past",
+            increments, step, end
+        )
+    })?;
+
+    Ok(TimeRangeReturn { end, start_time })
 }
 
 // Structs for Deserializing Prometheus JSON Response
@@ -150,15 +193,57 @@ struct PrometheusResult {
     values: Option<Vec<(f64, String)>>,
 }
 
-fn usage_query(grouping: Grouping, resource: Resource) -> String {
-    format!("sum by({grouping}) (slurm_job_{resource}{{state=\"running\",job=\"slurm\"}})")
+fn usage_query(grouping: Grouping, resource: Resource, accounts: Option<&[&str]>) -> String {
+    // jobs aren't labelled with the features of their nodes, so the exporter's idle resources
+    // are taken from its totals instead, which counts those of unavailable nodes as used
+    if let Grouping::Feature = grouping {
+        return format!(
+            "sum by({grouping}) (fi_nodes_feature_{resource} - fi_nodes_feature_idle_{resource})"
+        );
+    }
+    // label values are quoted like Go strings, so quotes and backslashes in them are escaped
+    let quote = |value: &str| value.replace('\\', "\\\\").replace('"', "\\\"");
+    let account_filter = match accounts {
+        None => String::new(),
+        Some([account]) => format!(",account=\"{}\"", quote(account)),
+        // several accounts, such as those of a center, are matched by an anchored regex
+        Some(accounts) => {
+            let alternatives: Vec<String> = accounts.iter().map(|a| regex_escape(a)).collect();
+            format!(",account=~\"{}\"", quote(&alternatives.join("|")))
+        }
+    };
+    format!(
+        "sum by({grouping}) (slurm_job_{resource}{{state=\"running\",job=\"slurm\"{account_filter}}})"
+    )
+}
+
+/// Escapes the characters RE2 treats specially, so that a label value is matched literally
+fn regex_escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if "\\.+*?()|[]{}^$".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
 }
 
 fn capacity_query(grouping: Option<Grouping>, resource: Resource) -> String {
+    if let Some(grouping @ Grouping::Feature) = grouping {
+        return format!("sum by({grouping}) (fi_nodes_feature_{resource})");
+    }
     let by_clause = grouping.map_or_else(String::new, |g| format!("by({g})"));
     format!("sum {by_clause} (slurm_node_{resource}{{state!=\"drain\",state!=\"down\"}})")
 }
 
+/// How long a range query of `increments` steps is given to answer, since Prometheus takes
+/// longer the more steps it returns
+pub fn query_timeout(increments: i64) -> std::time::Duration {
+    let per_step = std::time::Duration::from_millis((increments.max(0) as u64).saturating_mul(20));
+    (std::time::Duration::from_secs(20) + per_step).min(std::time::Duration::from_secs(600))
+}
+
 /// The core function for querying the Prometheus API
 fn query(
     query: &str,
@@ -168,9 +253,13 @@ fn query(
     step: Option<PrometheusTimeScale>,
 ) -> Result<PrometheusResponse, Box<dyn std::error::Error>> {
     let base_url = get_prometheus_url(cluster);
+    let increments = match (end, step) {
+        (Some(end_time), Some(step_val)) => (end_time - start).num_seconds() / step_val.step_secs(),
+        _ => 0,
+    };
     let client = Client::builder()
         .danger_accept_invalid_certs(true) // Equivalent to `verify=False`
-        .timeout(std::time::Duration::from_secs(20))
+        .timeout(query_timeout(increments))
         .build()?;
 
     let mut params = HashMap::new();
@@ -223,14 +312,7 @@ fn range_group_by(
     step: PrometheusTimeScale,
     increments: i64,
 ) -> HashMap<String, Vec<u64>> {
-    // Determine step size in seconds
-    let step_secs: i64 = match step {
-        PrometheusTimeScale::Minutes => 60,
-        PrometheusTimeScale::Hours => 3600,
-        PrometheusTimeScale::Days => 86400,
-        PrometheusTimeScale::Weeks => 86400 * 7,
-        PrometheusTimeScale::Years => 86400 * 365,
-    };
+    let step_secs = step.step_secs();
     let metric_key = metric.to_string();
     // Collect raw timestamp->value maps per group
     let mut raw: HashMap<String, HashMap<i64, u64>> = HashMap::new();
@@ -268,6 +350,17 @@ fn range_group_by(
     filled
 }
 
+/// The time of each of the steps filled in by `range_group_by`
+fn step_timestamps(
+    start_time: DateTime<Utc>,
+    step: PrometheusTimeScale,
+    increments: i64,
+) -> Vec<DateTime<Utc>> {
+    (0..=increments)
+        .map(|i| start_time + Duration::seconds(i * step.step_secs()))
+        .collect()
+}
+
 // --- Public API Functions ---
 
 pub fn get_usage_by(
@@ -277,17 +370,66 @@ pub fn get_usage_by(
     increments: i64,
     step: PrometheusTimeScale,
 ) -> Result<HashMap<String, Vec<u64>>, Box<dyn std::error::Error>> {
-    let time_return = get_time_range(increments, &step);
-    let now = time_return.now;
+    get_usage_series(cluster, grouping, resource, increments, step, Utc::now())
+        .map(|series| series.groups)
+}
+
+/// Like `get_usage_by`, but for the steps ending at `end` rather than now, and also returns
+/// when each step was, for labelling charts
+pub fn get_usage_series(
+    cluster: Cluster,
+    grouping: Grouping,
+    resource: Resource,
+    increments: i64,
+    step: PrometheusTimeScale,
+    end: DateTime<Utc>,
+) -> Result<TimeSeries, Box<dyn std::error::Error>> {
+    usage_series(cluster, grouping, resource, None, increments, step, end)
+}
+
+/// Like `get_usage_series`, but only counting the jobs of the given accounts, such as to break
+/// the usage of an account or of a whole center down by user
+pub fn get_account_usage_series(
+    cluster: Cluster,
+    accounts: &[&str],
+    grouping: Grouping,
+    resource: Resource,
+    increments: i64,
+    step: PrometheusTimeScale,
+    end: DateTime<Utc>,
+) -> Result<TimeSeries, Box<dyn std::error::Error>> {
+    usage_series(
+        cluster,
+        grouping,
+        resource,
+        Some(accounts),
+        increments,
+        step,
+        end,
+    )
+}
+
+fn usage_series(
+    cluster: Cluster,
+    grouping: Grouping,
+    resource: Resource,
+    accounts: Option<&[&str]>,
+    increments: i64,
+    step: PrometheusTimeScale,
+    end: DateTime<Utc>,
+) -> Result<TimeSeries, Box<dyn std::error::Error>> {
+    let time_return = get_time_range(end, increments, &step)?;
+    let end = time_return.end;
     let start_time = time_return.start_time;
 
-    let usage_query = usage_query(grouping, resource); // Assuming Cpus for now
-    let result = query(&usage_query, &cluster, start_time, Some(now), Some(step))?;
+    let usage_query = usage_query(grouping, resource, accounts); // Assuming Cpus for now
+    let result = query(&usage_query, &cluster, start_time, Some(end), Some(step))?;
 
     // Fill missing data points with zeros
-    Ok(range_group_by(
-        result, grouping, start_time, step, increments,
-    ))
+    Ok(TimeSeries {
+        timestamps: step_timestamps(start_time, step, increments),
+        groups: range_group_by(result, grouping, start_time, step, increments),
+    })
 }
 
 pub fn get_max_resource(
@@ -296,13 +438,14 @@ pub fn get_max_resource(
     resource: Resource,
     increments: i64,
     step: PrometheusTimeScale,
+    end: DateTime<Utc>,
 ) -> Result<HashMap<String, Vec<u64>>, Box<dyn std::error::Error>> {
-    let time_return = get_time_range(increments, &step);
-    let now = time_return.now;
+    let time_return = get_time_range(end, increments, &step)?;
+    let end = time_return.end;
     let start_time = time_return.start_time;
 
     let cap_query = capacity_query(grouping, resource); // Assuming Cpus
-    let result = query(&cap_query, &cluster, start_time, Some(now), Some(step))?;
+    let result = query(&cap_query, &cluster, start_time, Some(end), Some(step))?;
 
     // if days is none, then instantaneous regular groupby
     // otherwise range groupby
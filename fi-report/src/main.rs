@@ -0,0 +1,210 @@
+pub mod report;
+
+use chrono::{DateTime, Duration, Local, NaiveDate, TimeZone, Utc};
+use clap::{CommandFactory, Parser};
+use clap_complete::Shell;
+use fi_slurm::error::SlurmError;
+use fi_slurm::output::{OutputFormat, ignore_broken_pipe, write_records};
+use fi_slurm::utils::{SlurmConfig, initialize_slurm};
+use fi_slurm_db::db::DbPool;
+use fi_slurm_db::jobs::get_jobs_history;
+use fi_slurm_db::query::JobQuery;
+use fi_slurm_db::tres::init_tres_table;
+use std::process::ExitCode;
+use thiserror::Error;
+
+use crate::report::{GroupBy, Period, print_table, summarize};
+
+/// The main function for the fi-report CLI application
+/// Fetches the jobs of the window from slurmdbd and prints their usage by period
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::from(e.exit_code())
+        }
+    }
+}
+
+/// Errors that end a run of fi-report
+#[derive(Error, Debug)]
+enum CliError {
+    #[error(transparent)]
+    Slurm(#[from] SlurmError),
+    #[error("{0}")]
+    Other(String),
+}
+
+impl From<String> for CliError {
+    fn from(message: String) -> Self {
+        CliError::Other(message)
+    }
+}
+
+impl CliError {
+    fn exit_code(&self) -> u8 {
+        match self {
+            CliError::Slurm(e) => e.exit_code(),
+            CliError::Other(_) => 1,
+        }
+    }
+}
+
+fn run() -> Result<(), CliError> {
+    let args = Args::parse();
+
+    if let Some(shell) = args.completions {
+        print_completions(shell);
+        return Ok(());
+    }
+    if args.generate_man {
+        print_man_page();
+        return Ok(());
+    }
+
+    let end = args.end.map_or_else(Utc::now, local_midnight);
+    let start = match (args.start, args.since) {
+        (Some(start), _) => local_midnight(start),
+        (None, Some(since)) => end - since,
+        (None, None) => end - Duration::days(DEFAULT_DAYS),
+    };
+    if start >= end {
+        return Err(CliError::Other(
+            "the start of the report must come before its end".to_string(),
+        ));
+    }
+
+    initialize_slurm();
+    let _slurm_config = SlurmConfig::load()?;
+
+    // one slurmdbd connection serves every database query of the run
+    let mut db = DbPool::new();
+    // the GPUs of each job are read from its TRES, so the table must be loaded first
+    init_tres_table(&mut db);
+
+    let mut query = JobQuery::new().site_cluster().window(start, end);
+    if !args.account.is_empty() {
+        query = query.accounts(args.account);
+    }
+    if !args.user.is_empty() {
+        query = query.users(args.user);
+    }
+    let jobs = get_jobs_history(query, &mut db).map_err(|e| CliError::Other(e.to_string()))?;
+
+    let rows = summarize(&jobs, start, end, args.period, args.group_by);
+    if args.output.is_structured() {
+        ignore_broken_pipe(write_records(args.output, &rows, std::io::stdout().lock()))
+            .map_err(|e| CliError::Other(format!("Failed to write the report: {}", e)))?;
+    } else {
+        print_table(&rows);
+    }
+    Ok(())
+}
+
+/// Local midnight at the start of the day
+fn local_midnight(date: NaiveDate) -> DateTime<Utc> {
+    Local
+        .from_local_datetime(&date.and_time(Default::default()))
+        .earliest()
+        .map_or_else(
+            || date.and_time(Default::default()).and_utc(),
+            |time| time.with_timezone(&Utc),
+        )
+}
+
+/// Parses a window such as `12h`, `30d` or `4w` into its length
+fn parse_since(since: &str) -> Result<Duration, String> {
+    let invalid = || {
+        format!(
+            "invalid window '{}': expected a number and h, d or w",
+            since
+        )
+    };
+    let (amount, unit) = since.split_at(since.len().saturating_sub(1));
+    let amount: i64 = amount.parse().map_err(|_| invalid())?;
+    match unit {
+        "h" => Ok(Duration::hours(amount)),
+        "d" => Ok(Duration::days(amount)),
+        "w" => Ok(Duration::weeks(amount)),
+        _ => Err(invalid()),
+    }
+}
+
+/// Writes a man page for fi-report to stdout
+fn print_man_page() {
+    // rendering can only fail on writing to stdout, such as into a closed pipe
+    let _ = clap_mangen::Man::new(Args::command()).render(&mut std::io::stdout());
+}
+
+/// Writes a completion script for the given shell to stdout
+fn print_completions(shell: Shell) {
+    clap_complete::generate(
+        shell,
+        &mut Args::command(),
+        "fi-report",
+        &mut std::io::stdout(),
+    );
+}
+
+/// The days the report covers when neither --since nor --start is given
+const DEFAULT_DAYS: i64 = 7;
+
+const HELP: &str = "Summarizes the usage of the cluster's accounts and users over each day, week or month of a window: the jobs that started, the CPU-hours and GPU-hours used, and how long jobs waited after becoming eligible. Usage is read from slurmdbd, so it includes finished jobs. A job's hours are split across the periods it ran in, while the job and its wait count towards the period it started in. Periods begin at local midnight, weeks on Monday.";
+
+#[derive(Parser, Debug)]
+#[command(
+    version,
+    about,
+    after_help = HELP,
+    after_long_help = format!("{}\n\n{}", HELP, fi_slurm::AUTHOR_HELP),
+)]
+struct Args {
+    #[arg(long, value_name = "WINDOW", value_parser = parse_since, conflicts_with = "start")]
+    #[arg(
+        help = "How far back the report goes from its end, as a number and h, d or w, such as 30d. Defaults to 7d"
+    )]
+    since: Option<Duration>,
+
+    #[arg(long, value_name = "YYYY-MM-DD")]
+    #[arg(help = "The first day of the report")]
+    start: Option<NaiveDate>,
+
+    #[arg(long, value_name = "YYYY-MM-DD")]
+    #[arg(help = "The day the report stops before. Defaults to now")]
+    end: Option<NaiveDate>,
+
+    #[arg(short, long, value_enum, default_value_t)]
+    #[arg(help = "The length of each period usage is summed over")]
+    period: Period,
+
+    #[arg(short, long, value_enum, default_value_t)]
+    #[arg(
+        help = "Whether each row adds up an account, a center, a user, or a user under one account"
+    )]
+    #[arg(
+        long_help = "Whether each row adds up an account, a center, a user, or a user under one account. Accounts are mapped to centers by 'account = center' lines in centers.conf next to the binary; accounts not listed there count as centers of their own."
+    )]
+    group_by: GroupBy,
+
+    #[arg(short, long, value_enum, default_value_t, value_name = "FORMAT")]
+    #[arg(short_alias = 'f', alias = "format")]
+    #[arg(help = "How to print the report: a table, or json, csv or jsonl for other programs")]
+    output: OutputFormat,
+
+    #[arg(short, long, value_delimiter = ',')]
+    #[arg(help = "Only counts jobs of these accounts")]
+    account: Vec<String>,
+
+    #[arg(short, long, value_delimiter = ',')]
+    #[arg(help = "Only counts jobs of these users")]
+    user: Vec<String>,
+
+    #[arg(long, value_name = "SHELL", hide = true)]
+    #[arg(help = "Prints a completion script for the given shell")]
+    completions: Option<Shell>,
+
+    #[arg(long, hide = true)]
+    #[arg(help = "Prints a man page in roff format, generated from these options")]
+    generate_man: bool,
+}
@@ -0,0 +1,302 @@
+use chrono::{DateTime, Datelike, Days, Duration, Local, Months, NaiveDate, TimeZone, Utc};
+use clap::ValueEnum;
+use fi_slurm::site::center_of;
+use fi_slurm_db::jobs::SlurmJobs as DbJob;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// The length of the periods usage is summed over
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Default)]
+pub enum Period {
+    #[default]
+    Day,
+    /// Weeks starting on Monday
+    Week,
+    Month,
+}
+
+impl Period {
+    /// The first day of the period the date falls in
+    pub fn start_of(self, date: NaiveDate) -> NaiveDate {
+        match self {
+            Period::Day => date,
+            Period::Week => date - Days::new(date.weekday().num_days_from_monday().into()),
+            Period::Month => date.with_day(1).unwrap_or(date),
+        }
+    }
+
+    /// The first day of the period after the one starting on `start`
+    pub fn next(self, start: NaiveDate) -> NaiveDate {
+        match self {
+            Period::Day => start + Days::new(1),
+            Period::Week => start + Days::new(7),
+            Period::Month => start + Months::new(1),
+        }
+    }
+}
+
+/// Whose usage each row of the report adds up
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Default)]
+pub enum GroupBy {
+    Account,
+    /// The center of each account, as mapped in centers.conf, shown in place of the account
+    Center,
+    User,
+    /// Each user under each of their accounts
+    #[default]
+    Both,
+}
+
+/// The usage of one account, user or user under an account over one period
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UsageRow {
+    /// The first day of the period
+    pub period: NaiveDate,
+    /// The center instead when rows are grouped by center, and empty when they are grouped by
+    /// user only
+    pub account: String,
+    /// Empty when rows are grouped by account only
+    pub user: String,
+    /// Jobs that started during the period
+    pub jobs: u32,
+    pub cpu_hours: f64,
+    pub gpu_hours: f64,
+    /// How long the jobs that started during the period waited after becoming eligible, on
+    /// average, in minutes
+    pub avg_wait_minutes: Option<f64>,
+}
+
+/// Local midnight at the start of the day, as the rollups count days
+fn local_midnight(date: NaiveDate) -> DateTime<Utc> {
+    Local
+        .from_local_datetime(&date.and_time(Default::default()))
+        .earliest()
+        .map_or_else(
+            || date.and_time(Default::default()).and_utc(),
+            |time| time.with_timezone(&Utc),
+        )
+}
+
+/// The periods covering `start` to `end`, as their first day and their bounds
+pub fn periods(
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    period: Period,
+) -> Vec<(NaiveDate, DateTime<Utc>, DateTime<Utc>)> {
+    let mut periods = Vec::new();
+    let mut day = period.start_of(start.with_timezone(&Local).date_naive());
+    while local_midnight(day) < end {
+        let next = period.next(day);
+        periods.push((
+            day,
+            local_midnight(day).max(start),
+            local_midnight(next).min(end),
+        ));
+        day = next;
+    }
+    periods
+}
+
+#[derive(Default)]
+struct Totals {
+    jobs: u32,
+    cpu_hours: f64,
+    gpu_hours: f64,
+    wait_minutes: f64,
+}
+
+/// Adds up the jobs into one row per period and account, user, or both, counting a job's
+/// hours towards each period it ran in and the job itself towards the period it started in
+pub fn summarize(
+    jobs: &[DbJob],
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    period: Period,
+    group_by: GroupBy,
+) -> Vec<UsageRow> {
+    let periods = periods(start, end, period);
+    let mut totals: BTreeMap<(NaiveDate, &str, &str), Totals> = BTreeMap::new();
+
+    for job in jobs {
+        let (account, user) = match group_by {
+            GroupBy::Account => (job.account.as_str(), ""),
+            GroupBy::Center => (center_of(&job.account), ""),
+            GroupBy::User => ("", job.user.as_str()),
+            GroupBy::Both => (job.account.as_str(), job.user.as_str()),
+        };
+        for (day, period_start, period_end) in &periods {
+            let hours = job.hours_in_window(*period_start, *period_end, end);
+            let started = job.start_time != DateTime::UNIX_EPOCH
+                && job.start_time >= *period_start
+                && job.start_time < *period_end;
+            if hours == 0.0 && !started {
+                continue;
+            }
+            let row = totals.entry((*day, account, user)).or_default();
+            row.cpu_hours += job.alloc_cpus as f64 * hours;
+            row.gpu_hours += job.alloc_gpus as f64 * hours;
+            if started {
+                row.jobs += 1;
+                row.wait_minutes += wait_minutes(job);
+            }
+        }
+    }
+
+    totals
+        .into_iter()
+        .map(|((period, account, user), totals)| UsageRow {
+            period,
+            account: account.to_string(),
+            user: user.to_string(),
+            jobs: totals.jobs,
+            cpu_hours: totals.cpu_hours,
+            gpu_hours: totals.gpu_hours,
+            avg_wait_minutes: (totals.jobs > 0).then(|| totals.wait_minutes / totals.jobs as f64),
+        })
+        .collect()
+}
+
+/// How long a job that started waited, from when it became eligible to run, or from when it
+/// was submitted if slurmdbd has no eligible time
+fn wait_minutes(job: &DbJob) -> f64 {
+    let queued = if job.eligible == DateTime::UNIX_EPOCH {
+        job.submit_time
+    } else {
+        job.eligible
+    };
+    (job.start_time - queued)
+        .max(Duration::zero())
+        .num_seconds() as f64
+        / 60.0
+}
+
+pub fn print_table(rows: &[UsageRow]) {
+    let account_width = rows
+        .iter()
+        .map(|row| row.account.len())
+        .max()
+        .unwrap_or(0)
+        .max("ACCOUNT".len());
+    let user_width = rows
+        .iter()
+        .map(|row| row.user.len())
+        .max()
+        .unwrap_or(0)
+        .max("USER".len());
+
+    println!(
+        "{:<10}  {:<account_width$}  {:<user_width$}  {:>7}  {:>12}  {:>10}  {:>10}",
+        "PERIOD", "ACCOUNT", "USER", "JOBS", "CPU-HOURS", "GPU-HOURS", "AVG WAIT"
+    );
+    for row in rows {
+        let dash = |s: &str| if s.is_empty() { "-" } else { s }.to_string();
+        println!(
+            "{:<10}  {:<account_width$}  {:<user_width$}  {:>7}  {:>12.1}  {:>10.1}  {:>10}",
+            row.period.to_string(),
+            dash(&row.account),
+            dash(&row.user),
+            row.jobs,
+            row.cpu_hours,
+            row.gpu_hours,
+            row.avg_wait_minutes.map_or("-".to_string(), format_wait),
+        );
+    }
+}
+
+/// Formats a wait as minutes, hours or days, whichever reads best
+fn format_wait(minutes: f64) -> String {
+    if minutes < 60.0 {
+        format!("{:.0}m", minutes)
+    } else if minutes < 48.0 * 60.0 {
+        format!("{:.1}h", minutes / 60.0)
+    } else {
+        format!("{:.1}d", minutes / 1440.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fi_slurm::jobs::JobState;
+
+    #[test]
+    fn test_period_starts() {
+        let date = NaiveDate::from_ymd_opt(2025, 7, 17).unwrap();
+        assert_eq!(Period::Day.start_of(date), date);
+        assert_eq!(
+            Period::Week.start_of(date),
+            NaiveDate::from_ymd_opt(2025, 7, 14).unwrap()
+        );
+        assert_eq!(
+            Period::Month.next(Period::Month.start_of(date)),
+            NaiveDate::from_ymd_opt(2025, 8, 1).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_summarize_splits_jobs_across_days() {
+        let day = NaiveDate::from_ymd_opt(2025, 7, 14).unwrap();
+        let at = |hours: i64| local_midnight(day) + Duration::hours(hours);
+        let job = |user: &str, eligible, start, end| DbJob {
+            job_id: 1,
+            job_name: String::new(),
+            user: user.to_string(),
+            account: "cca".to_string(),
+            partition: String::new(),
+            wckey: String::new(),
+            state: JobState::Complete,
+            priority: 0,
+            node_names: String::new(),
+            alloc_nodes: 1,
+            eligible: at(eligible),
+            submit_time: at(eligible),
+            start_time: at(start),
+            end_time: at(end),
+            alloc_cpus: 4,
+            alloc_gpus: 0,
+        };
+        let jobs = [
+            // runs from 8pm to 4am, across midnight
+            job("alice", 18, 20, 28),
+            job("bob", 28, 30, 31),
+        ];
+
+        let rows = summarize(&jobs, at(0), at(48), Period::Day, GroupBy::Account);
+        assert_eq!(rows.len(), 2);
+        assert_eq!((rows[0].jobs, rows[0].cpu_hours), (1, 16.0));
+        assert_eq!((rows[1].jobs, rows[1].cpu_hours), (1, 20.0));
+        assert_eq!(rows[1].avg_wait_minutes, Some(120.0));
+        assert_eq!(rows[1].user, "");
+    }
+
+    #[test]
+    fn test_summarize_by_center_without_mapping() {
+        let day = NaiveDate::from_ymd_opt(2025, 7, 14).unwrap();
+        let at = |hours: i64| local_midnight(day) + Duration::hours(hours);
+        let job = |account: &str| DbJob {
+            job_id: 1,
+            job_name: String::new(),
+            user: "alice".to_string(),
+            account: account.to_string(),
+            partition: String::new(),
+            wckey: String::new(),
+            state: JobState::Complete,
+            priority: 0,
+            node_names: String::new(),
+            alloc_nodes: 1,
+            eligible: at(1),
+            submit_time: at(1),
+            start_time: at(2),
+            end_time: at(4),
+            alloc_cpus: 4,
+            alloc_gpus: 0,
+        };
+        let jobs = [job("cca"), job("ccq")];
+
+        // with no centers.conf, every account is a center of its own
+        let rows = summarize(&jobs, at(0), at(24), Period::Day, GroupBy::Center);
+        let centers: Vec<&str> = rows.iter().map(|row| row.account.as_str()).collect();
+        assert_eq!(centers, vec!["cca", "ccq"]);
+        assert_eq!(rows[0].cpu_hours, 8.0);
+    }
+}
@@ -1,36 +1,58 @@
 use chrono::{DateTime, Duration, Utc};
 use std::{
+    collections::BTreeMap,
     ffi::CStr,
     ops::{Deref, DerefMut},
 };
+use thiserror::Error;
 
+use fi_slurm::site;
 use fi_slurm_sys::{
-    slurm_list_destroy, slurmdb_assoc_cond_t, slurmdb_assoc_rec_t, slurmdb_user_cond_t,
-    slurmdb_user_rec_t, slurmdb_users_get, xlist,
+    slurm_list_destroy, slurmdb_assoc_cond_t, slurmdb_assoc_rec_t, slurmdb_associations_get,
+    slurmdb_user_cond_t, slurmdb_user_rec_t, slurmdb_users_get, xlist,
 };
 
-use fi_slurm::site;
-
 use users::get_current_username;
 
-use crate::db::{DbConn, slurmdb_connect};
-use crate::jobs::{JobsConfig, JobsQueryInfo, SlurmJobs, SlurmJobsList, process_jobs_list};
-use crate::qos::{QosConfig, QosError, QosQueryInfo, SlurmQos, SlurmQosList, process_qos_list};
+use crate::db::{DbConn, DbError, DbPool};
+use crate::jobs::{JobQueryError, SlurmJobs};
+use crate::qos::{QosError, SlurmQos};
+use crate::query::{AssocQuery, JobQuery, QosQuery, UserQuery};
+use crate::tres::tres_table;
 use crate::utils::{SlurmIterator, bool_to_int, vec_to_slurm_list};
 
-struct AssocConfig {
-    acct_list: Option<Vec<String>>,
-    cluster_list: Option<Vec<String>>,
-    def_qos_id_list: Option<Vec<String>>,
-    flags: u32, // bitflags
-    format_list: Option<Vec<String>>,
-    id_list: Option<Vec<String>>,
-    parent_acct_list: Option<Vec<String>>,
-    partition_list: Option<Vec<String>>,
-    qos_list: Option<Vec<String>>,
-    usage_end: DateTime<Utc>,
-    usage_start: DateTime<Utc>,
-    user_list: Option<Vec<String>>,
+#[derive(Error, Debug)]
+pub enum AssocError {
+    #[error(
+        "Could not find the current user: ensure that the running user is not deleted while the program is running"
+    )]
+    NoCurrentUser,
+    #[error("Pointer to user_list is null when looking up user '{0}'")]
+    UserListNull(String),
+    #[error("No user '{0}' in the Slurm database")]
+    NoSuchUser(String),
+    #[error("User '{0}' has no associations")]
+    NoAssociations(String),
+    #[error("Pointer to assoc_list is null for '{0}'")]
+    AssocListNull(String),
+    #[error("Pointer to qos_list of an association is null")]
+    QosListNull,
+}
+
+/// A Rust-side object corresponding to the slurmdb_assoc_cond_t object
+pub(crate) struct AssocConfig {
+    pub(crate) acct_list: Option<Vec<String>>,
+    pub(crate) cluster_list: Option<Vec<String>>,
+    pub(crate) def_qos_id_list: Option<Vec<String>>,
+    pub(crate) flags: u32, // bitflags
+    pub(crate) format_list: Option<Vec<String>>,
+    pub(crate) id_list: Option<Vec<String>>,
+    pub(crate) parent_acct_list: Option<Vec<String>>,
+    pub(crate) partition_list: Option<Vec<String>>,
+    pub(crate) qos_list: Option<Vec<String>>,
+    pub(crate) usage_end: DateTime<Utc>,
+    pub(crate) usage_start: DateTime<Utc>,
+    pub(crate) user_list: Option<Vec<String>>,
 }
 
 impl AssocConfig {
@@ -63,7 +85,7 @@ pub struct UserQueryInfo {
 
 impl UserQueryInfo {
     #[allow(clippy::too_many_arguments)]
-    fn new(
+    pub(crate) fn new(
         assoc_config: AssocConfig,
         def_acct_list: Option<Vec<String>>,
         def_wckey_list: Option<Vec<String>>,
@@ -108,35 +130,7 @@ impl Drop for UserQueryInfo {
                 }
                 // Destroy nested assoc_cond list struct
                 if !cond.assoc_cond.is_null() {
-                    // assoc_cond is a *mut slurmdb_assoc_cond_t; free its lists first
-                    let assoc: &mut slurmdb_assoc_cond_t = &mut *cond.assoc_cond;
-                    if !assoc.acct_list.is_null() {
-                        slurm_list_destroy(assoc.acct_list);
-                    }
-                    if !assoc.cluster_list.is_null() {
-                        slurm_list_destroy(assoc.cluster_list);
-                    }
-                    if !assoc.def_qos_id_list.is_null() {
-                        slurm_list_destroy(assoc.def_qos_id_list);
-                    }
-                    if !assoc.format_list.is_null() {
-                        slurm_list_destroy(assoc.format_list);
-                    }
-                    if !assoc.id_list.is_null() {
-                        slurm_list_destroy(assoc.id_list);
-                    }
-                    if !assoc.parent_acct_list.is_null() {
-                        slurm_list_destroy(assoc.parent_acct_list);
-                    }
-                    if !assoc.partition_list.is_null() {
-                        slurm_list_destroy(assoc.partition_list);
-                    }
-                    if !assoc.qos_list.is_null() {
-                        slurm_list_destroy(assoc.qos_list);
-                    }
-                    if !assoc.user_list.is_null() {
-                        slurm_list_destroy(assoc.user_list);
-                    }
+                    destroy_assoc_cond_lists(&mut *cond.assoc_cond);
                     // Now free the assoc_cond struct itself
                     let _ = Box::from_raw(cond.assoc_cond);
                 }
@@ -148,6 +142,28 @@ impl Drop for UserQueryInfo {
     }
 }
 
+/// Destroys the Slurm lists in an association filter, leaving the struct itself to its owner
+/// # Safety
+/// Every non-null list must have been created by Slurm and not yet destroyed
+unsafe fn destroy_assoc_cond_lists(assoc: &mut slurmdb_assoc_cond_t) {
+    let lists = [
+        assoc.acct_list,
+        assoc.cluster_list,
+        assoc.def_qos_id_list,
+        assoc.format_list,
+        assoc.id_list,
+        assoc.parent_acct_list,
+        assoc.partition_list,
+        assoc.qos_list,
+        assoc.user_list,
+    ];
+    for list in lists {
+        if !list.is_null() {
+            unsafe { slurm_list_destroy(list) };
+        }
+    }
+}
+
 impl Deref for UserQueryInfo {
     type Target = slurmdb_user_cond_t;
     fn deref(&self) -> &Self::Target {
@@ -165,31 +181,20 @@ fn create_user_cond(
     usernames: Vec<String>,
     usage_start: DateTime<Utc>,
     usage_end: DateTime<Utc>,
-) -> UserQueryInfo {
-    let assoc = AssocConfig {
-        acct_list: None,
-        cluster_list: site::cluster().clone().map(|s| vec![s]),
-        def_qos_id_list: None,
-        flags: 0,
-        format_list: None,
-        id_list: None,
-        parent_acct_list: None,
-        partition_list: None,
-        qos_list: None,
-        usage_end,
-        usage_start,
-        user_list: Some(usernames),
-    };
-
-    UserQueryInfo::new(assoc, None, None, true, false, false, false, 0)
+) -> UserQuery {
+    UserQuery::new()
+        .users(usernames)
+        .site_cluster()
+        .usage(usage_start, usage_end)
+        .with_assocs()
 }
 
-struct SlurmUserList {
+pub(crate) struct SlurmUserList {
     ptr: *mut xlist,
 }
 
 impl SlurmUserList {
-    fn new(db_conn: &mut DbConn, user_query: &mut UserQueryInfo) -> Self {
+    pub(crate) fn new(db_conn: &mut DbConn, user_query: &mut UserQueryInfo) -> Self {
         unsafe {
             // user_query.user is a *mut slurmdb_user_cond_t
             let ptr = slurmdb_users_get(db_conn.as_mut_ptr(), user_query.user);
@@ -207,18 +212,127 @@ impl Drop for SlurmUserList {
     }
 }
 
+/// Wrapper owning a heap-allocated Slurm association filter struct
+pub(crate) struct AssocQueryInfo {
+    assoc: *mut slurmdb_assoc_cond_t,
+}
+
+impl AssocQueryInfo {
+    pub(crate) fn new(config: AssocConfig) -> Self {
+        let boxed = Box::new(config.into_c_struct());
+        Self {
+            assoc: Box::into_raw(boxed),
+        }
+    }
+}
+
+impl Drop for AssocQueryInfo {
+    fn drop(&mut self) {
+        if !self.assoc.is_null() {
+            unsafe {
+                destroy_assoc_cond_lists(&mut *self.assoc);
+                let _ = Box::from_raw(self.assoc);
+            }
+            self.assoc = std::ptr::null_mut();
+        }
+    }
+}
+
+pub(crate) struct SlurmAssocList {
+    ptr: *mut xlist,
+}
+
+impl SlurmAssocList {
+    pub(crate) fn new(db_conn: &mut DbConn, assoc_query: &mut AssocQueryInfo) -> Self {
+        unsafe {
+            let ptr = slurmdb_associations_get(db_conn.as_mut_ptr(), assoc_query.assoc);
+            Self { ptr }
+        }
+    }
+}
+
+impl Drop for SlurmAssocList {
+    fn drop(&mut self) {
+        if !self.ptr.is_null() {
+            unsafe { slurm_list_destroy(self.ptr) }
+            self.ptr = std::ptr::null_mut();
+        }
+    }
+}
+
+/// The limits set on one association, as opposed to those of the QOS it runs under
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AssocLimits {
+    pub account: String,
+    /// The user of the association, or empty for the account's own association
+    pub user: String,
+    /// The partition the association is limited to, or empty for all of them
+    pub partition: String,
+    /// The account above this one, for an account's own association; empty for users and the
+    /// root account
+    pub parent_account: String,
+    /// The TRES all jobs of the association may use together, keyed by TRES id
+    pub grp_tres: String,
+    /// The TRES each job of the association may use, keyed by TRES id
+    pub max_tres_per_job: String,
+    pub grp_jobs: Option<u32>,
+    pub max_jobs: Option<u32>,
+    pub max_wall_minutes: Option<u32>,
+}
+
+impl AssocLimits {
+    fn from_c_rec(rec: *const slurmdb_assoc_rec_t) -> Self {
+        let string = |ptr: *const i8| {
+            if ptr.is_null() {
+                String::new()
+            } else {
+                unsafe { CStr::from_ptr(ptr) }
+                    .to_string_lossy()
+                    .into_owned()
+            }
+        };
+        // Slurm marks an unset limit with NO_VAL or INFINITE
+        let limit = |value: u32| (value < u32::MAX - 1).then_some(value);
+
+        unsafe {
+            Self {
+                account: string((*rec).acct),
+                user: string((*rec).user),
+                partition: string((*rec).partition),
+                parent_account: string((*rec).parent_acct),
+                grp_tres: string((*rec).grp_tres),
+                max_tres_per_job: string((*rec).max_tres_pj),
+                grp_jobs: limit((*rec).grp_jobs),
+                max_jobs: limit((*rec).max_jobs),
+                max_wall_minutes: limit((*rec).max_wall_pj),
+            }
+        }
+    }
+}
+
+/// A user's association with an account, and the QOS it may use
 #[derive(Debug)]
-#[allow(dead_code)]
-struct SlurmAssoc {
-    acct: String,
-    id: u32,
-    _user: String,
-    qos: Vec<String>,
-    comment: String,
+pub struct SlurmAssoc {
+    pub acct: String,
+    pub id: u32,
+    pub user: String,
+    pub qos: Vec<String>,
+    pub comment: String,
 }
 
 impl SlurmAssoc {
-    fn from_c_rec(rec: *const slurmdb_assoc_rec_t) -> Result<Self, QosError> {
+    /// Whether the association may use a QOS: one of its own, or the QOS named after its
+    /// account, as the center QOS are at Flatiron. slurmdbd lists the QOS of an association by
+    /// id, though by name in some replies, so both are matched
+    pub fn may_use(&self, qos: &SlurmQos) -> bool {
+        qos.name == self.acct
+            || self
+                .qos
+                .iter()
+                .any(|entry| *entry == qos.name || entry.parse() == Ok(qos.id))
+    }
+
+    fn from_c_rec(rec: *const slurmdb_assoc_rec_t) -> Result<Self, AssocError> {
         unsafe {
             let acct = if (*rec).acct.is_null() {
                 String::new()
@@ -228,7 +342,7 @@ impl SlurmAssoc {
 
             let id = (*rec).id;
 
-            let _user = if (*rec).user.is_null() {
+            let user = if (*rec).user.is_null() {
                 String::new()
             } else {
                 CStr::from_ptr((*rec).user).to_string_lossy().into_owned()
@@ -248,7 +362,7 @@ impl SlurmAssoc {
                     .collect();
                 Ok(qos)
             } else {
-                Err(QosError::QosListNull)
+                Err(AssocError::QosListNull)
             }?;
 
             let comment = if (*rec).comment.is_null() {
@@ -262,7 +376,7 @@ impl SlurmAssoc {
             Ok(Self {
                 acct,
                 id,
-                _user,
+                user,
                 qos,
                 comment,
             })
@@ -270,26 +384,43 @@ impl SlurmAssoc {
     }
 }
 
+/// Process a SlurmAssocList into the limits of each association; `accounts` names the query in
+/// the error
+pub(crate) fn process_assoc_list(
+    assoc_list: SlurmAssocList,
+    accounts: &str,
+) -> Result<Vec<AssocLimits>, AssocError> {
+    if assoc_list.ptr.is_null() {
+        return Err(AssocError::AssocListNull(accounts.to_string()));
+    }
+
+    let iterator = unsafe { SlurmIterator::new(assoc_list.ptr) };
+    Ok(iterator
+        .map(|node_ptr| AssocLimits::from_c_rec(node_ptr as *const slurmdb_assoc_rec_t))
+        .collect())
+}
+
 // need to pull more information out of assoc_rec_t
 
+/// A user in the Slurm database, with their associations if they were asked for
 #[derive(Debug)]
-struct SlurmUser {
-    _name: String,
-    _default_acct: String,
-    _admin_level: u16,
-    associations: Vec<SlurmAssoc>,
+pub struct SlurmUser {
+    pub name: String,
+    pub default_acct: String,
+    pub admin_level: u16,
+    pub associations: Vec<SlurmAssoc>,
 }
 
 impl SlurmUser {
-    fn from_c_rec(rec: *const slurmdb_user_rec_t) -> Result<Self, QosError> {
+    fn from_c_rec(rec: *const slurmdb_user_rec_t) -> Result<Self, AssocError> {
         unsafe {
-            let _name = if (*rec).name.is_null() {
+            let name = if (*rec).name.is_null() {
                 String::new()
             } else {
                 CStr::from_ptr((*rec).name).to_string_lossy().into_owned()
             };
 
-            let _default_acct = if (*rec).default_acct.is_null() {
+            let default_acct = if (*rec).default_acct.is_null() {
                 String::new()
             } else {
                 CStr::from_ptr((*rec).default_acct)
@@ -310,13 +441,13 @@ impl SlurmUser {
 
                 Ok(associations)
             } else {
-                Err(QosError::AssocListNull)
+                Err(AssocError::AssocListNull(name.clone()))
             }?;
 
             Ok(Self {
-                _name,
-                _default_acct,
-                _admin_level: (*rec).admin_level, // we read actual admin value from database
+                name,
+                default_acct,
+                admin_level: (*rec).admin_level, // we read actual admin value from database
                 // record, but don't let this be used for any purposes other than reading it. Is
                 // there any way to enforce that at the type level?
                 associations,
@@ -325,9 +456,12 @@ impl SlurmUser {
     }
 }
 
-fn process_user_list(user_list: SlurmUserList) -> Result<Vec<SlurmUser>, QosError> {
+pub(crate) fn process_user_list(
+    user_list: SlurmUserList,
+    name: &str,
+) -> Result<Vec<SlurmUser>, AssocError> {
     if user_list.ptr.is_null() {
-        return Err(QosError::UserListNull);
+        return Err(AssocError::UserListNull(name.to_string()));
     }
 
     let iterator = unsafe { SlurmIterator::new(user_list.ptr) };
@@ -347,55 +481,49 @@ pub struct QosJobInfo {
     pub jobs: Vec<SlurmJobs>,
 }
 
-fn get_qos_info(mut db_conn: DbConn, assocs: &[SlurmAssoc]) -> Vec<Vec<SlurmQos>> {
-    let ret: Vec<Vec<SlurmQos>> = assocs
+fn get_qos_info(db_conn: &mut DbConn, assocs: &[SlurmAssoc]) -> Vec<Vec<SlurmQos>> {
+    // without a site list, the QOS each association may use are picked out of all of them,
+    // which is a single small query
+    let all_qos = match site::limit_qos() {
+        Some(_) => Vec::new(),
+        None => QosQuery::new().fetch(db_conn).unwrap_or_default(),
+    };
+
+    assocs
         .iter()
         .filter_map(|target_assoc| {
             // query for qos details
-            let qos_details: Result<Vec<SlurmQos>, QosError> = if !target_assoc.acct.is_empty() {
-                // build the query, currently very sparse
-                let qos_config = QosConfig {
-                    name_list: Some(vec![
-                        target_assoc.acct.clone(),
-                        "inter".to_string(),
-                        "gpu".to_string(),
-                        "gpupreempt".to_string(),
-                        "gpuxl".to_string(),
-                        "eval".to_string(),
-                        "gen".to_string(),
-                        "preempt".to_string(),
-                        "genx".to_string(),
-                        "sljks".to_string(), //dummy
-                    ]),
-                    format_list: None,
-                    id_list: None,
-                };
-
-                // create the wrapper for the query
-                let mut qos_query = QosQueryInfo::new(qos_config);
-
-                // create the wrapper for the list, calls slurmdb_qos_get internally
-                let qos_list = SlurmQosList::new(&mut db_conn, &mut qos_query);
-
-                // process the resulting list and get details
-                process_qos_list(qos_list)
-            } else {
+            let qos_details: Result<Vec<SlurmQos>, QosError> = if target_assoc.acct.is_empty() {
                 // qos detail error
                 Err(QosError::EmptyAssocError)
+            } else if let Some(names) = site::limit_qos() {
+                QosQuery::new()
+                    .name(target_assoc.acct.as_str())
+                    .names(names)
+                    .fetch(db_conn)
+            } else {
+                let qos: Vec<SlurmQos> = all_qos
+                    .iter()
+                    .filter(|qos| target_assoc.may_use(qos))
+                    .cloned()
+                    .collect();
+                if qos.is_empty() {
+                    Err(QosError::EmptyQosListError)
+                } else {
+                    Ok(qos)
+                }
             };
 
             qos_details.ok()
         })
-        .collect();
-
-    ret
+        .collect()
 }
 
 fn get_jobs_info(
-    db_conn: DbConn,
+    db_conn: &mut DbConn,
     assocs: &[SlurmAssoc],
     qos: &Vec<Vec<SlurmQos>>,
-) -> Vec<SlurmJobs> {
+) -> Result<Vec<SlurmJobs>, DbError> {
     let accts: Vec<String> = assocs.iter().map(|assoc| assoc.acct.clone()).collect();
 
     let mut qos_names: Vec<String> = Vec::new();
@@ -407,60 +535,48 @@ fn get_jobs_info(
     }
 
     let now = Utc::now();
-    let jobs_config = JobsConfig {
-        acct_list: Some(accts),
-        format_list: None,
-        qos_list: Some(qos_names),
-        usage_end: now,
-        usage_start: now - Duration::weeks(5),
-    };
-
-    // create the wrapper for the query
-    let mut jobs_query = JobsQueryInfo::new(jobs_config);
-
-    // create the wrapper for the list, calls slurmdb_jobs_get internally
-    let jobs_list = SlurmJobsList::new(db_conn, &mut jobs_query);
-
-    // process the resulting list and get details
-    process_jobs_list(jobs_list).unwrap_or_default() // find a better way to handle this error case
-}
-
-fn handle_connection(persist_flags: &mut u16) -> Result<DbConn, QosError> {
-    let db_conn_result = slurmdb_connect(persist_flags);
-
-    let db_conn = match db_conn_result {
-        Ok(conn) => Ok(conn),
-        Err(_) => Err(QosError::DbConnError),
-    }?;
-
-    Ok(db_conn)
+    let jobs = JobQuery::new()
+        .accounts(accts.clone())
+        .qos(qos_names)
+        .window(now - Duration::weeks(5), now)
+        .fetch(db_conn);
+
+    // no recent jobs is not an error
+    match jobs {
+        Ok(jobs) => Ok(jobs),
+        Err(JobQueryError::EmptyJobsListError) => Ok(Vec::new()),
+        Err(source) => Err(DbError::Jobs {
+            accounts: accts,
+            source,
+        }),
+    }
 }
 
+/// Looks up the user `name` queried for by `user_query`, with the QOS of their associations
+/// and their recent jobs, all over the pool's one connection
 pub fn get_user_info(
-    user_query: &mut UserQueryInfo,
-    persist_flags: &mut u16,
-) -> Result<QosJobInfo, QosError> {
-    let mut db_conn_qos = handle_connection(persist_flags)?;
-    let db_conn_job = handle_connection(persist_flags)?;
+    name: &str,
+    user_query: UserQuery,
+    db: &mut DbPool,
+) -> Result<QosJobInfo, DbError> {
+    let db_conn = db.connection()?;
 
-    // will automatically drop when it drops out of scope
-
-    // make sure that C can take in the user info struct
-
-    let user_list = SlurmUserList::new(&mut db_conn_qos, user_query);
-
-    let users = process_user_list(user_list)?;
+    let users = user_query.fetch(db_conn)?;
 
     // assuming we only get one user back
     let Some(user) = users.first() else {
-        return Err(QosError::SlurmUserError);
+        return Err(AssocError::NoSuchUser(name.to_string()).into());
     };
 
-    let qos_vec = get_qos_info(db_conn_qos, &user.associations);
+    let acct = &user
+        .associations
+        .first()
+        .ok_or_else(|| AssocError::NoAssociations(name.to_string()))?
+        .acct;
 
-    let jobs_vec = get_jobs_info(db_conn_job, &user.associations, &qos_vec);
+    let qos_vec = get_qos_info(db_conn, &user.associations);
 
-    let acct = &user.associations.first().unwrap().acct;
+    let jobs_vec = get_jobs_info(db_conn, &user.associations, &qos_vec)?;
 
     Ok(QosJobInfo {
         user_acct: acct.to_string(),
@@ -469,26 +585,28 @@ pub fn get_user_info(
     })
 
     // at all points, wrap these raw return into Rust types with Drop impls that use the
-    // equivalent slurmdb_destroy_db function
-    // and at the very end of the function, the connection will drop out of scope and close
-    // itself
+    // equivalent slurmdb_destroy_db function; the connection stays open in the pool for the
+    // next query
 }
 
-pub fn get_tres_info(name: Option<String>) -> Result<(String, Vec<Vec<TresInfo>>), String> {
-    let name = name.unwrap_or_else(|| {
-        get_current_username().unwrap_or_else(|| {
-            eprintln!("Could not find user information: ensure that the running user is not deleted while the program is running");
-            "".into()
-        }).to_string_lossy().into_owned() // handle the rare None case
-    });
+/// The center account of the user, or of the current user if none is given, and the limits
+/// of the QOS of each of their associations
+pub fn get_tres_info(
+    name: Option<String>,
+    db: &mut DbPool,
+) -> Result<(String, Vec<Vec<TresInfo>>), DbError> {
+    let name = match name {
+        Some(name) => name,
+        None => get_current_username()
+            .ok_or(AssocError::NoCurrentUser)?
+            .to_string_lossy()
+            .into_owned(),
+    };
 
     let now = Utc::now();
-    let mut user_query = create_user_cond(vec![name.clone()], now - Duration::weeks(5), now);
+    let user_query = create_user_cond(vec![name.clone()], now - Duration::weeks(5), now);
 
-    let mut persist_flags: u16 = 0;
-
-    let qos_job_data = get_user_info(&mut user_query, &mut persist_flags)
-        .map_err(|e| format!("Error getting user info for \"{name}\": {e:?}"))?;
+    let qos_job_data = get_user_info(&name, user_query, db)?;
 
     // we could
     // also get the user associations out of here, extra return
@@ -505,6 +623,44 @@ pub fn get_tres_info(name: Option<String>) -> Result<(String, Vec<Vec<TresInfo>>
     Ok((user_acct, tres_infos))
 }
 
+/// Fetches the limits of the given account's own association and of the user's associations
+/// under it
+pub fn get_assoc_limits(
+    user: &str,
+    account: &str,
+    db: &mut DbPool,
+) -> Result<Vec<AssocLimits>, DbError> {
+    let mut limits = AssocQuery::new()
+        .account(account)
+        .site_cluster()
+        .fetch(db.connection()?)?;
+
+    // the query returns the associations of every user of the account, not only this one
+    limits.retain(|assoc| assoc.user.is_empty() || assoc.user == user);
+
+    Ok(limits)
+}
+
+/// The associations of the user, or of the current user if none is given, on the site's cluster
+pub fn get_user_assocs(name: Option<String>, db: &mut DbPool) -> Result<Vec<AssocLimits>, DbError> {
+    let name = match name {
+        Some(name) => name,
+        None => get_current_username()
+            .ok_or(AssocError::NoCurrentUser)?
+            .to_string_lossy()
+            .into_owned(),
+    };
+
+    let assocs = AssocQuery::new()
+        .user(name.clone())
+        .site_cluster()
+        .fetch(db.connection()?)?;
+    if assocs.is_empty() {
+        return Err(AssocError::NoAssociations(name).into());
+    }
+    Ok(assocs)
+}
+
 #[derive(Clone)]
 pub struct TresInfo {
     pub name: String,
@@ -513,6 +669,10 @@ pub struct TresInfo {
     pub max_tres_per_user: Option<String>,
     pub max_tres_per_group: Option<String>,
     pub max_tres_per_job: Option<String>,
+    /// The running jobs all users of the QOS may have together, or 0 for no limit
+    pub grp_jobs: u32,
+    /// The time limit each job of the QOS may ask for, in minutes
+    pub max_wall_minutes: Option<u32>,
 }
 
 impl TresInfo {
@@ -536,8 +696,24 @@ impl TresInfo {
             } else {
                 Some(qos.max_tres_per_job.clone())
             },
+            grp_jobs: qos.grp_jobs.unwrap_or(0),
+            max_wall_minutes: qos.max_wall_minutes,
         }
     }
+    /// A QOS with no limits, standing in for one the Slurm database couldn't be asked about
+    pub fn unlimited(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            priority: 0,
+            max_jobs_per_user: 0,
+            max_tres_per_user: None,
+            max_tres_per_group: None,
+            max_tres_per_job: None,
+            grp_jobs: 0,
+            max_wall_minutes: None,
+        }
+    }
+
     pub fn print(self) {
         let jpu = tres_parser(self.max_jobs_per_user.to_string());
         let tpu = tres_parser(self.max_tres_per_user.unwrap_or("".to_string()));
@@ -572,21 +748,19 @@ impl TresInfo {
 }
 
 fn tres_parser(tres: String) -> String {
-    tres.split(',')
-        .map(|t| {
-            if let Some((category, quantity)) = t.split_once('=') {
-                let unit = match category {
-                    "1" => "Cores",
-                    "2" => "Memory(gb)",
-                    "4" => "Nodes",
-                    "1001" => "GPUs",
-                    _ => "Unknown unit",
-                };
-
-                format!(" {quantity} {unit}")
-            } else {
-                "".to_string()
-            }
+    tres_table()
+        .parse(&tres)
+        .into_iter()
+        .map(|(name, quantity)| {
+            let unit = match name.as_str() {
+                "cpu" => "Cores",
+                "mem" => "Memory(gb)",
+                "node" => "Nodes",
+                "gres/gpu" => "GPUs",
+                other => other,
+            };
+
+            format!(" {quantity} {unit}")
         })
         .collect::<String>()
 }
@@ -596,6 +770,8 @@ pub struct TresMax {
     pub max_cores: Option<u32>,
     pub max_memory: Option<u32>,
     pub max_gpus: Option<u32>,
+    /// Limits on any other TRES, such as billing, licenses or other GRES, by TRES name
+    pub other: BTreeMap<String, u64>,
 }
 
 impl TresMax {
@@ -605,20 +781,27 @@ impl TresMax {
             max_cores: None,
             max_memory: None,
             max_gpus: None,
+            other: BTreeMap::new(),
         };
 
-        tres.split(',').for_each(|t| {
-            if let Some((category, quantity)) = t.split_once('=') {
-                match category {
-                    "1" => init.max_cores = Some(quantity.parse::<u32>().unwrap_or(8675309)),
-                    "2" => init.max_memory = Some(quantity.parse::<u32>().unwrap_or(8675309)),
-                    "4" => init.max_nodes = Some(quantity.parse::<u32>().unwrap_or(8675309)),
-                    "1001" => init.max_gpus = Some(quantity.parse::<u32>().unwrap_or(8675309)),
-                    _ => (),
-                };
-                //format!(" {quantity} {unit}")
-            }
-        });
+        // no limits need no TRES table, which would mean a round trip to slurmdbd
+        if tres.is_empty() {
+            return init;
+        }
+
+        // slurmdbd keys limits by TRES id, which only the cluster's TRES table can name
+        for (name, quantity) in tres_table().parse(&tres) {
+            let quantity_u32 = Some(u32::try_from(quantity).unwrap_or(u32::MAX));
+            match name.as_str() {
+                "cpu" => init.max_cores = quantity_u32,
+                "mem" => init.max_memory = quantity_u32,
+                "node" => init.max_nodes = quantity_u32,
+                "gres/gpu" => init.max_gpus = quantity_u32,
+                _ => {
+                    init.other.insert(name, quantity);
+                }
+            };
+        }
 
         init
     }
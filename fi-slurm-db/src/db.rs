@@ -2,6 +2,9 @@ use fi_slurm_sys::{slurmdb_connection_close, slurmdb_connection_get};
 use std::os::raw::c_void;
 use thiserror::Error;
 
+use crate::acct::AssocError;
+use crate::jobs::JobQueryError;
+
 #[derive(Error, Debug)]
 pub enum DbConnError {
     #[error(
@@ -10,6 +13,21 @@ pub enum DbConnError {
     DbConnectionError,
 }
 
+/// The ways a lookup in the Slurm database can fail, saying which part of it did
+#[derive(Error, Debug)]
+pub enum DbError {
+    #[error(transparent)]
+    Connection(#[from] DbConnError),
+    #[error(transparent)]
+    Assoc(#[from] AssocError),
+    #[error("Could not get the jobs of accounts {accounts:?}: {source}")]
+    Jobs {
+        accounts: Vec<String>,
+        #[source]
+        source: JobQueryError,
+    },
+}
+
 /// A Rust wrapper for a pointer to the SlurmDB database connection
 pub struct DbConn {
     ptr: *mut c_void,
@@ -47,6 +65,38 @@ impl Drop for DbConn {
     }
 }
 
+/// A slurmdbd connection shared by every query of a run
+///
+/// The connection is opened by the first query and kept open for the ones after it, until
+/// the pool is reset or dropped, which closes it
+#[derive(Default)]
+pub struct DbPool {
+    conn: Option<DbConn>,
+    persist_flags: u16,
+}
+
+impl DbPool {
+    /// A pool with no connection yet; creating one doesn't contact slurmdbd
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The shared connection, opened first if no query has needed it yet
+    pub fn connection(&mut self) -> Result<&mut DbConn, DbConnError> {
+        let conn = match self.conn.take() {
+            Some(conn) => conn,
+            None => slurmdb_connect(&mut self.persist_flags)?,
+        };
+        Ok(self.conn.insert(conn))
+    }
+
+    /// Closes the connection, so the next query opens a fresh one, such as after slurmdbd
+    /// has restarted
+    pub fn reset(&mut self) {
+        self.conn = None;
+    }
+}
+
 /// Wrapper function for the process of creating a DbConn
 pub fn slurmdb_connect(persist_flags: &mut u16) -> Result<DbConn, DbConnError> {
     DbConn::new(persist_flags)
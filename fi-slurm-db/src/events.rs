@@ -0,0 +1,265 @@
+use chrono::{DateTime, Utc};
+use fi_slurm::nodes::NodeState;
+use fi_slurm_sys::{
+    SLURMDB_EVENT_NODE, slurm_list_destroy, slurmdb_event_cond_t, slurmdb_event_rec_t,
+    slurmdb_events_get, xlist,
+};
+use std::collections::BTreeMap;
+use std::ffi::{CStr, CString};
+use std::ops::Deref;
+use thiserror::Error;
+use users::get_user_by_uid;
+
+use crate::db::{DbConn, DbConnError, DbPool};
+use crate::query::EventQuery;
+use crate::utils::{SlurmIterator, vec_to_slurm_list};
+
+#[derive(Error, Debug)]
+pub enum EventError {
+    #[error(transparent)]
+    Connection(#[from] DbConnError),
+    #[error("Pointer to event_list is null")]
+    EventListNull,
+}
+
+/// A Rust-side object corresponding to the slurmdb_event_cond_t object
+pub struct EventConfig {
+    pub cluster_list: Option<Vec<String>>,
+    /// Only node events, rather than those of the whole cluster
+    pub nodes_only: bool,
+    /// A hostlist expression; only events of these nodes are returned
+    pub node_list: Option<String>,
+    pub reason_list: Option<Vec<String>>,
+    pub period_start: DateTime<Utc>,
+    pub period_end: DateTime<Utc>,
+}
+
+impl EventConfig {
+    /// Converting an EventConfig object into a slurmdb_event_cond_t object to be passed into
+    /// Slurm
+    pub fn into_c_struct(self) -> slurmdb_event_cond_t {
+        unsafe {
+            let mut c_struct: slurmdb_event_cond_t = std::mem::zeroed();
+            c_struct.cluster_list = vec_to_slurm_list(self.cluster_list);
+            if self.nodes_only {
+                c_struct.event_type = SLURMDB_EVENT_NODE as u16;
+            }
+            // a hostlist can't hold a nul byte, so one that does is left out rather than cut short
+            c_struct.node_list = self
+                .node_list
+                .and_then(|nodes| CString::new(nodes).ok())
+                .map_or(std::ptr::null_mut(), CString::into_raw);
+            c_struct.reason_list = vec_to_slurm_list(self.reason_list);
+            c_struct.period_start = self.period_start.timestamp();
+            c_struct.period_end = self.period_end.timestamp();
+
+            c_struct
+        }
+    }
+}
+
+/// Wrapper owning a heap-allocated Slurm event filter struct
+pub struct EventQueryInfo {
+    pub event: *mut slurmdb_event_cond_t,
+}
+
+impl EventQueryInfo {
+    pub fn new(config: EventConfig) -> Self {
+        let boxed = Box::new(config.into_c_struct());
+        Self {
+            event: Box::into_raw(boxed),
+        }
+    }
+}
+
+impl Drop for EventQueryInfo {
+    /// Destroys the Slurm lists in the filter, then reclaims the struct itself
+    fn drop(&mut self) {
+        if !self.event.is_null() {
+            unsafe {
+                let cond: &mut slurmdb_event_cond_t = &mut *self.event;
+
+                if !cond.cluster_list.is_null() {
+                    slurm_list_destroy(cond.cluster_list);
+                }
+                if !cond.reason_list.is_null() {
+                    slurm_list_destroy(cond.reason_list);
+                }
+                // the node list was allocated by Rust, so Rust frees it
+                if !cond.node_list.is_null() {
+                    drop(CString::from_raw(cond.node_list));
+                }
+
+                let _ = Box::from_raw(self.event);
+            }
+            self.event = std::ptr::null_mut();
+        }
+    }
+}
+
+impl Deref for EventQueryInfo {
+    type Target = slurmdb_event_cond_t;
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.event }
+    }
+}
+
+pub struct SlurmEventList {
+    pub ptr: *mut xlist,
+}
+
+impl SlurmEventList {
+    pub fn new(db_conn: &mut DbConn, event_query: &mut EventQueryInfo) -> Self {
+        unsafe {
+            let ptr = slurmdb_events_get(db_conn.as_mut_ptr(), event_query.event);
+            Self { ptr }
+        }
+    }
+}
+
+impl Drop for SlurmEventList {
+    fn drop(&mut self) {
+        if !self.ptr.is_null() {
+            unsafe {
+                slurm_list_destroy(self.ptr);
+            }
+        }
+    }
+}
+
+/// A node or the cluster going down, draining or coming back, as `sacctmgr show event` lists
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SlurmEvent {
+    /// The node the event is about, or empty for an event of the whole cluster
+    pub node_name: String,
+    pub state: NodeState,
+    pub reason: String,
+    /// Who set the reason, by name if the uid can be looked up
+    pub reason_user: String,
+    pub period_start: DateTime<Utc>,
+    /// When the event ended, or the epoch if the node is still in that state
+    pub period_end: DateTime<Utc>,
+}
+
+impl SlurmEvent {
+    /// Generate a SlurmEvent object from a C slurmdb_event_rec_t object
+    /// # Safety
+    /// This function is unsafe because it dereferences a raw pointer from C.
+    /// The caller must ensure that the pointer is valid and points to a properly initialized
+    /// slurmdb_event_rec_t struct.
+    pub unsafe fn from_c_rec(rec: *const slurmdb_event_rec_t) -> Self {
+        unsafe {
+            let string = |ptr: *mut std::os::raw::c_char| {
+                if ptr.is_null() {
+                    String::new()
+                } else {
+                    CStr::from_ptr(ptr).to_string_lossy().into_owned()
+                }
+            };
+
+            let reason_user = get_user_by_uid((*rec).reason_uid)
+                .map(|u| u.name().to_string_lossy().into_owned())
+                .unwrap_or_else(|| (*rec).reason_uid.to_string());
+
+            Self {
+                node_name: string((*rec).node_name),
+                state: NodeState::from((*rec).state),
+                reason: string((*rec).reason),
+                reason_user,
+                period_start: DateTime::from_timestamp((*rec).period_start, 0).unwrap_or_default(),
+                period_end: DateTime::from_timestamp((*rec).period_end, 0).unwrap_or_default(),
+            }
+        }
+    }
+
+    /// The hours the event lasted between `start` and `end`, counting one still going on as
+    /// lasting until `now`
+    pub fn hours_in_window(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        now: DateTime<Utc>,
+    ) -> f64 {
+        let event_end = if self.period_end == DateTime::UNIX_EPOCH {
+            now
+        } else {
+            self.period_end
+        };
+        let seconds = (event_end.min(end) - self.period_start.max(start)).num_seconds();
+        seconds.max(0) as f64 / 3600.0
+    }
+}
+
+/// Process a SlurmEventList into a vector of SlurmEvent objects, or else return an Error
+pub fn process_event_list(event_list: SlurmEventList) -> Result<Vec<SlurmEvent>, EventError> {
+    if event_list.ptr.is_null() {
+        return Err(EventError::EventListNull);
+    }
+
+    let iterator = unsafe { SlurmIterator::new(event_list.ptr) };
+    Ok(iterator
+        .map(|node_ptr| unsafe { SlurmEvent::from_c_rec(node_ptr as *const slurmdb_event_rec_t) })
+        .collect())
+}
+
+/// Fetches from slurmdbd the node events of the site's cluster that overlap the window
+pub fn get_node_events(
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    db: &mut DbPool,
+) -> Result<Vec<SlurmEvent>, EventError> {
+    EventQuery::new()
+        .site_cluster()
+        .nodes_only()
+        .window(start, end)
+        .fetch(db.connection()?)
+}
+
+/// The node-hours lost between `start` and `end`, by the reason given for each event, such
+/// as "how many node-hours were lost to drains last month"
+pub fn node_hours_by_reason(
+    events: &[SlurmEvent],
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    now: DateTime<Utc>,
+) -> BTreeMap<String, f64> {
+    let mut hours: BTreeMap<String, f64> = BTreeMap::new();
+    for event in events.iter().filter(|event| !event.node_name.is_empty()) {
+        *hours.entry(event.reason.clone()).or_default() += event.hours_in_window(start, end, now);
+    }
+    hours.retain(|_, hours| *hours > 0.0);
+    hours
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn test_node_hours_by_reason_clips_to_window() {
+        let at = |hours: i64| DateTime::UNIX_EPOCH + Duration::hours(hours);
+        let event = |node: &str, reason: &str, start, end: Option<i64>| SlurmEvent {
+            node_name: node.to_string(),
+            state: NodeState::Down,
+            reason: reason.to_string(),
+            reason_user: "root".to_string(),
+            period_start: at(start),
+            period_end: end.map(at).unwrap_or(DateTime::UNIX_EPOCH),
+        };
+        let events = [
+            // 4 of its 10 hours fall before the window
+            event("worker1", "bad dimm", 6, Some(16)),
+            event("worker2", "bad dimm", 20, Some(22)),
+            // still drained at the end of the window
+            event("worker3", "reboot", 30, None),
+            // an event of the whole cluster isn't lost node time
+            event("", "cluster restart", 12, Some(14)),
+        ];
+
+        let hours = node_hours_by_reason(&events, at(10), at(40), at(50));
+        assert_eq!(hours.get("bad dimm"), Some(&8.0));
+        assert_eq!(hours.get("reboot"), Some(&10.0));
+        assert_eq!(hours.get("cluster restart"), None);
+    }
+}
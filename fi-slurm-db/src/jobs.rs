@@ -1,38 +1,48 @@
 use chrono::{DateTime, Utc};
+use fi_slurm::jobs::JobState;
+use fi_slurm::parser::parse_tres_str;
 use fi_slurm_sys::{
-    slurm_list_destroy, slurmdb_job_cond_t, slurmdb_job_rec_t, slurmdb_jobs_get, xlist,
+    JOB_STATE_BASE, slurm_list_destroy, slurmdb_job_cond_t, slurmdb_job_rec_t, slurmdb_jobs_get,
+    xlist,
+};
+use std::{
+    ffi::{CStr, CString},
+    ops::Deref,
 };
-use std::{ffi::CStr, ops::Deref};
 use thiserror::Error;
+use users::get_user_by_uid;
 
-use crate::db::DbConn;
+use crate::db::{DbConn, DbConnError, DbPool};
+use crate::query::JobQuery;
+use crate::tres::TresTable;
 use crate::utils::{SlurmIterator, vec_to_slurm_list};
 
 #[derive(Error, Debug)]
-pub enum JobsError {
-    #[error("Assoc vector was empty")]
-    EmptyAssocError,
-    #[error("No users found")]
-    SlurmUserError,
-    #[error("Pointer to assoc_list is null")]
-    AssocListNull,
+pub enum JobQueryError {
+    #[error(transparent)]
+    Connection(#[from] DbConnError),
     #[error("Pointer to jobs_list is null")]
     JobsListNull,
-    #[error("Pointer to user_list is null")]
-    UserListNull,
-    #[error(
-        "Database connection failed. Please ensure that SlurmDB is present and slurm_init has been run"
-    )]
-    DbConnError,
     #[error("List of jobs successfully retrieved but empty")]
     EmptyJobsListError,
+    #[error("No such user: {0}")]
+    NoSuchUser(String),
 }
 
 /// A Rust-side wrapper object corresponding to the slurmdb_job_cond_t object
 pub struct JobsConfig {
     pub acct_list: Option<Vec<String>>,
+    pub cluster_list: Option<Vec<String>>,
     pub format_list: Option<Vec<String>>,
+    pub partition_list: Option<Vec<String>>,
     pub qos_list: Option<Vec<String>>,
+    /// Job state codes, as the numbers of `JobState::code`
+    pub state_list: Option<Vec<String>>,
+    /// Numeric uids, rather than user names
+    pub userid_list: Option<Vec<String>>,
+    /// A hostlist expression; only jobs that ran on one of these nodes are returned
+    pub used_nodes: Option<String>,
+    pub wckey_list: Option<Vec<String>>,
     pub usage_end: DateTime<Utc>,
     pub usage_start: DateTime<Utc>,
 }
@@ -43,10 +53,20 @@ impl JobsConfig {
         unsafe {
             let mut c_struct: slurmdb_job_cond_t = std::mem::zeroed();
             c_struct.acct_list = vec_to_slurm_list(self.acct_list);
+            c_struct.cluster_list = vec_to_slurm_list(self.cluster_list);
             c_struct.format_list = vec_to_slurm_list(self.format_list);
+            c_struct.partition_list = vec_to_slurm_list(self.partition_list);
             c_struct.qos_list = vec_to_slurm_list(self.qos_list);
+            c_struct.state_list = vec_to_slurm_list(self.state_list);
+            c_struct.userid_list = vec_to_slurm_list(self.userid_list);
+            c_struct.wckey_list = vec_to_slurm_list(self.wckey_list);
+            // a hostlist can't hold a nul byte, so one that does is left out rather than cut short
+            c_struct.used_nodes = self
+                .used_nodes
+                .and_then(|nodes| CString::new(nodes).ok())
+                .map_or(std::ptr::null_mut(), CString::into_raw);
             c_struct.usage_end = self.usage_end.timestamp();
-            c_struct.usage_end = self.usage_start.timestamp();
+            c_struct.usage_start = self.usage_start.timestamp();
             //... add more fields as needed
 
             c_struct
@@ -84,12 +104,31 @@ impl Drop for JobsQueryInfo {
                 if !cond.acct_list.is_null() {
                     slurm_list_destroy(cond.acct_list);
                 }
+                if !cond.cluster_list.is_null() {
+                    slurm_list_destroy(cond.cluster_list);
+                }
                 if !cond.format_list.is_null() {
                     slurm_list_destroy(cond.format_list);
                 }
+                if !cond.partition_list.is_null() {
+                    slurm_list_destroy(cond.partition_list);
+                }
                 if !cond.qos_list.is_null() {
                     slurm_list_destroy(cond.qos_list);
                 }
+                if !cond.state_list.is_null() {
+                    slurm_list_destroy(cond.state_list);
+                }
+                if !cond.userid_list.is_null() {
+                    slurm_list_destroy(cond.userid_list);
+                }
+                if !cond.wckey_list.is_null() {
+                    slurm_list_destroy(cond.wckey_list);
+                }
+                // the node list was allocated by Rust, so Rust frees it
+                if !cond.used_nodes.is_null() {
+                    drop(CString::from_raw(cond.used_nodes));
+                }
                 // add more lists here as we add them to the struct
 
                 // Then, reconstruct the Box from the raw pointer. This gives
@@ -113,7 +152,7 @@ pub struct SlurmJobsList {
 }
 
 impl SlurmJobsList {
-    pub fn new(mut db_conn: DbConn, jobs_query: &mut JobsQueryInfo) -> Self {
+    pub fn new(db_conn: &mut DbConn, jobs_query: &mut JobsQueryInfo) -> Self {
         unsafe {
             // jobs_query.jobs is a *mut slurmdb_jobs_cond_t
             let ptr = slurmdb_jobs_get(db_conn.as_mut_ptr(), jobs_query.jobs);
@@ -137,12 +176,23 @@ impl Drop for SlurmJobsList {
 pub struct SlurmJobs {
     pub job_id: u32,
     pub job_name: String,
+    pub user: String,
+    pub account: String,
     pub partition: String,
+    /// The WCKey the job was charged to, or empty if it had none
+    pub wckey: String,
+    pub state: JobState,
     pub priority: u32,
     pub node_names: String,
     pub alloc_nodes: u32,
     pub eligible: DateTime<Utc>,
     pub submit_time: DateTime<Utc>,
+    /// When the job started, or the epoch if it never did
+    pub start_time: DateTime<Utc>,
+    /// When the job ended, or the epoch if it is still running
+    pub end_time: DateTime<Utc>,
+    pub alloc_cpus: u64,
+    pub alloc_gpus: u64,
 }
 
 impl SlurmJobs {
@@ -151,7 +201,7 @@ impl SlurmJobs {
     /// This function is unsafe because it dereferences a raw pointer from C.
     /// The caller must ensure that the pointer is valid and points to a properly initialized
     /// slurmdb_job_rec_t struct.
-    pub unsafe fn from_c_rec(rec: *const slurmdb_job_rec_t) -> Self {
+    pub unsafe fn from_c_rec(rec: *const slurmdb_job_rec_t, table: &TresTable) -> Self {
         unsafe {
             let partition = if (*rec).partition.is_null() {
                 String::new()
@@ -175,24 +225,108 @@ impl SlurmJobs {
                 CStr::from_ptr((*rec).nodes).to_string_lossy().into_owned()
             };
 
+            let wckey = if (*rec).wckey.is_null() {
+                String::new()
+            } else {
+                CStr::from_ptr((*rec).wckey).to_string_lossy().into_owned()
+            };
+
+            let account = if (*rec).account.is_null() {
+                String::new()
+            } else {
+                CStr::from_ptr((*rec).account)
+                    .to_string_lossy()
+                    .into_owned()
+            };
+
+            // slurmdbd may leave the name out, in which case we look up the uid ourselves
+            let user = if (*rec).user.is_null() {
+                get_user_by_uid((*rec).uid)
+                    .map(|u| u.name().to_string_lossy().into_owned())
+                    .unwrap_or_else(|| (*rec).uid.to_string())
+            } else {
+                CStr::from_ptr((*rec).user).to_string_lossy().into_owned()
+            };
+
+            // the database keys allocated TRES by id rather than by name
+            let tres_alloc = table.by_name(parse_tres_str((*rec).tres_alloc_str));
+
             Self {
                 job_id: (*rec).jobid,
                 job_name,
+                user,
+                account,
                 partition,
+                wckey,
+                state: JobState::from((*rec).state & JOB_STATE_BASE),
                 priority: (*rec).priority,
                 node_names,
                 alloc_nodes: (*rec).alloc_nodes,
                 eligible: DateTime::from_timestamp((*rec).eligible, 0).unwrap(), // i64 to datetime
                 submit_time: DateTime::from_timestamp((*rec).submit, 0).unwrap(), // i64 to datetime
+                start_time: DateTime::from_timestamp((*rec).start, 0).unwrap_or_default(),
+                end_time: DateTime::from_timestamp((*rec).end, 0).unwrap_or_default(),
+                alloc_cpus: tres_alloc.get("cpu").copied().unwrap_or(0),
+                alloc_gpus: tres_alloc.get("gres/gpu").copied().unwrap_or(0),
             }
         }
     }
+
+    /// The hours the job ran between `start` and `end`, counting a job still running as
+    /// running until `now`
+    pub fn hours_in_window(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        now: DateTime<Utc>,
+    ) -> f64 {
+        if self.start_time == DateTime::UNIX_EPOCH {
+            return 0.0;
+        }
+        let job_end = if self.end_time == DateTime::UNIX_EPOCH {
+            now
+        } else {
+            self.end_time
+        };
+        let seconds = (job_end.min(end) - self.start_time.max(start)).num_seconds();
+        seconds.max(0) as f64 / 3600.0
+    }
+}
+
+/// Fetches from slurmdbd the jobs of the site's cluster that ran at any time between `start`
+/// and `end`, whether they have finished or not
+pub fn get_jobs_in_window(
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    db: &mut DbPool,
+) -> Result<Vec<SlurmJobs>, JobQueryError> {
+    JobQuery::new()
+        .site_cluster()
+        .window(start, end)
+        .fetch(db.connection()?)
+}
+
+/// Fetches from slurmdbd the jobs matching a query, for reports on past usage
+///
+/// Unlike the query itself, finding no jobs is not an error
+pub fn get_jobs_history(
+    filter: JobQuery,
+    db: &mut DbPool,
+) -> Result<Vec<SlurmJobs>, JobQueryError> {
+    match filter.fetch(db.connection()?) {
+        Err(JobQueryError::EmptyJobsListError) => Ok(Vec::new()),
+        result => result,
+    }
 }
 
-/// Process a SlurmJobsList into a vector of SlurmJobs, or else return an error
-pub fn process_jobs_list(jobs_list: SlurmJobsList) -> Result<Vec<SlurmJobs>, JobsError> {
+/// Process a SlurmJobsList into a vector of SlurmJobs, naming their TRES from `table`, or else
+/// return an error
+pub fn process_jobs_list(
+    jobs_list: SlurmJobsList,
+    table: &TresTable,
+) -> Result<Vec<SlurmJobs>, JobQueryError> {
     if jobs_list.ptr.is_null() {
-        return Err(JobsError::JobsListNull);
+        return Err(JobQueryError::JobsListNull);
     }
 
     let iterator = unsafe { SlurmIterator::new(jobs_list.ptr) };
@@ -201,13 +335,13 @@ pub fn process_jobs_list(jobs_list: SlurmJobsList) -> Result<Vec<SlurmJobs>, Job
         .map(|node_ptr| {
             // not even an unsafe cast!
             let jobs_rec_ptr = node_ptr as *const slurmdb_job_rec_t;
-            unsafe { SlurmJobs::from_c_rec(jobs_rec_ptr) }
+            unsafe { SlurmJobs::from_c_rec(jobs_rec_ptr, table) }
         })
         .collect();
 
     if !results.is_empty() {
         Ok(results)
     } else {
-        Err(JobsError::EmptyJobsListError)
+        Err(JobQueryError::EmptyJobsListError)
     }
 }
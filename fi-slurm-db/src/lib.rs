@@ -4,6 +4,13 @@
 
 pub mod acct;
 pub mod db;
+pub mod events;
 pub mod jobs;
 pub mod qos;
+pub mod query;
+pub mod reports;
+pub mod reservations;
+pub mod tree;
+pub mod tres;
 pub mod utils;
+pub mod wckey;
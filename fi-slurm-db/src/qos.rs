@@ -1,7 +1,10 @@
+use bitflags::bitflags;
 use fi_slurm_sys::{
+    INFINITE, NO_VAL, PREEMPT_MODE_CANCEL, PREEMPT_MODE_COND_OFF, PREEMPT_MODE_GANG,
+    PREEMPT_MODE_OFF, PREEMPT_MODE_REQUEUE, PREEMPT_MODE_SUSPEND, PREEMPT_MODE_WITHIN,
     slurm_list_destroy, slurmdb_qos_cond_t, slurmdb_qos_get, slurmdb_qos_rec_t, xlist,
 };
-use std::{ffi::CStr, ops::Deref};
+use std::{ffi::CStr, fmt, ops::Deref};
 use thiserror::Error;
 
 use crate::db::DbConn;
@@ -9,20 +12,10 @@ use crate::utils::{SlurmIterator, vec_to_slurm_list};
 
 #[derive(Error, Debug)]
 pub enum QosError {
-    #[error("Assoc vector was empty")]
+    #[error("Association has no account to look up the QoS of")]
     EmptyAssocError,
-    #[error("No users found")]
-    SlurmUserError,
-    #[error("Pointer to assoc_list is null")]
-    AssocListNull,
     #[error("Pointer to qos_list is null")]
     QosListNull,
-    #[error("Pointer to user_list is null")]
-    UserListNull,
-    #[error(
-        "Database connection failed. Please ensure that SlurmDB is present and slurm_init has been run"
-    )]
-    DbConnError,
     #[error("List of QoS successfully retrieved but empty")]
     EmptyQosListError,
 }
@@ -127,16 +120,104 @@ impl Drop for SlurmQosList {
     }
 }
 
-#[derive(Debug)]
-/// A Rust object holding part of the information from a slurmdb_qos_rec_t object
+bitflags! {
+    /// The flags of a QOS, as set with `sacctmgr modify qos set flags=`
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct QosFlags: u32 {
+        const PART_MIN_NODE = fi_slurm_sys::bind_qos_flags_QOS_FLAGS_PART_MIN_NODE;
+        const PART_MAX_NODE = fi_slurm_sys::bind_qos_flags_QOS_FLAGS_PART_MAX_NODE;
+        const PART_TIME_LIMIT = fi_slurm_sys::bind_qos_flags_QOS_FLAGS_PART_TIME_LIMIT;
+        const ENFORCE_USAGE_THRES = fi_slurm_sys::bind_qos_flags_QOS_FLAGS_ENFORCE_USAGE_THRES;
+        const NO_RESERVE = fi_slurm_sys::bind_qos_flags_QOS_FLAGS_NO_RESERVE;
+        const REQ_RESV = fi_slurm_sys::bind_qos_flags_QOS_FLAGS_REQ_RESV;
+        const DENY_LIMIT = fi_slurm_sys::bind_qos_flags_QOS_FLAGS_DENY_LIMIT;
+        const OVER_PART_QOS = fi_slurm_sys::bind_qos_flags_QOS_FLAGS_OVER_PART_QOS;
+        const NO_DECAY = fi_slurm_sys::bind_qos_flags_QOS_FLAGS_NO_DECAY;
+        const USAGE_FACTOR_SAFE = fi_slurm_sys::bind_qos_flags_QOS_FLAGS_USAGE_FACTOR_SAFE;
+        const RELATIVE = fi_slurm_sys::bind_qos_flags_QOS_FLAGS_RELATIVE;
+    }
+}
+
+/// How jobs of a QOS are preempted by jobs of the QOS it is listed under
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PreemptMode {
+    /// The cluster's PreemptMode applies
+    Cluster,
+    Off,
+    Suspend,
+    Requeue,
+    Cancel,
+    Unknown(u16),
+}
+
+impl From<u16> for PreemptMode {
+    fn from(mode: u16) -> Self {
+        // gang scheduling and preemption within the QOS modify the mode rather than replace it
+        match u32::from(mode) & !(PREEMPT_MODE_GANG | PREEMPT_MODE_WITHIN) {
+            PREEMPT_MODE_OFF => PreemptMode::Cluster,
+            PREEMPT_MODE_COND_OFF => PreemptMode::Off,
+            PREEMPT_MODE_SUSPEND => PreemptMode::Suspend,
+            PREEMPT_MODE_REQUEUE => PreemptMode::Requeue,
+            PREEMPT_MODE_CANCEL => PreemptMode::Cancel,
+            _ => PreemptMode::Unknown(mode),
+        }
+    }
+}
+
+impl fmt::Display for PreemptMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PreemptMode::Cluster => write!(f, "cluster"),
+            PreemptMode::Off => write!(f, "OFF"),
+            PreemptMode::Suspend => write!(f, "SUSPEND"),
+            PreemptMode::Requeue => write!(f, "REQUEUE"),
+            PreemptMode::Cancel => write!(f, "CANCEL"),
+            PreemptMode::Unknown(mode) => write!(f, "unknown ({})", mode),
+        }
+    }
+}
+
+/// A limit Slurm marks as unset with NO_VAL or INFINITE
+fn limit(value: u32) -> Option<u32> {
+    (value != NO_VAL && value != INFINITE).then_some(value)
+}
+
+#[derive(Debug, Clone)]
+/// A Rust object holding the information from a slurmdb_qos_rec_t object
+///
+/// The TRES strings are keyed by TRES id, as slurmdbd stores them; the four that predate the
+/// rest hold `foo` when unset, while the others are empty
 pub struct SlurmQos {
+    pub id: u32,
     pub name: String,
+    pub description: String,
+    pub flags: QosFlags,
     pub priority: u32,
     pub max_jobs_per_user: u32,
     pub max_tres_per_user: String,
     pub max_tres_per_group: String,
     pub max_tres_per_account: String,
     pub max_tres_per_job: String,
+    pub max_tres_per_node: String,
+    pub min_tres_per_job: String,
+    /// The running jobs all users of the QOS may have together
+    pub grp_jobs: Option<u32>,
+    /// The running and pending jobs all users of the QOS may have together
+    pub grp_submit_jobs: Option<u32>,
+    /// The minutes of wall time all running jobs of the QOS may add up to
+    pub grp_wall_minutes: Option<u32>,
+    pub max_jobs_per_account: Option<u32>,
+    pub max_submit_jobs_per_user: Option<u32>,
+    pub max_submit_jobs_per_account: Option<u32>,
+    /// The time limit each job of the QOS may ask for, in minutes
+    pub max_wall_minutes: Option<u32>,
+    pub preempt_mode: PreemptMode,
+    /// How long a preempted job may keep running, in seconds
+    pub grace_time_secs: u32,
+    /// The factor usage under the QOS is charged at, 1.0 unless set
+    pub usage_factor: f64,
+    /// The share of fair-share usage below which jobs may not run, if enforced
+    pub usage_threshold: Option<f64>,
 }
 
 impl SlurmQos {
@@ -186,14 +267,40 @@ impl SlurmQos {
                     .into_owned()
             };
 
+            let string = |ptr: *mut std::os::raw::c_char| {
+                if ptr.is_null() {
+                    String::new()
+                } else {
+                    CStr::from_ptr(ptr).to_string_lossy().into_owned()
+                }
+            };
+            // Slurm stores NO_VAL in floating point fields as well
+            let factor = |value: f64| (value != f64::from(NO_VAL)).then_some(value);
+
             Self {
+                id: (*rec).id,
                 name,
+                description: string((*rec).description),
+                flags: QosFlags::from_bits_truncate((*rec).flags),
                 priority: (*rec).priority,
                 max_jobs_per_user: (*rec).max_jobs_pu,
                 max_tres_per_user,
                 max_tres_per_group,
                 max_tres_per_account,
                 max_tres_per_job,
+                max_tres_per_node: string((*rec).max_tres_pn),
+                min_tres_per_job: string((*rec).min_tres_pj),
+                grp_jobs: limit((*rec).grp_jobs),
+                grp_submit_jobs: limit((*rec).grp_submit_jobs),
+                grp_wall_minutes: limit((*rec).grp_wall),
+                max_jobs_per_account: limit((*rec).max_jobs_pa),
+                max_submit_jobs_per_user: limit((*rec).max_submit_jobs_pu),
+                max_submit_jobs_per_account: limit((*rec).max_submit_jobs_pa),
+                max_wall_minutes: limit((*rec).max_wall_pj),
+                preempt_mode: PreemptMode::from((*rec).preempt_mode),
+                grace_time_secs: (*rec).grace_time,
+                usage_factor: factor((*rec).usage_factor).unwrap_or(1.0),
+                usage_threshold: factor((*rec).usage_thres),
             }
         }
     }
@@ -221,3 +328,24 @@ pub fn process_qos_list(qos_list: SlurmQosList) -> Result<Vec<SlurmQos>, QosErro
         Err(QosError::EmptyQosListError)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preempt_mode_ignores_modifiers() {
+        assert_eq!(PreemptMode::from(0), PreemptMode::Cluster);
+        assert_eq!(
+            PreemptMode::from((PREEMPT_MODE_REQUEUE | PREEMPT_MODE_GANG) as u16),
+            PreemptMode::Requeue
+        );
+        assert_eq!(
+            PreemptMode::from(PREEMPT_MODE_COND_OFF as u16),
+            PreemptMode::Off
+        );
+        assert_eq!(PreemptMode::from(0x0100), PreemptMode::Unknown(0x0100));
+        assert_eq!(limit(NO_VAL), None);
+        assert_eq!(limit(48 * 60), Some(2880));
+    }
+}
@@ -0,0 +1,729 @@
+//! Builders for slurmdbd queries
+//!
+//! Each builder collects its filters as Rust values and only builds the C condition struct,
+//! with the Slurm lists in it, when the query is fetched, freeing them all once the results
+//! are read. New queries can be written without touching any unsafe code:
+//!
+//! ```no_run
+//! # use fi_slurm_db::{db::DbPool, query::UserQuery};
+//! let mut db = DbPool::new();
+//! let users = UserQuery::new()
+//!     .cluster("rusty")
+//!     .user("alice")
+//!     .with_assocs()
+//!     .fetch(db.connection().unwrap());
+//! ```
+
+use chrono::{DateTime, Utc};
+use fi_slurm::jobs::JobState;
+use fi_slurm::site;
+use users::get_user_by_name;
+
+use crate::acct::{
+    AssocConfig, AssocError, AssocLimits, AssocQueryInfo, SlurmAssocList, SlurmUser, SlurmUserList,
+    UserQueryInfo, process_assoc_list, process_user_list,
+};
+use crate::db::DbConn;
+use crate::events::{
+    EventConfig, EventError, EventQueryInfo, SlurmEvent, SlurmEventList, process_event_list,
+};
+use crate::jobs::{
+    JobQueryError, JobsConfig, JobsQueryInfo, SlurmJobs, SlurmJobsList, process_jobs_list,
+};
+use crate::qos::{QosConfig, QosError, QosQueryInfo, SlurmQos, SlurmQosList, process_qos_list};
+use crate::reservations::{
+    ReservationConfig, ReservationError, ReservationQueryInfo, SlurmReservation,
+    SlurmReservationList, process_reservation_list,
+};
+use crate::tres::{
+    SlurmTres, SlurmTresList, TresConfig, TresError, TresQueryInfo, init_tres_table_over,
+    process_tres_list,
+};
+use crate::wckey::{
+    SlurmWckey, SlurmWckeyList, WckeyConfig, WckeyError, WckeyQueryInfo, process_wckey_list,
+};
+
+/// Adds values to a filter list, which is left unset until the first value
+fn extend(list: &mut Option<Vec<String>>, values: impl IntoIterator<Item = impl Into<String>>) {
+    list.get_or_insert_with(Vec::new)
+        .extend(values.into_iter().map(Into::into));
+}
+
+/// The filters shared by user and association queries
+#[derive(Debug, Clone, Default)]
+struct AssocFilter {
+    accounts: Option<Vec<String>>,
+    clusters: Option<Vec<String>>,
+    partitions: Option<Vec<String>>,
+    users: Option<Vec<String>>,
+    usage_start: DateTime<Utc>,
+    usage_end: DateTime<Utc>,
+}
+
+impl AssocFilter {
+    fn into_config(self) -> AssocConfig {
+        AssocConfig {
+            acct_list: self.accounts,
+            cluster_list: self.clusters,
+            def_qos_id_list: None,
+            flags: 0,
+            format_list: None,
+            id_list: None,
+            parent_acct_list: None,
+            partition_list: self.partitions,
+            qos_list: None,
+            usage_end: self.usage_end,
+            usage_start: self.usage_start,
+            user_list: self.users,
+        }
+    }
+}
+
+/// A query for users, and optionally their associations
+#[derive(Debug, Clone, Default)]
+pub struct UserQuery {
+    filter: AssocFilter,
+    with_assocs: bool,
+    with_deleted: bool,
+}
+
+impl UserQuery {
+    /// A query for every user, until filters are added
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn user(self, name: impl Into<String>) -> Self {
+        self.users([name])
+    }
+
+    pub fn users(mut self, names: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        extend(&mut self.filter.users, names);
+        self
+    }
+
+    /// Only returns users with an association under the account
+    pub fn account(mut self, name: impl Into<String>) -> Self {
+        extend(&mut self.filter.accounts, [name]);
+        self
+    }
+
+    pub fn cluster(mut self, name: impl Into<String>) -> Self {
+        extend(&mut self.filter.clusters, [name]);
+        self
+    }
+
+    /// Limits the query to the cluster named in the site configuration, if one is
+    pub fn site_cluster(self) -> Self {
+        match site::cluster() {
+            Some(cluster) => self.cluster(cluster.clone()),
+            None => self,
+        }
+    }
+
+    /// The window usage is reported over
+    pub fn usage(mut self, start: DateTime<Utc>, end: DateTime<Utc>) -> Self {
+        self.filter.usage_start = start;
+        self.filter.usage_end = end;
+        self
+    }
+
+    /// Also returns the associations of each user
+    pub fn with_assocs(mut self) -> Self {
+        self.with_assocs = true;
+        self
+    }
+
+    /// Also returns users that have been deleted
+    pub fn with_deleted(mut self) -> Self {
+        self.with_deleted = true;
+        self
+    }
+
+    /// Builds the C condition struct, for the report functions that take one
+    pub(crate) fn into_query_info(self) -> UserQueryInfo {
+        UserQueryInfo::new(
+            self.filter.into_config(),
+            None,
+            None,
+            self.with_assocs,
+            false,
+            self.with_deleted,
+            false,
+            0,
+        )
+    }
+
+    pub fn fetch(self, db_conn: &mut DbConn) -> Result<Vec<SlurmUser>, AssocError> {
+        let names = self.filter.users.clone().unwrap_or_default().join(",");
+        let mut user_query = self.into_query_info();
+        process_user_list(SlurmUserList::new(db_conn, &mut user_query), &names)
+    }
+}
+
+/// A query for the limits of associations
+#[derive(Debug, Clone, Default)]
+pub struct AssocQuery {
+    filter: AssocFilter,
+}
+
+impl AssocQuery {
+    /// A query for every association, until filters are added
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn user(mut self, name: impl Into<String>) -> Self {
+        extend(&mut self.filter.users, [name]);
+        self
+    }
+
+    pub fn account(self, name: impl Into<String>) -> Self {
+        self.accounts([name])
+    }
+
+    pub fn accounts(mut self, names: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        extend(&mut self.filter.accounts, names);
+        self
+    }
+
+    pub fn cluster(mut self, name: impl Into<String>) -> Self {
+        extend(&mut self.filter.clusters, [name]);
+        self
+    }
+
+    /// Limits the query to the cluster named in the site configuration, if one is
+    pub fn site_cluster(self) -> Self {
+        match site::cluster() {
+            Some(cluster) => self.cluster(cluster.clone()),
+            None => self,
+        }
+    }
+
+    pub fn partition(mut self, name: impl Into<String>) -> Self {
+        extend(&mut self.filter.partitions, [name]);
+        self
+    }
+
+    pub fn fetch(self, db_conn: &mut DbConn) -> Result<Vec<AssocLimits>, AssocError> {
+        let accounts = self.filter.accounts.clone().unwrap_or_default().join(",");
+        let mut assoc_query = AssocQueryInfo::new(self.filter.into_config());
+        process_assoc_list(SlurmAssocList::new(db_conn, &mut assoc_query), &accounts)
+    }
+}
+
+/// A query for QOS and their limits
+#[derive(Debug, Clone, Default)]
+pub struct QosQuery {
+    names: Option<Vec<String>>,
+    ids: Option<Vec<String>>,
+}
+
+impl QosQuery {
+    /// A query for every QOS, until filters are added
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn name(self, name: impl Into<String>) -> Self {
+        self.names([name])
+    }
+
+    pub fn names(mut self, names: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        extend(&mut self.names, names);
+        self
+    }
+
+    pub fn id(mut self, id: u32) -> Self {
+        extend(&mut self.ids, [id.to_string()]);
+        self
+    }
+
+    pub fn fetch(self, db_conn: &mut DbConn) -> Result<Vec<SlurmQos>, QosError> {
+        let mut qos_query = QosQueryInfo::new(QosConfig {
+            name_list: self.names,
+            format_list: None,
+            id_list: self.ids,
+        });
+        process_qos_list(SlurmQosList::new(db_conn, &mut qos_query))
+    }
+}
+
+/// A query for the jobs in the accounting database
+#[derive(Debug, Clone, Default)]
+pub struct JobQuery {
+    accounts: Option<Vec<String>>,
+    clusters: Option<Vec<String>>,
+    partitions: Option<Vec<String>>,
+    qos: Option<Vec<String>>,
+    states: Option<Vec<String>>,
+    users: Option<Vec<String>>,
+    wckeys: Option<Vec<String>>,
+    nodes: Option<String>,
+    usage_start: DateTime<Utc>,
+    usage_end: DateTime<Utc>,
+    started_after: Option<DateTime<Utc>>,
+    started_before: Option<DateTime<Utc>>,
+}
+
+impl JobQuery {
+    /// A query for every job, until filters are added
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn account(self, name: impl Into<String>) -> Self {
+        self.accounts([name])
+    }
+
+    pub fn accounts(mut self, names: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        extend(&mut self.accounts, names);
+        self
+    }
+
+    pub fn cluster(mut self, name: impl Into<String>) -> Self {
+        extend(&mut self.clusters, [name]);
+        self
+    }
+
+    /// Limits the query to the cluster named in the site configuration, if one is
+    pub fn site_cluster(self) -> Self {
+        match site::cluster() {
+            Some(cluster) => self.cluster(cluster.clone()),
+            None => self,
+        }
+    }
+
+    pub fn qos(mut self, names: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        extend(&mut self.qos, names);
+        self
+    }
+
+    pub fn user(self, name: impl Into<String>) -> Self {
+        self.users([name])
+    }
+
+    /// Only returns jobs of these users, whose names are looked up when the query is fetched
+    pub fn users(mut self, names: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        extend(&mut self.users, names);
+        self
+    }
+
+    pub fn partition(self, name: impl Into<String>) -> Self {
+        self.partitions([name])
+    }
+
+    pub fn partitions(mut self, names: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        extend(&mut self.partitions, names);
+        self
+    }
+
+    /// Only returns jobs charged to one of these WCKeys
+    pub fn wckeys(mut self, names: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        extend(&mut self.wckeys, names);
+        self
+    }
+
+    /// Only returns jobs that ended up in one of these states, such as completed, failed or
+    /// timed out
+    pub fn states(mut self, states: impl IntoIterator<Item = JobState>) -> Self {
+        extend(
+            &mut self.states,
+            states
+                .into_iter()
+                .filter_map(|state| state.code())
+                .map(|code| code.to_string()),
+        );
+        self
+    }
+
+    /// Only returns jobs that ran on one of the nodes, given as a hostlist such as
+    /// `worker[1000-1010]`
+    pub fn nodes(mut self, hostlist: impl Into<String>) -> Self {
+        self.nodes = Some(hostlist.into());
+        self
+    }
+
+    /// Only returns jobs that started at or after `time`
+    pub fn started_after(mut self, time: DateTime<Utc>) -> Self {
+        self.started_after = Some(time);
+        self
+    }
+
+    /// Only returns jobs that started before `time`
+    pub fn started_before(mut self, time: DateTime<Utc>) -> Self {
+        self.started_before = Some(time);
+        self
+    }
+
+    /// Whether the job started within the bounds set by `started_after` and `started_before`,
+    /// which slurmdbd can't filter on itself
+    fn started_in_bounds(&self, job: &SlurmJobs) -> bool {
+        if job.start_time == DateTime::UNIX_EPOCH {
+            return self.started_after.is_none() && self.started_before.is_none();
+        }
+        self.started_after
+            .is_none_or(|after| job.start_time >= after)
+            && self
+                .started_before
+                .is_none_or(|before| job.start_time < before)
+    }
+
+    /// Only returns jobs that were eligible to run at some time between `start` and `end`
+    pub fn window(mut self, start: DateTime<Utc>, end: DateTime<Utc>) -> Self {
+        self.usage_start = start;
+        self.usage_end = end;
+        self
+    }
+
+    pub fn fetch(self, db_conn: &mut DbConn) -> Result<Vec<SlurmJobs>, JobQueryError> {
+        let userid_list = self
+            .users
+            .as_ref()
+            .map(|names| {
+                names
+                    .iter()
+                    .map(|name| {
+                        get_user_by_name(name)
+                            .map(|user| user.uid().to_string())
+                            .ok_or_else(|| JobQueryError::NoSuchUser(name.clone()))
+                    })
+                    .collect::<Result<Vec<String>, JobQueryError>>()
+            })
+            .transpose()?;
+
+        // a job can only have started while it was eligible, so the start bounds narrow the
+        // window slurmdbd searches when none was given
+        let (usage_start, usage_end) =
+            if self.usage_start == DateTime::UNIX_EPOCH && self.usage_end == DateTime::UNIX_EPOCH {
+                (
+                    self.started_after.unwrap_or_default(),
+                    self.started_before.unwrap_or_else(Utc::now),
+                )
+            } else {
+                (self.usage_start, self.usage_end)
+            };
+
+        let mut jobs_query = JobsQueryInfo::new(JobsConfig {
+            acct_list: self.accounts.clone(),
+            cluster_list: self.clusters.clone(),
+            format_list: None,
+            partition_list: self.partitions.clone(),
+            qos_list: self.qos.clone(),
+            state_list: self.states.clone(),
+            userid_list,
+            used_nodes: self.nodes.clone(),
+            wckey_list: self.wckeys.clone(),
+            usage_end,
+            usage_start,
+        });
+        // the database keys the jobs' TRES by id, so the table naming them is fetched first,
+        // over the same connection
+        let table = init_tres_table_over(db_conn);
+        let mut jobs = process_jobs_list(SlurmJobsList::new(db_conn, &mut jobs_query), table)?;
+        jobs.retain(|job| self.started_in_bounds(job));
+        if jobs.is_empty() {
+            return Err(JobQueryError::EmptyJobsListError);
+        }
+        Ok(jobs)
+    }
+}
+
+/// A query for the WCKeys users charge their jobs to
+#[derive(Debug, Clone, Default)]
+pub struct WckeyQuery {
+    clusters: Option<Vec<String>>,
+    names: Option<Vec<String>>,
+    users: Option<Vec<String>>,
+    usage_start: DateTime<Utc>,
+    usage_end: DateTime<Utc>,
+    only_defaults: bool,
+    with_deleted: bool,
+    with_usage: bool,
+}
+
+impl WckeyQuery {
+    /// A query for every WCKey, until filters are added
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cluster(mut self, name: impl Into<String>) -> Self {
+        extend(&mut self.clusters, [name]);
+        self
+    }
+
+    /// Limits the query to the cluster named in the site configuration, if one is
+    pub fn site_cluster(self) -> Self {
+        match site::cluster() {
+            Some(cluster) => self.cluster(cluster.clone()),
+            None => self,
+        }
+    }
+
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        extend(&mut self.names, [name]);
+        self
+    }
+
+    pub fn user(mut self, name: impl Into<String>) -> Self {
+        extend(&mut self.users, [name]);
+        self
+    }
+
+    /// Only returns the WCKey each user is charged to by default
+    pub fn only_defaults(mut self) -> Self {
+        self.only_defaults = true;
+        self
+    }
+
+    /// Also returns WCKeys that have been removed
+    pub fn with_deleted(mut self) -> Self {
+        self.with_deleted = true;
+        self
+    }
+
+    /// Also returns the core-hours charged to each WCKey between `start` and `end`
+    pub fn usage(mut self, start: DateTime<Utc>, end: DateTime<Utc>) -> Self {
+        self.usage_start = start;
+        self.usage_end = end;
+        self.with_usage = true;
+        self
+    }
+
+    pub fn fetch(self, db_conn: &mut DbConn) -> Result<Vec<SlurmWckey>, WckeyError> {
+        let mut wckey_query = WckeyQueryInfo::new(WckeyConfig {
+            cluster_list: self.clusters,
+            name_list: self.names,
+            user_list: self.users,
+            usage_start: self.usage_start,
+            usage_end: self.usage_end,
+            only_defaults: self.only_defaults,
+            with_deleted: self.with_deleted,
+            with_usage: self.with_usage,
+        });
+        process_wckey_list(SlurmWckeyList::new(db_conn, &mut wckey_query))
+    }
+}
+
+/// A query for the trackable resources the database knows of
+#[derive(Debug, Clone, Default)]
+pub struct TresQuery {
+    types: Option<Vec<String>>,
+    with_deleted: bool,
+}
+
+impl TresQuery {
+    /// A query for every TRES, until filters are added
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only returns TRES of the given type, such as `gres` or `license`
+    pub fn tres_type(mut self, tres_type: impl Into<String>) -> Self {
+        extend(&mut self.types, [tres_type]);
+        self
+    }
+
+    /// Also returns TRES that have been removed, which old jobs and limits may still name
+    pub fn with_deleted(mut self) -> Self {
+        self.with_deleted = true;
+        self
+    }
+
+    pub fn fetch(self, db_conn: &mut DbConn) -> Result<Vec<SlurmTres>, TresError> {
+        let mut tres_query = TresQueryInfo::new(TresConfig {
+            id_list: None,
+            name_list: None,
+            type_list: self.types,
+            with_deleted: self.with_deleted,
+        });
+        process_tres_list(SlurmTresList::new(db_conn, &mut tres_query))
+    }
+}
+
+/// A query for the node and cluster events in the accounting database, such as nodes going
+/// down or draining
+#[derive(Debug, Clone, Default)]
+pub struct EventQuery {
+    clusters: Option<Vec<String>>,
+    nodes: Option<String>,
+    nodes_only: bool,
+    reasons: Option<Vec<String>>,
+    period_start: DateTime<Utc>,
+    period_end: DateTime<Utc>,
+}
+
+impl EventQuery {
+    /// A query for every event, until filters are added
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cluster(mut self, name: impl Into<String>) -> Self {
+        extend(&mut self.clusters, [name]);
+        self
+    }
+
+    /// Limits the query to the cluster named in the site configuration, if one is
+    pub fn site_cluster(self) -> Self {
+        match site::cluster() {
+            Some(cluster) => self.cluster(cluster.clone()),
+            None => self,
+        }
+    }
+
+    /// Only returns events of the nodes, given as a hostlist such as `worker[1000-1010]`
+    pub fn nodes(mut self, hostlist: impl Into<String>) -> Self {
+        self.nodes = Some(hostlist.into());
+        self
+    }
+
+    /// Leaves out events of the cluster as a whole
+    pub fn nodes_only(mut self) -> Self {
+        self.nodes_only = true;
+        self
+    }
+
+    pub fn reason(mut self, reason: impl Into<String>) -> Self {
+        extend(&mut self.reasons, [reason]);
+        self
+    }
+
+    /// Only returns events that were going on at some time between `start` and `end`
+    pub fn window(mut self, start: DateTime<Utc>, end: DateTime<Utc>) -> Self {
+        self.period_start = start;
+        self.period_end = end;
+        self
+    }
+
+    pub fn fetch(self, db_conn: &mut DbConn) -> Result<Vec<SlurmEvent>, EventError> {
+        let mut event_query = EventQueryInfo::new(EventConfig {
+            cluster_list: self.clusters,
+            nodes_only: self.nodes_only,
+            node_list: self.nodes,
+            reason_list: self.reasons,
+            period_start: self.period_start,
+            period_end: self.period_end,
+        });
+        process_event_list(SlurmEventList::new(db_conn, &mut event_query))
+    }
+}
+
+/// A query for the reservations in the accounting database
+#[derive(Debug, Clone, Default)]
+pub struct ReservationQuery {
+    clusters: Option<Vec<String>>,
+    ids: Option<Vec<String>>,
+    names: Option<Vec<String>>,
+    nodes: Option<String>,
+    time_start: DateTime<Utc>,
+    time_end: DateTime<Utc>,
+    with_usage: bool,
+}
+
+impl ReservationQuery {
+    /// A query for every reservation, until filters are added
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cluster(mut self, name: impl Into<String>) -> Self {
+        extend(&mut self.clusters, [name]);
+        self
+    }
+
+    /// Limits the query to the cluster named in the site configuration, if one is
+    pub fn site_cluster(self) -> Self {
+        match site::cluster() {
+            Some(cluster) => self.cluster(cluster.clone()),
+            None => self,
+        }
+    }
+
+    pub fn id(mut self, id: u32) -> Self {
+        extend(&mut self.ids, [id.to_string()]);
+        self
+    }
+
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        extend(&mut self.names, [name]);
+        self
+    }
+
+    /// Only returns reservations of the nodes, given as a hostlist such as `worker[1000-1010]`
+    pub fn nodes(mut self, hostlist: impl Into<String>) -> Self {
+        self.nodes = Some(hostlist.into());
+        self
+    }
+
+    /// Only returns reservations in effect at some time between `start` and `end`
+    pub fn window(mut self, start: DateTime<Utc>, end: DateTime<Utc>) -> Self {
+        self.time_start = start;
+        self.time_end = end;
+        self
+    }
+
+    /// Also returns how much of each reservation was used by jobs
+    pub fn with_usage(mut self) -> Self {
+        self.with_usage = true;
+        self
+    }
+
+    pub fn fetch(self, db_conn: &mut DbConn) -> Result<Vec<SlurmReservation>, ReservationError> {
+        let mut resv_query = ReservationQueryInfo::new(ReservationConfig {
+            cluster_list: self.clusters,
+            id_list: self.ids,
+            name_list: self.names,
+            nodes: self.nodes,
+            time_start: self.time_start,
+            time_end: self.time_end,
+            with_usage: self.with_usage,
+        });
+        let table = init_tres_table_over(db_conn);
+        process_reservation_list(SlurmReservationList::new(db_conn, &mut resv_query), table)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job_started(start_time: DateTime<Utc>) -> SlurmJobs {
+        SlurmJobs {
+            job_id: 1,
+            job_name: String::new(),
+            user: String::new(),
+            account: String::new(),
+            partition: String::new(),
+            wckey: String::new(),
+            state: JobState::Complete,
+            priority: 0,
+            node_names: String::new(),
+            alloc_nodes: 1,
+            eligible: start_time,
+            submit_time: start_time,
+            start_time,
+            end_time: DateTime::UNIX_EPOCH,
+            alloc_cpus: 1,
+            alloc_gpus: 0,
+        }
+    }
+
+    #[test]
+    fn test_job_query_start_bounds() {
+        let at = |secs| DateTime::from_timestamp(secs, 0).unwrap();
+        let query = JobQuery::new()
+            .started_after(at(1000))
+            .started_before(at(2000));
+
+        assert!(query.started_in_bounds(&job_started(at(1000))));
+        assert!(!query.started_in_bounds(&job_started(at(999))));
+        assert!(!query.started_in_bounds(&job_started(at(2000))));
+        assert!(!query.started_in_bounds(&job_started(DateTime::UNIX_EPOCH)));
+        assert!(JobQuery::new().started_in_bounds(&job_started(DateTime::UNIX_EPOCH)));
+
+        let query = JobQuery::new().states([JobState::Failed, JobState::Timeout]);
+        assert_eq!(query.states, Some(vec!["5".to_string(), "6".to_string()]));
+    }
+}
@@ -0,0 +1,394 @@
+//! Usage reports read from the slurmdbd rollup tables, after `sreport cluster utilization`
+//! and `sreport user top`
+
+use chrono::{DateTime, Local, NaiveDate, Utc};
+use fi_slurm_sys::{
+    slurm_list_destroy, slurmdb_cluster_accounting_rec_t, slurmdb_cluster_cond_t,
+    slurmdb_cluster_rec_t, slurmdb_clusters_get, slurmdb_report_cluster_rec_t,
+    slurmdb_report_user_rec_t, slurmdb_report_user_top, slurmdb_tres_rec_t, xlist,
+};
+use std::collections::BTreeMap;
+use std::ffi::CStr;
+use thiserror::Error;
+
+use crate::acct::UserQueryInfo;
+use crate::db::{DbConn, DbConnError, DbPool};
+use crate::query::UserQuery;
+use crate::tres::{TresTable, init_tres_table};
+use crate::utils::{SlurmIterator, bool_to_int, vec_to_slurm_list};
+
+#[derive(Error, Debug)]
+pub enum ReportError {
+    #[error(transparent)]
+    Connection(#[from] DbConnError),
+    #[error("Pointer to cluster_list is null")]
+    ClusterListNull,
+    #[error("Pointer to the user report is null")]
+    UserReportNull,
+}
+
+/// Wrapper owning a heap-allocated Slurm cluster filter struct, asking for the cluster's
+/// usage over a window
+pub struct ClusterQueryInfo {
+    pub cluster: *mut slurmdb_cluster_cond_t,
+}
+
+impl ClusterQueryInfo {
+    pub fn new(
+        cluster_list: Option<Vec<String>>,
+        usage_start: DateTime<Utc>,
+        usage_end: DateTime<Utc>,
+    ) -> Self {
+        unsafe {
+            let mut c_struct: slurmdb_cluster_cond_t = std::mem::zeroed();
+            c_struct.cluster_list = vec_to_slurm_list(cluster_list);
+            c_struct.usage_start = usage_start.timestamp();
+            c_struct.usage_end = usage_end.timestamp();
+            c_struct.with_usage = bool_to_int(true);
+
+            Self {
+                cluster: Box::into_raw(Box::new(c_struct)),
+            }
+        }
+    }
+}
+
+impl Drop for ClusterQueryInfo {
+    /// Destroys the Slurm lists in the filter, then reclaims the struct itself
+    fn drop(&mut self) {
+        if !self.cluster.is_null() {
+            unsafe {
+                let cond: &mut slurmdb_cluster_cond_t = &mut *self.cluster;
+
+                if !cond.cluster_list.is_null() {
+                    slurm_list_destroy(cond.cluster_list);
+                }
+                if !cond.format_list.is_null() {
+                    slurm_list_destroy(cond.format_list);
+                }
+
+                let _ = Box::from_raw(self.cluster);
+            }
+            self.cluster = std::ptr::null_mut();
+        }
+    }
+}
+
+pub struct SlurmClusterList {
+    pub ptr: *mut xlist,
+}
+
+impl SlurmClusterList {
+    pub fn new(db_conn: &mut DbConn, cluster_query: &mut ClusterQueryInfo) -> Self {
+        unsafe {
+            let ptr = slurmdb_clusters_get(db_conn.as_mut_ptr(), cluster_query.cluster);
+            Self { ptr }
+        }
+    }
+}
+
+impl Drop for SlurmClusterList {
+    fn drop(&mut self) {
+        if !self.ptr.is_null() {
+            unsafe {
+                slurm_list_destroy(self.ptr);
+            }
+        }
+    }
+}
+
+/// The core-seconds a cluster's CPUs spent in each state over one rollup period
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClusterAccounting {
+    pub period_start: DateTime<Utc>,
+    pub alloc_secs: u64,
+    pub down_secs: u64,
+    /// Time nodes were powered down on purpose, which `sreport` calls PLND Down
+    pub planned_down_secs: u64,
+    pub idle_secs: u64,
+    /// Time held idle by reservations, which `sreport` calls Planned
+    pub reserved_secs: u64,
+}
+
+impl ClusterAccounting {
+    /// Generate a ClusterAccounting object from a C slurmdb_cluster_accounting_rec_t object
+    /// # Safety
+    /// This function is unsafe because it dereferences a raw pointer from C.
+    /// The caller must ensure that the pointer is valid and points to a properly initialized
+    /// slurmdb_cluster_accounting_rec_t struct.
+    pub unsafe fn from_c_rec(rec: *const slurmdb_cluster_accounting_rec_t) -> Self {
+        unsafe {
+            Self {
+                period_start: DateTime::from_timestamp((*rec).period_start, 0).unwrap_or_default(),
+                alloc_secs: (*rec).alloc_secs,
+                down_secs: (*rec).down_secs,
+                planned_down_secs: (*rec).pdown_secs,
+                idle_secs: (*rec).idle_secs,
+                reserved_secs: (*rec).plan_secs,
+            }
+        }
+    }
+}
+
+/// The core-hours a cluster spent in each state over one day, as in
+/// `sreport cluster utilization`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DayUtilization {
+    pub day: NaiveDate,
+    pub allocated: f64,
+    pub down: f64,
+    pub planned_down: f64,
+    pub idle: f64,
+    pub reserved: f64,
+}
+
+impl DayUtilization {
+    fn new(day: NaiveDate) -> Self {
+        Self {
+            day,
+            allocated: 0.0,
+            down: 0.0,
+            planned_down: 0.0,
+            idle: 0.0,
+            reserved: 0.0,
+        }
+    }
+
+    /// All the core-hours accounted for, which `sreport` calls Reported
+    pub fn reported(&self) -> f64 {
+        self.allocated + self.down + self.planned_down + self.idle + self.reserved
+    }
+
+    /// The share of the reported core-hours that were allocated to jobs
+    pub fn busy_fraction(&self) -> f64 {
+        let reported = self.reported();
+        if reported > 0.0 {
+            self.allocated / reported
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Adds up rollup periods into the days they fall on, in local time as slurmdbd rolls up
+pub fn utilization_by_day(records: &[ClusterAccounting]) -> Vec<DayUtilization> {
+    let hours = |secs: u64| secs as f64 / 3600.0;
+    let mut days: BTreeMap<NaiveDate, DayUtilization> = BTreeMap::new();
+    for record in records {
+        let day = record.period_start.with_timezone(&Local).date_naive();
+        let usage = days.entry(day).or_insert_with(|| DayUtilization::new(day));
+        usage.allocated += hours(record.alloc_secs);
+        usage.down += hours(record.down_secs);
+        usage.planned_down += hours(record.planned_down_secs);
+        usage.idle += hours(record.idle_secs);
+        usage.reserved += hours(record.reserved_secs);
+    }
+    days.into_values().collect()
+}
+
+/// The CPU accounting records of every cluster in the list
+fn process_cluster_list(
+    cluster_list: SlurmClusterList,
+) -> Result<Vec<ClusterAccounting>, ReportError> {
+    if cluster_list.ptr.is_null() {
+        return Err(ReportError::ClusterListNull);
+    }
+
+    let mut records = Vec::new();
+    for cluster_ptr in unsafe { SlurmIterator::new(cluster_list.ptr) } {
+        let cluster = cluster_ptr as *const slurmdb_cluster_rec_t;
+        let accounting = unsafe { SlurmIterator::new((*cluster).accounting_list) };
+        records.extend(accounting.filter_map(|rec_ptr| {
+            let rec = rec_ptr as *const slurmdb_cluster_accounting_rec_t;
+            // the cluster's other TRES, such as memory, are rolled up alongside its CPUs
+            unsafe { ((*rec).tres_rec.id == 1).then(|| ClusterAccounting::from_c_rec(rec)) }
+        }));
+    }
+    Ok(records)
+}
+
+/// Fetches from the rollup tables how busy the site's cluster was on each day between `start`
+/// and `end`
+pub fn cluster_utilization(
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    db: &mut DbPool,
+) -> Result<Vec<DayUtilization>, ReportError> {
+    let clusters = fi_slurm::site::cluster()
+        .clone()
+        .map(|cluster| vec![cluster]);
+    let mut cluster_query = ClusterQueryInfo::new(clusters, start, end);
+    let records =
+        process_cluster_list(SlurmClusterList::new(db.connection()?, &mut cluster_query))?;
+    Ok(utilization_by_day(&records))
+}
+
+/// What one user ran under one account, as in `sreport user top`
+#[derive(Debug, Clone, PartialEq)]
+pub struct UserUsage {
+    pub user: String,
+    pub account: String,
+    pub core_hours: f64,
+    pub gpu_hours: f64,
+}
+
+impl UserUsage {
+    /// Generate a UserUsage object from a C slurmdb_report_user_rec_t object
+    /// # Safety
+    /// This function is unsafe because it dereferences a raw pointer from C.
+    /// The caller must ensure that the pointer is valid and points to a properly initialized
+    /// slurmdb_report_user_rec_t struct.
+    pub unsafe fn from_c_rec(rec: *const slurmdb_report_user_rec_t, table: &TresTable) -> Self {
+        unsafe {
+            let string = |ptr: *mut std::os::raw::c_char| {
+                if ptr.is_null() {
+                    String::new()
+                } else {
+                    CStr::from_ptr(ptr).to_string_lossy().into_owned()
+                }
+            };
+
+            let mut usage = Self {
+                user: string((*rec).name),
+                account: string((*rec).acct),
+                core_hours: 0.0,
+                gpu_hours: 0.0,
+            };
+            for tres_ptr in SlurmIterator::new((*rec).tres_list) {
+                let tres = tres_ptr as *const slurmdb_tres_rec_t;
+                let hours = (*tres).alloc_secs as f64 / 3600.0;
+                match table.name(&(*tres).id.to_string()) {
+                    Some("cpu") => usage.core_hours += hours,
+                    Some("gres/gpu") => usage.gpu_hours += hours,
+                    _ => {}
+                }
+            }
+            usage
+        }
+    }
+}
+
+/// The list of clusters, each with its users' usage, returned by `slurmdb_report_user_top`
+pub struct SlurmUserReport {
+    pub ptr: *mut xlist,
+}
+
+impl SlurmUserReport {
+    /// Asks for one entry per user and account, rather than per user
+    pub fn new(db_conn: &mut DbConn, user_query: &mut UserQueryInfo) -> Self {
+        unsafe {
+            let ptr = slurmdb_report_user_top(db_conn.as_mut_ptr(), user_query.user, false);
+            Self { ptr }
+        }
+    }
+}
+
+impl Drop for SlurmUserReport {
+    fn drop(&mut self) {
+        if !self.ptr.is_null() {
+            unsafe {
+                slurm_list_destroy(self.ptr);
+            }
+        }
+    }
+}
+
+/// Groups usage by account, keeping the `limit` users who used the most core-hours in each
+pub fn top_users_by_account(
+    usage: Vec<UserUsage>,
+    limit: usize,
+) -> BTreeMap<String, Vec<UserUsage>> {
+    let mut accounts: BTreeMap<String, Vec<UserUsage>> = BTreeMap::new();
+    for user in usage {
+        accounts.entry(user.account.clone()).or_default().push(user);
+    }
+    for users in accounts.values_mut() {
+        users.sort_by(|a, b| b.core_hours.total_cmp(&a.core_hours));
+        users.truncate(limit);
+    }
+    accounts
+}
+
+/// Fetches from the rollup tables the heaviest users of each account on the site's cluster
+/// between `start` and `end`
+pub fn user_top(
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    limit: usize,
+    db: &mut DbPool,
+) -> Result<BTreeMap<String, Vec<UserUsage>>, ReportError> {
+    let table = init_tres_table(db);
+    let mut user_query = UserQuery::new()
+        .site_cluster()
+        .usage(start, end)
+        .into_query_info();
+
+    let report = SlurmUserReport::new(db.connection()?, &mut user_query);
+    if report.ptr.is_null() {
+        return Err(ReportError::UserReportNull);
+    }
+
+    let mut usage = Vec::new();
+    for cluster_ptr in unsafe { SlurmIterator::new(report.ptr) } {
+        let cluster = cluster_ptr as *const slurmdb_report_cluster_rec_t;
+        let users = unsafe { SlurmIterator::new((*cluster).user_list) };
+        usage.extend(users.map(|user_ptr| unsafe {
+            UserUsage::from_c_rec(user_ptr as *const slurmdb_report_user_rec_t, table)
+        }));
+    }
+    Ok(top_users_by_account(usage, limit))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_utilization_adds_up_days() {
+        let noon =
+            DateTime::from_timestamp(1_700_000_000 - 1_700_000_000 % 86400 + 43200, 0).unwrap();
+        let period = |start: DateTime<Utc>, alloc_secs, idle_secs| ClusterAccounting {
+            period_start: start,
+            alloc_secs,
+            down_secs: 0,
+            planned_down_secs: 0,
+            idle_secs,
+            reserved_secs: 0,
+        };
+        let days = utilization_by_day(&[
+            period(noon, 3600 * 30, 3600 * 10),
+            period(noon + chrono::Duration::hours(1), 3600 * 30, 3600 * 10),
+            period(noon + chrono::Duration::days(1), 0, 3600 * 40),
+        ]);
+
+        assert_eq!(days.len(), 2);
+        assert_eq!(days[0].allocated, 60.0);
+        assert_eq!(days[0].reported(), 80.0);
+        assert_eq!(days[0].busy_fraction(), 0.75);
+        assert_eq!(days[1].busy_fraction(), 0.0);
+    }
+
+    #[test]
+    fn test_top_users_per_account() {
+        let usage = |user: &str, account: &str, core_hours| UserUsage {
+            user: user.to_string(),
+            account: account.to_string(),
+            core_hours,
+            gpu_hours: 0.0,
+        };
+        let top = top_users_by_account(
+            vec![
+                usage("alice", "cca", 10.0),
+                usage("bob", "cca", 30.0),
+                usage("carol", "cca", 20.0),
+                usage("alice", "ccb", 5.0),
+            ],
+            2,
+        );
+
+        let names =
+            |account: &str| -> Vec<&str> { top[account].iter().map(|u| u.user.as_str()).collect() };
+        assert_eq!(names("cca"), vec!["bob", "carol"]);
+        assert_eq!(names("ccb"), vec!["alice"]);
+    }
+}
@@ -0,0 +1,255 @@
+use chrono::{DateTime, Utc};
+use fi_slurm::parser::parse_tres_str;
+use fi_slurm::reservations::ReservationFlags;
+use fi_slurm_sys::{
+    slurm_list_destroy, slurmdb_reservation_cond_t, slurmdb_reservation_rec_t,
+    slurmdb_reservations_get, xlist,
+};
+use std::ffi::{CStr, CString};
+use std::ops::Deref;
+use thiserror::Error;
+
+use crate::db::{DbConn, DbConnError, DbPool};
+use crate::query::ReservationQuery;
+use crate::tres::TresTable;
+use crate::utils::{SlurmIterator, bool_to_int, vec_to_slurm_list};
+
+#[derive(Error, Debug)]
+pub enum ReservationError {
+    #[error(transparent)]
+    Connection(#[from] DbConnError),
+    #[error("Pointer to reservation_list is null")]
+    ReservationListNull,
+}
+
+/// A Rust-side object corresponding to the slurmdb_reservation_cond_t object
+pub struct ReservationConfig {
+    pub cluster_list: Option<Vec<String>>,
+    pub id_list: Option<Vec<String>>,
+    pub name_list: Option<Vec<String>>,
+    /// A hostlist expression; only reservations of these nodes are returned
+    pub nodes: Option<String>,
+    pub time_start: DateTime<Utc>,
+    pub time_end: DateTime<Utc>,
+    /// Also return how much of each reservation was used by jobs
+    pub with_usage: bool,
+}
+
+impl ReservationConfig {
+    /// Converting a ReservationConfig object into a slurmdb_reservation_cond_t object to be
+    /// passed into Slurm
+    pub fn into_c_struct(self) -> slurmdb_reservation_cond_t {
+        unsafe {
+            let mut c_struct: slurmdb_reservation_cond_t = std::mem::zeroed();
+            c_struct.cluster_list = vec_to_slurm_list(self.cluster_list);
+            c_struct.id_list = vec_to_slurm_list(self.id_list);
+            c_struct.name_list = vec_to_slurm_list(self.name_list);
+            // a hostlist can't hold a nul byte, so one that does is left out rather than cut short
+            c_struct.nodes = self
+                .nodes
+                .and_then(|nodes| CString::new(nodes).ok())
+                .map_or(std::ptr::null_mut(), CString::into_raw);
+            c_struct.time_start = self.time_start.timestamp();
+            c_struct.time_end = self.time_end.timestamp();
+            c_struct.with_usage = bool_to_int(self.with_usage);
+
+            c_struct
+        }
+    }
+}
+
+/// Wrapper owning a heap-allocated Slurm reservation filter struct
+pub struct ReservationQueryInfo {
+    pub resv: *mut slurmdb_reservation_cond_t,
+}
+
+impl ReservationQueryInfo {
+    pub fn new(config: ReservationConfig) -> Self {
+        let boxed = Box::new(config.into_c_struct());
+        Self {
+            resv: Box::into_raw(boxed),
+        }
+    }
+}
+
+impl Drop for ReservationQueryInfo {
+    /// Destroys the Slurm lists in the filter, then reclaims the struct itself
+    fn drop(&mut self) {
+        if !self.resv.is_null() {
+            unsafe {
+                let cond: &mut slurmdb_reservation_cond_t = &mut *self.resv;
+
+                if !cond.cluster_list.is_null() {
+                    slurm_list_destroy(cond.cluster_list);
+                }
+                if !cond.format_list.is_null() {
+                    slurm_list_destroy(cond.format_list);
+                }
+                if !cond.id_list.is_null() {
+                    slurm_list_destroy(cond.id_list);
+                }
+                if !cond.name_list.is_null() {
+                    slurm_list_destroy(cond.name_list);
+                }
+                // the node list was allocated by Rust, so Rust frees it
+                if !cond.nodes.is_null() {
+                    drop(CString::from_raw(cond.nodes));
+                }
+
+                let _ = Box::from_raw(self.resv);
+            }
+            self.resv = std::ptr::null_mut();
+        }
+    }
+}
+
+impl Deref for ReservationQueryInfo {
+    type Target = slurmdb_reservation_cond_t;
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.resv }
+    }
+}
+
+pub struct SlurmReservationList {
+    pub ptr: *mut xlist,
+}
+
+impl SlurmReservationList {
+    pub fn new(db_conn: &mut DbConn, resv_query: &mut ReservationQueryInfo) -> Self {
+        unsafe {
+            let ptr = slurmdb_reservations_get(db_conn.as_mut_ptr(), resv_query.resv);
+            Self { ptr }
+        }
+    }
+}
+
+impl Drop for SlurmReservationList {
+    fn drop(&mut self) {
+        if !self.ptr.is_null() {
+            unsafe {
+                slurm_list_destroy(self.ptr);
+            }
+        }
+    }
+}
+
+/// A past or current reservation, as `sacctmgr show reservation` lists it
+///
+/// A reservation that was updated while active is returned once per version, each with the
+/// times that version was in effect
+#[derive(Debug, Clone, PartialEq)]
+pub struct SlurmReservation {
+    pub id: u32,
+    pub name: String,
+    pub nodes: String,
+    pub flags: ReservationFlags,
+    pub time_start: DateTime<Utc>,
+    pub time_end: DateTime<Utc>,
+    /// The cores held by the reservation
+    pub cores: u64,
+    /// The seconds of the reservation no job ran in it
+    pub unused_wall_secs: f64,
+}
+
+impl SlurmReservation {
+    /// Generate a SlurmReservation object from a C slurmdb_reservation_rec_t object
+    /// # Safety
+    /// This function is unsafe because it dereferences a raw pointer from C.
+    /// The caller must ensure that the pointer is valid and points to a properly initialized
+    /// slurmdb_reservation_rec_t struct.
+    pub unsafe fn from_c_rec(rec: *const slurmdb_reservation_rec_t, table: &TresTable) -> Self {
+        unsafe {
+            let string = |ptr: *mut std::os::raw::c_char| {
+                if ptr.is_null() {
+                    String::new()
+                } else {
+                    CStr::from_ptr(ptr).to_string_lossy().into_owned()
+                }
+            };
+
+            // the database keys reserved TRES by id rather than by name
+            let tres = table.by_name(parse_tres_str((*rec).tres_str));
+
+            Self {
+                id: (*rec).id,
+                name: string((*rec).name),
+                nodes: string((*rec).nodes),
+                flags: ReservationFlags::from_bits_truncate((*rec).flags),
+                time_start: DateTime::from_timestamp((*rec).time_start, 0).unwrap_or_default(),
+                time_end: DateTime::from_timestamp((*rec).time_end, 0).unwrap_or_default(),
+                cores: tres.get("cpu").copied().unwrap_or(0),
+                unused_wall_secs: (*rec).unused_wall,
+            }
+        }
+    }
+
+    /// The core-hours the reservation held
+    pub fn reserved_core_hours(&self) -> f64 {
+        let seconds = (self.time_end - self.time_start).num_seconds().max(0);
+        seconds as f64 / 3600.0 * self.cores as f64
+    }
+
+    /// The share of the reservation's time some job ran in it
+    pub fn utilization(&self) -> f64 {
+        let seconds = (self.time_end - self.time_start).num_seconds();
+        if seconds <= 0 {
+            return 0.0;
+        }
+        (1.0 - self.unused_wall_secs / seconds as f64).clamp(0.0, 1.0)
+    }
+}
+
+/// Process a SlurmReservationList into a vector of SlurmReservation objects, naming their TRES
+/// from `table`, or else return an Error
+pub fn process_reservation_list(
+    resv_list: SlurmReservationList,
+    table: &TresTable,
+) -> Result<Vec<SlurmReservation>, ReservationError> {
+    if resv_list.ptr.is_null() {
+        return Err(ReservationError::ReservationListNull);
+    }
+
+    let iterator = unsafe { SlurmIterator::new(resv_list.ptr) };
+    Ok(iterator
+        .map(|node_ptr| unsafe {
+            SlurmReservation::from_c_rec(node_ptr as *const slurmdb_reservation_rec_t, table)
+        })
+        .collect())
+}
+
+/// Fetches from slurmdbd the reservations of the site's cluster that overlap the window, with
+/// how much of each was used
+pub fn get_reservation_history(
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    db: &mut DbPool,
+) -> Result<Vec<SlurmReservation>, ReservationError> {
+    ReservationQuery::new()
+        .site_cluster()
+        .window(start, end)
+        .with_usage()
+        .fetch(db.connection()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn test_reservation_utilization() {
+        let start = DateTime::UNIX_EPOCH + Duration::days(1);
+        let resv = SlurmReservation {
+            id: 7,
+            name: "maint".to_string(),
+            nodes: "worker[1-4]".to_string(),
+            flags: ReservationFlags::MAINT,
+            time_start: start,
+            time_end: start + Duration::hours(10),
+            cores: 256,
+            unused_wall_secs: 4.0 * 3600.0,
+        };
+        assert_eq!(resv.reserved_core_hours(), 2560.0);
+        assert!((resv.utilization() - 0.6).abs() < 1e-9);
+    }
+}
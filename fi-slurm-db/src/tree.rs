@@ -0,0 +1,173 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::acct::AssocLimits;
+use crate::db::{DbError, DbPool};
+use crate::query::AssocQuery;
+
+/// One account in the hierarchy, with the accounts and users directly under it
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AccountNode {
+    /// The account above this one, or None for the root account
+    pub parent: Option<String>,
+    pub children: BTreeSet<String>,
+    pub users: BTreeSet<String>,
+}
+
+/// The account hierarchy of a cluster, as `sacctmgr show assoc tree` prints it
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AssocTree {
+    accounts: BTreeMap<String, AccountNode>,
+}
+
+impl AssocTree {
+    /// Builds the tree from associations, as returned by an `AssocQuery`
+    ///
+    /// An account's own association names its parent, while each user association adds its
+    /// user to the account; a user with associations on several partitions is listed once
+    pub fn from_assocs(assocs: &[AssocLimits]) -> Self {
+        let mut accounts: BTreeMap<String, AccountNode> = BTreeMap::new();
+        for assoc in assocs {
+            if assoc.user.is_empty() {
+                let parent = (!assoc.parent_account.is_empty()
+                    && assoc.parent_account != assoc.account)
+                    .then(|| assoc.parent_account.clone());
+                if let Some(parent) = &parent {
+                    accounts
+                        .entry(parent.clone())
+                        .or_default()
+                        .children
+                        .insert(assoc.account.clone());
+                }
+                accounts.entry(assoc.account.clone()).or_default().parent = parent;
+            } else {
+                accounts
+                    .entry(assoc.account.clone())
+                    .or_default()
+                    .users
+                    .insert(assoc.user.clone());
+            }
+        }
+        Self { accounts }
+    }
+
+    /// Fetches every association of the site's cluster and builds the tree from them
+    pub fn load(db: &mut DbPool) -> Result<Self, DbError> {
+        let assocs = AssocQuery::new().site_cluster().fetch(db.connection()?)?;
+        Ok(Self::from_assocs(&assocs))
+    }
+
+    pub fn get(&self, account: &str) -> Option<&AccountNode> {
+        self.accounts.get(account)
+    }
+
+    pub fn contains(&self, account: &str) -> bool {
+        self.accounts.contains_key(account)
+    }
+
+    /// The accounts with no parent, which on a well-formed cluster is only `root`
+    pub fn roots(&self) -> Vec<&str> {
+        self.accounts
+            .iter()
+            .filter(|(_, node)| node.parent.is_none())
+            .map(|(name, _)| name.as_str())
+            .collect()
+    }
+
+    pub fn parent_of(&self, account: &str) -> Option<&str> {
+        self.accounts.get(account)?.parent.as_deref()
+    }
+
+    /// The accounts directly under the account
+    pub fn children_of(&self, account: &str) -> Vec<&str> {
+        self.accounts
+            .get(account)
+            .map(|node| node.children.iter().map(String::as_str).collect())
+            .unwrap_or_default()
+    }
+
+    /// The accounts above the account, nearest first
+    pub fn ancestors_of(&self, account: &str) -> Vec<&str> {
+        let mut ancestors = Vec::new();
+        let mut current = self.parent_of(account);
+        while let Some(parent) = current {
+            // a cycle can only come from a corrupt database, but shouldn't hang us
+            if ancestors.contains(&parent) {
+                break;
+            }
+            ancestors.push(parent);
+            current = self.parent_of(parent);
+        }
+        ancestors
+    }
+
+    /// Every account below the account, depth first, not including the account itself
+    pub fn descendants_of(&self, account: &str) -> Vec<&str> {
+        let mut descendants = Vec::new();
+        let mut stack: Vec<&str> = self.children_of(account).into_iter().rev().collect();
+        while let Some(child) = stack.pop() {
+            if descendants.contains(&child) {
+                continue;
+            }
+            descendants.push(child);
+            stack.extend(self.children_of(child).into_iter().rev());
+        }
+        descendants
+    }
+
+    /// The users with an association directly under the account
+    pub fn users_of(&self, account: &str) -> Vec<&str> {
+        self.accounts
+            .get(account)
+            .map(|node| node.users.iter().map(String::as_str).collect())
+            .unwrap_or_default()
+    }
+
+    /// The users with an association under the account or any account below it
+    pub fn users_under(&self, account: &str) -> BTreeSet<&str> {
+        std::iter::once(account)
+            .chain(self.descendants_of(account))
+            .flat_map(|account| self.users_of(account))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tree_descendants_and_users() {
+        let account = |account: &str, parent: &str| AssocLimits {
+            account: account.to_string(),
+            parent_account: parent.to_string(),
+            ..Default::default()
+        };
+        let user = |account: &str, user: &str, partition: &str| AssocLimits {
+            account: account.to_string(),
+            user: user.to_string(),
+            partition: partition.to_string(),
+            ..Default::default()
+        };
+        let tree = AssocTree::from_assocs(&[
+            account("root", ""),
+            account("scc", "root"),
+            account("cca", "root"),
+            account("scc-ops", "scc"),
+            user("scc", "alice", ""),
+            user("scc-ops", "bob", "gpu"),
+            user("scc-ops", "bob", "gen"),
+            user("cca", "carol", ""),
+        ]);
+
+        assert_eq!(tree.roots(), vec!["root"]);
+        assert_eq!(tree.descendants_of("root"), vec!["cca", "scc", "scc-ops"]);
+        assert_eq!(tree.descendants_of("scc"), vec!["scc-ops"]);
+        assert_eq!(tree.ancestors_of("scc-ops"), vec!["scc", "root"]);
+        assert_eq!(tree.users_of("scc-ops"), vec!["bob"]);
+        assert_eq!(
+            tree.users_under("scc").into_iter().collect::<Vec<_>>(),
+            vec!["alice", "bob"]
+        );
+        assert!(tree.descendants_of("nonexistent").is_empty());
+    }
+}
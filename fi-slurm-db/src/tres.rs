@@ -0,0 +1,345 @@
+use fi_slurm_sys::{
+    slurm_list_destroy, slurmdb_tres_cond_t, slurmdb_tres_get, slurmdb_tres_rec_t, xlist,
+};
+use std::{
+    collections::HashMap,
+    ffi::CStr,
+    ops::Deref,
+    sync::{LazyLock, OnceLock},
+};
+use thiserror::Error;
+
+use crate::db::{DbConn, DbConnError, DbPool};
+use crate::query::TresQuery;
+use crate::utils::{SlurmIterator, bool_to_int, vec_to_slurm_list};
+
+#[derive(Error, Debug)]
+pub enum TresError {
+    #[error("Pointer to tres_list is null")]
+    TresListNull,
+    #[error(transparent)]
+    Connection(#[from] DbConnError),
+    #[error("List of TRES successfully retrieved but empty")]
+    EmptyTresListError,
+}
+
+/// The TRES whose ids are fixed by Slurm itself, and so are the same on every cluster
+const SLURM_TRES: [(u32, &str); 8] = [
+    (1, "cpu"),
+    (2, "mem"),
+    (3, "energy"),
+    (4, "node"),
+    (5, "billing"),
+    (6, "fs/disk"),
+    (7, "vmem"),
+    (8, "pages"),
+];
+
+/// The id `gres/gpu` was given at Flatiron, assumed when slurmdbd can't be asked
+const FALLBACK_GPU_ID: u32 = 1001;
+
+/// A Rust-side object corresponding to the slurmdb_tres_cond_t object
+pub struct TresConfig {
+    pub id_list: Option<Vec<String>>,
+    pub name_list: Option<Vec<String>>,
+    pub type_list: Option<Vec<String>>,
+    /// Also return TRES that have since been removed, which old jobs and limits may still name
+    pub with_deleted: bool,
+}
+
+impl TresConfig {
+    /// Converting a TresConfig object into a slurmdb_tres_cond_t object to be passed into Slurm
+    pub fn into_c_struct(self) -> slurmdb_tres_cond_t {
+        unsafe {
+            let mut c_struct: slurmdb_tres_cond_t = std::mem::zeroed();
+            c_struct.id_list = vec_to_slurm_list(self.id_list);
+            c_struct.name_list = vec_to_slurm_list(self.name_list);
+            c_struct.type_list = vec_to_slurm_list(self.type_list);
+            c_struct.with_deleted = bool_to_int(self.with_deleted);
+
+            c_struct
+        }
+    }
+}
+
+/// Wrapper owning a heap-allocated Slurm TRES filter struct
+pub struct TresQueryInfo {
+    pub tres: *mut slurmdb_tres_cond_t,
+}
+
+impl TresQueryInfo {
+    pub fn new(config: TresConfig) -> Self {
+        let boxed = Box::new(config.into_c_struct());
+        Self {
+            tres: Box::into_raw(boxed),
+        }
+    }
+}
+
+impl Drop for TresQueryInfo {
+    /// Destroys the Slurm lists in the filter, then reclaims the struct itself
+    fn drop(&mut self) {
+        if !self.tres.is_null() {
+            unsafe {
+                let cond: &mut slurmdb_tres_cond_t = &mut *self.tres;
+
+                if !cond.id_list.is_null() {
+                    slurm_list_destroy(cond.id_list);
+                }
+                if !cond.name_list.is_null() {
+                    slurm_list_destroy(cond.name_list);
+                }
+                if !cond.type_list.is_null() {
+                    slurm_list_destroy(cond.type_list);
+                }
+
+                let _ = Box::from_raw(self.tres);
+            }
+            self.tres = std::ptr::null_mut();
+        }
+    }
+}
+
+impl Deref for TresQueryInfo {
+    type Target = slurmdb_tres_cond_t;
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.tres }
+    }
+}
+
+pub struct SlurmTresList {
+    pub ptr: *mut xlist,
+}
+
+impl SlurmTresList {
+    pub fn new(db_conn: &mut DbConn, tres_query: &mut TresQueryInfo) -> Self {
+        unsafe {
+            let ptr = slurmdb_tres_get(db_conn.as_mut_ptr(), tres_query.tres);
+            Self { ptr }
+        }
+    }
+}
+
+impl Drop for SlurmTresList {
+    fn drop(&mut self) {
+        if !self.ptr.is_null() {
+            unsafe {
+                slurm_list_destroy(self.ptr);
+            }
+        }
+    }
+}
+
+/// A Rust object holding the information from a slurmdb_tres_rec_t object
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SlurmTres {
+    pub id: u32,
+    /// The kind of resource, such as `cpu`, `gres` or `license`
+    pub tres_type: String,
+    /// The name within the kind, such as `gpu` for `gres`; empty for most built-in TRES
+    pub name: String,
+    /// How much of the resource the cluster has, where Slurm tracks it
+    pub count: u64,
+}
+
+impl SlurmTres {
+    /// Generate a SlurmTres object from a C slurmdb_tres_rec_t object
+    /// # Safety
+    /// This function is unsafe because it dereferences a raw pointer from C.
+    /// The caller must ensure that the pointer is valid and points to a properly initialized
+    /// slurmdb_tres_rec_t struct.
+    pub unsafe fn from_c_rec(rec: *const slurmdb_tres_rec_t) -> Self {
+        unsafe {
+            let tres_type = if (*rec).type_.is_null() {
+                String::new()
+            } else {
+                CStr::from_ptr((*rec).type_).to_string_lossy().into_owned()
+            };
+
+            let name = if (*rec).name.is_null() {
+                String::new()
+            } else {
+                CStr::from_ptr((*rec).name).to_string_lossy().into_owned()
+            };
+
+            Self {
+                id: (*rec).id,
+                tres_type,
+                name,
+                count: (*rec).count,
+            }
+        }
+    }
+
+    /// The name Slurm prints the TRES under, such as `cpu`, `gres/gpu` or `license/matlab`
+    pub fn full_name(&self) -> String {
+        if self.name.is_empty() {
+            self.tres_type.clone()
+        } else {
+            format!("{}/{}", self.tres_type, self.name)
+        }
+    }
+}
+
+/// Process a SlurmTresList into a vector of SlurmTres objects, or else return an Error
+pub fn process_tres_list(tres_list: SlurmTresList) -> Result<Vec<SlurmTres>, TresError> {
+    if tres_list.ptr.is_null() {
+        return Err(TresError::TresListNull);
+    }
+
+    let iterator = unsafe { SlurmIterator::new(tres_list.ptr) };
+
+    let results: Vec<SlurmTres> = iterator
+        .map(|node_ptr| {
+            let tres_rec_ptr = node_ptr as *const slurmdb_tres_rec_t;
+            unsafe { SlurmTres::from_c_rec(tres_rec_ptr) }
+        })
+        .collect();
+
+    if !results.is_empty() {
+        Ok(results)
+    } else {
+        Err(TresError::EmptyTresListError)
+    }
+}
+
+/// The names of the cluster's TRES by id, for reading the TRES strings slurmdbd stores, which
+/// are keyed by id, such as `1=4,2=16000,1001=1`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TresTable {
+    names: HashMap<u32, String>,
+}
+
+impl TresTable {
+    /// The TRES every cluster has, plus `gres/gpu` under the id it has at Flatiron
+    pub fn builtin() -> Self {
+        let mut names: HashMap<u32, String> = SLURM_TRES
+            .iter()
+            .map(|(id, name)| (*id, name.to_string()))
+            .collect();
+        names.insert(FALLBACK_GPU_ID, "gres/gpu".to_string());
+        Self { names }
+    }
+
+    /// The table of the TRES slurmdbd returned; the ids fixed by Slurm are kept in case any
+    /// were left out
+    pub fn from_records(records: &[SlurmTres]) -> Self {
+        let mut names: HashMap<u32, String> = SLURM_TRES
+            .iter()
+            .map(|(id, name)| (*id, name.to_string()))
+            .collect();
+        names.extend(records.iter().map(|tres| (tres.id, tres.full_name())));
+        Self { names }
+    }
+
+    /// The name of the TRES with the given id, as written in a TRES string
+    pub fn name(&self, id: &str) -> Option<&str> {
+        let id: u32 = id.trim().parse().ok()?;
+        self.names.get(&id).map(String::as_str)
+    }
+
+    /// Translates a TRES string keyed by id into names and counts, in the order given
+    ///
+    /// A TRES missing from the table is named `tres/<id>` rather than dropped, so a limit is
+    /// never silently hidden
+    pub fn parse(&self, tres: &str) -> Vec<(String, u64)> {
+        tres.split(',')
+            .filter_map(|t| {
+                let (id, count) = t.split_once('=')?;
+                let count = count.trim().parse::<u64>().ok()?;
+                let name = self
+                    .name(id)
+                    .map_or_else(|| format!("tres/{}", id.trim()), str::to_string);
+                Some((name, count))
+            })
+            .collect()
+    }
+
+    /// Rekeys TRES counts parsed by id, such as from `fi_slurm::parser::parse_tres_str`, by name
+    pub fn by_name(&self, counts: HashMap<String, u64>) -> HashMap<String, u64> {
+        counts
+            .into_iter()
+            .map(|(id, count)| {
+                let name = self
+                    .name(&id)
+                    .map_or_else(|| format!("tres/{}", id), str::to_string);
+                (name, count)
+            })
+            .collect()
+    }
+}
+
+/// Fetches the cluster's TRES table from slurmdbd, including deleted TRES
+pub fn load_tres_table(db: &mut DbPool) -> Result<TresTable, TresError> {
+    let records = TresQuery::new().with_deleted().fetch(db.connection()?)?;
+
+    Ok(TresTable::from_records(&records))
+}
+
+static TABLE: OnceLock<TresTable> = OnceLock::new();
+static BUILTIN: LazyLock<TresTable> = LazyLock::new(TresTable::builtin);
+
+/// Fetches the cluster's TRES table over the pool's connection, unless it already has been
+///
+/// If slurmdbd can't be reached, the built-in table is used instead, which knows the TRES
+/// fixed by Slurm and Flatiron's GPUs but no other GRES or licenses
+pub fn init_tres_table(db: &mut DbPool) -> &'static TresTable {
+    match db.connection() {
+        Ok(db_conn) => init_tres_table_over(db_conn),
+        Err(_) => TABLE.get_or_init(TresTable::builtin),
+    }
+}
+
+/// Fetches the cluster's TRES table over a connection already open for another query, unless
+/// it already has been, as the queries whose records are keyed by TRES id do
+pub(crate) fn init_tres_table_over(db_conn: &mut DbConn) -> &'static TresTable {
+    TABLE.get_or_init(|| {
+        TresQuery::new().with_deleted().fetch(db_conn).map_or_else(
+            |_| TresTable::builtin(),
+            |records| TresTable::from_records(&records),
+        )
+    })
+}
+
+/// The cluster's TRES table, as fetched by `init_tres_table` or by a query of jobs or
+/// reservations, or else the built-in table
+///
+/// It never opens a connection of its own, so that a run keeps to the one slurmdbd connection
+/// of its pool
+pub fn tres_table() -> &'static TresTable {
+    TABLE.get().unwrap_or(&BUILTIN)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_table_names_site_tres() {
+        let tres = |id, tres_type: &str, name: &str| SlurmTres {
+            id,
+            tres_type: tres_type.to_string(),
+            name: name.to_string(),
+            count: 0,
+        };
+        let table = TresTable::from_records(&[
+            tres(5, "billing", ""),
+            tres(1002, "gres", "gpu"),
+            tres(1003, "license", "matlab"),
+        ]);
+
+        assert_eq!(table.name("1"), Some("cpu"));
+        assert_eq!(table.name("1002"), Some("gres/gpu"));
+        assert_eq!(table.name("1001"), None);
+        assert_eq!(
+            table.parse("1=64,5=120,1003=2,1004=1"),
+            vec![
+                ("cpu".to_string(), 64),
+                ("billing".to_string(), 120),
+                ("license/matlab".to_string(), 2),
+                ("tres/1004".to_string(), 1),
+            ]
+        );
+        assert_eq!(TresTable::builtin().name("1001"), Some("gres/gpu"));
+    }
+}
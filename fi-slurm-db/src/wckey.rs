@@ -0,0 +1,184 @@
+use chrono::{DateTime, Utc};
+use fi_slurm_sys::{
+    slurm_list_destroy, slurmdb_accounting_rec_t, slurmdb_wckey_cond_t, slurmdb_wckey_rec_t,
+    slurmdb_wckeys_get, xlist,
+};
+use std::{ffi::CStr, ops::Deref};
+use thiserror::Error;
+
+use crate::db::DbConn;
+use crate::utils::{SlurmIterator, bool_to_int, vec_to_slurm_list};
+
+#[derive(Error, Debug)]
+pub enum WckeyError {
+    #[error("Pointer to wckey_list is null")]
+    WckeyListNull,
+}
+
+/// A Rust-side object corresponding to the slurmdb_wckey_cond_t object
+pub struct WckeyConfig {
+    pub cluster_list: Option<Vec<String>>,
+    pub name_list: Option<Vec<String>>,
+    pub user_list: Option<Vec<String>>,
+    pub usage_start: DateTime<Utc>,
+    pub usage_end: DateTime<Utc>,
+    /// Only return the WCKey each user is charged to by default
+    pub only_defaults: bool,
+    pub with_deleted: bool,
+    /// Also return the usage charged to each WCKey between `usage_start` and `usage_end`
+    pub with_usage: bool,
+}
+
+impl WckeyConfig {
+    /// Converting a WckeyConfig object into a slurmdb_wckey_cond_t object to be passed into
+    /// Slurm
+    pub fn into_c_struct(self) -> slurmdb_wckey_cond_t {
+        unsafe {
+            let mut c_struct: slurmdb_wckey_cond_t = std::mem::zeroed();
+            c_struct.cluster_list = vec_to_slurm_list(self.cluster_list);
+            c_struct.name_list = vec_to_slurm_list(self.name_list);
+            c_struct.user_list = vec_to_slurm_list(self.user_list);
+            c_struct.usage_start = self.usage_start.timestamp();
+            c_struct.usage_end = self.usage_end.timestamp();
+            c_struct.only_defs = bool_to_int(self.only_defaults);
+            c_struct.with_deleted = bool_to_int(self.with_deleted);
+            c_struct.with_usage = bool_to_int(self.with_usage);
+
+            c_struct
+        }
+    }
+}
+
+/// Wrapper owning a heap-allocated Slurm WCKey filter struct
+pub struct WckeyQueryInfo {
+    pub wckey: *mut slurmdb_wckey_cond_t,
+}
+
+impl WckeyQueryInfo {
+    pub fn new(config: WckeyConfig) -> Self {
+        let boxed = Box::new(config.into_c_struct());
+        Self {
+            wckey: Box::into_raw(boxed),
+        }
+    }
+}
+
+impl Drop for WckeyQueryInfo {
+    /// Destroys the Slurm lists in the filter, then reclaims the struct itself
+    fn drop(&mut self) {
+        if !self.wckey.is_null() {
+            unsafe {
+                let cond: &mut slurmdb_wckey_cond_t = &mut *self.wckey;
+
+                if !cond.cluster_list.is_null() {
+                    slurm_list_destroy(cond.cluster_list);
+                }
+                if !cond.format_list.is_null() {
+                    slurm_list_destroy(cond.format_list);
+                }
+                if !cond.id_list.is_null() {
+                    slurm_list_destroy(cond.id_list);
+                }
+                if !cond.name_list.is_null() {
+                    slurm_list_destroy(cond.name_list);
+                }
+                if !cond.user_list.is_null() {
+                    slurm_list_destroy(cond.user_list);
+                }
+
+                let _ = Box::from_raw(self.wckey);
+            }
+            self.wckey = std::ptr::null_mut();
+        }
+    }
+}
+
+impl Deref for WckeyQueryInfo {
+    type Target = slurmdb_wckey_cond_t;
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.wckey }
+    }
+}
+
+pub struct SlurmWckeyList {
+    pub ptr: *mut xlist,
+}
+
+impl SlurmWckeyList {
+    pub fn new(db_conn: &mut DbConn, wckey_query: &mut WckeyQueryInfo) -> Self {
+        unsafe {
+            let ptr = slurmdb_wckeys_get(db_conn.as_mut_ptr(), wckey_query.wckey);
+            Self { ptr }
+        }
+    }
+}
+
+impl Drop for SlurmWckeyList {
+    fn drop(&mut self) {
+        if !self.ptr.is_null() {
+            unsafe {
+                slurm_list_destroy(self.ptr);
+            }
+        }
+    }
+}
+
+/// A workload characterization key one user may charge jobs to, with `--wckey`
+#[derive(Debug, Clone, PartialEq)]
+pub struct SlurmWckey {
+    pub id: u32,
+    pub name: String,
+    pub user: String,
+    pub cluster: String,
+    /// Whether jobs of the user that name no WCKey are charged to this one
+    pub is_default: bool,
+    /// The core-hours charged to the WCKey, when usage was asked for
+    pub core_hours: f64,
+}
+
+impl SlurmWckey {
+    /// Generate a SlurmWckey object from a C slurmdb_wckey_rec_t object
+    /// # Safety
+    /// This function is unsafe because it dereferences a raw pointer from C.
+    /// The caller must ensure that the pointer is valid and points to a properly initialized
+    /// slurmdb_wckey_rec_t struct.
+    pub unsafe fn from_c_rec(rec: *const slurmdb_wckey_rec_t) -> Self {
+        unsafe {
+            let string = |ptr: *mut std::os::raw::c_char| {
+                if ptr.is_null() {
+                    String::new()
+                } else {
+                    CStr::from_ptr(ptr).to_string_lossy().into_owned()
+                }
+            };
+
+            // the usage is rolled up per TRES, of which only the CPUs are wanted here
+            let core_secs: u64 = SlurmIterator::new((*rec).accounting_list)
+                .map(|node_ptr| node_ptr as *const slurmdb_accounting_rec_t)
+                .filter(|accounting| (**accounting).tres_rec.id == 1)
+                .map(|accounting| (*accounting).alloc_secs)
+                .sum();
+
+            Self {
+                id: (*rec).id,
+                name: string((*rec).name),
+                user: string((*rec).user),
+                cluster: string((*rec).cluster),
+                is_default: (*rec).is_def != 0,
+                core_hours: core_secs as f64 / 3600.0,
+            }
+        }
+    }
+}
+
+/// Process a SlurmWckeyList into a vector of SlurmWckey objects, or else return an Error
+pub fn process_wckey_list(wckey_list: SlurmWckeyList) -> Result<Vec<SlurmWckey>, WckeyError> {
+    if wckey_list.ptr.is_null() {
+        return Err(WckeyError::WckeyListNull);
+    }
+
+    let iterator = unsafe { SlurmIterator::new(wckey_list.ptr) };
+    Ok(iterator
+        .map(|node_ptr| unsafe { SlurmWckey::from_c_rec(node_ptr as *const slurmdb_wckey_rec_t) })
+        .collect())
+}
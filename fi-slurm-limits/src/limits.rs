@@ -1,26 +1,269 @@
-use fi_slurm::parser::parse_slurm_hostlist;
+use chrono::{DateTime, Duration, Utc};
+use clap::ValueEnum;
+use colored::Colorize;
+use fi_slurm::error::SlurmError;
 use fi_slurm::{
-    jobs::{
-        AccountJobUsage, FilterMethod, JobState, SlurmJobs, build_node_to_job_map, get_jobs,
-        print_accounts,
-    },
-    nodes::get_nodes,
+    cluster_state::ClusterState,
+    jobs::{AccountJobUsage, FilterMethod, Job, JobState, SlurmJobs, get_jobs},
+    partitions::SlurmPartitions,
+    site::center_of,
+    theme::Theme,
+    utils::count_blocks,
 };
-use fi_slurm_db::acct::{TresMax, get_tres_info};
-use std::collections::{HashMap, HashSet};
+use fi_slurm_db::acct::{AssocLimits, TresInfo, TresMax, get_assoc_limits, get_tres_info};
+use fi_slurm_db::db::DbPool;
+use fi_slurm_db::jobs::{JobQueryError, SlurmJobs as DbJob, get_jobs_in_window};
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap, HashSet};
 
 const ALWAYS_SHOW: [&str; 2] = ["preempt", "gpupreempt"];
 
-pub fn print_limits(name: &str) {
-    let (user_acct, accounts_to_process) =
-        get_tres_info(Some(name.to_string())).unwrap_or_else(|e| {
-            eprintln!("{e}");
-            std::process::exit(1);
-        });
+/// A user's usage against the limits of each QOS, both their own and their center's
+#[derive(Clone, Serialize)]
+pub struct LimitsReport {
+    pub user: String,
+    /// The center account the user belongs to
+    pub account: String,
+    pub user_limits: Vec<AccountJobUsage>,
+    pub center_limits: Vec<AccountJobUsage>,
+    /// The user's pending jobs per QOS
+    pub pending: Vec<PendingDemand>,
+    /// Every limit on each resource of the user's QOS, from the QOS, its partition and the
+    /// user's associations
+    pub effective: Vec<EffectiveLimit>,
+}
+
+/// One line of the limits as CSV or JSON Lines: the usage of one QOS by a user or by their
+/// center, against its limits. A limit of 0 means there is none
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LimitRow<'a> {
+    pub user: &'a str,
+    /// "user" for the user's own usage, or "center" for that of their whole center
+    pub scope: &'static str,
+    pub qos: &'a str,
+    pub nodes: u32,
+    pub max_nodes: u32,
+    pub cores: u32,
+    pub max_cores: u32,
+    pub gpus: u32,
+    pub max_gpus: u32,
+    pub memory_mb: u64,
+    pub max_memory_mb: u64,
+    pub jobs: u32,
+    pub max_jobs: u32,
+}
 
-    let accounts = accounts_to_process.first().unwrap().clone();
+/// Flattens the limits of each user into rows, their own before their center's
+pub fn limit_rows(reports: &[LimitsReport]) -> Vec<LimitRow<'_>> {
+    reports
+        .iter()
+        .flat_map(|report| {
+            let user = report.user.as_str();
+            report
+                .user_limits
+                .iter()
+                .map(move |usage| ("user", usage))
+                .chain(report.center_limits.iter().map(|usage| ("center", usage)))
+                .map(move |(scope, usage)| LimitRow {
+                    user,
+                    scope,
+                    qos: &usage.account,
+                    nodes: usage.nodes,
+                    max_nodes: usage.max_nodes,
+                    cores: usage.cores,
+                    max_cores: usage.max_cores,
+                    gpus: usage.gpus,
+                    max_gpus: usage.max_gpus,
+                    memory_mb: usage.memory_mb,
+                    max_memory_mb: usage.max_memory_mb,
+                    jobs: usage.jobs,
+                    max_jobs: usage.max_jobs,
+                })
+        })
+        .collect()
+}
+
+/// One limit on a resource, and where it is set
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Bound {
+    /// Where the limit is set, such as "QOS MaxTRESPU", "partition MaxNodes" or "account GrpTRES"
+    pub source: String,
+    pub value: u64,
+}
+
+/// The limits on one resource of a QOS from everywhere they can be set, tightest first
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct EffectiveLimit {
+    pub qos: String,
+    /// One of the RESOURCES, counting memory in megabytes and time in minutes
+    pub resource: &'static str,
+    pub bounds: Vec<Bound>,
+}
+
+/// The resources limits are merged over, in the order they are shown
+const RESOURCES: [&str; 6] = ["cores", "nodes", "gpus", "memory", "jobs", "time"];
+
+/// The resources a user's pending jobs ask for under one QOS, and the limits holding them back
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct PendingDemand {
+    pub qos: String,
+    /// Pending jobs, counting each pending task of an array separately
+    pub jobs: u32,
+    pub cores: u64,
+    pub gpus: u64,
+    pub memory_mb: u64,
+    /// The number of jobs held back by each limit, such as "MaxTRESPU cpu" or "MaxJobsPU"
+    pub blocked_by: BTreeMap<String, u32>,
+}
+
+/// The place of one user, account or center on the leaderboard
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LeaderboardEntry {
+    pub rank: usize,
+    /// The user, account or center, depending on how the leaderboard is grouped
+    pub name: String,
+    pub nodes: u32,
+    pub cores: u32,
+    pub gpus: u64,
+    pub memory_mb: u64,
+}
 
-    let mut jobs_collection = get_jobs().unwrap();
+/// The resource the leaderboard ranks users by
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum RankBy {
+    #[default]
+    Nodes,
+    Cores,
+    Gpus,
+    Memory,
+}
+
+/// Whose usage the leaderboard adds up
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum GroupBy {
+    #[default]
+    User,
+    Account,
+    /// The center of each account, as mapped in centers.conf
+    Center,
+}
+
+impl GroupBy {
+    /// The user, account or center the usage of a job by the given user and account counts
+    /// towards
+    fn key<'a>(self, user: &'a str, account: &'a str) -> &'a str {
+        match self {
+            GroupBy::User => user,
+            GroupBy::Account => account,
+            GroupBy::Center => center_of(account),
+        }
+    }
+}
+
+/// How to build a leaderboard: which jobs count, whose usage they add to, and what to rank by
+#[derive(Debug, Clone, Default)]
+pub struct Ranking {
+    pub top_n: usize,
+    pub by: RankBy,
+    pub group_by: GroupBy,
+    /// Only count jobs in these partitions, or in any if empty
+    pub partitions: Vec<String>,
+    /// Only count jobs under these QOS, or under any if empty
+    pub qos: Vec<String>,
+    /// Split the historical usage of each user, account or center by the WCKey it was charged
+    /// to
+    pub by_wckey: bool,
+}
+
+impl Ranking {
+    /// Whether a job in the given partition counts for the leaderboard
+    fn counts_partition(&self, partition: &str) -> bool {
+        self.partitions.is_empty() || self.partitions.iter().any(|p| p == partition)
+    }
+
+    /// Whether a job in the given partition and under the given QOS counts for the leaderboard
+    fn counts(&self, partition: &str, qos: &str) -> bool {
+        self.counts_partition(partition)
+            && (self.qos.is_empty() || self.qos.iter().any(|q| q == qos))
+    }
+}
+
+impl LeaderboardEntry {
+    /// The amount of the given resource this entry is using
+    fn usage_of(&self, by: RankBy) -> u64 {
+        match by {
+            RankBy::Nodes => self.nodes.into(),
+            RankBy::Cores => self.cores.into(),
+            RankBy::Gpus => self.gpus,
+            RankBy::Memory => self.memory_mb,
+        }
+    }
+}
+
+/// Everything fi-limits reports, as emitted by --json
+#[derive(Clone, Serialize)]
+pub struct JsonReport {
+    /// The limits of the one user asked about
+    #[serde(flatten)]
+    pub limits: Option<LimitsReport>,
+    /// The limits of each user, when several were asked about
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub users: Option<Vec<LimitsReport>>,
+    pub leaderboard: Vec<LeaderboardEntry>,
+    /// The historical leaderboard, when a window is given with --since
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub history: Option<Vec<HistoryEntry>>,
+}
+
+/// The place of one user, account or center on the historical leaderboard
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HistoryEntry {
+    pub rank: usize,
+    pub name: String,
+    /// The WCKey the usage was charged to, when split by WCKey; empty for jobs with none
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wckey: Option<String>,
+    /// Jobs that ran at any time during the window
+    pub jobs: u32,
+    pub cpu_hours: f64,
+    pub gpu_hours: f64,
+}
+
+/// Collects the running jobs of a user and their center against the QOS limits of each, out of
+/// all the jobs Slurm knows of, along with the limits the partitions and the user's
+/// associations add
+pub fn get_limits(
+    name: &str,
+    all_jobs: &SlurmJobs,
+    partitions: &SlurmPartitions,
+    db: &mut DbPool,
+) -> Result<LimitsReport, SlurmError> {
+    // without slurmdbd there are no limits to compare against, but the user's running jobs can
+    // still be shown, under the partitions they run in
+    let (user_acct, accounts, db_available) = match get_tres_info(Some(name.to_string()), db) {
+        Ok((user_acct, accounts_to_process)) => {
+            let accounts = accounts_to_process.into_iter().next().unwrap_or_default();
+            (user_acct, accounts, true)
+        }
+        Err(e) => {
+            eprintln!(
+                "WARNING: showing usage without limits, as the Slurm database could not be queried: {e}"
+            );
+            let (user_acct, partitions) = running_partitions(all_jobs, name);
+            let accounts = partitions.iter().map(|p| TresInfo::unlimited(p)).collect();
+            (user_acct, accounts, false)
+        }
+    };
+
+    let mut jobs_collection = all_jobs.clone();
+
+    // set aside the user's pending jobs, to compare against the limits once they are known
+    let pending_jobs: Vec<Job> = jobs_collection
+        .pending()
+        .filter(|job| job.user_name == name)
+        .cloned()
+        .collect();
 
     jobs_collection
         .jobs
@@ -54,30 +297,42 @@ pub fn print_limits(name: &str) {
         let user_max_nodes = user_tres_max.max_nodes.unwrap_or(0);
         let user_max_cores = user_tres_max.max_cores.unwrap_or(0);
         let user_max_gres = user_tres_max.max_gpus.unwrap_or(0);
+        let user_max_memory = user_tres_max.max_memory.unwrap_or(0);
 
         let center_tres_max = TresMax::new(a.max_tres_per_group.clone().unwrap_or("".to_string()));
         let center_max_nodes = center_tres_max.max_nodes.unwrap_or(0);
         let center_max_cores = center_tres_max.max_cores.unwrap_or(0);
         let center_max_gres = center_tres_max.max_gpus.unwrap_or(0);
+        let center_max_memory = center_tres_max.max_memory.unwrap_or(0);
 
-        user_usage.push(AccountJobUsage::new(
-            &group,
-            user_nodes,
-            user_cores,
-            user_gres_count,
-            user_max_nodes,
-            user_max_cores,
-            user_max_gres,
-        ));
-        center_usage.push(AccountJobUsage::new(
-            &group,
-            center_nodes,
-            center_cores,
-            center_gres_count,
-            center_max_nodes,
-            center_max_cores,
-            center_max_gres,
-        ));
+        user_usage.push(
+            AccountJobUsage::new(
+                &group,
+                user_nodes,
+                user_cores,
+                user_gres_count,
+                user_max_nodes,
+                user_max_cores,
+                user_max_gres,
+            )
+            .with_memory(user_jobs.get_memory_use(), user_max_memory as u64)
+            .with_jobs(user_jobs.jobs.len() as u32, job_limit(a.max_jobs_per_user))
+            .with_other_limits(user_tres_max.other),
+        );
+        center_usage.push(
+            AccountJobUsage::new(
+                &group,
+                center_nodes,
+                center_cores,
+                center_gres_count,
+                center_max_nodes,
+                center_max_cores,
+                center_max_gres,
+            )
+            .with_memory(center_jobs.get_memory_use(), center_max_memory as u64)
+            .with_jobs(center_jobs.jobs.len() as u32, job_limit(a.grp_jobs))
+            .with_other_limits(center_tres_max.other),
+        );
     });
 
     // a special edge case to deal with the fact that we need to get the QOS limits for the gen
@@ -118,7 +373,10 @@ pub fn print_limits(name: &str) {
             inter.max_nodes,
             inter.max_cores,
             inter.max_gpus,
-        );
+        )
+        .with_memory(gen_bla.memory_mb, inter.max_memory_mb)
+        .with_jobs(gen_bla.jobs, inter.max_jobs)
+        .with_other_limits(inter.other_limits.clone());
 
         user_usage.insert(0, gen_inter);
     } else {
@@ -127,9 +385,9 @@ pub fn print_limits(name: &str) {
             user_usage.insert(0, gen_bla);
         } else if let Some(inter) = inter_acc {
             user_usage.push(inter); // doesn't need to be at the top
-        } else {
+        } else if db_available {
             // the case where neither were present, we just pass a user warning
-            println!(
+            eprintln!(
                 "WARNING: Could not find both 'gen' and 'inter' accounts. No composite account was created."
             );
         };
@@ -147,9 +405,13 @@ pub fn print_limits(name: &str) {
                 user.max_nodes,
                 user.max_cores,
                 user.max_gpus,
+                user.jobs,
+                user.max_jobs,
             ]
             .iter()
             .all(|i| *i == 0)
+            || user.max_memory_mb != 0
+            || !user.other_limits.is_empty()
     });
 
     // only retain those lines for which there are some non-zero LIMITS
@@ -157,64 +419,658 @@ pub fn print_limits(name: &str) {
         ![center.max_nodes, center.max_cores, center.max_gpus]
             .iter()
             .all(|i| *i == 0)
+            || center.max_memory_mb != 0
+            || !center.other_limits.is_empty()
     });
 
     // Sort both by account name
     user_usage.sort_by(|a, b| a.account.cmp(&b.account));
     center_usage.sort_by(|a, b| a.account.cmp(&b.account));
 
-    println!("\nUser Limits ({})", name);
-    print_accounts(user_usage);
+    let pending = pending_demand(&pending_jobs, &user_usage, &center_usage);
 
-    println!("\nCenter Limits ({})", user_acct);
-    print_accounts(center_usage);
+    // association limits only add to the QOS ones, so the report goes on without them
+    let assocs = if db_available {
+        get_assoc_limits(name, &user_acct, db).unwrap_or_else(|e| {
+            eprintln!("WARNING: could not get the association limits of {name}: {e}");
+            Vec::new()
+        })
+    } else {
+        Vec::new()
+    };
+    let effective = effective_limits(&user_usage, &center_usage, &accounts, partitions, &assocs);
+
+    Ok(LimitsReport {
+        user: name.to_string(),
+        account: user_acct,
+        user_limits: user_usage,
+        center_limits: center_usage,
+        pending,
+        effective,
+    })
 }
 
-pub fn leaderboard(top_n: usize) {
-    let mut map: HashMap<String, (u32, u32)> = HashMap::new();
+/// The account of the user's jobs, and the partitions of those running, for when the Slurm
+/// database can't say which QOS the user has
+fn running_partitions(jobs: &SlurmJobs, name: &str) -> (String, Vec<String>) {
+    let user_jobs: Vec<&Job> = jobs
+        .jobs
+        .values()
+        .filter(|job| job.user_name == name)
+        .collect();
+    let account = user_jobs
+        .first()
+        .map(|job| job.account.clone())
+        .unwrap_or_default();
+    let mut partitions: Vec<String> = user_jobs
+        .iter()
+        .filter(|job| job.job_state == JobState::Running)
+        .map(|job| job.partition.clone())
+        .collect();
+    partitions.sort();
+    partitions.dedup();
+    (account, partitions)
+}
 
-    let jobs_collection = get_jobs().unwrap();
+/// Merges the limits on each resource of the user's QOS: those of the QOS for the user and the
+/// center, those of the partition of the same name, and those of the user's and the account's
+/// associations that cover the partition
+fn effective_limits(
+    user_usage: &[AccountJobUsage],
+    center_usage: &[AccountJobUsage],
+    qos_limits: &[TresInfo],
+    partitions: &SlurmPartitions,
+    assocs: &[AssocLimits],
+) -> Vec<EffectiveLimit> {
+    let mut limits = Vec::new();
+    for user in user_usage {
+        let qos = user.account.as_str();
+        let mut bounds: HashMap<&str, Vec<Bound>> = HashMap::new();
+        let mut add = |resource: &'static str, source: &str, value: Option<u64>| {
+            if let Some(value) = value.filter(|v| *v != 0) {
+                bounds.entry(resource).or_default().push(Bound {
+                    source: source.to_string(),
+                    value,
+                });
+            }
+        };
 
-    jobs_collection.jobs.iter().for_each(|(_, job)| {
-        if job.job_state == JobState::Running {
-            let usage = map.entry(job.user_name.clone()).or_insert((0, 0)); //(job.user_name, (job.num_nodes, job.num_cpus))
+        let qos_rows = [
+            (Some(user), "QOS MaxTRESPU"),
+            (
+                center_usage.iter().find(|c| c.account == qos),
+                "QOS GrpTRES",
+            ),
+        ];
+        for (usage, source) in qos_rows {
+            let Some(usage) = usage else { continue };
+            add("cores", source, Some(usage.max_cores.into()));
+            add("nodes", source, Some(usage.max_nodes.into()));
+            add("gpus", source, Some(usage.max_gpus.into()));
+            add("memory", source, Some(usage.max_memory_mb));
+        }
+        add("jobs", "QOS MaxJobsPU", Some(user.max_jobs.into()));
+        if let Some(center) = center_usage.iter().find(|c| c.account == qos) {
+            add("jobs", "QOS GrpJobs", Some(center.max_jobs.into()));
+        }
+        if let Some(qos_limit) = qos_limits.iter().find(|q| q.name == qos) {
+            add(
+                "time",
+                "QOS MaxWall",
+                qos_limit.max_wall_minutes.map(u64::from),
+            );
+        }
 
-            usage.0 += job.num_nodes;
-            usage.1 += job.num_cpus;
+        if let Some(partition) = partitions.get(qos) {
+            add(
+                "nodes",
+                "partition MaxNodes",
+                partition.limits.max_nodes.map(u64::from),
+            );
+            add(
+                "time",
+                "partition MaxTime",
+                partition.limits.max_time_minutes.map(u64::from),
+            );
         }
-    });
 
-    let mut sorted_scores: Vec<(&String, &(u32, u32))> = map.iter().collect();
+        for assoc in assocs
+            .iter()
+            .filter(|assoc| assoc.partition.is_empty() || assoc.partition == qos)
+        {
+            let owner = if assoc.user.is_empty() {
+                "account"
+            } else {
+                "user"
+            };
+            for (tres, limit) in [
+                (&assoc.grp_tres, "GrpTRES"),
+                (&assoc.max_tres_per_job, "MaxTRESPJ"),
+            ] {
+                let max = TresMax::new(tres.clone());
+                let source = format!("{} {}", owner, limit);
+                add("cores", &source, max.max_cores.map(u64::from));
+                add("nodes", &source, max.max_nodes.map(u64::from));
+                add("gpus", &source, max.max_gpus.map(u64::from));
+                add("memory", &source, max.max_memory.map(u64::from));
+            }
+            add(
+                "jobs",
+                &format!("{} GrpJobs", owner),
+                assoc.grp_jobs.map(u64::from),
+            );
+            add(
+                "jobs",
+                &format!("{} MaxJobs", owner),
+                assoc.max_jobs.map(u64::from),
+            );
+            add(
+                "time",
+                &format!("{} MaxWall", owner),
+                assoc.max_wall_minutes.map(u64::from),
+            );
+        }
+
+        for resource in RESOURCES {
+            if let Some(mut bounds) = bounds.remove(resource) {
+                bounds.sort_by_key(|bound| bound.value);
+                limits.push(EffectiveLimit {
+                    qos: qos.to_string(),
+                    resource,
+                    bounds,
+                });
+            }
+        }
+    }
+    limits
+}
+
+/// Whether asking for `request` more on top of `used` would go over `max`, where 0 is no limit
+fn exceeds(used: u64, request: u64, max: u64) -> bool {
+    max != 0 && used + request > max
+}
+
+/// The first QOS limit that keeps a pending job with the given request from starting, given
+/// what the user and their center are already running under the QOS
+///
+/// The request holds one job's nodes, cores, GPUs and memory, and no limits. Returns None when
+/// no limit stands in the way, and the job waits on priority or free resources instead
+fn blocking_limit(
+    request: &AccountJobUsage,
+    user: Option<&AccountJobUsage>,
+    center: Option<&AccountJobUsage>,
+) -> Option<String> {
+    let over = |usage: &AccountJobUsage| -> Option<&'static str> {
+        [
+            (
+                "cpu",
+                exceeds(
+                    usage.cores.into(),
+                    request.cores.into(),
+                    usage.max_cores.into(),
+                ),
+            ),
+            (
+                "gres/gpu",
+                exceeds(
+                    usage.gpus.into(),
+                    request.gpus.into(),
+                    usage.max_gpus.into(),
+                ),
+            ),
+            (
+                "node",
+                exceeds(
+                    usage.nodes.into(),
+                    request.nodes.into(),
+                    usage.max_nodes.into(),
+                ),
+            ),
+            (
+                "mem",
+                exceeds(usage.memory_mb, request.memory_mb, usage.max_memory_mb),
+            ),
+        ]
+        .into_iter()
+        .find(|(_, over)| *over)
+        .map(|(tres, _)| tres)
+    };
+
+    if let Some(user) = user {
+        if user.max_jobs != 0 && user.jobs >= user.max_jobs {
+            return Some("MaxJobsPU".to_string());
+        }
+        if let Some(tres) = over(user) {
+            return Some(format!("MaxTRESPU {}", tres));
+        }
+    }
+    let center = center?;
+    if center.max_jobs != 0 && center.jobs >= center.max_jobs {
+        return Some("GrpJobs".to_string());
+    }
+    over(center).map(|tres| format!("GrpTRES {}", tres))
+}
+
+/// Sums the user's pending jobs per QOS, and finds the limit holding back each of them
+fn pending_demand(
+    pending_jobs: &[Job],
+    user_usage: &[AccountJobUsage],
+    center_usage: &[AccountJobUsage],
+) -> Vec<PendingDemand> {
+    let mut demand: BTreeMap<&str, PendingDemand> = BTreeMap::new();
+
+    for job in pending_jobs {
+        // jobs are matched to QOS by partition, like the running usage above
+        let qos = job.partition.as_str();
+        let tasks = job.task_count();
+        let request = AccountJobUsage::new(
+            qos,
+            job.num_nodes,
+            job.num_cpus,
+            job.requested_gpus() as u32,
+            0,
+            0,
+            0,
+        )
+        .with_memory(job.total_memory_mb(), 0);
+
+        let line = demand.entry(qos).or_insert_with(|| PendingDemand {
+            qos: qos.to_string(),
+            ..PendingDemand::default()
+        });
+        line.jobs += tasks;
+        line.cores += request.cores as u64 * tasks as u64;
+        line.gpus += request.gpus as u64 * tasks as u64;
+        line.memory_mb += request.memory_mb * tasks as u64;
+
+        let user = user_usage.iter().find(|usage| usage.account == qos);
+        let center = center_usage.iter().find(|usage| usage.account == qos);
+        if let Some(limit) = blocking_limit(&request, user, center) {
+            *line.blocked_by.entry(limit).or_default() += tasks;
+        }
+    }
+
+    demand.into_values().collect()
+}
+
+/// The share of a limit beyond which its bar is drawn in the theme's high color
+const WARN_FRACTION: f64 = 0.9;
+
+const BAR_WIDTH: usize = 20;
+
+/// Slurm marks a QOS without a job limit with NO_VAL or INFINITE; both become 0, for no limit
+fn job_limit(max_jobs: u32) -> u32 {
+    if max_jobs >= u32::MAX - 1 {
+        0
+    } else {
+        max_jobs
+    }
+}
+
+/// Formats megabytes with the largest binary unit that keeps the number above 1
+fn format_memory(mb: u64) -> String {
+    const GB: u64 = 1024;
+    const TB: u64 = 1024 * 1024;
+    if mb >= TB {
+        format!("{:.1}T", mb as f64 / TB as f64)
+    } else if mb >= GB {
+        format!("{:.1}G", mb as f64 / GB as f64)
+    } else {
+        format!("{}M", mb)
+    }
+}
+
+/// Formats a time limit in minutes the way `sinfo` does, as `[days-]hours:minutes:00`
+fn format_minutes(minutes: u64) -> String {
+    let (days, hours, minutes) = (minutes / 1440, minutes / 60 % 24, minutes % 60);
+    if days > 0 {
+        format!("{}-{:02}:{:02}:00", days, hours, minutes)
+    } else {
+        format!("{}:{:02}:00", hours, minutes)
+    }
+}
+
+/// A bar filled to the share of the limit in use, empty when there is no limit
+fn usage_bar(used: u64, max: u64, theme: &Theme) -> String {
+    if max == 0 {
+        return format!("│{}│", " ".repeat(BAR_WIDTH));
+    }
+    let fraction = (used as f64 / max as f64).min(1.0);
+    let (full, empty, partial) = count_blocks(BAR_WIDTH, fraction);
+    let color = if used as f64 > max as f64 * WARN_FRACTION {
+        theme.palette.high
+    } else {
+        theme.palette.low
+    };
+    let filled = format!("{}{}", "█".repeat(full), partial.unwrap_or_default());
+    format!("│{}{}│", theme.paint(&filled, color), " ".repeat(empty))
+}
 
-    sorted_scores.sort_by(|a, b| b.1.cmp(a.1));
+/// Prints a bar per resource of each QOS, showing its use against its limit
+///
+/// Resources with neither use nor a limit are left out; a limit of "-" means there is none
+fn print_usage(title: &str, accounts: &[AccountJobUsage], theme: &Theme) {
+    println!("\n{}", title.bold());
+    for acc in accounts {
+        println!("{}", acc.account);
 
-    for (position, (user, score)) in sorted_scores.iter().enumerate().take(top_n) {
-        let rank = position + 1;
+        let count: fn(u64) -> String = |n| n.to_string();
+        let rows = [
+            ("CORES", acc.cores.into(), acc.max_cores.into(), count),
+            ("NODES", acc.nodes.into(), acc.max_nodes.into(), count),
+            ("GPUS", acc.gpus.into(), acc.max_gpus.into(), count),
+            ("MEMORY", acc.memory_mb, acc.max_memory_mb, format_memory),
+            ("JOBS", acc.jobs.into(), acc.max_jobs.into(), count),
+        ];
+
+        let mut shown = false;
+        for (label, used, max, format) in rows {
+            if used == 0 && max == 0 {
+                continue;
+            }
+            shown = true;
+            let (limit, percent) = if max == 0 {
+                ("-".to_string(), String::new())
+            } else {
+                let percent = used as f64 / max as f64 * 100.0;
+                (format(max), format!("{:>5.1}%", percent))
+            };
+            println!(
+                "  {:<6} {} {:>8}/{:<8} {}",
+                label,
+                usage_bar(used, max, theme),
+                format(used),
+                limit,
+                percent
+            );
+        }
+        // usage of other TRES isn't tracked per job, so only their limits are shown
+        for (tres, max) in &acc.other_limits {
+            shown = true;
+            println!("  {:<6} limit {}", tres.to_uppercase(), max);
+        }
+        if !shown {
+            println!("  nothing in use, no limits");
+        }
+    }
+}
+
+/// Prints what the user's pending jobs ask for per QOS, and the limits holding them back
+fn print_pending(title: &str, pending: &[PendingDemand], theme: &Theme) {
+    println!("\n{}", title.bold());
+    if pending.is_empty() {
+        println!("No pending jobs");
+        return;
+    }
+    let width = pending.iter().map(|p| p.qos.len()).max().unwrap_or(0);
+    for line in pending {
+        let blocked: Vec<String> = line
+            .blocked_by
+            .iter()
+            .map(|(limit, jobs)| format!("{} ({} jobs)", limit, jobs))
+            .collect();
+        let status = if blocked.is_empty() {
+            "not held by a limit".normal()
+        } else {
+            theme.paint(
+                &format!("blocked by {}", blocked.join(", ")),
+                theme.palette.high,
+            )
+        };
+        println!(
+            "{:<width$}  {:>5} jobs  {:>6} cores  {:>4} GPUs  {:>7}  {}",
+            line.qos,
+            line.jobs,
+            line.cores,
+            line.gpus,
+            format_memory(line.memory_mb),
+            status
+        );
+    }
+}
+
+/// Prints every limit on each resource of the user's QOS, highlighting the tightest, which is
+/// the one that applies
+fn print_effective(title: &str, limits: &[EffectiveLimit], theme: &Theme) {
+    println!("\n{}", title.bold());
+    if limits.is_empty() {
+        println!("No limits known");
+        return;
+    }
+    let mut qos = None;
+    for limit in limits {
+        if qos != Some(&limit.qos) {
+            println!("{}", limit.qos);
+            qos = Some(&limit.qos);
+        }
+        let format: fn(u64) -> String = match limit.resource {
+            "memory" => format_memory,
+            "time" => format_minutes,
+            _ => |n| n.to_string(),
+        };
+        let Some((tightest, looser)) = limit.bounds.split_first() else {
+            continue;
+        };
+        let looser: Vec<String> = looser
+            .iter()
+            .map(|bound| format!("{} {}", format(bound.value), bound.source))
+            .collect();
+        println!(
+            "  {:<6} {:>10}  {}{}",
+            limit.resource.to_uppercase(),
+            format(tightest.value),
+            theme.paint(&tightest.source, theme.palette.warning),
+            if looser.is_empty() {
+                String::new()
+            } else {
+                format!(" (then {})", looser.join(", "))
+            }
+        );
+    }
+}
+
+/// Formats used/max for the comparison table, with "-" for no limit, padded to `width` and
+/// painted when above the warning threshold
+fn usage_cell(
+    used: u64,
+    max: u64,
+    format: fn(u64) -> String,
+    width: usize,
+    theme: &Theme,
+) -> String {
+    let limit = if max == 0 {
+        "-".to_string()
+    } else {
+        format(max)
+    };
+    let cell = format!("{:>width$}", format!("{}/{}", format(used), limit));
+    if max != 0 && used as f64 > max as f64 * WARN_FRACTION {
+        theme.paint(&cell, theme.palette.high).to_string()
+    } else {
+        cell
+    }
+}
+
+/// Prints one line per QOS of each row's usage against its limits, for comparing users or
+/// centers side by side
+fn print_comparison_table(
+    header: &str,
+    rows: &[(&str, &AccountJobUsage, Option<&PendingDemand>)],
+    theme: &Theme,
+) {
+    let name_width = rows
+        .iter()
+        .map(|(name, _, _)| name.len())
+        .max()
+        .unwrap_or(0)
+        .max(header.len());
+    let qos_width = rows
+        .iter()
+        .map(|(_, usage, _)| usage.account.len())
+        .max()
+        .unwrap_or(0)
+        .max("QOS".len());
+
+    println!(
+        "{}",
+        format!(
+            "{:<name_width$}  {:<qos_width$}  {:>11}  {:>9}  {:>9}  {:>13}  {:>9}  {:>7}",
+            header, "QOS", "CORES", "NODES", "GPUS", "MEMORY", "JOBS", "PENDING"
+        )
+        .bold()
+    );
+    let count: fn(u64) -> String = |n| n.to_string();
+    for (name, usage, pending) in rows {
         println!(
-            "{:>2}. {:<12} is using {:>4} nodes and {:>5} cores",
-            rank, user, score.0, score.1
+            "{:<name_width$}  {:<qos_width$}  {}  {}  {}  {}  {}  {:>7}",
+            name,
+            usage.account,
+            usage_cell(usage.cores.into(), usage.max_cores.into(), count, 11, theme),
+            usage_cell(usage.nodes.into(), usage.max_nodes.into(), count, 9, theme),
+            usage_cell(usage.gpus.into(), usage.max_gpus.into(), count, 9, theme),
+            usage_cell(
+                usage.memory_mb,
+                usage.max_memory_mb,
+                format_memory,
+                13,
+                theme
+            ),
+            usage_cell(usage.jobs.into(), usage.max_jobs.into(), count, 9, theme),
+            pending.map_or(0, |p| p.jobs),
         );
     }
 }
 
-pub fn leaderboard_feature(top_n: usize, features: Vec<String>) {
-    let mut map: HashMap<String, (u32, u32)> = HashMap::new();
+/// Prints a compact table comparing several users' usage against their limits, followed by
+/// the limits of each of their centers
+pub fn print_comparison(reports: &[LimitsReport], theme: &Theme) {
+    let user_rows: Vec<(&str, &AccountJobUsage, Option<&PendingDemand>)> = reports
+        .iter()
+        .flat_map(|report| {
+            report.user_limits.iter().map(|usage| {
+                let pending = report.pending.iter().find(|p| p.qos == usage.account);
+                (report.user.as_str(), usage, pending)
+            })
+        })
+        .collect();
+    println!();
+    print_comparison_table("USER", &user_rows, theme);
+
+    // users of the same center share its limits, so each center is shown once
+    let mut centers: Vec<&LimitsReport> = Vec::new();
+    for report in reports {
+        if !centers.iter().any(|c| c.account == report.account) {
+            centers.push(report);
+        }
+    }
+    let center_rows: Vec<(&str, &AccountJobUsage, Option<&PendingDemand>)> = centers
+        .iter()
+        .flat_map(|report| {
+            report
+                .center_limits
+                .iter()
+                .map(|usage| (report.account.as_str(), usage, None))
+        })
+        .collect();
+    println!();
+    print_comparison_table("CENTER", &center_rows, theme);
+}
+
+pub fn print_limits(report: &LimitsReport, theme: &Theme) {
+    print_usage(
+        &format!("User Limits ({})", report.user),
+        &report.user_limits,
+        theme,
+    );
+    print_usage(
+        &format!("Center Limits ({})", report.account),
+        &report.center_limits,
+        theme,
+    );
+    print_effective(
+        &format!("Effective Limits ({})", report.user),
+        &report.effective,
+        theme,
+    );
+    print_pending(
+        &format!("Pending Jobs ({})", report.user),
+        &report.pending,
+        theme,
+    );
+}
+
+/// Ranks the users, accounts or centers of the running jobs by the given resource, breaking
+/// ties by nodes, then cores
+fn rank_usage<'a>(jobs: impl Iterator<Item = &'a Job>, ranking: &Ranking) -> Vec<LeaderboardEntry> {
+    let mut map: HashMap<String, LeaderboardEntry> = HashMap::new();
+
+    jobs.filter(|job| job.job_state == JobState::Running)
+        .filter(|job| ranking.counts(&job.partition, &job.qos))
+        .for_each(|job| {
+            let name = ranking.group_by.key(&job.user_name, &job.account);
+            let usage = map
+                .entry(name.to_string())
+                .or_insert_with(|| LeaderboardEntry {
+                    rank: 0,
+                    name: name.to_string(),
+                    nodes: 0,
+                    cores: 0,
+                    gpus: 0,
+                    memory_mb: 0,
+                });
+
+            usage.nodes += job.num_nodes;
+            usage.cores += job.num_cpus;
+            usage.gpus += job.allocated_gpus();
+            usage.memory_mb += job.total_memory_mb();
+        });
 
-    let mut jobs_collection = get_jobs().unwrap();
+    let mut sorted_scores: Vec<LeaderboardEntry> = map.into_values().collect();
+
+    sorted_scores
+        .sort_by_key(|entry| Reverse((entry.usage_of(ranking.by), entry.nodes, entry.cores)));
+
+    sorted_scores
+        .into_iter()
+        .take(ranking.top_n)
+        .enumerate()
+        .map(|(position, entry)| LeaderboardEntry {
+            rank: position + 1,
+            ..entry
+        })
+        .collect()
+}
 
-    let nodes_collection = get_nodes().unwrap();
+/// The users with the highest current usage of the whole cluster
+pub fn leaderboard(ranking: &Ranking) -> Result<Vec<LeaderboardEntry>, SlurmError> {
+    let jobs_collection = get_jobs()?;
 
-    enrich_jobs_with_node_ids(&mut jobs_collection, &nodes_collection.name_to_id);
+    Ok(rank_usage(jobs_collection.jobs.values(), ranking))
+}
 
-    // keys are node host ids, values are job ids running on those nodes
-    let node_to_job_map = build_node_to_job_map(&jobs_collection);
+/// The users with the highest current usage of the nodes with any of the given features
+pub fn leaderboard_feature(
+    ranking: &Ranking,
+    features: Vec<String>,
+) -> Result<Vec<LeaderboardEntry>, SlurmError> {
+    // the node to job map's keys are node host ids, values are job ids running on those nodes
+    let ClusterState {
+        nodes: nodes_collection,
+        jobs: jobs_collection,
+        node_to_job_map,
+        ..
+    } = ClusterState::load()?;
 
     let features_set: HashSet<String> = HashSet::from_iter(features.iter().cloned());
 
     let filtered_job_ids: Vec<u32> = nodes_collection
         .nodes
         .iter()
-        .filter(|node| node.features.iter().any(|item| features_set.contains(item)))
+        .filter(|node| {
+            node.features
+                .iter()
+                .any(|item| features_set.contains(&**item))
+        })
         .filter_map(|node| node_to_job_map.get(&node.id))
         .flatten()
         .cloned()
@@ -223,49 +1079,315 @@ pub fn leaderboard_feature(top_n: usize, features: Vec<String>) {
     let filtered_jobs_collection =
         jobs_collection.filter_by(FilterMethod::JobIds(filtered_job_ids));
 
-    filtered_jobs_collection.jobs.iter().for_each(|(_, job)| {
-        if job.job_state == JobState::Running {
-            let usage = map.entry(job.user_name.clone()).or_insert((0, 0)); //(job.user_name, (job.num_nodes, job.num_cpus))
+    Ok(rank_usage(filtered_jobs_collection.jobs.values(), ranking))
+}
+
+pub fn print_leaderboard(entries: &[LeaderboardEntry]) {
+    for entry in entries {
+        println!(
+            "{:>2}. {:<12} is using {:>4} nodes, {:>5} cores, {:>3} GPUs and {:>7} of memory",
+            entry.rank,
+            entry.name,
+            entry.nodes,
+            entry.cores,
+            entry.gpus,
+            format_memory(entry.memory_mb)
+        );
+    }
+}
+
+/// Parses a window length such as `7d`, `12h` or `2w` into a duration
+pub fn parse_since(since: &str) -> Result<Duration, String> {
+    let invalid = || {
+        format!(
+            "invalid window '{}': expected a number and h, d or w",
+            since
+        )
+    };
+    let (amount, unit) = since.split_at(since.len().saturating_sub(1));
+    let amount: i64 = amount.parse().map_err(|_| invalid())?;
+    match unit {
+        "h" => Ok(Duration::hours(amount)),
+        "d" => Ok(Duration::days(amount)),
+        "w" => Ok(Duration::weeks(amount)),
+        _ => Err(invalid()),
+    }
+}
+
+/// Ranks the users, accounts or centers of the jobs by the CPU-hours, or with `by` set to
+/// GPUs the GPU-hours, they used between `start` and `end`
+///
+/// The database records only the id of a job's QOS, so `ranking.qos` is not applied here
+fn rank_history(
+    jobs: &[DbJob],
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    ranking: &Ranking,
+) -> Vec<HistoryEntry> {
+    let mut map: HashMap<(String, Option<String>), HistoryEntry> = HashMap::new();
 
-            usage.0 += job.num_nodes;
-            usage.1 += job.num_cpus;
+    for job in jobs {
+        let hours = job.hours_in_window(start, end, end);
+        if hours == 0.0 || !ranking.counts_partition(&job.partition) {
+            continue;
         }
-    });
+        let name = ranking.group_by.key(&job.user, &job.account);
+        let wckey = ranking.by_wckey.then(|| job.wckey.clone());
+        let key = (name.to_string(), wckey.clone());
+        let usage = map.entry(key).or_insert_with(|| HistoryEntry {
+            rank: 0,
+            name: name.to_string(),
+            wckey,
+            jobs: 0,
+            cpu_hours: 0.0,
+            gpu_hours: 0.0,
+        });
+        usage.jobs += 1;
+        usage.cpu_hours += job.alloc_cpus as f64 * hours;
+        usage.gpu_hours += job.alloc_gpus as f64 * hours;
+    }
+
+    let mut sorted: Vec<HistoryEntry> = map.into_values().collect();
+    let score = |entry: &HistoryEntry| match ranking.by {
+        RankBy::Gpus => entry.gpu_hours,
+        _ => entry.cpu_hours,
+    };
+    sorted.sort_by(|a, b| score(b).total_cmp(&score(a)));
 
-    let mut sorted_scores: Vec<(&String, &(u32, u32))> = map.iter().collect();
+    sorted
+        .into_iter()
+        .take(ranking.top_n)
+        .enumerate()
+        .map(|(position, entry)| HistoryEntry {
+            rank: position + 1,
+            ..entry
+        })
+        .collect()
+}
 
-    sorted_scores.sort_by(|a, b| b.1.cmp(a.1));
+/// The users, accounts or centers that used the most of the cluster over the window before
+/// now, according to slurmdbd
+pub fn history_leaderboard(
+    ranking: &Ranking,
+    since: Duration,
+    db: &mut DbPool,
+) -> Result<Vec<HistoryEntry>, JobQueryError> {
+    let end = Utc::now();
+    let start = end - since;
+    let jobs = match get_jobs_in_window(start, end, db) {
+        Ok(jobs) => jobs,
+        Err(JobQueryError::EmptyJobsListError) => Vec::new(),
+        Err(e) => return Err(e),
+    };
+    Ok(rank_history(&jobs, start, end, ranking))
+}
 
-    for (position, (user, score)) in sorted_scores.iter().enumerate().take(top_n) {
-        let rank = position + 1;
-        // let (initial, surname) = user.split_at_checked(1).unwrap_or(("Dr", "Evil"));
+pub fn print_history(entries: &[HistoryEntry]) {
+    for entry in entries {
+        let name = match &entry.wckey {
+            Some(wckey) if wckey.is_empty() => format!("{:<12} {:<12}", entry.name, "-"),
+            Some(wckey) => format!("{:<12} {:<12}", entry.name, wckey),
+            None => format!("{:<12}", entry.name),
+        };
         println!(
-            "{:>2}. {:<12} is using {:>4} nodes and {:>5} cores",
-            rank, user, score.0, score.1
+            "{:>2}. {} used {:>10.1} CPU-hours and {:>8.1} GPU-hours in {:>5} jobs",
+            entry.rank, name, entry.cpu_hours, entry.gpu_hours, entry.jobs
         );
     }
 }
 
-pub fn enrich_jobs_with_node_ids(slurm_jobs: &mut SlurmJobs, name_to_id: &HashMap<String, usize>) {
-    // iterate mutably over the jobs vector
-    for job in &mut slurm_jobs.jobs.values_mut() {
-        if job.raw_hostlist.is_empty() {
-            continue;
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        // parse the hostlist string
-        let expanded_nodes = parse_slurm_hostlist(&job.raw_hostlist);
+    #[test]
+    fn test_usage_bar_and_memory() {
+        let theme = Theme::plain();
+        assert_eq!(usage_bar(95, 100, &theme), format!("│{} │", "█".repeat(19)));
+        // usage beyond the limit fills the bar without overflowing it
+        assert_eq!(
+            usage_bar(150, 100, &theme),
+            format!("│{}│", "█".repeat(BAR_WIDTH))
+        );
+        assert_eq!(
+            usage_bar(5, 0, &theme),
+            format!("│{}│", " ".repeat(BAR_WIDTH))
+        );
 
-        // convert names to IDs and populate the job's node_ids vector
-        job.node_ids.reserve(expanded_nodes.len());
-        for node_name in expanded_nodes {
-            if let Some(&id) = name_to_id.get(&node_name) {
-                job.node_ids.push(id);
-            }
-        }
+        assert_eq!(format_memory(512), "512M");
+        assert_eq!(format_memory(1536), "1.5G");
+        assert_eq!(format_memory(2 * 1024 * 1024), "2.0T");
+        assert_eq!(job_limit(u32::MAX), 0);
+    }
+
+    #[test]
+    fn test_history_counts_only_the_window() {
+        let at = |hours: i64| DateTime::UNIX_EPOCH + Duration::hours(hours);
+        let job = |user: &str, start, end: Option<i64>, cpus, gpus| DbJob {
+            job_id: 1,
+            job_name: String::new(),
+            user: user.to_string(),
+            account: "cca".to_string(),
+            partition: String::new(),
+            wckey: String::new(),
+            state: JobState::Complete,
+            priority: 0,
+            node_names: String::new(),
+            alloc_nodes: 1,
+            eligible: at(start),
+            submit_time: at(start),
+            start_time: at(start),
+            end_time: end.map(at).unwrap_or(DateTime::UNIX_EPOCH),
+            alloc_cpus: cpus,
+            alloc_gpus: gpus,
+        };
+        let mut jobs = vec![
+            // ran 10 hours, 4 of them before the window
+            job("alice", 10, Some(20), 8, 0),
+            // still running at the end of the window
+            job("bob", 30, None, 2, 1),
+            // ended before the window
+            job("carol", 1, Some(5), 100, 4),
+        ];
 
-        // free the memory from the raw string if it's no longer needed.
-        job.raw_hostlist.clear();
-        job.raw_hostlist.shrink_to_fit();
+        let ranking = Ranking {
+            top_n: 10,
+            by: RankBy::Cores,
+            ..Ranking::default()
+        };
+        let ranked = rank_history(&jobs, at(14), at(40), &ranking);
+        assert_eq!(ranked.len(), 2);
+        assert_eq!(ranked[0].name, "alice");
+        assert_eq!(ranked[0].cpu_hours, 8.0 * 6.0);
+        assert_eq!(ranked[1].gpu_hours, 10.0);
+
+        let by_gpu = Ranking {
+            by: RankBy::Gpus,
+            group_by: GroupBy::Account,
+            ..ranking.clone()
+        };
+        let by_gpu = rank_history(&jobs, at(14), at(40), &by_gpu);
+        assert_eq!(by_gpu.len(), 1);
+        assert_eq!(by_gpu[0].jobs, 2);
+
+        jobs[1].wckey = "sims".to_string();
+        let by_wckey = Ranking {
+            group_by: GroupBy::Account,
+            by_wckey: true,
+            ..ranking.clone()
+        };
+        let by_wckey = rank_history(&jobs, at(14), at(40), &by_wckey);
+        assert_eq!(by_wckey.len(), 2);
+        assert_eq!(by_wckey[0].wckey.as_deref(), Some(""));
+        assert_eq!(by_wckey[1].wckey.as_deref(), Some("sims"));
+
+        assert_eq!(parse_since("7d"), Ok(Duration::days(7)));
+        assert!(parse_since("7").is_err());
+        assert!(parse_since("d").is_err());
+    }
+
+    #[test]
+    fn test_limit_rows() {
+        let report = LimitsReport {
+            user: "alice".to_string(),
+            account: "cca".to_string(),
+            user_limits: vec![AccountJobUsage::new("gen", 1, 64, 0, 4, 256, 0)],
+            center_limits: vec![AccountJobUsage::new("cca", 10, 640, 2, 100, 6400, 8)],
+            pending: Vec::new(),
+            effective: Vec::new(),
+        };
+        let rows = limit_rows(std::slice::from_ref(&report));
+        assert_eq!(rows.len(), 2);
+        assert_eq!(
+            (rows[0].scope, rows[0].qos, rows[0].max_cores),
+            ("user", "gen", 256)
+        );
+        assert_eq!(
+            (rows[1].scope, rows[1].qos, rows[1].gpus),
+            ("center", "cca", 2)
+        );
+        assert!(rows.iter().all(|row| row.user == "alice"));
+    }
+
+    #[test]
+    fn test_blocking_limit() {
+        let request = AccountJobUsage::new("gen", 1, 64, 0, 0, 0, 0);
+        let user = AccountJobUsage::new("gen", 2, 960, 0, 0, 1000, 0).with_jobs(5, 10);
+        let center = AccountJobUsage::new("gen", 0, 2000, 0, 0, 4000, 0);
+
+        assert_eq!(
+            blocking_limit(&request, Some(&user), Some(&center)),
+            Some("MaxTRESPU cpu".to_string())
+        );
+        let full = user.clone().with_jobs(10, 10);
+        assert_eq!(
+            blocking_limit(&request, Some(&full), None),
+            Some("MaxJobsPU".to_string())
+        );
+        let roomy = AccountJobUsage::new("gen", 2, 100, 0, 0, 1000, 0);
+        assert_eq!(blocking_limit(&request, Some(&roomy), Some(&center)), None);
+        let busy_center = AccountJobUsage::new("gen", 0, 3990, 0, 0, 4000, 0);
+        assert_eq!(
+            blocking_limit(&request, Some(&roomy), Some(&busy_center)),
+            Some("GrpTRES cpu".to_string())
+        );
+    }
+
+    #[test]
+    fn test_ranking_scope() {
+        let everything = Ranking::default();
+        assert!(everything.counts("gpu", "gen"));
+
+        let scoped = Ranking {
+            partitions: vec!["gpu".to_string()],
+            qos: vec!["preempt".to_string(), "gpupreempt".to_string()],
+            ..Ranking::default()
+        };
+        assert!(scoped.counts("gpu", "gpupreempt"));
+        assert!(!scoped.counts("gpu", "gen"));
+        assert!(!scoped.counts("ccq", "preempt"));
+    }
+
+    #[test]
+    fn test_effective_limits_tightest_first() {
+        let user = AccountJobUsage::new("gen", 0, 0, 0, 0, 1280, 0).with_jobs(0, 500);
+        let center = AccountJobUsage::new("gen", 0, 0, 0, 0, 640, 0);
+        let assocs = [
+            AssocLimits {
+                account: "cca".to_string(),
+                grp_jobs: Some(200),
+                ..Default::default()
+            },
+            AssocLimits {
+                account: "cca".to_string(),
+                user: "alice".to_string(),
+                partition: "gpu".to_string(),
+                max_jobs: Some(1),
+                ..Default::default()
+            },
+        ];
+        let partitions = SlurmPartitions {
+            partitions: Vec::new(),
+            last_update: DateTime::UNIX_EPOCH,
+        };
+
+        let qos = TresInfo {
+            max_wall_minutes: Some(7 * 1440),
+            ..TresInfo::unlimited("gen")
+        };
+
+        let limits = effective_limits(&[user], &[center], &[qos], &partitions, &assocs);
+        let resources: Vec<&str> = limits.iter().map(|l| l.resource).collect();
+        assert_eq!(resources, ["cores", "jobs", "time"]);
+        assert_eq!(limits[2].bounds[0].source, "QOS MaxWall");
+        assert_eq!(limits[0].bounds[0].source, "QOS GrpTRES");
+        assert_eq!(limits[0].bounds[1].value, 1280);
+        // the user's association only covers the gpu partition
+        let jobs: Vec<(&str, u64)> = limits[1]
+            .bounds
+            .iter()
+            .map(|b| (b.source.as_str(), b.value))
+            .collect();
+        assert_eq!(jobs, [("account GrpJobs", 200), ("QOS MaxJobsPU", 500)]);
     }
 }
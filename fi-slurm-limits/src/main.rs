@@ -1,51 +1,297 @@
 pub mod limits;
 
-use clap::Parser;
+use chrono::Local;
+use clap::builder::PossibleValuesParser;
+use clap::{CommandFactory, Parser};
+use clap_complete::Shell;
+use fi_slurm::error::SlurmError;
+use fi_slurm::filter::gather_all_features;
+use fi_slurm::jobs::get_jobs;
+use fi_slurm::nodes::get_nodes;
+use fi_slurm::output::{OutputFormat, ignore_broken_pipe, write_records};
+use fi_slurm::partitions::get_partitions;
+use fi_slurm::site;
+use fi_slurm::theme::Theme;
 use fi_slurm::utils::{SlurmConfig, initialize_slurm};
+use fi_slurm_db::db::DbPool;
+use fi_slurm_db::tres::init_tres_table;
+use std::io::Write;
+use std::process::ExitCode;
+use std::thread;
+use std::time::Duration;
+use thiserror::Error;
 
-use crate::limits::{leaderboard, leaderboard_feature, print_limits};
+use crate::limits::{
+    GroupBy, JsonReport, LeaderboardEntry, LimitsReport, RankBy, Ranking, get_limits,
+    history_leaderboard, leaderboard, leaderboard_feature, limit_rows, parse_since,
+    print_comparison, print_history, print_leaderboard, print_limits,
+};
 
 use users::get_current_username;
 
 /// The main function for the fi-slurm-limits CLI application
 /// Parses the inputs and manages the pipeline for the fi-slurm-limits and leaderboard utilities
-fn main() -> Result<(), String> {
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::from(e.exit_code())
+        }
+    }
+}
+
+/// Errors that end a run of fi-slurm-limits
+#[derive(Error, Debug)]
+enum CliError {
+    #[error(transparent)]
+    Slurm(#[from] SlurmError),
+    #[error("{0}")]
+    Other(String),
+}
+
+impl From<String> for CliError {
+    fn from(message: String) -> Self {
+        CliError::Other(message)
+    }
+}
+
+impl CliError {
+    fn exit_code(&self) -> u8 {
+        match self {
+            CliError::Slurm(e) => e.exit_code(),
+            CliError::Other(_) => 1,
+        }
+    }
+}
+
+fn run() -> Result<(), CliError> {
     let args = Args::parse();
 
+    if let Some(shell) = args.completions {
+        print_completions(shell);
+        return Ok(());
+    }
+    if args.generate_man {
+        print_man_page();
+        return Ok(());
+    }
+
+    let theme = Theme::load(args.theme.as_deref(), args.no_color)?;
+
     initialize_slurm();
-    let _slurm_config = SlurmConfig::load()?;
-    // not clear we need to load config, but let's test that later
-
-    match args.leaderboard {
-        None => {} // do nothing
-        Some(num) => {
-            // number is imputed from default of 20
-            if args.filter.is_empty() {
-                leaderboard(num);
-                return Ok(());
-            } else {
-                println!("\nFiltering on: {:?}", args.filter);
-                leaderboard_feature(num, args.filter);
-                return Ok(());
-            }
+    let slurm_config = SlurmConfig::load()?;
+    // the accounting queries are limited to one cluster: the one asked for, else the one named
+    // in site.conf, else the one this host belongs to
+    match args.cluster {
+        Some(cluster) => {
+            site::set_cluster(cluster);
+        }
+        None => {
+            site::set_local_cluster(slurm_config.cluster_name());
         }
     }
 
-    // getting the user name passed in, if it exists, or else passes in None,
-    // which will cause the print_limits function to get the username from OS
-    let user_name = args.user.unwrap_or_else(|| {
-        get_current_username()
-            .unwrap()
-            .to_string_lossy()
-            .into_owned()
-    });
+    // getting the user names passed in, if any, or else the current user's name from the OS
+    let user_names = if args.user.is_empty() {
+        vec![
+            get_current_username()
+                .unwrap()
+                .to_string_lossy()
+                .into_owned(),
+        ]
+    } else {
+        args.user
+    };
+
+    // which jobs the leaderboards count, and how they rank them
+    let ranking = Ranking {
+        top_n: args.leaderboard.unwrap_or(DEFAULT_TOP_N),
+        by: args.by,
+        group_by: args.group_by,
+        partitions: args.partition,
+        qos: args.qos,
+        by_wckey: args.wckey,
+    };
+    // one slurmdbd connection serves every database query of the run
+    let mut db = DbPool::new();
+    let history = args
+        .since
+        .map(|since| history_leaderboard(&ranking, since, &mut db))
+        .transpose()
+        .map_err(|e| CliError::Other(e.to_string()))?;
+
+    // --json predates --output, and is kept for the web portal
+    let output = if args.json {
+        OutputFormat::Json
+    } else {
+        args.output
+    };
+
+    // the limits and the leaderboard together, for the web portal
+    if output == OutputFormat::Json {
+        let leaderboard = current_leaderboard(&ranking, &args.filter)?;
+        let mut reports = user_limits(&user_names, &mut db)?;
+        let (limits, users) = if reports.len() == 1 {
+            (reports.pop(), None)
+        } else {
+            (None, Some(reports))
+        };
+        let report = JsonReport {
+            limits,
+            users,
+            leaderboard,
+            history,
+        };
+        // plain strings and numbers always serialize
+        let json = serde_json::to_string_pretty(&report).expect("report serializes to JSON");
+        println!("{}", json);
+        return Ok(());
+    }
+
+    // one record per line: the history, the leaderboard or the limits, whichever was asked for
+    if matches!(output, OutputFormat::Csv | OutputFormat::Jsonl) {
+        let out = std::io::stdout().lock();
+        let written = if let Some(history) = history {
+            write_records(output, &history, out)
+        } else if args.leaderboard.is_some() {
+            write_records(output, &current_leaderboard(&ranking, &args.filter)?, out)
+        } else {
+            write_records(
+                output,
+                &limit_rows(&user_limits(&user_names, &mut db)?),
+                out,
+            )
+        };
+        ignore_broken_pipe(written)
+            .map_err(|e| CliError::Other(format!("Failed to write the report: {}", e)))?;
+        return Ok(());
+    }
+
+    // usage over a past window, from slurmdbd, in place of the current leaderboard
+    if let Some(history) = history {
+        print_history(&history);
+        return Ok(());
+    }
+
+    if let Some(seconds) = args.watch {
+        watch_leaderboard(&ranking, &args.filter, seconds);
+    }
+
+    if args.leaderboard.is_some() {
+        if !args.filter.is_empty() {
+            println!("\nFiltering on: {:?}", args.filter);
+        }
+        print_leaderboard(&current_leaderboard(&ranking, &args.filter)?);
+        return Ok(());
+    }
 
-    print_limits(&user_name);
+    let reports = user_limits(&user_names, &mut db)?;
+    if let [report] = reports.as_slice() {
+        print_limits(report, &theme);
+    } else {
+        print_comparison(&reports, &theme);
+    }
     Ok(())
 }
 
-const HELP: &str =
-    "Displays current Slurm resource usage compared to limits. A value of \"-\" indictes no limit.";
+/// The leaderboard of running jobs, over the nodes with any of the features if given
+fn current_leaderboard(
+    ranking: &Ranking,
+    features: &[String],
+) -> Result<Vec<LeaderboardEntry>, SlurmError> {
+    if features.is_empty() {
+        leaderboard(ranking)
+    } else {
+        leaderboard_feature(ranking, features.to_vec())
+    }
+}
+
+/// Redraws the leaderboard every `seconds` until interrupted
+///
+/// A failed refresh, such as when slurmctld is briefly unreachable, is shown in place of the
+/// leaderboard and retried on the next one
+fn watch_leaderboard(ranking: &Ranking, features: &[String], seconds: u64) -> ! {
+    loop {
+        // load before clearing, so the screen isn't left blank while Slurm answers
+        let entries = current_leaderboard(ranking, features);
+        print!("\x1b[2J\x1b[H");
+        println!(
+            "Every {}s, top {} by {:?}: {}\n",
+            seconds,
+            ranking.top_n,
+            ranking.by,
+            Local::now().format("%Y-%m-%d %H:%M:%S")
+        );
+        match entries {
+            Ok(entries) => print_leaderboard(&entries),
+            Err(e) => eprintln!("Error: {}", e),
+        }
+        let _ = std::io::stdout().flush();
+        thread::sleep(Duration::from_secs(seconds));
+    }
+}
+
+/// The limits and usage of each of the users, in the order given
+fn user_limits(user_names: &[String], db: &mut DbPool) -> Result<Vec<LimitsReport>, SlurmError> {
+    let jobs = get_jobs()?;
+    let partitions = get_partitions()?;
+    init_tres_table(db);
+    user_names
+        .iter()
+        .map(|name| get_limits(name, &jobs, &partitions, db))
+        .collect()
+}
+
+/// Writes a man page generated from the command line definition to stdout
+fn print_man_page() {
+    // rendering can only fail on writing to stdout, such as into a closed pipe
+    let _ = clap_mangen::Man::new(Args::command()).render(&mut std::io::stdout());
+}
+
+/// Writes a completion script for the given shell to stdout
+///
+/// If Slurm can be reached, the cluster's node features are offered as completions for
+/// --filter, so the script is best generated on a cluster node
+fn print_completions(shell: Shell) {
+    let mut command = Args::command();
+    let features = cluster_features();
+    if !features.is_empty() {
+        // the command is only used to write the script, so the features needn't outlive it
+        let features: Vec<&'static str> = features
+            .into_iter()
+            .map(|feature| &*feature.leak())
+            .collect();
+        command = command.mut_arg("filter", |arg| {
+            arg.value_parser(PossibleValuesParser::new(features))
+        });
+    }
+    clap_complete::generate(
+        shell,
+        &mut command,
+        env!("CARGO_BIN_NAME"),
+        &mut std::io::stdout(),
+    );
+}
+
+/// The sorted node features of the cluster, or none if Slurm can't be reached
+fn cluster_features() -> Vec<String> {
+    initialize_slurm();
+    let Ok(_slurm_config) = SlurmConfig::load() else {
+        return Vec::new();
+    };
+    let Ok(nodes) = get_nodes() else {
+        return Vec::new();
+    };
+    let mut features: Vec<String> = gather_all_features(&nodes).into_iter().collect();
+    features.sort();
+    features
+}
+
+/// The leaderboard length when none is given
+const DEFAULT_TOP_N: usize = 10;
+
+const HELP: &str = "Displays current Slurm resource usage compared to limits, with a bar per resource of each QOS that turns red above 90% of the limit. A value of \"-\" indictes no limit.";
 
 #[derive(Parser, Debug)]
 #[command(
@@ -55,8 +301,10 @@ const HELP: &str =
     after_long_help = format!("{}\n\n{}", HELP, fi_slurm::AUTHOR_HELP),
 )]
 struct Args {
-    #[arg(help = "The username for which to show limits. Defaults to the current user.")]
-    user: Option<String>,
+    #[arg(
+        help = "The usernames for which to show limits. Defaults to the current user. With several users, prints a table comparing them."
+    )]
+    user: Vec<String>,
 
     #[arg(short, long)]
     #[arg(num_args(0..=1))]
@@ -71,4 +319,99 @@ struct Args {
         help = "For the leaderboard: select feature(s) to filter by. \"icelake\" would only show information for icelake nodes."
     )]
     filter: Vec<String>,
+
+    #[arg(long, value_enum, default_value_t = RankBy::Nodes)]
+    #[arg(
+        help = "For the leaderboard: the resource to rank users by. GPUs and memory count what running jobs were allocated."
+    )]
+    by: RankBy,
+
+    #[arg(long, value_name = "SECONDS", num_args(0..=1), default_missing_value = "30")]
+    #[arg(value_parser = clap::value_parser!(u64).range(1..))]
+    #[arg(requires = "leaderboard", conflicts_with_all = ["json", "output", "since"])]
+    #[arg(
+        help = "For the leaderboard: redraws it in place every SECONDS (default 30) until interrupted, for keeping on a dashboard screen"
+    )]
+    watch: Option<u64>,
+
+    #[arg(long, value_name = "PARTITION", value_delimiter = ',')]
+    #[arg(
+        help = "For the leaderboard: only count jobs in the given partitions. Accepts multiple uses or a comma-separated list."
+    )]
+    partition: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "QOS",
+        value_delimiter = ',',
+        conflicts_with = "since"
+    )]
+    #[arg(
+        help = "For the leaderboard: only count jobs under the given QOS, such as preempt. Accepts multiple uses or a comma-separated list."
+    )]
+    qos: Vec<String>,
+
+    #[arg(long, value_name = "WINDOW", value_parser = parse_since, conflicts_with = "filter")]
+    #[arg(
+        help = "For the leaderboard: ranks by CPU-hours (or GPU-hours with --by gpus) used over the past window, such as 7d, 12h or 2w, from the Slurm database"
+    )]
+    since: Option<chrono::Duration>,
+
+    #[arg(long, value_enum, default_value_t = GroupBy::User)]
+    #[arg(
+        help = "For the leaderboard: whose usage to add up, per user, per Slurm account, or per center"
+    )]
+    #[arg(
+        long_help = "For the leaderboard: whose usage to add up, per user, per Slurm account, or per center. Accounts are mapped to centers by 'account = center' lines in centers.conf next to the binary; accounts not listed there count as centers of their own."
+    )]
+    group_by: GroupBy,
+
+    #[arg(long, requires = "since")]
+    #[arg(
+        help = "For the leaderboard over --since: splits each user, account or center's usage by the WCKey it was charged to"
+    )]
+    wckey: bool,
+
+    #[arg(short = 'M', long, value_name = "CLUSTER")]
+    #[arg(
+        help = "The cluster whose limits and usage to look up in the Slurm database. Defaults to the one named in site.conf, or else to the ClusterName of slurm.conf"
+    )]
+    cluster: Option<String>,
+
+    #[arg(long)]
+    #[arg(help = "Disable colors in output")]
+    no_color: bool,
+
+    #[arg(long, value_name = "NAME|FILE")]
+    #[arg(
+        help = "Colors to use: a preset (default, colorblind) or a palette file, as described in fi-nodes --help"
+    )]
+    theme: Option<String>,
+
+    #[arg(long)]
+    #[arg(
+        help = "Prints the user's limits and usage per QOS, with the leaderboard, as JSON. Limits of 0 mean no limit."
+    )]
+    #[arg(
+        long_help = "Prints the user's limits and usage per QOS, with the leaderboard, as JSON. Limits of 0 mean no limit. The leaderboard holds the top 10 users unless --leaderboard gives another number, and honors --filter."
+    )]
+    json: bool,
+
+    #[arg(short, long, value_enum, default_value_t, value_name = "FORMAT")]
+    #[arg(conflicts_with = "json")]
+    #[arg(help = "How to print the report: a table, or json, csv or jsonl for other programs")]
+    #[arg(
+        long_help = "How to print the report: a table, or json, csv or jsonl for other programs. json is the same as --json. csv and jsonl print one record per line: with --since, each place on the historical leaderboard; with --leaderboard, each place on it; otherwise, each QOS of each user and of their center, with its usage and limits, where a limit of 0 means none."
+    )]
+    output: OutputFormat,
+
+    #[arg(long, value_name = "SHELL", hide = true)]
+    #[arg(
+        help = "Prints a completion script for the given shell, including the cluster's features when Slurm is reachable"
+    )]
+    completions: Option<Shell>,
+
+    #[arg(long, hide = true)]
+    #[arg(help = "Prints a man page in roff format, generated from these options")]
+    generate_man: bool,
 }
@@ -39,3 +39,51 @@ enum bind_show_flags : uint16_t {
     FEDERATION = SHOW_FEDERATION, /* Show federated state information. Shows local info if not in federation */
     FUTURE = SHOW_FUTURE,    /* Show future nodes */
 };
+
+enum bind_partition_flags : uint16_t {
+    PARTITION_FLAG_DEFAULT = PART_FLAG_DEFAULT,               /* Set if default partition */
+    PARTITION_FLAG_HIDDEN = PART_FLAG_HIDDEN,                 /* Set if partition is hidden */
+    PARTITION_FLAG_NO_ROOT = PART_FLAG_NO_ROOT,               /* Set if user root jobs disabled */
+    PARTITION_FLAG_ROOT_ONLY = PART_FLAG_ROOT_ONLY,           /* Set if only root can submit jobs */
+    PARTITION_FLAG_REQ_RESV = PART_FLAG_REQ_RESV,             /* Set if reservation is required */
+    PARTITION_FLAG_LLN = PART_FLAG_LLN,                       /* Set if least loaded node selection is desired */
+    PARTITION_FLAG_EXCLUSIVE_USER = PART_FLAG_EXCLUSIVE_USER, /* Set if nodes allocated exclusively by user */
+    PARTITION_FLAG_PDOI = PART_FLAG_PDOI,                     /* Set if nodes POWER_DOWN on IDLE, after running jobs */
+};
+
+enum bind_reservation_flags : uint64_t {
+    RESERVATION_FLAG_MAINT = RESERVE_FLAG_MAINT,               /* Set MAINT flag */
+    RESERVATION_FLAG_DAILY = RESERVE_FLAG_DAILY,               /* Set DAILY flag */
+    RESERVATION_FLAG_WEEKLY = RESERVE_FLAG_WEEKLY,             /* Set WEEKLY flag */
+    RESERVATION_FLAG_IGN_JOBS = RESERVE_FLAG_IGN_JOBS,         /* Ignore running jobs */
+    RESERVATION_FLAG_ANY_NODES = RESERVE_FLAG_ANY_NODES,       /* Use any compute nodes */
+    RESERVATION_FLAG_STATIC = RESERVE_FLAG_STATIC,             /* Static node allocation */
+    RESERVATION_FLAG_PART_NODES = RESERVE_FLAG_PART_NODES,     /* Use partition nodes only */
+    RESERVATION_FLAG_OVERLAP = RESERVE_FLAG_OVERLAP,           /* Permit to overlap others */
+    RESERVATION_FLAG_SPEC_NODES = RESERVE_FLAG_SPEC_NODES,     /* Contains specific nodes */
+    RESERVATION_FLAG_TIME_FLOAT = RESERVE_FLAG_TIME_FLOAT,     /* Time offset is relative */
+    RESERVATION_FLAG_REPLACE = RESERVE_FLAG_REPLACE,           /* Replace resources as assigned to jobs */
+    RESERVATION_FLAG_ALL_NODES = RESERVE_FLAG_ALL_NODES,       /* Use all compute nodes */
+    RESERVATION_FLAG_PURGE_COMP = RESERVE_FLAG_PURGE_COMP,     /* Purge reservation after last job done */
+    RESERVATION_FLAG_WEEKDAY = RESERVE_FLAG_WEEKDAY,           /* Set WEEKDAY flag */
+    RESERVATION_FLAG_WEEKEND = RESERVE_FLAG_WEEKEND,           /* Set WEEKEND flag */
+    RESERVATION_FLAG_FLEX = RESERVE_FLAG_FLEX,                 /* Set FLEX flag */
+    RESERVATION_FLAG_NO_HOLD_JOBS = RESERVE_FLAG_NO_HOLD_JOBS, /* No hold jobs after end */
+    RESERVATION_FLAG_REPLACE_DOWN = RESERVE_FLAG_REPLACE_DOWN, /* Replace DOWN or DRAINED nodes */
+    RESERVATION_FLAG_MAGNETIC = RESERVE_FLAG_MAGNETIC,         /* Allow jobs to run without requesting the reservation */
+    RESERVATION_FLAG_HOURLY = RESERVE_FLAG_HOURLY,             /* Set HOURLY flag */
+};
+
+enum bind_qos_flags : uint32_t {
+    QOS_FLAGS_PART_MIN_NODE = QOS_FLAG_PART_MIN_NODE,             /* Partition MinNodes doesn't apply */
+    QOS_FLAGS_PART_MAX_NODE = QOS_FLAG_PART_MAX_NODE,             /* Partition MaxNodes doesn't apply */
+    QOS_FLAGS_PART_TIME_LIMIT = QOS_FLAG_PART_TIME_LIMIT,         /* Partition MaxTime doesn't apply */
+    QOS_FLAGS_ENFORCE_USAGE_THRES = QOS_FLAG_ENFORCE_USAGE_THRES, /* Enforce UsageThreshold */
+    QOS_FLAGS_NO_RESERVE = QOS_FLAG_NO_RESERVE,                   /* Don't reserve resources for backfill */
+    QOS_FLAGS_REQ_RESV = QOS_FLAG_REQ_RESV,                       /* Jobs must run in a reservation */
+    QOS_FLAGS_DENY_LIMIT = QOS_FLAG_DENY_LIMIT,                   /* Reject jobs over a limit at submission */
+    QOS_FLAGS_OVER_PART_QOS = QOS_FLAG_OVER_PART_QOS,             /* Job QOS limits override the partition QOS */
+    QOS_FLAGS_NO_DECAY = QOS_FLAG_NO_DECAY,                       /* GrpTRESMins usage doesn't decay */
+    QOS_FLAGS_USAGE_FACTOR_SAFE = QOS_FLAG_USAGE_FACTOR_SAFE,     /* Apply UsageFactor only to safe jobs */
+    QOS_FLAGS_RELATIVE = QOS_FLAG_RELATIVE,                       /* Limits are a percentage of the cluster */
+};
@@ -0,0 +1,33 @@
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use fi_slurm::parser::{compress_hostlist, parse_slurm_hostlist};
+use std::hint::black_box;
+
+/// The hostnames of `count` nodes in racks of 64, with every third node missing, as the
+/// hostlist of a large job on a fragmented cluster is
+fn hostnames(count: usize) -> Vec<String> {
+    (0..count * 3 / 2)
+        .filter(|i| i % 3 != 2)
+        .map(|i| format!("worker{:03}{:02}", i / 64, i % 64))
+        .collect()
+}
+
+fn bench_hostlist(c: &mut Criterion) {
+    let mut group = c.benchmark_group("hostlist");
+    for count in [100, 1_000, 10_000] {
+        let names = hostnames(count);
+        let hostlist = compress_hostlist(&names);
+
+        group.bench_with_input(
+            BenchmarkId::new("parse", count),
+            &hostlist,
+            |b, hostlist| b.iter(|| parse_slurm_hostlist(black_box(hostlist))),
+        );
+        group.bench_with_input(BenchmarkId::new("compress", count), &names, |b, names| {
+            b.iter(|| compress_hostlist(black_box(names)))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_hostlist);
+criterion_main!(benches);
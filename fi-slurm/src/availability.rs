@@ -0,0 +1,255 @@
+use crate::jobs::SlurmJobs;
+use crate::nodes::{Node, NodeState};
+use crate::states::NodeStateFlags;
+use std::collections::HashMap;
+
+/// Helper function to determine if a node is available for new work
+pub fn is_node_available(state: &NodeState) -> bool {
+    match state {
+        NodeState::Idle => true,
+        NodeState::Compound { base, flags } => {
+            if **base == NodeState::Idle {
+                // Node is idle, but check for disqualifying flags
+                !flags.intersects(NodeStateFlags::UNAVAILABLE)
+            } else {
+                false
+            }
+        }
+        _ => false,
+    }
+}
+
+/// Helper function to determine if a node partly available for new work
+pub fn is_node_mixed(state: &NodeState) -> bool {
+    match state {
+        NodeState::Mixed => true,
+        NodeState::Compound { base, flags } => {
+            if **base == NodeState::Mixed {
+                // Node is mixed, but check for disqualifying flags
+                !flags.intersects(NodeStateFlags::UNAVAILABLE)
+            } else {
+                false
+            }
+        }
+        _ => false,
+    }
+}
+
+/// The CPUs allocated on a node, as Slurm accounts for them in the node's allocated TRES, or,
+/// where it doesn't say, the sum of the CPUs each of the node's jobs holds on it
+pub fn allocated_cpus(
+    node: &Node,
+    jobs: &SlurmJobs,
+    node_to_job_map: &HashMap<usize, Vec<u32>>,
+) -> u32 {
+    if let Some(tres) = &node.allocated_tres {
+        return tres.cpus;
+    }
+    node_to_job_map.get(&node.id).map_or(0, |job_ids| {
+        job_ids
+            .iter()
+            .filter_map(|job_id| jobs.jobs.get(job_id))
+            .map(|job| job.cpus_on_node(node.id))
+            .sum()
+    })
+}
+
+/// The memory allocated on a node, in megabytes, as Slurm accounts for it in the node's
+/// allocated TRES, or, where it doesn't say, the sum of the memory each of the node's jobs
+/// holds on it, by their memory per node or per CPU
+pub fn allocated_memory_mb(
+    node: &Node,
+    jobs: &SlurmJobs,
+    node_to_job_map: &HashMap<usize, Vec<u32>>,
+) -> u64 {
+    if let Some(tres) = &node.allocated_tres {
+        return tres.memory_mb;
+    }
+    node_to_job_map.get(&node.id).map_or(0, |job_ids| {
+        job_ids
+            .iter()
+            .filter_map(|job_id| jobs.jobs.get(job_id))
+            .map(|job| job.memory_on_node(node.id))
+            .sum()
+    })
+}
+
+/// The memory of a node available to jobs, less what is reserved for the system
+/// (`MemSpecLimit`), and how much of it jobs hold, in megabytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NodeMemory {
+    pub total_mb: u64,
+    pub allocated_mb: u64,
+}
+
+impl NodeMemory {
+    /// The memory of a node and how much of it its jobs hold, which can't exceed its total
+    pub fn of_node(
+        node: &Node,
+        jobs: &SlurmJobs,
+        node_to_job_map: &HashMap<usize, Vec<u32>>,
+    ) -> Self {
+        let total_mb = node.real_memory.saturating_sub(node.mem_spec_limit);
+        NodeMemory {
+            total_mb,
+            allocated_mb: allocated_memory_mb(node, jobs, node_to_job_map).min(total_mb),
+        }
+    }
+
+    pub fn idle_mb(&self) -> u64 {
+        self.total_mb.saturating_sub(self.allocated_mb)
+    }
+
+    /// The fraction of the node's memory held by jobs, from 0 to 1
+    pub fn utilization(&self) -> f64 {
+        if self.total_mb == 0 {
+            0.0
+        } else {
+            self.allocated_mb as f64 / self.total_mb as f64
+        }
+    }
+}
+
+/// The state of a node with the given number of allocated CPUs, marking partly allocated
+/// nodes as mixed, which Slurm does not do by default
+pub fn derive_node_state(node: &Node, alloc_cpus: u32) -> NodeState {
+    if alloc_cpus > 0 && alloc_cpus < node.cpus as u32 {
+        match &node.state {
+            NodeState::Compound { flags, .. } => NodeState::Compound {
+                base: Box::new(NodeState::Mixed),
+                flags: *flags,
+            },
+            _ => NodeState::Mixed,
+        }
+    } else {
+        // Otherwise, we trust the state reported by Slurm
+        node.state.clone()
+    }
+}
+
+/// The resources of a node that are free for new work, with CPUs and GPUs tracked
+/// independently, so that a node whose GPUs are all allocated still offers its free CPUs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct NodeAvailability {
+    pub idle_cpus: u32,
+    pub idle_gpus: u32,
+    /// Whether the node can take whole-node work
+    pub available: bool,
+    /// Whether the node is usable but partly allocated
+    pub mixed: bool,
+}
+
+impl NodeAvailability {
+    /// Computes the free resources of a node in the given (derived) state
+    ///
+    /// Only available and mixed nodes offer any resources. A GPU can't be used without a CPU
+    /// to drive it, so a node with no free CPUs offers no GPUs either
+    pub fn new(
+        state: &NodeState,
+        (cpus, alloc_cpus): (u32, u32),
+        (gpus, alloc_gpus): (u32, u32),
+    ) -> Self {
+        let available = is_node_available(state);
+        let mixed = is_node_mixed(state);
+        if !available && !mixed {
+            return NodeAvailability::default();
+        }
+
+        let idle_cpus = cpus.saturating_sub(alloc_cpus);
+        let idle_gpus = if idle_cpus == 0 {
+            0
+        } else {
+            gpus.saturating_sub(alloc_gpus)
+        };
+        NodeAvailability {
+            idle_cpus,
+            idle_gpus,
+            available,
+            mixed,
+        }
+    }
+
+    /// Computes the free resources of a node, given the CPUs allocated to jobs on it
+    pub fn of_node(node: &Node, alloc_cpus: u32) -> Self {
+        let (gpus, alloc_gpus) = node.gpu_info.as_ref().map_or((0, 0), |gpu| {
+            (gpu.total_gpus as u32, gpu.allocated_gpus as u32)
+        });
+        Self::new(
+            &derive_node_state(node, alloc_cpus),
+            (node.cpus as u32, alloc_cpus),
+            (gpus, alloc_gpus),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_availability_tracks_cpus_and_gpus_independently() {
+        // all GPUs allocated, but CPUs left for CPU-only work
+        let availability = NodeAvailability::new(&NodeState::Mixed, (64, 8), (4, 4));
+        assert_eq!((availability.idle_cpus, availability.idle_gpus), (56, 0));
+
+        // free GPUs are stranded once every CPU is allocated
+        let availability = NodeAvailability::new(&NodeState::Mixed, (64, 64), (4, 1));
+        assert_eq!((availability.idle_cpus, availability.idle_gpus), (0, 0));
+
+        // a drained node offers nothing
+        let drained = NodeState::Compound {
+            base: Box::new(NodeState::Idle),
+            flags: NodeStateFlags::DRAIN,
+        };
+        let availability = NodeAvailability::new(&drained, (64, 0), (4, 0));
+        assert_eq!(availability, NodeAvailability::default());
+    }
+
+    #[cfg(feature = "synthetic")]
+    #[test]
+    fn test_node_memory() {
+        use crate::jobs::MemoryRequest;
+        use crate::nodes::NodeTres;
+
+        let mut state = crate::synthetic::SyntheticCluster::new(640, 2_000).build();
+        let (&node_id, job_ids) = state
+            .node_to_job_map
+            .iter()
+            .find(|(_, job_ids)| job_ids.len() >= 2)
+            .unwrap();
+        let job_ids = job_ids.clone();
+        for (i, job_id) in job_ids.iter().enumerate() {
+            let job = state.jobs.jobs.get_mut(job_id).unwrap();
+            job.memory_request = if i == 0 {
+                MemoryRequest::PerNode(10_000)
+            } else {
+                MemoryRequest::PerCpu(1_000)
+            };
+        }
+        let per_cpu_cpus: u32 = job_ids[1..]
+            .iter()
+            .map(|id| state.jobs.jobs[id].cpus_on_node(node_id))
+            .sum();
+
+        let node = state
+            .nodes
+            .nodes
+            .iter_mut()
+            .find(|n| n.id == node_id)
+            .unwrap();
+        node.real_memory = 1_000_000;
+        node.mem_spec_limit = 4_000;
+        let memory = NodeMemory::of_node(node, &state.jobs, &state.node_to_job_map);
+        assert_eq!(memory.total_mb, 996_000);
+        assert_eq!(memory.allocated_mb, 10_000 + 1_000 * per_cpu_cpus as u64);
+
+        // what Slurm accounts for takes precedence, and jobs can't hold more than there is
+        node.allocated_tres = Some(NodeTres {
+            memory_mb: 2_000_000,
+            ..Default::default()
+        });
+        let memory = NodeMemory::of_node(node, &state.jobs, &state.node_to_job_map);
+        assert_eq!((memory.allocated_mb, memory.idle_mb()), (996_000, 0));
+    }
+}
@@ -0,0 +1,169 @@
+use crate::error::SlurmError;
+use crate::utils::c_str_to_string;
+use fi_slurm_sys::{
+    burst_buffer_info_msg_t, burst_buffer_info_t, burst_buffer_pool_t,
+    slurm_free_burst_buffer_info_msg, slurm_load_burst_buffer_info,
+};
+
+/// We use this struct to manage the C-allocated memory,
+/// automatically dropping it when it goes out of memory
+pub struct RawSlurmBurstBufferInfo {
+    ptr: *mut burst_buffer_info_msg_t,
+}
+
+impl Drop for RawSlurmBurstBufferInfo {
+    fn drop(&mut self) {
+        if !self.ptr.is_null() {
+            unsafe {
+                slurm_free_burst_buffer_info_msg(self.ptr);
+            }
+            self.ptr = std::ptr::null_mut();
+        }
+    }
+}
+
+impl RawSlurmBurstBufferInfo {
+    /// Loads the state of every burst buffer plugin from the Slurm controller
+    pub fn load() -> Result<Self, SlurmError> {
+        let mut bb_info_msg_ptr: *mut burst_buffer_info_msg_t = std::ptr::null_mut();
+
+        let return_code = unsafe { slurm_load_burst_buffer_info(&mut bb_info_msg_ptr) };
+
+        if return_code != 0 {
+            Err(SlurmError::from_errno(
+                "load burst buffer information from Slurm",
+            ))
+        } else {
+            // a cluster without burst buffers may return no message at all
+            Ok(RawSlurmBurstBufferInfo {
+                ptr: bb_info_msg_ptr,
+            })
+        }
+    }
+
+    pub fn as_slice(&self) -> &[burst_buffer_info_t] {
+        if self.ptr.is_null() {
+            return &[];
+        }
+
+        unsafe {
+            let msg = &*self.ptr;
+            if msg.burst_buffer_array.is_null() {
+                return &[];
+            }
+            std::slice::from_raw_parts(msg.burst_buffer_array, msg.record_count as usize)
+        }
+    }
+
+    /// Consumes the wrapper to transform the raw C data into safe, owned `BurstBuffer`s
+    pub fn into_burst_buffers(self) -> Vec<BurstBuffer> {
+        self.as_slice()
+            .iter()
+            .map(BurstBuffer::from_raw_binding)
+            .collect()
+    }
+}
+
+/// The space of a burst buffer pool, in bytes
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BurstBufferPool {
+    pub name: String,
+    pub total_space: u64,
+    /// The space allocated to jobs' buffers
+    pub used_space: u64,
+    /// The space that can't be allocated, which is the used space and any that is set aside,
+    /// such as for buffers being torn down
+    pub unfree_space: u64,
+    /// The unit space is allocated in
+    pub granularity: u64,
+}
+
+impl BurstBufferPool {
+    /// The space that can still be allocated
+    pub fn free_space(&self) -> u64 {
+        self.total_space.saturating_sub(self.unfree_space)
+    }
+
+    fn from_raw_binding(raw_pool: &burst_buffer_pool_t) -> Self {
+        BurstBufferPool {
+            name: unsafe { c_str_to_string(raw_pool.name) },
+            total_space: raw_pool.total_space,
+            used_space: raw_pool.used_space,
+            unfree_space: raw_pool.unfree_space,
+            granularity: raw_pool.granularity,
+        }
+    }
+}
+
+/// A safe, owned representation of the state of one burst buffer plugin, such as datawarp or
+/// lua
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BurstBuffer {
+    /// The name of the plugin
+    pub name: String,
+    pub default_pool: String,
+    /// The plugin's default pool, holding the space not in any named pool
+    pub default: BurstBufferPool,
+    pub pools: Vec<BurstBufferPool>,
+    /// The buffers allocated to jobs
+    pub buffer_count: u32,
+}
+
+impl BurstBuffer {
+    /// Creates a safe, owned Rust `BurstBuffer` from a raw C `burst_buffer_info_t` struct
+    pub fn from_raw_binding(raw_bb: &burst_buffer_info_t) -> Self {
+        let default_pool = unsafe { c_str_to_string(raw_bb.default_pool) };
+        let pools = if raw_bb.pool_ptr.is_null() {
+            Vec::new()
+        } else {
+            unsafe { std::slice::from_raw_parts(raw_bb.pool_ptr, raw_bb.pool_cnt as usize) }
+                .iter()
+                .map(BurstBufferPool::from_raw_binding)
+                .collect()
+        };
+        BurstBuffer {
+            name: unsafe { c_str_to_string(raw_bb.name) },
+            default: BurstBufferPool {
+                name: default_pool.clone(),
+                total_space: raw_bb.total_space,
+                used_space: raw_bb.used_space,
+                unfree_space: raw_bb.unfree_space,
+                granularity: raw_bb.granularity,
+            },
+            default_pool,
+            pools,
+            buffer_count: raw_bb.buffer_count,
+        }
+    }
+}
+
+/// Fetches the state of every burst buffer plugin from Slurm, which is empty on clusters
+/// without burst buffers
+pub fn get_burst_buffers() -> Result<Vec<BurstBuffer>, SlurmError> {
+    Ok(RawSlurmBurstBufferInfo::load()?.into_burst_buffers())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_free_space() {
+        let pool = BurstBufferPool {
+            name: "ssd".to_string(),
+            total_space: 1_000,
+            used_space: 300,
+            unfree_space: 400,
+            granularity: 100,
+        };
+        assert_eq!(pool.free_space(), 600);
+        // space set aside can briefly exceed the pool while it is resized
+        let shrunk = BurstBufferPool {
+            total_space: 200,
+            ..pool
+        };
+        assert_eq!(shrunk.free_space(), 0);
+    }
+}
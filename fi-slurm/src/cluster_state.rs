@@ -1,33 +1,397 @@
-//use fi_slurm_sys::time_t;
-//
-//use crate::{jobs::{self, RawSlurmJobInfo}, nodes::{self, RawSlurmNodeInfo}};
-//
-//
-//
-//struct ClusterState {
-//    nodes: nodes::SlurmNodes,
-//    jobs: jobs::SlurmJobs,
-//    last_nodes_update_time: time_t,
-//    last_jobs_update_time: time_t,
-//}
-//
-//impl ClusterState {
-//    fn new() -> Self {
-//        let node_info_msg = RawSlurmNodeInfo::load(0)?.into_message();
-//        let job_info_msg = RawSlurmJobInfo::load(0)?.into_message();
-//
-//        // retrieve update and backfill information, parse the node and job slices as normal,
-//        // return the ClusterState object
-//
-//
-//    }
-//
-//    fn update(&mut self) {
-//        let incremental_node_message = RawSlurmNodeInfo::load(self.last_nodes_update_time)?.into_message();
-//        let incremental_job_message = RawSlurmJobInfo::load(self.last_jobs_update_time)?.into_message();
-//
-//        // make an incremental call to nodes and jobs,
-//        // update the cluster state
-//        // perhaps store changes elsewhere for historical comparison?
-//    }
-//}
+use crate::availability::{NodeAvailability, allocated_cpus};
+use crate::error::SlurmError;
+use crate::filter::FeatureExpr;
+use crate::jobs::{
+    JobState, SlurmJobs, build_node_to_job_map, enrich_jobs_with_node_ids, get_jobs,
+};
+use crate::nodes::{Node, NodeState, SlurmNodes, get_nodes};
+use crate::partitions::{SlurmPartitions, get_partitions};
+use crate::states::NodeStateFlags;
+use chrono::{DateTime, Utc};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// Everything the binaries need to know about the cluster at one point in time: its nodes,
+/// jobs and partitions, with the jobs already cross-referenced against the nodes
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ClusterState {
+    pub nodes: SlurmNodes,
+    pub jobs: SlurmJobs,
+    pub partitions: SlurmPartitions,
+    /// Maps each node id to the ids of the jobs running on it
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub node_to_job_map: HashMap<usize, Vec<u32>>,
+    /// When the state was loaded from Slurm
+    pub taken_at: DateTime<Utc>,
+}
+
+impl ClusterState {
+    /// Loads the nodes, jobs and partitions from the Slurm controller and builds the node to
+    /// job map
+    ///
+    /// The jobs are loaded on a separate thread so that the requests to the controller overlap
+    pub fn load() -> Result<Self, SlurmError> {
+        let taken_at = Utc::now();
+        let (nodes, jobs, partitions) = std::thread::scope(|scope| {
+            let jobs_handle = scope.spawn(get_jobs);
+            let nodes = get_nodes();
+            let partitions = get_partitions();
+            let jobs = jobs_handle.join().unwrap_or_else(|_| {
+                Err(SlurmError::InvalidData(
+                    "thread loading job data from Slurm panicked".to_string(),
+                ))
+            });
+            (nodes, jobs, partitions)
+        });
+        let nodes = nodes?;
+        let mut jobs = jobs?;
+        let partitions = partitions?;
+
+        // add the node ids instead of just node hostnames to the jobs, which the node to job
+        // map and the reports rely on
+        enrich_jobs_with_node_ids(&mut jobs, &nodes.name_to_id);
+        let node_to_job_map = build_node_to_job_map(&jobs);
+
+        Ok(ClusterState {
+            nodes,
+            jobs,
+            partitions,
+            node_to_job_map,
+            taken_at,
+        })
+    }
+
+    /// Writes the state to a file as JSON, to be read back with `from_snapshot`
+    #[cfg(feature = "serde")]
+    pub fn save(&self, path: &str) -> Result<(), SlurmError> {
+        let json = serde_json::to_string(self).map_err(|e| SlurmError::Snapshot {
+            path: path.to_string(),
+            message: e.to_string(),
+        })?;
+        std::fs::write(path, json).map_err(|e| SlurmError::Snapshot {
+            path: path.to_string(),
+            message: e.to_string(),
+        })
+    }
+
+    /// Reads a state previously written with `save`, rebuilding the node to job map
+    #[cfg(feature = "serde")]
+    pub fn from_snapshot(path: &str) -> Result<Self, SlurmError> {
+        let json = std::fs::read_to_string(path).map_err(|e| SlurmError::Snapshot {
+            path: path.to_string(),
+            message: e.to_string(),
+        })?;
+        let mut state: ClusterState =
+            serde_json::from_str(&json).map_err(|e| SlurmError::Snapshot {
+                path: path.to_string(),
+                message: e.to_string(),
+            })?;
+        state.node_to_job_map = build_node_to_job_map(&state.jobs);
+        Ok(state)
+    }
+
+    /// Keeps only the nodes which satisfy at least one of the given feature filters, or all of
+    /// them if there are none
+    pub fn retain_features(&mut self, features: &[String]) {
+        if !features.is_empty() {
+            let exprs: Vec<FeatureExpr> = features
+                .iter()
+                .map(|filter| FeatureExpr::from_filter(filter))
+                .collect();
+            self.nodes
+                .nodes
+                .retain(|node| exprs.iter().any(|expr| expr.matches(&node.features, true)));
+        }
+    }
+
+    /// Sums the resources of the nodes per feature, counting idle resources as the tree report
+    /// does: only available nodes, and the unallocated resources of mixed nodes, are idle
+    pub fn feature_availability(&self) -> HashMap<&str, FeatureAvailability> {
+        let mut features: HashMap<&str, FeatureAvailability> = HashMap::new();
+        for node in &self.nodes.nodes {
+            let alloc_cpus = allocated_cpus(node, &self.jobs, &self.node_to_job_map);
+            let availability = NodeAvailability::of_node(node, alloc_cpus);
+            let gpus = node.gpu_info.as_ref().map_or(0, |gpu| gpu.total_gpus);
+            for feature in &node.features {
+                let entry = features.entry(&**feature).or_default();
+                entry.total_nodes += 1;
+                entry.idle_nodes += availability.available as u32;
+                entry.total_cpus += node.cpus as u32;
+                entry.idle_cpus += availability.idle_cpus;
+                entry.total_gpus += gpus;
+                entry.idle_gpus += availability.idle_gpus as u64;
+            }
+        }
+        features
+    }
+
+    /// Compares this state against a later one, listing the nodes and jobs that appeared,
+    /// disappeared or changed state in between, and the availability per feature of both
+    pub fn diff(&self, other: &ClusterState) -> ClusterDiff {
+        let old_nodes: BTreeSet<&str> = self.nodes.nodes.iter().map(|n| n.name.as_str()).collect();
+        let new_nodes: BTreeSet<&str> = other.nodes.nodes.iter().map(|n| n.name.as_str()).collect();
+
+        // node ids count the nodes Slurm reported, including any skipped for having no CPUs,
+        // so they can't index the node list; match the nodes by name instead
+        let old_by_name: HashMap<&str, &Node> = self
+            .nodes
+            .nodes
+            .iter()
+            .map(|node| (node.name.as_str(), node))
+            .collect();
+        let mut node_state_changes: Vec<NodeStateChange> = other
+            .nodes
+            .nodes
+            .iter()
+            .filter_map(|new| {
+                let old = old_by_name.get(new.name.as_str())?;
+                (old.state != new.state).then(|| NodeStateChange {
+                    name: new.name.clone(),
+                    old_state: old.state.clone(),
+                    new_state: new.state.clone(),
+                    reason: new.reason.clone(),
+                })
+            })
+            .collect();
+        node_state_changes.sort_by(|a, b| a.name.cmp(&b.name));
+        let newly_drained = node_state_changes
+            .iter()
+            .filter(|change| is_drained(&change.new_state) && !is_drained(&change.old_state))
+            .cloned()
+            .collect();
+
+        let mut job_state_changes: Vec<JobStateChange> = other
+            .jobs
+            .jobs
+            .values()
+            .filter_map(|new| {
+                let old = self.jobs.jobs.get(&new.job_id)?;
+                (old.job_state != new.job_state).then(|| JobStateChange {
+                    job_id: new.job_id,
+                    old_state: old.job_state.clone(),
+                    new_state: new.job_state.clone(),
+                })
+            })
+            .collect();
+        job_state_changes.sort_by_key(|change| change.job_id);
+
+        let mut new_jobs: Vec<u32> = other
+            .jobs
+            .jobs
+            .keys()
+            .filter(|id| !self.jobs.jobs.contains_key(id))
+            .copied()
+            .collect();
+        new_jobs.sort_unstable();
+        let mut gone_jobs: Vec<u32> = self
+            .jobs
+            .jobs
+            .keys()
+            .filter(|id| !other.jobs.jobs.contains_key(id))
+            .copied()
+            .collect();
+        gone_jobs.sort_unstable();
+
+        let old_features = self.feature_availability();
+        let new_features = other.feature_availability();
+        let features = old_features
+            .keys()
+            .chain(new_features.keys())
+            .map(|&feature| {
+                (
+                    feature.to_string(),
+                    (
+                        old_features.get(feature).copied().unwrap_or_default(),
+                        new_features.get(feature).copied().unwrap_or_default(),
+                    ),
+                )
+            })
+            .collect();
+
+        ClusterDiff {
+            old_taken_at: self.taken_at,
+            new_taken_at: other.taken_at,
+            added_nodes: new_nodes
+                .difference(&old_nodes)
+                .map(|s| s.to_string())
+                .collect(),
+            removed_nodes: old_nodes
+                .difference(&new_nodes)
+                .map(|s| s.to_string())
+                .collect(),
+            node_state_changes,
+            newly_drained,
+            new_jobs,
+            gone_jobs,
+            job_state_changes,
+            features,
+        }
+    }
+}
+
+/// Whether the node is drained or draining, so takes no new work until an admin resumes it
+fn is_drained(state: &NodeState) -> bool {
+    matches!(state, NodeState::Compound { flags, .. } if flags.contains(NodeStateFlags::DRAIN))
+}
+
+/// The resources of all the nodes sharing a feature, and how much of them is idle
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+pub struct FeatureAvailability {
+    pub total_nodes: u32,
+    pub idle_nodes: u32,
+    pub total_cpus: u32,
+    pub idle_cpus: u32,
+    pub total_gpus: u64,
+    pub idle_gpus: u64,
+}
+
+/// A node whose state differs between two cluster states
+#[derive(Debug, Clone, PartialEq)]
+pub struct NodeStateChange {
+    pub name: String,
+    pub old_state: NodeState,
+    pub new_state: NodeState,
+    /// Why the node is in its new state, as an admin gave it
+    pub reason: String,
+}
+
+/// A job whose state differs between two cluster states
+#[derive(Debug, Clone, PartialEq)]
+pub struct JobStateChange {
+    pub job_id: u32,
+    pub old_state: JobState,
+    pub new_state: JobState,
+}
+
+/// The differences between an older and a newer `ClusterState`, as returned by
+/// `ClusterState::diff`. All lists are sorted by node name or job id
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClusterDiff {
+    pub old_taken_at: DateTime<Utc>,
+    pub new_taken_at: DateTime<Utc>,
+    pub added_nodes: Vec<String>,
+    pub removed_nodes: Vec<String>,
+    pub node_state_changes: Vec<NodeStateChange>,
+    /// The state changes that drained a node
+    pub newly_drained: Vec<NodeStateChange>,
+    /// Jobs in the newer state only, whether newly submitted or newly visible
+    pub new_jobs: Vec<u32>,
+    /// Jobs in the older state only, which have since left the controller's job list
+    pub gone_jobs: Vec<u32>,
+    pub job_state_changes: Vec<JobStateChange>,
+    /// Availability per feature before and after, for every feature present in either state
+    pub features: BTreeMap<String, (FeatureAvailability, FeatureAvailability)>,
+}
+
+impl ClusterDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added_nodes.is_empty()
+            && self.removed_nodes.is_empty()
+            && self.node_state_changes.is_empty()
+            && self.new_jobs.is_empty()
+            && self.gone_jobs.is_empty()
+            && self.job_state_changes.is_empty()
+            && self.features.values().all(|(old, new)| old == new)
+    }
+}
+
+#[cfg(all(test, feature = "synthetic"))]
+mod tests {
+    use super::*;
+    use crate::synthetic::SyntheticCluster;
+
+    #[test]
+    fn test_diff() {
+        let old = SyntheticCluster::new(640, 2_000).build();
+        let mut new = old.clone();
+        assert!(old.diff(&new).is_empty());
+
+        // a node drained, one removed and one added under a new name
+        let node = new
+            .nodes
+            .nodes
+            .iter_mut()
+            .find(|node| node.state == NodeState::Idle)
+            .unwrap();
+        node.state = NodeState::Compound {
+            base: Box::new(NodeState::Idle),
+            flags: NodeStateFlags::DRAIN,
+        };
+        node.reason = "bad DIMM".to_string();
+        let drained = node.name.clone();
+        let removed = new.nodes.nodes.pop().unwrap();
+        let mut added = removed.clone();
+        added.name = "added001".to_string();
+        new.nodes.nodes.push(added);
+
+        // a job finished, one was submitted and one started
+        let mut job_ids: Vec<u32> = new.jobs.jobs.keys().copied().collect();
+        job_ids.sort_unstable();
+        let gone = new.jobs.jobs.remove(&job_ids[0]).unwrap();
+        let mut submitted = gone.clone();
+        submitted.job_id = job_ids[job_ids.len() - 1] + 1;
+        new.jobs.jobs.insert(submitted.job_id, submitted);
+        let started = new.jobs.jobs.get_mut(&job_ids[1]).unwrap();
+        let old_job_state = started.job_state.clone();
+        started.job_state = if old_job_state == JobState::Running {
+            JobState::Complete
+        } else {
+            JobState::Running
+        };
+
+        let diff = old.diff(&new);
+        assert!(!diff.is_empty());
+        assert_eq!(diff.added_nodes, vec!["added001".to_string()]);
+        assert_eq!(diff.removed_nodes, vec![removed.name.clone()]);
+        assert_eq!(diff.node_state_changes.len(), 1);
+        assert_eq!(diff.newly_drained[0].name, drained);
+        assert_eq!(diff.newly_drained[0].reason, "bad DIMM");
+        assert_eq!(diff.new_jobs, vec![job_ids[job_ids.len() - 1] + 1]);
+        assert_eq!(diff.gone_jobs, vec![job_ids[0]]);
+        assert_eq!(diff.job_state_changes.len(), 1);
+        assert_eq!(diff.job_state_changes[0].job_id, job_ids[1]);
+        assert_eq!(diff.job_state_changes[0].old_state, old_job_state);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_snapshot_keeps_job_nodes() {
+        // the raw hostlists of the jobs are dropped once resolved, so the nodes of each job
+        // must survive as node ids
+        let state = SyntheticCluster::new(640, 2_000).build();
+        let json = serde_json::to_string(&state).unwrap();
+        let mut loaded: ClusterState = serde_json::from_str(&json).unwrap();
+        loaded.node_to_job_map = build_node_to_job_map(&loaded.jobs);
+
+        // the jobs of a node are listed in the order of the job map, which isn't kept
+        let sorted = |state: &ClusterState| {
+            let mut map = state.node_to_job_map.clone();
+            map.values_mut().for_each(|jobs| jobs.sort_unstable());
+            map
+        };
+        assert!(!state.node_to_job_map.is_empty());
+        assert_eq!(sorted(&loaded), sorted(&state));
+        assert!(state.diff(&loaded).is_empty());
+    }
+
+    #[test]
+    fn test_feature_availability() {
+        let mut state = SyntheticCluster::new(640, 0).build();
+        let totals = |state: &ClusterState| {
+            state
+                .feature_availability()
+                .values()
+                .fold((0, 0), |(nodes, idle), a| {
+                    (nodes + a.total_nodes, idle + a.idle_nodes)
+                })
+        };
+        let (nodes, idle) = totals(&state);
+        assert!(nodes >= 640 && idle > 0);
+
+        // only the nodes of a kind are left, and all of them have that feature
+        state.retain_features(&["gpu".to_string()]);
+        let gpu = state.feature_availability()["gpu"];
+        assert_eq!(gpu.total_nodes as usize, state.nodes.nodes.len());
+        assert!(gpu.total_gpus > 0);
+    }
+}
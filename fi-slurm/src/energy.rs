@@ -1,8 +1,10 @@
+use crate::error::SlurmError;
 use crate::utils::time_t_to_datetime;
 use chrono::{DateTime, Utc};
 use fi_slurm_sys::acct_gather_energy_t;
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[allow(dead_code)]
 pub struct AcctGatherEnergy {
     average_watts: u32, // average power consumption of node, in watts
@@ -22,7 +24,7 @@ impl AcctGatherEnergy {
     /// # Safety
     ///
     /// The caller must ensure that `raw_energy` is a valid, non-null pointer
-    pub fn from_raw_binding(raw_energy: &acct_gather_energy_t) -> Result<Self, String> {
+    pub fn from_raw_binding(raw_energy: &acct_gather_energy_t) -> Result<Self, SlurmError> {
         Ok(AcctGatherEnergy {
             average_watts: raw_energy.ave_watts,
             base_consumed_energy: raw_energy.base_consumed_energy,
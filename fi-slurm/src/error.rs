@@ -0,0 +1,192 @@
+use crate::utils::c_str_to_string;
+use fi_slurm_sys::{slurm_get_errno, slurm_strerror};
+use thiserror::Error;
+
+// Slurm error numbers signalling that the controller could not be reached, from slurm_errno.h
+const SLURM_COMMUNICATIONS_CONNECTION_ERROR: i32 = 1001;
+const SLURM_COMMUNICATIONS_SEND_ERROR: i32 = 1002;
+const SLURM_COMMUNICATIONS_RECEIVE_ERROR: i32 = 1003;
+const SLURM_COMMUNICATIONS_SHUTDOWN_ERROR: i32 = 1004;
+const SLURMCTLD_COMMUNICATIONS_CONNECTION_ERROR: i32 = 1800;
+const SLURMCTLD_COMMUNICATIONS_SEND_ERROR: i32 = 1801;
+const SLURMCTLD_COMMUNICATIONS_RECEIVE_ERROR: i32 = 1802;
+const SLURMCTLD_COMMUNICATIONS_SHUTDOWN_ERROR: i32 = 1803;
+const SLURMCTLD_COMMUNICATIONS_BACKOFF: i32 = 1804;
+const SLURM_PROTOCOL_SOCKET_IMPL_TIMEOUT: i32 = 5004;
+
+/// Errors returned by the fi_slurm API
+#[derive(Error, Debug)]
+pub enum SlurmError {
+    #[error(
+        "Failed to {action}: could not contact the Slurm controller ({message}). Check that slurmctld is running and reachable from this host."
+    )]
+    ControllerUnreachable {
+        action: &'static str,
+        errno: i32,
+        message: String,
+    },
+    #[error(
+        "Failed to load the Slurm configuration: {message}. Check the slurmctld logs or the SLURM_CONF environment variable."
+    )]
+    Config { errno: i32, message: String },
+    #[error("Failed to {action}: {message} (Slurm error {errno})")]
+    Api {
+        action: &'static str,
+        errno: i32,
+        message: String,
+    },
+    #[error("Failed to {action}: Slurm reported success but returned no data")]
+    NullResponse { action: &'static str },
+    #[error("Invalid data from Slurm: {0}")]
+    InvalidData(String),
+    #[error("Failed to read or write cluster snapshot {path}: {message}")]
+    Snapshot { path: String, message: String },
+    #[error(
+        "This build of fi-slurm-utils is for Slurm {release}, with libslurm API {compiled}, but the libslurm loaded has API {running}, whose data it can't read. Rebuild against the headers of the Slurm release installed."
+    )]
+    IncompatibleVersion {
+        release: String,
+        compiled: String,
+        running: String,
+    },
+    #[error(
+        "Unknown Slurm cluster {0}: it is not registered in the Slurm database, or the database can't be reached"
+    )]
+    UnknownCluster(String),
+}
+
+/// Whether the errno signals that the controller could not be reached
+fn is_communication_errno(errno: i32) -> bool {
+    matches!(
+        errno,
+        SLURM_COMMUNICATIONS_CONNECTION_ERROR
+            | SLURM_COMMUNICATIONS_SEND_ERROR
+            | SLURM_COMMUNICATIONS_RECEIVE_ERROR
+            | SLURM_COMMUNICATIONS_SHUTDOWN_ERROR
+            | SLURMCTLD_COMMUNICATIONS_CONNECTION_ERROR
+            | SLURMCTLD_COMMUNICATIONS_SEND_ERROR
+            | SLURMCTLD_COMMUNICATIONS_RECEIVE_ERROR
+            | SLURMCTLD_COMMUNICATIONS_SHUTDOWN_ERROR
+            | SLURMCTLD_COMMUNICATIONS_BACKOFF
+            | SLURM_PROTOCOL_SOCKET_IMPL_TIMEOUT
+    )
+}
+
+/// Reads the errno of the last failed Slurm API call, along with Slurm's description of it
+fn last_error() -> (i32, String) {
+    unsafe {
+        let errno = slurm_get_errno();
+        (errno, c_str_to_string(slurm_strerror(errno)))
+    }
+}
+
+impl SlurmError {
+    /// Builds an error from the errno of the Slurm API call that just failed, where `action`
+    /// describes the call, as in "load node information"
+    pub fn from_errno(action: &'static str) -> Self {
+        let (errno, message) = last_error();
+        if is_communication_errno(errno) {
+            SlurmError::ControllerUnreachable {
+                action,
+                errno,
+                message,
+            }
+        } else {
+            SlurmError::Api {
+                action,
+                errno,
+                message,
+            }
+        }
+    }
+
+    /// Builds a configuration error from the errno of the failed configuration load
+    pub fn config_from_errno() -> Self {
+        let (errno, message) = last_error();
+        SlurmError::Config { errno, message }
+    }
+
+    /// Whether the controller could not be reached, as while it fails over, including when
+    /// loading the configuration from it
+    pub fn is_unreachable(&self) -> bool {
+        match self {
+            SlurmError::ControllerUnreachable { .. } => true,
+            SlurmError::Config { errno, .. } => is_communication_errno(*errno),
+            _ => false,
+        }
+    }
+
+    /// The Slurm errno behind this error, if there is one
+    pub fn errno(&self) -> Option<i32> {
+        match self {
+            SlurmError::ControllerUnreachable { errno, .. }
+            | SlurmError::Config { errno, .. }
+            | SlurmError::Api { errno, .. } => Some(*errno),
+            SlurmError::NullResponse { .. }
+            | SlurmError::InvalidData(_)
+            | SlurmError::Snapshot { .. }
+            | SlurmError::IncompatibleVersion { .. }
+            | SlurmError::UnknownCluster(_) => None,
+        }
+    }
+
+    /// A process exit code for this kind of error, following the BSD sysexits conventions, so
+    /// that scripts can tell an unreachable controller from a misconfiguration
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            SlurmError::ControllerUnreachable { .. } => 69, // EX_UNAVAILABLE
+            SlurmError::Config { .. } => 78,                // EX_CONFIG
+            SlurmError::InvalidData(_) => 65,               // EX_DATAERR
+            SlurmError::Snapshot { .. } => 74,              // EX_IOERR
+            SlurmError::IncompatibleVersion { .. } => 72,   // EX_OSFILE
+            SlurmError::UnknownCluster(_) => 68,            // EX_NOHOST
+            SlurmError::Api { .. } | SlurmError::NullResponse { .. } => 70, // EX_SOFTWARE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exit_codes_are_distinct() {
+        let errors = [
+            SlurmError::ControllerUnreachable {
+                action: "load node information",
+                errno: SLURMCTLD_COMMUNICATIONS_CONNECTION_ERROR,
+                message: String::new(),
+            },
+            SlurmError::Config {
+                errno: 1,
+                message: String::new(),
+            },
+            SlurmError::Api {
+                action: "load node information",
+                errno: 1,
+                message: String::new(),
+            },
+            SlurmError::InvalidData(String::new()),
+            SlurmError::Snapshot {
+                path: String::new(),
+                message: String::new(),
+            },
+            SlurmError::IncompatibleVersion {
+                release: String::new(),
+                compiled: String::new(),
+                running: String::new(),
+            },
+            SlurmError::UnknownCluster(String::new()),
+        ];
+        let codes: std::collections::HashSet<u8> = errors.iter().map(|e| e.exit_code()).collect();
+        assert_eq!(codes.len(), errors.len());
+        assert_eq!(errors[0].errno(), Some(1800));
+        assert!(errors[0].is_unreachable());
+        assert!(!errors[1].is_unreachable());
+        let config_unreachable = SlurmError::Config {
+            errno: SLURM_COMMUNICATIONS_CONNECTION_ERROR,
+            message: String::new(),
+        };
+        assert!(config_unreachable.is_unreachable());
+    }
+}
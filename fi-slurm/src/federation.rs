@@ -0,0 +1,238 @@
+use crate::error::SlurmError;
+use crate::utils::c_str_to_string;
+use fi_slurm_sys::{
+    list_t, slurm_destroy_federation_rec, slurm_list_iterator_create, slurm_list_iterator_destroy,
+    slurm_list_next, slurm_list_peek, slurm_load_federation, slurmdb_cluster_rec_t,
+    slurmdb_federation_rec_t, slurmdb_get_info_cluster,
+};
+use std::ffi::{CString, c_void};
+
+// The states of a cluster in its federation, from slurmdb.h: the low bits hold the base state
+// and the rest hold flags
+const CLUSTER_FED_STATE_BASE: u32 = 0x000f;
+const CLUSTER_FED_STATE_ACTIVE: u32 = 1;
+const CLUSTER_FED_STATE_INACTIVE: u32 = 2;
+const CLUSTER_FED_STATE_DRAIN: u32 = 0x0010;
+const CLUSTER_FED_STATE_REMOVE: u32 = 0x0020;
+
+/// We use this struct to manage the C-allocated memory,
+/// automatically dropping it when it goes out of memory
+pub struct RawSlurmFederation {
+    ptr: *mut slurmdb_federation_rec_t,
+}
+
+impl Drop for RawSlurmFederation {
+    fn drop(&mut self) {
+        if !self.ptr.is_null() {
+            unsafe {
+                slurm_destroy_federation_rec(self.ptr as *mut c_void);
+            }
+            self.ptr = std::ptr::null_mut();
+        }
+    }
+}
+
+impl RawSlurmFederation {
+    /// Loads the federation of the current cluster from the Slurm controller, which is null if
+    /// the cluster isn't in one
+    pub fn load() -> Result<Self, SlurmError> {
+        let mut fed_ptr: *mut c_void = std::ptr::null_mut();
+
+        let return_code = unsafe { slurm_load_federation(&mut fed_ptr) };
+
+        if return_code != 0 {
+            Err(SlurmError::from_errno(
+                "load federation information from Slurm",
+            ))
+        } else {
+            Ok(RawSlurmFederation {
+                ptr: fed_ptr as *mut slurmdb_federation_rec_t,
+            })
+        }
+    }
+
+    /// Consumes the wrapper to transform the raw C data into a safe, owned `Federation`, or
+    /// None if the cluster isn't federated
+    pub fn into_federation(self) -> Option<Federation> {
+        if self.ptr.is_null() {
+            return None;
+        }
+        let raw = unsafe { &*self.ptr };
+        let name = unsafe { c_str_to_string(raw.name) };
+
+        let mut clusters = Vec::new();
+        unsafe {
+            for_each_in_list(raw.cluster_list, |item| {
+                clusters.push(FederationMember::from_raw_binding(
+                    &*(item as *const slurmdb_cluster_rec_t),
+                ));
+            });
+        }
+        clusters.sort_by_key(|cluster| cluster.fed_id);
+
+        Some(Federation { name, clusters })
+    }
+}
+
+/// Calls `f` on each item of a Slurm list, which may be null
+///
+/// # Safety
+///
+/// `list` must be null or a valid Slurm list, which outlives the call
+unsafe fn for_each_in_list(list: *mut list_t, mut f: impl FnMut(*mut c_void)) {
+    if list.is_null() {
+        return;
+    }
+    unsafe {
+        let iter = slurm_list_iterator_create(list);
+        if iter.is_null() {
+            return;
+        }
+        loop {
+            let item = slurm_list_next(iter);
+            if item.is_null() {
+                break;
+            }
+            f(item);
+        }
+        slurm_list_iterator_destroy(iter);
+    }
+}
+
+/// Whether a cluster of a federation takes new jobs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FederationState {
+    Active,
+    /// Running its own jobs, but not taking new ones
+    Draining,
+    Inactive,
+    /// Being removed from the federation once its jobs are done
+    Removing,
+    Unknown,
+}
+
+impl FederationState {
+    fn from_raw(state: u32) -> Self {
+        if state & CLUSTER_FED_STATE_REMOVE != 0 {
+            FederationState::Removing
+        } else if state & CLUSTER_FED_STATE_DRAIN != 0 {
+            FederationState::Draining
+        } else {
+            match state & CLUSTER_FED_STATE_BASE {
+                CLUSTER_FED_STATE_ACTIVE => FederationState::Active,
+                CLUSTER_FED_STATE_INACTIVE => FederationState::Inactive,
+                _ => FederationState::Unknown,
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for FederationState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            FederationState::Active => "ACTIVE",
+            FederationState::Draining => "DRAIN",
+            FederationState::Inactive => "INACTIVE",
+            FederationState::Removing => "REMOVE",
+            FederationState::Unknown => "UNKNOWN",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A cluster of a federation
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FederationMember {
+    pub name: String,
+    /// The cluster's id within the federation, which prefixes the ids of its jobs
+    pub fed_id: u32,
+    pub state: FederationState,
+    pub control_host: String,
+    pub control_port: u32,
+    pub features: Vec<String>,
+}
+
+impl FederationMember {
+    /// Creates a safe, owned Rust `FederationMember` from a raw C `slurmdb_cluster_rec_t`
+    /// struct
+    ///
+    /// # Safety
+    ///
+    /// The feature list of the record must be null or a valid list of C strings
+    pub unsafe fn from_raw_binding(raw_cluster: &slurmdb_cluster_rec_t) -> Self {
+        let mut features = Vec::new();
+        unsafe {
+            for_each_in_list(raw_cluster.fed.feature_list, |item| {
+                features.push(c_str_to_string(item as *const i8));
+            });
+        }
+        FederationMember {
+            name: unsafe { c_str_to_string(raw_cluster.name) },
+            fed_id: raw_cluster.fed.id,
+            state: FederationState::from_raw(raw_cluster.fed.state),
+            control_host: unsafe { c_str_to_string(raw_cluster.control_host) },
+            control_port: raw_cluster.control_port,
+            features,
+        }
+    }
+}
+
+/// A federation of clusters, which share a job id space and may run each other's jobs
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Federation {
+    pub name: String,
+    /// The clusters of the federation, by their id in it
+    pub clusters: Vec<FederationMember>,
+}
+
+/// Fetches the federation of the current cluster, or None if it isn't federated
+pub fn get_federation() -> Result<Option<Federation>, SlurmError> {
+    Ok(RawSlurmFederation::load()?.into_federation())
+}
+
+/// Directs every later call to Slurm to the named cluster instead of the local one, as `-M` does
+/// for the Slurm commands
+///
+/// The cluster is looked up in the Slurm database, which must be reachable. Its record is kept
+/// for the rest of the process, as libslurm holds on to it
+pub fn use_cluster(name: &str) -> Result<(), SlurmError> {
+    let unknown = || SlurmError::UnknownCluster(name.to_string());
+    let c_name = CString::new(name).map_err(|_| unknown())?;
+
+    unsafe {
+        let clusters = slurmdb_get_info_cluster(c_name.as_ptr() as *mut i8);
+        if clusters.is_null() {
+            return Err(unknown());
+        }
+        let cluster = slurm_list_peek(clusters) as *mut slurmdb_cluster_rec_t;
+        if cluster.is_null() {
+            return Err(unknown());
+        }
+        fi_slurm_sys::working_cluster_rec = cluster;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_federation_state() {
+        assert_eq!(FederationState::from_raw(1), FederationState::Active);
+        assert_eq!(FederationState::from_raw(2), FederationState::Inactive);
+        assert_eq!(
+            FederationState::from_raw(1 | 0x10),
+            FederationState::Draining
+        );
+        assert_eq!(
+            FederationState::from_raw(1 | 0x30),
+            FederationState::Removing
+        );
+        assert_eq!(FederationState::from_raw(0), FederationState::Unknown);
+        assert_eq!(FederationState::from_raw(0x11).to_string(), "DRAIN");
+    }
+}
@@ -1,4 +1,9 @@
+#[cfg(feature = "slurm")]
+use crate::jobs::{FilterMethod, SlurmJobs};
+#[cfg(feature = "slurm")]
 use crate::nodes::{Node, SlurmNodes};
+#[cfg(feature = "slurm")]
+use std::collections::HashMap;
 use std::collections::HashSet;
 
 /// Filters a collection of nodes based on a list of required features.
@@ -6,6 +11,9 @@ use std::collections::HashSet;
 /// This function is optimized to be very fast. It avoids cloning node data and
 /// only performs the filtering logic if a filter is provided.
 ///
+/// Each filter is a `FeatureExpr`, so it may be a plain feature name or an expression such
+/// as `icelake&!gpu`. A node is kept if it satisfies any of the filters
+///
 /// # Arguments
 ///
 /// * `all_nodes` - A reference to the complete, unfiltered `SlurmNodes` collection.
@@ -16,6 +24,7 @@ use std::collections::HashSet;
 /// # Returns
 ///
 /// A `Vec` containing borrowed references to the nodes that passed the filter.
+#[cfg(feature = "slurm")]
 pub fn filter_nodes_by_feature<'a>(
     all_nodes: &'a SlurmNodes,
     feature_filter: &[String],
@@ -29,28 +38,213 @@ pub fn filter_nodes_by_feature<'a>(
         all_nodes.nodes.iter().collect()
     } else {
         // --- Filtering Path: Filters were provided ---
-        // Iterate through all nodes and collect references to only those that match.
+        // Parse the filters once, then collect references to only the nodes that match.
+        let exprs: Vec<FeatureExpr> = feature_filter
+            .iter()
+            .map(|filter| FeatureExpr::from_filter(filter))
+            .collect();
         all_nodes
             .nodes
             .iter()
             .filter(|node| {
-                // The `any` closure returns true if the node should be included.
-                feature_filter.iter().any(|required_feat| {
-                    if exact_match {
-                        // Exact matching
-                        node.features.contains(required_feat)
-                    } else {
-                        // Substring matching
-                        node.features
-                            .iter()
-                            .any(|actual_feat| actual_feat.contains(required_feat))
-                    }
-                })
+                exprs
+                    .iter()
+                    .any(|expr| expr.matches(&node.features, exact_match))
             })
             .collect()
     }
 }
 
+/// A node feature constraint, written in the syntax of Slurm's `--constraint` option
+///
+/// Feature names may be combined with `&` (and), `|` (or) and `!` (not), and grouped with
+/// parentheses, as in `icelake&!gpu` or `(genoa|rome)&ib`. `!` binds tightest, then `&`,
+/// then `|`. A plain feature name is the simplest expression
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FeatureExpr {
+    Feature(String),
+    Not(Box<FeatureExpr>),
+    And(Box<FeatureExpr>, Box<FeatureExpr>),
+    Or(Box<FeatureExpr>, Box<FeatureExpr>),
+}
+
+/// Recursive descent parser over the characters of a feature expression
+struct ExprParser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl ExprParser<'_> {
+    fn peek(&mut self) -> Option<char> {
+        let rest = &self.input[self.pos..];
+        let trimmed = rest.trim_start();
+        self.pos += rest.len() - trimmed.len();
+        trimmed.chars().next()
+    }
+
+    fn parse_or(&mut self) -> Result<FeatureExpr, String> {
+        let mut expr = self.parse_and()?;
+        while self.peek() == Some('|') {
+            self.pos += 1;
+            expr = FeatureExpr::Or(Box::new(expr), Box::new(self.parse_and()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_and(&mut self) -> Result<FeatureExpr, String> {
+        let mut expr = self.parse_unary()?;
+        while self.peek() == Some('&') {
+            self.pos += 1;
+            expr = FeatureExpr::And(Box::new(expr), Box::new(self.parse_unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_unary(&mut self) -> Result<FeatureExpr, String> {
+        match self.peek() {
+            Some('!') => {
+                self.pos += 1;
+                Ok(FeatureExpr::Not(Box::new(self.parse_unary()?)))
+            }
+            Some('(') => {
+                self.pos += 1;
+                let expr = self.parse_or()?;
+                if self.peek() != Some(')') {
+                    return Err(format!(
+                        "Missing ')' at position {} of feature expression '{}'",
+                        self.pos + 1,
+                        self.input
+                    ));
+                }
+                self.pos += 1;
+                Ok(expr)
+            }
+            _ => {
+                let rest = &self.input[self.pos..];
+                let end = rest.find(['&', '|', '!', '(', ')']).unwrap_or(rest.len());
+                let name = rest[..end].trim();
+                if name.is_empty() {
+                    return Err(format!(
+                        "Expected a feature name at position {} of feature expression '{}'",
+                        self.pos + 1,
+                        self.input
+                    ));
+                }
+                self.pos += end;
+                Ok(FeatureExpr::Feature(name.to_string()))
+            }
+        }
+    }
+}
+
+impl FeatureExpr {
+    /// Parses a feature expression, such as `icelake&!gpu`, reporting where it is malformed
+    pub fn parse(expr: &str) -> Result<Self, String> {
+        let mut parser = ExprParser {
+            input: expr,
+            pos: 0,
+        };
+        let parsed = parser.parse_or()?;
+        match parser.peek() {
+            None => Ok(parsed),
+            Some(c) => Err(format!(
+                "Unexpected '{}' at position {} of feature expression '{}'",
+                c,
+                parser.pos + 1,
+                expr
+            )),
+        }
+    }
+
+    /// Parses a feature filter given on the command line, treating one that is not a valid
+    /// expression as a plain feature name. Use `parse` first to report mistakes to the user
+    pub fn from_filter(filter: &str) -> Self {
+        Self::parse(filter).unwrap_or_else(|_| FeatureExpr::Feature(filter.to_string()))
+    }
+
+    /// Whether a node with the given features satisfies the expression
+    ///
+    /// With `exact_match`, feature names must match exactly, otherwise a name matches any
+    /// feature containing it
+    pub fn matches<S: AsRef<str>>(&self, features: &[S], exact_match: bool) -> bool {
+        match self {
+            FeatureExpr::Feature(name) => {
+                if exact_match {
+                    features.iter().any(|feature| feature.as_ref() == name)
+                } else {
+                    features
+                        .iter()
+                        .any(|feature| feature.as_ref().contains(name.as_str()))
+                }
+            }
+            FeatureExpr::Not(expr) => !expr.matches(features, exact_match),
+            FeatureExpr::And(a, b) => {
+                a.matches(features, exact_match) && b.matches(features, exact_match)
+            }
+            FeatureExpr::Or(a, b) => {
+                a.matches(features, exact_match) || b.matches(features, exact_match)
+            }
+        }
+    }
+
+    /// The feature names the expression refers to, in the order they appear
+    pub fn feature_names(&self) -> Vec<&str> {
+        match self {
+            FeatureExpr::Feature(name) => vec![name.as_str()],
+            FeatureExpr::Not(expr) => expr.feature_names(),
+            FeatureExpr::And(a, b) | FeatureExpr::Or(a, b) => {
+                let mut names = a.feature_names();
+                names.extend(b.feature_names());
+                names
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for FeatureExpr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+/// Keeps only the nodes currently running a job selected by at least one of the given
+/// filters, such as the jobs of a user or an account
+///
+/// This uses the node to job map, so only running jobs are considered. With no filters, all
+/// nodes are kept
+///
+/// # Arguments
+///
+/// * `nodes` - The nodes to filter, usually already filtered by feature.
+/// * `jobs` - The `SlurmJobs` collection the node to job map refers to.
+/// * `node_to_job_map` - A map from node ids to the ids of the jobs running on them.
+/// * `owners` - Filters selecting the jobs whose nodes are kept, such as
+///   `FilterMethod::UserName` or `FilterMethod::Account`.
+#[cfg(feature = "slurm")]
+pub fn filter_nodes_by_job<'a>(
+    nodes: Vec<&'a Node>,
+    jobs: &SlurmJobs,
+    node_to_job_map: &HashMap<usize, Vec<u32>>,
+    owners: &[FilterMethod],
+) -> Vec<&'a Node> {
+    if owners.is_empty() {
+        return nodes;
+    }
+    nodes
+        .into_iter()
+        .filter(|node| {
+            node_to_job_map.get(&node.id).is_some_and(|job_ids| {
+                job_ids
+                    .iter()
+                    .filter_map(|id| jobs.jobs.get(id))
+                    .any(|job| owners.iter().any(|owner| owner.matches(job)))
+            })
+        })
+        .collect()
+}
+
 /// Gathers a complete set of all unique features available on the cluster.
 ///
 /// This is a relatively expensive operation as it iterates through every feature
@@ -64,12 +258,123 @@ pub fn filter_nodes_by_feature<'a>(
 /// # Returns
 ///
 /// A `HashSet<String>` containing all unique feature names.
+#[cfg(feature = "slurm")]
 pub fn gather_all_features(all_nodes: &SlurmNodes) -> HashSet<String> {
     let mut all_features = HashSet::new();
     for node in all_nodes.nodes.iter() {
         for feature in &node.features {
-            all_features.insert(feature.clone());
+            all_features.insert(feature.to_string());
         }
     }
     all_features
 }
+
+/// The Levenshtein distance between two strings, counted in characters
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, &cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Ranks the known features by how closely they resemble a feature name that matched no
+/// nodes, for "did you mean" suggestions
+///
+/// A feature is suggested if it is within a few edits of the name, or if one contains the
+/// other, as with "h100" and "h100_pcie". At most `limit` suggestions are returned, closest
+/// first
+///
+/// # Arguments
+///
+/// * `name` - The feature name that matched nothing.
+/// * `known_features` - All the features on the cluster, as from `gather_all_features`.
+/// * `limit` - The maximum number of suggestions.
+pub fn suggest_features<'a>(
+    name: &str,
+    known_features: &'a HashSet<String>,
+    limit: usize,
+) -> Vec<&'a str> {
+    let name = name.to_lowercase();
+    let max_distance = (name.chars().count() / 3).max(2);
+    let mut ranked: Vec<(usize, &str)> = known_features
+        .iter()
+        .filter_map(|feature| {
+            let lower = feature.to_lowercase();
+            let distance = edit_distance(&name, &lower);
+            if distance <= max_distance {
+                Some((distance, feature.as_str()))
+            } else if lower.contains(&name) || name.contains(&lower) {
+                // rank containment behind close misspellings
+                Some((max_distance + 1, feature.as_str()))
+            } else {
+                None
+            }
+        })
+        .collect();
+    ranked.sort();
+    ranked
+        .into_iter()
+        .take(limit)
+        .map(|(_, feature)| feature)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn features(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_feature_expressions() {
+        let expr = FeatureExpr::parse("icelake&!gpu").unwrap();
+        assert!(expr.matches(&features(&["icelake", "ib"]), true));
+        assert!(!expr.matches(&features(&["icelake", "gpu"]), true));
+
+        let expr = FeatureExpr::parse("(genoa | rome) & ib").unwrap();
+        assert!(expr.matches(&features(&["rome", "ib"]), true));
+        assert!(!expr.matches(&features(&["rome"]), true));
+
+        // & binds tighter than |
+        let expr = FeatureExpr::parse("a|b&c").unwrap();
+        assert!(expr.matches(&features(&["a"]), true));
+        assert!(!expr.matches(&features(&["b"]), true));
+
+        assert_eq!(
+            FeatureExpr::parse("icelake").unwrap(),
+            FeatureExpr::Feature("icelake".to_string())
+        );
+    }
+
+    #[test]
+    fn test_malformed_feature_expressions() {
+        assert!(FeatureExpr::parse("").is_err());
+        assert!(FeatureExpr::parse("(genoa|rome").is_err());
+        assert!(FeatureExpr::parse("genoa&").is_err());
+        assert!(FeatureExpr::parse("genoa)").is_err());
+    }
+
+    #[test]
+    fn test_suggest_features() {
+        let known: HashSet<String> = ["genoa", "icelake", "skylake", "h100", "h100_pcie", "ib"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert_eq!(suggest_features("icelak", &known, 3), vec!["icelake"]);
+        assert_eq!(suggest_features("Genoa", &known, 3), vec!["genoa"]);
+        assert_eq!(
+            suggest_features("h100", &known, 3),
+            vec!["h100", "h100_pcie"]
+        );
+        assert!(suggest_features("cascadelake", &known, 3).is_empty());
+    }
+}
@@ -0,0 +1,354 @@
+use std::collections::BTreeMap;
+
+/// One generic resource from a Slurm GRES string, such as `gpu:h100:8(S:0-1)`
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GresEntry {
+    /// The kind of resource, such as "gpu" or "shard"
+    pub name: String,
+    /// The model of the resource, such as "h100", if the GRES is typed
+    pub type_: Option<String>,
+    pub count: u64,
+    /// The sockets the resource is attached to, from an `(S:...)` suffix
+    pub sockets: Vec<u32>,
+    /// The indices of the devices in use, from the `(IDX:...)` suffix of `gres_used` strings
+    pub indices: Vec<u32>,
+}
+
+impl GresEntry {
+    pub fn is_gpu(&self) -> bool {
+        self.name == "gpu"
+    }
+
+    /// Whether the entry is a share of a GPU that several jobs can use at once
+    pub fn is_shard(&self) -> bool {
+        self.name == "shard"
+    }
+
+    /// The compute slices of a MIG instance, such as 3 for `gpu:nvidia_a100_3g.20gb`, or `None`
+    /// for whole GPUs and other resources
+    pub fn mig_slices(&self) -> Option<u32> {
+        if !self.is_gpu() {
+            return None;
+        }
+        // the profile is the last part of the type, before any `+me` media extension
+        let profile = self.type_.as_deref()?.rsplit('_').next()?;
+        let profile = profile.split('+').next()?;
+        let (slices, memory) = profile.split_once("g.")?;
+        if !memory.ends_with("gb") || memory[..memory.len() - 2].parse::<u32>().is_err() {
+            return None;
+        }
+        slices.parse().ok()
+    }
+
+    /// The name and type joined as Slurm writes them, such as `gpu:h100`
+    pub fn key(&self) -> String {
+        match &self.type_ {
+            Some(type_) => format!("{}:{}", self.name, type_),
+            None => self.name.clone(),
+        }
+    }
+}
+
+/// Splits a GRES string on the commas between entries, leaving those inside parentheses, as in
+/// `gpu:h100:3(IDX:0,2-3)`, alone
+fn split_entries(gres: &str) -> Vec<&str> {
+    let mut entries = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (i, c) in gres.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                entries.push(&gres[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    entries.push(&gres[start..]);
+    entries
+}
+
+/// Expands a list of numbers and ranges, such as `0,2-3`, ignoring `N/A`
+fn parse_index_list(list: &str) -> Vec<u32> {
+    let mut indices = Vec::new();
+    for part in list.split(',').map(str::trim) {
+        match part.split_once('-') {
+            Some((start, end)) => {
+                if let (Ok(start), Ok(end)) = (start.parse::<u32>(), end.parse::<u32>()) {
+                    indices.extend(start..=end);
+                }
+            }
+            None => {
+                if let Ok(index) = part.parse::<u32>() {
+                    indices.push(index);
+                }
+            }
+        }
+    }
+    indices
+}
+
+/// Parses a GRES count, which may carry a K, M, G, T or P suffix for multiples of 1024
+fn parse_count(count: &str) -> Option<u64> {
+    let digits_end = count
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(count.len());
+    if digits_end == 0 {
+        return None;
+    }
+    let (number, suffix) = count.split_at(digits_end);
+    let multiplier: u64 = match suffix {
+        "" => 1,
+        "K" | "k" => 1 << 10,
+        "M" | "m" => 1 << 20,
+        "G" | "g" => 1 << 30,
+        "T" | "t" => 1 << 40,
+        "P" | "p" => 1 << 50,
+        _ => return None,
+    };
+    number.parse::<u64>().ok().map(|n| n * multiplier)
+}
+
+fn parse_entry(entry: &str) -> Option<GresEntry> {
+    let entry = entry.trim();
+    let (main, metadata) = match entry.split_once('(') {
+        Some((main, rest)) => (main, rest.trim_end_matches(')')),
+        None => (entry, ""),
+    };
+    if main.is_empty() {
+        return None;
+    }
+
+    let mut gres = GresEntry::default();
+    match metadata.split_once(':') {
+        Some(("S", sockets)) => gres.sockets = parse_index_list(sockets),
+        Some(("IDX", indices)) => gres.indices = parse_index_list(indices),
+        _ => {}
+    }
+
+    // name, name:count, name:type or name:type:count, where the type may itself contain colons
+    let mut parts = main.split(':');
+    gres.name = parts.next()?.to_string();
+    let rest: Vec<&str> = parts.collect();
+    match rest.split_last() {
+        None => gres.count = 1,
+        Some((last, type_parts)) => match parse_count(last) {
+            Some(count) => {
+                gres.count = count;
+                if !type_parts.is_empty() {
+                    gres.type_ = Some(type_parts.join(":"));
+                }
+            }
+            None => {
+                gres.count = 1;
+                gres.type_ = Some(rest.join(":"));
+            }
+        },
+    }
+    Some(gres)
+}
+
+/// Parses a Slurm GRES string, such as a node's `gres` or `gres_used`, into its entries
+///
+/// # Examples
+///
+/// * `"gpu:h100:8(S:0-1),shard:32"` -> a typed GPU entry with 8 devices on sockets 0 and 1,
+///   and an untyped shard entry with a count of 32
+/// * `"gpu:a100:3(IDX:0,2-3)"` -> 3 GPUs in use, with indices 0, 2 and 3
+pub fn parse_gres(gres: &str) -> Vec<GresEntry> {
+    if gres.trim().is_empty() || gres == "(null)" {
+        return Vec::new();
+    }
+    split_entries(gres)
+        .into_iter()
+        .filter_map(parse_entry)
+        .collect()
+}
+
+/// Sums the GPUs in a set of GRES entries by model, using "gpu" for untyped GPUs
+pub fn gpu_counts_by_model(entries: &[GresEntry]) -> BTreeMap<String, u64> {
+    let mut counts = BTreeMap::new();
+    for entry in entries.iter().filter(|e| e.is_gpu()) {
+        let model = entry.type_.clone().unwrap_or_else(|| entry.name.clone());
+        *counts.entry(model).or_insert(0) += entry.count;
+    }
+    counts
+}
+
+/// The total number of GPUs in a set of GRES entries
+pub fn total_gpus(entries: &[GresEntry]) -> u64 {
+    entries.iter().filter(|e| e.is_gpu()).map(|e| e.count).sum()
+}
+
+/// The compute slices of a GPU that MIG partitions, as on the A100 and H100
+pub const MIG_SLICES_PER_GPU: u32 = 7;
+
+/// How many of one kind of resource a node has, and how many of those are allocated
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GresCount {
+    pub configured: u64,
+    pub allocated: u64,
+}
+
+impl GresCount {
+    pub fn free(&self) -> u64 {
+        self.configured.saturating_sub(self.allocated)
+    }
+}
+
+/// GPU capacity in whole GPUs, which MIG instances and shards make fractional
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GpuEquivalents {
+    pub configured: f64,
+    pub allocated: f64,
+}
+
+impl GpuEquivalents {
+    pub fn free(&self) -> f64 {
+        (self.configured - self.allocated).max(0.0)
+    }
+}
+
+/// The GPUs of a node, with whole GPUs, MIG instances and shards counted apart
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct GpuShares {
+    pub whole: GresCount,
+    /// MIG instances, whatever their size
+    pub mig: GresCount,
+    /// Shards, which are carved out of the whole GPUs rather than added to them
+    pub shards: GresCount,
+    pub equivalents: GpuEquivalents,
+}
+
+/// Counts the whole GPUs, MIG instances and shards of a node from its `gres` and `gres_used`
+/// entries
+///
+/// A MIG instance counts for its compute slices out of `MIG_SLICES_PER_GPU`. Shards add no GPUs,
+/// and as Slurm doesn't say which GPUs the allocated shards are on, they are taken to be spread
+/// evenly, so that half the shards of a node amount to half its whole GPUs
+pub fn gpu_shares(configured: &[GresEntry], used: &[GresEntry]) -> GpuShares {
+    let mut shares = GpuShares::default();
+    let mut mig_equivalents = GpuEquivalents::default();
+    for (entries, allocated) in [(configured, false), (used, true)] {
+        for entry in entries {
+            let (count, slices) = if entry.is_shard() {
+                (&mut shares.shards, None)
+            } else if let Some(slices) = entry.mig_slices() {
+                (&mut shares.mig, Some(slices))
+            } else if entry.is_gpu() {
+                (&mut shares.whole, None)
+            } else {
+                continue;
+            };
+            let slice_equivalents = slices.map_or(0.0, |slices| {
+                entry.count as f64 * slices as f64 / MIG_SLICES_PER_GPU as f64
+            });
+            if allocated {
+                count.allocated += entry.count;
+                mig_equivalents.allocated += slice_equivalents;
+            } else {
+                count.configured += entry.count;
+                mig_equivalents.configured += slice_equivalents;
+            }
+        }
+    }
+
+    let whole = shares.whole.configured as f64;
+    let shard_equivalents = if shares.shards.configured == 0 {
+        0.0
+    } else {
+        shares.shards.allocated as f64 * whole / shares.shards.configured as f64
+    };
+    shares.equivalents = GpuEquivalents {
+        configured: whole + mig_equivalents.configured,
+        allocated: (shares.whole.allocated as f64 + shard_equivalents).min(whole)
+            + mig_equivalents.allocated,
+    };
+    shares
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_typed_gres_with_sockets_and_shards() {
+        let entries = parse_gres("gpu:h100:8(S:0-1),shard:32");
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].key(), "gpu:h100");
+        assert_eq!(entries[0].count, 8);
+        assert_eq!(entries[0].sockets, vec![0, 1]);
+        assert_eq!(entries[1].name, "shard");
+        assert_eq!(entries[1].type_, None);
+        assert_eq!(entries[1].count, 32);
+    }
+
+    #[test]
+    fn test_gres_used_with_indices() {
+        let entries = parse_gres("gpu:a100-sxm4-40gb:3(IDX:0,2-3),gpu:v100:0(IDX:N/A)");
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].indices, vec![0, 2, 3]);
+        assert_eq!(entries[1].count, 0);
+        assert!(entries[1].indices.is_empty());
+        assert_eq!(total_gpus(&entries), 3);
+    }
+
+    #[test]
+    fn test_untyped_and_suffixed_counts() {
+        let entries = parse_gres("gpu:4,bandwidth:lustre:4G,nic");
+        assert_eq!(entries[0].type_, None);
+        assert_eq!(entries[0].count, 4);
+        assert_eq!(entries[1].count, 4 << 30);
+        assert_eq!(entries[2].count, 1);
+        assert!(parse_gres("").is_empty());
+    }
+
+    #[test]
+    fn test_gpu_counts_by_model() {
+        let entries = parse_gres("gpu:h100:4(S:0),gpu:h100:4(S:1),gpu:l40s:2,shard:16");
+        let counts = gpu_counts_by_model(&entries);
+        assert_eq!(counts.get("h100"), Some(&8));
+        assert_eq!(counts.get("l40s"), Some(&2));
+        assert_eq!(counts.len(), 2);
+    }
+
+    #[test]
+    fn test_mig_slices() {
+        let entries = parse_gres("gpu:nvidia_a100_3g.20gb:2,gpu:1g.10gb+me:1,gpu:h100:4,shard:8");
+        assert_eq!(entries[0].mig_slices(), Some(3));
+        assert_eq!(entries[1].mig_slices(), Some(1));
+        assert_eq!(entries[2].mig_slices(), None);
+        assert_eq!(entries[3].mig_slices(), None);
+    }
+
+    #[test]
+    fn test_gpu_shares() {
+        let configured =
+            parse_gres("gpu:a100:2(S:0),gpu:a100_3g.20gb:2,gpu:a100_1g.10gb:1,shard:16");
+        let used = parse_gres("gpu:a100:1(IDX:0),gpu:a100_3g.20gb:1(IDX:2),shard:4");
+        let shares = gpu_shares(&configured, &used);
+        assert_eq!(
+            shares.whole,
+            GresCount {
+                configured: 2,
+                allocated: 1
+            }
+        );
+        assert_eq!(
+            shares.mig,
+            GresCount {
+                configured: 3,
+                allocated: 1
+            }
+        );
+        assert_eq!(shares.shards.free(), 12);
+        // two whole GPUs and a partitioned one; one whole GPU, half a GPU of shards and 3/7
+        assert!((shares.equivalents.configured - 3.0).abs() < 1e-9);
+        assert!((shares.equivalents.allocated - (1.5 + 3.0 / 7.0)).abs() < 1e-9);
+    }
+}
@@ -1,9 +1,15 @@
-use crate::parser::parse_tres_str;
+use crate::error::SlurmError;
+use crate::gres::{parse_gres, total_gpus};
+use crate::parser::{parse_array_task_str, parse_tres_str};
 use crate::utils::{c_str_to_string, time_t_to_datetime};
 use chrono::{DateTime, Utc};
-use fi_slurm_sys::{job_info, job_info_msg_t, slurm_free_job_info_msg, slurm_load_jobs, time_t};
-use std::collections::HashMap;
+use fi_slurm_sys::{
+    job_info, job_info_msg_t, slurm_free_job_info_msg, slurm_job_cpus_allocated_on_node_id,
+    slurm_job_reason_string, slurm_load_job, slurm_load_jobs, time_t,
+};
+use std::collections::{BTreeMap, HashMap};
 use std::ffi::CStr;
+use std::fmt;
 
 /// We use this struct to manage the C-allocated memory,
 /// automatically dropping it when it goes out of memory
@@ -30,7 +36,7 @@ impl RawSlurmJobInfo {
     /// This is the only function that directly calls the unsafe `slurm_load_jobs`
     /// FFI function. On success, it returns an instance of the safe RAII wrapper,
     /// to be consumed by the .into_slurm_info() method
-    pub fn load(update_time: time_t) -> Result<Self, String> {
+    pub fn load(update_time: time_t) -> Result<Self, SlurmError> {
         let mut job_info_msg_ptr: *mut job_info_msg_t = std::ptr::null_mut();
 
         let show_flags = 2; // just using the SHOW_DETAIL flag
@@ -38,14 +44,41 @@ impl RawSlurmJobInfo {
         let return_code =
             unsafe { slurm_load_jobs(update_time, &mut job_info_msg_ptr, show_flags) };
 
-        if return_code == 0 && !job_info_msg_ptr.is_null() {
+        if return_code != 0 {
+            // Failure: return an error. No struct is created, no memory is leaked
+            Err(SlurmError::from_errno("load job information from Slurm"))
+        } else if job_info_msg_ptr.is_null() {
+            Err(SlurmError::NullResponse {
+                action: "load job information from Slurm",
+            })
+        } else {
             // Success: wrap the raw pointer in our safe struct and return it.
             Ok(Self {
                 ptr: job_info_msg_ptr,
             })
+        }
+    }
+
+    /// Loads a single job from the Slurm controller, or every task of it if it is a job array
+    ///
+    /// Much cheaper than `load` on a busy cluster, since slurmctld only packs the one job
+    pub fn load_job(job_id: u32) -> Result<Self, SlurmError> {
+        let mut job_info_msg_ptr: *mut job_info_msg_t = std::ptr::null_mut();
+
+        let show_flags = 2; // just using the SHOW_DETAIL flag
+
+        let return_code = unsafe { slurm_load_job(&mut job_info_msg_ptr, job_id, show_flags) };
+
+        if return_code != 0 {
+            Err(SlurmError::from_errno("load job information from Slurm"))
+        } else if job_info_msg_ptr.is_null() {
+            Err(SlurmError::NullResponse {
+                action: "load job information from Slurm",
+            })
         } else {
-            // Failure: return an error. No struct is created, no memory is leaked
-            Err("Failed to load job information from Slurm".to_string())
+            Ok(Self {
+                ptr: job_info_msg_ptr,
+            })
         }
     }
 
@@ -87,7 +120,7 @@ impl RawSlurmJobInfo {
     //    }
     //}
     /// Consumes the wrapper to transform the raw C data into a safe, owned `SlurmJobs` collection
-    pub fn into_slurm_jobs(self) -> Result<SlurmJobs, String> {
+    pub fn into_slurm_jobs(self) -> Result<SlurmJobs, SlurmError> {
         let raw_jobs_slice = self.as_slice();
 
         let jobs_map = raw_jobs_slice
@@ -95,7 +128,7 @@ impl RawSlurmJobInfo {
             .try_fold(HashMap::new(), |mut map, raw_job| {
                 let safe_job = Job::from_raw_binding(raw_job)?;
                 map.insert(safe_job.job_id, safe_job);
-                Ok::<HashMap<u32, Job>, String>(map)
+                Ok::<HashMap<u32, Job>, SlurmError>(map)
             })?;
 
         let (last_update, last_backfill) = unsafe {
@@ -119,13 +152,25 @@ impl RawSlurmJobInfo {
 ///
 /// This function is the primary entry point for accessing job data. It handles
 /// all unsafe FFI calls, data conversion, and memory management internally
-pub fn get_jobs() -> Result<SlurmJobs, String> {
+pub fn get_jobs() -> Result<SlurmJobs, SlurmError> {
     // We load the raw C data into memory,
     // convert into safe, Rust-native structs,
     // and then consume the wrapper to drop the original C memory
     RawSlurmJobInfo::load(0)?.into_slurm_jobs()
 }
 
+/// Fetches a single job from Slurm: the job itself, or all of its tasks if it is a job array,
+/// ordered by task id
+pub fn get_job(job_id: u32) -> Result<Vec<Job>, SlurmError> {
+    let mut jobs: Vec<Job> = RawSlurmJobInfo::load_job(job_id)?
+        .as_slice()
+        .iter()
+        .map(Job::from_raw_binding)
+        .collect::<Result<_, _>>()?;
+    jobs.sort_by_key(|job| (job.array_task_id, job.job_id));
+    Ok(jobs)
+}
+
 struct _JobInfoMsg {
     last_backfill: time_t,
     last_update: time_t,
@@ -135,6 +180,7 @@ struct _JobInfoMsg {
 
 /// Represents the state of a Slurm job in a type-safe way
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum JobState {
     Pending,
     Running,
@@ -188,6 +234,52 @@ impl From<u32> for JobState {
     }
 }
 
+impl JobState {
+    /// The number Slurm stores the state as, the inverse of `JobState::from`, or None for a
+    /// state not known to this crate
+    pub fn code(&self) -> Option<u32> {
+        Some(match self {
+            JobState::Pending => 0,
+            JobState::Running => 1,
+            JobState::Suspended => 2,
+            JobState::Complete => 3,
+            JobState::Cancelled => 4,
+            JobState::Failed => 5,
+            JobState::Timeout => 6,
+            JobState::NodeFail => 7,
+            JobState::Preempted => 8,
+            JobState::BootFail => 9,
+            JobState::Deadline => 10,
+            JobState::OutOfMemory => 11,
+            JobState::End => 12,
+            JobState::Unknown(_) => return None,
+        })
+    }
+}
+
+/// Formats the state the way `squeue --long` does, such as "RUNNING" or "NODE_FAIL"
+impl fmt::Display for JobState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            JobState::Pending => "PENDING",
+            JobState::Running => "RUNNING",
+            JobState::Suspended => "SUSPENDED",
+            JobState::Complete => "COMPLETED",
+            JobState::Cancelled => "CANCELLED",
+            JobState::Failed => "FAILED",
+            JobState::Timeout => "TIMEOUT",
+            JobState::NodeFail => "NODE_FAIL",
+            JobState::Preempted => "PREEMPTED",
+            JobState::BootFail => "BOOT_FAIL",
+            JobState::Deadline => "DEADLINE",
+            JobState::OutOfMemory => "OUT_OF_MEMORY",
+            JobState::End => "END",
+            JobState::Unknown(s) => return write!(f, "UNKNOWN({})", s),
+        };
+        f.write_str(name)
+    }
+}
+
 type JobId = u32;
 
 /// A safe, owned, and idiomatic Rust representation of a Slurm job
@@ -196,21 +288,32 @@ type JobId = u32;
 /// raw C `job_info` struct, converted into clean Rust types
 /// We may expand these fields as we go in order to enable more features
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Job {
     // Core Identification
     pub job_id: JobId,
+    /// The id of the array this job belongs to, or 0 if it is not part of an array
     pub array_job_id: u32,
+    /// This job's task in its array. Slurm reports the tasks still pending in an array as a
+    /// single record, whose task id is NO_VAL and whose tasks are listed in `array_task_str`
     pub array_task_id: u32,
+    pub array_task_str: String,
+    /// The limit on simultaneously running tasks of the array, or 0 if there is none
+    pub array_max_tasks: u32,
     pub name: String,
     pub user_id: u32,
     pub user_name: String,
     pub group_id: u32,
     pub partition: String,
     pub account: String,
+    pub qos: String,
 
     // State and Time
     pub job_state: JobState,
     pub state_description: String,
+    /// Why the job is in its current state, such as "Priority" or "Resources" for pending jobs
+    pub state_reason: String,
+    pub priority: u32,
     pub submit_time: DateTime<Utc>,
     pub start_time: DateTime<Utc>,
     pub end_time: DateTime<Utc>,
@@ -223,30 +326,110 @@ pub struct Job {
     pub num_tasks: u32,
     pub raw_hostlist: String,
     pub node_ids: Vec<usize>,
+    /// CPUs allocated on each node of the job, in hostlist order, and parallel to `node_ids` once
+    /// the job is enriched. Empty if Slurm did not report the job's resource layout
+    pub node_cpus: Vec<u32>,
+    /// GPUs allocated on each node of the job, in hostlist order, and parallel to `node_ids` once
+    /// the job is enriched. Empty if Slurm did not report the job's GRES layout
+    pub node_gpus: Vec<u32>,
+    pub memory_request: MemoryRequest,
     pub allocated_gres: HashMap<String, u64>,
     pub gres_total: Option<String>,
 
+    // Requested Resources, which are all that is known of pending jobs
+    pub requested_features: String,
+    pub requested_tres: HashMap<String, u64>,
+    /// The licenses the job asked for, as given to --licenses, such as "matlab:2"
+    pub licenses: String,
+
     // Other Information
     pub work_dir: String,
     pub command: String,
     pub exit_code: u32,
 }
 
+/// The memory a job asked for, in megabytes, either for each of its nodes or for each of its CPUs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MemoryRequest {
+    PerNode(u64),
+    PerCpu(u64),
+    Unknown,
+}
+
+impl MemoryRequest {
+    /// Decodes Slurm's `pn_min_memory`, which sets the `MEM_PER_CPU` bit for per-CPU requests
+    pub fn from_raw(pn_min_memory: u64) -> Self {
+        if pn_min_memory == fi_slurm_sys::NO_VAL64 || pn_min_memory == fi_slurm_sys::INFINITE64 {
+            MemoryRequest::Unknown
+        } else if pn_min_memory & fi_slurm_sys::MEM_PER_CPU != 0 {
+            MemoryRequest::PerCpu(pn_min_memory & !fi_slurm_sys::MEM_PER_CPU)
+        } else {
+            MemoryRequest::PerNode(pn_min_memory)
+        }
+    }
+}
+
+/// Reads the CPUs allocated to a job on each of its nodes from its `job_resrcs` layout, which
+/// Slurm only provides for jobs that hold resources
+fn allocated_cpus_per_node(raw_job: &job_info) -> Vec<u32> {
+    if raw_job.job_resrcs.is_null() {
+        return Vec::new();
+    }
+    let mut node_cpus = Vec::with_capacity(raw_job.num_nodes as usize);
+    for node_index in 0..raw_job.num_nodes as i32 {
+        let cpus = unsafe { slurm_job_cpus_allocated_on_node_id(raw_job.job_resrcs, node_index) };
+        if cpus < 0 {
+            // the layout doesn't cover every node, so fall back to the even split
+            return Vec::new();
+        }
+        node_cpus.push(cpus as u32);
+    }
+    node_cpus
+}
+
+/// Reads the GPUs allocated to a job on each of its nodes from its `gres_detail_str`, which
+/// Slurm fills with one GRES string per node, such as `gpu:a100:2(IDX:0-1)`, for jobs that hold
+/// resources
+fn allocated_gpus_per_node(raw_job: &job_info) -> Vec<u32> {
+    if raw_job.gres_detail_str.is_null() {
+        return Vec::new();
+    }
+    let details: Vec<String> = (0..raw_job.gres_detail_cnt as usize)
+        .map(|i| unsafe { c_str_to_string(*raw_job.gres_detail_str.add(i)) })
+        .collect();
+    gpus_per_node(&details)
+}
+
+/// The GPUs in each of a job's per-node GRES strings
+fn gpus_per_node(details: &[String]) -> Vec<u32> {
+    details
+        .iter()
+        .map(|detail| total_gpus(&parse_gres(detail)) as u32)
+        .collect()
+}
+
 impl Job {
     /// Creates a safe, owned Rust `Job` from a raw C `job_info` struct
-    pub fn from_raw_binding(raw_job: &job_info) -> Result<Self, String> {
+    pub fn from_raw_binding(raw_job: &job_info) -> Result<Self, SlurmError> {
         Ok(Job {
             job_id: raw_job.job_id,
             array_job_id: raw_job.array_job_id,
             array_task_id: raw_job.array_task_id,
+            array_task_str: unsafe { c_str_to_string(raw_job.array_task_str) },
+            array_max_tasks: raw_job.array_max_tasks,
             name: unsafe { c_str_to_string(raw_job.name) },
             user_id: raw_job.user_id,
             user_name: unsafe { c_str_to_string(raw_job.user_name) },
             group_id: raw_job.group_id,
             partition: unsafe { c_str_to_string(raw_job.partition) },
             account: unsafe { c_str_to_string(raw_job.account) },
-            job_state: JobState::from(raw_job.job_state),
+            qos: unsafe { c_str_to_string(raw_job.qos) },
+            // the state flags above JOB_STATE_BASE, such as JOB_REQUEUE, don't change the base state
+            job_state: JobState::from(raw_job.job_state & fi_slurm_sys::JOB_STATE_BASE),
             state_description: unsafe { c_str_to_string(raw_job.state_desc) },
+            state_reason: unsafe { c_str_to_string(slurm_job_reason_string(raw_job.state_reason)) },
+            priority: raw_job.priority,
             submit_time: time_t_to_datetime(raw_job.submit_time),
             start_time: time_t_to_datetime(raw_job.start_time),
             end_time: time_t_to_datetime(raw_job.end_time),
@@ -257,6 +440,9 @@ impl Job {
             num_tasks: raw_job.num_tasks,
             raw_hostlist: unsafe { c_str_to_string(raw_job.nodes) },
             node_ids: Vec::new(),
+            node_cpus: allocated_cpus_per_node(raw_job),
+            node_gpus: allocated_gpus_per_node(raw_job),
+            memory_request: MemoryRequest::from_raw(raw_job.pn_min_memory),
             allocated_gres: unsafe { parse_tres_str(raw_job.tres_alloc_str) },
             gres_total: if !raw_job.gres_total.is_null() {
                 Some(
@@ -267,12 +453,133 @@ impl Job {
             } else {
                 None
             },
+            requested_features: unsafe { c_str_to_string(raw_job.features) },
+            requested_tres: unsafe { parse_tres_str(raw_job.tres_req_str) },
+            licenses: unsafe { c_str_to_string(raw_job.licenses) },
             // like the tres are
             work_dir: unsafe { c_str_to_string(raw_job.work_dir) },
             command: unsafe { c_str_to_string(raw_job.command) },
             exit_code: raw_job.exit_code,
         })
     }
+
+    /// The number of CPUs the job holds on the node with the given id
+    ///
+    /// Uses the exact layout from Slurm where it is known, and otherwise assumes that the job's
+    /// CPUs are split evenly across its nodes
+    pub fn cpus_on_node(&self, node_id: usize) -> u32 {
+        if let Some(position) = self.node_ids.iter().position(|&id| id == node_id)
+            && let Some(&cpus) = self.node_cpus.get(position)
+        {
+            return cpus;
+        }
+        self.num_cpus / self.num_nodes.max(1)
+    }
+
+    /// The number of GPUs the job holds on the node with the given id
+    ///
+    /// Uses the per-node GRES from Slurm where it is known, and otherwise assumes that the job's
+    /// GPUs are split evenly across its nodes
+    pub fn gpus_on_node(&self, node_id: usize) -> u32 {
+        if let Some(position) = self.node_ids.iter().position(|&id| id == node_id)
+            && let Some(&gpus) = self.node_gpus.get(position)
+        {
+            return gpus;
+        }
+        self.allocated_gpus() as u32 / self.num_nodes.max(1)
+    }
+
+    /// Whether the job is part of a job array
+    pub fn is_array(&self) -> bool {
+        self.array_job_id != 0
+    }
+
+    /// This job's task id in its array, if it is a single array task
+    pub fn array_task(&self) -> Option<u32> {
+        (self.is_array() && self.array_task_id != fi_slurm_sys::NO_VAL)
+            .then_some(self.array_task_id)
+    }
+
+    /// The ids of the array tasks this record stands for when it holds an array's pending tasks
+    pub fn pending_array_tasks(&self) -> Vec<u32> {
+        if self.array_task().is_some() || self.array_task_str.is_empty() {
+            return Vec::new();
+        }
+        parse_array_task_str(&self.array_task_str).0
+    }
+
+    /// The number of jobs this record stands for, which is more than one only for the record
+    /// holding the pending tasks of an array
+    pub fn task_count(&self) -> u32 {
+        (self.pending_array_tasks().len() as u32).max(1)
+    }
+
+    /// The job id as `squeue` shows it, such as `1234_5` for an array task, or `1234_[6-10%2]`
+    /// for the pending tasks of an array
+    pub fn display_id(&self) -> String {
+        if !self.is_array() {
+            self.job_id.to_string()
+        } else if let Some(task) = self.array_task() {
+            format!("{}_{}", self.array_job_id, task)
+        } else {
+            format!("{}_[{}]", self.array_job_id, self.array_task_str)
+        }
+    }
+
+    /// The number of GPUs the job asked for, across all of its nodes
+    pub fn requested_gpus(&self) -> u64 {
+        self.requested_tres.get("gres/gpu").copied().unwrap_or(0)
+    }
+
+    /// The number of GPUs allocated to the job, across all of its nodes
+    ///
+    /// Taken from the job's allocated TRES, or from its per-node GRES if the TRES leave GPUs out
+    pub fn allocated_gpus(&self) -> u64 {
+        self.allocated_gres
+            .get("gres/gpu")
+            .copied()
+            .unwrap_or_else(|| self.node_gpus.iter().map(|&gpus| gpus as u64).sum())
+    }
+
+    /// The job's time limit in minutes, or None if it has none
+    pub fn time_limit(&self) -> Option<u32> {
+        (self.time_limit_minutes != fi_slurm_sys::INFINITE
+            && self.time_limit_minutes != fi_slurm_sys::NO_VAL)
+            .then_some(self.time_limit_minutes)
+    }
+
+    /// When a running job will reach its time limit, if it has one
+    pub fn expected_end(&self) -> Option<DateTime<Utc>> {
+        if self.job_state != JobState::Running || self.start_time == DateTime::UNIX_EPOCH {
+            return None;
+        }
+        let time_limit = self.time_limit()?;
+        Some(self.start_time + chrono::Duration::minutes(time_limit.into()))
+    }
+
+    /// When Slurm expects a pending job to start, if the scheduler has estimated it yet
+    pub fn estimated_start(&self) -> Option<DateTime<Utc>> {
+        (self.job_state == JobState::Pending && self.start_time != DateTime::UNIX_EPOCH)
+            .then_some(self.start_time)
+    }
+
+    /// The memory, in megabytes, the job holds across all of its nodes
+    pub fn total_memory_mb(&self) -> u64 {
+        match self.memory_request {
+            MemoryRequest::PerNode(mb) => mb * self.num_nodes as u64,
+            MemoryRequest::PerCpu(mb) => mb * self.num_cpus as u64,
+            MemoryRequest::Unknown => 0,
+        }
+    }
+
+    /// The memory, in megabytes, the job holds on the node with the given id
+    pub fn memory_on_node(&self, node_id: usize) -> u64 {
+        match self.memory_request {
+            MemoryRequest::PerNode(mb) => mb,
+            MemoryRequest::PerCpu(mb) => mb * self.cpus_on_node(node_id) as u64,
+            MemoryRequest::Unknown => 0,
+        }
+    }
 }
 
 pub enum FilterMethod {
@@ -281,10 +588,34 @@ pub enum FilterMethod {
     UserName(String),
     Partition(String),
     Account(String),
+    State(JobState),
+}
+
+impl FilterMethod {
+    /// Whether a job is selected by this filter
+    pub fn matches(&self, job: &Job) -> bool {
+        match self {
+            FilterMethod::JobIds(ids) => ids.contains(&job.job_id),
+            FilterMethod::UserId(id) => *id == job.user_id,
+            FilterMethod::UserName(name) => *name == job.user_name,
+            FilterMethod::Partition(partition) => *partition == job.partition,
+            FilterMethod::Account(account) => *account == job.account,
+            FilterMethod::State(state) => *state == job.job_state,
+        }
+    }
+}
+
+/// The number of pending jobs, and the CPUs they request, for one pending reason
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PendingSummary {
+    pub jobs: usize,
+    pub cpus: u64,
 }
 
 /// A safe, owned collection of Slurm jobs, mapping job ID to the Job object
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SlurmJobs {
     pub jobs: HashMap<u32, Job>,
     /// The timestamp of the last update from the Slurm controller
@@ -297,13 +628,7 @@ impl SlurmJobs {
     pub fn filter_by(mut self, method: FilterMethod) -> Self {
         // go through the hashmap of jobs and figure out which ones either meet the user id
         // or the user name, just pass those back out, no need to change the other fields.
-        self.jobs.retain(|_, job| match &method {
-            FilterMethod::JobIds(ids) => ids.contains(&job.job_id),
-            FilterMethod::UserId(id) => *id == job.user_id,
-            FilterMethod::UserName(name) => *name == job.user_name,
-            FilterMethod::Partition(partition) => *partition == job.partition,
-            FilterMethod::Account(account) => *account == job.account,
-        });
+        self.jobs.retain(|_, job| method.matches(job));
 
         Self {
             jobs: self.jobs,
@@ -311,6 +636,34 @@ impl SlurmJobs {
             last_backfill: self.last_backfill,
         }
     }
+    /// The jobs waiting in the queue
+    pub fn pending(&self) -> impl Iterator<Item = &Job> {
+        self.jobs
+            .values()
+            .filter(|job| job.job_state == JobState::Pending)
+    }
+
+    /// The tasks of the given job array that Slurm reports, whether running or pending
+    pub fn array_tasks(&self, array_job_id: u32) -> impl Iterator<Item = &Job> {
+        self.jobs
+            .values()
+            .filter(move |job| job.is_array() && job.array_job_id == array_job_id)
+    }
+
+    /// Counts the pending jobs, and the CPUs they request, by the reason they are waiting
+    ///
+    /// The pending tasks of an array count as separate jobs
+    pub fn pending_by_reason(&self) -> BTreeMap<String, PendingSummary> {
+        let mut reasons: BTreeMap<String, PendingSummary> = BTreeMap::new();
+        for job in self.pending() {
+            let tasks = job.task_count();
+            let summary = reasons.entry(job.state_reason.clone()).or_default();
+            summary.jobs += tasks as usize;
+            summary.cpus += job.num_cpus as u64 * tasks as u64;
+        }
+        reasons
+    }
+
     pub fn get_resource_use(&self) -> (u32, u32) {
         let (node_use, core_use) = self.jobs.iter().fold((0, 0), |mut acc, (_, job)| {
             acc.0 += job.num_nodes;
@@ -320,6 +673,10 @@ impl SlurmJobs {
 
         (node_use, core_use)
     }
+    /// The memory, in megabytes, held by all the jobs
+    pub fn get_memory_use(&self) -> u64 {
+        self.jobs.values().map(Job::total_memory_mb).sum()
+    }
     pub fn get_gres_total(&self) -> u32 {
         let gres_totals: Vec<Vec<u32>> = self
             .jobs
@@ -367,12 +724,23 @@ pub fn enrich_jobs_with_node_ids(
         // 1. Parse the hostlist string
         let expanded_nodes = crate::parser::parse_slurm_hostlist(&job.raw_hostlist);
 
-        // 2. Convert names to IDs and populate the job's node_ids vector
+        // 2. Convert names to IDs and populate the job's node_ids vector, keeping the per-node
+        //    CPU and GPU counts aligned with it when nodes unknown to the node list are dropped
         //    Pre-allocating capacity is a small extra optimization.
+        let node_cpus = std::mem::take(&mut job.node_cpus);
+        let node_gpus = std::mem::take(&mut job.node_gpus);
+        let has_layout = node_cpus.len() == expanded_nodes.len();
+        let has_gres_layout = node_gpus.len() == expanded_nodes.len();
         job.node_ids.reserve(expanded_nodes.len());
-        for node_name in expanded_nodes {
-            if let Some(&id) = name_to_id.get(&node_name) {
+        for (position, node_name) in expanded_nodes.iter().enumerate() {
+            if let Some(&id) = name_to_id.get(node_name) {
                 job.node_ids.push(id);
+                if has_layout {
+                    job.node_cpus.push(node_cpus[position]);
+                }
+                if has_gres_layout {
+                    job.node_gpus.push(node_gpus[position]);
+                }
             }
         }
 
@@ -382,7 +750,10 @@ pub fn enrich_jobs_with_node_ids(
     }
 }
 
+/// The resources a user or center is using under one QOS, with its limits; a limit of 0 means
+/// there is none
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AccountJobUsage {
     pub account: String,
     pub nodes: u32,
@@ -391,6 +762,12 @@ pub struct AccountJobUsage {
     pub max_nodes: u32,
     pub max_cores: u32,
     pub max_gpus: u32,
+    pub memory_mb: u64,
+    pub max_memory_mb: u64,
+    pub jobs: u32,
+    pub max_jobs: u32,
+    /// Limits on TRES other than the above, such as billing or licenses, by TRES name
+    pub other_limits: BTreeMap<String, u64>,
 }
 
 impl AccountJobUsage {
@@ -412,8 +789,33 @@ impl AccountJobUsage {
             max_nodes,
             max_cores,
             max_gpus,
+            memory_mb: 0,
+            max_memory_mb: 0,
+            jobs: 0,
+            max_jobs: 0,
+            other_limits: BTreeMap::new(),
         }
     }
+
+    /// Sets the memory in use and its limit, both in megabytes
+    pub fn with_memory(mut self, memory_mb: u64, max_memory_mb: u64) -> Self {
+        self.memory_mb = memory_mb;
+        self.max_memory_mb = max_memory_mb;
+        self
+    }
+
+    /// Sets the number of running jobs and its limit
+    pub fn with_jobs(mut self, jobs: u32, max_jobs: u32) -> Self {
+        self.jobs = jobs;
+        self.max_jobs = max_jobs;
+        self
+    }
+
+    /// Sets the limits on TRES other than nodes, cores, GPUs and memory
+    pub fn with_other_limits(mut self, other_limits: BTreeMap<String, u64>) -> Self {
+        self.other_limits = other_limits;
+        self
+    }
     // pub fn print_user(&self, padding: usize) {
     //     println!("{} {} {}/{} {}/{} {}/{}",
     //         self.account,
@@ -556,6 +958,9 @@ pub fn print_accounts(accounts: Vec<AccountJobUsage>) {
 
 /// Builds a map where keys are node hostnames and values are a list of job IDs
 /// running on that node
+///
+/// Running array tasks have job ids of their own, distinct from the id of their array, so each
+/// task appears separately, and a job listed twice on a node is only counted once
 pub fn build_node_to_job_map(slurm_jobs: &SlurmJobs) -> HashMap<usize, Vec<u32>> {
     let mut node_to_job_map: HashMap<usize, Vec<u32>> = HashMap::new();
 
@@ -564,8 +969,46 @@ pub fn build_node_to_job_map(slurm_jobs: &SlurmJobs) -> HashMap<usize, Vec<u32>>
             continue;
         }
         for &node_id in &job.node_ids {
-            node_to_job_map.entry(node_id).or_default().push(job.job_id);
+            let job_ids = node_to_job_map.entry(node_id).or_default();
+            if !job_ids.contains(&job.job_id) {
+                job_ids.push(job.job_id);
+            }
         }
     }
     node_to_job_map
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_request_from_raw() {
+        assert_eq!(MemoryRequest::from_raw(4096), MemoryRequest::PerNode(4096));
+        assert_eq!(
+            MemoryRequest::from_raw(fi_slurm_sys::MEM_PER_CPU | 2048),
+            MemoryRequest::PerCpu(2048)
+        );
+        assert_eq!(
+            MemoryRequest::from_raw(fi_slurm_sys::NO_VAL64),
+            MemoryRequest::Unknown
+        );
+    }
+
+    #[test]
+    fn test_job_state_display() {
+        assert_eq!(JobState::Running.to_string(), "RUNNING");
+        assert_eq!(JobState::NodeFail.to_string(), "NODE_FAIL");
+        assert_eq!(JobState::from(42).to_string(), "UNKNOWN(State code 42)");
+    }
+
+    #[test]
+    fn test_gpus_per_node() {
+        let details = [
+            "gpu:a100:2(IDX:0-1)".to_string(),
+            "gpu:a100:1(IDX:3),shard:4".to_string(),
+            "".to_string(),
+        ];
+        assert_eq!(gpus_per_node(&details), vec![2, 1, 0]);
+    }
+}
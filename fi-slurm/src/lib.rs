@@ -1,15 +1,64 @@
+//! Safe, owned Rust types for the state of a Slurm cluster, and the parsers and filters the
+//! fi-slurm-utils tools share
+//!
+//! The modules that load data from slurmctld, such as `nodes`, `jobs` and `partitions`,
+//! need libslurm and its headers, and are behind the default `slurm` feature. Without it, as
+//! with `default-features = false`, the crate still offers the parsers of hostlists, TRES and
+//! GRES strings ([`parser`], [`gres`]), feature expressions ([`filter`]), the site
+//! configuration ([`site`]), the color themes ([`theme`]) and the output formats
+//! ([`output`]), none of which touch Slurm. The `serde` feature derives `Serialize` and
+//! `Deserialize` for the owned types and writes records in the output formats, the `clap`
+//! feature parses the shared option types on the command line, and the `synthetic` feature adds
+//! generated clusters of any size for benchmarks and tests.
+//!
+//! See API.md for how the loading modules manage Slurm's memory, and README.md for the
+//! versioning of the public API.
+
 #![allow(non_camel_case_types)]
 #![allow(non_upper_case_globals)]
 #![allow(non_snake_case)]
 
 pub const AUTHOR_HELP: &str = "Author: Nicolas Posner (nicolasposner@gmail.com)\nContributors: Lehman Garrison, Dylan Simon, and Alex Chavkin\nRepo: https://github.com/flatironinstitute/fi-slurm-utils";
 
+#[cfg(feature = "slurm")]
+pub mod availability;
+#[cfg(feature = "slurm")]
+pub mod burst_buffer;
+#[cfg(feature = "slurm")]
 pub mod cluster_state;
+#[cfg(feature = "slurm")]
 pub mod energy;
+#[cfg(feature = "slurm")]
+pub mod error;
+#[cfg(feature = "slurm")]
+pub mod federation;
 pub mod filter;
+pub mod gres;
+#[cfg(feature = "slurm")]
 pub mod jobs;
+#[cfg(feature = "slurm")]
+pub mod licenses;
+#[cfg(feature = "slurm")]
 pub mod nodes;
+pub mod output;
 pub mod parser;
+#[cfg(feature = "slurm")]
+pub mod partitions;
+#[cfg(feature = "slurm")]
+pub mod reservations;
 pub mod site;
+#[cfg(feature = "slurm")]
+pub mod source;
+#[cfg(feature = "slurm")]
 pub mod states;
+#[cfg(feature = "slurm")]
+pub mod statistics;
+#[cfg(feature = "synthetic")]
+pub mod synthetic;
+pub mod theme;
+#[cfg(feature = "slurm")]
+pub mod topology;
+#[cfg(feature = "slurm")]
 pub mod utils;
+#[cfg(feature = "slurm")]
+pub mod version;
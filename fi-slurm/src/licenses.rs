@@ -0,0 +1,157 @@
+use crate::error::SlurmError;
+use crate::utils::{c_str_to_string, time_t_to_datetime};
+use chrono::{DateTime, Utc};
+use fi_slurm_sys::{
+    license_info_msg_t, slurm_free_license_info_msg, slurm_license_info_t, slurm_load_licenses,
+    time_t,
+};
+
+/// We use this struct to manage the C-allocated memory,
+/// automatically dropping it when it goes out of memory
+pub struct RawSlurmLicenseInfo {
+    ptr: *mut license_info_msg_t,
+}
+
+impl Drop for RawSlurmLicenseInfo {
+    fn drop(&mut self) {
+        if !self.ptr.is_null() {
+            unsafe {
+                slurm_free_license_info_msg(self.ptr);
+            }
+            self.ptr = std::ptr::null_mut();
+        }
+    }
+}
+
+impl RawSlurmLicenseInfo {
+    /// Loads all license information from the Slurm controller
+    pub fn load(update_time: time_t) -> Result<Self, SlurmError> {
+        let mut license_info_msg_ptr: *mut license_info_msg_t = std::ptr::null_mut();
+
+        let return_code = unsafe { slurm_load_licenses(update_time, &mut license_info_msg_ptr, 0) };
+
+        if return_code != 0 {
+            Err(SlurmError::from_errno(
+                "load license information from Slurm",
+            ))
+        } else if license_info_msg_ptr.is_null() {
+            Err(SlurmError::NullResponse {
+                action: "load license information from Slurm",
+            })
+        } else {
+            Ok(RawSlurmLicenseInfo {
+                ptr: license_info_msg_ptr,
+            })
+        }
+    }
+
+    pub fn as_slice(&self) -> &[slurm_license_info_t] {
+        if self.ptr.is_null() {
+            return &[];
+        }
+
+        unsafe {
+            let msg = &*self.ptr;
+            if msg.lic_array.is_null() {
+                return &[];
+            }
+            std::slice::from_raw_parts(msg.lic_array, msg.num_lic as usize)
+        }
+    }
+
+    /// Consumes the wrapper to transform the raw C data into a safe, owned `SlurmLicenses`
+    /// collection
+    pub fn into_slurm_licenses(self) -> SlurmLicenses {
+        let licenses = self
+            .as_slice()
+            .iter()
+            .map(License::from_raw_binding)
+            .collect();
+
+        let last_update = time_t_to_datetime(unsafe { (*self.ptr).last_update });
+
+        SlurmLicenses {
+            licenses,
+            last_update,
+        }
+    }
+}
+
+/// A safe, owned representation of a Slurm license, as configured with `Licenses=` in
+/// slurm.conf or, for remote licenses, in the Slurm database
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct License {
+    pub name: String,
+    pub total: u32,
+    pub in_use: u32,
+    /// The licenses neither in use nor held by reservations
+    pub available: u32,
+    /// The licenses held by reservations
+    pub reserved: u32,
+    /// Whether the license is tracked in the Slurm database rather than slurm.conf
+    pub remote: bool,
+}
+
+impl License {
+    /// Creates a safe, owned Rust `License` from a raw C `slurm_license_info_t` struct
+    pub fn from_raw_binding(raw_license: &slurm_license_info_t) -> Self {
+        License {
+            name: unsafe { c_str_to_string(raw_license.name) },
+            total: raw_license.total,
+            in_use: raw_license.in_use,
+            available: raw_license.available,
+            reserved: raw_license.reserved,
+            remote: raw_license.remote != 0,
+        }
+    }
+}
+
+/// The collection of licenses returned by `get_licenses`
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SlurmLicenses {
+    pub licenses: Vec<License>,
+    pub last_update: DateTime<Utc>,
+}
+
+/// Fetches all licenses from Slurm and returns them as a safe,
+/// owned Rust data structure
+pub fn get_licenses() -> Result<SlurmLicenses, SlurmError> {
+    Ok(RawSlurmLicenseInfo::load(0)?.into_slurm_licenses())
+}
+
+/// Parses the licenses a job asks for, as in `--licenses=matlab:2,comsol@db`, into the name
+/// and count of each
+///
+/// Slurm accepts both `name:count` and `name*count`, and a license without a count means one.
+/// Alternatives separated by `|` are not expanded, so each is listed as if asked for
+pub fn parse_license_request(request: &str) -> Vec<(String, u32)> {
+    request
+        .split([',', '|'])
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(|item| match item.split_once([':', '*']) {
+            Some((name, count)) => (name.to_string(), count.trim().parse().unwrap_or(1)),
+            None => (item.to_string(), 1),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_license_request() {
+        assert_eq!(
+            parse_license_request("matlab:2,comsol@db,ansys*4"),
+            vec![
+                ("matlab".to_string(), 2),
+                ("comsol@db".to_string(), 1),
+                ("ansys".to_string(), 4)
+            ]
+        );
+        assert!(parse_license_request("").is_empty());
+    }
+}
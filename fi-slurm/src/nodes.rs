@@ -1,11 +1,22 @@
 use crate::energy::AcctGatherEnergy;
+use crate::error::SlurmError;
+use crate::gres::{GpuEquivalents, GresCount, gpu_shares, parse_gres};
+use crate::parser::parse_tres_str;
 use crate::states::{NodeStateFlags, ShowFlags};
 use crate::utils::{c_str_to_string, time_t_to_datetime};
 use chrono::{DateTime, Utc};
 use fi_slurm_sys::{
-    node_info, node_info_msg_t, node_info_t, slurm_free_node_info_msg, slurm_load_node, time_t,
+    node_info, node_info_msg_t, node_info_t, node_states_NODE_STATE_ALLOCATED,
+    select_nodedata_type, select_nodedata_type_SELECT_NODEDATA_MEM_ALLOC,
+    select_nodedata_type_SELECT_NODEDATA_SUBCNT, slurm_free_node_info_msg,
+    slurm_get_select_nodeinfo, slurm_load_node, time_t,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::{CStr, c_void},
+    fmt,
+    sync::Arc,
 };
-use std::{collections::HashMap, ffi::CStr, fmt};
 
 pub struct RawSlurmNodeInfo {
     ptr: *mut node_info_msg_t,
@@ -24,7 +35,7 @@ impl Drop for RawSlurmNodeInfo {
 }
 
 impl RawSlurmNodeInfo {
-    pub fn load(update_time: time_t) -> Result<Self, String> {
+    pub fn load(update_time: time_t) -> Result<Self, SlurmError> {
         let mut node_info_msg_ptr: *mut node_info_msg_t = std::ptr::null_mut();
 
         // let update_time = 0; // defaulting to time 0 to get all information
@@ -34,8 +45,12 @@ impl RawSlurmNodeInfo {
         let return_code =
             unsafe { slurm_load_node(update_time, &mut node_info_msg_ptr, show_flags.bits()) };
 
-        if return_code != 0 || node_info_msg_ptr.is_null() {
-            Err("Failed to load node information from Slurm".to_string())
+        if return_code != 0 {
+            Err(SlurmError::from_errno("load node information from Slurm"))
+        } else if node_info_msg_ptr.is_null() {
+            Err(SlurmError::NullResponse {
+                action: "load node information from Slurm",
+            })
         } else {
             Ok(RawSlurmNodeInfo {
                 ptr: node_info_msg_ptr,
@@ -82,7 +97,7 @@ impl RawSlurmNodeInfo {
 
     // In your slurm_data.rs or equivalent file
 
-    pub fn into_slurm_nodes(self) -> Result<SlurmNodes, String> {
+    pub fn into_slurm_nodes(self) -> Result<SlurmNodes, SlurmError> {
         let raw_nodes_slice = self.as_slice();
 
         let num_nodes = raw_nodes_slice.len();
@@ -90,8 +105,11 @@ impl RawSlurmNodeInfo {
         let mut name_to_id_map = HashMap::with_capacity(num_nodes);
 
         let mut skip_count = 0;
+        let mut interner = FeatureInterner::default();
         for (id, raw_node) in raw_nodes_slice.iter().enumerate() {
-            let safe_node = Node::from_raw_binding(id, raw_node)?;
+            let mut safe_node = Node::from_raw_binding(id, raw_node)?;
+            interner.intern_all(&mut safe_node.features);
+            interner.intern_all(&mut safe_node.active_features);
 
             // Misconfigured node?
             if safe_node.cpus == 0 {
@@ -116,13 +134,33 @@ impl RawSlurmNodeInfo {
     }
 }
 
+/// Shares one allocation between all the copies of a feature name, which otherwise are
+/// repeated on every node that has the feature
+#[derive(Default)]
+struct FeatureInterner {
+    features: HashSet<Arc<str>>,
+}
+
+impl FeatureInterner {
+    fn intern_all(&mut self, features: &mut [Arc<str>]) {
+        for feature in features {
+            match self.features.get(feature) {
+                Some(shared) => *feature = Arc::clone(shared),
+                None => {
+                    self.features.insert(Arc::clone(feature));
+                }
+            }
+        }
+    }
+}
+
 struct _NodeInfoMsg {
     last_update: time_t,
     record_count: u32,
     node_array: *mut node_info,
 }
 
-pub fn get_nodes() -> Result<SlurmNodes, String> {
+pub fn get_nodes() -> Result<SlurmNodes, SlurmError> {
     // We load the raw C data into memory,
     // convert into safe, Rust-native structs,
     // and then consume the wrapper to drop the original C memory
@@ -130,6 +168,7 @@ pub fn get_nodes() -> Result<SlurmNodes, String> {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum NodeState {
     Allocated,
     Down,
@@ -140,7 +179,7 @@ pub enum NodeState {
     Unknown(String),
     Compound {
         base: Box<NodeState>,
-        flags: Vec<String>,
+        flags: NodeStateFlags,
     },
     End,
 }
@@ -169,23 +208,7 @@ impl From<u32> for NodeState {
             _ => NodeState::Unknown(format!("BASE({})", base_state_num)),
         };
 
-        let flags_struct = NodeStateFlags::from_bits_truncate(state_num);
-
-        let flags: Vec<String> = flags_struct
-            .iter()
-            .map(|flag| {
-                // Get the verbose debug string, e.g., "NodeStateFlags(DRAIN | MAINT)"
-                let debug_str = format!("{:?}", flag);
-                // Extract just the name, e.g., "DRAIN"
-                if let Some(start) = debug_str.find('(')
-                    && let Some(end) = debug_str.rfind(')')
-                {
-                    return debug_str[start + 1..end].to_string();
-                }
-                // Fallback for simple flags that might not have parentheses
-                debug_str
-            })
-            .collect();
+        let flags = NodeStateFlags::from_bits_truncate(state_num);
 
         if flags.is_empty() {
             // If no recognized flags are set, just return the base state
@@ -204,12 +227,7 @@ impl fmt::Display for NodeState {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             NodeState::Compound { base, flags } => {
-                let flags_str = flags
-                    .iter()
-                    .map(|flag| flag.to_uppercase())
-                    .collect::<Vec<String>>()
-                    .join("+");
-                write!(f, "{}+{}", base.to_string().to_uppercase(), flags_str)
+                write!(f, "{}+{}", base.to_string().to_uppercase(), flags)
             }
             NodeState::Unknown(s) => write!(f, "UNKNOWN({})", s),
             _ => write!(f, "{:?}", self),
@@ -218,70 +236,138 @@ impl fmt::Display for NodeState {
 }
 
 /// Represents the GPU GRES of a node, assuming that a given node has only one kind of GPU
-#[derive(Clone, Debug)]
+///
+/// The GPU counts are of whole GPUs, while MIG instances and shards are counted apart, and all
+/// of them together in GPU-equivalents
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct GpuInfo {
     pub name: String,
     pub total_gpus: u64,
     pub allocated_gpus: u64,
+    /// The MIG instances, whatever their size, rather than the compute slices they take up
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub mig_instances: GresCount,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub shards: GresCount,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub equivalents: GpuEquivalents,
 }
 
-/// Parses gres and gres_used strings to create an optional GpuInfo struct
-fn create_gpu_info(gres_str_ptr: *const i8, gres_used_ptr: *const i8) -> Option<GpuInfo> {
-    /// A robust, local helper function to parse GRES strings
-    fn parse_local_gres(raw_ptr: *const i8) -> HashMap<String, u64> {
-        if raw_ptr.is_null() {
-            return HashMap::new();
+impl GpuInfo {
+    /// The configured and allocated GPUs in whole GPU-equivalents, so that a node with only
+    /// MIG instances still has GPUs, with what is allocated rounded up, as a partly used GPU
+    /// can't take a job asking for a whole one
+    ///
+    /// Falls back to the whole GPUs for states saved before equivalents were counted
+    pub fn whole_equivalents(&self) -> (u32, u32) {
+        let GpuEquivalents {
+            configured,
+            allocated,
+        } = self.equivalents;
+        if configured == 0.0 {
+            return (self.total_gpus as u32, self.allocated_gpus as u32);
         }
-        let gres_str = unsafe { CStr::from_ptr(raw_ptr) }.to_string_lossy();
-
-        gres_str
-            .split(',')
-            .filter_map(|entry| {
-                // First, strip off any parenthesized metadata like (IDX:...)
-                let main_part = entry.split('(').next().unwrap_or(entry).trim();
-
-                // Now, split the remaining "name:count" part.
-                if let Some((key, count_str)) = main_part.rsplit_once(':') {
-                    if let Ok(value) = count_str.parse::<u64>() {
-                        Some((key.to_string(), value))
-                    } else {
-                        None
-                    }
-                } else {
-                    None
-                }
-            })
-            .collect()
+        let configured = configured.round() as u32;
+        (configured, (allocated.ceil() as u32).min(configured))
     }
+}
 
-    let configured_map = parse_local_gres(gres_str_ptr);
-    let allocated_map = parse_local_gres(gres_used_ptr);
-
-    // Find the first (and likely only) GRES key that represents a GPU
-    let gpu_key = configured_map
-        .keys()
-        .find(|key| key.starts_with("gpu"))
-        .cloned()?; // Clone the key so we can use it for lookups
+/// Parses gres and gres_used strings to create an optional GpuInfo struct
+///
+/// The name is that of the first whole GPU GRES, such as `gpu:h100`, or else of the first MIG
+/// slice, while the counts include every GPU on the node
+fn create_gpu_info(gres_str_ptr: *const i8, gres_used_ptr: *const i8) -> Option<GpuInfo> {
+    let configured = parse_gres(&unsafe { c_str_to_string(gres_str_ptr) });
+    let used = parse_gres(&unsafe { c_str_to_string(gres_used_ptr) });
 
-    let total_gpus = *configured_map.get(&gpu_key).unwrap_or(&0);
-    let allocated_gpus = *allocated_map.get(&gpu_key).unwrap_or(&0);
+    let name = configured
+        .iter()
+        .filter(|entry| entry.is_gpu())
+        .min_by_key(|entry| entry.mig_slices().is_some())?
+        .key();
+    let shares = gpu_shares(&configured, &used);
 
     // Only create a GpuInfo struct if there are actually GPUs configured
-    if total_gpus > 0 {
+    if shares.whole.configured > 0 || shares.mig.configured > 0 {
         Some(GpuInfo {
-            name: gpu_key,
-            total_gpus,
-            allocated_gpus,
+            name,
+            total_gpus: shares.whole.configured,
+            allocated_gpus: shares.whole.allocated,
+            mig_instances: shares.mig,
+            shards: shares.shards,
+            equivalents: shares.equivalents,
         })
     } else {
         None
     }
 }
 
+/// The CPUs, memory and GPUs of a node, as Slurm counts them in its trackable resources
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NodeTres {
+    pub cpus: u32,
+    pub memory_mb: u64,
+    pub gpus: u64,
+}
+
+impl NodeTres {
+    /// Takes the counts from a parsed TRES string, such as `cpu=64,mem=500G,gres/gpu=4`, or
+    /// `None` if it has no CPU count
+    pub fn from_tres(tres: &HashMap<String, u64>) -> Option<Self> {
+        Some(NodeTres {
+            cpus: *tres.get("cpu")? as u32,
+            memory_mb: tres.get("mem").map_or(0, |bytes| bytes >> 20),
+            gpus: tres.get("gres/gpu").copied().unwrap_or(0),
+        })
+    }
+}
+
+/// Reads the resources allocated on a node, which make up its allocated TRES, from the data
+/// of the select plugin: the CPUs and memory, with the GPUs in use from `gres_used`
+///
+/// Returns `None` if the plugin has none for the node, so that callers fall back on adding
+/// up what its jobs hold
+fn allocated_tres(raw_node: &node_info_t, gpu_info: Option<&GpuInfo>) -> Option<NodeTres> {
+    if raw_node.select_nodeinfo.is_null() {
+        return None;
+    }
+    let read = |data_type: select_nodedata_type, data: *mut c_void| {
+        let rc = unsafe {
+            slurm_get_select_nodeinfo(
+                raw_node.select_nodeinfo,
+                data_type,
+                node_states_NODE_STATE_ALLOCATED,
+                data,
+            )
+        };
+        rc == fi_slurm_sys::SLURM_SUCCESS as i32
+    };
+
+    let mut cpus: u16 = 0;
+    let mut memory_mb: u64 = 0;
+    if !read(
+        select_nodedata_type_SELECT_NODEDATA_SUBCNT,
+        &mut cpus as *mut u16 as *mut c_void,
+    ) || !read(
+        select_nodedata_type_SELECT_NODEDATA_MEM_ALLOC,
+        &mut memory_mb as *mut u64 as *mut c_void,
+    ) {
+        return None;
+    }
+    Some(NodeTres {
+        cpus: cpus as u32,
+        memory_mb,
+        gpus: gpu_info.map_or(0, |gpu| gpu.allocated_gpus),
+    })
+}
+
 type NodeName = String;
 
 // pub struct Node, a safe counterpart to node_info_t
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Node {
     pub id: usize,
     pub name: NodeName,
@@ -307,9 +393,9 @@ pub struct Node {
     // Energy information
     _energy: Option<AcctGatherEnergy>,
 
-    // Slurm Features
-    pub features: Vec<String>,
-    pub active_features: Vec<String>, // aka features_act
+    // Slurm Features, interned so that all the nodes with a feature share one copy of its name
+    pub features: Vec<Arc<str>>,
+    pub active_features: Vec<Arc<str>>, // aka features_act
 
     // Generic Resources (GRES), like GPUs
     // pub configured_gres: HashMap<String, u64>,
@@ -354,24 +440,36 @@ pub struct Node {
     pub tmp_disk: u32,
     pub weight: u32,
     pub tres_fmt_str: String,
+    /// The resources of the node, from `tres_fmt_str`
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub configured_tres: Option<NodeTres>,
+    /// The resources allocated on the node, as Slurm accounts for them
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub allocated_tres: Option<NodeTres>,
     pub version: String,
 }
 
 impl Node {
+    /// The load average of the node in CPUs, which Slurm reports in hundredths, or `None` if
+    /// the node hasn't reported one
+    pub fn load(&self) -> Option<f64> {
+        (self.cpu_load != fi_slurm_sys::NO_VAL).then(|| self.cpu_load as f64 / 100.0)
+    }
+
     /// Creates a safe, owned Rust `Node` from a raw C-style `node_info_t` struct.
     ///
     /// # Safety
     ///
     /// The caller must ensure that the `raw_node` contains valid pointers
     /// for all string fields, as provided by a trusted Slurm API call
-    pub fn from_raw_binding(id: usize, raw_node: &node_info_t) -> Result<Self, String> {
+    pub fn from_raw_binding(id: usize, raw_node: &node_info_t) -> Result<Self, SlurmError> {
         // Helper to convert comma-separated C string to a Vec<String>
-        let c_str_to_vec = |ptr: *const i8| -> Vec<String> {
+        let c_str_to_vec = |ptr: *const i8| -> Vec<Arc<str>> {
             if ptr.is_null() {
                 Vec::new()
             } else {
                 let r_str = unsafe { CStr::from_ptr(ptr) }.to_string_lossy();
-                r_str.split(',').map(String::from).collect()
+                r_str.split(',').map(Arc::from).collect()
             }
         };
 
@@ -383,6 +481,9 @@ impl Node {
             })?)
         };
 
+        let gpu_info = create_gpu_info(raw_node.gres, raw_node.gres_used);
+        let tres_fmt_str = unsafe { c_str_to_string(raw_node.tres_fmt_str) };
+
         // used as a sentinel value in C, replace when we have proper enums set up
         const NODE_STATE_END: u32 = 7;
         let next_state_val = if raw_node.next_state == NODE_STATE_END {
@@ -421,7 +522,8 @@ impl Node {
             active_features: c_str_to_vec(raw_node.features_act),
 
             // Generic Resources (GRES)
-            gpu_info: create_gpu_info(raw_node.gres, raw_node.gres_used),
+            allocated_tres: allocated_tres(raw_node, gpu_info.as_ref()),
+            gpu_info,
             gres: unsafe { c_str_to_string(raw_node.gres) }, // Keep the raw string for reference
             gres_drain: unsafe { c_str_to_string(raw_node.gres_drain) },
             gres_used: unsafe { c_str_to_string(raw_node.gres_used) }, // Keep the raw string for reference
@@ -454,22 +556,91 @@ impl Node {
             reason_uid: raw_node.reason_uid,
             resv_name: unsafe { c_str_to_string(raw_node.resv_name) },
 
-            // TODO: `select_nodeinfo` is a void pointer to plugin-specific data
-            // Handling this requires knowing which select plugin is active and how
-            // to interpret its data structure
-            // For now, we will ignore it
-            // select_nodeinfo: ...,
+            // `select_nodeinfo` is plugin-specific data, only read for the allocated TRES above
             sockets: raw_node.sockets,
             threads: raw_node.threads,
             tmp_disk: raw_node.tmp_disk,
             weight: raw_node.weight,
-            tres_fmt_str: "TODO: Parse TRES format string".to_string(), // Placeholder
+            configured_tres: NodeTres::from_tres(&unsafe { parse_tres_str(raw_node.tres_fmt_str) }),
+            tres_fmt_str,
             version: unsafe { c_str_to_string(raw_node.version) },
         })
     }
+
+    /// Creates a node with the given name, state, CPUs, features and GPUs, and every other
+    /// field empty, as the nodes of a synthetic cluster are
+    #[cfg(feature = "synthetic")]
+    pub fn new(
+        id: usize,
+        name: String,
+        state: NodeState,
+        cpus: u16,
+        features: Vec<Arc<str>>,
+        gpu_info: Option<GpuInfo>,
+    ) -> Self {
+        let epoch = DateTime::UNIX_EPOCH;
+        Node {
+            id,
+            node_addr: name.clone(),
+            node_hostname: name.clone(),
+            name,
+            state,
+            next_state: NodeState::Unknown("N/A".to_string()),
+            cpus,
+            cores: cpus,
+            core_spec_count: 0,
+            cpu_bind: 0,
+            cpu_load: 0,
+            cpus_effective: cpus,
+            cpu_spec_list: String::new(),
+            real_memory: cpus as u64 * 4096,
+            free_memory: 0,
+            mem_spec_limit: 0,
+            _energy: None,
+            active_features: features.clone(),
+            features,
+            gpu_info,
+            gres: String::new(),
+            gres_drain: String::new(),
+            gres_used: String::new(),
+            res_cores_per_gpu: 0,
+            gpu_spec: String::new(),
+            boot_time: epoch,
+            last_busy: epoch,
+            slurmd_start_time: epoch,
+            architecture: String::new(),
+            operating_system: String::new(),
+            reason: String::new(),
+            broadcast_address: String::new(),
+            boards: 1,
+            cluster_name: String::new(),
+            extra: String::new(),
+            instance_id: String::new(),
+            instance_type: String::new(),
+            mcs_label: String::new(),
+            os: String::new(),
+            owner: 0,
+            partitions: String::new(),
+            port: 0,
+            comment: String::new(),
+            reason_time: epoch,
+            reason_uid: 0,
+            resume_after: epoch,
+            resv_name: String::new(),
+            sockets: 1,
+            threads: 1,
+            tmp_disk: 0,
+            weight: 1,
+            tres_fmt_str: String::new(),
+            configured_tres: None,
+            allocated_tres: None,
+            version: String::new(),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SlurmNodes {
     // The primary data store: a contiguous vector of nodes.
     pub nodes: Vec<Node>,
@@ -480,3 +651,75 @@ pub struct SlurmNodes {
     // conditions like having 0 CPUs
     pub skip_count: usize,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compound_state_display() {
+        let state =
+            NodeState::from(2 | NodeStateFlags::DRAIN.bits() | NodeStateFlags::MAINT.bits());
+        assert_eq!(state.to_string(), "IDLE+DRAIN+MAINT");
+        assert_eq!(NodeState::from(3), NodeState::Allocated);
+    }
+
+    #[test]
+    fn test_features_are_interned() {
+        let mut interner = FeatureInterner::default();
+        let mut first: Vec<Arc<str>> = vec![Arc::from("genoa"), Arc::from("ib")];
+        let mut second: Vec<Arc<str>> = vec![Arc::from("ib"), Arc::from("genoa")];
+        interner.intern_all(&mut first);
+        interner.intern_all(&mut second);
+        assert!(Arc::ptr_eq(&first[0], &second[1]));
+        assert!(Arc::ptr_eq(&first[1], &second[0]));
+    }
+
+    #[test]
+    fn test_node_tres() {
+        let tres: HashMap<String, u64> = [
+            ("cpu", 64),
+            ("mem", 500 << 30),
+            ("billing", 64),
+            ("gres/gpu", 4),
+            ("gres/gpu:h100", 4),
+        ]
+        .into_iter()
+        .map(|(name, count)| (name.to_string(), count))
+        .collect();
+        assert_eq!(
+            NodeTres::from_tres(&tres),
+            Some(NodeTres {
+                cpus: 64,
+                memory_mb: 500 * 1024,
+                gpus: 4
+            })
+        );
+        assert_eq!(NodeTres::from_tres(&HashMap::new()), None);
+    }
+
+    #[test]
+    fn test_whole_equivalents() {
+        // one A100 split into a 3g and a 4g instance, the 3g one allocated
+        let configured = parse_gres("gpu:nvidia_a100_3g.20gb:1,gpu:nvidia_a100_4g.20gb:1");
+        let used = parse_gres("gpu:nvidia_a100_3g.20gb:1(IDX:0)");
+        let shares = gpu_shares(&configured, &used);
+        let mig_only = GpuInfo {
+            total_gpus: shares.whole.configured,
+            allocated_gpus: shares.whole.allocated,
+            mig_instances: shares.mig,
+            equivalents: shares.equivalents,
+            ..Default::default()
+        };
+        assert_eq!(mig_only.total_gpus, 0);
+        assert_eq!(mig_only.mig_instances.configured, 2);
+        assert_eq!(mig_only.whole_equivalents(), (1, 1));
+
+        let whole = GpuInfo {
+            total_gpus: 4,
+            allocated_gpus: 1,
+            ..Default::default()
+        };
+        assert_eq!(whole.whole_equivalents(), (4, 1));
+    }
+}
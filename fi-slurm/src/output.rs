@@ -0,0 +1,132 @@
+//! The output formats every tool offers through its `--output` option
+//!
+//! Each tool prints its own tables, but writes records for other programs the same way, so
+//! that a pipeline can take the output of any of them: JSON as one document, JSON Lines with
+//! one record per line, or CSV with a header line.
+
+use std::fmt;
+#[cfg(feature = "serde")]
+use std::io::{self, Write};
+
+/// How a tool prints its report
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum OutputFormat {
+    /// A table for reading in a terminal
+    #[default]
+    Table,
+    /// One JSON document
+    Json,
+    /// Comma-separated values, with a header line
+    Csv,
+    /// One JSON object per line
+    Jsonl,
+}
+
+impl OutputFormat {
+    /// Whether the format is meant for other programs rather than for reading
+    pub fn is_structured(self) -> bool {
+        self != OutputFormat::Table
+    }
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            OutputFormat::Table => "table",
+            OutputFormat::Json => "json",
+            OutputFormat::Csv => "csv",
+            OutputFormat::Jsonl => "jsonl",
+        })
+    }
+}
+
+/// Writes records in one of the structured formats: a JSON array, one JSON object per line, or
+/// CSV, whose records must be flat, with a header line named after their fields
+///
+/// Tables differ from tool to tool, so are left to each tool, and asking for one here is an error
+#[cfg(feature = "serde")]
+pub fn write_records<T: serde::Serialize>(
+    format: OutputFormat,
+    records: &[T],
+    mut out: impl Write,
+) -> io::Result<()> {
+    match format {
+        OutputFormat::Table => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "tables are printed by each tool",
+            ));
+        }
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(&mut out, records)?;
+            out.write_all(b"\n")?;
+        }
+        OutputFormat::Jsonl => {
+            for record in records {
+                serde_json::to_writer(&mut out, record)?;
+                out.write_all(b"\n")?;
+            }
+        }
+        OutputFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(&mut out);
+            for record in records {
+                writer.serialize(record).map_err(io::Error::other)?;
+            }
+            writer.flush()?;
+        }
+    }
+    out.flush()
+}
+
+/// Treats a reader that stopped reading, as `head` does, as the end of the output rather than
+/// an error
+#[cfg(feature = "serde")]
+pub fn ignore_broken_pipe(result: io::Result<()>) -> io::Result<()> {
+    match result {
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        other => other,
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+
+    #[derive(serde::Serialize)]
+    struct Row {
+        name: &'static str,
+        count: u32,
+    }
+
+    fn written(format: OutputFormat) -> String {
+        let rows = [
+            Row {
+                name: "genoa",
+                count: 3,
+            },
+            Row {
+                name: "rome, old",
+                count: 1,
+            },
+        ];
+        let mut out = Vec::new();
+        write_records(format, &rows, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_write_records() {
+        assert_eq!(
+            written(OutputFormat::Csv),
+            "name,count\ngenoa,3\n\"rome, old\",1\n"
+        );
+        assert_eq!(
+            written(OutputFormat::Jsonl),
+            "{\"name\":\"genoa\",\"count\":3}\n{\"name\":\"rome, old\",\"count\":1}\n"
+        );
+        let json: serde_json::Value = serde_json::from_str(&written(OutputFormat::Json)).unwrap();
+        assert_eq!(json[1]["count"], 1);
+        assert!(write_records(OutputFormat::Table, &[0], Vec::new()).is_err());
+    }
+}
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::ffi::CStr;
 use std::sync::OnceLock;
 
@@ -7,13 +7,15 @@ use regex::Regex;
 /// A robust parser for Slurm hostlist strings
 ///
 /// This function can handle simple comma-separated lists as well as complex
-/// ranged expressions with zero-padding
+/// ranged expressions with zero-padding. A name may contain several bracketed
+/// range groups, which expand to every combination in order, as Slurm does
 ///
 /// # Examples
 ///
 /// * `"n01,n02"` -> `["n01", "n02"]`
 /// * `"compute-b[10-12,15]"` -> `["compute-b10", "compute-b11", "compute-b12", "compute-b15"]`
 /// * `"gpu-a[01-02]-ib"` -> `["gpu-a01-ib", "gpu-a02-ib"]`
+/// * `"rack[1-2]-n[01-02]"` -> `["rack1-n01", "rack1-n02", "rack2-n01", "rack2-n02"]`
 ///
 /// # Arguments
 ///
@@ -23,209 +25,260 @@ use regex::Regex;
 ///
 /// A `Vec<String>` containing all the individual, expanded hostnames
 pub fn parse_slurm_hostlist(hostlist_str: &str) -> Vec<String> {
-    static RE: OnceLock<Regex> = OnceLock::new();
-    let re = RE.get_or_init(|| {
-        Regex::new(r"^(.*)\[([^\]]+)\](.*)$").expect("Failed to compile hostlist regex")
-    });
-
     let mut expanded_nodes = Vec::new();
-    let mut expressions = Vec::new();
-    let mut current_expression = String::new();
-    let mut bracket_level = 0;
+    for expression in split_hostlist_expressions(hostlist_str) {
+        expand_hostlist_expression(expression, &mut expanded_nodes);
+    }
+    expanded_nodes
+}
 
-    // This loop correctly separates expressions like "node[01-02],login01"
-    // by respecting brackets
-    for ch in hostlist_str.chars() {
+/// Splits a hostlist on its top-level commas, so that "node[01-02],login01"
+/// yields "node[01-02]" and "login01"
+fn split_hostlist_expressions(hostlist_str: &str) -> Vec<&str> {
+    let mut expressions = Vec::new();
+    let mut bracket_level = 0usize;
+    let mut start = 0;
+    for (i, ch) in hostlist_str.char_indices() {
         match ch {
             '[' => bracket_level += 1,
-            ']' => bracket_level -= 1,
+            ']' => bracket_level = bracket_level.saturating_sub(1),
             ',' if bracket_level == 0 => {
-                // We found a top-level comma separator.
-                if !current_expression.is_empty() {
-                    expressions.push(current_expression.trim().to_string());
-                    current_expression.clear();
-                }
-                continue; // Skip adding the comma to the expression
+                expressions.push(hostlist_str[start..i].trim());
+                start = i + 1;
             }
             _ => {}
         }
-        current_expression.push(ch);
-    }
-    // Add the last expression to the list
-    if !current_expression.is_empty() {
-        expressions.push(current_expression.trim().to_string());
-    }
-
-    for part in expressions {
-        // For each part, check if it matches our ranged expression regex
-        if let Some(captures) = re.captures(&part) {
-            // It's a ranged expression like "prefix[ranges]suffix"
-            let prefix = captures.get(1).map_or("", |m| m.as_str());
-            let range_list = captures.get(2).map_or("", |m| m.as_str());
-            let suffix = captures.get(3).map_or("", |m| m.as_str());
-
-            // Process the list of ranges inside the brackets
-            for range_spec in range_list.split(',') {
-                if let Some((start_str, end_str)) = range_spec.split_once('-') {
-                    // It's a range like "01-03"
-                    if let (Ok(start), Ok(end)) = (start_str.parse::<u32>(), end_str.parse::<u32>())
-                        && start <= end
-                    {
-                        // Detect zero-padding width from the start of the range
-                        let width = start_str.len();
-                        for i in start..=end {
-                            // Format the number with leading zeros to match the width
-                            expanded_nodes.push(format!(
-                                "{}{:0width$}{}",
-                                prefix,
-                                i,
-                                suffix,
-                                width = width
-                            ));
-                        }
-                    }
-                    // Ignore invalid ranges where start > end
-                } else {
-                    // It's a single number like "07"
-                    expanded_nodes.push(format!("{}{}{}", prefix, range_spec, suffix));
-                }
-            }
-        } else {
-            // It's a simple hostname, not a ranged expression.
-            if !part.is_empty() {
-                expanded_nodes.push(part.to_string());
+    }
+    expressions.push(hostlist_str[start..].trim());
+    expressions.retain(|expression| !expression.is_empty());
+    expressions
+}
+
+/// Expands the contents of one bracket group, such as "01-03,07", into
+/// zero-padded numbers. The padding width of a range is taken from its start
+fn expand_range_list(range_list: &str) -> Vec<String> {
+    let mut values = Vec::new();
+    for range_spec in range_list.split(',').map(str::trim) {
+        if let Some((start_str, end_str)) = range_spec.split_once('-') {
+            // It's a range like "01-03"; ranges that can't be parsed or run
+            // backwards are ignored
+            if let (Ok(start), Ok(end)) = (start_str.parse::<u64>(), end_str.parse::<u64>())
+                && start <= end
+            {
+                let width = start_str.len();
+                values.extend((start..=end).map(|i| format!("{:0width$}", i, width = width)));
             }
+        } else if !range_spec.is_empty() {
+            // It's a single number like "07"
+            values.push(range_spec.to_string());
         }
     }
-    expanded_nodes
+    values
 }
 
-/// Compresses a vector of hostnames into a compact Slurm hostlist string.
-/// This is the reverse operation of `parse_slurm_hostlist`.
-pub fn compress_hostlist(nodes: &[String]) -> String {
+/// Expands a single hostlist expression, which may hold any number of
+/// bracket groups, appending the resulting names to `out`
+///
+/// An expression with unbalanced brackets is passed through unchanged
+fn expand_hostlist_expression(expression: &str, out: &mut Vec<String>) {
+    // Each expression is a sequence of literal text and bracket groups,
+    // and the names it expands to are the product of those pieces
+    let mut names = vec![String::new()];
+    let mut rest = expression;
+
+    while let Some(open) = rest.find('[') {
+        let Some(close) = rest[open..].find(']').map(|i| open + i) else {
+            out.push(expression.to_string());
+            return;
+        };
+        let literal = &rest[..open];
+        let values = expand_range_list(&rest[open + 1..close]);
+
+        names = names
+            .iter()
+            .flat_map(|name| {
+                values
+                    .iter()
+                    .map(move |value| format!("{}{}{}", name, literal, value))
+            })
+            .collect();
+        rest = &rest[close + 1..];
+    }
+
+    if rest.contains(']') {
+        out.push(expression.to_string());
+        return;
+    }
+    out.extend(names.into_iter().map(|name| name + rest));
+}
+
+/// A hostname split around its trailing number, as Slurm's hostlist code splits it
+struct NumberedHost<'a> {
+    prefix: &'a str,
+    number: u64,
+    digits: &'a str,
+    suffix: &'a str,
+}
+
+/// Splits a hostname around its last run of digits, so "gpu01-a100" becomes
+/// "gpu01-a" and 100, and "node-08-ib" becomes "node-", 8 and "-ib"
+fn split_numbered_host(name: &str) -> Option<NumberedHost<'_>> {
     // Regex to parse a node name into three parts:
-    // 1. A non-digit prefix (e.g., "node-")
-    // 2. A numeric middle part (e.g., "007")
+    // 1. A prefix, which may contain digits (e.g., "rack2-node")
+    // 2. The last numeric part (e.g., "007")
     // 3. A non-digit suffix (e.g., "-ib")
     static RE: OnceLock<Regex> = OnceLock::new();
     let re = RE.get_or_init(|| {
-        Regex::new(r"^(\D*)(\d+)(\D*)$").expect("Failed to compile compression regex")
+        Regex::new(r"^(.*?)(\d+)(\D*)$").expect("Failed to compile compression regex")
     });
 
-    // A map to group nodes by their (prefix, suffix) pair.
-    // The key is (prefix, suffix).
-    // The value is a vector of (number, padding_width) tuples.
-    let mut groups: HashMap<(String, String), Vec<(u32, usize)>> = HashMap::new();
-    // A list to hold node names that don't fit the numeric pattern (e.g., "login").
-    let mut standalone_nodes: Vec<String> = Vec::new();
+    let caps = re.captures(name)?;
+    let digits = caps.get(2)?.as_str();
+    Some(NumberedHost {
+        prefix: caps.get(1).map_or("", |m| m.as_str()),
+        number: digits.parse().ok()?,
+        digits,
+        suffix: caps.get(3).map_or("", |m| m.as_str()),
+    })
+}
 
-    for node_name in nodes {
-        if let Some(caps) = re.captures(node_name) {
-            let prefix = caps.get(1).map_or("", |m| m.as_str()).to_string();
-            let number_str = caps.get(2).map_or("", |m| m.as_str());
-            let suffix = caps.get(3).map_or("", |m| m.as_str()).to_string();
-
-            if let Ok(number) = number_str.parse::<u32>() {
-                let padding = number_str.len();
-                groups
-                    .entry((prefix, suffix))
-                    .or_default()
-                    .push((number, padding));
-            } else {
-                standalone_nodes.push(node_name.clone());
-            }
+/// Whether `digits` is how a range whose start is `width` digits wide writes its number,
+/// which is what `parse_slurm_hostlist` expects when it expands the range
+fn fits_width(digits: &str, width: usize) -> bool {
+    let zero_padded = digits.len() > 1 && digits.starts_with('0');
+    if zero_padded {
+        digits.len() == width
+    } else {
+        digits.len() >= width
+    }
+}
+
+/// A run of consecutive numbers sharing a padding width, such as `08-10`
+struct HostRange {
+    start: u64,
+    end: u64,
+    width: usize,
+}
+
+impl std::fmt::Display for HostRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.start == self.end {
+            write!(f, "{:0width$}", self.start, width = self.width)
         } else {
-            standalone_nodes.push(node_name.clone());
+            write!(
+                f,
+                "{:0width$}-{:0width$}",
+                self.start,
+                self.end,
+                width = self.width
+            )
         }
     }
+}
 
-    // --- FIX: Re-classify single-node groups as standalone nodes ---
-    // This prevents a single node like "login01" from being formatted as "login[01]".
-    // We collect the keys of groups to modify first to avoid borrowing issues with the map.
-    let single_node_group_keys: Vec<_> = groups
-        .iter()
-        .filter(|(_, numbers)| numbers.len() == 1)
-        .map(|(key, _)| key.clone())
-        .collect();
-
-    for key in single_node_group_keys {
-        if let Some(numbers) = groups.remove(&key) {
-            let (prefix, suffix) = key;
-            let (number, padding) = numbers[0];
-            // Reconstruct the original node name and add it to the standalone list.
-            // THIS LINE IS FIXED: Added `suffix` to the format arguments.
-            let original_name = format!("{}{:0width$}{}", prefix, number, suffix, width = padding);
-            standalone_nodes.push(original_name);
+/// Folds a set of numbers, as written in the hostnames, into ranges in ascending order
+///
+/// Zero-padded numbers only join ranges of their own width, while unpadded ones may
+/// outgrow the width of their range, so "n9,n10" becomes `[9-10]` but "n09,n10" stays
+/// `[09-10]` and "n1,n01" stays `[01,1]`
+fn fold_ranges(mut numbers: Vec<(u64, &str)>) -> Vec<HostRange> {
+    numbers.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.len().cmp(&b.1.len())));
+    numbers.dedup();
+
+    let mut ranges: Vec<HostRange> = Vec::new();
+    for (number, digits) in numbers {
+        let open = ranges.iter_mut().find(|range| {
+            range.end.checked_add(1) == Some(number) && fits_width(digits, range.width)
+        });
+        match open {
+            Some(range) => range.end = number,
+            None => ranges.push(HostRange {
+                start: number,
+                end: number,
+                width: digits.len(),
+            }),
         }
     }
-    // --- End of FIX ---
 
-    let mut compressed_parts: Vec<String> = standalone_nodes;
-
-    for ((prefix, suffix), mut numbers) in groups {
-        // Sort numbers to make finding consecutive ranges easy.
-        numbers.sort();
+    ranges.sort_by(|a, b| a.start.cmp(&b.start).then(b.width.cmp(&a.width)));
+    ranges
+}
 
-        if numbers.is_empty() {
-            continue;
-        }
+/// The numbers of the hosts sharing a prefix, keyed by the suffix that follows them
+type SuffixGroups<'a> = BTreeMap<&'a str, Vec<(u64, &'a str)>>;
 
-        let mut ranges: Vec<String> = Vec::new();
-        let mut current_start_num = numbers[0].0;
-        let mut current_end_num = numbers[0].0;
-        let mut current_padding = numbers[0].1;
+/// Compresses a list of hostnames into one hostlist expression per prefix, in the
+/// order `scontrol show hostlistsorted` would list them
+///
+/// Each entry pairs the prefix, such as "worker" or "gpu-", with the hostlist covering
+/// every node that shares it, such as `worker[1000-1009,1014]`. Nodes with no number
+/// are their own prefix. This is handy for reports that print one group per line
+///
+/// # Examples
+///
+/// * `["n2", "n1", "login", "gpu01-ib", "gpu02-ib"]` ->
+///   `[("gpu", "gpu[01-02]-ib"), ("login", "login"), ("n", "n[1-2]")]`
+pub fn compress_hostlist_grouped(nodes: &[String]) -> Vec<(String, String)> {
+    // Nodes are grouped by prefix, and within a prefix by suffix. The numbers are kept
+    // as written so their padding survives the roundtrip
+    let mut groups: BTreeMap<&str, SuffixGroups> = BTreeMap::new();
+    let mut standalone_nodes: BTreeSet<&str> = BTreeSet::new();
 
-        for &(num, padding) in &numbers[1..] {
-            // A new range starts if the number is not consecutive OR if the padding changes.
-            if num == current_end_num + 1 && padding == current_padding {
-                current_end_num = num;
-            } else {
-                // Finalize the previous range
-                if current_start_num == current_end_num {
-                    ranges.push(format!(
-                        "{:0width$}",
-                        current_start_num,
-                        width = current_padding
-                    ));
-                } else {
-                    ranges.push(format!(
-                        "{:0width$}-{:0width$}",
-                        current_start_num,
-                        current_end_num,
-                        width = current_padding
-                    ));
-                }
-                // Start a new range
-                current_start_num = num;
-                current_end_num = num;
-                current_padding = padding;
+    for node_name in nodes {
+        match split_numbered_host(node_name) {
+            Some(host) => groups
+                .entry(host.prefix)
+                .or_default()
+                .entry(host.suffix)
+                .or_default()
+                .push((host.number, host.digits)),
+            None => {
+                standalone_nodes.insert(node_name);
             }
         }
-        // Add the very last range
-        if current_start_num == current_end_num {
-            ranges.push(format!(
-                "{:0width$}",
-                current_start_num,
-                width = current_padding
-            ));
-        } else {
-            ranges.push(format!(
-                "{:0width$}-{:0width$}",
-                current_start_num,
-                current_end_num,
-                width = current_padding
-            ));
-        }
+    }
+
+    let mut compressed: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for name in standalone_nodes {
+        compressed
+            .entry(name.to_string())
+            .or_default()
+            .push(name.to_string());
+    }
 
-        // Only add brackets if there is something to put in them.
-        if !ranges.is_empty() {
-            compressed_parts.push(format!("{}[{}]{}", prefix, ranges.join(","), suffix));
+    for (prefix, suffixes) in groups {
+        let entry = compressed.entry(prefix.to_string()).or_default();
+        for (suffix, numbers) in suffixes {
+            let ranges = fold_ranges(numbers);
+            // A single node like "login01" is written as is, not as "login[01]"
+            if let [range] = ranges.as_slice()
+                && range.start == range.end
+            {
+                entry.push(format!("{}{}{}", prefix, range, suffix));
+                continue;
+            }
+            let ranges: Vec<String> = ranges.iter().map(HostRange::to_string).collect();
+            entry.push(format!("{}[{}]{}", prefix, ranges.join(","), suffix));
         }
     }
 
-    // Sort the final parts for a deterministic output order.
-    compressed_parts.sort();
-    compressed_parts.join(",")
+    compressed
+        .into_iter()
+        .map(|(prefix, parts)| (prefix, parts.join(",")))
+        .collect()
+}
+
+/// Compresses a vector of hostnames into a compact Slurm hostlist string.
+/// This is the reverse operation of `parse_slurm_hostlist`.
+///
+/// Duplicates are dropped and the output is sorted by prefix and then by number,
+/// matching `scontrol show hostlistsorted`
+pub fn compress_hostlist(nodes: &[String]) -> String {
+    compress_hostlist_grouped(nodes)
+        .into_iter()
+        .map(|(_, hostlist)| hostlist)
+        .collect::<Vec<_>>()
+        .join(",")
 }
 
 /// Parses a comma-separated TRES string (e.g., "cpu=4,mem=8G,gres/gpu=1")
@@ -288,6 +341,43 @@ pub unsafe fn parse_tres_str(tres_ptr: *const i8) -> HashMap<String, u64> {
         .collect()
 }
 
+/// Parses the task list of a job array, as in `squeue`'s `1-3,5,7-15:2%4`
+///
+/// Ranges may carry a `:step`, and a trailing `%N` limits how many tasks run at once. Parts that
+/// cannot be parsed, such as the `...` Slurm appends to truncated lists, are skipped
+///
+/// # Returns
+///
+/// The task ids in the list, and the limit on simultaneously running tasks, if any
+pub fn parse_array_task_str(task_str: &str) -> (Vec<u32>, Option<u32>) {
+    let (list, max_running) = match task_str.split_once('%') {
+        Some((list, max)) => (list, max.trim().parse::<u32>().ok()),
+        None => (task_str, None),
+    };
+
+    let mut task_ids = Vec::new();
+    for part in list.split(',').map(str::trim) {
+        let (range, step) = match part.split_once(':') {
+            Some((range, step)) => (range, step.parse::<u32>().unwrap_or(1).max(1)),
+            None => (part, 1),
+        };
+        match range.split_once('-') {
+            Some((start, end)) => {
+                if let (Ok(start), Ok(end)) = (start.parse::<u32>(), end.parse::<u32>()) {
+                    task_ids.extend((start..=end).step_by(step as usize));
+                }
+            }
+            None => {
+                if let Ok(id) = range.parse::<u32>() {
+                    task_ids.push(id);
+                }
+            }
+        }
+    }
+
+    (task_ids, max_running)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -372,6 +462,77 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_multiple_bracket_groups() {
+        assert_eq!(
+            parse_slurm_hostlist("rack[1-2]-n[01-02]"),
+            vec!["rack1-n01", "rack1-n02", "rack2-n01", "rack2-n02"]
+        );
+        assert_eq!(
+            parse_slurm_hostlist("worker[1000-1009,1014],gpu[01-04]-a100"),
+            vec![
+                "worker1000",
+                "worker1001",
+                "worker1002",
+                "worker1003",
+                "worker1004",
+                "worker1005",
+                "worker1006",
+                "worker1007",
+                "worker1008",
+                "worker1009",
+                "worker1014",
+                "gpu01-a100",
+                "gpu02-a100",
+                "gpu03-a100",
+                "gpu04-a100",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_malformed_hostlist() {
+        assert_eq!(parse_slurm_hostlist("n[01-02"), vec!["n[01-02"]);
+        assert_eq!(parse_slurm_hostlist("n[03-01],m1"), vec!["m1"]);
+        assert!(parse_slurm_hostlist(" , ").is_empty());
+    }
+
+    #[test]
+    fn test_compress_parse_roundtrip() {
+        // A small deterministic generator stands in for a property testing crate, covering
+        // many random node sets across prefixes, suffixes and padding widths
+        let mut seed: u64 = 0x5eed;
+        let mut next = move |bound: u64| {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (seed >> 33) % bound
+        };
+        let prefixes = ["n", "worker", "gpu-", "rusty"];
+        let suffixes = ["", "-ib", "-a"];
+
+        for _ in 0..200 {
+            let mut nodes = Vec::new();
+            for _ in 0..=next(4) {
+                let prefix = prefixes[next(prefixes.len() as u64) as usize];
+                let suffix = suffixes[next(suffixes.len() as u64) as usize];
+                let width = 1 + next(4) as usize;
+                for _ in 0..=next(20) {
+                    let number = next(10u64.pow(width as u32));
+                    nodes.push(format!("{prefix}{number:0width$}{suffix}"));
+                }
+            }
+            nodes.push("login".to_string());
+            nodes.sort();
+            nodes.dedup();
+
+            let compressed = compress_hostlist(&nodes);
+            let mut expanded = parse_slurm_hostlist(&compressed);
+            expanded.sort();
+            assert_eq!(expanded, nodes, "roundtrip through {compressed}");
+        }
+    }
+
     #[test]
     fn test_compress_simple_consecutive() {
         let nodes = vec!["n01".to_string(), "n02".to_string(), "n03".to_string()];
@@ -438,6 +599,33 @@ mod tests {
         assert_eq!(compress_hostlist(&nodes), "n[1-2,03-04]");
     }
 
+    #[test]
+    fn test_compress_matches_slurm_sorted_output() {
+        let nodes: Vec<String> = ["n10", "n9", "n9", "n08", "rack2-n1", "rack2-n2", "login"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        // Unpadded numbers may grow past their width, duplicates are dropped, and digits in
+        // the prefix don't stop the trailing number from compressing
+        assert_eq!(compress_hostlist(&nodes), "login,n[08,9-10],rack2-n[1-2]");
+    }
+
+    #[test]
+    fn test_compress_hostlist_grouped() {
+        let nodes: Vec<String> = ["n2", "n1", "login", "gpu01-ib", "gpu02-ib", "gpu03"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert_eq!(
+            compress_hostlist_grouped(&nodes),
+            vec![
+                ("gpu".to_string(), "gpu03,gpu[01-02]-ib".to_string()),
+                ("login".to_string(), "login".to_string()),
+                ("n".to_string(), "n[1-2]".to_string()),
+            ]
+        );
+    }
+
     #[test]
     fn test_simple_tres_string() {
         let input_str = "cpu=512,mem=4000G,node=4,billing=512";
@@ -499,4 +687,13 @@ mod tests {
         let result_map = unsafe { parse_tres_str(c_string.as_ptr()) };
         assert!(result_map.is_empty());
     }
+
+    #[test]
+    fn test_array_task_str() {
+        assert_eq!(
+            parse_array_task_str("1-3,5,7-11:2%4"),
+            (vec![1, 2, 3, 5, 7, 9, 11], Some(4))
+        );
+        assert_eq!(parse_array_task_str("0-2,..."), (vec![0, 1, 2], None));
+    }
 }
@@ -0,0 +1,306 @@
+use crate::error::SlurmError;
+use crate::parser::parse_slurm_hostlist;
+use crate::states::ShowFlags;
+use crate::utils::{c_str_to_string, time_t_to_datetime};
+use bitflags::bitflags;
+use chrono::{DateTime, Utc};
+use fi_slurm_sys::{
+    partition_info_msg_t, partition_info_t, slurm_free_partition_info_msg, slurm_load_partitions,
+    time_t,
+};
+use std::fmt;
+
+/// We use this struct to manage the C-allocated memory,
+/// automatically dropping it when it goes out of memory
+pub struct RawSlurmPartitionInfo {
+    ptr: *mut partition_info_msg_t,
+}
+
+impl Drop for RawSlurmPartitionInfo {
+    fn drop(&mut self) {
+        if !self.ptr.is_null() {
+            unsafe {
+                slurm_free_partition_info_msg(self.ptr);
+            }
+            self.ptr = std::ptr::null_mut();
+        }
+    }
+}
+
+impl RawSlurmPartitionInfo {
+    /// Loads all partition information from the Slurm controller, including hidden partitions
+    pub fn load(update_time: time_t) -> Result<Self, SlurmError> {
+        let mut partition_info_msg_ptr: *mut partition_info_msg_t = std::ptr::null_mut();
+
+        let show_flags = ShowFlags::ALL | ShowFlags::DETAIL;
+
+        let return_code = unsafe {
+            slurm_load_partitions(update_time, &mut partition_info_msg_ptr, show_flags.bits())
+        };
+
+        if return_code != 0 {
+            Err(SlurmError::from_errno(
+                "load partition information from Slurm",
+            ))
+        } else if partition_info_msg_ptr.is_null() {
+            Err(SlurmError::NullResponse {
+                action: "load partition information from Slurm",
+            })
+        } else {
+            Ok(RawSlurmPartitionInfo {
+                ptr: partition_info_msg_ptr,
+            })
+        }
+    }
+
+    pub fn as_slice(&self) -> &[partition_info_t] {
+        if self.ptr.is_null() {
+            return &[];
+        }
+
+        unsafe {
+            let msg = &*self.ptr;
+            std::slice::from_raw_parts(msg.partition_array, msg.record_count as usize)
+        }
+    }
+
+    /// Consumes the wrapper to transform the raw C data into a safe, owned `SlurmPartitions`
+    /// collection
+    pub fn into_slurm_partitions(self) -> Result<SlurmPartitions, SlurmError> {
+        let partitions = self
+            .as_slice()
+            .iter()
+            .map(Partition::from_raw_binding)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let last_update = time_t_to_datetime(unsafe { (*self.ptr).last_update });
+
+        Ok(SlurmPartitions {
+            partitions,
+            last_update,
+        })
+    }
+}
+
+/// Fetches all partition information from Slurm and returns it as a safe,
+/// owned Rust data structure
+pub fn get_partitions() -> Result<SlurmPartitions, SlurmError> {
+    RawSlurmPartitionInfo::load(0)?.into_slurm_partitions()
+}
+
+bitflags! {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    pub struct PartitionFlags: u16 {
+        const DEFAULT = fi_slurm_sys::bind_partition_flags_PARTITION_FLAG_DEFAULT;
+        const HIDDEN = fi_slurm_sys::bind_partition_flags_PARTITION_FLAG_HIDDEN;
+        const NO_ROOT = fi_slurm_sys::bind_partition_flags_PARTITION_FLAG_NO_ROOT;
+        const ROOT_ONLY = fi_slurm_sys::bind_partition_flags_PARTITION_FLAG_ROOT_ONLY;
+        const REQ_RESV = fi_slurm_sys::bind_partition_flags_PARTITION_FLAG_REQ_RESV;
+        const LLN = fi_slurm_sys::bind_partition_flags_PARTITION_FLAG_LLN;
+        const EXCLUSIVE_USER = fi_slurm_sys::bind_partition_flags_PARTITION_FLAG_EXCLUSIVE_USER;
+        const PDOI = fi_slurm_sys::bind_partition_flags_PARTITION_FLAG_PDOI;
+    }
+}
+
+/// Whether a partition accepts and schedules jobs, as reported by `scontrol show partition`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PartitionState {
+    /// Jobs may be submitted and scheduled
+    Up,
+    /// Jobs may be submitted but will not be scheduled
+    Down,
+    /// Jobs may not be submitted, but queued jobs will be scheduled
+    Drain,
+    /// Jobs may neither be submitted nor scheduled
+    Inactive,
+    Unknown(u16),
+}
+
+impl From<u16> for PartitionState {
+    fn from(state: u16) -> Self {
+        match state as u32 {
+            fi_slurm_sys::PARTITION_UP => PartitionState::Up,
+            fi_slurm_sys::PARTITION_DOWN => PartitionState::Down,
+            fi_slurm_sys::PARTITION_DRAIN => PartitionState::Drain,
+            fi_slurm_sys::PARTITION_INACTIVE => PartitionState::Inactive,
+            _ => PartitionState::Unknown(state),
+        }
+    }
+}
+
+impl fmt::Display for PartitionState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PartitionState::Up => write!(f, "UP"),
+            PartitionState::Down => write!(f, "DOWN"),
+            PartitionState::Drain => write!(f, "DRAIN"),
+            PartitionState::Inactive => write!(f, "INACTIVE"),
+            PartitionState::Unknown(state) => write!(f, "UNKNOWN({})", state),
+        }
+    }
+}
+
+/// Converts a Slurm limit into an Option, where None means that there is no limit
+fn limit(value: u32) -> Option<u32> {
+    if value == fi_slurm_sys::INFINITE || value == fi_slurm_sys::NO_VAL {
+        None
+    } else {
+        Some(value)
+    }
+}
+
+/// The job limits of a partition; None means that the partition sets no limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PartitionLimits {
+    pub max_time_minutes: Option<u32>,
+    pub default_time_minutes: Option<u32>,
+    pub max_nodes: Option<u32>,
+    pub min_nodes: Option<u32>,
+    pub max_cpus_per_node: Option<u32>,
+}
+
+/// A safe, owned representation of a Slurm partition
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Partition {
+    pub name: String,
+    pub raw_hostlist: String,
+    pub node_names: Vec<String>,
+    pub total_nodes: u32,
+    pub total_cpus: u32,
+    pub state: PartitionState,
+    pub priority_tier: u16,
+    pub priority_job_factor: u16,
+    pub flags: PartitionFlags,
+    pub limits: PartitionLimits,
+    pub allow_accounts: String,
+    pub deny_accounts: String,
+    pub allow_qos: String,
+    pub deny_qos: String,
+    pub qos: String,
+    pub tres: String,
+}
+
+impl Partition {
+    /// Creates a safe, owned Rust `Partition` from a raw C `partition_info_t` struct
+    pub fn from_raw_binding(raw_partition: &partition_info_t) -> Result<Self, SlurmError> {
+        let raw_hostlist = unsafe { c_str_to_string(raw_partition.nodes) };
+        let node_names = parse_slurm_hostlist(&raw_hostlist);
+
+        Ok(Partition {
+            name: unsafe { c_str_to_string(raw_partition.name) },
+            raw_hostlist,
+            node_names,
+            total_nodes: raw_partition.total_nodes,
+            total_cpus: raw_partition.total_cpus,
+            state: PartitionState::from(raw_partition.state_up),
+            priority_tier: raw_partition.priority_tier,
+            priority_job_factor: raw_partition.priority_job_factor,
+            flags: PartitionFlags::from_bits_truncate(raw_partition.flags),
+            limits: PartitionLimits {
+                max_time_minutes: limit(raw_partition.max_time),
+                default_time_minutes: limit(raw_partition.default_time),
+                max_nodes: limit(raw_partition.max_nodes),
+                // a minimum of 0 or 1 node is no real limit
+                min_nodes: limit(raw_partition.min_nodes).filter(|&n| n > 1),
+                max_cpus_per_node: limit(raw_partition.max_cpus_per_node),
+            },
+            allow_accounts: unsafe { c_str_to_string(raw_partition.allow_accounts) },
+            deny_accounts: unsafe { c_str_to_string(raw_partition.deny_accounts) },
+            allow_qos: unsafe { c_str_to_string(raw_partition.allow_qos) },
+            deny_qos: unsafe { c_str_to_string(raw_partition.deny_qos) },
+            qos: unsafe { c_str_to_string(raw_partition.qos_char) },
+            tres: unsafe { c_str_to_string(raw_partition.tres_fmt_str) },
+        })
+    }
+
+    /// Whether this is the cluster's default partition
+    pub fn is_default(&self) -> bool {
+        self.flags.contains(PartitionFlags::DEFAULT)
+    }
+
+    /// Whether the partition is hidden from users by default
+    pub fn is_hidden(&self) -> bool {
+        self.flags.contains(PartitionFlags::HIDDEN)
+    }
+
+    /// Whether jobs of the account may run in this partition, per its AllowAccounts and
+    /// DenyAccounts
+    pub fn allows_account(&self, account: &str) -> bool {
+        let listed = |list: &str| list.split(',').any(|name| name == account);
+        let allowed = self.allow_accounts.is_empty()
+            || self.allow_accounts == "ALL"
+            || listed(&self.allow_accounts);
+        allowed && !listed(&self.deny_accounts)
+    }
+
+    /// Whether the named node belongs to this partition
+    pub fn contains_node(&self, node_name: &str) -> bool {
+        self.node_names.iter().any(|name| name == node_name)
+    }
+}
+
+/// The collection of partitions returned by `get_partitions`, in the order Slurm reports them
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SlurmPartitions {
+    pub partitions: Vec<Partition>,
+    pub last_update: DateTime<Utc>,
+}
+
+impl SlurmPartitions {
+    /// Looks up a partition by name
+    pub fn get(&self, name: &str) -> Option<&Partition> {
+        self.partitions.iter().find(|p| p.name == name)
+    }
+
+    /// The partition jobs are submitted to when none is requested, if one is configured
+    pub fn default_partition(&self) -> Option<&Partition> {
+        self.partitions.iter().find(|p| p.is_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_partition_state_and_limits() {
+        assert_eq!(PartitionState::from(3), PartitionState::Up);
+        assert_eq!(PartitionState::from(1), PartitionState::Down);
+        assert_eq!(PartitionState::from(2).to_string(), "DRAIN");
+        assert_eq!(limit(fi_slurm_sys::INFINITE), None);
+        assert_eq!(limit(fi_slurm_sys::NO_VAL), None);
+        assert_eq!(limit(10080), Some(10080));
+    }
+
+    #[test]
+    fn test_allows_account() {
+        let partition = |allow: &str, deny: &str| Partition {
+            name: "gen".to_string(),
+            raw_hostlist: String::new(),
+            node_names: Vec::new(),
+            total_nodes: 0,
+            total_cpus: 0,
+            state: PartitionState::Up,
+            priority_tier: 1,
+            priority_job_factor: 1,
+            flags: PartitionFlags::empty(),
+            limits: PartitionLimits::default(),
+            allow_accounts: allow.to_string(),
+            deny_accounts: deny.to_string(),
+            allow_qos: String::new(),
+            deny_qos: String::new(),
+            qos: String::new(),
+            tres: String::new(),
+        };
+        assert!(partition("", "").allows_account("cca"));
+        assert!(partition("ALL", "scc").allows_account("cca"));
+        assert!(!partition("ALL", "scc,cca").allows_account("cca"));
+        assert!(partition("ccb,cca", "").allows_account("cca"));
+        assert!(!partition("ccb", "").allows_account("cca"));
+    }
+}
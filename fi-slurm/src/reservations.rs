@@ -0,0 +1,267 @@
+use crate::error::SlurmError;
+use crate::parser::parse_slurm_hostlist;
+use crate::utils::{c_str_to_string, time_t_to_datetime};
+use bitflags::bitflags;
+use chrono::{DateTime, Utc};
+use fi_slurm_sys::{
+    reserve_info_msg_t, reserve_info_t, slurm_free_reservation_info_msg, slurm_load_reservations,
+    time_t,
+};
+
+/// We use this struct to manage the C-allocated memory,
+/// automatically dropping it when it goes out of memory
+pub struct RawSlurmReservationInfo {
+    ptr: *mut reserve_info_msg_t,
+}
+
+impl Drop for RawSlurmReservationInfo {
+    fn drop(&mut self) {
+        if !self.ptr.is_null() {
+            unsafe {
+                slurm_free_reservation_info_msg(self.ptr);
+            }
+            self.ptr = std::ptr::null_mut();
+        }
+    }
+}
+
+impl RawSlurmReservationInfo {
+    /// Loads all reservation information from the Slurm controller
+    pub fn load(update_time: time_t) -> Result<Self, SlurmError> {
+        let mut reserve_info_msg_ptr: *mut reserve_info_msg_t = std::ptr::null_mut();
+
+        let return_code =
+            unsafe { slurm_load_reservations(update_time, &mut reserve_info_msg_ptr) };
+
+        if return_code != 0 {
+            Err(SlurmError::from_errno(
+                "load reservation information from Slurm",
+            ))
+        } else if reserve_info_msg_ptr.is_null() {
+            Err(SlurmError::NullResponse {
+                action: "load reservation information from Slurm",
+            })
+        } else {
+            Ok(RawSlurmReservationInfo {
+                ptr: reserve_info_msg_ptr,
+            })
+        }
+    }
+
+    pub fn as_slice(&self) -> &[reserve_info_t] {
+        if self.ptr.is_null() {
+            return &[];
+        }
+
+        unsafe {
+            let msg = &*self.ptr;
+            std::slice::from_raw_parts(msg.reservation_array, msg.record_count as usize)
+        }
+    }
+
+    /// Consumes the wrapper to transform the raw C data into a safe, owned `SlurmReservations`
+    /// collection
+    pub fn into_slurm_reservations(self) -> Result<SlurmReservations, SlurmError> {
+        let reservations = self
+            .as_slice()
+            .iter()
+            .map(Reservation::from_raw_binding)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let last_update = time_t_to_datetime(unsafe { (*self.ptr).last_update });
+
+        Ok(SlurmReservations {
+            reservations,
+            last_update,
+        })
+    }
+}
+
+/// Fetches all reservations from Slurm and returns them as a safe,
+/// owned Rust data structure
+pub fn get_reservations() -> Result<SlurmReservations, SlurmError> {
+    RawSlurmReservationInfo::load(0)?.into_slurm_reservations()
+}
+
+bitflags! {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    pub struct ReservationFlags: u64 {
+        const MAINT = fi_slurm_sys::bind_reservation_flags_RESERVATION_FLAG_MAINT;
+        const DAILY = fi_slurm_sys::bind_reservation_flags_RESERVATION_FLAG_DAILY;
+        const WEEKLY = fi_slurm_sys::bind_reservation_flags_RESERVATION_FLAG_WEEKLY;
+        const IGN_JOBS = fi_slurm_sys::bind_reservation_flags_RESERVATION_FLAG_IGN_JOBS;
+        const ANY_NODES = fi_slurm_sys::bind_reservation_flags_RESERVATION_FLAG_ANY_NODES;
+        const STATIC = fi_slurm_sys::bind_reservation_flags_RESERVATION_FLAG_STATIC;
+        const PART_NODES = fi_slurm_sys::bind_reservation_flags_RESERVATION_FLAG_PART_NODES;
+        const OVERLAP = fi_slurm_sys::bind_reservation_flags_RESERVATION_FLAG_OVERLAP;
+        const SPEC_NODES = fi_slurm_sys::bind_reservation_flags_RESERVATION_FLAG_SPEC_NODES;
+        const TIME_FLOAT = fi_slurm_sys::bind_reservation_flags_RESERVATION_FLAG_TIME_FLOAT;
+        const REPLACE = fi_slurm_sys::bind_reservation_flags_RESERVATION_FLAG_REPLACE;
+        const ALL_NODES = fi_slurm_sys::bind_reservation_flags_RESERVATION_FLAG_ALL_NODES;
+        const PURGE_COMP = fi_slurm_sys::bind_reservation_flags_RESERVATION_FLAG_PURGE_COMP;
+        const WEEKDAY = fi_slurm_sys::bind_reservation_flags_RESERVATION_FLAG_WEEKDAY;
+        const WEEKEND = fi_slurm_sys::bind_reservation_flags_RESERVATION_FLAG_WEEKEND;
+        const FLEX = fi_slurm_sys::bind_reservation_flags_RESERVATION_FLAG_FLEX;
+        const NO_HOLD_JOBS = fi_slurm_sys::bind_reservation_flags_RESERVATION_FLAG_NO_HOLD_JOBS;
+        const REPLACE_DOWN = fi_slurm_sys::bind_reservation_flags_RESERVATION_FLAG_REPLACE_DOWN;
+        const MAGNETIC = fi_slurm_sys::bind_reservation_flags_RESERVATION_FLAG_MAGNETIC;
+        const HOURLY = fi_slurm_sys::bind_reservation_flags_RESERVATION_FLAG_HOURLY;
+    }
+}
+
+/// Splits a comma-separated Slurm list, such as the users of a reservation, dropping empty items
+fn split_list(list: &str) -> Vec<String> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// A safe, owned representation of a Slurm reservation
+///
+/// Users and accounts are listed as Slurm reports them, so an entry prefixed with '-' is excluded
+/// from the reservation rather than granted access to it
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Reservation {
+    pub name: String,
+    pub users: Vec<String>,
+    pub accounts: Vec<String>,
+    pub groups: Vec<String>,
+    pub raw_hostlist: String,
+    pub node_names: Vec<String>,
+    pub node_count: u32,
+    pub core_count: u32,
+    pub partition: String,
+    pub features: String,
+    pub start_time: DateTime<Utc>,
+    pub end_time: DateTime<Utc>,
+    pub flags: ReservationFlags,
+    pub tres: String,
+    pub comment: String,
+}
+
+impl Reservation {
+    /// Creates a safe, owned Rust `Reservation` from a raw C `reserve_info_t` struct
+    pub fn from_raw_binding(raw_reservation: &reserve_info_t) -> Result<Self, SlurmError> {
+        let raw_hostlist = unsafe { c_str_to_string(raw_reservation.node_list) };
+        let node_names = parse_slurm_hostlist(&raw_hostlist);
+
+        Ok(Reservation {
+            name: unsafe { c_str_to_string(raw_reservation.name) },
+            users: split_list(&unsafe { c_str_to_string(raw_reservation.users) }),
+            accounts: split_list(&unsafe { c_str_to_string(raw_reservation.accounts) }),
+            groups: split_list(&unsafe { c_str_to_string(raw_reservation.groups) }),
+            raw_hostlist,
+            node_names,
+            node_count: raw_reservation.node_cnt,
+            core_count: raw_reservation.core_cnt,
+            partition: unsafe { c_str_to_string(raw_reservation.partition) },
+            features: unsafe { c_str_to_string(raw_reservation.features) },
+            start_time: time_t_to_datetime(raw_reservation.start_time),
+            end_time: time_t_to_datetime(raw_reservation.end_time),
+            flags: ReservationFlags::from_bits_truncate(raw_reservation.flags),
+            tres: unsafe { c_str_to_string(raw_reservation.tres_str) },
+            comment: unsafe { c_str_to_string(raw_reservation.comment) },
+        })
+    }
+
+    /// Whether the reservation holds its nodes at the given time
+    pub fn is_active_at(&self, time: DateTime<Utc>) -> bool {
+        self.start_time <= time && time < self.end_time
+    }
+
+    /// Whether the reservation holds its nodes at some time between `start` and `end`
+    pub fn overlaps(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> bool {
+        self.start_time < end && start < self.end_time
+    }
+
+    /// Whether this is a maintenance reservation, during which nodes are not usable
+    pub fn is_maintenance(&self) -> bool {
+        self.flags.contains(ReservationFlags::MAINT)
+    }
+
+    /// Whether the named node belongs to this reservation
+    pub fn contains_node(&self, node_name: &str) -> bool {
+        self.node_names.iter().any(|name| name == node_name)
+    }
+}
+
+/// The collection of reservations returned by `get_reservations`
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SlurmReservations {
+    pub reservations: Vec<Reservation>,
+    pub last_update: DateTime<Utc>,
+}
+
+impl SlurmReservations {
+    /// Looks up a reservation by name
+    pub fn get(&self, name: &str) -> Option<&Reservation> {
+        self.reservations.iter().find(|r| r.name == name)
+    }
+
+    /// The reservations that hold their nodes at the given time
+    pub fn active_at(&self, time: DateTime<Utc>) -> impl Iterator<Item = &Reservation> {
+        self.reservations
+            .iter()
+            .filter(move |r| r.is_active_at(time))
+    }
+
+    /// The reservations that hold their nodes at some time between `start` and `end`
+    pub fn overlapping(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> impl Iterator<Item = &Reservation> {
+        self.reservations
+            .iter()
+            .filter(move |r| r.overlaps(start, end))
+    }
+
+    /// The reservations that include the named node, whether or not they have started
+    pub fn for_node<'a>(&'a self, node_name: &'a str) -> impl Iterator<Item = &'a Reservation> {
+        self.reservations
+            .iter()
+            .filter(move |r| r.contains_node(node_name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_list() {
+        assert_eq!(split_list("alice,-bob"), vec!["alice", "-bob"]);
+        assert!(split_list("").is_empty());
+    }
+
+    #[test]
+    fn test_overlaps() {
+        let at = |hour: i64| DateTime::from_timestamp(hour * 3600, 0).unwrap();
+        let reservation = Reservation {
+            name: "maint".to_string(),
+            users: vec![],
+            accounts: vec![],
+            groups: vec![],
+            raw_hostlist: String::new(),
+            node_names: vec![],
+            node_count: 0,
+            core_count: 0,
+            partition: String::new(),
+            features: String::new(),
+            start_time: at(10),
+            end_time: at(12),
+            flags: ReservationFlags::empty(),
+            tres: String::new(),
+            comment: String::new(),
+        };
+        assert!(reservation.overlaps(at(0), at(11)));
+        assert!(reservation.overlaps(at(11), at(20)));
+        assert!(!reservation.overlaps(at(0), at(10)));
+        assert!(!reservation.overlaps(at(12), at(20)));
+    }
+}
@@ -1,25 +1,225 @@
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
 use std::env;
 use std::fs;
+use std::path::PathBuf;
 use std::sync::OnceLock;
 
 static SITE_FN: &str = "site.conf";
+static HIDDEN_FEATURES_FN: &str = "hidden_features.conf";
+static CENTERS_FN: &str = "centers.conf";
+static LIMIT_QOS_FN: &str = "limit_qos.conf";
+
+/// Environment variable overriding the hidden features list, as a comma-separated list
+pub static HIDDEN_FEATURES_ENV: &str = "FI_HIDDEN_FEATURES";
+
+/// Environment variable overriding the QOS whose limits are looked up, as a comma-separated list
+pub static LIMIT_QOS_ENV: &str = "FI_LIMIT_QOS";
+
+// the features hidden by default at Flatiron, used when no site configuration is present
+static DEFAULT_HIDDEN_FEATURES: &[&str] = &[
+    "rocky8", "rocky9", "sxm", "sxm2", "sxm4", "sxm5", "nvlink", "a100", "h100", "v100", "ib",
+];
 
 // Static global storage that will be initialized on first access
 static CLUSTER: OnceLock<Option<String>> = OnceLock::new();
+static HIDDEN_FEATURES: OnceLock<HashSet<String>> = OnceLock::new();
+static ACCOUNT_CENTERS: OnceLock<HashMap<String, String>> = OnceLock::new();
+static LIMIT_QOS: OnceLock<Option<Vec<String>>> = OnceLock::new();
+
+/// Returns the path of a site configuration file located next to the running binary
+fn site_file(name: &str) -> Option<PathBuf> {
+    let exe_path = env::current_exe().ok()?;
+    Some(exe_path.parent()?.join(name))
+}
 
 /// Returns the cluster configuration from site.conf
 /// The file is read only on first access and its contents are cached
 pub fn cluster() -> &'static Option<String> {
-    CLUSTER.get_or_init(|| {
-        // Try to read from site.conf in the binary's directory
-        if let Ok(exe_path) = env::current_exe()
-            && let Some(exe_dir) = exe_path.parent()
+    CLUSTER.get_or_init(site_conf_cluster)
+}
+
+/// Sets the cluster in place of site.conf, as given to a --cluster option
+///
+/// Must be called before the cluster is first read, and returns whether it was in time
+pub fn set_cluster(name: impl Into<String>) -> bool {
+    CLUSTER.set(Some(name.into())).is_ok()
+}
+
+/// Falls back on the given cluster, such as the ClusterName of slurm.conf, if site.conf names
+/// none and no cluster has been set, so that a binary needn't be configured for each cluster
+pub fn set_local_cluster(cluster_name: Option<String>) -> &'static Option<String> {
+    CLUSTER.get_or_init(|| site_conf_cluster().or(cluster_name))
+}
+
+fn site_conf_cluster() -> Option<String> {
+    // Try to read from site.conf in the binary's directory
+    let content = fs::read_to_string(site_file(SITE_FN)?).ok()?;
+    let cluster = content.trim();
+    (!cluster.is_empty()).then(|| cluster.to_string())
+}
+
+/// Returns the set of uninformative or redundant node features excluded from default
+/// presentation, such as OS versions or interconnect details.
+///
+/// The list is taken from the `FI_HIDDEN_FEATURES` environment variable if set, otherwise
+/// from hidden_features.conf in the binary's directory, falling back to the Flatiron defaults.
+/// It is read only on first access and cached
+pub fn hidden_features() -> &'static HashSet<String> {
+    HIDDEN_FEATURES.get_or_init(|| {
+        if let Ok(list) = env::var(HIDDEN_FEATURES_ENV) {
+            return parse_feature_list(&list);
+        }
+        if let Some(conf_path) = site_file(HIDDEN_FEATURES_FN)
+            && let Ok(content) = fs::read_to_string(&conf_path)
         {
-            let conf_path = exe_dir.join(SITE_FN);
-            if let Ok(content) = fs::read_to_string(&conf_path) {
-                return Some(content.trim().to_string());
-            }
+            return parse_feature_list(&content);
         }
-        None
+        DEFAULT_HIDDEN_FEATURES
+            .iter()
+            .map(|f| f.to_string())
+            .collect()
+    })
+}
+
+/// Returns the QOS whose limits fi-slurm-limits looks up for each association, besides the QOS
+/// named after its account, if the site lists them.
+///
+/// The list is taken from the `FI_LIMIT_QOS` environment variable if set, otherwise from
+/// limit_qos.conf in the binary's directory, in the same format as hidden_features.conf. Without
+/// either, it is None, and the QOS each association may use are looked up instead. It is read
+/// only on first access and cached
+pub fn limit_qos() -> &'static Option<Vec<String>> {
+    LIMIT_QOS.get_or_init(|| {
+        let list = env::var(LIMIT_QOS_ENV).ok().or_else(|| {
+            site_file(LIMIT_QOS_FN).and_then(|conf_path| fs::read_to_string(conf_path).ok())
+        })?;
+        let mut names: Vec<String> = parse_feature_list(&list).into_iter().collect();
+        names.sort();
+        Some(names)
     })
 }
+
+/// Parses a list of features separated by commas or whitespace, ignoring `#` comments
+fn parse_feature_list(content: &str) -> HashSet<String> {
+    content
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default())
+        .flat_map(|line| line.split(|c: char| c == ',' || c.is_whitespace()))
+        .filter(|f| !f.is_empty())
+        .map(|f| f.to_string())
+        .collect()
+}
+
+/// Returns the mapping of Slurm accounts to the centers they belong to, read from
+/// centers.conf in the binary's directory as `account = center` lines.
+///
+/// Without the file the mapping is empty. It is read only on first access and cached
+pub fn account_centers() -> &'static HashMap<String, String> {
+    ACCOUNT_CENTERS.get_or_init(|| {
+        site_file(CENTERS_FN)
+            .and_then(|conf_path| fs::read_to_string(conf_path).ok())
+            .map(|content| parse_center_map(&content))
+            .unwrap_or_default()
+    })
+}
+
+/// The center an account belongs to. Accounts missing from the mapping are taken to be
+/// centers of their own, as most Flatiron accounts are
+pub fn center_of(account: &str) -> &str {
+    account_centers()
+        .get(account)
+        .map(String::as_str)
+        .unwrap_or(account)
+}
+
+/// The accounts that belong to a center: those mapped to it in centers.conf, and the center
+/// itself, which is usually an account of its own
+pub fn accounts_of(center: &str) -> Vec<String> {
+    accounts_in(account_centers(), center)
+}
+
+fn accounts_in(centers: &HashMap<String, String>, center: &str) -> Vec<String> {
+    let mut accounts: Vec<String> = centers
+        .iter()
+        .filter(|(_, c)| c.as_str() == center)
+        .map(|(account, _)| account.clone())
+        .chain(std::iter::once(center.to_string()))
+        .collect();
+    accounts.sort();
+    accounts.dedup();
+    accounts
+}
+
+/// Adds up values kept per account, such as usage series, into values per center, combining
+/// those of accounts of the same center with `merge`
+pub fn fold_by_center<T>(
+    by_account: impl IntoIterator<Item = (String, T)>,
+    mut merge: impl FnMut(&mut T, T),
+) -> HashMap<String, T> {
+    let mut by_center: HashMap<String, T> = HashMap::new();
+    for (account, value) in by_account {
+        match by_center.entry(center_of(&account).to_string()) {
+            Entry::Occupied(mut entry) => merge(entry.get_mut(), value),
+            Entry::Vacant(entry) => {
+                entry.insert(value);
+            }
+        }
+    }
+    by_center
+}
+
+/// Parses `account = center` lines, ignoring blank lines, malformed lines and `#` comments
+fn parse_center_map(content: &str) -> HashMap<String, String> {
+    content
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default())
+        .filter_map(|line| line.split_once('='))
+        .map(|(account, center)| (account.trim(), center.trim()))
+        .filter(|(account, center)| !account.is_empty() && !center.is_empty())
+        .map(|(account, center)| (account.to_string(), center.to_string()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_feature_list() {
+        let content = "# OS versions\nrocky8, rocky9\nsxm nvlink # interconnect\n\n";
+        let expected: HashSet<String> = ["rocky8", "rocky9", "sxm", "nvlink"]
+            .iter()
+            .map(|f| f.to_string())
+            .collect();
+        assert_eq!(parse_feature_list(content), expected);
+    }
+
+    #[test]
+    fn test_parse_empty_feature_list() {
+        assert!(parse_feature_list("").is_empty());
+    }
+
+    #[test]
+    fn test_parse_center_map() {
+        let map = parse_center_map(
+            "# subaccounts
+cca-gw = cca
+  scc=scc # itself
+bogus
+ = x
+",
+        );
+        assert_eq!(map.len(), 2);
+        assert_eq!(map["cca-gw"], "cca");
+        assert_eq!(map["scc"], "scc");
+    }
+
+    #[test]
+    fn test_accounts_in_center() {
+        let map = parse_center_map("cca-gw = cca\ncca-sims = cca\nccq-x = ccq\n");
+        assert_eq!(accounts_in(&map, "cca"), vec!["cca", "cca-gw", "cca-sims"]);
+        // a center missing from the mapping is an account of its own
+        assert_eq!(accounts_in(&map, "scc"), vec!["scc"]);
+    }
+}
@@ -0,0 +1,107 @@
+//! Where the binaries get the state of the cluster from
+//!
+//! The reports only need a `ClusterState`, so taking it from a `ClusterDataSource` rather than
+//! from slurmctld directly lets them run on a saved snapshot, and lets tests and CI run the full
+//! report pipeline without a cluster.
+
+use crate::cluster_state::ClusterState;
+use crate::error::SlurmError;
+#[cfg(feature = "serde")]
+use std::path::PathBuf;
+
+/// A source of the nodes, jobs and partitions of a cluster
+pub trait ClusterDataSource {
+    /// Loads the state of the cluster, with the jobs cross-referenced against the nodes
+    fn load(&self) -> Result<ClusterState, SlurmError>;
+
+    /// Whether loading goes through libslurm, which must be initialized first
+    fn needs_slurm(&self) -> bool {
+        false
+    }
+}
+
+/// The live cluster, loaded from the Slurm controller
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SlurmController;
+
+impl ClusterDataSource for SlurmController {
+    fn load(&self) -> Result<ClusterState, SlurmError> {
+        ClusterState::load()
+    }
+
+    fn needs_slurm(&self) -> bool {
+        true
+    }
+}
+
+/// A fixture file: a JSON snapshot of a cluster written by `ClusterState::save`
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone)]
+pub struct FixtureFile {
+    pub path: PathBuf,
+}
+
+#[cfg(feature = "serde")]
+impl FixtureFile {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        FixtureFile { path: path.into() }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl ClusterDataSource for FixtureFile {
+    fn load(&self) -> Result<ClusterState, SlurmError> {
+        ClusterState::from_snapshot(&self.path.to_string_lossy())
+    }
+}
+
+#[cfg(feature = "synthetic")]
+impl ClusterDataSource for crate::synthetic::SyntheticCluster {
+    fn load(&self) -> Result<ClusterState, SlurmError> {
+        Ok(self.build())
+    }
+}
+
+/// The source the binaries' `--state-file` option asks for: the fixture file if one is given,
+/// or else the live cluster
+#[cfg(feature = "serde")]
+pub fn source_for(state_file: Option<&str>) -> Box<dyn ClusterDataSource> {
+    match state_file {
+        Some(path) => Box::new(FixtureFile::new(path)),
+        None => Box::new(SlurmController),
+    }
+}
+
+#[cfg(all(test, feature = "serde", feature = "synthetic"))]
+mod tests {
+    use super::*;
+    use crate::synthetic::SyntheticCluster;
+
+    #[test]
+    fn test_fixture_round_trip() {
+        let cluster = SyntheticCluster::new(200, 1_000);
+        let state = cluster.load().unwrap();
+        let path =
+            std::env::temp_dir().join(format!("fi-slurm-fixture-{}.json", std::process::id()));
+        state.save(&path.to_string_lossy()).unwrap();
+
+        let fixture = FixtureFile::new(&path);
+        assert!(!fixture.needs_slurm());
+        let loaded = fixture.load();
+        let _ = std::fs::remove_file(&path);
+        let loaded = loaded.unwrap();
+
+        assert_eq!(loaded.nodes.nodes.len(), 200);
+        assert_eq!(loaded.jobs.jobs.len(), 1_000);
+        // the node to job map isn't saved, but rebuilt on loading
+        let sorted = |state: &ClusterState| {
+            let mut map: Vec<(usize, Vec<u32>)> =
+                state.node_to_job_map.clone().into_iter().collect();
+            map.iter_mut().for_each(|(_, jobs)| jobs.sort());
+            map.sort();
+            map
+        };
+        assert!(!state.node_to_job_map.is_empty());
+        assert_eq!(sorted(&loaded), sorted(&state));
+    }
+}
@@ -2,9 +2,11 @@ use bitflags::bitflags;
 // bitflags has no dependencies of its own, and is already required as a dependency by bindgen and
 // crossterm. As a result, it's not recommended for dependency pruning
 use fi_slurm_sys;
+use std::fmt;
 
 bitflags! {
     #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     pub struct NodeStateFlags: u32 {
         const NET = fi_slurm_sys::bind_node_state_flags_NET;
         const RES = fi_slurm_sys::bind_node_state_flags_RES;
@@ -33,6 +35,24 @@ bitflags! {
     }
 }
 
+impl NodeStateFlags {
+    /// The flags that keep a node from taking new work, even when it is idle or mixed
+    pub const UNAVAILABLE: Self = Self::MAINT.union(Self::DRAIN).union(Self::INVALID_REG);
+}
+
+/// Displays the set flags by name, joined as Slurm does, such as `DRAIN+MAINT`
+impl fmt::Display for NodeStateFlags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (name, _)) in self.iter_names().enumerate() {
+            if i > 0 {
+                write!(f, "+")?;
+            }
+            write!(f, "{}", name)?;
+        }
+        Ok(())
+    }
+}
+
 bitflags! {
     #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
     pub struct ShowFlags: u16 {
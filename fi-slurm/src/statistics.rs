@@ -0,0 +1,258 @@
+use crate::error::SlurmError;
+use crate::utils::time_t_to_datetime;
+use chrono::{DateTime, Utc};
+use fi_slurm_sys::{
+    STAT_COMMAND_GET, slurm_free_stats_response_msg, slurm_get_statistics,
+    stats_info_request_msg_t, stats_info_response_msg_t,
+};
+
+/// We use this struct to manage the C-allocated memory,
+/// automatically dropping it when it goes out of memory
+pub struct RawSlurmStatistics {
+    ptr: *mut stats_info_response_msg_t,
+}
+
+impl Drop for RawSlurmStatistics {
+    fn drop(&mut self) {
+        if !self.ptr.is_null() {
+            unsafe {
+                slurm_free_stats_response_msg(self.ptr);
+            }
+            self.ptr = std::ptr::null_mut();
+        }
+    }
+}
+
+/// Copies `len` values from a C array that may be null into a Vec
+///
+/// # Safety
+///
+/// A non-null `ptr` must point to at least `len` values
+unsafe fn array_to_vec<T: Copy>(ptr: *const T, len: u32) -> Vec<T> {
+    if ptr.is_null() {
+        Vec::new()
+    } else {
+        unsafe { std::slice::from_raw_parts(ptr, len as usize) }.to_vec()
+    }
+}
+
+impl RawSlurmStatistics {
+    /// Loads the scheduler statistics from the Slurm controller, as sdiag does
+    pub fn load() -> Result<Self, SlurmError> {
+        let mut stats_ptr: *mut stats_info_response_msg_t = std::ptr::null_mut();
+        let mut request = stats_info_request_msg_t {
+            command_id: STAT_COMMAND_GET as u16,
+        };
+
+        let return_code = unsafe { slurm_get_statistics(&mut stats_ptr, &mut request) };
+
+        if return_code != 0 {
+            Err(SlurmError::from_errno(
+                "load scheduler statistics from Slurm",
+            ))
+        } else if stats_ptr.is_null() {
+            Err(SlurmError::NullResponse {
+                action: "load scheduler statistics from Slurm",
+            })
+        } else {
+            Ok(RawSlurmStatistics { ptr: stats_ptr })
+        }
+    }
+
+    /// Consumes the wrapper to transform the raw C data into a safe, owned
+    /// `SchedulerStatistics`
+    pub fn into_scheduler_statistics(self) -> SchedulerStatistics {
+        let raw = unsafe { &*self.ptr };
+
+        let rpc_types = unsafe {
+            let ids = array_to_vec(raw.rpc_type_id, raw.rpc_type_size);
+            let counts = array_to_vec(raw.rpc_type_cnt, raw.rpc_type_size);
+            let times = array_to_vec(raw.rpc_type_time, raw.rpc_type_size);
+            rpc_stats(ids.into_iter().map(u32::from), counts, times)
+        };
+        let rpc_users = unsafe {
+            let ids = array_to_vec(raw.rpc_user_id, raw.rpc_user_size);
+            let counts = array_to_vec(raw.rpc_user_cnt, raw.rpc_user_size);
+            let times = array_to_vec(raw.rpc_user_time, raw.rpc_user_size);
+            rpc_stats(ids, counts, times)
+        };
+
+        SchedulerStatistics {
+            taken_at: time_t_to_datetime(raw.req_time),
+            counting_since: time_t_to_datetime(raw.req_time_start),
+            server_thread_count: raw.server_thread_count,
+            agent_queue_size: raw.agent_queue_size,
+            dbd_agent_queue_size: raw.dbd_agent_queue_size,
+            jobs_submitted: raw.jobs_submitted,
+            jobs_started: raw.jobs_started,
+            jobs_completed: raw.jobs_completed,
+            jobs_canceled: raw.jobs_canceled,
+            jobs_failed: raw.jobs_failed,
+            jobs_pending: raw.jobs_pending,
+            jobs_running: raw.jobs_running,
+            main: CycleStatistics {
+                cycles: raw.schedule_cycle_counter,
+                last_cycle_us: raw.schedule_cycle_last,
+                max_cycle_us: raw.schedule_cycle_max,
+                total_cycle_us: raw.schedule_cycle_sum as u64,
+                depth_sum: raw.schedule_cycle_depth as u64,
+                queue_len: raw.schedule_queue_len,
+            },
+            backfill: CycleStatistics {
+                cycles: raw.bf_cycle_counter,
+                last_cycle_us: raw.bf_cycle_last,
+                max_cycle_us: raw.bf_cycle_max,
+                total_cycle_us: raw.bf_cycle_sum,
+                depth_sum: raw.bf_depth_sum as u64,
+                queue_len: raw.bf_queue_len,
+            },
+            backfill_active: raw.bf_active != 0,
+            backfill_last_depth: raw.bf_last_depth,
+            backfill_last_depth_try: raw.bf_last_depth_try,
+            backfilled_jobs: raw.bf_backfilled_jobs,
+            backfill_last_cycle_at: (raw.bf_when_last_cycle != 0)
+                .then(|| time_t_to_datetime(raw.bf_when_last_cycle)),
+            rpc_types,
+            rpc_users,
+        }
+    }
+}
+
+/// Zips the parallel arrays of RPC statistics, busiest first
+fn rpc_stats(
+    ids: impl IntoIterator<Item = u32>,
+    counts: Vec<u32>,
+    times: Vec<u64>,
+) -> Vec<RpcStatistics> {
+    let mut stats: Vec<RpcStatistics> = ids
+        .into_iter()
+        .zip(counts)
+        .zip(times)
+        .map(|((id, count), total_time_us)| RpcStatistics {
+            id,
+            count,
+            total_time_us,
+        })
+        .collect();
+    stats.sort_by_key(|stat| std::cmp::Reverse(stat.total_time_us));
+    stats
+}
+
+/// The mean of a total over a number of samples, or None without samples
+fn mean(total: u64, count: u32) -> Option<u64> {
+    (count > 0).then(|| total / count as u64)
+}
+
+/// The statistics of one of the scheduler's loops, the main one or backfill, since the
+/// counters were last reset
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CycleStatistics {
+    pub cycles: u32,
+    pub last_cycle_us: u32,
+    pub max_cycle_us: u32,
+    pub total_cycle_us: u64,
+    /// The jobs considered over all cycles
+    pub depth_sum: u64,
+    /// The jobs in the queue the last cycle went through
+    pub queue_len: u32,
+}
+
+impl CycleStatistics {
+    /// The mean time of a cycle, in microseconds
+    pub fn mean_cycle_us(&self) -> Option<u64> {
+        mean(self.total_cycle_us, self.cycles)
+    }
+
+    /// The mean number of jobs a cycle considered
+    pub fn mean_depth(&self) -> Option<u64> {
+        mean(self.depth_sum, self.cycles)
+    }
+}
+
+/// The RPCs slurmctld has handled of one message type or from one user
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RpcStatistics {
+    /// The message type, as numbered in Slurm's slurm_protocol_defs.h, or the user id
+    pub id: u32,
+    pub count: u32,
+    pub total_time_us: u64,
+}
+
+impl RpcStatistics {
+    /// The mean time slurmctld took over one of these RPCs, in microseconds
+    pub fn mean_time_us(&self) -> Option<u64> {
+        mean(self.total_time_us, self.count)
+    }
+}
+
+/// A safe, owned copy of the statistics slurmctld keeps on its scheduling, as shown by sdiag
+///
+/// The counters are reset at midnight and whenever an admin runs `sdiag --reset`, so they cover
+/// the time since `counting_since`
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SchedulerStatistics {
+    pub taken_at: DateTime<Utc>,
+    pub counting_since: DateTime<Utc>,
+    pub server_thread_count: u32,
+    pub agent_queue_size: u32,
+    /// Messages waiting to be sent to slurmdbd, which grow while it is unreachable
+    pub dbd_agent_queue_size: u32,
+    pub jobs_submitted: u32,
+    pub jobs_started: u32,
+    pub jobs_completed: u32,
+    pub jobs_canceled: u32,
+    pub jobs_failed: u32,
+    pub jobs_pending: u32,
+    pub jobs_running: u32,
+    pub main: CycleStatistics,
+    pub backfill: CycleStatistics,
+    /// Whether a backfill cycle is running now
+    pub backfill_active: bool,
+    pub backfill_last_depth: u32,
+    /// The jobs the last backfill cycle tried to schedule, as opposed to only looked at
+    pub backfill_last_depth_try: u32,
+    pub backfilled_jobs: u32,
+    pub backfill_last_cycle_at: Option<DateTime<Utc>>,
+    /// By message type, the busiest first
+    pub rpc_types: Vec<RpcStatistics>,
+    /// By user id, the busiest first
+    pub rpc_users: Vec<RpcStatistics>,
+}
+
+/// Fetches the scheduler statistics from Slurm and returns them as a safe,
+/// owned Rust data structure
+pub fn get_scheduler_statistics() -> Result<SchedulerStatistics, SlurmError> {
+    Ok(RawSlurmStatistics::load()?.into_scheduler_statistics())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rpc_stats_busiest_first() {
+        let stats = rpc_stats([1001, 2003, 4019], vec![10, 200, 3], vec![500, 40_000, 90]);
+        let ids: Vec<u32> = stats.iter().map(|s| s.id).collect();
+        assert_eq!(ids, [2003, 1001, 4019]);
+        assert_eq!(stats[0].mean_time_us(), Some(200));
+
+        // arrays of different lengths are cut to the shortest
+        assert_eq!(rpc_stats([1, 2], vec![1], vec![1, 2]).len(), 1);
+    }
+
+    #[test]
+    fn test_cycle_means() {
+        let cycle = CycleStatistics {
+            cycles: 4,
+            total_cycle_us: 1_000,
+            depth_sum: 42,
+            ..Default::default()
+        };
+        assert_eq!(cycle.mean_cycle_us(), Some(250));
+        assert_eq!(cycle.mean_depth(), Some(10));
+        assert_eq!(CycleStatistics::default().mean_cycle_us(), None);
+    }
+}
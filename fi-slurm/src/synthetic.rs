@@ -0,0 +1,346 @@
+//! Synthetic clusters of any size, for benchmarks and tests that need more nodes and jobs than a
+//! test cluster has
+//!
+//! The clusters are made of a few kinds of CPU and GPU nodes, with the features a real cluster
+//! gives them, and of running and pending jobs. Running jobs are placed on nodes with free CPUs,
+//! and the node states follow from what is placed on them, so the reports see a consistent
+//! cluster. The same size and seed always give the same cluster.
+
+use crate::cluster_state::ClusterState;
+use crate::gres::GpuEquivalents;
+use crate::jobs::{
+    Job, JobState, MemoryRequest, SlurmJobs, build_node_to_job_map, enrich_jobs_with_node_ids,
+};
+use crate::nodes::{GpuInfo, Node, NodeState, SlurmNodes};
+use crate::parser::compress_hostlist;
+use crate::partitions::SlurmPartitions;
+use crate::states::NodeStateFlags;
+use chrono::{DateTime, Duration, Utc};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// The kinds of nodes, as (features, CPUs, GPU type, GPUs), taken in turn by blocks of nodes
+const NODE_KINDS: &[(&[&str], u16, &str, u64)] = &[
+    (&["cpu", "genoa", "ib"], 192, "", 0),
+    (&["cpu", "icelake", "ib"], 64, "", 0),
+    (&["cpu", "rome", "eth"], 128, "", 0),
+    (&["gpu", "h100", "ib"], 64, "h100", 8),
+    (&["gpu", "a100", "ib"], 64, "a100", 4),
+    (&["gpu", "v100", "eth"], 40, "v100", 4),
+];
+
+/// The nodes of a kind that are named and numbered together, like the nodes of a rack
+const BLOCK_SIZE: usize = 64;
+
+/// A small, deterministic random number generator (xorshift64*), so that a seed always gives
+/// the same cluster without depending on a random number crate
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        // xorshift is stuck at zero
+        Rng(seed.max(1))
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    /// A number in `low..=high`
+    fn range(&mut self, low: u64, high: u64) -> u64 {
+        low + self.next() % (high - low + 1)
+    }
+
+    /// True with the given chance, in percent
+    fn chance(&mut self, percent: u64) -> bool {
+        self.next() % 100 < percent
+    }
+}
+
+/// The size of a synthetic cluster, and the seed it is generated from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SyntheticCluster {
+    pub nodes: usize,
+    /// The jobs, running and pending; running jobs that don't fit on the nodes are left pending
+    pub jobs: usize,
+    pub seed: u64,
+}
+
+impl Default for SyntheticCluster {
+    /// A cluster the size of a large site, with 10,000 nodes and 100,000 jobs
+    fn default() -> Self {
+        SyntheticCluster {
+            nodes: 10_000,
+            jobs: 100_000,
+            seed: 1,
+        }
+    }
+}
+
+impl SyntheticCluster {
+    pub fn new(nodes: usize, jobs: usize) -> Self {
+        SyntheticCluster {
+            nodes,
+            jobs,
+            ..Default::default()
+        }
+    }
+
+    /// The nodes of the cluster, all idle, or down for a few of them
+    pub fn build_nodes(&self) -> SlurmNodes {
+        let mut rng = Rng::new(self.seed);
+        let kinds: Vec<(Vec<Arc<str>>, u16, &str, u64)> = NODE_KINDS
+            .iter()
+            .map(|&(features, cpus, gpu_type, gpus)| {
+                let features = features.iter().map(|&feature| Arc::from(feature)).collect();
+                (features, cpus, gpu_type, gpus)
+            })
+            .collect();
+
+        let mut nodes = Vec::with_capacity(self.nodes);
+        let mut name_to_id = HashMap::with_capacity(self.nodes);
+        for id in 0..self.nodes {
+            let block = id / BLOCK_SIZE;
+            let (features, cpus, gpu_type, gpus) = &kinds[block % kinds.len()];
+            let name = format!("worker{:03}{:02}", block, id % BLOCK_SIZE);
+
+            let state = if rng.chance(2) {
+                NodeState::Down
+            } else if rng.chance(2) {
+                NodeState::Compound {
+                    base: Box::new(NodeState::Idle),
+                    flags: NodeStateFlags::DRAIN,
+                }
+            } else {
+                NodeState::Idle
+            };
+            let gpu_info = (*gpus > 0).then(|| GpuInfo {
+                name: format!("gpu:{}", gpu_type),
+                total_gpus: *gpus,
+                equivalents: GpuEquivalents {
+                    configured: *gpus as f64,
+                    allocated: 0.0,
+                },
+                ..Default::default()
+            });
+
+            name_to_id.insert(name.clone(), id);
+            nodes.push(Node::new(
+                id,
+                name,
+                state,
+                *cpus,
+                features.clone(),
+                gpu_info,
+            ));
+        }
+
+        SlurmNodes {
+            nodes,
+            name_to_id,
+            last_update: Utc::now(),
+            skip_count: 0,
+        }
+    }
+
+    /// The jobs of the cluster, running on the given nodes or pending
+    ///
+    /// The states of the nodes, and their allocated GPUs, are updated to match the jobs placed
+    /// on them. The jobs are not yet enriched with their node ids, so that
+    /// `enrich_jobs_with_node_ids` has their hostlists to parse
+    pub fn build_jobs(&self, nodes: &mut SlurmNodes) -> SlurmJobs {
+        let mut rng = Rng::new(self.seed.wrapping_add(1));
+        let now = Utc::now();
+        let mut free_cpus: Vec<u32> = nodes.nodes.iter().map(|n| n.cpus as u32).collect();
+        let mut jobs = HashMap::with_capacity(self.jobs);
+
+        for index in 0..self.jobs {
+            let job_id = 1_000_000 + index as u32;
+            let mut job = synthetic_job(job_id, &mut rng, now);
+
+            if !nodes.nodes.is_empty() && rng.chance(70) {
+                let node_count = if rng.chance(90) { 1 } else { rng.range(2, 4) } as usize;
+                let node_count = node_count.min(nodes.nodes.len());
+                let first = rng.range(0, nodes.nodes.len() as u64 - 1) as usize;
+                let placed = (first..first + node_count)
+                    .map(|id| id % nodes.nodes.len())
+                    .take_while(|&id| available(&nodes.nodes[id]))
+                    .collect::<Vec<usize>>();
+                let cpus_per_node = rng.range(1, 32) as u32;
+
+                if placed.len() == node_count
+                    && placed.iter().all(|&id| free_cpus[id] >= cpus_per_node)
+                {
+                    let mut names = Vec::with_capacity(node_count);
+                    for &id in &placed {
+                        let node = &mut nodes.nodes[id];
+                        free_cpus[id] -= cpus_per_node;
+                        let gpus = node.gpu_info.as_mut().map_or(0, |gpu| {
+                            let gpus = (gpu.total_gpus - gpu.allocated_gpus).min(1);
+                            gpu.allocated_gpus += gpus;
+                            gpu.equivalents.allocated += gpus as f64;
+                            gpus as u32
+                        });
+                        job.node_cpus.push(cpus_per_node);
+                        job.node_gpus.push(gpus);
+                        names.push(node.name.clone());
+                    }
+                    job.job_state = JobState::Running;
+                    job.state_reason = "None".to_string();
+                    job.start_time = now - Duration::minutes(rng.range(1, 600) as i64);
+                    job.end_time =
+                        job.start_time + Duration::minutes(job.time_limit_minutes as i64);
+                    job.num_nodes = node_count as u32;
+                    job.num_cpus = cpus_per_node * node_count as u32;
+                    job.raw_hostlist = compress_hostlist(&names);
+                    if rng.chance(10) {
+                        job.preemptable_time = job.start_time;
+                    }
+                }
+            }
+            jobs.insert(job_id, job);
+        }
+
+        for (node, free) in nodes.nodes.iter_mut().zip(free_cpus) {
+            if free < node.cpus as u32 {
+                let base = if free == 0 {
+                    NodeState::Allocated
+                } else {
+                    NodeState::Mixed
+                };
+                node.state = match &node.state {
+                    NodeState::Compound { flags, .. } => NodeState::Compound {
+                        base: Box::new(base),
+                        flags: *flags,
+                    },
+                    _ => base,
+                };
+            }
+        }
+
+        SlurmJobs {
+            jobs,
+            last_update: now,
+            last_backfill: now,
+        }
+    }
+
+    /// The whole cluster, with its jobs enriched and the node to job map built, as
+    /// `ClusterState::load` would give it
+    pub fn build(&self) -> ClusterState {
+        let mut nodes = self.build_nodes();
+        let mut jobs = self.build_jobs(&mut nodes);
+        enrich_jobs_with_node_ids(&mut jobs, &nodes.name_to_id);
+        let node_to_job_map = build_node_to_job_map(&jobs);
+        ClusterState {
+            nodes,
+            jobs,
+            partitions: SlurmPartitions {
+                partitions: Vec::new(),
+                last_update: Utc::now(),
+            },
+            node_to_job_map,
+            taken_at: Utc::now(),
+        }
+    }
+}
+
+/// Whether jobs can be placed on the node, which excludes down and draining nodes
+fn available(node: &Node) -> bool {
+    matches!(node.state, NodeState::Idle | NodeState::Mixed)
+}
+
+/// A pending job, which `build_jobs` starts on nodes if it places it
+fn synthetic_job(job_id: u32, rng: &mut Rng, now: DateTime<Utc>) -> Job {
+    let user = rng.range(0, 499) as u32;
+    let cpus = rng.range(1, 64) as u32;
+    let reasons = [
+        "Priority",
+        "Resources",
+        "QOSMaxCpuPerUserLimit",
+        "Dependency",
+    ];
+    Job {
+        job_id,
+        array_job_id: 0,
+        array_task_id: 0,
+        array_task_str: String::new(),
+        array_max_tasks: 0,
+        name: format!("job{}", job_id),
+        user_id: 10_000 + user,
+        user_name: format!("user{:03}", user),
+        group_id: 10_000 + user / 10,
+        partition: "gen".to_string(),
+        account: format!("center{}", user % 8),
+        qos: "gen".to_string(),
+        job_state: JobState::Pending,
+        state_description: String::new(),
+        state_reason: reasons[rng.range(0, reasons.len() as u64 - 1) as usize].to_string(),
+        priority: rng.range(1, 100_000) as u32,
+        submit_time: now - Duration::minutes(rng.range(1, 2_880) as i64),
+        start_time: DateTime::UNIX_EPOCH,
+        end_time: DateTime::UNIX_EPOCH,
+        time_limit_minutes: rng.range(1, 168) as u32 * 60,
+        preemptable_time: DateTime::UNIX_EPOCH,
+        num_nodes: 1,
+        num_cpus: cpus,
+        num_tasks: cpus,
+        raw_hostlist: String::new(),
+        node_ids: Vec::new(),
+        node_cpus: Vec::new(),
+        node_gpus: Vec::new(),
+        memory_request: MemoryRequest::PerCpu(4096),
+        allocated_gres: HashMap::new(),
+        gres_total: None,
+        requested_features: String::new(),
+        requested_tres: HashMap::from([("cpu".to_string(), cpus as u64)]),
+        licenses: String::new(),
+        work_dir: String::new(),
+        command: String::new(),
+        exit_code: 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_synthetic_cluster() {
+        let cluster = SyntheticCluster::new(500, 2_000);
+        let state = cluster.build();
+        assert_eq!(state.nodes.nodes.len(), 500);
+        assert_eq!(state.jobs.jobs.len(), 2_000);
+
+        // every running job landed on nodes it was placed on, and left them allocated or mixed
+        let running: Vec<&Job> = state
+            .jobs
+            .jobs
+            .values()
+            .filter(|job| job.job_state == JobState::Running)
+            .collect();
+        assert!(!running.is_empty());
+        for job in running {
+            assert_eq!(job.node_ids.len(), job.num_nodes as usize);
+            assert_eq!(job.node_cpus.len(), job.node_ids.len());
+            for &id in &job.node_ids {
+                let node = &state.nodes.nodes[id];
+                assert!(matches!(
+                    node.state,
+                    NodeState::Allocated | NodeState::Mixed
+                ));
+                assert!(state.node_to_job_map[&id].contains(&job.job_id));
+            }
+        }
+
+        // the same seed gives the same cluster
+        let again = cluster.build();
+        let states = |state: &ClusterState| -> Vec<NodeState> {
+            state.nodes.nodes.iter().map(|n| n.state.clone()).collect()
+        };
+        assert_eq!(states(&state), states(&again));
+    }
+}
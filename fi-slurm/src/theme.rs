@@ -0,0 +1,289 @@
+use colored::{Color, ColoredString, Colorize};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+static THEME_FN: &str = "theme.conf";
+
+/// Environment variable selecting a theme, as a preset name or the path of a palette file
+pub static THEME_ENV: &str = "FI_THEME";
+
+/// The names of the built-in palettes, as accepted by `--theme`
+pub static PRESETS: &[&str] = &["default", "colorblind"];
+
+/// The colors the reports use for each kind of information
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Palette {
+    // node states
+    pub idle: Color,
+    pub mixed: Color,
+    pub allocated: Color,
+    pub down: Color,
+    pub error: Color,
+    pub other: Color,
+    // availability bars
+    pub nodes: Color,
+    pub cpus: Color,
+    pub gpus: Color,
+    // utilization, from mostly free to nearly full
+    pub low: Color,
+    pub medium: Color,
+    pub high: Color,
+    // changes between snapshots
+    pub gain: Color,
+    pub loss: Color,
+    /// Preemptable resources and other things worth a second look
+    pub warning: Color,
+}
+
+impl Palette {
+    /// The colors fi-nodes has always used
+    pub const DEFAULT: Palette = Palette {
+        idle: Color::Green,
+        mixed: Color::Blue,
+        allocated: Color::Yellow,
+        down: Color::Red,
+        error: Color::Magenta,
+        other: Color::Cyan,
+        nodes: Color::Green,
+        cpus: Color::Cyan,
+        gpus: Color::Red,
+        low: Color::Green,
+        medium: Color::Yellow,
+        high: Color::Red,
+        gain: Color::Green,
+        loss: Color::Red,
+        warning: Color::Yellow,
+    };
+
+    /// The Okabe-Ito palette, which stays distinguishable with the common forms of color
+    /// blindness
+    pub const COLORBLIND: Palette = {
+        const BLUE: Color = rgb(0, 114, 178);
+        const SKY_BLUE: Color = rgb(86, 180, 233);
+        const BLUISH_GREEN: Color = rgb(0, 158, 115);
+        const ORANGE: Color = rgb(230, 159, 0);
+        const VERMILLION: Color = rgb(213, 94, 0);
+        const REDDISH_PURPLE: Color = rgb(204, 121, 167);
+        const YELLOW: Color = rgb(240, 228, 66);
+        Palette {
+            idle: BLUISH_GREEN,
+            mixed: SKY_BLUE,
+            allocated: ORANGE,
+            down: VERMILLION,
+            error: REDDISH_PURPLE,
+            other: BLUE,
+            nodes: BLUISH_GREEN,
+            cpus: BLUE,
+            gpus: VERMILLION,
+            low: SKY_BLUE,
+            medium: ORANGE,
+            high: VERMILLION,
+            gain: BLUE,
+            loss: VERMILLION,
+            warning: YELLOW,
+        }
+    };
+
+    /// Looks up a built-in palette by name
+    pub fn preset(name: &str) -> Option<Palette> {
+        match name {
+            "default" => Some(Palette::DEFAULT),
+            "colorblind" => Some(Palette::COLORBLIND),
+            _ => None,
+        }
+    }
+
+    fn role_mut(&mut self, role: &str) -> Option<&mut Color> {
+        Some(match role {
+            "idle" => &mut self.idle,
+            "mixed" => &mut self.mixed,
+            "allocated" => &mut self.allocated,
+            "down" => &mut self.down,
+            "error" => &mut self.error,
+            "other" => &mut self.other,
+            "nodes" => &mut self.nodes,
+            "cpus" => &mut self.cpus,
+            "gpus" => &mut self.gpus,
+            "low" => &mut self.low,
+            "medium" => &mut self.medium,
+            "high" => &mut self.high,
+            "gain" => &mut self.gain,
+            "loss" => &mut self.loss,
+            "warning" => &mut self.warning,
+            _ => return None,
+        })
+    }
+
+    /// Parses a palette file of `role = color` lines, where a color is a terminal color
+    /// name such as `red` or `bright blue`, or a `#rrggbb` hex code
+    ///
+    /// A `preset = <name>` line starts from that preset instead of the default; roles not
+    /// listed keep the preset's colors. `#` starts a comment unless it begins a hex code
+    pub fn parse(content: &str) -> Result<Palette, String> {
+        let mut palette = Palette::DEFAULT;
+        for (number, line) in content.lines().enumerate() {
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .map(|(key, value)| (key.trim(), value.trim()))
+                .ok_or_else(|| format!("line {}: expected 'role = color'", number + 1))?;
+            if key == "preset" {
+                palette = Palette::preset(value)
+                    .ok_or_else(|| format!("line {}: unknown preset '{}'", number + 1, value))?;
+                continue;
+            }
+            let color = parse_color(value)
+                .ok_or_else(|| format!("line {}: unknown color '{}'", number + 1, value))?;
+            *palette
+                .role_mut(key)
+                .ok_or_else(|| format!("line {}: unknown role '{}'", number + 1, key))? = color;
+        }
+        Ok(palette)
+    }
+}
+
+const fn rgb(r: u8, g: u8, b: u8) -> Color {
+    Color::TrueColor { r, g, b }
+}
+
+/// Drops a trailing `#` comment, keeping a `#` that starts a hex color after `=`
+fn strip_comment(line: &str) -> &str {
+    let mut after_equals = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '=' => after_equals = true,
+            '#' => {
+                let starts_value = after_equals && line[..i].trim_end().ends_with('=');
+                if !starts_value {
+                    return &line[..i];
+                }
+            }
+            _ => {}
+        }
+    }
+    line
+}
+
+/// Parses a color name, such as `red` or `bright blue`, or a `#rrggbb` hex code
+fn parse_color(value: &str) -> Option<Color> {
+    if let Some(hex) = value.strip_prefix('#') {
+        if hex.len() != 6 || !hex.is_ascii() {
+            return None;
+        }
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+        return Some(rgb(channel(0)?, channel(2)?, channel(4)?));
+    }
+    value.replace('_', " ").parse().ok()
+}
+
+/// The colors used by the reports, or none at all when color is turned off
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Theme {
+    pub palette: Palette,
+    pub enabled: bool,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            palette: Palette::DEFAULT,
+            enabled: true,
+        }
+    }
+}
+
+impl Theme {
+    /// Picks the theme for this run
+    ///
+    /// The palette comes from `choice` (the `--theme` flag) if given, otherwise from the
+    /// `FI_THEME` environment variable, otherwise from theme.conf in the binary's directory,
+    /// falling back to the default colors. A choice is either a preset name or the path of a
+    /// palette file, in the format read by `Palette::parse`
+    ///
+    /// Color is turned off entirely by `no_color` (the `--no-color` flag) or by setting the
+    /// `NO_COLOR` environment variable, per https://no-color.org
+    pub fn load(choice: Option<&str>, no_color: bool) -> Result<Theme, String> {
+        let enabled = !no_color && env::var_os("NO_COLOR").is_none_or(|value| value.is_empty());
+
+        let choice = choice
+            .map(str::to_string)
+            .or_else(|| env::var(THEME_ENV).ok());
+        let palette = match choice {
+            Some(choice) => match Palette::preset(&choice) {
+                Some(palette) => palette,
+                None => read_palette(Path::new(&choice)).map_err(|e| {
+                    format!(
+                        "theme '{}' is neither a preset ({}) nor a readable palette file: {}",
+                        choice,
+                        PRESETS.join(", "),
+                        e
+                    )
+                })?,
+            },
+            None => match site_theme_file() {
+                Some(path) if path.exists() => read_palette(&path)?,
+                _ => Palette::DEFAULT,
+            },
+        };
+
+        Ok(Theme { palette, enabled })
+    }
+
+    /// A theme that doesn't color anything
+    pub fn plain() -> Theme {
+        Theme {
+            enabled: false,
+            ..Theme::default()
+        }
+    }
+
+    /// Colors the text, unless color is turned off
+    pub fn paint(&self, text: &str, color: Color) -> ColoredString {
+        if self.enabled {
+            text.color(color)
+        } else {
+            text.normal()
+        }
+    }
+}
+
+fn site_theme_file() -> Option<PathBuf> {
+    let exe_path = env::current_exe().ok()?;
+    Some(exe_path.parent()?.join(THEME_FN))
+}
+
+fn read_palette(path: &Path) -> Result<Palette, String> {
+    let content = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    Palette::parse(&content).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_palette() {
+        let palette = Palette::parse(
+            "# high contrast tweaks\npreset = colorblind\nidle = bright green\ngpus = #FF00aa # pink\n\n",
+        )
+        .unwrap();
+        assert_eq!(palette.idle, Color::BrightGreen);
+        assert_eq!(palette.gpus, rgb(255, 0, 170));
+        assert_eq!(palette.down, Palette::COLORBLIND.down);
+
+        assert!(Palette::parse("idle = chartreuse").is_err());
+        assert!(Palette::parse("background = red").is_err());
+        assert!(Palette::parse("preset = neon").is_err());
+        assert!(Palette::parse("idle").is_err());
+    }
+
+    #[test]
+    fn test_plain_theme_paints_nothing() {
+        let painted = Theme::plain().paint("idle", Palette::DEFAULT.idle);
+        assert_eq!(painted.fgcolor, None);
+    }
+}
@@ -0,0 +1,194 @@
+use crate::error::SlurmError;
+use crate::parser::parse_slurm_hostlist;
+use crate::utils::c_str_to_string;
+use fi_slurm_sys::{
+    slurm_free_topo_info_msg, slurm_load_topo, topo_info_response_msg_t, topoinfo_switch_t,
+    topoinfo_tree_t,
+};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+// The plugin id of topology/tree in the plugin data of the response, from slurm.h
+const TOPOLOGY_PLUGIN_TREE: u32 = 102;
+
+/// We use this struct to manage the C-allocated memory,
+/// automatically dropping it when it goes out of memory
+pub struct RawSlurmTopologyInfo {
+    ptr: *mut topo_info_response_msg_t,
+}
+
+impl Drop for RawSlurmTopologyInfo {
+    fn drop(&mut self) {
+        if !self.ptr.is_null() {
+            unsafe {
+                slurm_free_topo_info_msg(self.ptr);
+            }
+            self.ptr = std::ptr::null_mut();
+        }
+    }
+}
+
+impl RawSlurmTopologyInfo {
+    /// Loads the switch topology from the Slurm controller
+    pub fn load() -> Result<Self, SlurmError> {
+        let mut topo_info_msg_ptr: *mut topo_info_response_msg_t = std::ptr::null_mut();
+
+        let return_code = unsafe { slurm_load_topo(&mut topo_info_msg_ptr) };
+
+        if return_code != 0 {
+            Err(SlurmError::from_errno(
+                "load topology information from Slurm",
+            ))
+        } else if topo_info_msg_ptr.is_null() {
+            Err(SlurmError::NullResponse {
+                action: "load topology information from Slurm",
+            })
+        } else {
+            Ok(RawSlurmTopologyInfo {
+                ptr: topo_info_msg_ptr,
+            })
+        }
+    }
+
+    /// The switches of a tree topology, or none if the cluster uses another topology plugin,
+    /// whose data isn't a list of switches
+    pub fn as_slice(&self) -> &[topoinfo_switch_t] {
+        if self.ptr.is_null() {
+            return &[];
+        }
+
+        unsafe {
+            let plugin_data = (*self.ptr).topo_info;
+            if plugin_data.is_null()
+                || (*plugin_data).plugin_id != TOPOLOGY_PLUGIN_TREE
+                || (*plugin_data).data.is_null()
+            {
+                return &[];
+            }
+            let tree = &*((*plugin_data).data as *const topoinfo_tree_t);
+            if tree.topo_array.is_null() {
+                return &[];
+            }
+            std::slice::from_raw_parts(tree.topo_array, tree.record_count as usize)
+        }
+    }
+
+    /// Consumes the wrapper to transform the raw C data into a safe, owned `SlurmTopology`
+    pub fn into_slurm_topology(self) -> SlurmTopology {
+        let switches = self
+            .as_slice()
+            .iter()
+            .map(|raw| unsafe {
+                Switch {
+                    name: c_str_to_string(raw.name),
+                    level: raw.level,
+                    link_speed: raw.link_speed,
+                    nodes: parse_slurm_hostlist(&c_str_to_string(raw.nodes)),
+                    switches: parse_slurm_hostlist(&c_str_to_string(raw.switches)),
+                }
+            })
+            .collect();
+        SlurmTopology { switches }
+    }
+}
+
+/// A network switch of the cluster, as configured in topology.conf
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Switch {
+    pub name: String,
+    /// The switch's level in the tree, where the leaf switches, to which nodes connect, are 0
+    pub level: u16,
+    pub link_speed: u32,
+    /// The nodes connected directly to the switch
+    pub nodes: Vec<String>,
+    /// The switches connected directly below this one
+    pub switches: Vec<String>,
+}
+
+/// The switch topology of the cluster, empty unless it uses the topology/tree plugin
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SlurmTopology {
+    pub switches: Vec<Switch>,
+}
+
+impl SlurmTopology {
+    /// The switches above each node, from the top of the tree down to its leaf switch
+    ///
+    /// Nodes missing from the topology are left out
+    pub fn node_paths(&self) -> HashMap<String, Vec<Arc<str>>> {
+        let parents: HashMap<&str, &str> = self
+            .switches
+            .iter()
+            .flat_map(|switch| {
+                switch
+                    .switches
+                    .iter()
+                    .map(|child| (child.as_str(), switch.name.as_str()))
+            })
+            .collect();
+
+        let mut paths = HashMap::new();
+        for leaf in self
+            .switches
+            .iter()
+            .filter(|switch| !switch.nodes.is_empty())
+        {
+            let mut path: Vec<Arc<str>> = vec![Arc::from(leaf.name.as_str())];
+            let mut current = leaf.name.as_str();
+            // bounded by the number of switches, in case topology.conf has a cycle
+            while let Some(&parent) = parents.get(current)
+                && path.len() <= self.switches.len()
+            {
+                path.push(Arc::from(parent));
+                current = parent;
+            }
+            path.reverse();
+            for node in &leaf.nodes {
+                paths.insert(node.clone(), path.clone());
+            }
+        }
+        paths
+    }
+}
+
+/// Fetches the switch topology from Slurm and returns it as a safe, owned `SlurmTopology`
+pub fn get_topology() -> Result<SlurmTopology, SlurmError> {
+    Ok(RawSlurmTopologyInfo::load()?.into_slurm_topology())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn switch(name: &str, level: u16, nodes: &[&str], switches: &[&str]) -> Switch {
+        Switch {
+            name: name.to_string(),
+            level,
+            link_speed: 1,
+            nodes: nodes.iter().map(|n| n.to_string()).collect(),
+            switches: switches.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_node_paths() {
+        let topology = SlurmTopology {
+            switches: vec![
+                switch("core", 2, &[], &["spine1"]),
+                switch("spine1", 1, &[], &["leaf1", "leaf2"]),
+                switch("leaf1", 0, &["n01", "n02"], &[]),
+                switch("leaf2", 0, &["n03"], &[]),
+                switch("lone", 0, &["n04"], &[]),
+            ],
+        };
+        let paths = topology.node_paths();
+        let path = |node: &str| -> Vec<&str> { paths[node].iter().map(|s| &**s).collect() };
+
+        assert_eq!(path("n01"), ["core", "spine1", "leaf1"]);
+        assert_eq!(path("n03"), ["core", "spine1", "leaf2"]);
+        assert_eq!(path("n04"), ["lone"]);
+        assert!(!paths.contains_key("n05"));
+    }
+}
@@ -1,6 +1,12 @@
+use crate::error::SlurmError;
+use crate::version::check_slurm_version;
 use chrono::{DateTime, Utc};
 use fi_slurm_sys;
+use std::env;
 use std::ffi::CStr;
+use std::io::{IsTerminal, Write};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::process::{Child, Command, Stdio};
 
 pub fn time_t_to_datetime(timestamp: i64) -> DateTime<Utc> {
     chrono::DateTime::from_timestamp(timestamp, 0).unwrap_or_default()
@@ -30,22 +36,32 @@ pub struct SlurmConfig {
 impl SlurmConfig {
     /// Loads the Slurm configuration and returns a guard object
     /// The configuration will be freed when the guard is dropped
-    pub fn load() -> Result<Self, String> {
+    ///
+    /// As every tool loads the configuration before anything else, this is also where a libslurm
+    /// of another release than the bindings is caught, before any of its structs are read
+    pub fn load() -> Result<Self, SlurmError> {
+        check_slurm_version()?;
         let mut conf_ptr: *mut fi_slurm_sys::slurm_conf_t = std::ptr::null_mut();
         unsafe {
             if fi_slurm_sys::slurm_load_ctl_conf(0, &mut conf_ptr) != 0 {
-                return Err(
-                    "Failed to load slurm.conf. Check logs or SLURM_CONF env variable.".to_string(),
-                );
+                return Err(SlurmError::config_from_errno());
             }
         }
         if conf_ptr.is_null() {
             // This is a defensive check; slurm_load_ctl_conf should not return 0
             // and a null pointer, but we check just in case
-            return Err("slurm_load_ctl_conf succeeded but returned a null pointer.".to_string());
+            return Err(SlurmError::NullResponse {
+                action: "load slurm.conf",
+            });
         }
         Ok(SlurmConfig { _ptr: conf_ptr })
     }
+
+    /// The ClusterName of slurm.conf, the name slurmdbd knows this cluster by
+    pub fn cluster_name(&self) -> Option<String> {
+        let name = unsafe { c_str_to_string((*self._ptr).cluster_name) };
+        (!name.is_empty()).then_some(name)
+    }
 }
 
 impl Drop for SlurmConfig {
@@ -95,9 +111,91 @@ pub fn count_blocks(max_blocks: usize, percentage: f64) -> (usize, usize, Option
     (full_blocks, empty_blocks, partial_block)
 }
 
+/// The pager used when $PAGER is not set
+const DEFAULT_PAGER: &str = "less";
+
+/// Picks the pager command from the value of $PAGER, the way git does: unset means the
+/// default pager, while an empty value or `cat` turns paging off
+fn pager_command(pager_env: Option<String>) -> Option<String> {
+    match pager_env {
+        None => Some(DEFAULT_PAGER.to_string()),
+        Some(pager) if pager.trim().is_empty() || pager.trim() == "cat" => None,
+        Some(pager) => Some(pager),
+    }
+}
+
+/// Sends everything printed to stdout through a pager, like git does for long output
+///
+/// stdout is redirected into the pager for as long as this guard lives; dropping it flushes
+/// the output, restores stdout and waits for the user to quit the pager
+pub struct Pager {
+    child: Child,
+    /// The original stdout, restored on drop
+    saved_stdout: OwnedFd,
+}
+
+impl Pager {
+    /// Starts the pager named by $PAGER (`less` by default) if stdout is a terminal
+    ///
+    /// Returns None, leaving stdout alone, when `no_pager` is set (the `--no-pager` flag),
+    /// when stdout is redirected, or when the pager can't be started. Unless $LESS is set,
+    /// `less` is run with -FRX, so that it keeps colors and exits straight away when the
+    /// output fits on one screen
+    pub fn start(no_pager: bool) -> Option<Pager> {
+        if no_pager || !std::io::stdout().is_terminal() {
+            return None;
+        }
+        let pager = pager_command(env::var("PAGER").ok())?;
+
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(&pager).stdin(Stdio::piped());
+        if env::var_os("LESS").is_none() {
+            command.env("LESS", "FRX");
+        }
+        let mut child = command.spawn().ok()?;
+        let pipe = child.stdin.take()?;
+
+        // colored turns itself off when stdout isn't a terminal, so settle its choice while
+        // stdout still is one
+        colored::control::set_override(colored::control::SHOULD_COLORIZE.should_colorize());
+
+        let _ = std::io::stdout().flush();
+        let stdout_fd = std::io::stdout().as_raw_fd();
+        // keep a copy of the terminal's stdout before pointing stdout at the pipe; the pipe's
+        // own descriptor is closed when `pipe` is dropped, leaving stdout its only writer
+        unsafe {
+            let saved = libc::dup(stdout_fd);
+            if saved < 0 || libc::dup2(pipe.as_raw_fd(), stdout_fd) < 0 {
+                if saved >= 0 {
+                    libc::close(saved);
+                }
+                let _ = child.kill();
+                let _ = child.wait();
+                return None;
+            }
+            Some(Pager {
+                child,
+                saved_stdout: OwnedFd::from_raw_fd(saved),
+            })
+        }
+    }
+}
+
+impl Drop for Pager {
+    fn drop(&mut self) {
+        let _ = std::io::stdout().flush();
+        // restoring stdout closes the last writer of the pipe, so the pager sees the end of
+        // the output
+        unsafe {
+            libc::dup2(self.saved_stdout.as_raw_fd(), std::io::stdout().as_raw_fd());
+        }
+        let _ = self.child.wait();
+    }
+}
+
 #[cfg(test)]
 pub mod tests {
-    use super::count_blocks;
+    use super::{count_blocks, pager_command};
 
     #[test]
     fn t1() {
@@ -113,4 +211,15 @@ pub mod tests {
         assert_eq!(result.1, 1);
         assert_eq!(result.2, Some("▍".to_string()));
     }
+
+    #[test]
+    fn test_pager_command_follows_git() {
+        assert_eq!(pager_command(None), Some("less".to_string()));
+        assert_eq!(
+            pager_command(Some("more -d".into())),
+            Some("more -d".to_string())
+        );
+        assert_eq!(pager_command(Some("cat".into())), None);
+        assert_eq!(pager_command(Some("".into())), None);
+    }
 }
@@ -0,0 +1,145 @@
+use crate::error::SlurmError;
+use std::fmt;
+
+/// A Slurm release, such as 24.11.5
+///
+/// The bindings are generated from the headers of one release, and the layouts of the structs
+/// libslurm hands back, such as `node_info_t` and `slurm_conf_t`, change between major
+/// releases but not between the micro releases of one, so a binary only works with a libslurm
+/// of the same major release as the headers it was built against
+///
+/// There are no versioned accessors reading the structs of several releases: a cluster running
+/// several releases needs one build per release, each installed alongside its libslurm
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SlurmVersion {
+    pub major: u8,
+    pub minor: u8,
+    pub micro: u8,
+}
+
+impl SlurmVersion {
+    /// Unpacks a version in Slurm's `SLURM_VERSION_NUM` encoding, `major << 16 | minor << 8 | micro`
+    pub fn from_packed(packed: u32) -> Self {
+        SlurmVersion {
+            major: (packed >> 16) as u8,
+            minor: (packed >> 8) as u8,
+            micro: packed as u8,
+        }
+    }
+
+    /// The release whose headers the bindings were generated from
+    pub fn compiled() -> Self {
+        SlurmVersion::from_packed(fi_slurm_sys::SLURM_VERSION_NUMBER)
+    }
+}
+
+impl fmt::Display for SlurmVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{:02}.{}", self.major, self.minor, self.micro)
+    }
+}
+
+/// The version of libslurm's API, `SLURM_API_VERSION`, which is what `slurm_api_version()`
+/// returns at runtime rather than the release
+///
+/// It is packed as a release is, `current << 16 | revision << 8 | age`, but counts the changes
+/// to the API, so 24.11 is API 42, and only compares with another API version. `current` is
+/// bumped with every major release, and is the number in libslurm's soname, libslurm.so.42
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ApiVersion {
+    pub current: u32,
+    pub revision: u8,
+    pub age: u8,
+}
+
+impl ApiVersion {
+    /// Unpacks a version in the `SLURM_API_VERSION` encoding
+    pub fn from_packed(packed: u32) -> Self {
+        ApiVersion {
+            current: packed >> 16,
+            revision: (packed >> 8) as u8,
+            age: packed as u8,
+        }
+    }
+
+    /// The API of the headers the bindings were generated from
+    pub fn compiled() -> Self {
+        ApiVersion::from_packed(fi_slurm_sys::SLURM_API_VERSION)
+    }
+
+    /// The API of the libslurm loaded at runtime
+    pub fn running() -> Self {
+        ApiVersion::from_packed(unsafe { fi_slurm_sys::slurm_api_version() } as u32)
+    }
+
+    /// Whether the structs of this API are laid out as in `other`'s, which holds as long as
+    /// `current` is the same, as within one major release
+    pub fn is_compatible_with(&self, other: &ApiVersion) -> bool {
+        self.current == other.current
+    }
+}
+
+impl fmt::Display for ApiVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.current)
+    }
+}
+
+/// Checks that the libslurm loaded at runtime has the same API as the headers the bindings
+/// were built against, before any of its structs are read
+///
+/// Reading the structs of another release would return garbage or crash, so a mismatch, as on
+/// a cluster upgraded since the tools were built, is an error telling which release the build
+/// is for rather than something to work around
+pub fn check_slurm_version() -> Result<(), SlurmError> {
+    let (compiled, running) = (ApiVersion::compiled(), ApiVersion::running());
+    if running.is_compatible_with(&compiled) {
+        Ok(())
+    } else {
+        Err(SlurmError::IncompatibleVersion {
+            release: SlurmVersion::compiled().to_string(),
+            compiled: compiled.to_string(),
+            running: running.to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slurm_version() {
+        let version = SlurmVersion::from_packed(0x180b05);
+        assert_eq!(
+            version,
+            SlurmVersion {
+                major: 24,
+                minor: 11,
+                micro: 5
+            }
+        );
+        assert_eq!(version.to_string(), "24.11.5");
+        assert_eq!(SlurmVersion::from_packed(0x170207).to_string(), "23.02.7");
+    }
+
+    #[test]
+    fn test_api_version() {
+        // slurm_api_version() of 24.11, as libslurm.so.42 returns it
+        let api = ApiVersion::from_packed(0x2a0000);
+        assert_eq!(
+            api,
+            ApiVersion {
+                current: 42,
+                revision: 0,
+                age: 0
+            }
+        );
+        assert_eq!(api.to_string(), "42");
+
+        assert!(api.is_compatible_with(&ApiVersion::from_packed(0x2a0100)));
+        // 23.02 is API 39, and a release number is never an API version
+        assert!(!api.is_compatible_with(&ApiVersion::from_packed(0x270000)));
+        assert!(!api.is_compatible_with(&ApiVersion::from_packed(0x180b05)));
+    }
+}